@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::thread;
 
 use criterion::{criterion_group, criterion_main, Bencher, Criterion};
 use halo2_proofs::{
@@ -6,6 +7,7 @@ use halo2_proofs::{
     poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
 };
 use ndarray::Array2;
+use rayon::ThreadPoolBuilder;
 use zero_g::{checked_in_test_data::*, load_grayscale_image, load_wnn, Wnn};
 
 fn setup(model_info: (u32, &str)) -> (Wnn, Array2<u8>, ParamsKZG<Bn256>) {
@@ -24,22 +26,34 @@ fn setup(model_info: (u32, &str)) -> (Wnn, Array2<u8>, ParamsKZG<Bn256>) {
 fn bench_key_generation(b: &mut Bencher, model_info: (u32, &str)) {
     let (wnn, _img, kzg_params) = setup(model_info);
 
-    b.iter(|| wnn.generate_proving_key(&kzg_params));
+    b.iter(|| wnn.generate_proving_key(&kzg_params).unwrap());
+}
+
+/// Key generation on a thread pool of the given size, to measure the speedup of parallel key
+/// generation over a single thread.
+fn bench_key_generation_with_threads(b: &mut Bencher, model_info: (u32, &str), num_threads: usize) {
+    let (wnn, _img, kzg_params) = setup(model_info);
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .unwrap();
+
+    b.iter(|| pool.install(|| wnn.generate_proving_key(&kzg_params).unwrap()));
 }
 
 fn bench_proof_generation(b: &mut Bencher, model_info: (u32, &str)) {
     let (wnn, img, kzg_params) = setup(model_info);
 
-    let pk = wnn.generate_proving_key(&kzg_params);
+    let pk = wnn.generate_proving_key(&kzg_params).unwrap();
 
-    b.iter(|| wnn.proof(&pk, &kzg_params, &img));
+    b.iter(|| wnn.proof(&pk, &kzg_params, &img).unwrap());
 }
 
 fn bench_verification(b: &mut Bencher, model_info: (u32, &str)) {
     let (wnn, img, kzg_params) = setup(model_info);
 
-    let pk = wnn.generate_proving_key(&kzg_params);
-    let (proof, outputs) = wnn.proof(&pk, &kzg_params, &img);
+    let pk = wnn.generate_proving_key(&kzg_params).unwrap();
+    let (proof, outputs) = wnn.proof(&pk, &kzg_params, &img).unwrap();
 
     b.iter(|| Wnn::verify_proof(&proof, &kzg_params, pk.get_vk(), &outputs));
 }
@@ -58,6 +72,13 @@ fn criterion_benchmark(c: &mut Criterion) {
         bench_verification(b, MNIST_TINY)
     });
 
+    let num_cores = thread::available_parallelism().map_or(1, |n| n.get());
+    group.bench_function("key_generation_mnist_small_single_thread", |b| {
+        bench_key_generation_with_threads(b, MNIST_SMALL, 1)
+    });
+    group.bench_function("key_generation_mnist_small_all_cores", |b| {
+        bench_key_generation_with_threads(b, MNIST_SMALL, num_cores)
+    });
     group.bench_function("proof_generation_mnist_small", |b| {
         bench_proof_generation(b, MNIST_SMALL)
     });
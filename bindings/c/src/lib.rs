@@ -0,0 +1,4 @@
+//! The C API of `zero_g` (see the `capi` module of `zero_g` and include/zero_g.h), built as a
+//! shared and a static library.
+
+pub use zero_g::capi::*;
@@ -0,0 +1,114 @@
+//! Node.js bindings for `zero_g` (via napi-rs), see the readme.
+//!
+//! Proofs are exchanged as proof files (`.zgp`), i.e. the format written by `zero_g proof`.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use napi::bindgen_prelude::{AsyncTask, Buffer};
+use napi::{Env, Error, Result, Task};
+use napi_derive::napi;
+use zero_g::eth::{encode_verifier_calldata, encode_verify_call};
+use zero_g::load_image_from_bytes;
+use zero_g::proof_file::ProofFile;
+use zero_g::verifier_bundle::{VerificationError, VerifierBundle};
+
+fn to_napi_error(e: impl ToString) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+fn read_proof_file(proof_file: &[u8]) -> Result<ProofFile> {
+    ProofFile::from_bytes(proof_file).map_err(to_napi_error)
+}
+
+/// Proves inference of a fixed model.
+#[napi]
+pub struct Prover {
+    inner: Arc<zero_g::prover::Prover>,
+}
+
+#[napi]
+impl Prover {
+    /// Loads the model (in .zgm format) and the artifact directory written by `zero_g setup`.
+    #[napi(factory)]
+    pub fn open(model_path: String, artifact_dir: String) -> Result<Self> {
+        let prover = zero_g::prover::Prover::open(Path::new(&model_path), Path::new(&artifact_dir))
+            .map_err(to_napi_error)?;
+        Ok(Self {
+            inner: Arc::new(prover),
+        })
+    }
+
+    /// Proves inference of the image (PNG, JPEG, ...) on a worker thread and resolves to the
+    /// proof file.
+    #[napi(ts_return_type = "Promise<Buffer>")]
+    pub fn prove(&self, image: Buffer) -> AsyncTask<ProveTask> {
+        AsyncTask::new(ProveTask {
+            prover: self.inner.clone(),
+            image: image.to_vec(),
+        })
+    }
+
+    /// The verifier bundle for the proofs of this prover, see `verify`.
+    #[napi]
+    pub fn verifier_bundle(&self) -> Result<Buffer> {
+        let mut bundle = vec![];
+        self.inner
+            .verifier_bundle()
+            .write_to(&mut bundle)
+            .map_err(to_napi_error)?;
+        Ok(bundle.into())
+    }
+
+    /// The commitment to the model parameters, as a hex string.
+    #[napi(getter)]
+    pub fn model_commitment(&self) -> String {
+        format!("0x{}", hex::encode(self.inner.wnn().commitment()))
+    }
+}
+
+pub struct ProveTask {
+    prover: Arc<zero_g::prover::Prover>,
+    image: Vec<u8>,
+}
+
+impl Task for ProveTask {
+    type Output = Vec<u8>;
+    type JsValue = Buffer;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let image = load_image_from_bytes(&self.image).map_err(to_napi_error)?;
+        let proof_file = self.prover.prove(&image).map_err(to_napi_error)?;
+        Ok(proof_file.to_bytes())
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output.into())
+    }
+}
+
+/// Verifies a proof file against a verifier bundle. Returns `false` if the proof is invalid
+/// and throws if it was generated for a different circuit or verification key.
+#[napi]
+pub fn verify(bundle: Buffer, proof_file: Buffer) -> Result<bool> {
+    let bundle = VerifierBundle::read_from(&mut &bundle[..]).map_err(to_napi_error)?;
+    match bundle.verify(&read_proof_file(&proof_file)?) {
+        Ok(()) => Ok(true),
+        Err(VerificationError::InvalidProof(_)) => Ok(false),
+        Err(e) => Err(to_napi_error(e)),
+    }
+}
+
+/// The calldata to call the generated verifier contract directly with the proof.
+#[napi(js_name = "encodeVerifierCalldata")]
+pub fn encode_verifier_calldata_for(proof_file: Buffer) -> Result<Buffer> {
+    let proof_file = read_proof_file(&proof_file)?;
+    Ok(encode_verifier_calldata(&proof_file.proof, &proof_file.public_inputs).into())
+}
+
+/// The calldata for `ZeroGVerifier.verify(uint256[],bytes)` (see `zero_g export-evm`).
+#[napi(js_name = "encodeVerifyCall")]
+pub fn encode_verify_call_for(proof_file: Buffer) -> Result<Buffer> {
+    let proof_file = read_proof_file(&proof_file)?;
+    Ok(encode_verify_call(&proof_file.proof, &proof_file.public_inputs).into())
+}
@@ -0,0 +1,6 @@
+//! WebAssembly bindings for `zero_g`, see the `wasm` module of `zero_g`.
+//!
+//! This crate only builds the bindings as a `cdylib`, so that users of the `zero_g` library
+//! don't have to. Build the package with `wasm-pack build bindings/wasm`.
+
+pub use zero_g::wasm::*;
@@ -0,0 +1,5 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/zero_g.proto")
+        .expect("Unable to compile the protobuf definitions");
+}
@@ -0,0 +1,63 @@
+/*
+ * C API of zero_g, see the `capi` module for details.
+ *
+ * Build the library with `cargo build --release --manifest-path bindings/c/Cargo.toml`, which
+ * produces a shared and a static library (libzero_g_c) in bindings/c/target/release.
+ */
+
+#ifndef ZERO_G_H
+#define ZERO_G_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum {
+    ZG_OK = 0,
+    ZG_INVALID_PROOF = 1,
+    ZG_INVALID_ARGUMENT = 2,
+    ZG_ERROR = 3,
+} ZgStatus;
+
+/* A byte buffer allocated by the library, free with zg_buffer_free. */
+typedef struct {
+    uint8_t *data;
+    size_t len;
+} ZgBuffer;
+
+/* An opaque prover handle, free with zg_prover_free. */
+typedef struct ZgProver ZgProver;
+
+/* The message of the last error on this thread, or NULL. */
+const char *zg_last_error(void);
+
+ZgStatus zg_prover_open(const char *model_path, const char *artifact_dir, ZgProver **out);
+
+/* If pk is NULL, the proving key is generated. */
+ZgStatus zg_prover_from_bytes(const uint8_t *model, size_t model_len, const uint8_t *srs,
+                              size_t srs_len, const uint8_t *pk, size_t pk_len, ZgProver **out);
+
+void zg_prover_free(ZgProver *prover);
+
+/* Writes the proof file (.zgp) to out. */
+ZgStatus zg_prove(const ZgProver *prover, const uint8_t *image, size_t image_len, ZgBuffer *out);
+
+/* Like zg_prove, for the feature vector of a tabular model. */
+ZgStatus zg_prove_features(const ZgProver *prover, const uint16_t *features, size_t num_features,
+                           ZgBuffer *out);
+
+ZgStatus zg_verifier_bundle(const ZgProver *prover, ZgBuffer *out);
+
+ZgStatus zg_verify(const uint8_t *bundle, size_t bundle_len, const uint8_t *proof_file,
+                   size_t proof_file_len);
+
+void zg_buffer_free(ZgBuffer buffer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ZERO_G_H */
@@ -0,0 +1,499 @@
+//! Storage backends for large artifacts (SRS, keys and models).
+//!
+//! Artifacts are addressed by keys like `keys/mnist-small.pk`. Besides the local file system,
+//! artifacts can be pulled from an HTTP server or an S3-compatible object storage, if the
+//! `download` feature is enabled. See [`open_store`] for how to select a backend, and
+//! [`crate::io::read_srs_from_store`] and friends for loading artifacts from a store.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// A key-value store for artifacts.
+pub trait ArtifactStore: Send + Sync {
+    /// Reads the artifact with the given key.
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if it does not exist.
+    fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+
+    /// Writes the artifact with the given key, replacing any existing artifact.
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()>;
+
+    /// Opens the artifact with the given key for reading, without loading it into memory
+    /// if the backend supports it. By default, the artifact is read with [`Self::get`].
+    fn reader(&self, key: &str) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::Cursor::new(self.get(key)?)))
+    }
+
+    /// Copies the artifact with the given key to a file, streaming it via [`Self::reader`].
+    fn get_to_path(&self, key: &str, path: &Path) -> io::Result<()> {
+        let mut reader = self.reader(key)?;
+        io::copy(&mut reader, &mut File::create(path)?)?;
+        Ok(())
+    }
+}
+
+/// Stores artifacts as files in a directory.
+///
+/// Keys must be relative paths without `..` (or other special) components, so that all
+/// artifacts are stored below the root directory.
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The path of the artifact with the given key. Returns an error of kind
+    /// [`io::ErrorKind::InvalidInput`] if the key would escape the root directory.
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        let key_path = Path::new(key);
+        let is_valid = key_path.components().next().is_some()
+            && key_path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !is_valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid artifact key: {key}"),
+            ));
+        }
+        Ok(self.root.join(key_path))
+    }
+}
+
+impl ArtifactStore for LocalStore {
+    fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(key)?)
+    }
+
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, bytes)
+    }
+
+    fn reader(&self, key: &str) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(self.path(key)?)?))
+    }
+
+    fn get_to_path(&self, key: &str, path: &Path) -> io::Result<()> {
+        fs::copy(self.path(key)?, path).map(|_| ())
+    }
+}
+
+/// Opens a store given its location:
+/// - `http://...` or `https://...`: An [`HttpStore`], keys are appended to the URL.
+/// - `s3://<bucket>/<prefix>`: An [`S3Store`], configured by the environment variables
+///   `AWS_ENDPOINT_URL` (defaults to AWS), `AWS_REGION` (defaults to `us-east-1`),
+///   `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`.
+/// - Anything else: A [`LocalStore`] rooted at the given directory.
+pub fn open_store(location: &str) -> io::Result<Box<dyn ArtifactStore>> {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        return open_http_store(location);
+    }
+    if let Some(path) = location.strip_prefix("s3://") {
+        return open_s3_store(path);
+    }
+    Ok(Box::new(LocalStore::new(location)))
+}
+
+#[cfg(feature = "download")]
+fn open_http_store(location: &str) -> io::Result<Box<dyn ArtifactStore>> {
+    Ok(Box::new(remote::HttpStore::new(location)))
+}
+
+#[cfg(feature = "download")]
+fn open_s3_store(path: &str) -> io::Result<Box<dyn ArtifactStore>> {
+    Ok(Box::new(remote::S3Store::from_env(path)?))
+}
+
+#[cfg(not(feature = "download"))]
+fn open_http_store(location: &str) -> io::Result<Box<dyn ArtifactStore>> {
+    Err(remote_disabled(location))
+}
+
+#[cfg(not(feature = "download"))]
+fn open_s3_store(path: &str) -> io::Result<Box<dyn ArtifactStore>> {
+    Err(remote_disabled(&format!("s3://{path}")))
+}
+
+#[cfg(not(feature = "download"))]
+fn remote_disabled(location: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Cannot open {location}: remote stores require the download feature"),
+    )
+}
+
+#[cfg(feature = "download")]
+pub use self::remote::{HttpStore, S3Store};
+
+#[cfg(feature = "download")]
+mod remote {
+    use std::env;
+    use std::io::{self, Read};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use hmac::{Hmac, Mac};
+    use sha2::{Digest, Sha256};
+
+    use super::ArtifactStore;
+    use crate::download::to_io_error;
+
+    fn response_reader(
+        url: &str,
+        response: Result<ureq::Response, ureq::Error>,
+    ) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(
+            response.map_err(|e| to_io_error(url, e))?.into_reader(),
+        ))
+    }
+
+    fn read_response(
+        url: &str,
+        response: Result<ureq::Response, ureq::Error>,
+    ) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        response_reader(url, response)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Reads artifacts via `GET <base URL>/<key>` and writes them via `PUT`.
+    pub struct HttpStore {
+        base_url: String,
+    }
+
+    impl HttpStore {
+        pub fn new(base_url: &str) -> Self {
+            Self {
+                base_url: base_url.trim_end_matches('/').to_string(),
+            }
+        }
+
+        fn url(&self, key: &str) -> String {
+            format!("{}/{key}", self.base_url)
+        }
+    }
+
+    impl ArtifactStore for HttpStore {
+        fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+            let url = self.url(key);
+            read_response(&url, ureq::get(&url).call())
+        }
+
+        fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+            let url = self.url(key);
+            read_response(&url, ureq::put(&url).send_bytes(bytes)).map(|_| ())
+        }
+
+        fn reader(&self, key: &str) -> io::Result<Box<dyn Read + Send>> {
+            let url = self.url(key);
+            response_reader(&url, ureq::get(&url).call())
+        }
+    }
+
+    /// Credentials for an [`S3Store`].
+    #[derive(Clone)]
+    pub struct S3Credentials {
+        pub access_key_id: String,
+        pub secret_access_key: String,
+    }
+
+    /// Stores artifacts in a bucket of an S3-compatible object storage (e.g. AWS S3, MinIO or
+    /// Cloudflare R2), using path-style URLs and AWS Signature Version 4.
+    pub struct S3Store {
+        endpoint: String,
+        region: String,
+        bucket: String,
+        prefix: String,
+        credentials: Option<S3Credentials>,
+    }
+
+    impl S3Store {
+        /// Creates a store for the given bucket. Keys are prefixed with `prefix`.
+        /// Requests are unsigned if no credentials are given (e.g. for public buckets).
+        pub fn new(
+            endpoint: &str,
+            region: &str,
+            bucket: &str,
+            prefix: &str,
+            credentials: Option<S3Credentials>,
+        ) -> Self {
+            Self {
+                endpoint: endpoint.trim_end_matches('/').to_string(),
+                region: region.to_string(),
+                bucket: bucket.to_string(),
+                prefix: prefix.trim_matches('/').to_string(),
+                credentials,
+            }
+        }
+
+        /// Creates a store for `<bucket>/<prefix>`, configured by environment variables
+        /// (see [`super::open_store`]).
+        pub fn from_env(path: &str) -> io::Result<Self> {
+            let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+            let region = env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+            let endpoint = env::var("AWS_ENDPOINT_URL")
+                .unwrap_or_else(|_| format!("https://s3.{region}.amazonaws.com"));
+            let credentials = match (
+                env::var("AWS_ACCESS_KEY_ID"),
+                env::var("AWS_SECRET_ACCESS_KEY"),
+            ) {
+                (Ok(access_key_id), Ok(secret_access_key)) => Some(S3Credentials {
+                    access_key_id,
+                    secret_access_key,
+                }),
+                _ => None,
+            };
+            Ok(Self::new(&endpoint, &region, bucket, prefix, credentials))
+        }
+
+        fn path(&self, key: &str) -> String {
+            let key = if self.prefix.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}/{key}", self.prefix)
+            };
+            format!("/{}/{}", uri_encode(&self.bucket), uri_encode(&key))
+        }
+
+        fn request(&self, method: &str, key: &str, payload: &[u8]) -> (String, ureq::Request) {
+            let path = self.path(key);
+            let url = format!("{}{path}", self.endpoint);
+            let request = ureq::request(method, &url);
+            let request = match &self.credentials {
+                Some(credentials) => {
+                    let host = self
+                        .endpoint
+                        .split_once("://")
+                        .map_or(self.endpoint.as_str(), |(_, host)| host);
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .expect("System time is before 1970")
+                        .as_secs();
+                    sign(
+                        request,
+                        credentials,
+                        &self.region,
+                        method,
+                        host,
+                        &path,
+                        payload,
+                        now,
+                    )
+                }
+                None => request,
+            };
+            (url, request)
+        }
+    }
+
+    impl ArtifactStore for S3Store {
+        fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+            let (url, request) = self.request("GET", key, &[]);
+            read_response(&url, request.call())
+        }
+
+        fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+            let (url, request) = self.request("PUT", key, bytes);
+            read_response(&url, request.send_bytes(bytes)).map(|_| ())
+        }
+
+        fn reader(&self, key: &str) -> io::Result<Box<dyn Read + Send>> {
+            let (url, request) = self.request("GET", key, &[]);
+            response_reader(&url, request.call())
+        }
+    }
+
+    /// Adds the headers of
+    /// [AWS Signature Version 4](https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html).
+    #[allow(clippy::too_many_arguments)]
+    fn sign(
+        request: ureq::Request,
+        credentials: &S3Credentials,
+        region: &str,
+        method: &str,
+        host: &str,
+        path: &str,
+        payload: &[u8],
+        unix_time: u64,
+    ) -> ureq::Request {
+        let timestamp = format_timestamp(unix_time);
+        let date = &timestamp[..8];
+        let payload_hash = hex::encode(Sha256::digest(payload));
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{method}\n{path}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{timestamp}\n\n{signed_headers}\n{payload_hash}"
+        );
+        let scope = format!("{date}/{region}/s3/aws4_request");
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request))
+        );
+        let key = signing_key(&credentials.secret_access_key, date, region, "s3");
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        request
+            .set("x-amz-content-sha256", &payload_hash)
+            .set("x-amz-date", &timestamp)
+            .set(
+                "Authorization",
+                &format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                    credentials.access_key_id
+                ),
+            )
+    }
+
+    fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+        mac.update(message);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+        let key = hmac_sha256(format!("AWS4{secret}").as_bytes(), date.as_bytes());
+        let key = hmac_sha256(&key, region.as_bytes());
+        let key = hmac_sha256(&key, service.as_bytes());
+        hmac_sha256(&key, b"aws4_request")
+    }
+
+    /// Percent-encodes everything except unreserved characters and `/`.
+    fn uri_encode(s: &str) -> String {
+        s.bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{b:02X}"),
+            })
+            .collect()
+    }
+
+    /// Formats a UNIX timestamp as `YYYYMMDD'T'HHMMSS'Z'`.
+    fn format_timestamp(unix_time: u64) -> String {
+        let (days, seconds) = (unix_time / 86400, unix_time % 86400);
+
+        // Convert days since 1970-01-01 to a civil date, see
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719468;
+        let era = z / 146097;
+        let day_of_era = z - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as u64;
+
+        format!(
+            "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{format_timestamp, signing_key, uri_encode};
+
+        #[test]
+        fn test_format_timestamp() {
+            assert_eq!(format_timestamp(0), "19700101T000000Z");
+            assert_eq!(format_timestamp(1369353600), "20130524T000000Z");
+            assert_eq!(format_timestamp(1709251199), "20240229T235959Z");
+        }
+
+        #[test]
+        fn test_signing_key() {
+            // Example from the AWS documentation
+            let key = signing_key(
+                "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+                "20120215",
+                "us-east-1",
+                "iam",
+            );
+            assert_eq!(
+                hex::encode(key),
+                "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+            );
+        }
+
+        #[test]
+        fn test_uri_encode() {
+            assert_eq!(uri_encode("keys/model 1.pk"), "keys/model%201.pk");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+    use std::{env, fs, process};
+
+    use super::{open_store, ArtifactStore, LocalStore};
+
+    #[test]
+    fn test_local_store() {
+        let dir = env::temp_dir().join(format!("zero_g_store_{}", process::id()));
+        let store = LocalStore::new(&dir);
+
+        store.put("keys/model.vk", b"vk").unwrap();
+        assert_eq!(store.get("keys/model.vk").unwrap(), b"vk");
+        assert_eq!(
+            open_store(dir.to_str().unwrap())
+                .unwrap()
+                .get("keys/model.vk")
+                .unwrap(),
+            b"vk"
+        );
+        assert_eq!(
+            store.get("missing").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+
+        let mut streamed = vec![];
+        store
+            .reader("keys/model.vk")
+            .unwrap()
+            .read_to_end(&mut streamed)
+            .unwrap();
+        assert_eq!(streamed, b"vk");
+        let copy = dir.join("copy.vk");
+        store.get_to_path("keys/model.vk", &copy).unwrap();
+        assert_eq!(fs::read(&copy).unwrap(), b"vk");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_local_store_rejects_escaping_keys() {
+        let dir = env::temp_dir().join(format!("zero_g_store_keys_{}", process::id()));
+        let store = LocalStore::new(dir.join("root"));
+
+        for key in [
+            "../outside",
+            "keys/../../outside",
+            "/tmp/outside",
+            "./keys",
+            "",
+        ] {
+            assert_eq!(
+                store.put(key, b"x").unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+            assert_eq!(
+                store.get(key).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+        }
+        assert!(!dir.exists());
+    }
+}
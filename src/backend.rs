@@ -0,0 +1,182 @@
+//! The proving system behind key generation, proving and verification, see
+//! [`ProvingBackend`].
+//!
+//! All entry points of the crate (e.g. [`crate::Wnn::proof`],
+//! [`crate::verification::verify_raw_proof`] and
+//! [`crate::verifier_bundle::VerifierBundle::verify_batch`]) go through [`DefaultBackend`],
+//! so that moving to a different halo2 version (e.g. the split `halo2_frontend` /
+//! `halo2_backend` crates) or a different commitment scheme only requires a new
+//! implementation of the trait and changing the alias, not every call site.
+//!
+//! The circuits themselves are written against the `halo2_proofs` frontend API, and proofs use
+//! the [`EvmTranscript`], so that they remain verifiable on-chain.
+
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{self, Circuit, Error, ProvingKey, VerifyingKey},
+    poly::{
+        commitment::ParamsProver,
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG},
+            multiopen::{ProverGWC, VerifierGWC},
+            strategy::{AccumulatorStrategy, SingleStrategy},
+        },
+        VerificationStrategy,
+    },
+    transcript::{TranscriptReadBuffer, TranscriptWrite},
+};
+use rand_core::OsRng;
+use snark_verifier::system::halo2::transcript::evm::{ChallengeEvm, EvmTranscript};
+
+/// The backend used by all entry points of the crate.
+pub type DefaultBackend = KzgGwc;
+
+/// A proving system for circuits over the BN254 scalar field, with proofs written to an
+/// [`EvmTranscript`]. Instances are passed as one vector per instance column.
+pub trait ProvingBackend {
+    /// The public parameters (e.g. the SRS).
+    type Params;
+    type ProvingKey;
+    type VerifyingKey;
+
+    fn keygen_vk<C: Circuit<Fr>>(
+        params: &Self::Params,
+        circuit: &C,
+    ) -> Result<Self::VerifyingKey, Error>;
+
+    fn keygen_pk<C: Circuit<Fr>>(
+        params: &Self::Params,
+        vk: Self::VerifyingKey,
+        circuit: &C,
+    ) -> Result<Self::ProvingKey, Error>;
+
+    /// Proves that the circuit is satisfied with the given instances, writing the proof to the
+    /// transcript.
+    fn prove<C, T>(
+        params: &Self::Params,
+        pk: &Self::ProvingKey,
+        circuit: C,
+        instances: &[Vec<Fr>],
+        transcript: &mut T,
+    ) -> Result<(), Error>
+    where
+        C: Circuit<Fr>,
+        T: TranscriptWrite<G1Affine, ChallengeEvm<G1Affine>>;
+
+    fn verify(
+        params: &Self::Params,
+        vk: &Self::VerifyingKey,
+        instances: &[Vec<Fr>],
+        proof: &[u8],
+    ) -> Result<(), Error>;
+
+    /// Verifies several proofs, sharing the expensive final check if the backend supports it.
+    /// Returns the result of each proof, which might be `Ok` even for an invalid proof if the
+    /// error is only detected by the final check, and whether the final check succeeded.
+    fn verify_batch(
+        params: &Self::Params,
+        vk: &Self::VerifyingKey,
+        proofs: &[(&[u8], Vec<Vec<Fr>>)],
+    ) -> (Vec<Result<(), Error>>, bool) {
+        let results: Vec<_> = proofs
+            .iter()
+            .map(|(proof, instances)| Self::verify(params, vk, instances, proof))
+            .collect();
+        let all_valid = results.iter().all(Result::is_ok);
+        (results, all_valid)
+    }
+}
+
+/// KZG commitments with the multi-open argument of Gabizon, Williamson and Ciobotaru, as
+/// implemented in `halo2_proofs`. This is what the EVM verifier supports.
+#[derive(Debug, Clone, Copy)]
+pub struct KzgGwc;
+
+impl ProvingBackend for KzgGwc {
+    type Params = ParamsKZG<Bn256>;
+    type ProvingKey = ProvingKey<G1Affine>;
+    type VerifyingKey = VerifyingKey<G1Affine>;
+
+    fn keygen_vk<C: Circuit<Fr>>(
+        params: &Self::Params,
+        circuit: &C,
+    ) -> Result<Self::VerifyingKey, Error> {
+        plonk::keygen_vk(params, circuit)
+    }
+
+    fn keygen_pk<C: Circuit<Fr>>(
+        params: &Self::Params,
+        vk: Self::VerifyingKey,
+        circuit: &C,
+    ) -> Result<Self::ProvingKey, Error> {
+        plonk::keygen_pk(params, vk, circuit)
+    }
+
+    fn prove<C, T>(
+        params: &Self::Params,
+        pk: &Self::ProvingKey,
+        circuit: C,
+        instances: &[Vec<Fr>],
+        transcript: &mut T,
+    ) -> Result<(), Error>
+    where
+        C: Circuit<Fr>,
+        T: TranscriptWrite<G1Affine, ChallengeEvm<G1Affine>>,
+    {
+        let instances: Vec<&[Fr]> = instances.iter().map(Vec::as_slice).collect();
+        plonk::create_proof::<KZGCommitmentScheme<Bn256>, ProverGWC<_>, _, _, _, _>(
+            params,
+            pk,
+            &[circuit],
+            &[&instances],
+            OsRng,
+            transcript,
+        )
+    }
+
+    fn verify(
+        params: &Self::Params,
+        vk: &Self::VerifyingKey,
+        instances: &[Vec<Fr>],
+        proof: &[u8],
+    ) -> Result<(), Error> {
+        let instances: Vec<&[Fr]> = instances.iter().map(Vec::as_slice).collect();
+        let mut transcript = TranscriptReadBuffer::<_, G1Affine, _>::init(proof);
+        plonk::verify_proof::<_, VerifierGWC<_>, _, EvmTranscript<_, _, _, _>, _>(
+            params.verifier_params(),
+            vk,
+            SingleStrategy::new(params),
+            &[&instances],
+            &mut transcript,
+        )
+    }
+
+    /// Accumulates the final multi-scalar multiplications and pairings of all proofs, so that
+    /// they only have to be computed once.
+    fn verify_batch(
+        params: &Self::Params,
+        vk: &Self::VerifyingKey,
+        proofs: &[(&[u8], Vec<Vec<Fr>>)],
+    ) -> (Vec<Result<(), Error>>, bool) {
+        let mut strategy = AccumulatorStrategy::new(params);
+        let mut results = vec![];
+        for (proof, instances) in proofs {
+            let instances: Vec<&[Fr]> = instances.iter().map(Vec::as_slice).collect();
+            let mut transcript = TranscriptReadBuffer::<_, G1Affine, _>::init(*proof);
+            match plonk::verify_proof::<_, VerifierGWC<_>, _, EvmTranscript<_, _, _, _>, _>(
+                params.verifier_params(),
+                vk,
+                strategy.clone(),
+                &[&instances],
+                &mut transcript,
+            ) {
+                Ok(accumulated) => {
+                    strategy = accumulated;
+                    results.push(Ok(()));
+                }
+                Err(e) => results.push(Err(e)),
+            }
+        }
+        (results, strategy.finalize())
+    }
+}
@@ -0,0 +1,615 @@
+//! Proving many images, e.g. all images in a directory, with a manifest that allows
+//! resuming an interrupted run.
+//!
+//! For each image, a proof file (see [`crate::proof_file`]) is written to the output
+//! directory. The manifest (`manifest.json`) records the proof file and prediction of every
+//! proven image, as well as the error for images that could not be proven. It is rewritten
+//! after every image, so that a re-run skips all images that were already proven.
+//!
+//! The manifest also records the (unsalted) commitment of every proven image, so that the
+//! whole batch can be registered with a single Merkle root, see
+//! [`Manifest::image_commitment_root`]. The root is computed outside of the circuit and is not
+//! a public input of the proofs, so by itself it only shows which commitments the prover
+//! recorded. A leaf is bound to its proof if the model exposes the image commitment (see
+//! [`Wnn::with_image_commitment`]), which a verifier checks with
+//! [`Manifest::proves_image_commitment`].
+
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, G1Affine},
+    plonk::ProvingKey,
+    poly::kzg::commitment::ParamsKZG,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{instrument, warn};
+
+use crate::error::ZeroGError;
+use crate::gadgets::wnn::InstanceLayout;
+use crate::image_commitment::{
+    exposed_image_commitment, image_commitment, merkle_opening, merkle_root, MerkleOpening,
+};
+use crate::io::{invalid_data, load_grayscale_image};
+use crate::memory;
+use crate::proof_file::{write_proof_file, ProofFile};
+use crate::utils::keccak256;
+use crate::verifier_bundle::vk_fingerprint;
+use crate::wnn::Wnn;
+
+/// The name of the manifest in the output directory.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// The outcome of proving a single image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestEntry {
+    Proven {
+        /// The proof file, relative to the output directory.
+        proof_file: String,
+        /// The scores computed by [`Wnn::predict`].
+        scores: Vec<u64>,
+        /// The unsalted commitment of the image (see [`image_commitment`]), which is missing
+        /// in manifests written by earlier versions.
+        #[serde(default)]
+        image_commitment: Option<[u8; 32]>,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+/// Records the outcome for each image, identified by its path relative to the image directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// See [`Wnn::commitment`].
+    pub model_commitment: [u8; 32],
+    pub entries: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Reads a manifest written by [`BatchProver`].
+    pub fn read(path: &Path) -> Result<Self, ZeroGError> {
+        let read = || -> Result<Self, Box<dyn Error + Send + Sync>> {
+            Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+        };
+        read().map_err(|source| ZeroGError::Format {
+            path: path.to_path_buf(),
+            format: "manifest",
+            source,
+        })
+    }
+
+    /// Reads the manifest in `output_dir`, or starts an empty one if there is none yet.
+    ///
+    /// Returns an error if the existing manifest is for a model with a different commitment.
+    pub fn open(output_dir: &Path, model_commitment: [u8; 32]) -> Result<Self, ZeroGError> {
+        let path = output_dir.join(MANIFEST_FILE_NAME);
+        if !path.exists() {
+            return Ok(Self {
+                model_commitment,
+                entries: BTreeMap::new(),
+            });
+        }
+        let manifest = Self::read(&path)?;
+        if manifest.model_commitment != model_commitment {
+            return Err(ZeroGError::Format {
+                path,
+                format: "manifest",
+                source: Box::new(invalid_data(
+                    "Existing proofs were generated for a different model",
+                )),
+            });
+        }
+        Ok(manifest)
+    }
+
+    /// Writes the manifest to `output_dir`, via a temporary file so that it is never left
+    /// half-written.
+    pub fn write(&self, output_dir: &Path) -> Result<(), ZeroGError> {
+        let path = output_dir.join(MANIFEST_FILE_NAME);
+        let tmp_path = path.with_extension("json.tmp");
+        let write = || -> io::Result<()> {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            serde_json::to_writer_pretty(&mut writer, self)?;
+            writer.flush()?;
+            fs::rename(&tmp_path, &path)
+        };
+        write().map_err(|source| ZeroGError::Io {
+            action: "write",
+            path,
+            source,
+        })
+    }
+
+    /// Whether the image is recorded as proven and its proof file exists in `output_dir`.
+    pub fn is_proven(&self, output_dir: &Path, image_id: &str) -> bool {
+        match self.entries.get(image_id) {
+            Some(ManifestEntry::Proven { proof_file, .. }) => output_dir.join(proof_file).exists(),
+            _ => false,
+        }
+    }
+
+    /// The commitments of the proven images, ordered by image id. Images proven by earlier
+    /// versions, which didn't record the commitment, are left out.
+    pub fn image_commitments(&self) -> Vec<(&str, [u8; 32])> {
+        self.entries
+            .iter()
+            .filter_map(|(image, entry)| match entry {
+                ManifestEntry::Proven {
+                    image_commitment: Some(commitment),
+                    ..
+                } => Some((image.as_str(), *commitment)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The Merkle root over [`Manifest::image_commitments`], see [`merkle_root`].
+    ///
+    /// The root is not a public input of the proofs, see the module documentation.
+    pub fn image_commitment_root(&self) -> [u8; 32] {
+        let commitments: Vec<_> = self
+            .image_commitments()
+            .into_iter()
+            .map(|(_, c)| c)
+            .collect();
+        merkle_root(&commitments)
+    }
+
+    /// Opens the commitment of the image in [`Manifest::image_commitment_root`], or returns
+    /// `None` if there is no commitment for the image.
+    pub fn image_opening(&self, image_id: &str) -> Option<MerkleOpening> {
+        let image_commitments = self.image_commitments();
+        let index = image_commitments
+            .iter()
+            .position(|(image, _)| *image == image_id)?;
+        let commitments: Vec<_> = image_commitments.into_iter().map(|(_, c)| c).collect();
+        merkle_opening(&commitments, index)
+    }
+
+    /// Whether the proof (with the given instance layout) exposes the commitment recorded for
+    /// the image, i.e. whether the leaf of the image in [`Manifest::image_commitment_root`] is
+    /// bound to the proof. This requires a model with [`Wnn::with_image_commitment`].
+    ///
+    /// The proof itself is not verified.
+    pub fn proves_image_commitment(
+        &self,
+        image_id: &str,
+        layout: &InstanceLayout,
+        proof_file: &ProofFile,
+    ) -> bool {
+        let recorded = match self.entries.get(image_id) {
+            Some(ManifestEntry::Proven {
+                image_commitment: Some(commitment),
+                ..
+            }) => commitment,
+            _ => return false,
+        };
+        exposed_image_commitment(layout, &proof_file.public_inputs).as_ref() == Some(recorded)
+    }
+
+    /// The images that could not be proven, with the corresponding error.
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .filter_map(|(image, entry)| match entry {
+                ManifestEntry::Failed { error } => Some((image.as_str(), error.as_str())),
+                ManifestEntry::Proven { .. } => None,
+            })
+    }
+}
+
+/// Proves images one by one, keeping the manifest in the output directory up to date.
+pub struct BatchProver<'a> {
+    wnn: &'a Wnn,
+    pk: &'a ProvingKey<G1Affine>,
+    kzg_params: &'a ParamsKZG<Bn256>,
+    output_dir: PathBuf,
+    manifest: Manifest,
+}
+
+impl<'a> BatchProver<'a> {
+    /// Creates the output directory if needed and reads the manifest of a previous run.
+    ///
+    /// Returns an error if the previous run used a different model.
+    pub fn open(
+        wnn: &'a Wnn,
+        pk: &'a ProvingKey<G1Affine>,
+        kzg_params: &'a ParamsKZG<Bn256>,
+        output_dir: &Path,
+    ) -> Result<Self, ZeroGError> {
+        fs::create_dir_all(output_dir).map_err(|source| ZeroGError::Io {
+            action: "create",
+            path: output_dir.to_path_buf(),
+            source,
+        })?;
+
+        let manifest = Manifest::open(output_dir, wnn.commitment())?;
+        Ok(Self {
+            wnn,
+            pk,
+            kzg_params,
+            output_dir: output_dir.to_path_buf(),
+            manifest,
+        })
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Whether the image was proven in this or a previous run (and the proof file still exists).
+    pub fn is_proven(&self, image_id: &str) -> bool {
+        self.manifest.is_proven(&self.output_dir, image_id)
+    }
+
+    /// Proves the image at `path` and records the outcome under `image_id` in the manifest.
+    ///
+    /// Failing to load or prove the image is recorded in the manifest, only failing to
+    /// write the manifest is returned as an error.
+    #[instrument(skip(self, path))]
+    pub fn prove(&mut self, image_id: &str, path: &Path) -> Result<&ManifestEntry, ZeroGError> {
+        let proof_file = proof_file_name(image_id);
+        let result = prove_image(
+            self.wnn,
+            self.pk,
+            self.kzg_params,
+            path,
+            &self.output_dir.join(&proof_file),
+        );
+        self.record(image_id, proof_file, result)
+    }
+
+    /// Proves all images that are not proven yet, running up to `workers` proofs at the same
+    /// time, and calls `on_done` with the outcome of each image as soon as it is recorded.
+    ///
+    /// If a memory budget (in bytes) is given, no new proof is started while the memory usage
+    /// exceeds it (see [`memory::wait_for_memory`]), so that fewer proofs run concurrently
+    /// instead of running out of memory. Like [`BatchProver::prove`], only failing to write the
+    /// manifest is returned as an error.
+    pub fn prove_all(
+        &mut self,
+        images: &[(String, PathBuf)],
+        workers: usize,
+        memory_budget: Option<u64>,
+        mut on_done: impl FnMut(&str, &ManifestEntry),
+    ) -> Result<(), ZeroGError> {
+        let pending: Mutex<VecDeque<_>> = Mutex::new(
+            images
+                .iter()
+                .filter(|(image_id, _)| !self.is_proven(image_id))
+                .collect(),
+        );
+        let running = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let (wnn, pk, kzg_params) = (self.wnn, self.pk, self.kzg_params);
+        let output_dir = self.output_dir.clone();
+
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for _ in 0..workers.max(1) {
+                let sender = sender.clone();
+                let (pending, running, stop, output_dir) = (&pending, &running, &stop, &output_dir);
+                scope.spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        if let Some(budget) = memory_budget {
+                            memory::wait_for_memory(budget, running, stop);
+                        }
+                        let (image_id, path) = match pending.lock().unwrap().pop_front() {
+                            Some(image) => image,
+                            None => return,
+                        };
+                        let proof_file = proof_file_name(image_id);
+                        running.fetch_add(1, Ordering::SeqCst);
+                        let result =
+                            prove_image(wnn, pk, kzg_params, path, &output_dir.join(&proof_file));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        if sender.send((image_id, proof_file, result)).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            for (image_id, proof_file, result) in receiver {
+                match self.record(image_id, proof_file, result) {
+                    Ok(entry) => on_done(image_id, entry),
+                    Err(e) => {
+                        // Let the workers finish their current proof, but don't start new ones
+                        stop.store(true, Ordering::Relaxed);
+                        return Err(e);
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Records the outcome of proving an image in the manifest and writes the manifest.
+    fn record(
+        &mut self,
+        image_id: &str,
+        proof_file: String,
+        result: Result<(Vec<u64>, [u8; 32]), ZeroGError>,
+    ) -> Result<&ManifestEntry, ZeroGError> {
+        let entry = match result {
+            Ok((scores, image_commitment)) => ManifestEntry::Proven {
+                proof_file,
+                scores,
+                image_commitment: Some(image_commitment),
+            },
+            Err(e) => {
+                warn!("Proving {image_id} failed: {e}");
+                ManifestEntry::Failed {
+                    error: e.to_string(),
+                }
+            }
+        };
+        self.manifest.entries.insert(image_id.to_string(), entry);
+        self.manifest.write(&self.output_dir)?;
+        Ok(&self.manifest.entries[image_id])
+    }
+}
+
+/// Proves the image and writes the proof file. Returns the scores and the (unsalted)
+/// commitment of the image.
+fn prove_image(
+    wnn: &Wnn,
+    pk: &ProvingKey<G1Affine>,
+    kzg_params: &ParamsKZG<Bn256>,
+    image_path: &Path,
+    proof_path: &Path,
+) -> Result<(Vec<u64>, [u8; 32]), ZeroGError> {
+    let image = load_grayscale_image(image_path)?;
+    let (proof, outputs) = wnn.proof(pk, kzg_params, &image)?;
+
+    let circuit_params = wnn.get_circuit_params();
+    let proof_file = ProofFile::new(proof, outputs)
+        .with_vk_fingerprint(vk_fingerprint(pk.get_vk(), &circuit_params))
+        .with_circuit_params(circuit_params)
+        .with_model_commitment(wnn.commitment());
+    write_proof_file(&proof_file, proof_path).map_err(|source| ZeroGError::Io {
+        action: "write",
+        path: proof_path.to_path_buf(),
+        source,
+    })?;
+    Ok((wnn.predict(&image), image_commitment(&image, None)))
+}
+
+/// The name of the proof file for an image: The image id without path separators, followed by
+/// a hash of the id, so that ids like `a/b.png` and `a_b.png` don't share a proof file.
+pub(crate) fn proof_file_name(image_id: &str) -> String {
+    format!(
+        "{}-{}.zgp",
+        image_id.replace(['/', '\\'], "_"),
+        hex::encode(&keccak256(image_id.as_bytes())[..8])
+    )
+}
+
+/// Lists the images in `dir` (recursively), sorted by path, together with their id,
+/// i.e. their path relative to `dir`.
+pub fn image_files(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut paths = vec![];
+    crate::datasets::collect_images(dir, &mut paths)?;
+    paths.sort();
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let id = path
+                .strip_prefix(dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .into_owned();
+            (id, path)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use halo2_proofs::halo2curves::bn256::Fr as Fp;
+
+    use super::{proof_file_name, Manifest, ManifestEntry};
+    use crate::gadgets::wnn::{InstanceLayout, PublicValue};
+    use crate::image_commitment::{commitment_from_field, merkle_root};
+    use crate::proof_file::ProofFile;
+
+    #[test]
+    fn test_proof_file_name() {
+        let name = proof_file_name("a/b.png");
+        assert!(name.starts_with("a_b.png-") && name.ends_with(".zgp"));
+        assert_ne!(name, proof_file_name("a_b.png"));
+        assert_ne!(name, proof_file_name("a\\b.png"));
+        assert_eq!(name, proof_file_name("a/b.png"));
+    }
+
+    #[cfg(feature = "hdf5")]
+    #[test]
+    fn test_resume() {
+        use std::{env, fs, path::Path, process};
+
+        use halo2_proofs::{
+            halo2curves::bn256::Bn256,
+            poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
+        };
+
+        use super::BatchProver;
+        use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+        use crate::load_wnn;
+
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let kzg_params = ParamsKZG::<Bn256>::new(k);
+        let pk = wnn.generate_proving_key(&kzg_params).unwrap();
+
+        let dir = env::temp_dir().join(format!("zero_g_batch_resume_{}", process::id()));
+        let output_dir = dir.join("proofs");
+        // Ids that would map to the same proof file without the hash
+        let images: Vec<_> = ["a/b.png", "a_b.png"]
+            .into_iter()
+            .map(|id| {
+                let path = dir.join("images").join(id);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::copy(TEST_IMG_PATH, &path).unwrap();
+                (id.to_string(), path)
+            })
+            .collect();
+
+        let mut proven = vec![];
+        let mut prover = BatchProver::open(&wnn, &pk, &kzg_params, &output_dir).unwrap();
+        prover
+            .prove_all(&images[..1], 1, None, |id, _| proven.push(id.to_string()))
+            .unwrap();
+
+        // A second run only proves the remaining image
+        let mut prover = BatchProver::open(&wnn, &pk, &kzg_params, &output_dir).unwrap();
+        assert!(prover.is_proven("a/b.png"));
+        assert!(!prover.is_proven("a_b.png"));
+        prover
+            .prove_all(&images, 2, None, |id, _| proven.push(id.to_string()))
+            .unwrap();
+        let manifest = prover.manifest().clone();
+        let num_proof_files = fs::read_dir(&output_dir)
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension().unwrap() == "zgp")
+            .count();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(proven, vec!["a/b.png", "a_b.png"]);
+        assert_eq!(manifest.failures().count(), 0);
+        assert_eq!(manifest.image_commitments().len(), 2);
+        assert_eq!(num_proof_files, 2);
+    }
+
+    #[test]
+    fn test_manifest_serialization() {
+        let manifest = Manifest {
+            model_commitment: [3; 32],
+            entries: BTreeMap::from([
+                (
+                    "0000_7.png".to_string(),
+                    ManifestEntry::Proven {
+                        proof_file: "0000_7.png.zgp".to_string(),
+                        scores: vec![1, 2],
+                        image_commitment: Some([4; 32]),
+                    },
+                ),
+                (
+                    "0001_2.png".to_string(),
+                    ManifestEntry::Failed {
+                        error: "Unable to load image".to_string(),
+                    },
+                ),
+            ]),
+        };
+        let json = serde_json::to_string(&manifest).unwrap();
+        let loaded: Manifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, manifest);
+        assert_eq!(
+            loaded.failures().collect::<Vec<_>>(),
+            vec![("0001_2.png", "Unable to load image")]
+        );
+
+        // Manifests of earlier versions don't record image commitments
+        let image_commitment = serde_json::to_string(&[4; 32]).unwrap();
+        let json = json.replace(&format!(",\"image_commitment\":{image_commitment}"), "");
+        assert!(!json.contains("image_commitment"));
+        let loaded: Manifest = serde_json::from_str(&json).unwrap();
+        assert!(loaded.image_commitments().is_empty());
+    }
+
+    #[test]
+    fn test_image_commitment_root() {
+        let proven = |image_commitment| ManifestEntry::Proven {
+            proof_file: "proof.zgp".to_string(),
+            scores: vec![1, 2],
+            image_commitment,
+        };
+        let manifest = Manifest {
+            model_commitment: [3; 32],
+            entries: BTreeMap::from([
+                ("a.png".to_string(), proven(Some([1; 32]))),
+                ("b.png".to_string(), proven(None)),
+                (
+                    "c.png".to_string(),
+                    ManifestEntry::Failed {
+                        error: "Unable to load image".to_string(),
+                    },
+                ),
+                ("d.png".to_string(), proven(Some([2; 32]))),
+                ("e.png".to_string(), proven(Some([3; 32]))),
+            ]),
+        };
+        assert_eq!(
+            manifest.image_commitments(),
+            vec![("a.png", [1; 32]), ("d.png", [2; 32]), ("e.png", [3; 32])]
+        );
+        let root = manifest.image_commitment_root();
+        assert_eq!(root, merkle_root(&[[1; 32], [2; 32], [3; 32]]));
+
+        let opening = manifest.image_opening("d.png").unwrap();
+        assert_eq!(opening.index, 1);
+        assert!(opening.verify(&[2; 32], &root));
+        assert_eq!(manifest.image_opening("b.png"), None);
+        assert_eq!(manifest.image_opening("c.png"), None);
+    }
+
+    #[test]
+    fn test_proves_image_commitment() {
+        let commitment = commitment_from_field(&Fp::from(5));
+        let manifest = Manifest {
+            model_commitment: [3; 32],
+            entries: BTreeMap::from([
+                (
+                    "a.png".to_string(),
+                    ManifestEntry::Proven {
+                        proof_file: "proof.zgp".to_string(),
+                        scores: vec![1, 2],
+                        image_commitment: Some(commitment),
+                    },
+                ),
+                (
+                    "b.png".to_string(),
+                    ManifestEntry::Proven {
+                        proof_file: "proof.zgp".to_string(),
+                        scores: vec![1, 2],
+                        image_commitment: None,
+                    },
+                ),
+            ]),
+        };
+        let layout = InstanceLayout {
+            values: vec![
+                PublicValue::Score { class: 0 },
+                PublicValue::Score { class: 1 },
+                PublicValue::ImageCommitment,
+            ],
+            num_columns: 1,
+        };
+        let proof = |commitment| {
+            ProofFile::new(vec![], vec![Fp::from(1), Fp::from(2), Fp::from(commitment)])
+        };
+
+        assert!(manifest.proves_image_commitment("a.png", &layout, &proof(5)));
+        assert!(!manifest.proves_image_commitment("a.png", &layout, &proof(6)));
+        assert!(!manifest.proves_image_commitment("b.png", &layout, &proof(5)));
+        assert!(!manifest.proves_image_commitment("c.png", &layout, &proof(5)));
+
+        // Without the image commitment, the proof isn't bound to the leaf
+        let unbound = InstanceLayout {
+            values: layout.values[..2].to_vec(),
+            num_columns: 1,
+        };
+        assert!(!manifest.proves_image_commitment("a.png", &unbound, &proof(5)));
+    }
+}
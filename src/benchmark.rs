@@ -0,0 +1,349 @@
+//! Benchmarking key generation and proving, with a breakdown of the proving time by phase.
+//!
+//! The phases are observed through the transcript the proof is written to, so no changes
+//! to the prover are necessary:
+//! - *Synthesis*: Until the first commitment is written, i.e. witness generation and the
+//!   commitments to the advice columns.
+//! - *Commitment*: Until the first evaluation is written, i.e. the commitments of the lookup,
+//!   permutation and vanishing arguments.
+//! - *Opening*: The remaining time, i.e. the evaluations and the multi-opening argument.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+use halo2_proofs::{
+    arithmetic::CurveAffine,
+    halo2curves::bn256::{Bn256, G1Affine},
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    transcript::{EncodedChallenge, Transcript, TranscriptWrite, TranscriptWriterBuffer},
+};
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+use snark_verifier::{loader::native::NativeLoader, system::halo2::transcript::evm::EvmTranscript};
+
+use crate::error::ZeroGError;
+use crate::memory::measure_peak;
+use crate::proof_chain::GENESIS;
+use crate::telemetry::{ProofTelemetry, TelemetryLog};
+use crate::wnn::Wnn;
+
+/// Durations of the phases of a single proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhaseTimings {
+    pub synthesis: Duration,
+    pub commitment: Duration,
+    pub opening: Duration,
+}
+
+impl PhaseTimings {
+    pub fn total(&self) -> Duration {
+        self.synthesis + self.commitment + self.opening
+    }
+}
+
+/// Statistics (in seconds) over the durations of several runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    /// Computes the statistics, using the nearest-rank method for percentiles.
+    ///
+    /// Panics if `durations` is empty.
+    pub fn new(durations: &[Duration]) -> Self {
+        let mut seconds: Vec<f64> = durations.iter().map(Duration::as_secs_f64).collect();
+        seconds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * seconds.len() as f64).ceil() as usize;
+            seconds[rank.max(1) - 1]
+        };
+        Self {
+            mean: seconds.iter().sum::<f64>() / seconds.len() as f64,
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+            max: seconds[seconds.len() - 1],
+        }
+    }
+}
+
+/// Timings of the proving phases, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvingStats {
+    pub total: Stats,
+    pub synthesis: Stats,
+    pub commitment: Stats,
+    pub opening: Stats,
+}
+
+/// The result of [`run_benchmark`], which can be written as JSON to track regressions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchReport {
+    /// Version of `zero_g` that ran the benchmark.
+    pub crate_version: String,
+    /// See [`Wnn::commitment`].
+    pub model_commitment: [u8; 32],
+    pub k: u32,
+    pub num_proofs: usize,
+    /// Time (in seconds) to generate the proving and verifying keys.
+    pub keygen: f64,
+    pub proving: ProvingStats,
+    /// Peak resident set size of the process (in bytes), if available on this platform.
+    pub peak_rss: Option<u64>,
+    /// Peak heap allocation (in bytes) during key generation, if the
+    /// [`crate::memory::TrackingAllocator`] is installed.
+    #[serde(default)]
+    pub keygen_peak_heap: Option<u64>,
+    /// Maximum peak heap allocation (in bytes) of all proofs, if the
+    /// [`crate::memory::TrackingAllocator`] is installed.
+    #[serde(default)]
+    pub proving_peak_heap: Option<u64>,
+}
+
+impl BenchReport {
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "k = {}, {} proofs", self.k, self.num_proofs)?;
+        writeln!(f, "Key generation: {:.3}s", self.keygen)?;
+        writeln!(
+            f,
+            "\n{:<12} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            "Phase", "mean", "p50", "p90", "p99", "max"
+        )?;
+        for (phase, stats) in [
+            ("Synthesis", &self.proving.synthesis),
+            ("Commitment", &self.proving.commitment),
+            ("Opening", &self.proving.opening),
+            ("Total", &self.proving.total),
+        ] {
+            writeln!(
+                f,
+                "{phase:<12} {:>8.3}s {:>8.3}s {:>8.3}s {:>8.3}s {:>8.3}s",
+                stats.mean, stats.p50, stats.p90, stats.p99, stats.max
+            )?;
+        }
+        for (name, bytes) in [
+            ("Peak heap (key generation)", self.keygen_peak_heap),
+            ("Peak heap (proving)", self.proving_peak_heap),
+        ] {
+            if let Some(bytes) = bytes {
+                write!(f, "\n{name}: {:.1} MiB", bytes as f64 / (1 << 20) as f64)?;
+            }
+        }
+        match self.peak_rss {
+            Some(peak_rss) => write!(
+                f,
+                "\nPeak RSS: {:.1} MiB",
+                peak_rss as f64 / (1 << 20) as f64
+            ),
+            None => write!(f, "\nPeak RSS: unknown"),
+        }
+    }
+}
+
+/// Generates the keys for the model and `num_proofs` proofs for the image. If a telemetry log
+/// is given, a record (including the phase timings) is written for every proof.
+///
+/// Panics if `num_proofs` is zero.
+pub fn run_benchmark(
+    wnn: &Wnn,
+    kzg_params: &ParamsKZG<Bn256>,
+    image: &Array2<u8>,
+    num_proofs: usize,
+    telemetry: Option<&TelemetryLog>,
+) -> Result<BenchReport, ZeroGError> {
+    assert!(num_proofs > 0, "At least one proof is required");
+
+    let start = Instant::now();
+    let (pk, keygen_peak_heap) = measure_peak(|| wnn.generate_proving_key(kzg_params));
+    let pk = pk?;
+    let keygen = start.elapsed();
+    // Like the `Prover`, reuse the image-independent data across proofs
+    let synthesis_cache = wnn.synthesis_cache();
+
+    let timings = (0..num_proofs)
+        .map(|_| {
+            let started_at = SystemTime::now();
+            let mut transcript = PhaseTimer::new(
+                EvmTranscript::<G1Affine, NativeLoader, _, _>::init(Vec::new()),
+            );
+            // Every position in the chain has the same cost
+            let previous_chaining_value = wnn.chaining.then_some(&GENESIS);
+            let (result, peak_heap) = measure_peak(|| {
+                wnn.proof_with_transcript(
+                    &pk,
+                    kzg_params,
+                    image,
+                    None,
+                    previous_chaining_value,
+                    Some(&synthesis_cache),
+                    &mut transcript,
+                )
+            });
+            let (transcript, timings) = transcript.finish();
+            let proof_size = transcript.finalize().len();
+            if let Some(telemetry) = telemetry {
+                let record = ProofTelemetry::new(
+                    wnn.commitment(),
+                    kzg_params.k(),
+                    started_at,
+                    timings.total(),
+                    result.as_ref().map(|_| proof_size),
+                );
+                telemetry.record(&record.with_phases(timings).with_peak_heap(peak_heap));
+            }
+            result?;
+            Ok((timings, peak_heap))
+        })
+        .collect::<Result<Vec<_>, ZeroGError>>()?;
+    let (timings, peak_heaps): (Vec<_>, Vec<_>) = timings.into_iter().unzip();
+    let stats = |phase: fn(&PhaseTimings) -> Duration| {
+        Stats::new(&timings.iter().map(phase).collect::<Vec<_>>())
+    };
+
+    Ok(BenchReport {
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        model_commitment: wnn.commitment(),
+        k: kzg_params.k(),
+        num_proofs,
+        keygen: keygen.as_secs_f64(),
+        proving: ProvingStats {
+            total: stats(PhaseTimings::total),
+            synthesis: stats(|t| t.synthesis),
+            commitment: stats(|t| t.commitment),
+            opening: stats(|t| t.opening),
+        },
+        peak_rss: peak_rss(),
+        keygen_peak_heap,
+        proving_peak_heap: peak_heaps.into_iter().flatten().max(),
+    })
+}
+
+/// Reads the peak resident set size of the current process from `/proc` (Linux only).
+pub(crate) fn peak_rss() -> Option<u64> {
+    proc_status_bytes("VmHWM")
+}
+
+/// Reads the current resident set size of the current process from `/proc` (Linux only).
+pub(crate) fn current_rss() -> Option<u64> {
+    proc_status_bytes("VmRSS")
+}
+
+/// Reads a memory size (given in kB) from `/proc/self/status`.
+fn proc_status_bytes(field: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let prefix = format!("{field}:");
+    let line = status.lines().find(|line| line.starts_with(&prefix))?;
+    let kib: u64 = line[prefix.len()..]
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+/// Wraps a transcript, recording when the first commitment and the first evaluation are written.
+struct PhaseTimer<T> {
+    inner: T,
+    start: Instant,
+    first_point: Option<Instant>,
+    first_scalar: Option<Instant>,
+}
+
+impl<T> PhaseTimer<T> {
+    fn new(inner: T) -> Self {
+        Self {
+            inner,
+            start: Instant::now(),
+            first_point: None,
+            first_scalar: None,
+        }
+    }
+
+    /// Returns the wrapped transcript and the timings.
+    fn finish(self) -> (T, PhaseTimings) {
+        let end = Instant::now();
+        let first_point = self.first_point.unwrap_or(end);
+        let first_scalar = self.first_scalar.unwrap_or(end).max(first_point);
+        let timings = PhaseTimings {
+            synthesis: first_point - self.start,
+            commitment: first_scalar - first_point,
+            opening: end - first_scalar,
+        };
+        (self.inner, timings)
+    }
+}
+
+impl<C, E, T> Transcript<C, E> for PhaseTimer<T>
+where
+    C: CurveAffine,
+    E: EncodedChallenge<C>,
+    T: TranscriptWrite<C, E>,
+{
+    fn squeeze_challenge(&mut self) -> E {
+        self.inner.squeeze_challenge()
+    }
+
+    fn common_point(&mut self, point: C) -> io::Result<()> {
+        self.inner.common_point(point)
+    }
+
+    fn common_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        self.inner.common_scalar(scalar)
+    }
+}
+
+impl<C, E, T> TranscriptWrite<C, E> for PhaseTimer<T>
+where
+    C: CurveAffine,
+    E: EncodedChallenge<C>,
+    T: TranscriptWrite<C, E>,
+{
+    fn write_point(&mut self, point: C) -> io::Result<()> {
+        self.first_point.get_or_insert_with(Instant::now);
+        self.inner.write_point(point)
+    }
+
+    fn write_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        self.first_scalar.get_or_insert_with(Instant::now);
+        self.inner.write_scalar(scalar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Stats;
+
+    #[test]
+    fn test_stats() {
+        let durations: Vec<_> = (1..=10).rev().map(Duration::from_secs).collect();
+        let stats = Stats::new(&durations);
+        assert_eq!(stats.mean, 5.5);
+        assert_eq!(stats.p50, 5.0);
+        assert_eq!(stats.p90, 9.0);
+        assert_eq!(stats.p99, 10.0);
+        assert_eq!(stats.max, 10.0);
+
+        let single = Stats::new(&[Duration::from_millis(500)]);
+        assert_eq!(single.p50, 0.5);
+        assert_eq!(single.max, 0.5);
+    }
+}
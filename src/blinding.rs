@@ -0,0 +1,194 @@
+//! Explicit control over the blinding of [`WnnCircuit`], which makes proofs zero-knowledge.
+//!
+//! halo2 fills the last rows of every advice column with random values, so that the
+//! evaluations of the advice polynomials revealed in a proof don't leak the witness. The number
+//! of these rows (the blinding factors) is derived from the constraint system: each of the `t`
+//! distinct queries of a column reveals one evaluation, and `max(3, t) + 2` blinding factors
+//! hide all of them. The row after the blinding rows is reserved as well, so the circuit can
+//! use `2^k - blinding_factors - 1` rows.
+//!
+//! [`BlindingInfo`] reports these numbers and [`BlindingInfo::check`] asserts that every
+//! advice column receives enough blinding. To use more blinding factors than halo2 derives
+//! (e.g. as a margin for deployments with strict privacy requirements), set
+//! [`WnnCircuitParams::min_blinding_factors`] (see [`crate::Wnn::with_min_blinding_factors`]).
+//! This is implemented with an additional advice column that is queried at enough rotations,
+//! which also makes proofs slightly larger.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use ff::Field;
+use halo2_proofs::{
+    halo2curves::bn256::Fr as Fp,
+    plonk::{Circuit, ConstraintSystem},
+    poly::Rotation,
+};
+use serde::Serialize;
+
+use crate::error::ZeroGError;
+use crate::gadgets::wnn::{WnnCircuit, WnnCircuitParams};
+
+/// Extra blinding factors on top of the number of queries of a column: one for the
+/// evaluation in the multiopen argument and one for the vanishing argument.
+const EXTRA_BLINDING_FACTORS: usize = 2;
+
+/// The blinding of the circuit at a given `k`, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlindingInfo {
+    pub k: u32,
+    /// The number of rows at the end of every advice column that are filled with random values.
+    pub blinding_factors: usize,
+    /// The number of rows the circuit can use.
+    pub usable_rows: usize,
+    pub num_advice_columns: usize,
+    /// The largest number of distinct queries (rotations) of any advice column.
+    pub max_advice_queries: usize,
+}
+
+impl BlindingInfo {
+    pub fn new(params: &WnnCircuitParams, k: u32) -> Self {
+        let mut cs = ConstraintSystem::<Fp>::default();
+        WnnCircuit::<Fp>::configure_with_params(&mut cs, params.clone());
+
+        let mut queries = BTreeMap::new();
+        for (column, _) in cs.advice_queries() {
+            *queries.entry(column.index()).or_insert(0) += 1;
+        }
+        let blinding_factors = cs.blinding_factors();
+        Self {
+            k,
+            blinding_factors,
+            usable_rows: (1usize << k).saturating_sub(blinding_factors + 1),
+            num_advice_columns: cs.num_advice_columns(),
+            max_advice_queries: queries.values().copied().max().unwrap_or(0),
+        }
+    }
+
+    /// Checks that every advice column is blinded with more random rows than it reveals
+    /// evaluations, and that the requested number of blinding factors is met.
+    pub fn check(&self, params: &WnnCircuitParams) -> Result<(), ZeroGError> {
+        let required = (self.max_advice_queries.max(3) + EXTRA_BLINDING_FACTORS)
+            .max(params.min_blinding_factors);
+        if self.blinding_factors < required {
+            return Err(ZeroGError::InvalidModel(format!(
+                "The circuit has {} blinding factors, but {required} are required",
+                self.blinding_factors
+            )));
+        }
+        if self.usable_rows == 0 {
+            return Err(ZeroGError::InvalidModel(format!(
+                "k = {} leaves no usable rows",
+                self.k
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BlindingInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Blinding factors:   {}", self.blinding_factors)?;
+        writeln!(
+            f,
+            "Usable rows:        {} of {}",
+            self.usable_rows,
+            1usize << self.k
+        )?;
+        write!(
+            f,
+            "Advice columns:     {} (at most {} queries each)",
+            self.num_advice_columns, self.max_advice_queries
+        )
+    }
+}
+
+/// Raises the number of blinding factors of the constraint system to at least
+/// `min_blinding_factors`, by querying a dedicated advice column at enough rotations in a gate
+/// that is never enabled. Should be called after all other columns are configured.
+pub(crate) fn configure_min_blinding_factors<F: Field>(
+    meta: &mut ConstraintSystem<F>,
+    min_blinding_factors: usize,
+) {
+    if meta.blinding_factors() >= min_blinding_factors {
+        return;
+    }
+    let num_queries = min_blinding_factors - EXTRA_BLINDING_FACTORS;
+    let column = meta.advice_column();
+    let selector = meta.selector();
+    meta.create_gate("extra blinding", |meta| {
+        let selector = meta.query_selector(selector);
+        (0..num_queries)
+            .map(|rotation| selector.clone() * meta.query_advice(column, Rotation(rotation as i32)))
+            .collect::<Vec<_>>()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlindingInfo;
+    use crate::gadgets::wnn::WnnCircuitParams;
+
+    fn params(min_blinding_factors: usize) -> WnnCircuitParams {
+        WnnCircuitParams {
+            p: 2097143,
+            l: 20,
+            n_hashes: 2,
+            bits_per_hash: 10,
+            bits_per_filter: 12,
+            n_classes: 2,
+            min_blinding_factors,
+            num_instance_columns: 1,
+            class_lookup: false,
+            window_num_bits: 8,
+            chaining: false,
+            multi_label: false,
+            regression: false,
+            tabular: false,
+            occlusion_num_pixels: 0,
+            robustness: false,
+            image_commitment: false,
+        }
+    }
+
+    #[test]
+    fn test_min_blinding_factors() {
+        let default = BlindingInfo::new(&params(0), 12);
+        assert!(default.check(&params(0)).is_ok());
+        assert_eq!(
+            default.usable_rows,
+            (1 << 12) - default.blinding_factors - 1
+        );
+
+        let min_blinding_factors = default.blinding_factors + 5;
+        let blinded = BlindingInfo::new(&params(min_blinding_factors), 12);
+        assert_eq!(blinded.blinding_factors, min_blinding_factors);
+        assert_eq!(blinded.num_advice_columns, default.num_advice_columns + 1);
+        assert!(blinded.check(&params(min_blinding_factors)).is_ok());
+        assert!(default.check(&params(min_blinding_factors)).is_err());
+    }
+
+    /// Two proofs of the same image share no commitment or evaluation, so they can't be linked.
+    #[cfg(feature = "hdf5")]
+    #[test]
+    fn test_proofs_are_unlinkable() {
+        use std::path::Path;
+
+        use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+        use crate::setup::{get_srs, SrsSource};
+        use crate::{load_grayscale_image, load_wnn, Wnn};
+
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let image = load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap();
+        let kzg_params = get_srs(&SrsSource::Generate, k).unwrap();
+        let pk = wnn.generate_proving_key(&kzg_params).unwrap();
+
+        let (first, public_inputs) = wnn.proof(&pk, &kzg_params, &image).unwrap();
+        let (second, _) = wnn.proof(&pk, &kzg_params, &image).unwrap();
+        for proof in [&first, &second] {
+            Wnn::verify_proof(proof, &kzg_params, pk.get_vk(), &public_inputs);
+        }
+        assert_eq!(first.len(), second.len());
+        assert!(first.chunks(32).zip(second.chunks(32)).all(|(a, b)| a != b));
+    }
+}
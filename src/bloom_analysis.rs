@@ -0,0 +1,251 @@
+//! Analysis of the bloom filters of a model, to judge whether a model is worth proving before
+//! spending time on key generation.
+//!
+//! [`FalsePositiveAnalysis`] estimates from the bloom filters alone how often a filter responds
+//! to an input it was not trained on. A filter with density `d` (fraction of set entries) and
+//! `h` hashes responds to a random input with probability `d^h`. If this is close to the rate at
+//! which filters respond to inputs of their own class, the scores carry little information.
+//!
+//! [`ScoreDistribution`] measures the actual scores on a labeled dataset.
+
+use std::fmt;
+
+use image::ImageError;
+use serde::Serialize;
+
+use crate::datasets::Example;
+use crate::utils::argmax;
+use crate::wnn::Wnn;
+
+/// Estimated false positives of the bloom filters of one class.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClassFalsePositives {
+    /// Fraction of bloom filter entries that are set.
+    pub density: f64,
+    /// The probability that a filter responds to a random input, averaged over the filters.
+    pub false_positive_rate: f64,
+    /// The expected score of a random input.
+    pub expected_random_score: f64,
+    /// Number of filters with all entries set, which respond to every input.
+    pub saturated_filters: usize,
+}
+
+/// Estimated false positives of all classes, see [`FalsePositiveAnalysis::new`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FalsePositiveAnalysis {
+    /// Number of filters per class, i.e. the maximal score.
+    pub num_filters: usize,
+    pub num_filter_hashes: usize,
+    pub classes: Vec<ClassFalsePositives>,
+}
+
+impl FalsePositiveAnalysis {
+    pub fn new(wnn: &Wnn) -> Self {
+        let [num_classes, num_filters, num_filter_entries] = wnn.bloom_filters.shape();
+        let classes = (0..num_classes)
+            .map(|class| {
+                let densities: Vec<_> = (0..num_filters)
+                    .map(|filter| {
+                        let set = wnn.bloom_filters.count_set(class, filter);
+                        set as f64 / num_filter_entries as f64
+                    })
+                    .collect();
+                let false_positives: Vec<_> = densities
+                    .iter()
+                    .map(|density| density.powi(wnn.num_filter_hashes as i32))
+                    .collect();
+                let expected_random_score = false_positives.iter().sum::<f64>();
+                ClassFalsePositives {
+                    density: densities.iter().sum::<f64>() / num_filters as f64,
+                    false_positive_rate: expected_random_score / num_filters as f64,
+                    expected_random_score,
+                    saturated_filters: densities.iter().filter(|d| **d == 1.0).count(),
+                }
+            })
+            .collect();
+        Self {
+            num_filters,
+            num_filter_hashes: wnn.num_filter_hashes,
+            classes,
+        }
+    }
+}
+
+impl fmt::Display for FalsePositiveAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} filters per class, {} hashes",
+            self.num_filters, self.num_filter_hashes
+        )?;
+        write!(
+            f,
+            "  {:<8} {:>8} {:>10} {:>13} {:>10}",
+            "Class", "density", "FP rate", "random score", "saturated"
+        )?;
+        for (class, stats) in self.classes.iter().enumerate() {
+            write!(
+                f,
+                "\n  {class:<8} {:>7.1}% {:>9.2}% {:>13.1} {:>10}",
+                stats.density * 100.0,
+                stats.false_positive_rate * 100.0,
+                stats.expected_random_score,
+                stats.saturated_filters
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The scores of one class over a dataset.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClassScores {
+    /// The mean score on examples of this class.
+    pub mean_own: f64,
+    /// The mean score on examples of other classes.
+    pub mean_other: f64,
+    /// The number of examples (of any class) with each score.
+    pub histogram: Vec<u64>,
+}
+
+/// The scores of a model over a dataset, see [`ScoreDistribution::simulate`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScoreDistribution {
+    pub num_examples: usize,
+    pub classes: Vec<ClassScores>,
+    /// The number of examples with each margin between the highest and the second-highest
+    /// score. Examples with a margin of zero are ties, which are broken by the class index.
+    pub margin_histogram: Vec<u64>,
+}
+
+impl ScoreDistribution {
+    /// Predicts every example and collects the scores.
+    pub fn simulate(
+        wnn: &Wnn,
+        examples: impl IntoIterator<Item = Result<Example, ImageError>>,
+    ) -> Result<Self, ImageError> {
+        let num_filters = wnn.bloom_filters.shape()[1];
+        let mut histograms = vec![vec![0; num_filters + 1]; wnn.num_classes];
+        let mut sum_own = vec![0u64; wnn.num_classes];
+        let mut count_own = vec![0u64; wnn.num_classes];
+        let mut sum_all = vec![0u64; wnn.num_classes];
+        let mut margin_histogram = vec![0; num_filters + 1];
+        let mut num_examples = 0;
+
+        for example in examples {
+            let example = example?;
+            let scores = wnn.predict(&example.image);
+            for (class, score) in scores.iter().enumerate() {
+                histograms[class][*score as usize] += 1;
+                sum_all[class] += score;
+            }
+            if example.label < wnn.num_classes {
+                sum_own[example.label] += scores[example.label];
+                count_own[example.label] += 1;
+            }
+            let best = argmax(&scores);
+            let runner_up = scores
+                .iter()
+                .enumerate()
+                .filter(|(class, _)| *class != best)
+                .map(|(_, score)| *score)
+                .max()
+                .unwrap_or(0);
+            margin_histogram[(scores[best] - runner_up) as usize] += 1;
+            num_examples += 1;
+        }
+
+        let mean = |sum: u64, count: u64| {
+            if count == 0 {
+                0.0
+            } else {
+                sum as f64 / count as f64
+            }
+        };
+        let classes = histograms
+            .into_iter()
+            .enumerate()
+            .map(|(class, histogram)| ClassScores {
+                mean_own: mean(sum_own[class], count_own[class]),
+                mean_other: mean(
+                    sum_all[class] - sum_own[class],
+                    num_examples as u64 - count_own[class],
+                ),
+                histogram,
+            })
+            .collect();
+        Ok(Self {
+            num_examples,
+            classes,
+            margin_histogram,
+        })
+    }
+
+    /// The fraction of examples where the two highest scores are equal.
+    pub fn tie_rate(&self) -> f64 {
+        self.margin_histogram[0] as f64 / self.num_examples.max(1) as f64
+    }
+}
+
+impl fmt::Display for ScoreDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Scores over {} examples", self.num_examples)?;
+        writeln!(f, "  {:<8} {:>10} {:>10}", "Class", "own", "other")?;
+        for (class, scores) in self.classes.iter().enumerate() {
+            writeln!(
+                f,
+                "  {class:<8} {:>10.1} {:>10.1}",
+                scores.mean_own, scores.mean_other
+            )?;
+        }
+        write!(f, "Ties: {:.1}%", self.tie_rate() * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{s, Array1, Array2, Array3};
+
+    use super::{FalsePositiveAnalysis, ScoreDistribution};
+    use crate::datasets::Example;
+    use crate::wnn::Wnn;
+
+    #[test]
+    fn test_analysis() {
+        // Class 0 has half of the entries set, class 1 all entries
+        let mut bloom_filters = Array3::from_elem((2, 2, 4), false);
+        bloom_filters.slice_mut(s![0, .., ..2]).fill(true);
+        bloom_filters.slice_mut(s![1, .., ..]).fill(true);
+        let wnn = Wnn::new(
+            2,
+            4,
+            2,
+            4,
+            2097143,
+            bloom_filters,
+            (0..8u64).collect::<Array1<_>>(),
+            Array3::zeros((2, 2, 2)),
+        );
+
+        let analysis = FalsePositiveAnalysis::new(&wnn);
+        assert_eq!(analysis.classes[0].density, 0.5);
+        assert_eq!(analysis.classes[0].false_positive_rate, 0.25);
+        assert_eq!(analysis.classes[0].expected_random_score, 0.5);
+        assert_eq!(analysis.classes[1].false_positive_rate, 1.0);
+        assert_eq!(analysis.classes[1].saturated_filters, 2);
+
+        let examples = (0..3).map(|i| {
+            Ok(Example {
+                id: i.to_string(),
+                image: Array2::zeros((2, 2)),
+                label: 1,
+            })
+        });
+        let distribution = ScoreDistribution::simulate(&wnn, examples).unwrap();
+        assert_eq!(distribution.num_examples, 3);
+        assert_eq!(distribution.classes[1].mean_own, 2.0);
+        assert_eq!(distribution.classes[1].histogram, vec![0, 0, 3]);
+        assert_eq!(distribution.classes[0].mean_own, 0.0);
+        assert_eq!(distribution.margin_histogram.iter().sum::<u64>(), 3);
+    }
+}
@@ -0,0 +1,315 @@
+//! A C API, so that zero_g can be embedded in mobile apps and services not written in Rust.
+//! See `include/zero_g.h` for the declarations.
+//!
+//! All functions return a [`ZgStatus`]. If it is [`ZgStatus::Error`], [`zg_last_error`] returns
+//! a description of the error. Buffers returned by the library are owned by the caller and have
+//! to be freed with [`zg_buffer_free`]; handles are freed with [`zg_prover_free`].
+//!
+//! The prover handle may be used from several threads at the same time.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::{ptr, slice};
+
+use ndarray::Array1;
+
+use crate::classifier::WnnInput;
+use crate::io::{
+    load_image_from_bytes, load_model_from_bytes, read_pk_from_bytes, read_srs_from_bytes,
+};
+use crate::proof_file::ProofFile;
+use crate::prover::Prover;
+use crate::verifier_bundle::{VerificationError, VerifierBundle};
+
+/// The result of every function of the C API.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZgStatus {
+    Ok = 0,
+    /// The proof was rejected by [`zg_verify`].
+    InvalidProof = 1,
+    /// A required pointer argument was null, or a string was not valid UTF-8.
+    InvalidArgument = 2,
+    /// Any other error, see [`zg_last_error`].
+    Error = 3,
+}
+
+/// A byte buffer allocated by the library, see [`zg_buffer_free`].
+#[repr(C)]
+pub struct ZgBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl ZgBuffer {
+    fn new(bytes: Vec<u8>) -> Self {
+        let bytes = Box::leak(bytes.into_boxed_slice());
+        Self {
+            data: bytes.as_mut_ptr(),
+            len: bytes.len(),
+        }
+    }
+}
+
+/// An opaque handle to a [`Prover`].
+pub struct ZgProver(Prover);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+fn set_last_error(message: String) {
+    // Interior null bytes can't be represented, so they are dropped
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+/// Runs `f`, turning errors and panics into a status and recording the error message.
+fn run(f: impl FnOnce() -> Result<ZgStatus, Box<dyn std::error::Error>>) -> ZgStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(status)) => status,
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            ZgStatus::Error
+        }
+        Err(_) => {
+            set_last_error("zero_g panicked".to_string());
+            ZgStatus::Error
+        }
+    }
+}
+
+/// # Safety
+/// `data` must be null or point to `len` readable bytes.
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    (!data.is_null()).then(|| slice::from_raw_parts(data, len))
+}
+
+/// # Safety
+/// `s` must be null or point to a null-terminated string.
+unsafe fn c_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+/// The message of the last error on this thread, or null if there was none. The string is
+/// valid until the next call into the library on this thread.
+#[no_mangle]
+pub extern "C" fn zg_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Opens a prover for a model and the artifact directory written by `zero_g setup`
+/// (see [`Prover::open`]).
+///
+/// # Safety
+/// The paths must be null-terminated strings and `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn zg_prover_open(
+    model_path: *const c_char,
+    artifact_dir: *const c_char,
+    out: *mut *mut ZgProver,
+) -> ZgStatus {
+    let (model_path, artifact_dir) = match (c_str(model_path), c_str(artifact_dir)) {
+        (Some(model_path), Some(artifact_dir)) if !out.is_null() => (model_path, artifact_dir),
+        _ => return ZgStatus::InvalidArgument,
+    };
+    run(|| {
+        let prover = Prover::open(Path::new(model_path), Path::new(artifact_dir))?;
+        *out = Box::into_raw(Box::new(ZgProver(prover)));
+        Ok(ZgStatus::Ok)
+    })
+}
+
+/// Creates a prover from a `.zgm` model, the SRS and the proving key in memory. If `pk` is null,
+/// the proving key is generated.
+///
+/// # Safety
+/// The buffers must be readable for the given lengths and `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn zg_prover_from_bytes(
+    model: *const u8,
+    model_len: usize,
+    srs: *const u8,
+    srs_len: usize,
+    pk: *const u8,
+    pk_len: usize,
+    out: *mut *mut ZgProver,
+) -> ZgStatus {
+    let (model, srs) = match (bytes(model, model_len), bytes(srs, srs_len)) {
+        (Some(model), Some(srs)) if !out.is_null() => (model, srs),
+        _ => return ZgStatus::InvalidArgument,
+    };
+    let pk = bytes(pk, pk_len);
+    run(|| {
+        let wnn = load_model_from_bytes(model)?;
+        let kzg_params = read_srs_from_bytes(srs)?;
+        let pk = match pk {
+            Some(pk) => read_pk_from_bytes(pk, wnn.get_circuit_params())?,
+            None => wnn.generate_proving_key(&kzg_params)?,
+        };
+        *out = Box::into_raw(Box::new(ZgProver(Prover::new(wnn, kzg_params, pk))));
+        Ok(ZgStatus::Ok)
+    })
+}
+
+/// Frees a prover. Does nothing if `prover` is null.
+///
+/// # Safety
+/// `prover` must have been returned by this library and not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn zg_prover_free(prover: *mut ZgProver) {
+    if !prover.is_null() {
+        drop(Box::from_raw(prover));
+    }
+}
+
+/// Proves inference of an image (PNG, JPEG, ...) and writes the proof file (`.zgp`) to `out`.
+///
+/// # Safety
+/// `prover` must be a valid handle, `image` must be readable for `image_len` bytes and `out`
+/// must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn zg_prove(
+    prover: *const ZgProver,
+    image: *const u8,
+    image_len: usize,
+    out: *mut ZgBuffer,
+) -> ZgStatus {
+    let (prover, image) = match (prover.as_ref(), bytes(image, image_len)) {
+        (Some(prover), Some(image)) if !out.is_null() => (prover, image),
+        _ => return ZgStatus::InvalidArgument,
+    };
+    run(|| {
+        let input = WnnInput::Image(load_image_from_bytes(image)?);
+        *out = ZgBuffer::new(prover.0.prove(&input)?.to_bytes());
+        Ok(ZgStatus::Ok)
+    })
+}
+
+/// Proves inference of the feature vector of a tabular model and writes the proof file
+/// (`.zgp`) to `out`.
+///
+/// # Safety
+/// `prover` must be a valid handle, `features` must be readable for `num_features` values and
+/// `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn zg_prove_features(
+    prover: *const ZgProver,
+    features: *const u16,
+    num_features: usize,
+    out: *mut ZgBuffer,
+) -> ZgStatus {
+    let prover = match prover.as_ref() {
+        Some(prover) if !features.is_null() && !out.is_null() => prover,
+        _ => return ZgStatus::InvalidArgument,
+    };
+    let features = slice::from_raw_parts(features, num_features);
+    run(|| {
+        let input = WnnInput::Features(Array1::from(features.to_vec()));
+        *out = ZgBuffer::new(prover.0.prove(&input)?.to_bytes());
+        Ok(ZgStatus::Ok)
+    })
+}
+
+/// Writes the verifier bundle for the proofs of the prover to `out`, see [`zg_verify`].
+///
+/// # Safety
+/// `prover` must be a valid handle and `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn zg_verifier_bundle(
+    prover: *const ZgProver,
+    out: *mut ZgBuffer,
+) -> ZgStatus {
+    let prover = match prover.as_ref() {
+        Some(prover) if !out.is_null() => prover,
+        _ => return ZgStatus::InvalidArgument,
+    };
+    run(|| {
+        let mut bundle = vec![];
+        prover.0.verifier_bundle().write_to(&mut bundle)?;
+        *out = ZgBuffer::new(bundle);
+        Ok(ZgStatus::Ok)
+    })
+}
+
+/// Verifies a proof file against a verifier bundle. Returns [`ZgStatus::InvalidProof`] if the
+/// proof is invalid, and [`ZgStatus::Error`] if it was generated for a different circuit or
+/// verification key. In both cases, [`zg_last_error`] returns the reason.
+///
+/// # Safety
+/// The buffers must be readable for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn zg_verify(
+    bundle: *const u8,
+    bundle_len: usize,
+    proof_file: *const u8,
+    proof_file_len: usize,
+) -> ZgStatus {
+    let (bundle, proof_file) = match (bytes(bundle, bundle_len), bytes(proof_file, proof_file_len))
+    {
+        (Some(bundle), Some(proof_file)) => (bundle, proof_file),
+        _ => return ZgStatus::InvalidArgument,
+    };
+    run(|| {
+        let bundle = VerifierBundle::read_from(&mut &bundle[..])?;
+        let proof_file = ProofFile::from_bytes(proof_file)?;
+        match bundle.verify(&proof_file) {
+            Ok(()) => Ok(ZgStatus::Ok),
+            Err(e @ VerificationError::InvalidProof(_)) => {
+                set_last_error(e.to_string());
+                Ok(ZgStatus::InvalidProof)
+            }
+            Err(e) => Err(e.into()),
+        }
+    })
+}
+
+/// Frees a buffer returned by the library. Does nothing if `buffer.data` is null.
+///
+/// # Safety
+/// The buffer must have been returned by this library and not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn zg_buffer_free(buffer: ZgBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+    use std::ptr;
+
+    use super::{zg_last_error, zg_prove, zg_prove_features, zg_verify, ZgBuffer, ZgStatus};
+
+    #[test]
+    fn test_invalid_arguments() {
+        let mut out = ZgBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let status = unsafe { zg_prove(ptr::null(), ptr::null(), 0, &mut out) };
+        assert_eq!(status, ZgStatus::InvalidArgument);
+        let status = unsafe { zg_prove_features(ptr::null(), ptr::null(), 0, &mut out) };
+        assert_eq!(status, ZgStatus::InvalidArgument);
+
+        let garbage = [0u8; 8];
+        let status = unsafe { zg_verify(garbage.as_ptr(), 8, garbage.as_ptr(), 8) };
+        assert_eq!(status, ZgStatus::Error);
+        let message = unsafe { CStr::from_ptr(zg_last_error()) };
+        assert!(message.to_str().unwrap().contains("verifier bundle"));
+    }
+}
@@ -0,0 +1,382 @@
+//! The structure of a circuit (which chips constrain which columns, which columns are looked
+//! up in which tables and how many regions each chip assigns) as a
+//! [Graphviz](https://graphviz.org) DOT graph.
+//!
+//! Chips are recovered from the names of the gates (see [`chip_for_gate`]) and regions (see
+//! [`chip_for_region`]), so the graph can be rendered for any circuit, but only the gadgets of
+//! this crate are grouped into chips. Render it e.g. with `dot -Tsvg circuit.dot -o circuit.svg`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use ff::Field;
+use halo2_proofs::plonk::{Circuit, ConstraintSystem, Error, Expression};
+
+use crate::region_layout::region_rows;
+use crate::testing::chip_for_region;
+
+/// A column of the circuit. Selectors are not included, they are not shared between chips.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ColumnId {
+    Instance(usize),
+    Advice(usize),
+    Fixed(usize),
+}
+
+impl fmt::Display for ColumnId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnId::Instance(index) => write!(f, "instance {index}"),
+            ColumnId::Advice(index) => write!(f, "advice {index}"),
+            ColumnId::Fixed(index) => write!(f, "fixed {index}"),
+        }
+    }
+}
+
+/// A chip, with the gates it creates and the columns it constrains or assigns.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChipNode {
+    pub name: String,
+    pub gates: Vec<String>,
+    pub columns: BTreeSet<ColumnId>,
+    pub num_regions: usize,
+}
+
+/// A lookup table, named after the region that assigns it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableNode {
+    pub name: String,
+    pub columns: BTreeSet<ColumnId>,
+}
+
+/// A lookup argument, from the columns of its input expressions into a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupEdge {
+    pub inputs: BTreeSet<ColumnId>,
+    /// Index into [`CircuitGraph::tables`].
+    pub table: usize,
+}
+
+/// The structure of a circuit, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitGraph {
+    pub columns: Vec<ColumnId>,
+    /// Sorted by name.
+    pub chips: Vec<ChipNode>,
+    pub tables: Vec<TableNode>,
+    pub lookups: Vec<LookupEdge>,
+}
+
+impl CircuitGraph {
+    /// Configures and synthesizes (without witness) the circuit to recover its structure.
+    pub fn new<F: Field, C: Circuit<F>>(circuit: &C) -> Result<Self, Error> {
+        let mut cs = ConstraintSystem::default();
+        C::configure_with_params(&mut cs, circuit.params());
+
+        let columns = (0..cs.num_instance_columns())
+            .map(ColumnId::Instance)
+            .chain((0..cs.num_advice_columns()).map(ColumnId::Advice))
+            .chain((0..cs.num_fixed_columns()).map(ColumnId::Fixed))
+            .collect();
+
+        let mut chips: BTreeMap<String, ChipNode> = BTreeMap::new();
+        for gate in cs.gates() {
+            let name = chip_for_gate(gate.name()).unwrap_or(gate.name());
+            let node = chip_node(&mut chips, name);
+            node.gates.push(gate.name().to_string());
+            for polynomial in gate.polynomials() {
+                node.columns.extend(queried_columns(polynomial));
+            }
+        }
+
+        let regions = region_rows(circuit)?;
+        let mut tables: Vec<TableNode> = vec![];
+        let lookups = cs
+            .lookups()
+            .iter()
+            .enumerate()
+            .map(|(i, lookup)| {
+                let inputs = lookup
+                    .input_expressions()
+                    .iter()
+                    .flat_map(queried_columns)
+                    .collect();
+                let table_columns: BTreeSet<_> = lookup
+                    .table_expressions()
+                    .iter()
+                    .flat_map(queried_columns)
+                    .collect();
+                let table = match tables
+                    .iter()
+                    .position(|table| table.columns == table_columns)
+                {
+                    Some(table) => table,
+                    None => {
+                        let name = regions
+                            .iter()
+                            .find(|region| {
+                                !region.columns.is_empty()
+                                    && region.columns.is_subset(&table_columns)
+                            })
+                            .map_or_else(|| format!("table {i}"), |region| region.name.clone());
+                        tables.push(TableNode {
+                            name,
+                            columns: table_columns,
+                        });
+                        tables.len() - 1
+                    }
+                };
+                LookupEdge { inputs, table }
+            })
+            .collect();
+
+        for region in &regions {
+            let is_table = tables.iter().any(|table| {
+                !region.columns.is_empty() && region.columns.is_subset(&table.columns)
+            });
+            if is_table {
+                continue;
+            }
+            let name = chip_for_region(&region.name).unwrap_or(region.name.as_str());
+            let node = chip_node(&mut chips, name);
+            node.num_regions += 1;
+            node.columns.extend(region.columns.iter().copied());
+        }
+
+        Ok(Self {
+            columns,
+            chips: chips.into_values().collect(),
+            tables,
+            lookups,
+        })
+    }
+
+    /// Returns the columns used by more than one chip, with the names of these chips.
+    pub fn shared_columns(&self) -> BTreeMap<ColumnId, Vec<&str>> {
+        let mut users: BTreeMap<ColumnId, Vec<&str>> = BTreeMap::new();
+        for chip in &self.chips {
+            for column in &chip.columns {
+                users.entry(*column).or_default().push(&chip.name);
+            }
+        }
+        users.retain(|_, chips| chips.len() > 1);
+        users
+    }
+
+    /// Renders the graph in the DOT language.
+    pub fn to_dot(&self) -> String {
+        let shared_columns = self.shared_columns();
+        let mut dot = String::new();
+        // Writing to a string can't fail
+        let mut line = |line: String| writeln!(dot, "    {line}").unwrap();
+
+        line("rankdir=LR;".to_string());
+        line("node [fontname=\"sans-serif\"];".to_string());
+        for chip in &self.chips {
+            let label = format!(
+                "{}\n{} gates, {} regions",
+                chip.name,
+                chip.gates.len(),
+                chip.num_regions
+            );
+            line(format!(
+                "{} [shape=box, label={}];",
+                quote(&chip_id(chip)),
+                quote(&label)
+            ));
+        }
+        for column in &self.columns {
+            let style = match shared_columns.get(column) {
+                Some(chips) => format!(
+                    ", label={}, style=filled, fillcolor=lightgrey",
+                    quote(&format!("{column}\nshared by {} chips", chips.len()))
+                ),
+                None => String::new(),
+            };
+            line(format!(
+                "{} [shape=ellipse{style}];",
+                quote(&column.to_string())
+            ));
+        }
+        for (i, table) in self.tables.iter().enumerate() {
+            line(format!(
+                "{} [shape=cylinder, label={}];",
+                quote(&table_id(i)),
+                quote(&table.name)
+            ));
+        }
+
+        for chip in &self.chips {
+            for column in &chip.columns {
+                line(format!(
+                    "{} -> {};",
+                    quote(&chip_id(chip)),
+                    quote(&column.to_string())
+                ));
+            }
+        }
+        for (i, table) in self.tables.iter().enumerate() {
+            for column in &table.columns {
+                line(format!(
+                    "{} -> {} [dir=none];",
+                    quote(&table_id(i)),
+                    quote(&column.to_string())
+                ));
+            }
+        }
+        for (i, lookup) in self.lookups.iter().enumerate() {
+            for column in &lookup.inputs {
+                line(format!(
+                    "{} -> {} [style=dashed, label={}];",
+                    quote(&column.to_string()),
+                    quote(&table_id(lookup.table)),
+                    quote(&format!("lookup {i}"))
+                ));
+            }
+        }
+        format!("digraph circuit {{\n{dot}}}\n")
+    }
+
+    /// Writes the DOT graph to a file.
+    pub fn write_dot(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_dot())
+    }
+}
+
+/// Returns the name of the chip that creates the gate with the given name, if known.
+pub fn chip_for_gate(gate_name: &str) -> Option<&'static str> {
+    Some(match gate_name {
+        "next_num_constraint" => "Bits2NumChip",
+        "hash" | "hash (reduced)" => "HashChip",
+        "validate_hash_accumulators"
+        | "hash_equality"
+        | "validate_bloom_accumulators"
+        | "validate_bit_acc"
+        | "selector_is_bit"
+        | "selector_acc"
+        | "right_byte_selected"
+        | "byte_acc" => "BloomFilterChip",
+        "accumulate_responses" => "ResponseAccumulatorChip",
+        "x + diff = 256 * is_gt + y" => "GreaterThanChip",
+        "le" | "short range check shift" => "RangeCheckConfig",
+        _ => return None,
+    })
+}
+
+fn chip_node<'a>(chips: &'a mut BTreeMap<String, ChipNode>, name: &str) -> &'a mut ChipNode {
+    chips.entry(name.to_string()).or_insert_with(|| ChipNode {
+        name: name.to_string(),
+        ..Default::default()
+    })
+}
+
+/// Returns the (non-selector) columns queried in the expression.
+fn queried_columns<F: Field>(expression: &Expression<F>) -> BTreeSet<ColumnId> {
+    let single = |column| BTreeSet::from([column]);
+    expression.evaluate(
+        &|_| BTreeSet::new(),
+        &|_| BTreeSet::new(),
+        &|query| single(ColumnId::Fixed(query.column_index())),
+        &|query| single(ColumnId::Advice(query.column_index())),
+        &|query| single(ColumnId::Instance(query.column_index())),
+        &|_| BTreeSet::new(),
+        &|columns| columns,
+        &|mut a, b| {
+            a.extend(b);
+            a
+        },
+        &|mut a, b| {
+            a.extend(b);
+            a
+        },
+        &|columns, _| columns,
+    )
+}
+
+fn chip_id(chip: &ChipNode) -> String {
+    format!("chip {}", chip.name)
+}
+
+fn table_id(index: usize) -> String {
+    format!("table {index}")
+}
+
+/// Quotes a DOT identifier or label.
+fn quote(s: &str) -> String {
+    format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::halo2curves::bn256::Fr as Fp;
+    use ndarray::{Array2, Array3};
+
+    use super::{quote, CircuitGraph, ColumnId};
+    use crate::gadgets::WnnCircuit;
+    use crate::wnn::Wnn;
+
+    fn graph() -> CircuitGraph {
+        let wnn = Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            2097143,
+            Array3::from_elem((2, 2, 1024), false),
+            (0..24u64).collect(),
+            Array3::zeros((4, 3, 2)),
+        );
+        let circuit: WnnCircuit<Fp> = WnnCircuit::builder(&wnn)
+            .image(Array2::zeros((4, 3)))
+            .build()
+            .unwrap();
+        CircuitGraph::new(&circuit).unwrap()
+    }
+
+    #[test]
+    fn test_circuit_graph() {
+        let graph = graph();
+        let chip = |name: &str| graph.chips.iter().find(|chip| chip.name == name).unwrap();
+
+        assert_eq!(chip("HashChip").gates, vec!["hash"]);
+        // One hash per filter
+        assert_eq!(chip("HashChip").num_regions, 2);
+        // The image region and one "bit is one" region per (zero) threshold
+        assert_eq!(chip("EncodeImageChip").num_regions, 1 + 24);
+        assert!(chip("EncodeImageChip").gates.is_empty());
+
+        let table_names: Vec<_> = graph.tables.iter().map(|table| &table.name).collect();
+        assert!(table_names.contains(&&"byte table".to_string()));
+        assert!(table_names.contains(&&"bloom_filters".to_string()));
+        assert!(graph.tables.iter().all(|table| table
+            .columns
+            .iter()
+            .all(|c| matches!(c, ColumnId::Fixed(_)))));
+
+        // The first advice column is used by most chips
+        assert!(graph.shared_columns()[&ColumnId::Advice(0)].len() > 2);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = graph().to_dot();
+        assert!(dot.starts_with("digraph circuit {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("\"chip HashChip\" -> \"advice 0\";"));
+        assert!(dot.contains("[shape=cylinder, label=\"byte table\"];"));
+        assert!(dot.contains("style=dashed"));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("a \"b\"\nc\\"), "\"a \\\"b\\\"\\nc\\\\\"");
+    }
+}
@@ -0,0 +1,403 @@
+//! An abstraction over model families whose inference can be proven, see
+//! [`ProvableClassifier`].
+//!
+//! Entry points such as [`crate::prover::Prover`] are generic over this trait, so that new
+//! model families (e.g. other weightless architectures or ensembles) can reuse them. Currently,
+//! [`Wnn`] is the only implementation.
+
+use std::sync::Arc;
+
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr as Fp, G1Affine},
+    plonk::{ProvingKey, VerifyingKey},
+    poly::kzg::commitment::ParamsKZG,
+};
+use ndarray::{Array1, Array2};
+
+use crate::error::ZeroGError;
+use crate::gadgets::wnn::WnnSynthesisCache;
+use crate::proof_file::ProofFile;
+use crate::verification::{verify_with_key, VerificationError};
+use crate::verifier_bundle::{check_proof_metadata, vk_fingerprint};
+use crate::wnn::Wnn;
+
+/// A classifier whose predictions can be proven with a KZG-based Halo2 circuit.
+pub trait ProvableClassifier: Send + Sync {
+    /// The input of the model, e.g. a grayscale image.
+    type Input;
+
+    /// Image-independent data used to synthesize the circuit (e.g. the lookup tables), which a
+    /// long-running prover computes once and reuses for every proof.
+    type SynthesisCache: Send + Sync;
+
+    fn num_classes(&self) -> usize;
+
+    /// A commitment to all model parameters, which is the same for two models if and only if
+    /// they lead to the same circuit and predictions.
+    fn commitment(&self) -> [u8; 32];
+
+    /// Checks that the input is accepted by the model (e.g. has the right shape), without
+    /// proving.
+    fn check_input(&self, input: &Self::Input) -> Result<(), ZeroGError>;
+
+    /// The score of each class.
+    fn predict(&self, input: &Self::Input) -> Vec<u64>;
+
+    fn generate_proving_key(
+        &self,
+        kzg_params: &ParamsKZG<Bn256>,
+    ) -> Result<ProvingKey<G1Affine>, ZeroGError>;
+
+    /// A fingerprint of the verification key together with everything else needed to
+    /// deserialize it, see [`vk_fingerprint`].
+    fn vk_fingerprint(&self, vk: &VerifyingKey<G1Affine>) -> [u8; 32];
+
+    /// Computes the data reused by [`ProvableClassifier::prove_with_cache`].
+    fn synthesis_cache(&self) -> Self::SynthesisCache;
+
+    /// Proves inference of the input. The proof file should record enough metadata to detect
+    /// proofs for a different circuit.
+    fn prove(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        input: &Self::Input,
+    ) -> Result<ProofFile, ZeroGError> {
+        self.prove_with_cache(pk, kzg_params, &self.synthesis_cache(), input)
+    }
+
+    /// Like [`ProvableClassifier::prove`], with data computed by
+    /// [`ProvableClassifier::synthesis_cache`].
+    fn prove_with_cache(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        synthesis_cache: &Self::SynthesisCache,
+        input: &Self::Input,
+    ) -> Result<ProofFile, ZeroGError>;
+
+    /// Verifies a proof of this model, including the metadata recorded by
+    /// [`ProvableClassifier::prove`].
+    fn verify(
+        &self,
+        vk: &VerifyingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        proof: &ProofFile,
+    ) -> Result<(), VerificationError>;
+}
+
+/// The input of a [`Wnn`]: an image, or the feature vector of a tabular model (see
+/// [`Wnn::new_tabular`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WnnInput {
+    Image(Array2<u8>),
+    Features(Array1<u16>),
+}
+
+impl From<Array2<u8>> for WnnInput {
+    fn from(image: Array2<u8>) -> Self {
+        Self::Image(image)
+    }
+}
+
+impl From<Array1<u16>> for WnnInput {
+    fn from(features: Array1<u16>) -> Self {
+        Self::Features(features)
+    }
+}
+
+impl Wnn {
+    /// Wraps a proof in a proof file with all metadata checked by
+    /// [`ProvableClassifier::verify`].
+    fn proof_file(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        (proof, outputs): (Vec<u8>, Vec<Fp>),
+    ) -> ProofFile {
+        ProofFile::new(proof, outputs)
+            .with_circuit_params(self.get_circuit_params())
+            .with_vk_fingerprint(ProvableClassifier::vk_fingerprint(self, pk.get_vk()))
+            .with_model_commitment(Wnn::commitment(self))
+    }
+}
+
+impl ProvableClassifier for Wnn {
+    type Input = WnnInput;
+    type SynthesisCache = Arc<WnnSynthesisCache<Fp>>;
+
+    fn num_classes(&self) -> usize {
+        self.num_classes
+    }
+
+    fn commitment(&self) -> [u8; 32] {
+        Wnn::commitment(self)
+    }
+
+    fn check_input(&self, input: &WnnInput) -> Result<(), ZeroGError> {
+        match input {
+            WnnInput::Image(_) if self.is_tabular() => Err(ZeroGError::InvalidInput(
+                "The model takes feature vectors, got an image".to_string(),
+            )),
+            WnnInput::Features(_) if !self.is_tabular() => Err(ZeroGError::InvalidInput(
+                "The model takes images, got a feature vector".to_string(),
+            )),
+            WnnInput::Image(image) => {
+                let expected = self.img_shape();
+                if image.dim() != expected {
+                    return Err(ZeroGError::ImageShape {
+                        expected,
+                        actual: image.dim(),
+                    });
+                }
+                Ok(())
+            }
+            WnnInput::Features(features) => {
+                if features.len() != self.num_features() {
+                    return Err(ZeroGError::InvalidInput(format!(
+                        "Expected {} features, got {}",
+                        self.num_features(),
+                        features.len()
+                    )));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn predict(&self, input: &WnnInput) -> Vec<u64> {
+        match input {
+            WnnInput::Image(image) => Wnn::predict(self, image),
+            WnnInput::Features(features) => self.predict_features(features),
+        }
+    }
+
+    fn generate_proving_key(
+        &self,
+        kzg_params: &ParamsKZG<Bn256>,
+    ) -> Result<ProvingKey<G1Affine>, ZeroGError> {
+        Wnn::generate_proving_key(self, kzg_params)
+    }
+
+    fn vk_fingerprint(&self, vk: &VerifyingKey<G1Affine>) -> [u8; 32] {
+        vk_fingerprint(vk, &self.get_circuit_params())
+    }
+
+    fn synthesis_cache(&self) -> Self::SynthesisCache {
+        Wnn::synthesis_cache(self)
+    }
+
+    fn prove(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        input: &WnnInput,
+    ) -> Result<ProofFile, ZeroGError> {
+        let proof = match input {
+            WnnInput::Image(image) => self.proof(pk, kzg_params, image)?,
+            WnnInput::Features(features) => self.features_proof(pk, kzg_params, features)?,
+        };
+        Ok(self.proof_file(pk, proof))
+    }
+
+    fn prove_with_cache(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        synthesis_cache: &Self::SynthesisCache,
+        input: &WnnInput,
+    ) -> Result<ProofFile, ZeroGError> {
+        let proof = match input {
+            WnnInput::Image(image) => {
+                self.proof_with_cache(pk, kzg_params, image, synthesis_cache)?
+            }
+            WnnInput::Features(features) => {
+                self.features_proof_with_cache(pk, kzg_params, features, synthesis_cache)?
+            }
+        };
+        Ok(self.proof_file(pk, proof))
+    }
+
+    fn verify(
+        &self,
+        vk: &VerifyingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        proof: &ProofFile,
+    ) -> Result<(), VerificationError> {
+        let circuit_params = self.get_circuit_params();
+        check_proof_metadata(
+            proof,
+            &circuit_params,
+            vk_fingerprint(vk, &circuit_params),
+            Wnn::commitment(self),
+        )?;
+        verify_with_key(
+            &proof.proof,
+            &proof.public_inputs,
+            vk,
+            kzg_params,
+            &circuit_params,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array3};
+
+    use super::{ProvableClassifier, WnnInput};
+    use crate::error::ZeroGError;
+    use crate::wnn::Wnn;
+
+    #[test]
+    fn test_tabular_input() {
+        let wnn = Wnn::new_tabular(
+            2,
+            1024,
+            2,
+            6,
+            2097143,
+            Array3::from_elem((2, 1, 1024), false),
+            (0..6u64).rev().collect(),
+            array![[0, 1000, 2000], [10000, 40000, 50000]],
+        );
+        let features = WnnInput::from(array![1000u16, 65535]);
+        assert!(wnn.check_input(&features).is_ok());
+        assert_eq!(
+            ProvableClassifier::predict(&wnn, &features),
+            wnn.predict_features(&array![1000, 65535])
+        );
+
+        assert!(matches!(
+            wnn.check_input(&array![1000u16].into()),
+            Err(ZeroGError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            wnn.check_input(&array![[0u8, 0]].into()),
+            Err(ZeroGError::InvalidInput(_))
+        ));
+    }
+
+    #[cfg(feature = "hdf5")]
+    #[test]
+    fn test_other_classifier() {
+        use std::path::Path;
+
+        use halo2_proofs::{
+            halo2curves::bn256::{Bn256, G1Affine},
+            plonk::{ProvingKey, VerifyingKey},
+            poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
+        };
+        use ndarray::Array2;
+
+        use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+        use crate::proof_file::{ProofFile, ProofMetadata};
+        use crate::prover::Prover;
+        use crate::verification::VerificationError;
+        use crate::{load_grayscale_image, load_wnn};
+
+        /// Takes images as row-major pixel vectors, to check that the entry points work for
+        /// implementations other than [`Wnn`].
+        struct FlatImageClassifier(Wnn);
+
+        impl FlatImageClassifier {
+            fn image(&self, pixels: &[u8]) -> Array2<u8> {
+                Array2::from_shape_vec(self.0.img_shape(), pixels.to_vec()).unwrap()
+            }
+        }
+
+        impl ProvableClassifier for FlatImageClassifier {
+            type Input = Vec<u8>;
+            type SynthesisCache = <Wnn as ProvableClassifier>::SynthesisCache;
+
+            fn num_classes(&self) -> usize {
+                self.0.num_classes
+            }
+
+            fn commitment(&self) -> [u8; 32] {
+                self.0.commitment()
+            }
+
+            fn check_input(&self, input: &Vec<u8>) -> Result<(), ZeroGError> {
+                let (rows, columns) = self.0.img_shape();
+                if input.len() != rows * columns {
+                    return Err(ZeroGError::InvalidInput(format!(
+                        "Expected {} pixels, got {}",
+                        rows * columns,
+                        input.len()
+                    )));
+                }
+                Ok(())
+            }
+
+            fn predict(&self, input: &Vec<u8>) -> Vec<u64> {
+                self.0.predict(&self.image(input))
+            }
+
+            fn generate_proving_key(
+                &self,
+                kzg_params: &ParamsKZG<Bn256>,
+            ) -> Result<ProvingKey<G1Affine>, ZeroGError> {
+                self.0.generate_proving_key(kzg_params)
+            }
+
+            fn vk_fingerprint(&self, vk: &VerifyingKey<G1Affine>) -> [u8; 32] {
+                ProvableClassifier::vk_fingerprint(&self.0, vk)
+            }
+
+            fn synthesis_cache(&self) -> Self::SynthesisCache {
+                self.0.synthesis_cache()
+            }
+
+            fn prove_with_cache(
+                &self,
+                pk: &ProvingKey<G1Affine>,
+                kzg_params: &ParamsKZG<Bn256>,
+                synthesis_cache: &Self::SynthesisCache,
+                input: &Vec<u8>,
+            ) -> Result<ProofFile, ZeroGError> {
+                let input = WnnInput::Image(self.image(input));
+                self.0
+                    .prove_with_cache(pk, kzg_params, synthesis_cache, &input)
+            }
+
+            fn verify(
+                &self,
+                vk: &VerifyingKey<G1Affine>,
+                kzg_params: &ParamsKZG<Bn256>,
+                proof: &ProofFile,
+            ) -> Result<(), VerificationError> {
+                ProvableClassifier::verify(&self.0, vk, kzg_params, proof)
+            }
+        }
+
+        let (k, model_path) = MNIST_TINY;
+        let classifier = FlatImageClassifier(load_wnn(Path::new(model_path)).unwrap());
+        let kzg_params = ParamsKZG::<Bn256>::new(k);
+        let pk = classifier.generate_proving_key(&kzg_params).unwrap();
+        let vk = pk.get_vk().clone();
+        let prover = Prover::new(classifier, kzg_params.clone(), pk);
+
+        let image = load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap();
+        let pixels: Vec<_> = image.iter().copied().collect();
+        assert!(matches!(
+            prover.check_input(&pixels[1..].to_vec()),
+            Err(ZeroGError::InvalidInput(_))
+        ));
+        let proof = prover.prove(&pixels).unwrap();
+        let model = prover.model();
+        assert!(model.verify(&vk, &kzg_params, &proof).is_ok());
+
+        let mut other_key = proof.clone();
+        other_key.metadata.vk_fingerprint = Some([0; 32]);
+        assert!(matches!(
+            model.verify(&vk, &kzg_params, &other_key),
+            Err(VerificationError::VkFingerprintMismatch { .. })
+        ));
+        let mut without_metadata = proof;
+        without_metadata.metadata = ProofMetadata::default();
+        assert!(matches!(
+            model.verify(&vk, &kzg_params, &without_metadata),
+            Err(VerificationError::MissingMetadata(_))
+        ));
+    }
+}
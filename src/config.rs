@@ -0,0 +1,181 @@
+//! Project files (`zero_g.toml`), so that paths and parameters don't have to be repeated on
+//! every invocation of the command-line tool and configurations are reproducible.
+//!
+//! Example:
+//! ```toml
+//! model = "models/model_28input_256entry_1hash_1bpi.hdf5"
+//! # Written by `zero_g setup`, see `zero_g::setup`
+//! artifact_dir = "artifacts"
+//! k = 14
+//! commitment = "random-salt"
+//!
+//! # Optional: Fail if the model implies different circuit params
+//! [circuit_params]
+//! p = 2097143
+//! l = 21
+//! n_hashes = 1
+//! bits_per_hash = 8
+//! bits_per_filter = 28
+//! n_classes = 10
+//!
+//! [datasets]
+//! test_set = "data/MNIST/png"
+//! ```
+//!
+//! Relative paths are resolved against the directory of the project file.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ZeroGError;
+use crate::gadgets::wnn::WnnCircuitParams;
+use crate::setup::{SetupFiles, SetupManifest};
+use crate::wnn::Wnn;
+
+/// The name of the project file, which is looked up in the working directory.
+pub const CONFIG_FILE_NAME: &str = "zero_g.toml";
+
+/// How images are committed to (see [`crate::image_commitment`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CommitmentMode {
+    /// Commit to the image without a salt.
+    #[default]
+    Unsalted,
+    /// Commit to the image with a random salt.
+    RandomSalt,
+}
+
+/// Locations of datasets.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatasetConfig {
+    /// The test set, see [`crate::datasets::Dataset::open`].
+    pub test_set: Option<PathBuf>,
+    /// Labels of the test set, see [`crate::labels::LabelSource::read_sidecar`].
+    pub labels: Option<PathBuf>,
+}
+
+/// The content of a project file, see the module documentation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Path to the model, in HDF5 or .zgm format.
+    pub model: Option<PathBuf>,
+    /// The artifact directory written by [`crate::setup::setup`].
+    pub artifact_dir: Option<PathBuf>,
+    /// The value `k` used for the powers of tau.
+    pub k: Option<u32>,
+    /// The expected circuit params of the model, see [`ProjectConfig::check_circuit_params`].
+    pub circuit_params: Option<WnnCircuitParams>,
+    pub commitment: CommitmentMode,
+    pub datasets: DatasetConfig,
+}
+
+impl ProjectConfig {
+    /// Reads a project file, resolving relative paths against its directory.
+    pub fn read(path: &Path) -> Result<Self, ZeroGError> {
+        let content = fs::read_to_string(path).map_err(|source| ZeroGError::Io {
+            action: "read",
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut config: Self = toml::from_str(&content).map_err(|source| ZeroGError::Format {
+            path: path.to_path_buf(),
+            format: "project file",
+            source: Box::new(source),
+        })?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for path in [
+            &mut config.model,
+            &mut config.artifact_dir,
+            &mut config.datasets.test_set,
+            &mut config.datasets.labels,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = base.join(&*path);
+        }
+        Ok(config)
+    }
+
+    /// Reads [`CONFIG_FILE_NAME`] from `dir`, if it exists.
+    pub fn find(dir: &Path) -> Result<Option<Self>, ZeroGError> {
+        let path = dir.join(CONFIG_FILE_NAME);
+        if path.exists() {
+            Self::read(&path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Checks that the model implies the circuit params of the project file, if given.
+    pub fn check_circuit_params(&self, wnn: &Wnn) -> Result<(), ZeroGError> {
+        match &self.circuit_params {
+            Some(expected) if *expected != wnn.get_circuit_params() => {
+                Err(ZeroGError::InvalidModel(format!(
+                    "The model implies the circuit params {:?}, but the project file expects {:?}",
+                    wnn.get_circuit_params(),
+                    expected
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The path of an artifact in the artifact directory, as listed in its manifest.
+    /// Returns `None` if there is no artifact directory.
+    pub fn artifact_path(
+        &self,
+        file: impl FnOnce(&SetupFiles) -> &String,
+    ) -> Result<Option<PathBuf>, ZeroGError> {
+        match &self.artifact_dir {
+            Some(dir) => {
+                let manifest = SetupManifest::read(dir)?;
+                Ok(Some(dir.join(file(&manifest.files))))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::Path, process};
+
+    use super::{CommitmentMode, ProjectConfig};
+
+    #[test]
+    fn test_read_config() {
+        let dir = env::temp_dir().join(format!("zero_g_config_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("zero_g.toml");
+        fs::write(
+            &path,
+            "model = \"model.zgm\"\nk = 14\ncommitment = \"random-salt\"\n\n\
+             [datasets]\ntest_set = \"/data/MNIST/png\"\n",
+        )
+        .unwrap();
+
+        let config = ProjectConfig::read(&path).unwrap();
+        let found = ProjectConfig::find(&dir).unwrap();
+        fs::write(&path, "modle = \"model.zgm\"\n").unwrap();
+        let typo = ProjectConfig::read(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(config.model, Some(dir.join("model.zgm")));
+        assert_eq!(config.k, Some(14));
+        assert_eq!(config.artifact_dir, None);
+        assert_eq!(config.commitment, CommitmentMode::RandomSalt);
+        assert_eq!(
+            config.datasets.test_set.as_deref(),
+            Some(Path::new("/data/MNIST/png"))
+        );
+        assert_eq!(found, Some(config));
+        assert!(typo.is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Checks that a model, circuit params and keys belong together, before an expensive
+//! proving attempt fails with an opaque constraint error.
+
+use std::fmt;
+
+use halo2_proofs::{
+    dev::MockProver,
+    halo2curves::bn256::{Bn256, G1Affine},
+    plonk::{Error, VerifyingKey},
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+};
+use ndarray::Array2;
+
+use crate::backend::{DefaultBackend, ProvingBackend};
+use crate::error::ZeroGError;
+use crate::gadgets::wnn::WnnCircuitParams;
+use crate::verifier_bundle::vk_fingerprint;
+use crate::wnn::Wnn;
+
+/// A mismatch found by [`check_consistency`] or [`check_key_matches_model`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// A circuit parameter differs from the value derived from the model.
+    CircuitParam {
+        name: &'static str,
+        model: u64,
+        params: u64,
+    },
+    /// The circuit does not fit into `2^k` rows, where `k` is the size of the verifying key.
+    KTooSmall { k: u32 },
+    /// The verifying key was generated for a different model (e.g. before retraining).
+    StaleKey,
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CircuitParam {
+                name,
+                model,
+                params,
+            } => write!(
+                f,
+                "Circuit params have {name} = {params}, but the model requires {name} = {model}"
+            ),
+            Self::KTooSmall { k } => write!(
+                f,
+                "The circuit does not fit into 2^{k} rows, the key has to be generated for a larger k"
+            ),
+            Self::StaleKey => write!(
+                f,
+                "The verifying key does not match the model (was the model retrained?)"
+            ),
+        }
+    }
+}
+
+/// Checks that the circuit params match the model and that the circuit fits into the
+/// size of the verifying key. Returns all mismatches found.
+///
+/// This does not detect a key that was generated for a different model with the same
+/// parameters, see [`check_key_matches_model`].
+pub fn check_consistency(
+    wnn: &Wnn,
+    circuit_params: &WnnCircuitParams,
+    vk: &VerifyingKey<G1Affine>,
+) -> Result<Vec<Inconsistency>, ZeroGError> {
+    let expected = wnn.get_circuit_params();
+    let mut inconsistencies: Vec<_> = [
+        ("p", expected.p, circuit_params.p),
+        ("l", expected.l as u64, circuit_params.l as u64),
+        (
+            "n_hashes",
+            expected.n_hashes as u64,
+            circuit_params.n_hashes as u64,
+        ),
+        (
+            "bits_per_hash",
+            expected.bits_per_hash as u64,
+            circuit_params.bits_per_hash as u64,
+        ),
+        (
+            "bits_per_filter",
+            expected.bits_per_filter as u64,
+            circuit_params.bits_per_filter as u64,
+        ),
+        (
+            "n_classes",
+            expected.n_classes as u64,
+            circuit_params.n_classes as u64,
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, model, params)| model != params)
+    .map(|(name, model, params)| Inconsistency::CircuitParam {
+        name,
+        model,
+        params,
+    })
+    .collect();
+
+    let k = vk.get_domain().k();
+    let image = Array2::zeros(wnn.img_shape());
+    match MockProver::run(k, &wnn.get_circuit(&image), wnn.public_inputs(&image)) {
+        Ok(_) => {}
+        Err(Error::NotEnoughRowsAvailable { .. }) => {
+            inconsistencies.push(Inconsistency::KTooSmall { k })
+        }
+        Err(source) => {
+            return Err(ZeroGError::Plonk {
+                action: "Synthesizing the circuit",
+                source,
+            })
+        }
+    }
+
+    Ok(inconsistencies)
+}
+
+/// Regenerates the verifying key for the model and compares it to the given key.
+///
+/// This is more expensive than [`check_consistency`], but also detects stale keys,
+/// e.g. after retraining a model without changing its parameters.
+pub fn check_key_matches_model(
+    wnn: &Wnn,
+    vk: &VerifyingKey<G1Affine>,
+    kzg_params: &ParamsKZG<Bn256>,
+) -> Result<Option<Inconsistency>, ZeroGError> {
+    let k = vk.get_domain().k();
+    if kzg_params.k() < k {
+        return Err(ZeroGError::Plonk {
+            action: "Regenerating the verifying key",
+            source: Error::NotEnoughRowsAvailable {
+                current_k: kzg_params.k(),
+            },
+        });
+    }
+    let mut kzg_params = kzg_params.clone();
+    kzg_params.downsize(k);
+
+    let circuit = wnn.get_circuit(&Array2::zeros(wnn.img_shape()));
+    let expected =
+        DefaultBackend::keygen_vk(&kzg_params, &circuit).map_err(|source| ZeroGError::Plonk {
+            action: "Regenerating the verifying key",
+            source,
+        })?;
+
+    let circuit_params = wnn.get_circuit_params();
+    Ok(
+        (vk_fingerprint(vk, &circuit_params) != vk_fingerprint(&expected, &circuit_params))
+            .then_some(Inconsistency::StaleKey),
+    )
+}
+
+#[cfg(all(test, feature = "hdf5"))]
+mod tests {
+    use std::path::Path;
+
+    use halo2_proofs::{
+        halo2curves::bn256::Bn256,
+        poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
+    };
+
+    use super::{check_consistency, Inconsistency};
+    use crate::checked_in_test_data::MNIST_TINY;
+    use crate::gadgets::wnn::WnnCircuitParams;
+    use crate::load_wnn;
+
+    #[test]
+    fn test_check_consistency() {
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let kzg_params = ParamsKZG::<Bn256>::new(k);
+        let pk = wnn.generate_proving_key(&kzg_params).unwrap();
+
+        let circuit_params = wnn.get_circuit_params();
+        assert_eq!(
+            check_consistency(&wnn, &circuit_params, pk.get_vk()).unwrap(),
+            vec![]
+        );
+
+        let wrong_params = WnnCircuitParams {
+            bits_per_hash: circuit_params.bits_per_hash + 1,
+            ..circuit_params
+        };
+        assert_eq!(
+            check_consistency(&wnn, &wrong_params, pk.get_vk()).unwrap(),
+            vec![Inconsistency::CircuitParam {
+                name: "bits_per_hash",
+                model: 8,
+                params: 9,
+            }]
+        );
+    }
+}
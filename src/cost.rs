@@ -0,0 +1,430 @@
+//! Estimates of the resources needed to prove inference for a model, so that hardware and
+//! the size of the SRS can be planned before generating keys.
+//!
+//! The minimal `k` is determined by laying out the circuit once (which doesn't depend on `k`,
+//! see [`used_rows`]) and confirmed with the [`MockProver`]. The same layout yields the rows
+//! used by each chip. Key and proof sizes follow from the shape of the constraint system. The
+//! proving time is extrapolated from a multi-scalar multiplication benchmarked on the current
+//! machine, as these dominate proving; the estimate is therefore only a rough lower bound.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use ff::Field;
+use group::{Curve, Group};
+use halo2_proofs::{
+    arithmetic::best_multiexp,
+    dev::MockProver,
+    halo2curves::bn256::{Fr as Fp, G1Affine, G1},
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+use ndarray::Array2;
+use rand_core::OsRng;
+use serde::Serialize;
+
+use crate::error::ZeroGError;
+use crate::gadgets::byte_table::DEFAULT_WINDOW_NUM_BITS;
+use crate::gadgets::WnnCircuit;
+use crate::region_layout::{region_rows, used_rows};
+use crate::testing::chip_for_region;
+use crate::wnn::Wnn;
+
+/// The smallest `k` that is tried.
+/// The byte table of the range checks alone needs 256 rows (plus blinding rows).
+pub(crate) const MIN_K: u32 = 9;
+/// The largest `k` that is tried, which is also the size of the largest available
+/// powers of tau ceremony for BN254.
+const MAX_K: u32 = 28;
+/// Size (in bytes) of a field element and of a (uncompressed) point, as written by the EVM transcript.
+const SCALAR_SIZE: usize = 32;
+const POINT_SIZE: usize = 64;
+/// The largest MSM that is benchmarked; larger ones are extrapolated linearly.
+const MAX_BENCHMARK_K: u32 = 14;
+
+/// Estimated costs of proving inference for a model.
+#[derive(Debug, Clone, Serialize)]
+pub struct CostEstimate {
+    /// The minimal `k`, i.e. the circuit fits into `2^k` rows.
+    pub k: u32,
+    /// The number of times each chip is invoked for a single proof.
+    pub calls_per_chip: Vec<(&'static str, usize)>,
+    /// The number of rows assigned by the regions of each chip (or table), sorted by name.
+    /// Regions of different chips may share rows if they use different columns.
+    pub rows_per_chip: Vec<(String, usize)>,
+    pub num_advice_columns: usize,
+    pub num_fixed_columns: usize,
+    pub num_instance_columns: usize,
+    pub num_selectors: usize,
+    pub num_lookups: usize,
+    /// The maximum degree of all constraints.
+    pub degree: usize,
+    /// Size of the serialized proving key (in bytes).
+    pub proving_key_size: usize,
+    /// Size of a proof (in bytes).
+    pub proof_size: usize,
+    /// Rough lower bound on the proving time on this machine.
+    pub proving_time: Duration,
+}
+
+/// Estimates the costs of proving inference for the given model.
+pub fn estimate(wnn: &Wnn) -> Result<CostEstimate, ZeroGError> {
+    let k = minimal_k(wnn)?;
+
+    let mut cs = ConstraintSystem::<Fp>::default();
+    WnnCircuit::<Fp>::configure_with_params(&mut cs, wnn.get_circuit_params());
+
+    let num_advice_columns = cs.num_advice_columns();
+    let num_fixed_columns = cs.num_fixed_columns();
+    let num_selectors = cs.num_selectors();
+    let num_lookups = cs.lookups().len();
+    let num_permutation_columns = cs.permutation().get_columns().len();
+    let degree = cs.degree();
+
+    // The quotient polynomial is split into `degree - 1` pieces and the permutation
+    // argument into products of `degree - 2` columns each.
+    let quotient_pieces = degree - 1;
+    let chunk_len = (degree - 2).max(1);
+    let permutation_products = (num_permutation_columns + chunk_len - 1) / chunk_len;
+    // Rough number of distinct sets of rotations (e.g. {cur}, {cur, next}, {cur, next, last})
+    let point_sets = 4;
+
+    let commitments = num_advice_columns
+        + 3 * num_lookups
+        + permutation_products
+        + quotient_pieces
+        + 1 // Random polynomial of the vanishing argument
+        + point_sets;
+    let evaluations = cs.advice_queries().len()
+        + cs.fixed_queries().len()
+        + 1 // Random polynomial of the vanishing argument
+        + num_permutation_columns
+        + 3 * permutation_products
+        - 1 // The last product is not evaluated at the last row
+        + 5 * num_lookups;
+    let proof_size = commitments * POINT_SIZE + evaluations * SCALAR_SIZE;
+
+    // The proving key contains all fixed columns (selectors are turned into fixed columns)
+    // and the permutation columns in Lagrange and coefficient form, and on the extended domain.
+    let n = 1usize << k;
+    let extended_n = n * quotient_pieces.next_power_of_two();
+    let columns = num_fixed_columns + num_selectors + num_permutation_columns;
+    let proving_key_size = SCALAR_SIZE * (columns * (2 * n + extended_n) + 3 * extended_n);
+
+    let msms = commitments - point_sets + 1;
+    let proving_time = benchmark_msm(k) * msms as u32;
+
+    Ok(CostEstimate {
+        k,
+        calls_per_chip: calls_per_chip(wnn),
+        rows_per_chip: rows_per_chip(wnn)?,
+        num_advice_columns,
+        num_fixed_columns,
+        num_instance_columns: cs.num_instance_columns(),
+        num_selectors,
+        num_lookups,
+        degree,
+        proving_key_size,
+        proof_size,
+        proving_time,
+    })
+}
+
+/// Finds the smallest `k` for which the circuit of the model can be synthesized.
+///
+/// The number of rows is computed from the layout of the circuit, plus the rows reserved for
+/// blinding. The result is confirmed with the [`MockProver`], trying the next `k` only if
+/// the layout underestimates the rows.
+pub fn minimal_k(wnn: &Wnn) -> Result<u32, ZeroGError> {
+    let to_error = |source| ZeroGError::Plonk {
+        action: "Synthesizing the circuit",
+        source,
+    };
+    let image = Array2::zeros(wnn.img_shape());
+    let circuit = wnn.get_circuit(&image);
+    let instances = wnn.public_inputs(&image);
+
+    let mut cs = ConstraintSystem::<Fp>::default();
+    WnnCircuit::<Fp>::configure_with_params(&mut cs, wnn.get_circuit_params());
+    let instance_rows = instances.iter().map(Vec::len).max().unwrap_or(0);
+    let rows = used_rows(&circuit).map_err(to_error)?.max(instance_rows);
+    let lower_bound = (rows + cs.blinding_factors() + 1)
+        .next_power_of_two()
+        .trailing_zeros()
+        .max(MIN_K);
+
+    for k in lower_bound..=MAX_K {
+        match MockProver::run(k, &circuit, instances.clone()) {
+            Ok(_) => return Ok(k),
+            Err(Error::NotEnoughRowsAvailable { .. }) => continue,
+            Err(source) => return Err(to_error(source)),
+        }
+    }
+    Err(to_error(Error::NotEnoughRowsAvailable { current_k: MAX_K }))
+}
+
+/// Sums the rows of the regions of each chip, see [`CostEstimate::rows_per_chip`].
+fn rows_per_chip(wnn: &Wnn) -> Result<Vec<(String, usize)>, ZeroGError> {
+    let circuit = wnn
+        .get_circuit(&Array2::zeros(wnn.img_shape()))
+        .with_region_annotations();
+    let regions = region_rows(&circuit).map_err(|source| ZeroGError::Plonk {
+        action: "Synthesizing the circuit",
+        source,
+    })?;
+    let mut rows: BTreeMap<String, usize> = BTreeMap::new();
+    for region in regions {
+        let chip = chip_for_region(&region.name).map_or(region.name.clone(), str::to_string);
+        *rows.entry(chip).or_default() += region.rows.map_or(0, |rows| rows.len());
+    }
+    Ok(rows.into_iter().collect())
+}
+
+/// Counts the gadget calls of [`crate::gadgets::WnnChip::predict`].
+fn calls_per_chip(wnn: &Wnn) -> Vec<(&'static str, usize)> {
+    let num_bits = wnn.binarization_thresholds.len();
+    let num_filters = num_bits / wnn.num_filter_inputs;
+    let num_lookups = wnn.num_classes * num_filters;
+    vec![
+        ("EncodeImageChip", num_bits),
+        ("Bits2NumChip", num_filters),
+        ("HashChip", num_filters),
+        ("BloomFilterChip", num_lookups),
+        ("ResponseAccumulatorChip", wnn.num_classes),
+    ]
+}
+
+/// What [`suggest_params`] minimizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Objective {
+    /// The minimal `k` (i.e. the size of the SRS and the keys).
+    K,
+    /// The estimated proving time, see [`CostEstimate::proving_time`].
+    ProvingTime,
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "k" => Ok(Self::K),
+            "proving-time" => Ok(Self::ProvingTime),
+            _ => Err(format!(
+                "Unknown objective {s:?}, expected k or proving-time"
+            )),
+        }
+    }
+}
+
+/// The circuit parameters searched by [`suggest_params`], see [`Wnn::with_window_num_bits`],
+/// [`Wnn::with_class_lookup`] and [`Wnn::with_num_instance_columns`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CircuitKnobs {
+    pub window_num_bits: usize,
+    pub class_lookup: bool,
+    pub num_instance_columns: usize,
+}
+
+impl CircuitKnobs {
+    /// Returns a copy of the model with these parameters.
+    pub fn apply(&self, wnn: &Wnn) -> Wnn {
+        wnn.clone()
+            .with_window_num_bits(self.window_num_bits)
+            .with_class_lookup(self.class_lookup)
+            .with_num_instance_columns(self.num_instance_columns)
+    }
+}
+
+/// A configuration tried by [`suggest_params`], with its costs.
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub knobs: CircuitKnobs,
+    pub k: u32,
+    /// Only estimated for [`Objective::ProvingTime`].
+    pub proving_time: Option<Duration>,
+}
+
+/// The values of [`CircuitKnobs::window_num_bits`] tried by [`suggest_params`].
+const WINDOW_NUM_BITS: [usize; 5] = [DEFAULT_WINDOW_NUM_BITS, 4, 6, 10, 12];
+/// The values of [`CircuitKnobs::num_instance_columns`] tried by [`suggest_params`].
+const NUM_INSTANCE_COLUMNS: [usize; 3] = [1, 2, 4];
+
+/// Tries all combinations of the circuit parameters of [`CircuitKnobs`] that are valid for the
+/// model, and returns them ordered by the objective, best first. Ties are broken by the
+/// proving time (if estimated), then in favor of the default parameters.
+///
+/// Each configuration is laid out and checked with the [`MockProver`] (see [`minimal_k`]),
+/// so this takes a while for large models.
+pub fn suggest_params(wnn: &Wnn, objective: Objective) -> Result<Vec<Suggestion>, ZeroGError> {
+    let mut suggestions = vec![];
+    for class_lookup in [wnn.class_lookup, !wnn.class_lookup] {
+        for window_num_bits in WINDOW_NUM_BITS {
+            for num_instance_columns in NUM_INSTANCE_COLUMNS {
+                let knobs = CircuitKnobs {
+                    window_num_bits,
+                    class_lookup,
+                    num_instance_columns,
+                };
+                let candidate = knobs.apply(wnn);
+                if candidate.validate().is_err() {
+                    continue;
+                }
+                let (k, proving_time) = match objective {
+                    Objective::K => (minimal_k(&candidate)?, None),
+                    Objective::ProvingTime => {
+                        let estimate = estimate(&candidate)?;
+                        (estimate.k, Some(estimate.proving_time))
+                    }
+                };
+                suggestions.push(Suggestion {
+                    knobs,
+                    k,
+                    proving_time,
+                });
+            }
+        }
+    }
+    // The sort is stable, so the default parameters (tried first) win ties
+    match objective {
+        Objective::K => suggestions.sort_by_key(|s| s.k),
+        Objective::ProvingTime => suggestions.sort_by_key(|s| (s.proving_time, s.k)),
+    }
+    Ok(suggestions)
+}
+
+/// Measures the time of a multi-scalar multiplication of size `2^k`.
+/// Sizes above `2^MAX_BENCHMARK_K` are extrapolated linearly.
+fn benchmark_msm(k: u32) -> Duration {
+    let benchmark_k = k.min(MAX_BENCHMARK_K);
+    let n = 1 << benchmark_k;
+
+    let mut points = Vec::with_capacity(n);
+    let mut point = G1::generator();
+    for _ in 0..n {
+        points.push(point);
+        point += G1::generator();
+    }
+    let mut bases = vec![G1Affine::default(); n];
+    G1::batch_normalize(&points, &mut bases);
+    let scalars: Vec<_> = (0..n).map(|_| Fp::random(OsRng)).collect();
+
+    let start = Instant::now();
+    std::hint::black_box(best_multiexp(&scalars, &bases));
+    start.elapsed() * (1 << (k - benchmark_k))
+}
+
+impl fmt::Display for CostEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Minimal k: {} ({} rows)", self.k, 1u64 << self.k)?;
+        writeln!(f, "\nCalls per chip:")?;
+        for (chip, calls) in &self.calls_per_chip {
+            writeln!(f, "  {chip:<24} {calls:>10}")?;
+        }
+        writeln!(f, "\nRows per chip:")?;
+        for (chip, rows) in &self.rows_per_chip {
+            writeln!(f, "  {chip:<24} {rows:>10}")?;
+        }
+        writeln!(f, "\nColumns:")?;
+        writeln!(f, "  {:<24} {:>10}", "Advice", self.num_advice_columns)?;
+        writeln!(f, "  {:<24} {:>10}", "Fixed", self.num_fixed_columns)?;
+        writeln!(f, "  {:<24} {:>10}", "Instance", self.num_instance_columns)?;
+        writeln!(f, "  {:<24} {:>10}", "Selectors", self.num_selectors)?;
+        writeln!(f, "  {:<24} {:>10}", "Lookups", self.num_lookups)?;
+        writeln!(f, "  {:<24} {:>10}", "Max. degree", self.degree)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Proving key size: ~{:.1} MiB",
+            self.proving_key_size as f64 / (1 << 20) as f64
+        )?;
+        writeln!(
+            f,
+            "SRS size:         ~{:.1} MiB",
+            ((1u64 << self.k) * POINT_SIZE as u64) as f64 / (1 << 20) as f64
+        )?;
+        writeln!(f, "Proof size:       ~{} bytes", self.proof_size)?;
+        write!(
+            f,
+            "Proving time:     >{:.1}s (on this machine)",
+            self.proving_time.as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::{dev::MockProver, plonk::Error};
+    use ndarray::{Array2, Array3};
+
+    use super::{estimate, minimal_k, suggest_params, Objective};
+    use crate::wnn::Wnn;
+
+    fn wnn() -> Wnn {
+        Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            2097143,
+            Array3::from_elem((2, 2, 1024), false),
+            (0..24u64).collect(),
+            Array3::zeros((4, 3, 2)),
+        )
+    }
+
+    #[test]
+    fn test_minimal_k() {
+        let wnn = wnn();
+        let k = minimal_k(&wnn).unwrap();
+
+        let image = Array2::zeros(wnn.img_shape());
+        let circuit = wnn.get_circuit(&image);
+        let instances = wnn.public_inputs(&image);
+        assert!(MockProver::run(k, &circuit, instances.clone()).is_ok());
+        assert!(matches!(
+            MockProver::run(k - 1, &circuit, instances),
+            Err(Error::NotEnoughRowsAvailable { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rows_per_chip() {
+        let estimate = estimate(&wnn()).unwrap();
+        let rows = |chip: &str| {
+            estimate
+                .rows_per_chip
+                .iter()
+                .find(|(name, _)| name == chip)
+                .map(|(_, rows)| *rows)
+        };
+        // One row per pixel
+        assert_eq!(rows("EncodeImageChip").map(|rows| rows >= 12), Some(true));
+        assert!(rows("HashChip").unwrap() > 0);
+        assert!(rows("BloomFilterChip").unwrap() > 0);
+        assert!(estimate
+            .rows_per_chip
+            .iter()
+            .all(|(_, rows)| *rows < 1 << estimate.k));
+    }
+
+    #[test]
+    fn test_suggest_params() {
+        let wnn = wnn();
+        let suggestions = suggest_params(&wnn, Objective::K).unwrap();
+
+        let best = &suggestions[0];
+        assert!(best.k <= minimal_k(&wnn).unwrap());
+        assert!(suggestions.iter().all(|s| s.k >= best.k));
+        assert_eq!(minimal_k(&best.knobs.apply(&wnn)).unwrap(), best.k);
+        // Windows of more than 8 bits need a larger byte table, which doesn't pay off for this
+        // small circuit
+        let largest_window = suggestions
+            .iter()
+            .find(|s| s.knobs.window_num_bits == 12)
+            .unwrap();
+        assert!(largest_window.k > best.k);
+    }
+}
@@ -0,0 +1,274 @@
+//! Fingerprints of the constraint system of [`WnnCircuit`], to detect unintentional circuit
+//! changes.
+//!
+//! Any change of the gates, lookups or columns of the circuit changes the verifying keys, so
+//! keys and EVM verifiers deployed for an earlier version no longer accept new proofs.
+//! [`fingerprint`] hashes the configured constraint system, and [`check_fingerprint`] compares
+//! it with a value recorded in a JSON file (mapping names to hex-encoded fingerprints), which
+//! is checked in next to the tests.
+//!
+//! The fingerprint only depends on the [`WnnCircuitParams`], not on the contents of the lookup
+//! tables (i.e. the bloom filters), which are covered by [`crate::Wnn::commitment`]. It is
+//! computed from the `Debug` representation of halo2's pinned constraint system (which is also
+//! hashed into the verifying key), so it can change when halo2 is upgraded.
+//!
+//! Missing entries are reported like changed ones, so that a lost or incomplete file doesn't
+//! silently pass. To record new fingerprints after an intentional change (or for a new
+//! variant), run the tests with `ZERO_G_UPDATE_FINGERPRINTS=1`.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use halo2_proofs::{
+    halo2curves::bn256::Fr as Fp,
+    plonk::{Circuit, ConstraintSystem},
+};
+use tracing::warn;
+
+use crate::gadgets::wnn::{WnnCircuit, WnnCircuitParams};
+use crate::utils::keccak256;
+
+/// If set, [`check_fingerprint`] records missing or changed fingerprints instead of reporting
+/// them.
+pub const UPDATE_ENV_VAR: &str = "ZERO_G_UPDATE_FINGERPRINTS";
+
+/// Hashes (with keccak256) the constraint system of the circuit with the given params.
+pub fn fingerprint(params: &WnnCircuitParams) -> [u8; 32] {
+    let mut cs = ConstraintSystem::<Fp>::default();
+    WnnCircuit::<Fp>::configure_with_params(&mut cs, params.clone());
+    keccak256(format!("{:?}", cs.pinned()))
+}
+
+/// The result of [`check_fingerprint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FingerprintCheck {
+    /// The fingerprint matches the recorded one.
+    Unchanged,
+    /// The fingerprint was missing or changed and [`UPDATE_ENV_VAR`] is set, so the current one
+    /// was recorded.
+    Recorded,
+    /// No fingerprint was recorded under the name (the actual one is hex-encoded).
+    Missing { actual: String },
+    /// The fingerprint differs from the recorded one (both hex-encoded).
+    Changed { recorded: String, actual: String },
+}
+
+/// Compares the fingerprint of the circuit with the given params with the one recorded under
+/// `name` in the JSON file at `path`, see the module documentation.
+pub fn check_fingerprint(
+    path: &Path,
+    name: &str,
+    params: &WnnCircuitParams,
+) -> io::Result<FingerprintCheck> {
+    let mut recorded = read_fingerprints(path)?;
+    let actual = hex::encode(fingerprint(params));
+    let update = env::var_os(UPDATE_ENV_VAR).is_some();
+    match recorded.get(name) {
+        Some(fingerprint) if *fingerprint == actual => return Ok(FingerprintCheck::Unchanged),
+        Some(fingerprint) if !update => {
+            return Ok(FingerprintCheck::Changed {
+                recorded: fingerprint.clone(),
+                actual,
+            })
+        }
+        None if !update => return Ok(FingerprintCheck::Missing { actual }),
+        _ => {}
+    }
+
+    warn!(
+        "Recording the constraint system fingerprint of {name} in {}",
+        path.display()
+    );
+    recorded.insert(name.to_string(), actual);
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &recorded)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    Ok(FingerprintCheck::Recorded)
+}
+
+/// Reads the recorded fingerprints, which are empty if the file doesn't exist yet.
+pub fn read_fingerprints(path: &Path) -> io::Result<BTreeMap<String, String>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+/// Like [`check_fingerprint`], but panics if the fingerprint is missing or changed.
+pub fn assert_fingerprint_unchanged(path: &Path, name: &str, params: &WnnCircuitParams) {
+    match check_fingerprint(path, name, params) {
+        Ok(FingerprintCheck::Unchanged | FingerprintCheck::Recorded) => {}
+        Ok(FingerprintCheck::Changed { recorded, actual }) => panic!(
+            "The constraint system of {name} changed (recorded fingerprint {recorded}, now \
+             {actual}), which invalidates deployed verifying keys. If this is intentional, run \
+             the tests with {UPDATE_ENV_VAR}=1 and commit {}.",
+            path.display()
+        ),
+        Ok(FingerprintCheck::Missing { actual }) => panic!(
+            "No constraint system fingerprint of {name} is recorded in {} (now {actual}). Run \
+             the tests with {UPDATE_ENV_VAR}=1 and commit the file.",
+            path.display()
+        ),
+        Err(e) => panic!("Unable to check the fingerprint in {}: {e}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::{env, fs, process};
+
+    use super::{
+        assert_fingerprint_unchanged, check_fingerprint, fingerprint, FingerprintCheck,
+        UPDATE_ENV_VAR,
+    };
+    use crate::gadgets::wnn::WnnCircuitParams;
+
+    const PARAMS: WnnCircuitParams = WnnCircuitParams {
+        p: 2097143,
+        l: 20,
+        n_hashes: 2,
+        bits_per_hash: 10,
+        bits_per_filter: 12,
+        n_classes: 2,
+        min_blinding_factors: 0,
+        num_instance_columns: 1,
+        class_lookup: false,
+        window_num_bits: 8,
+        chaining: false,
+        multi_label: false,
+        regression: false,
+        tabular: false,
+        occlusion_num_pixels: 0,
+        robustness: false,
+        image_commitment: false,
+    };
+
+    fn variants() -> Vec<(&'static str, WnnCircuitParams)> {
+        vec![
+            ("default", PARAMS),
+            (
+                "class_lookup",
+                WnnCircuitParams {
+                    class_lookup: true,
+                    ..PARAMS
+                },
+            ),
+            (
+                "two_instance_columns",
+                WnnCircuitParams {
+                    num_instance_columns: 2,
+                    ..PARAMS
+                },
+            ),
+            (
+                "min_blinding_factors",
+                WnnCircuitParams {
+                    min_blinding_factors: 10,
+                    ..PARAMS
+                },
+            ),
+            (
+                "window_num_bits",
+                WnnCircuitParams {
+                    window_num_bits: 11,
+                    ..PARAMS
+                },
+            ),
+            (
+                "chaining",
+                WnnCircuitParams {
+                    chaining: true,
+                    ..PARAMS
+                },
+            ),
+            (
+                "multi_label",
+                WnnCircuitParams {
+                    multi_label: true,
+                    ..PARAMS
+                },
+            ),
+            (
+                "regression",
+                WnnCircuitParams {
+                    n_classes: 1,
+                    regression: true,
+                    ..PARAMS
+                },
+            ),
+            (
+                "tabular",
+                WnnCircuitParams {
+                    tabular: true,
+                    ..PARAMS
+                },
+            ),
+            (
+                "occlusion",
+                WnnCircuitParams {
+                    occlusion_num_pixels: 12,
+                    ..PARAMS
+                },
+            ),
+            (
+                "robustness",
+                WnnCircuitParams {
+                    robustness: true,
+                    ..PARAMS
+                },
+            ),
+            (
+                "image_commitment",
+                WnnCircuitParams {
+                    image_commitment: true,
+                    ..PARAMS
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let changed = WnnCircuitParams {
+            class_lookup: true,
+            ..PARAMS
+        };
+        assert_eq!(fingerprint(&PARAMS), fingerprint(&PARAMS.clone()));
+        assert_ne!(fingerprint(&PARAMS), fingerprint(&changed));
+
+        let path = env::temp_dir().join(format!("zero_g_fingerprints_{}.json", process::id()));
+        let check = |params| check_fingerprint(&path, "default", params).unwrap();
+        if env::var_os(UPDATE_ENV_VAR).is_none() {
+            let actual = hex::encode(fingerprint(&PARAMS));
+            assert_eq!(
+                check(&PARAMS),
+                FingerprintCheck::Missing {
+                    actual: actual.clone()
+                }
+            );
+            assert!(!path.exists());
+
+            fs::write(&path, serde_json::json!({ "default": actual }).to_string()).unwrap();
+            assert_eq!(check(&PARAMS), FingerprintCheck::Unchanged);
+            assert!(matches!(check(&changed), FingerprintCheck::Changed { .. }));
+        } else {
+            assert_eq!(check(&PARAMS), FingerprintCheck::Recorded);
+            assert_eq!(check(&PARAMS), FingerprintCheck::Unchanged);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// Fails if the circuit changed, see the module documentation.
+    #[test]
+    fn test_fingerprints_unchanged() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cs_fingerprints.json");
+        for (name, params) in variants() {
+            assert_fingerprint_unchanged(&path, name, &params);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! A long-running daemon that watches an input directory and proves every image placed into it,
+//! see [`Daemon`].
+//!
+//! This is the batch proving flow of [`crate::batch_proving`] as a service: Proofs and the
+//! manifest are written to the output directory in the same format, and since the manifest is
+//! updated after every image, a restarted daemon continues where the previous one stopped.
+//! Images that failed are retried after a restart, or as soon as the image file is modified.
+//!
+//! Images should be moved into the input directory atomically (e.g. written elsewhere and
+//! renamed), so that the daemon never reads a partially written image.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use tracing::{info, instrument, warn};
+
+use crate::batch_proving::{image_files, proof_file_name, Manifest, ManifestEntry};
+use crate::classifier::{ProvableClassifier, WnnInput};
+use crate::error::ZeroGError;
+use crate::image_commitment::image_commitment;
+use crate::io::load_grayscale_image;
+use crate::memory;
+use crate::proof_file::write_proof_file;
+use crate::prover::Prover;
+
+/// How often the stop flag is checked while waiting for the next scan.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Configuration of a [`Daemon`].
+#[derive(Debug, Clone)]
+pub struct DaemonConfig {
+    /// The directory watched for images (recursively).
+    pub input_dir: PathBuf,
+    /// The directory to write the proofs and the manifest to.
+    pub output_dir: PathBuf,
+    /// Number of images proven at the same time.
+    pub workers: usize,
+    /// If set, no new proof is started while the memory usage of the process (see
+    /// [`memory::memory_usage`]) exceeds this many bytes, unless no proof is running at all.
+    pub memory_budget: Option<u64>,
+    /// How often the input directory is scanned for new images.
+    pub poll_interval: Duration,
+}
+
+/// Proves the images of an input directory, see the module documentation.
+pub struct Daemon {
+    prover: Prover,
+    config: DaemonConfig,
+    manifest: Mutex<Manifest>,
+    /// The modification time of every image that was attempted in this run, so that failed
+    /// images are only retried once they change.
+    attempted: Mutex<HashMap<String, Option<SystemTime>>>,
+    running: AtomicUsize,
+}
+
+impl Daemon {
+    /// Creates the output directory if needed and reads the manifest of a previous run.
+    ///
+    /// Returns an error if the previous run used a different model.
+    pub fn open(prover: Prover, config: DaemonConfig) -> Result<Self, ZeroGError> {
+        fs::create_dir_all(&config.output_dir).map_err(|source| ZeroGError::Io {
+            action: "create",
+            path: config.output_dir.clone(),
+            source,
+        })?;
+        let manifest = Manifest::open(&config.output_dir, prover.model().commitment())?;
+        Ok(Self {
+            prover,
+            config,
+            manifest: Mutex::new(manifest),
+            attempted: Mutex::new(HashMap::new()),
+            running: AtomicUsize::new(0),
+        })
+    }
+
+    /// A snapshot of the manifest.
+    pub fn manifest(&self) -> Manifest {
+        self.manifest.lock().unwrap().clone()
+    }
+
+    /// Proves images until `stop` is set. Proofs that are running when `stop` is set are
+    /// finished first.
+    pub fn run(&self, stop: &AtomicBool) -> Result<(), ZeroGError> {
+        info!("Watching {}", self.config.input_dir.display());
+        while !stop.load(Ordering::Relaxed) {
+            let proven = self.run_once(stop)?;
+            if proven > 0 {
+                info!("Proved {proven} images");
+            }
+            let mut waited = Duration::ZERO;
+            while waited < self.config.poll_interval && !stop.load(Ordering::Relaxed) {
+                let step = STOP_POLL_INTERVAL.min(self.config.poll_interval - waited);
+                thread::sleep(step);
+                waited += step;
+            }
+        }
+        Ok(())
+    }
+
+    /// Scans the input directory once and proves all pending images with the worker pool.
+    /// Returns the number of images that were proven successfully.
+    #[instrument(skip_all)]
+    pub fn run_once(&self, stop: &AtomicBool) -> Result<usize, ZeroGError> {
+        let pending = Mutex::new(self.pending_images()?);
+        let proven = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.config.workers.max(1))
+                .map(|_| {
+                    scope.spawn(|| -> Result<(), ZeroGError> {
+                        while !stop.load(Ordering::Relaxed) {
+                            self.wait_for_memory(stop);
+                            let (image_id, path) = match pending.lock().unwrap().pop_front() {
+                                Some(image) => image,
+                                None => return Ok(()),
+                            };
+                            if self.prove(&image_id, &path)? {
+                                proven.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .try_for_each(|worker| worker.join().expect("Worker panicked"))
+        })?;
+        Ok(proven.into_inner())
+    }
+
+    /// The images that are neither proven nor failed (unless modified since).
+    fn pending_images(&self) -> Result<VecDeque<(String, PathBuf)>, ZeroGError> {
+        let images = image_files(&self.config.input_dir).map_err(|source| ZeroGError::Io {
+            action: "read",
+            path: self.config.input_dir.clone(),
+            source,
+        })?;
+        let manifest = self.manifest.lock().unwrap();
+        let attempted = self.attempted.lock().unwrap();
+        Ok(images
+            .into_iter()
+            .filter(|(image_id, path)| {
+                !manifest.is_proven(&self.config.output_dir, image_id)
+                    && attempted
+                        .get(image_id)
+                        .map_or(true, |modified| *modified != modification_time(path))
+            })
+            .collect())
+    }
+
+    /// Waits until the memory usage is below the budget, or no other proof is running.
+    fn wait_for_memory(&self, stop: &AtomicBool) {
+        if let Some(budget) = self.config.memory_budget {
+            memory::wait_for_memory(budget, &self.running, stop);
+        }
+    }
+
+    /// Proves an image and records the outcome in the manifest. Returns whether proving
+    /// succeeded; only failing to write the manifest is returned as an error.
+    #[instrument(skip(self, path))]
+    fn prove(&self, image_id: &str, path: &Path) -> Result<bool, ZeroGError> {
+        let modified = modification_time(path);
+        let proof_file = proof_file_name(image_id);
+
+        self.running.fetch_add(1, Ordering::SeqCst);
+        let result = self.prove_image(path, &self.config.output_dir.join(&proof_file));
+        self.running.fetch_sub(1, Ordering::SeqCst);
+
+        let entry = match result {
+            Ok((scores, image_commitment)) => ManifestEntry::Proven {
+                proof_file,
+                scores,
+                image_commitment: Some(image_commitment),
+            },
+            Err(e) => {
+                warn!("Proving failed: {e}");
+                ManifestEntry::Failed {
+                    error: e.to_string(),
+                }
+            }
+        };
+        let proven = matches!(entry, ManifestEntry::Proven { .. });
+        self.attempted
+            .lock()
+            .unwrap()
+            .insert(image_id.to_string(), modified);
+        let mut manifest = self.manifest.lock().unwrap();
+        manifest.entries.insert(image_id.to_string(), entry);
+        manifest.write(&self.config.output_dir)?;
+        Ok(proven)
+    }
+
+    fn prove_image(
+        &self,
+        image_path: &Path,
+        proof_path: &Path,
+    ) -> Result<(Vec<u64>, [u8; 32]), ZeroGError> {
+        let image = load_grayscale_image(image_path)?;
+        let commitment = image_commitment(&image, None);
+        let input = WnnInput::Image(image);
+        let proof_file = self.prover.prove(&input)?;
+        write_proof_file(&proof_file, proof_path).map_err(|source| ZeroGError::Io {
+            action: "write",
+            path: proof_path.to_path_buf(),
+            source,
+        })?;
+        Ok((
+            ProvableClassifier::predict(self.prover.model(), &input),
+            commitment,
+        ))
+    }
+}
+
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(all(test, feature = "hdf5"))]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+    use std::{env, fs, path::Path, process};
+
+    use super::{Daemon, DaemonConfig};
+    use crate::batch_proving::ManifestEntry;
+    use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+    use crate::load_wnn;
+    use crate::prover::Prover;
+    use crate::setup::{setup, SrsSource};
+
+    #[test]
+    fn test_daemon_resumes() {
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let dir = env::temp_dir().join(format!("zero_g_daemon_{}", process::id()));
+        let artifact_dir = dir.join("artifacts");
+        setup(&wnn, Some(k), &SrsSource::Generate, &artifact_dir).unwrap();
+        let input_dir = dir.join("input");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::copy(TEST_IMG_PATH, input_dir.join("a.png")).unwrap();
+        fs::write(input_dir.join("broken.png"), b"not an image").unwrap();
+
+        let config = DaemonConfig {
+            input_dir: input_dir.clone(),
+            output_dir: dir.join("output"),
+            workers: 2,
+            memory_budget: None,
+            poll_interval: Duration::from_secs(1),
+        };
+        let open = || {
+            let prover = Prover::open(Path::new(model_path), &artifact_dir).unwrap();
+            Daemon::open(prover, config.clone()).unwrap()
+        };
+        let stop = AtomicBool::new(false);
+
+        let daemon = open();
+        let first_run = daemon.run_once(&stop).unwrap();
+        // Failed images are not retried until they change
+        let second_run = daemon.run_once(&stop).unwrap();
+        fs::copy(TEST_IMG_PATH, input_dir.join("b.png")).unwrap();
+        // After a restart, only the new image is proven
+        let third_run = open().run_once(&stop).unwrap();
+        let manifest = open().manifest();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((first_run, second_run, third_run), (1, 0, 1));
+        assert!(matches!(
+            manifest.entries["broken.png"],
+            ManifestEntry::Failed { .. }
+        ));
+        assert_eq!(manifest.failures().count(), 1);
+        assert_eq!(manifest.entries.len(), 3);
+    }
+}
@@ -0,0 +1,393 @@
+//! Iterating over labeled images, e.g. to evaluate the accuracy of a model or to prove
+//! inference on many images.
+//!
+//! Supported formats are:
+//! - A directory of image files, labeled by a [`LabelSource`]
+//! - The IDX format used by [MNIST](http://yann.lecun.com/exdb/mnist/)
+//! - The binary format of [CIFAR-10](https://www.cs.toronto.edu/~kriz/cifar.html)
+//!
+//! Image files are decoded in parallel (see [`Dataset::par_iter`]).
+//! With the `download` feature, MNIST can be downloaded using [`mnist::fetch`].
+
+#[cfg(feature = "download")]
+pub mod mnist;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use image::{DynamicImage, ImageError, RgbImage};
+use ndarray::Array2;
+
+use crate::image_loading::{convert_image, load_image_with, ImageLoadOptions};
+use crate::io::invalid_data;
+use crate::labels::LabelSource;
+
+/// File extensions of images that are picked up in directories.
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "bmp", "gif"];
+
+/// A labeled image.
+#[derive(Debug, Clone)]
+pub struct Example {
+    /// Identifies the image, e.g. its path or its index in an IDX file.
+    pub id: String,
+    pub image: Array2<u8>,
+    pub label: usize,
+}
+
+#[derive(Debug)]
+enum Sample {
+    File { path: PathBuf, label: usize },
+    Decoded(Example),
+}
+
+impl Sample {
+    fn load(&self, options: ImageLoadOptions) -> Result<Example, ImageError> {
+        match self {
+            Sample::File { path, label } => Ok(Example {
+                id: path.display().to_string(),
+                image: load_image_with(path, options)?,
+                label: *label,
+            }),
+            Sample::Decoded(example) => Ok(example.clone()),
+        }
+    }
+}
+
+/// A collection of labeled images.
+pub struct Dataset {
+    samples: Arc<Vec<Sample>>,
+    options: ImageLoadOptions,
+}
+
+impl Dataset {
+    fn new(samples: Vec<Sample>) -> Self {
+        Self {
+            samples: Arc::new(samples),
+            options: ImageLoadOptions::default(),
+        }
+    }
+
+    /// Opens a dataset, detecting the format:
+    /// - Directories that only contain subdirectories are read with
+    ///   [`LabelSource::class_directories`], other directories with [`LabelSource::FileName`].
+    /// - `.bin` files are read as CIFAR-10.
+    /// - Other files are read as IDX images, with the labels in the corresponding
+    ///   `labels-idx1` file (e.g. `t10k-labels-idx1-ubyte` for `t10k-images-idx3-ubyte`).
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if path.is_dir() {
+            let only_dirs = fs::read_dir(path)?
+                .map(|entry| Ok(entry?.file_type()?.is_dir()))
+                .collect::<io::Result<Vec<_>>>()?;
+            let labels = if !only_dirs.is_empty() && only_dirs.iter().all(|is_dir| *is_dir) {
+                LabelSource::class_directories(path)?
+            } else {
+                LabelSource::FileName
+            };
+            Self::directory(path, &labels)
+        } else if path
+            .extension()
+            .map_or(false, |extension| extension == "bin")
+        {
+            Self::cifar10(path)
+        } else {
+            let file_name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("");
+            if !file_name.contains("images-idx3") {
+                return Err(invalid_data(format!(
+                    "Unable to detect the dataset format of {}",
+                    path.display()
+                )));
+            }
+            let labels_path = path.with_file_name(file_name.replace("images-idx3", "labels-idx1"));
+            Self::idx(path, &labels_path)
+        }
+    }
+
+    /// Collects all images in `root` (recursively), skipping images without a label.
+    /// Images are sorted by path.
+    pub fn directory(root: &Path, labels: &LabelSource) -> io::Result<Self> {
+        let mut paths = vec![];
+        collect_images(root, &mut paths)?;
+        paths.sort();
+
+        Ok(Self::new(
+            paths
+                .into_iter()
+                .filter_map(|path| {
+                    let label = labels.label(&path)?;
+                    Some(Sample::File { path, label })
+                })
+                .collect(),
+        ))
+    }
+
+    /// Reads images and labels in the IDX format.
+    pub fn idx(images_path: &Path, labels_path: &Path) -> io::Result<Self> {
+        let images = fs::read(images_path)?;
+        let labels = fs::read(labels_path)?;
+        Self::from_idx_bytes(&images, &labels)
+    }
+
+    /// Parses images and labels in the IDX format.
+    pub fn from_idx_bytes(images: &[u8], labels: &[u8]) -> io::Result<Self> {
+        let be_u32 = |bytes: &[u8], i: usize| -> io::Result<usize> {
+            let word = bytes
+                .get(4 * i..4 * i + 4)
+                .ok_or_else(|| invalid_data("IDX file is truncated"))?;
+            Ok(u32::from_be_bytes(word.try_into().unwrap()) as usize)
+        };
+
+        if be_u32(images, 0)? != 0x0803 || be_u32(labels, 0)? != 0x0801 {
+            return Err(invalid_data("Not an IDX images or labels file"));
+        }
+        let (n, rows, cols) = (be_u32(images, 1)?, be_u32(images, 2)?, be_u32(images, 3)?);
+        if be_u32(labels, 1)? != n {
+            return Err(invalid_data("Number of images and labels differ"));
+        }
+        if rows == 0 || cols == 0 {
+            return Err(invalid_data("IDX images must not be empty"));
+        }
+        let pixels = &images[16..];
+        let labels = &labels[8..];
+        let num_pixels = n
+            .checked_mul(rows)
+            .and_then(|x| x.checked_mul(cols))
+            .ok_or_else(|| invalid_data("IDX dimensions are too large"))?;
+        if pixels.len() != num_pixels || labels.len() != n {
+            return Err(invalid_data("IDX file has the wrong size"));
+        }
+
+        Ok(Self::new(
+            pixels
+                .chunks_exact(rows * cols)
+                .zip(labels)
+                .enumerate()
+                .map(|(i, (pixels, label))| {
+                    Sample::Decoded(Example {
+                        id: i.to_string(),
+                        image: Array2::from_shape_vec((rows, cols), pixels.to_vec()).unwrap(),
+                        label: *label as usize,
+                    })
+                })
+                .collect(),
+        ))
+    }
+
+    /// Reads a CIFAR-10 batch in the binary format.
+    /// Images are converted to grayscale using the default [`ImageLoadOptions`], see
+    /// [`Dataset::from_cifar10_bytes`] for other options.
+    pub fn cifar10(path: &Path) -> io::Result<Self> {
+        Self::from_cifar10_bytes(&fs::read(path)?, ImageLoadOptions::default())
+    }
+
+    /// Parses a CIFAR-10 batch in the binary format.
+    pub fn from_cifar10_bytes(bytes: &[u8], options: ImageLoadOptions) -> io::Result<Self> {
+        const SIZE: usize = 32;
+        const RECORD_SIZE: usize = 1 + 3 * SIZE * SIZE;
+        if bytes.len() % RECORD_SIZE != 0 {
+            return Err(invalid_data("CIFAR-10 file has the wrong size"));
+        }
+
+        let samples = bytes
+            .chunks_exact(RECORD_SIZE)
+            .enumerate()
+            .map(|(i, record)| {
+                // Channels are stored in planar order (all red values first)
+                let planes = &record[1..];
+                let image = RgbImage::from_fn(SIZE as u32, SIZE as u32, |x, y| {
+                    let offset = y as usize * SIZE + x as usize;
+                    image::Rgb([0, 1, 2].map(|c| planes[c * SIZE * SIZE + offset]))
+                });
+                Sample::Decoded(Example {
+                    id: i.to_string(),
+                    image: convert_image(&DynamicImage::ImageRgb8(image), options),
+                    label: record[0] as usize,
+                })
+            })
+            .collect();
+        Ok(Self {
+            options,
+            ..Self::new(samples)
+        })
+    }
+
+    /// Sets how image files are converted to grayscale.
+    pub fn with_image_load_options(mut self, options: ImageLoadOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Iterates over all examples in order, decoding images on the calling thread.
+    pub fn iter(&self) -> impl Iterator<Item = Result<Example, ImageError>> + '_ {
+        self.samples.iter().map(|sample| sample.load(self.options))
+    }
+
+    /// Iterates over all examples, decoding images on all available cores.
+    ///
+    /// Note that examples are yielded in **no particular order**.
+    pub fn par_iter(&self) -> ParIter {
+        let num_threads = thread::available_parallelism().map_or(1, |n| n.get());
+        self.par_iter_with(num_threads, 4 * num_threads)
+    }
+
+    /// Like [`Dataset::par_iter`], with a given number of threads. At most `prefetch`
+    /// decoded images are buffered, which bounds the memory usage if the consumer is slow
+    /// (e.g. when generating a proof for each image).
+    pub fn par_iter_with(&self, num_threads: usize, prefetch: usize) -> ParIter {
+        let (sender, receiver) = sync_channel(prefetch);
+        let next_index = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..num_threads.max(1) {
+            let samples = self.samples.clone();
+            let next_index = next_index.clone();
+            let sender = sender.clone();
+            let options = self.options;
+            thread::spawn(move || loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                let sample = match samples.get(index) {
+                    Some(sample) => sample,
+                    None => break,
+                };
+                // Stop if the iterator was dropped
+                if sender.send(sample.load(options)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        ParIter { receiver }
+    }
+}
+
+/// Iterator returned by [`Dataset::par_iter`].
+pub struct ParIter {
+    receiver: Receiver<Result<Example, ImageError>>,
+}
+
+impl Iterator for ParIter {
+    type Item = Result<Example, ImageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Fails once all workers are done and have dropped their sender
+        self.receiver.recv().ok()
+    }
+}
+
+pub(crate) fn collect_images(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_images(&path, paths)?;
+        } else if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| {
+                IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+            })
+        {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::path::Path;
+
+    use crate::checked_in_test_data::TEST_IMG_PATH;
+    use crate::image_loading::ImageLoadOptions;
+    use crate::labels::LabelSource;
+
+    use super::Dataset;
+
+    fn idx_example() -> (Vec<u8>, Vec<u8>) {
+        let mut images = vec![0, 0, 8, 3, 0, 0, 0, 3, 0, 0, 0, 2, 0, 0, 0, 2];
+        images.extend(0..12);
+        let labels = vec![0, 0, 8, 1, 0, 0, 0, 3, 5, 7, 9];
+        (images, labels)
+    }
+
+    #[test]
+    fn test_idx() {
+        let (images, labels) = idx_example();
+        let dataset = Dataset::from_idx_bytes(&images, &labels).unwrap();
+
+        let examples = dataset.iter().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(examples.len(), 3);
+        assert_eq!(examples[1].image, ndarray::array![[4, 5], [6, 7]]);
+        assert_eq!(
+            examples.iter().map(|e| e.label).collect::<Vec<_>>(),
+            vec![5, 7, 9]
+        );
+
+        assert!(Dataset::from_idx_bytes(&images[..20], &labels).is_err());
+    }
+
+    #[test]
+    fn test_idx_invalid_dimensions() {
+        let with_shape = |rows: [u8; 4], cols: [u8; 4]| {
+            let (mut images, labels) = idx_example();
+            images[8..12].copy_from_slice(&rows);
+            images[12..16].copy_from_slice(&cols);
+            Dataset::from_idx_bytes(&images, &labels).map(|_| ())
+        };
+        for (rows, cols) in [([0; 4], [0, 0, 0, 2]), ([0xff; 4], [0xff; 4])] {
+            let error = with_shape(rows, cols).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_par_iter_yields_all_examples() {
+        let (images, labels) = idx_example();
+        let dataset = Dataset::from_idx_bytes(&images, &labels).unwrap();
+
+        let mut labels = dataset
+            .par_iter_with(2, 1)
+            .map(|example| example.unwrap().label)
+            .collect::<Vec<_>>();
+        labels.sort();
+        assert_eq!(labels, vec![5, 7, 9]);
+    }
+
+    #[test]
+    fn test_cifar10() {
+        let mut bytes = vec![3];
+        bytes.extend([10; 1024]);
+        bytes.extend([20; 1024]);
+        bytes.extend([30; 1024]);
+        let dataset = Dataset::from_cifar10_bytes(&bytes, ImageLoadOptions::default()).unwrap();
+
+        let example = dataset.iter().next().unwrap().unwrap();
+        assert_eq!(example.label, 3);
+        assert_eq!(example.image.dim(), (32, 32));
+        assert!(example.image.iter().all(|x| *x == 10));
+    }
+
+    #[test]
+    fn test_directory() {
+        let dir = Path::new(TEST_IMG_PATH).parent().unwrap();
+        let dataset = Dataset::directory(dir, &LabelSource::FileName).unwrap();
+
+        let examples = dataset.iter().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(examples.len(), 1);
+        assert_eq!(examples[0].label, 7);
+    }
+}
@@ -0,0 +1,108 @@
+//! Downloading the [MNIST](http://yann.lecun.com/exdb/mnist/) dataset.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use image::GrayImage;
+
+use crate::datasets::Dataset;
+use crate::download::{download, gunzip, verify_md5};
+
+/// The mirror that the archives are downloaded from (the original site rate-limits downloads).
+pub const MIRROR: &str = "https://ossci-datasets.s3.amazonaws.com/mnist/";
+
+/// Archive names and their MD5 checksums.
+const ARCHIVES: [(&str, &str); 4] = [
+    (
+        "train-images-idx3-ubyte.gz",
+        "f68b3c2dcbeaaa9fbdd348bbdeb94873",
+    ),
+    (
+        "train-labels-idx1-ubyte.gz",
+        "d53e105ee54ea40749a09fcbcd1e9432",
+    ),
+    (
+        "t10k-images-idx3-ubyte.gz",
+        "9fb629c4189551a2d022fa330f9573f3",
+    ),
+    (
+        "t10k-labels-idx1-ubyte.gz",
+        "ec29112dd5afa0611ce80d1b7f02629c",
+    ),
+];
+
+/// Paths to the files written by [`fetch`].
+pub struct MnistFiles {
+    pub train_images: PathBuf,
+    pub train_labels: PathBuf,
+    pub test_images: PathBuf,
+    pub test_labels: PathBuf,
+    /// The test set as PNG files named `<index>_<label>.png`, the format exported by the
+    /// training code.
+    pub test_png_dir: PathBuf,
+}
+
+impl MnistFiles {
+    fn new(dir: &Path) -> Self {
+        Self {
+            train_images: dir.join("train-images-idx3-ubyte"),
+            train_labels: dir.join("train-labels-idx1-ubyte"),
+            test_images: dir.join("t10k-images-idx3-ubyte"),
+            test_labels: dir.join("t10k-labels-idx1-ubyte"),
+            test_png_dir: dir.join("png"),
+        }
+    }
+
+    /// The training set.
+    pub fn train_set(&self) -> io::Result<Dataset> {
+        Dataset::idx(&self.train_images, &self.train_labels)
+    }
+
+    /// The test set.
+    pub fn test_set(&self) -> io::Result<Dataset> {
+        Dataset::idx(&self.test_images, &self.test_labels)
+    }
+}
+
+/// Downloads MNIST into `dir` (e.g. `data/MNIST`), verifying the checksums of the archives.
+///
+/// The archives are decompressed into IDX files, and the test set is additionally exported
+/// as PNG files to `<dir>/png`. Files that already exist are not downloaded again.
+pub fn fetch(dir: &Path) -> io::Result<MnistFiles> {
+    fs::create_dir_all(dir)?;
+
+    for (archive, md5) in ARCHIVES {
+        let path = dir.join(archive.trim_end_matches(".gz"));
+        if path.exists() {
+            continue;
+        }
+        let bytes = download(&format!("{MIRROR}{archive}"))?;
+        verify_md5(archive, &bytes, md5)?;
+        fs::write(path, gunzip(&bytes)?)?;
+    }
+
+    let files = MnistFiles::new(dir);
+    if !files.test_png_dir.exists() {
+        export_png(&files.test_set()?, &files.test_png_dir)?;
+    }
+    Ok(files)
+}
+
+/// Writes each example as `<index>_<label>.png`.
+fn export_png(dataset: &Dataset, dir: &Path) -> io::Result<()> {
+    // Write to a temporary directory first, so that an interrupted export is not mistaken
+    // for a complete one.
+    let tmp_dir = dir.with_extension("tmp");
+    fs::create_dir_all(&tmp_dir)?;
+    for (i, example) in dataset.iter().enumerate() {
+        let example = example.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let (height, width) = example.image.dim();
+        let image = GrayImage::from_raw(width as u32, height as u32, example.image.into_raw_vec())
+            .expect("Shape matches the number of pixels");
+        image
+            .save(tmp_dir.join(format!("{i:04}_{}.png", example.label)))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    }
+    fs::rename(tmp_dir, dir)
+}
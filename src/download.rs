@@ -0,0 +1,54 @@
+//! Helpers to download artifacts and verify their checksums.
+
+use std::io::{self, Read};
+
+use sha2::{Digest, Sha256};
+
+/// Downloads the given URL into memory.
+pub fn download(url: &str) -> io::Result<Vec<u8>> {
+    let response = ureq::get(url).call().map_err(|e| to_io_error(url, e))?;
+    let mut bytes = vec![];
+    response.into_reader().read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Converts a failed request into an [`io::Error`], keeping track of missing resources.
+pub fn to_io_error(url: &str, error: ureq::Error) -> io::Error {
+    let kind = match error {
+        ureq::Error::Status(404, _) => io::ErrorKind::NotFound,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, format!("Request to {url} failed: {error}"))
+}
+
+/// Returns an error if the MD5 hash of `bytes` does not match `expected` (in hex).
+pub fn verify_md5(name: &str, bytes: &[u8], expected: &str) -> io::Result<()> {
+    check_digest(name, "MD5", format!("{:x}", md5::compute(bytes)), expected)
+}
+
+/// Returns an error if the SHA-256 hash of `bytes` does not match `expected` (in hex).
+pub fn verify_sha256(name: &str, bytes: &[u8], expected: &str) -> io::Result<()> {
+    check_digest(
+        name,
+        "SHA-256",
+        hex::encode(Sha256::digest(bytes)),
+        expected,
+    )
+}
+
+fn check_digest(name: &str, algorithm: &str, actual: String, expected: &str) -> io::Result<()> {
+    if actual != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Checksum mismatch for {name}: expected {algorithm} {expected}, got {actual}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Decompresses a gzip archive.
+pub fn gunzip(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut decompressed = vec![];
+    flate2::read::GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
@@ -0,0 +1,79 @@
+//! The error type returned by the public API of this crate.
+
+use std::error::Error;
+use std::io;
+use std::path::PathBuf;
+
+use halo2_proofs::plonk;
+use image::ImageError;
+use thiserror::Error;
+
+/// Errors returned when loading or writing artifacts and when generating keys or proofs.
+#[derive(Debug, Error)]
+pub enum ZeroGError {
+    /// A file could not be opened, read or written.
+    #[error("Unable to {action} {}: {source}", path.display())]
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// A file could be read, but not parsed in the expected format.
+    #[error("Unable to read {} as {format}: {source}", path.display())]
+    Format {
+        path: PathBuf,
+        format: &'static str,
+        #[source]
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// An artifact could not be read from an [`crate::artifact_store::ArtifactStore`].
+    #[error("Unable to read artifact {key} as {format}: {source}")]
+    Artifact {
+        key: String,
+        format: &'static str,
+        #[source]
+        source: io::Error,
+    },
+    /// An image could not be loaded.
+    #[error("Unable to load image {}: {source}", path.display())]
+    Image {
+        path: PathBuf,
+        #[source]
+        source: ImageError,
+    },
+    /// An example of a dataset could not be decoded, see [`crate::datasets::Dataset`].
+    #[error("Unable to decode dataset example: {0}")]
+    Dataset(#[source] ImageError),
+    /// A model could not be loaded.
+    #[error("Unable to load model: {0}")]
+    Model(String),
+    /// A model was loaded, but its parameters are inconsistent (see [`crate::Wnn::validate`]).
+    #[error("Invalid model: {0}")]
+    InvalidModel(String),
+    /// An image does not have the input shape of the model.
+    #[error("Expected an image of shape {expected:?}, got {actual:?}")]
+    ImageShape {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    /// An input does not match the kind of the model, e.g. a feature vector for an image
+    /// model or a feature vector of the wrong length.
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    /// The images of a robustness proof differ by more than the bound, see
+    /// [`crate::Wnn::robustness_proof`].
+    #[error("The images have an L∞ distance of {distance}, which exceeds the bound {bound}")]
+    Perturbation { distance: u8, bound: u8 },
+    /// A function was called without the arguments it requires, e.g. [`crate::setup::gen_srs`]
+    /// without a model or `k`.
+    #[error("{0}")]
+    MissingArgument(&'static str),
+    /// Halo2 returned an error during key generation or proving.
+    #[error("{action} failed: {source}")]
+    Plonk {
+        action: &'static str,
+        #[source]
+        source: plonk::Error,
+    },
+}
@@ -15,6 +15,7 @@
 //!     eth::{
 //!         dry_run_verifier, gen_evm_verifier,
 //!     },
+//!     gadgets::wnn::InstanceLayout,
 //!     load_grayscale_image, load_wnn,
 //! };
 //!
@@ -25,34 +26,37 @@
 //!
 //! // Generate keys
 //! let kzg_params = ParamsKZG::new(k);
-//! let pk = wnn.generate_proving_key(&kzg_params);
+//! let pk = wnn.generate_proving_key(&kzg_params).unwrap();
 //!
 //! // Generate proof
-//! let (proof, outputs) = wnn.proof(&pk, &kzg_params, &img);
+//! let (proof, outputs) = wnn.proof(&pk, &kzg_params, &img).unwrap();
 //!
 //! // Generate contract bytecode
-//! let deployment_code = gen_evm_verifier(&kzg_params, pk.get_vk(), vec![outputs.len()]);
+//! let layout = InstanceLayout::from_params(&wnn.get_circuit_params());
+//! let deployment_code = gen_evm_verifier(&kzg_params, pk.get_vk(), layout.column_lengths());
 //!
 //! // Verify the proof using the EVM verifier
-//! let gas_used = dry_run_verifier(deployment_code, vec![outputs], proof).unwrap();
+//! let gas_used = dry_run_verifier(deployment_code, layout.to_columns(&outputs), proof).unwrap();
 //! ```
 
 use ethers::{
-    abi::Abi,
+    abi::{encode, Abi, Token},
     contract::ContractFactory,
     middleware::SignerMiddleware,
     prelude::k256::ecdsa::SigningKey,
     providers::{Http, Middleware, Provider},
     signers::{LocalWallet, Signer, Wallet},
-    types::{Address, TransactionReceipt, TransactionRequest},
-    utils::{Anvil, AnvilInstance},
+    types::{Address, TransactionReceipt, TransactionRequest, U256},
+    utils::{id, keccak256, Anvil, AnvilInstance},
 };
 use eyre::{eyre, Result};
+use ff::PrimeField;
 use halo2_proofs::{
     halo2curves::bn256::{Bn256, Fq, Fr, G1Affine},
     plonk::VerifyingKey,
     poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
 };
+use serde::Serialize;
 use snark_verifier::loader::evm::encode_calldata;
 use snark_verifier::loader::evm::ExecutorBuilder;
 use snark_verifier::{
@@ -61,22 +65,45 @@ use snark_verifier::{
     system::halo2::{compile, transcript::evm::EvmTranscript, Config},
     verifier::{plonk::PlonkVerifier, SnarkVerifier},
 };
-use std::{env, rc::Rc, str::FromStr};
+use std::{env, fs, path::Path, rc::Rc, str::FromStr};
 use std::{sync::Arc, time::Duration};
+use tracing::info;
 
-/// Generates EVM bytecode for a verifier contract.
+use crate::gadgets::wnn::{InstanceLayout, PublicValue};
+use crate::verifier_bundle::vk_fingerprint;
+use crate::wnn::Wnn;
+
+/// Generates EVM bytecode for a verifier contract, with the number of public inputs of each
+/// instance column (see [`InstanceLayout::column_lengths`]).
 pub fn gen_evm_verifier(
     params: &ParamsKZG<Bn256>,
     vk: &VerifyingKey<G1Affine>,
     num_instance: Vec<usize>,
 ) -> Vec<u8> {
+    let yul_code = gen_evm_verifier_yul(params, vk, num_instance);
+    let bytecode = evm::compile_yul(&yul_code);
+
+    info!("Byte code size: {}", bytecode.len());
+
+    bytecode
+}
+
+/// Generates the Yul source code of a verifier contract (see [`gen_evm_verifier`]).
+///
+/// The contract has no functions: It expects the public inputs followed by the proof as
+/// calldata (see [`encode_calldata`]) and reverts if the proof is invalid.
+pub fn gen_evm_verifier_yul(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    num_instance: Vec<usize>,
+) -> String {
     let protocol = compile(
         params,
         vk,
         Config::kzg().with_num_instance(num_instance.clone()),
     );
 
-    println!(
+    info!(
         "Verification key: Number of fixed commitments: {}",
         vk.fixed_commitments().len()
     );
@@ -97,12 +124,220 @@ pub fn gen_evm_verifier(
     .unwrap();
     PlonkVerifier::<KzgAs<Bn256, Gwc19>>::verify(&vk, &protocol, &instances, &proof).unwrap();
 
-    let yul_code = loader.yul_code();
-    let bytecode = evm::compile_yul(&yul_code);
+    loader.yul_code()
+}
 
-    println!("Byte code size: {}", bytecode.len());
+/// A Solidity contract with a typed `verify` function, which forwards to the verifier
+/// generated by [`gen_evm_verifier`] (whose address is passed to the constructor).
+const VERIFIER_WRAPPER_SOL: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.17;
 
-    bytecode
+/// Typed interface to a zero_g verifier, which expects the public inputs followed by the
+/// proof as raw calldata and reverts if the proof is invalid.
+contract ZeroGVerifier {
+    uint256 public constant NUM_INSTANCES = {num_instances};
+
+    address public immutable verifier;
+
+    constructor(address _verifier) {
+        verifier = _verifier;
+    }
+
+    /// Returns whether the proof is valid for the given public inputs (the score of each class).
+    function verify(uint256[] calldata instances, bytes calldata proof) external view returns (bool) {
+        require(instances.length == NUM_INSTANCES, "Wrong number of instances");
+        (bool success, ) = verifier.staticcall(abi.encodePacked(instances, proof));
+        return success;
+    }
+}
+"#;
+
+/// Writes everything needed to deploy a verifier and submit proofs to it to `dir`:
+/// - `Verifier.yul`: The verifier contract (see [`gen_evm_verifier_yul`]).
+/// - `Verifier.bin`: Its deployment bytecode (hex).
+/// - `ZeroGVerifier.sol` and `ZeroGVerifier.abi.json`: A Solidity contract with a typed
+///   `verify(uint256[],bytes)` function, which forwards to the verifier, and its ABI.
+/// - If a proof is given, `calldata.hex` and `ZeroGVerifier.calldata.hex`: Example calldata
+///   for the verifier and for `ZeroGVerifier.verify`.
+///
+/// The public inputs are passed in the order of the [`InstanceLayout`], i.e. as the
+/// concatenation of the instance columns.
+pub fn export_evm_verifier(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    instance_layout: &InstanceLayout,
+    proof: Option<(&[u8], &[Fr])>,
+    dir: &Path,
+) -> Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let num_instances = instance_layout.len();
+    let yul_code = gen_evm_verifier_yul(params, vk, instance_layout.column_lengths());
+    fs::write(
+        dir.join("Verifier.bin"),
+        hex::encode(evm::compile_yul(&yul_code)),
+    )?;
+    fs::write(dir.join("Verifier.yul"), yul_code)?;
+
+    fs::write(
+        dir.join("ZeroGVerifier.sol"),
+        VERIFIER_WRAPPER_SOL.replace("{num_instances}", &num_instances.to_string()),
+    )?;
+    let abi = serde_json::json!([
+        {
+            "type": "constructor",
+            "inputs": [{ "name": "_verifier", "type": "address" }],
+            "stateMutability": "nonpayable"
+        },
+        {
+            "type": "function",
+            "name": "NUM_INSTANCES",
+            "inputs": [],
+            "outputs": [{ "name": "", "type": "uint256" }],
+            "stateMutability": "view"
+        },
+        {
+            "type": "function",
+            "name": "verifier",
+            "inputs": [],
+            "outputs": [{ "name": "", "type": "address" }],
+            "stateMutability": "view"
+        },
+        {
+            "type": "function",
+            "name": "verify",
+            "inputs": [
+                { "name": "instances", "type": "uint256[]" },
+                { "name": "proof", "type": "bytes" }
+            ],
+            "outputs": [{ "name": "", "type": "bool" }],
+            "stateMutability": "view"
+        }
+    ]);
+    fs::write(
+        dir.join("ZeroGVerifier.abi.json"),
+        serde_json::to_string_pretty(&abi)?,
+    )?;
+
+    if let Some((proof, instances)) = proof {
+        let calldata = encode_verifier_calldata(proof, instances);
+        fs::write(dir.join("calldata.hex"), hex::encode(calldata))?;
+        fs::write(
+            dir.join("ZeroGVerifier.calldata.hex"),
+            hex::encode(encode_verify_call(proof, instances)),
+        )?;
+    }
+    Ok(())
+}
+
+/// Encodes the calldata for the verifier generated by [`gen_evm_verifier`]. The verifier
+/// has no ABI; it expects the instances followed by the proof.
+pub fn encode_verifier_calldata(proof: &[u8], instances: &[Fr]) -> Vec<u8> {
+    encode_calldata(&[instances.to_vec()], proof)
+}
+
+/// ABI-encodes a call to `ZeroGVerifier.verify(uint256[],bytes)` (see [`export_evm_verifier`]).
+pub fn encode_verify_call(proof: &[u8], instances: &[Fr]) -> Vec<u8> {
+    let instances = instances
+        .iter()
+        .map(|instance| Token::Uint(U256::from_little_endian(instance.to_repr().as_ref())))
+        .collect();
+    let mut calldata = id("verify(uint256[],bytes)").to_vec();
+    calldata.extend(encode(&[
+        Token::Array(instances),
+        Token::Bytes(proof.to_vec()),
+    ]));
+    calldata
+}
+
+/// The values to register a model with an on-chain model registry, see
+/// [`RegistrationPayload::new`].
+///
+/// The hashes follow the conventions of this crate, so that a registry contract can recompute
+/// or check them:
+///
+/// ```solidity
+/// // See `instance_layout_hash`; the number of instance columns, then a score of class `c` is
+/// // the pair (0, c)
+/// bytes32 layoutHash = keccak256(abi.encode(uint256(1), uint256(0), uint256(0), uint256(0), uint256(1), ...));
+/// registry.register(vkFingerprint, modelCommitment, layoutHash, verifier);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RegistrationPayload {
+    /// See [`crate::verifier_bundle::vk_fingerprint`].
+    pub vk_fingerprint: [u8; 32],
+    /// See [`Wnn::commitment`].
+    pub model_commitment: [u8; 32],
+    /// See [`instance_layout_hash`].
+    pub instance_layout_hash: [u8; 32],
+    /// The address of the deployed verifier (or `ZeroGVerifier` wrapper) contract.
+    pub verifier: Address,
+}
+
+impl RegistrationPayload {
+    pub fn new(wnn: &Wnn, vk: &VerifyingKey<G1Affine>, verifier: Address) -> Self {
+        let circuit_params = wnn.get_circuit_params();
+        Self {
+            vk_fingerprint: vk_fingerprint(vk, &circuit_params),
+            model_commitment: wnn.commitment(),
+            instance_layout_hash: instance_layout_hash(&InstanceLayout::from_params(
+                &circuit_params,
+            )),
+            verifier,
+        }
+    }
+
+    /// The payload as 32-byte words, in field order, i.e. the values of the storage slots of a
+    /// registry that stores the tuple as a struct. The address is left-padded with zeros.
+    pub fn slot_values(&self) -> [[u8; 32]; 4] {
+        let mut verifier = [0u8; 32];
+        verifier[12..].copy_from_slice(self.verifier.as_bytes());
+        [
+            self.vk_fingerprint,
+            self.model_commitment,
+            self.instance_layout_hash,
+            verifier,
+        ]
+    }
+
+    /// ABI-encodes a call to `register(bytes32,bytes32,bytes32,address)`.
+    pub fn encode_register_call(&self) -> Vec<u8> {
+        let mut calldata = id("register(bytes32,bytes32,bytes32,address)").to_vec();
+        calldata.extend(self.slot_values().concat());
+        calldata
+    }
+}
+
+/// Hashes the layout of the public inputs: The number of instance columns is encoded as a
+/// `uint256` word, followed by two words for every value, a tag and an index (a score of class
+/// `c` is `(0, c)`), and the hash is `keccak256` of the ABI-encoded words. The chaining value
+/// is `(1, 0)`, the label of class `c` is `(2, c)`, the output of a regression model is
+/// `(3, 0)`, the occlusion of pixel `i` is `(4, i)`, the perturbed score of class `c` is
+/// `(5, c)`, the perturbation bound is `(6, 0)`, the image commitment is `(7, 0)` and the
+/// previous chaining value is `(8, 0)`.
+pub fn instance_layout_hash(layout: &InstanceLayout) -> [u8; 32] {
+    let values = layout.values.iter().flat_map(|value| match value {
+        PublicValue::Score { class } => [Token::Uint(U256::zero()), Token::Uint((*class).into())],
+        PublicValue::ChainingValue => [Token::Uint(U256::one()), Token::Uint(U256::zero())],
+        PublicValue::Label { class } => [Token::Uint(U256::from(2)), Token::Uint((*class).into())],
+        PublicValue::Output => [Token::Uint(U256::from(3)), Token::Uint(U256::zero())],
+        PublicValue::Occluded { pixel } => {
+            [Token::Uint(U256::from(4)), Token::Uint((*pixel).into())]
+        }
+        PublicValue::PerturbedScore { class } => {
+            [Token::Uint(U256::from(5)), Token::Uint((*class).into())]
+        }
+        PublicValue::PerturbationBound => [Token::Uint(U256::from(6)), Token::Uint(U256::zero())],
+        PublicValue::ImageCommitment => [Token::Uint(U256::from(7)), Token::Uint(U256::zero())],
+        PublicValue::PreviousChainingValue => {
+            [Token::Uint(U256::from(8)), Token::Uint(U256::zero())]
+        }
+    });
+    let words: Vec<_> = [Token::Uint(layout.num_columns.into())]
+        .into_iter()
+        .chain(values)
+        .collect();
+    keccak256(encode(&words))
 }
 
 /// Dry runs a given EVM contract locally using `revm`, returning the gas used.
@@ -138,10 +373,12 @@ pub fn dry_run_verifier(
 }
 
 fn print_receipt(receipt: TransactionReceipt) {
-    println!("== Transaction summary");
-    println!("  Transaction hash: {:?}", receipt.transaction_hash);
-    println!("  Included in block: {:?}", receipt.block_number);
-    println!("  Gas used: {:?}", receipt.gas_used.unwrap());
+    info!(
+        transaction_hash = ?receipt.transaction_hash,
+        block_number = ?receipt.block_number,
+        gas_used = ?receipt.gas_used.unwrap(),
+        "Transaction included"
+    );
 }
 
 type ConcreteMiddleware = SignerMiddleware<Provider<Http>, Wallet<SigningKey>>;
@@ -196,7 +433,7 @@ impl EthClient {
 
         let (contract, deploy_receipt) = factory.deploy(())?.send_with_receipt().await?;
         print_receipt(deploy_receipt);
-        println!("Deployed to address: {:?}", contract.address());
+        info!("Deployed to address: {:?}", contract.address());
 
         Ok(contract.address())
     }
@@ -225,3 +462,75 @@ impl EthClient {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Address;
+
+    use super::{instance_layout_hash, RegistrationPayload};
+    use crate::gadgets::wnn::{InstanceLayout, PublicValue};
+
+    // The expected values were computed independently of this crate, as the keccak256 hash of
+    // the ABI encoding described in the documentation (i.e. what a Solidity registry computes).
+
+    #[test]
+    fn test_instance_layout_hash() {
+        let scores = |num_columns| InstanceLayout {
+            values: vec![
+                PublicValue::Score { class: 0 },
+                PublicValue::Score { class: 1 },
+            ],
+            num_columns,
+        };
+        // keccak256(abi.encode(1, 0, 0, 0, 1))
+        assert_eq!(
+            hex::encode(instance_layout_hash(&scores(1))),
+            "074fc334a5c6f8cba68cd3247936f7e8e14c661ae398b40855cdf424ae927f4b"
+        );
+        // keccak256(abi.encode(2, 0, 0, 0, 1))
+        assert_eq!(
+            hex::encode(instance_layout_hash(&scores(2))),
+            "d589b08b2cc605aee81bf9be1bf106c06d8c7415390d32fa15dafb692b57719e"
+        );
+
+        let all_values = InstanceLayout {
+            values: vec![
+                PublicValue::Score { class: 0 },
+                PublicValue::ChainingValue,
+                PublicValue::Label { class: 1 },
+                PublicValue::Output,
+                PublicValue::Occluded { pixel: 2 },
+                PublicValue::PerturbedScore { class: 1 },
+                PublicValue::PerturbationBound,
+            ],
+            num_columns: 2,
+        };
+        // keccak256(abi.encode(2, 0, 0, 1, 0, 2, 1, 3, 0, 4, 2, 5, 1, 6, 0))
+        assert_eq!(
+            hex::encode(instance_layout_hash(&all_values)),
+            "a754bc41c0e359a38630693991667eefa6220199efd17a21256c2a2314d87856"
+        );
+    }
+
+    #[test]
+    fn test_registration_payload() {
+        let payload = RegistrationPayload {
+            vk_fingerprint: [1; 32],
+            model_commitment: [2; 32],
+            instance_layout_hash: [3; 32],
+            verifier: Address::from_low_u64_be(0x1234),
+        };
+        let slots = payload.slot_values();
+        assert_eq!(slots[..3], [[1; 32], [2; 32], [3; 32]]);
+        assert_eq!(
+            hex::encode(slots[3]),
+            "0000000000000000000000000000000000000000000000000000000000001234"
+        );
+
+        // abi.encodeWithSignature("register(bytes32,bytes32,bytes32,address)", ...)
+        let calldata = payload.encode_register_call();
+        assert_eq!(hex::encode(&calldata[..4]), "3aa8cd8b");
+        assert_eq!(calldata.len(), 4 + 4 * 32);
+        assert_eq!(calldata[4..], slots.concat());
+    }
+}
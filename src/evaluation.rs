@@ -0,0 +1,258 @@
+//! Evaluation of the accuracy of a model on a labeled dataset, see [`crate::Wnn::evaluate`].
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Precision and recall of a single class.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassMetrics {
+    /// Fraction of the images predicted as this class that actually belong to it
+    /// (0 if no image was predicted as this class).
+    pub precision: f64,
+    /// Fraction of the images of this class that were predicted correctly
+    /// (0 if there is no image of this class).
+    pub recall: f64,
+    /// Number of images of this class.
+    pub support: usize,
+}
+
+/// The result of evaluating a model on a labeled dataset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalReport {
+    /// Number of images for each pair of true class (row) and predicted class (column).
+    pub confusion_matrix: Vec<Vec<usize>>,
+}
+
+impl EvalReport {
+    /// Creates an empty report for the given number of classes.
+    pub fn new(num_classes: usize) -> Self {
+        Self {
+            confusion_matrix: vec![vec![0; num_classes]; num_classes],
+        }
+    }
+
+    /// Records a single prediction.
+    /// Labels outside of the range of known classes add a new class.
+    pub fn add(&mut self, label: usize, prediction: usize) {
+        let num_classes = self.num_classes().max(label + 1).max(prediction + 1);
+        if num_classes > self.num_classes() {
+            for row in &mut self.confusion_matrix {
+                row.resize(num_classes, 0);
+            }
+            self.confusion_matrix
+                .resize(num_classes, vec![0; num_classes]);
+        }
+        self.confusion_matrix[label][prediction] += 1;
+    }
+
+    pub fn num_classes(&self) -> usize {
+        self.confusion_matrix.len()
+    }
+
+    /// The total number of images.
+    pub fn total(&self) -> usize {
+        self.confusion_matrix.iter().flatten().sum()
+    }
+
+    /// The number of correctly predicted images.
+    pub fn correct(&self) -> usize {
+        (0..self.num_classes())
+            .map(|c| self.confusion_matrix[c][c])
+            .sum()
+    }
+
+    /// The fraction of correctly predicted images (0 for an empty dataset).
+    pub fn accuracy(&self) -> f64 {
+        ratio(self.correct(), self.total())
+    }
+
+    /// Precision and recall for each class.
+    pub fn class_metrics(&self) -> Vec<ClassMetrics> {
+        (0..self.num_classes())
+            .map(|c| {
+                let correct = self.confusion_matrix[c][c];
+                let support = self.confusion_matrix[c].iter().sum();
+                let predicted = self.confusion_matrix.iter().map(|row| row[c]).sum();
+                ClassMetrics {
+                    precision: ratio(correct, predicted),
+                    recall: ratio(correct, support),
+                    support,
+                }
+            })
+            .collect()
+    }
+
+    /// Writes the report as JSON, including the derived metrics.
+    pub fn write_json(&self, writer: impl Write) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            accuracy: f64,
+            correct: usize,
+            total: usize,
+            classes: Vec<ClassMetrics>,
+            confusion_matrix: &'a Vec<Vec<usize>>,
+        }
+
+        let json = Json {
+            accuracy: self.accuracy(),
+            correct: self.correct(),
+            total: self.total(),
+            classes: self.class_metrics(),
+            confusion_matrix: &self.confusion_matrix,
+        };
+        serde_json::to_writer_pretty(writer, &json)?;
+        Ok(())
+    }
+
+    /// Writes the report as CSV, with one line per class containing the metrics
+    /// and the row of the confusion matrix.
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        write!(writer, "class,support,precision,recall")?;
+        for c in 0..self.num_classes() {
+            write!(writer, ",predicted_{c}")?;
+        }
+        writeln!(writer)?;
+
+        for (c, metrics) in self.class_metrics().into_iter().enumerate() {
+            write!(
+                writer,
+                "{c},{},{},{}",
+                metrics.support, metrics.precision, metrics.recall
+            )?;
+            for count in &self.confusion_matrix[c] {
+                write!(writer, ",{count}")?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the report to file, as CSV if the extension is `.csv` and as JSON otherwise.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if path
+            .extension()
+            .map_or(false, |extension| extension == "csv")
+        {
+            self.write_csv(&mut writer)?;
+        } else {
+            self.write_json(&mut writer)?;
+        }
+        writer.flush()
+    }
+}
+
+/// Prints the accuracy, the metrics of each class and the confusion matrix as tables.
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Accuracy: {} / {} ({:.2}%)",
+            self.correct(),
+            self.total(),
+            100.0 * self.accuracy()
+        )?;
+
+        writeln!(f, "\nClass  Precision  Recall  Support")?;
+        for (c, metrics) in self.class_metrics().into_iter().enumerate() {
+            writeln!(
+                f,
+                "{c:>5}  {:>9.4}  {:>6.4}  {:>7}",
+                metrics.precision, metrics.recall, metrics.support
+            )?;
+        }
+
+        // Rows are true classes, columns are predicted classes
+        let width = self
+            .confusion_matrix
+            .iter()
+            .flatten()
+            .max()
+            .map_or(1, |max| max.to_string().len())
+            .max(self.num_classes().to_string().len());
+        write!(
+            f,
+            "\nConfusion matrix (rows: true class, columns: predicted class)\n{:>width$}",
+            ""
+        )?;
+        for c in 0..self.num_classes() {
+            write!(f, " {c:>width$}")?;
+        }
+        writeln!(f)?;
+        for (c, row) in self.confusion_matrix.iter().enumerate() {
+            write!(f, "{c:>width$}")?;
+            for count in row {
+                write!(f, " {count:>width$}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EvalReport;
+
+    fn report() -> EvalReport {
+        let mut report = EvalReport::new(2);
+        for (label, prediction) in [(0, 0), (0, 0), (0, 1), (1, 1), (2, 1)] {
+            report.add(label, prediction);
+        }
+        report
+    }
+
+    #[test]
+    fn test_metrics() {
+        let report = report();
+        assert_eq!(
+            report.confusion_matrix,
+            vec![vec![2, 1, 0], vec![0, 1, 0], vec![0, 1, 0]]
+        );
+        assert_eq!(report.accuracy(), 0.6);
+
+        let metrics = report.class_metrics();
+        assert_eq!(metrics[0].precision, 1.0);
+        assert_eq!(metrics[0].recall, 2.0 / 3.0);
+        assert_eq!(metrics[1].precision, 1.0 / 3.0);
+        assert_eq!(metrics[1].recall, 1.0);
+        assert_eq!(metrics[2].precision, 0.0);
+        assert_eq!(metrics[2].support, 1);
+    }
+
+    #[test]
+    fn test_display() {
+        let output = report().to_string();
+        assert!(output.starts_with("Accuracy: 3 / 5 (60.00%)"));
+        assert!(output.ends_with("  0 1 2\n0 2 1 0\n1 0 1 0\n2 0 1 0\n"));
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut csv = vec![];
+        report().write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv)
+                .unwrap()
+                .lines()
+                .take(2)
+                .collect::<Vec<_>>(),
+            vec![
+                "class,support,precision,recall,predicted_0,predicted_1,predicted_2",
+                "0,3,1,0.6666666666666666,2,1,0"
+            ]
+        );
+    }
+}
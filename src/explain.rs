@@ -0,0 +1,122 @@
+//! Explains predictions by the individual bloom filter responses, see [`FilterResponses`].
+//!
+//! Every filter sees a fixed tuple of pixels (in the thermometer encoding), so the positive
+//! responses of a class can be mapped back to the image regions that drove its score.
+
+use halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr as Fp};
+use ndarray::Array2;
+
+use crate::error::ZeroGError;
+use crate::wnn::Wnn;
+
+/// The response of every bloom filter to an image, see [`Wnn::predict_with_responses`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterResponses {
+    /// The response of each filter, indexed by class and filter.
+    pub responses: Array2<bool>,
+    /// The pixels `(row, column)` that are inputs to each filter, see [`Wnn::filter_pixels`].
+    pub filter_pixels: Vec<Vec<(usize, usize)>>,
+    /// `(rows, columns)` of the image.
+    pub image_shape: (usize, usize),
+}
+
+impl FilterResponses {
+    pub fn new(wnn: &Wnn, responses: Array2<bool>) -> Self {
+        Self {
+            filter_pixels: (0..responses.ncols())
+                .map(|filter| wnn.filter_pixels(filter))
+                .collect(),
+            responses,
+            image_shape: wnn.img_shape(),
+        }
+    }
+
+    /// The score of each class, i.e. the number of positive responses.
+    pub fn scores(&self) -> Vec<u64> {
+        self.responses
+            .rows()
+            .into_iter()
+            .map(|responses| responses.iter().filter(|r| **r).count() as u64)
+            .collect()
+    }
+
+    /// The filters of the class that responded positively.
+    pub fn positive_filters(&self, class: usize) -> Vec<usize> {
+        self.responses
+            .row(class)
+            .iter()
+            .enumerate()
+            .filter(|(_, response)| **response)
+            .map(|(filter, _)| filter)
+            .collect()
+    }
+
+    /// For each pixel, the number of positive filters of the class that it is an input to.
+    pub fn heatmap(&self, class: usize) -> Array2<u32> {
+        let mut heatmap = Array2::zeros(self.image_shape);
+        for filter in self.positive_filters(class) {
+            for pixel in &self.filter_pixels[filter] {
+                heatmap[*pixel] += 1;
+            }
+        }
+        heatmap
+    }
+}
+
+/// Debug mode: Synthesizes the circuit with the [`MockProver`] and returns the filter responses
+/// it witnesses, which should equal [`Wnn::predict_with_responses`].
+pub fn circuit_filter_responses(
+    wnn: &Wnn,
+    image: &Array2<u8>,
+    k: u32,
+) -> Result<FilterResponses, ZeroGError> {
+    let (circuit, capture) = wnn.get_circuit(image).capture_responses();
+    MockProver::<Fp>::run(k, &circuit, wnn.public_inputs(image)).map_err(|source| {
+        ZeroGError::Plonk {
+            action: "Synthesizing the circuit",
+            source,
+        }
+    })?;
+    let responses = capture
+        .responses()
+        .expect("The circuit is synthesized with witnesses");
+    Ok(FilterResponses::new(wnn, responses))
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::FilterResponses;
+
+    #[test]
+    fn test_heatmap() {
+        let responses = FilterResponses {
+            responses: array![[true, false], [true, true]],
+            filter_pixels: vec![vec![(0, 0), (0, 1)], vec![(0, 1), (1, 1)]],
+            image_shape: (2, 2),
+        };
+        assert_eq!(responses.scores(), vec![1, 2]);
+        assert_eq!(responses.positive_filters(0), vec![0]);
+        assert_eq!(responses.heatmap(0), array![[1, 1], [0, 0]]);
+        assert_eq!(responses.heatmap(1), array![[1, 2], [0, 1]]);
+    }
+
+    #[cfg(feature = "hdf5")]
+    #[test]
+    fn test_circuit_responses_match_predict() {
+        use std::path::Path;
+
+        use super::circuit_filter_responses;
+        use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+        use crate::{load_grayscale_image, load_wnn};
+
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let image = load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap();
+
+        let expected = wnn.predict_with_responses(&image);
+        assert_eq!(expected.scores(), wnn.predict(&image));
+        assert_eq!(circuit_filter_responses(&wnn, &image, k).unwrap(), expected);
+    }
+}
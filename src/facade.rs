@@ -0,0 +1,124 @@
+//! High-level entry points that hide the management of the SRS, the keys and the circuit
+//! params, see [`ZeroG`].
+
+use std::path::Path;
+
+use crate::error::ZeroGError;
+use crate::gadgets::wnn::PublicValue;
+use crate::proof_file::ProofFile;
+use crate::prover::Prover;
+use crate::utils::to_u32;
+use crate::verifier_bundle::{VerificationError, VerifierBundle};
+
+/// Creates a [`Prover`] or a [`Verifier`].
+///
+/// # Example
+/// ```no_run
+/// use std::path::Path;
+/// use zero_g::{load_grayscale_image, ZeroG};
+///
+/// // The artifact directory is written by `zero_g setup`
+/// let prover = ZeroG::prover("model.zgm", "artifacts").unwrap();
+/// let image = load_grayscale_image(Path::new("image.png")).unwrap();
+/// let proof = prover.prove(&image.into()).unwrap();
+///
+/// let verifier = ZeroG::verifier("verifier.zgvb").unwrap();
+/// let scores = verifier.verify(&proof).unwrap();
+/// ```
+pub struct ZeroG;
+
+impl ZeroG {
+    /// Loads the model and the artifacts written by [`crate::setup::setup`], see
+    /// [`Prover::open`].
+    pub fn prover(
+        model_path: impl AsRef<Path>,
+        artifact_dir: impl AsRef<Path>,
+    ) -> Result<Prover, ZeroGError> {
+        Prover::open(model_path.as_ref(), artifact_dir.as_ref())
+    }
+
+    /// Loads a verifier bundle (e.g. written by `zero_g generate-keys --bundle-path` or
+    /// [`Prover::verifier_bundle`]).
+    pub fn verifier(bundle_path: impl AsRef<Path>) -> Result<Verifier, ZeroGError> {
+        let path = bundle_path.as_ref();
+        let bundle = VerifierBundle::read(path).map_err(|source| ZeroGError::Format {
+            path: path.to_path_buf(),
+            format: "verifier bundle",
+            source: Box::new(source),
+        })?;
+        Ok(Verifier::new(bundle))
+    }
+}
+
+/// Verifies proofs of a fixed model.
+pub struct Verifier {
+    bundle: VerifierBundle,
+}
+
+impl Verifier {
+    pub fn new(bundle: VerifierBundle) -> Self {
+        Self { bundle }
+    }
+
+    pub fn bundle(&self) -> &VerifierBundle {
+        &self.bundle
+    }
+
+    /// Verifies the proof and returns the proven score of each class. For multi-label models,
+    /// these are the labels instead (1 if the class applies, 0 otherwise), and for regression
+    /// models, the only value is the output.
+    pub fn verify(&self, proof: &ProofFile) -> Result<Vec<u64>, VerificationError> {
+        self.bundle.verify(proof)?;
+        let mut scores = vec![0; self.bundle.circuit_params.n_classes];
+        for (value, input) in self
+            .bundle
+            .instance_layout
+            .values
+            .iter()
+            .zip(&proof.public_inputs)
+        {
+            match value {
+                PublicValue::Score { class } | PublicValue::Label { class } => {
+                    scores[*class] = to_u32(input) as u64
+                }
+                PublicValue::Output => scores[0] = to_u32(input) as u64,
+                PublicValue::Occluded { .. }
+                | PublicValue::PerturbedScore { .. }
+                | PublicValue::PerturbationBound => {}
+                PublicValue::ImageCommitment
+                | PublicValue::PreviousChainingValue
+                | PublicValue::ChainingValue => {}
+            }
+        }
+        Ok(scores)
+    }
+}
+
+#[cfg(all(test, feature = "hdf5"))]
+mod tests {
+    use std::{env, fs, path::Path, process};
+
+    use super::ZeroG;
+    use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+    use crate::load_grayscale_image;
+    use crate::setup::{setup, SrsSource};
+
+    #[test]
+    fn test_prove_and_verify() {
+        let (k, model_path) = MNIST_TINY;
+        let dir = env::temp_dir().join(format!("zero_g_facade_{}", process::id()));
+        let prover_dir = dir.join("artifacts");
+        let wnn = crate::load_wnn(Path::new(model_path)).unwrap();
+        setup(&wnn, Some(k), &SrsSource::Generate, &prover_dir).unwrap();
+
+        let prover = ZeroG::prover(model_path, &prover_dir).unwrap();
+        let image = load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap();
+        let proof = prover.prove(&image.clone().into()).unwrap();
+        let bundle_path = dir.join("verifier.zgvb");
+        prover.verifier_bundle().write(&bundle_path).unwrap();
+        let scores = ZeroG::verifier(&bundle_path).unwrap().verify(&proof);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(scores.unwrap(), wnn.predict(&image));
+    }
+}
@@ -1,14 +1,25 @@
 //! Collection of Halo2 gadgets.
 //!
 //! [`WnnChip`] is the main gadget for WNNs; [`WnnCircuit`] is the corresponding circuit.
+//! Some of its building blocks can be used on their own, e.g. the [`hash::HashChip`] (see
+//! [`hash::HashCircuit`] for a standalone example) and the [`bloom_filter::BloomFilterChip`]
+//! (see [`bloom_filter::AllowListCircuit`]).
 
+pub mod annotations;
 pub mod bits2num;
 pub mod bloom_filter;
+pub mod byte_table;
+pub mod encode_features;
 pub mod encode_image;
 pub mod greater_than;
 pub mod hash;
+pub mod image_commitment;
+pub mod mask;
+pub mod perturbation;
+pub mod poseidon;
 pub mod range_check;
 pub mod response_accumulator;
+pub mod threshold;
 pub mod wnn;
 
 pub use wnn::{WnnChip, WnnCircuit};
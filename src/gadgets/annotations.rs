@@ -0,0 +1,240 @@
+//! Descriptive region names, to make [`halo2_proofs::dev::MockProver`] failures and layout
+//! plots interpretable.
+//!
+//! The chips wrap their regions in namespaces that name the chip and the pixel, filter or
+//! class a region belongs to, e.g. `BloomFilterChip class 3 filter 17`. halo2 itself drops
+//! namespaces when reporting failures, so [`AnnotatedLayouter`] prefixes every region name
+//! with the current namespaces instead, resulting in names like
+//! `BloomFilterChip class 3 filter 17/look up hash values`. [`RegionPath`] parses them back.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use ff::Field;
+use halo2_proofs::{
+    circuit::{Cell, Layouter, Region, Table, Value},
+    plonk::{Challenge, Column, Error, Instance},
+};
+
+/// Separates the namespaces and the region name in an annotated region name.
+const SEPARATOR: char = '/';
+
+/// The chips that annotate their regions, see [`RegionPath::chip`].
+const CHIPS: [&str; 6] = [
+    "EncodeImageChip",
+    "Bits2NumChip",
+    "HashChip",
+    "BloomFilterChip",
+    "ResponseAccumulatorChip",
+    "WnnChip",
+];
+
+/// A [`Layouter`] that prefixes the names of all regions and tables with the namespaces they
+/// are assigned in. If disabled, names are passed through unchanged and namespaces are not
+/// evaluated, so that annotations cost nothing when proving.
+#[derive(Debug)]
+pub struct AnnotatedLayouter<F: Field, L: Layouter<F>> {
+    inner: L,
+    enabled: bool,
+    namespaces: Vec<String>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: Field, L: Layouter<F>> AnnotatedLayouter<F, L> {
+    pub fn new(inner: L, enabled: bool) -> Self {
+        Self {
+            inner,
+            enabled,
+            namespaces: vec![],
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: Field, L: Layouter<F>> Layouter<F> for AnnotatedLayouter<F, L> {
+    type Root = Self;
+
+    fn assign_region<A, AR, N, NR>(&mut self, name: N, assignment: A) -> Result<AR, Error>
+    where
+        A: FnMut(Region<'_, F>) -> Result<AR, Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        let namespaces = &self.namespaces;
+        self.inner
+            .assign_region(|| qualify(namespaces, name().into()), assignment)
+    }
+
+    fn assign_table<A, N, NR>(&mut self, name: N, assignment: A) -> Result<(), Error>
+    where
+        A: FnMut(Table<'_, F>) -> Result<(), Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        let namespaces = &self.namespaces;
+        self.inner
+            .assign_table(|| qualify(namespaces, name().into()), assignment)
+    }
+
+    fn constrain_instance(
+        &mut self,
+        cell: Cell,
+        column: Column<Instance>,
+        row: usize,
+    ) -> Result<(), Error> {
+        self.inner.constrain_instance(cell, column, row)
+    }
+
+    fn get_challenge(&self, challenge: Challenge) -> Value<F> {
+        self.inner.get_challenge(challenge)
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        if self.enabled {
+            let name: String = name_fn().into();
+            self.namespaces.push(name.clone());
+            self.inner.get_root().push_namespace(|| name);
+        } else {
+            self.inner.get_root().push_namespace(name_fn);
+        }
+    }
+
+    fn pop_namespace(&mut self, gadget_name: Option<String>) {
+        if self.enabled {
+            self.namespaces.pop();
+        }
+        self.inner.get_root().pop_namespace(gadget_name)
+    }
+}
+
+/// Prefixes `name` with the namespaces.
+fn qualify(namespaces: &[String], name: String) -> String {
+    let mut qualified = String::new();
+    for namespace in namespaces {
+        qualified.push_str(namespace);
+        qualified.push(SEPARATOR);
+    }
+    qualified.push_str(&name);
+    qualified
+}
+
+/// A region name written by [`AnnotatedLayouter`], split into the chip and the semantic
+/// indices found in its namespaces.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegionPath {
+    /// The outermost chip the region belongs to, if any.
+    pub chip: Option<&'static str>,
+    /// The namespaces, outermost first.
+    pub namespaces: Vec<String>,
+    /// The name the region was assigned with.
+    pub region: String,
+    /// The pixel `(row, column)`.
+    pub pixel: Option<(usize, usize)>,
+    /// The index of the threshold (i.e. of the bit) of the pixel.
+    pub bit: Option<usize>,
+    pub filter: Option<usize>,
+    pub class: Option<usize>,
+}
+
+impl RegionPath {
+    pub fn parse(name: &str) -> Self {
+        let mut namespaces: Vec<_> = name.split(SEPARATOR).map(str::to_string).collect();
+        let region = namespaces.pop().unwrap_or_default();
+        let mut path = Self {
+            region,
+            ..Default::default()
+        };
+        for namespace in &namespaces {
+            if path.chip.is_none() {
+                path.chip = CHIPS
+                    .into_iter()
+                    .find(|chip| namespace.split(' ').next() == Some(*chip));
+            }
+            path.pixel = path.pixel.or_else(|| parse_pixel(namespace));
+            path.bit = path.bit.or_else(|| parse_index(namespace, "bit"));
+            path.filter = path.filter.or_else(|| parse_index(namespace, "filter"));
+            path.class = path.class.or_else(|| parse_index(namespace, "class"));
+        }
+        path.namespaces = namespaces;
+        path
+    }
+
+    /// Whether any semantic index is known.
+    pub fn has_index(&self) -> bool {
+        self.pixel.is_some() || self.bit.is_some() || self.filter.is_some() || self.class.is_some()
+    }
+}
+
+impl fmt::Display for RegionPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        if let Some((row, column)) = self.pixel {
+            parts.push(format!("pixel ({row}, {column})"));
+        }
+        if let Some(bit) = self.bit {
+            parts.push(format!("bit {bit}"));
+        }
+        if let Some(class) = self.class {
+            parts.push(format!("class {class}"));
+        }
+        if let Some(filter) = self.filter {
+            parts.push(format!("filter {filter}"));
+        }
+        write!(f, "'{}'", self.region)?;
+        if !parts.is_empty() {
+            write!(f, " ({})", parts.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `<keyword> <index>` from a namespace like `HashChip filter 3`.
+fn parse_index(namespace: &str, keyword: &str) -> Option<usize> {
+    let mut words = namespace.split(' ');
+    words.find(|word| *word == keyword)?;
+    words.next()?.parse().ok()
+}
+
+/// Parses `pixel (<row>, <column>)` from a namespace.
+fn parse_pixel(namespace: &str) -> Option<(usize, usize)> {
+    let start = namespace.find("pixel (")? + "pixel (".len();
+    let end = start + namespace[start..].find(')')?;
+    let (row, column) = namespace[start..end].split_once(", ")?;
+    Some((row.parse().ok()?, column.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RegionPath;
+
+    #[test]
+    fn test_parse_region_path() {
+        let path = RegionPath::parse("EncodeImageChip/pixel (3, 14) bit 1/greater_than_witness");
+        assert_eq!(path.chip, Some("EncodeImageChip"));
+        assert_eq!(path.pixel, Some((3, 14)));
+        assert_eq!(path.bit, Some(1));
+        assert_eq!(path.filter, None);
+        assert_eq!(path.region, "greater_than_witness");
+        assert_eq!(
+            path.to_string(),
+            "'greater_than_witness' (pixel (3, 14), bit 1)"
+        );
+
+        let path = RegionPath::parse("BloomFilterChip class 2 filter 7/look up hash values");
+        assert_eq!(path.chip, Some("BloomFilterChip"));
+        assert_eq!((path.class, path.filter), (Some(2), Some(7)));
+
+        let path = RegionPath::parse("hash");
+        assert_eq!(path.chip, None);
+        assert!(!path.has_index());
+        assert_eq!(path.region, "hash");
+    }
+}
@@ -8,28 +8,64 @@
 //!   the lookup table stores larger words which are decomposed into bytes and bits.
 //!   A hyperparameter trades off the number of advice rows and the number of table rows,
 //!   which is set automatically such that the two are roughly equal.
+//!   With [`BloomFilterChip::configure_with_class_lookup`], the words of all classes are looked
+//!   up at once (see [`ClassLookupChip`]).
 //!
 //! Both gadgets implement the [`BloomFilterInstructions`] trait and can be used interchangibly.
+//!
+//! [`BloomFilterChip`] can also be used outside of the WNN circuit, as a lookup of bits in
+//! fixed tables (see [`BloomFilterChip::bit_lookup`]), which are committed to in the verifying
+//! key. For example, encoding a set as a table whose bit `x` is set iff `x` is in the set
+//! proves set membership, as in [`AllowListCircuit`]:
+//!
+//! ```
+//! use halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr as Fp};
+//! use zero_g::gadgets::bloom_filter::AllowListCircuit;
+//!
+//! // Members have to be smaller than 2^10
+//! let allow_list = [3, 14, 159, 265];
+//!
+//! // The public input is 1 iff the private value is on the allow-list
+//! let circuit = AllowListCircuit::<Fp>::new(&allow_list, 10, 159);
+//! MockProver::run(14, &circuit, vec![vec![Fp::from(1)]])
+//!     .unwrap()
+//!     .assert_satisfied();
+//!
+//! let circuit = AllowListCircuit::<Fp>::new(&allow_list, 10, 42);
+//! MockProver::run(14, &circuit, vec![vec![Fp::from(0)]])
+//!     .unwrap()
+//!     .assert_satisfied();
+//! ```
+use std::marker::PhantomData;
+use std::sync::Arc;
+
 use ff::PrimeFieldBits;
 use halo2_proofs::{
-    circuit::{AssignedCell, Layouter},
-    plonk::{Advice, Column, ConstraintSystem, Error, TableColumn},
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
 };
-use ndarray::Array2;
+use ndarray::{Array2, Axis};
+
+use crate::gadgets::byte_table::{ByteTable, ByteTableConfig};
+use crate::packed_bloom_filters::PackedBloomFilters;
+use crate::utils::to_u32;
 
 pub use self::{
     and_bits::{AndBitsChip, AndBitsChipConfig, AndBitsInstruction},
     array_lookup::{
         ArrayLookupChip, ArrayLookupChipConfig, ArrayLookupConfig, ArrayLookupInstructions,
+        LookupResult,
     },
     bit_selector::{BitSelectorChip, BitSelectorChipConfig, BitSelectorInstructions},
     byte_selector::{ByteSelectorChip, ByteSelectorChipConfig, ByteSelectorInstructions},
+    class_lookup::{ClassLookupChip, ClassLookupChipConfig, ClassLookupResult},
 };
 
 pub mod and_bits;
 pub mod array_lookup;
 pub mod bit_selector;
 pub mod byte_selector;
+pub mod class_lookup;
 pub mod single_bit_bloom_filter;
 
 /// Configuration of the bloom filter.
@@ -57,15 +93,77 @@ pub trait BloomFilterInstructions<F: PrimeFieldBits> {
     ) -> Result<AssignedCell<F, F>, Error>;
 }
 
+/// The gadget used to look up the words of the bloom filters.
+#[derive(Debug, Clone)]
+enum WordLookupConfig {
+    /// One lookup per class, see [`ArrayLookupChip`].
+    PerClass(ArrayLookupChipConfig),
+    /// One lookup for all classes, see [`ClassLookupChip`].
+    AllClasses(ClassLookupChipConfig),
+}
+
+enum WordLookupChip<F: PrimeFieldBits> {
+    PerClass(ArrayLookupChip<F>),
+    AllClasses(ClassLookupChip<F>),
+}
+
+/// The bloom filters, packed into the words of the lookup table.
+///
+/// Packing is the most expensive image-independent part of synthesizing the WNN circuit, so the
+/// words can be computed once per model and shared between circuits, see
+/// [`BloomFilterChip::construct_with_words`].
+#[derive(Debug, Clone)]
+pub struct BloomFilterWords<F: PrimeFieldBits> {
+    n_classes: usize,
+    n_filters: usize,
+    words: PackedWords<F>,
+}
+
+#[derive(Debug, Clone)]
+enum PackedWords<F> {
+    /// See [`ArrayLookupChip::compute_bloom_filter_words`].
+    PerClass(Arc<Vec<Vec<F>>>),
+    /// See [`ClassLookupChip::compute_bloom_filter_words`].
+    AllClasses(Arc<Vec<Vec<Vec<F>>>>),
+}
+
+impl<F: PrimeFieldBits> BloomFilterWords<F> {
+    /// Packs the bloom filters for a chip configured with [`BloomFilterChip::configure`] (or,
+    /// if `class_lookup` is set, [`BloomFilterChip::configure_with_class_lookup`]) with the
+    /// given config.
+    pub fn new(
+        bloom_filters: &PackedBloomFilters,
+        bloom_filter_config: BloomFilterConfig,
+        class_lookup: bool,
+    ) -> Self {
+        let [n_classes, n_filters, _] = bloom_filters.shape();
+        let array_lookup_config = ArrayLookupConfig::from(bloom_filter_config);
+        let words = if class_lookup {
+            PackedWords::AllClasses(Arc::new(ClassLookupChip::compute_bloom_filter_words(
+                bloom_filters,
+                &array_lookup_config,
+            )))
+        } else {
+            PackedWords::PerClass(Arc::new(ArrayLookupChip::compute_bloom_filter_words(
+                bloom_filters,
+                array_lookup_config.bits_per_hash,
+                array_lookup_config.word_index_bits,
+            )))
+        };
+        Self {
+            n_classes,
+            n_filters,
+            words,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BloomFilterChipConfig {
-    array_lookup_config: ArrayLookupChipConfig,
+    word_lookup_config: WordLookupConfig,
     byte_selector_config: ByteSelectorChipConfig,
     bit_selector_config: BitSelectorChipConfig,
     and_bits_config: AndBitsChipConfig,
-
-    // A column of all bytes (not unique). Public so that it can be reused by other gadgets.
-    pub byte_column: TableColumn,
 }
 
 /// Implements a bloom filter lookup using a 3-way lookup strategy.
@@ -78,46 +176,114 @@ pub struct BloomFilterChipConfig {
 /// 3. The [`BitSelectorChip`] is used to select the bit using a table
 ///    lookup.
 /// 4. The [`AndBitsChip`] is used to and together the bits.
+///
+/// If configured with [`BloomFilterChip::configure_with_class_lookup`], step 1 uses the
+/// [`ClassLookupChip`] instead, which looks up the words of all classes at once, see
+/// [`BloomFilterChip::bloom_lookup_all_classes`].
 pub struct BloomFilterChip<F: PrimeFieldBits> {
-    array_lookup_chip: ArrayLookupChip<F>,
+    word_lookup_chip: WordLookupChip<F>,
+    n_classes: usize,
+    n_filters: usize,
     byte_selector_chip: ByteSelectorChip<F>,
     bit_selector_chip: BitSelectorChip<F>,
     and_bits_chip: AndBitsChip<F>,
+    /// The response `(class, filter)` to flip, see [`BloomFilterChip::tamper_response`].
+    tampered_response: Option<(usize, usize)>,
 }
 
 impl<F: PrimeFieldBits> BloomFilterChip<F> {
     /// Constructs a new bloom filter chip.
-    pub fn construct(config: BloomFilterChipConfig, bloom_filter_arrays: &Array2<bool>) -> Self {
-        let array_lookup_chip =
-            ArrayLookupChip::construct(config.array_lookup_config.clone(), bloom_filter_arrays);
+    /// Constructs the chip for the given bloom filters. The bloom index of the filter
+    /// `(class, filter)` is `class * num_filters + filter`.
+    pub fn construct(config: BloomFilterChipConfig, bloom_filters: &PackedBloomFilters) -> Self {
+        let word_lookup_chip = match &config.word_lookup_config {
+            WordLookupConfig::PerClass(config) => {
+                WordLookupChip::PerClass(ArrayLookupChip::construct(config.clone(), bloom_filters))
+            }
+            WordLookupConfig::AllClasses(config) => WordLookupChip::AllClasses(
+                ClassLookupChip::construct(config.clone(), bloom_filters),
+            ),
+        };
+        let [n_classes, n_filters, _] = bloom_filters.shape();
+        Self::with_word_lookup_chip(config, word_lookup_chip, n_classes, n_filters)
+    }
+
+    /// Like [`BloomFilterChip::construct`], but with bloom filters that have already been
+    /// packed for the same config.
+    ///
+    /// Panics if the words were packed for a different word lookup (i.e., with or without
+    /// class lookup).
+    pub fn construct_with_words(
+        config: BloomFilterChipConfig,
+        words: &BloomFilterWords<F>,
+    ) -> Self {
+        let word_lookup_chip = match (&config.word_lookup_config, &words.words) {
+            (WordLookupConfig::PerClass(config), PackedWords::PerClass(words)) => {
+                WordLookupChip::PerClass(ArrayLookupChip::construct_with_words(
+                    config.clone(),
+                    words.clone(),
+                ))
+            }
+            (WordLookupConfig::AllClasses(config), PackedWords::AllClasses(words)) => {
+                WordLookupChip::AllClasses(ClassLookupChip::construct_with_words(
+                    config.clone(),
+                    words.clone(),
+                ))
+            }
+            _ => panic!("The bloom filter words were packed for a different word lookup"),
+        };
+        Self::with_word_lookup_chip(config, word_lookup_chip, words.n_classes, words.n_filters)
+    }
+
+    fn with_word_lookup_chip(
+        config: BloomFilterChipConfig,
+        word_lookup_chip: WordLookupChip<F>,
+        n_classes: usize,
+        n_filters: usize,
+    ) -> Self {
         let byte_selector_chip =
             ByteSelectorChip::<F>::construct(config.byte_selector_config.clone());
         let bit_selector_chip = BitSelectorChip::<F>::construct(config.bit_selector_config.clone());
         let and_bits_chip = AndBitsChip::<F>::construct(config.and_bits_config);
 
         Self {
-            array_lookup_chip,
+            word_lookup_chip,
+            n_classes,
+            n_filters,
             byte_selector_chip,
             bit_selector_chip,
             and_bits_chip,
+            tampered_response: None,
         }
     }
 
-    /// Loads all lookup tables.
-    /// Should be called once before [`BloomFilterInstructions::bloom_lookup`]!
-    pub fn load(&mut self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
-        self.array_lookup_chip.load(layouter)?;
+    /// The number of classes and the number of filters per class.
+    pub fn shape(&self) -> (usize, usize) {
+        (self.n_classes, self.n_filters)
+    }
 
-        // The byte selector reuses the bytes table of the bit selector,
-        // so nothing else to be loaded here.
-        self.bit_selector_chip.load(layouter)?;
+    /// Test support: Flips the response of the given class and filter, see
+    /// [`crate::gadgets::wnn::Tampering::BloomResponse`].
+    #[cfg(any(test, feature = "test-support"))]
+    pub(crate) fn tamper_response(&mut self, class: usize, filter: usize) {
+        self.tampered_response = Some((class, filter));
+    }
 
-        Ok(())
+    /// Loads the bloom filter table.
+    /// Should be called once before [`BloomFilterInstructions::bloom_lookup`]!
+    ///
+    /// The byte and bit selectors use the [`ByteTable`], which has to be loaded separately.
+    pub fn load(&mut self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        match &mut self.word_lookup_chip {
+            WordLookupChip::PerClass(chip) => chip.load(layouter),
+            WordLookupChip::AllClasses(chip) => chip.load(layouter),
+        }
     }
 
     pub fn configure(
         meta: &mut ConstraintSystem<F>,
         advice_columns: [Column<Advice>; 6],
+        byte_table: &mut ByteTable<F>,
         bloom_filter_config: BloomFilterConfig,
     ) -> BloomFilterChipConfig {
         let array_lookup_config = ArrayLookupChip::configure(
@@ -129,65 +295,335 @@ impl<F: PrimeFieldBits> BloomFilterChip<F> {
             advice_columns[4],
             bloom_filter_config.into(),
         );
-        let bit_selector_config = BitSelectorChip::configure(
+        Self::configure_selectors(
+            meta,
+            advice_columns,
+            byte_table,
+            WordLookupConfig::PerClass(array_lookup_config),
+        )
+    }
+
+    /// Like [`BloomFilterChip::configure`], but looks up the words of all `n_classes` classes
+    /// at once, using a [`ClassLookupChip`] (which adds `2 * n_classes + 2` columns).
+    pub fn configure_with_class_lookup(
+        meta: &mut ConstraintSystem<F>,
+        advice_columns: [Column<Advice>; 6],
+        byte_table: &mut ByteTable<F>,
+        bloom_filter_config: BloomFilterConfig,
+        n_classes: usize,
+    ) -> BloomFilterChipConfig {
+        let class_lookup_config = ClassLookupChip::configure(
             meta,
             advice_columns[0],
             advice_columns[1],
             advice_columns[2],
+            advice_columns[3],
+            n_classes,
+            bloom_filter_config.into(),
         );
+        Self::configure_selectors(
+            meta,
+            advice_columns,
+            byte_table,
+            WordLookupConfig::AllClasses(class_lookup_config),
+        )
+    }
 
-        // Reuse byte column of bit selector chip
-        let byte_column = bit_selector_config.byte_column;
-
+    fn configure_selectors(
+        meta: &mut ConstraintSystem<F>,
+        advice_columns: [Column<Advice>; 6],
+        byte_table: &mut ByteTable<F>,
+        word_lookup_config: WordLookupConfig,
+    ) -> BloomFilterChipConfig {
+        // The byte lookups read from the last advice column, which other gadgets can use for
+        // their range checks (see `ByteTable`)
+        let bit_selector_config = BitSelectorChip::configure(
+            meta,
+            advice_columns[5],
+            advice_columns[1],
+            advice_columns[2],
+            byte_table,
+        );
         let byte_selector_config = ByteSelectorChip::configure(
             meta,
-            advice_columns[0],
+            advice_columns[5],
             advice_columns[1],
             advice_columns[2],
             advice_columns[3],
             advice_columns[4],
-            advice_columns[5],
-            byte_column,
+            advice_columns[0],
+            byte_table,
         );
         let and_bits_config = AndBitsChip::configure(meta, advice_columns[4], advice_columns[5]);
 
         BloomFilterChipConfig {
-            array_lookup_config,
+            word_lookup_config,
             byte_selector_config,
             bit_selector_config,
             and_bits_config,
-            byte_column,
         }
     }
 }
 
-impl<F: PrimeFieldBits> BloomFilterInstructions<F> for BloomFilterChip<F> {
-    fn bloom_lookup(
+impl<F: PrimeFieldBits> BloomFilterChip<F> {
+    /// Configures the chip to look up single bits in tables of `2^index_bits` bits (i.e.,
+    /// bloom filters with a single hash), see [`BloomFilterChip::bit_lookup`].
+    ///
+    /// As for [`BloomFilterChip::configure`], `index_bits` has to be at least 7.
+    pub fn configure_bit_lookup(
+        meta: &mut ConstraintSystem<F>,
+        advice_columns: [Column<Advice>; 6],
+        byte_table: &mut ByteTable<F>,
+        index_bits: usize,
+    ) -> BloomFilterChipConfig {
+        let bloom_filter_config = BloomFilterConfig {
+            n_hashes: 1,
+            bits_per_hash: index_bits,
+        };
+        Self::configure(meta, advice_columns, byte_table, bloom_filter_config)
+    }
+
+    /// Constructs a chip configured with [`BloomFilterChip::configure_bit_lookup`] for the
+    /// given tables, which has shape `(n_tables, 2^index_bits)`.
+    pub fn construct_bit_lookup(config: BloomFilterChipConfig, tables: &Array2<bool>) -> Self {
+        let bloom_filters = PackedBloomFilters::from(tables.clone().insert_axis(Axis(0)));
+        Self::construct(config, &bloom_filters)
+    }
+
+    /// Looks up bit `index` of the given table, returning a cell that is `1` iff it is set.
+    ///
+    /// The circuit is not satisfied if the index has more than `index_bits` bits, so it does
+    /// not need to be range-checked.
+    pub fn bit_lookup(
         &self,
         layouter: &mut impl Layouter<F>,
-        hash_value: AssignedCell<F, F>,
-        bloom_index: F,
+        index: AssignedCell<F, F>,
+        table: usize,
     ) -> Result<AssignedCell<F, F>, Error> {
-        let lookup_results =
-            self.array_lookup_chip
-                .array_lookup(layouter, hash_value, bloom_index)?;
+        self.bloom_lookup(layouter, index, F::from(table as u64))
+    }
+
+    /// Whether the chip was configured with
+    /// [`BloomFilterChip::configure_with_class_lookup`].
+    pub fn looks_up_all_classes(&self) -> bool {
+        matches!(self.word_lookup_chip, WordLookupChip::AllClasses(_))
+    }
 
+    /// Performs the bloom filter lookup of the given filter for all classes, returning one
+    /// response per class. Only one lookup per hash is needed if the chip was configured with
+    /// [`BloomFilterChip::configure_with_class_lookup`], otherwise this is equivalent to
+    /// calling [`BloomFilterInstructions::bloom_lookup`] for each class.
+    pub fn bloom_lookup_all_classes(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        hash_value: AssignedCell<F, F>,
+        filter_index: usize,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        match &self.word_lookup_chip {
+            WordLookupChip::PerClass(_) => (0..self.n_classes)
+                .map(|class| {
+                    self.bloom_lookup(
+                        &mut layouter.namespace(|| format!("class {class}")),
+                        hash_value.clone(),
+                        F::from((class * self.n_filters + filter_index) as u64),
+                    )
+                })
+                .collect(),
+            WordLookupChip::AllClasses(chip) => {
+                let lookup_results = chip.class_lookup(layouter, hash_value, filter_index)?;
+                (0..self.n_classes)
+                    .map(|class| {
+                        let results = lookup_results.iter().map(|result| LookupResult {
+                            word: result.words[class].clone(),
+                            byte_index: result.byte_index.clone(),
+                            bit_index: result.bit_index.clone(),
+                        });
+                        self.select_and_bits(
+                            &mut layouter.namespace(|| format!("class {class}")),
+                            results,
+                            chip.bytes_per_word(),
+                            self.tampered_response == Some((class, filter_index)),
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Selects the bit of each lookup result and ands them together (negating the result if
+    /// `flip` is set, see [`BloomFilterChip::tamper_response`]).
+    fn select_and_bits(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        lookup_results: impl IntoIterator<Item = LookupResult<F>>,
+        bytes_per_word: usize,
+        flip: bool,
+    ) -> Result<AssignedCell<F, F>, Error> {
         let mut bits = vec![];
         for lookup_result in lookup_results {
             let byte = self.byte_selector_chip.select_byte(
                 layouter,
                 lookup_result.word,
                 lookup_result.byte_index,
-                self.array_lookup_chip.bytes_per_word(),
+                bytes_per_word,
             )?;
             let bit = self
                 .bit_selector_chip
                 .select_bit(layouter, byte, lookup_result.bit_index)?;
             bits.push(bit);
         }
-        let result = self.and_bits_chip.and_bits(layouter, bits)?;
+        self.and_bits_chip.and_bits_with_flip(layouter, bits, flip)
+    }
+}
+
+impl<F: PrimeFieldBits> BloomFilterInstructions<F> for BloomFilterChip<F> {
+    fn bloom_lookup(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        hash_value: AssignedCell<F, F>,
+        bloom_index: F,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let (class, filter) = {
+            let bloom_index = to_u32(&bloom_index) as usize;
+            (bloom_index / self.n_filters, bloom_index % self.n_filters)
+        };
+        let flip = self.tampered_response == Some((class, filter));
+        match &self.word_lookup_chip {
+            WordLookupChip::PerClass(chip) => {
+                let lookup_results = chip.array_lookup(layouter, hash_value, bloom_index)?;
+                self.select_and_bits(layouter, lookup_results, chip.bytes_per_word(), flip)
+            }
+            WordLookupChip::AllClasses(chip) => {
+                // Looks up the words of all classes, but only uses those of one class
+                let lookup_results = chip.class_lookup(layouter, hash_value, filter)?;
+                let results = lookup_results.into_iter().map(|result| LookupResult {
+                    word: result.words[class].clone(),
+                    byte_index: result.byte_index,
+                    bit_index: result.bit_index,
+                });
+                self.select_and_bits(layouter, results, chip.bytes_per_word(), flip)
+            }
+        }
+    }
+}
+
+/// The configuration of [`AllowListCircuit`].
+#[derive(Debug, Clone)]
+pub struct AllowListCircuitConfig {
+    bloom_filter_chip_config: BloomFilterChipConfig,
+    byte_table_config: ByteTableConfig,
+    advice_columns: [Column<Advice>; 6],
+    instance: Column<Instance>,
+}
+
+/// A standalone circuit using [`BloomFilterChip::bit_lookup`]: Proves whether a private value
+/// is on an allow-list, which is fixed in the circuit (and thus the verifying key). The public
+/// input (in the first row of the only instance column) is `1` iff it is.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Debug, Clone)]
+pub struct AllowListCircuit<F: PrimeFieldBits> {
+    /// The allow-list as a table of shape `(1, 2^index_bits)`.
+    table: Array2<bool>,
+    index_bits: usize,
+    value: Value<u64>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeFieldBits> AllowListCircuit<F> {
+    /// Panics if a member of the allow-list or the value has more than `index_bits` bits.
+    pub fn new(allow_list: &[u64], index_bits: usize, value: u64) -> Self {
+        assert!(
+            value < 1 << index_bits,
+            "The value has more than {index_bits} bits"
+        );
+        let mut table = Array2::from_elem((1, 1 << index_bits), false);
+        for &member in allow_list {
+            assert!(
+                member < 1 << index_bits,
+                "{member} has more than {index_bits} bits"
+            );
+            table[[0, member as usize]] = true;
+        }
+        Self {
+            table,
+            index_bits,
+            value: Value::known(value),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeFieldBits> Circuit<F> for AllowListCircuit<F> {
+    type Config = AllowListCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+    /// The number of bits of the values.
+    type Params = usize;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            value: Value::unknown(),
+            ..self.clone()
+        }
+    }
+
+    fn params(&self) -> Self::Params {
+        self.index_bits
+    }
+
+    fn configure_with_params(meta: &mut ConstraintSystem<F>, params: Self::Params) -> Self::Config {
+        let advice_columns = [(); 6].map(|_| meta.advice_column());
+        for advice in advice_columns {
+            meta.enable_equality(advice);
+        }
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let constants = meta.fixed_column();
+        meta.enable_constant(constants);
+
+        let mut byte_table = ByteTable::new(meta);
+        let bloom_filter_chip_config =
+            BloomFilterChip::configure_bit_lookup(meta, advice_columns, &mut byte_table, params);
+        AllowListCircuitConfig {
+            bloom_filter_chip_config,
+            byte_table_config: byte_table.configure(meta),
+            advice_columns,
+            instance,
+        }
+    }
 
-        Ok(result)
+    fn configure(_meta: &mut ConstraintSystem<F>) -> Self::Config {
+        unimplemented!("configure_with_params should be used!")
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let value = layouter.assign_region(
+            || "value",
+            |mut region| {
+                region.assign_advice(
+                    || "value",
+                    config.advice_columns[0],
+                    0,
+                    || self.value.map(F::from),
+                )
+            },
+        )?;
+
+        let mut bloom_filter_chip =
+            BloomFilterChip::construct_bit_lookup(config.bloom_filter_chip_config, &self.table);
+        bloom_filter_chip.load(&mut layouter)?;
+        config.byte_table_config.load(&mut layouter)?;
+
+        let is_member = bloom_filter_chip.bit_lookup(
+            &mut layouter.namespace(|| "allow-list lookup"),
+            value,
+            0,
+        )?;
+        layouter.constrain_instance(is_member.cell(), config.instance, 0)
     }
 }
 
@@ -202,11 +638,15 @@ mod tests {
         halo2curves::bn256::Fr as Fp,
         plonk::{Advice, Circuit, Column, Instance},
     };
-    use ndarray::Array2;
+    use ndarray::{Array2, Axis};
+
+    use crate::packed_bloom_filters::PackedBloomFilters;
 
     use super::{
-        BloomFilterChip, BloomFilterChipConfig, BloomFilterConfig, BloomFilterInstructions,
+        AllowListCircuit, BloomFilterChip, BloomFilterChipConfig, BloomFilterConfig,
+        BloomFilterInstructions,
     };
+    use crate::gadgets::byte_table::{ByteTable, ByteTableConfig};
 
     #[derive(Default)]
     struct MyCircuit<F: PrimeFieldBits> {
@@ -219,6 +659,7 @@ mod tests {
     #[derive(Clone, Debug)]
     struct Config {
         bloom_filter_chip_config: BloomFilterChipConfig,
+        byte_table_config: ByteTableConfig,
         advice_columns: [Column<Advice>; 6],
         instance: Column<Instance>,
     }
@@ -256,11 +697,17 @@ mod tests {
                 n_hashes: 2,
                 bits_per_hash: 10,
             };
-            let bloom_filter_chip_config =
-                BloomFilterChip::configure(meta, advice_columns, bloom_filter_config);
+            let mut byte_table = ByteTable::new(meta);
+            let bloom_filter_chip_config = BloomFilterChip::configure(
+                meta,
+                advice_columns,
+                &mut byte_table,
+                bloom_filter_config,
+            );
 
             Config {
                 bloom_filter_chip_config,
+                byte_table_config: byte_table.configure(meta),
                 advice_columns,
                 instance,
             }
@@ -283,11 +730,12 @@ mod tests {
                 },
             )?;
 
-            let mut bloom_filter_chip = BloomFilterChip::construct(
-                config.bloom_filter_chip_config,
-                &self.bloom_filter_arrays,
-            );
+            let bloom_filters =
+                PackedBloomFilters::from(self.bloom_filter_arrays.clone().insert_axis(Axis(0)));
+            let mut bloom_filter_chip =
+                BloomFilterChip::construct(config.bloom_filter_chip_config, &bloom_filters);
             bloom_filter_chip.load(&mut layouter)?;
+            config.byte_table_config.load(&mut layouter)?;
 
             let hash_value = bloom_filter_chip.bloom_lookup(
                 &mut layouter.namespace(|| "bloom_filter_lookup"),
@@ -365,6 +813,22 @@ mod tests {
     }
 
     #[test]
+    fn test_allow_list() {
+        let k = 14;
+        let allow_list = [0, 3, 14, 159, 265, 1023];
+        for value in [0, 1, 3, 42, 159, 1022, 1023] {
+            let circuit = AllowListCircuit::<Fp>::new(&allow_list, 10, value);
+            let is_member = Fp::from(allow_list.contains(&value) as u64);
+            let prover = MockProver::run(k, &circuit, vec![vec![is_member]]).unwrap();
+            prover.assert_satisfied();
+
+            let prover = MockProver::run(k, &circuit, vec![vec![Fp::from(1) - is_member]]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "plotting")]
     fn plot() {
         use plotters::prelude::*;
 
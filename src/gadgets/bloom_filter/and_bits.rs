@@ -84,6 +84,19 @@ impl<F: PrimeField> AndBitsInstruction<F> for AndBitsChip<F> {
         &self,
         layouter: &mut impl Layouter<F>,
         bits: Vec<AssignedCell<F, F>>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.and_bits_with_flip(layouter, bits, false)
+    }
+}
+
+impl<F: PrimeField> AndBitsChip<F> {
+    /// Like [`AndBitsInstruction::and_bits`], but with `flip`, the result is negated, which
+    /// the constraints have to reject. See [`crate::gadgets::wnn::Tampering`].
+    pub(crate) fn and_bits_with_flip(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        bits: Vec<AssignedCell<F, F>>,
+        flip: bool,
     ) -> Result<AssignedCell<F, F>, Error> {
         layouter.assign_region(
             || "and bits",
@@ -99,6 +112,10 @@ impl<F: PrimeField> AndBitsInstruction<F> for AndBitsChip<F> {
                     let acc_value = prev_value.zip(*bit_value).map(|(prev, bit)| prev * bit);
                     acc_values.push(acc_value);
                 }
+                if flip {
+                    let result = acc_values.last_mut().unwrap();
+                    *result = result.map(|result| F::ONE - result);
+                }
 
                 for (i, bit) in bits.iter().enumerate() {
                     bit.copy_advice(|| "bit", &mut region, self.config.bits, i)?;
@@ -1,13 +1,16 @@
-use crate::utils::{decompose_word_be, enable_range, from_be_bits, to_u32};
+use std::sync::Arc;
+
+use crate::utils::{decompose_word_le, enable_range, from_be_bits, to_u32};
 use ff::PrimeFieldBits;
 use halo2_proofs::{
     circuit::{AssignedCell, Layouter, Value},
     plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector, TableColumn},
     poly::Rotation,
 };
-use ndarray::Array2;
+use rayon::prelude::*;
 
 use super::BloomFilterConfig;
+use crate::packed_bloom_filters::PackedBloomFilters;
 
 /// The result of the array lookup.
 ///
@@ -117,18 +120,27 @@ pub struct ArrayLookupChipConfig {
 ///    wouldn't end with a constant `0` after `n_hashes + 1` rows).
 pub struct ArrayLookupChip<F: PrimeFieldBits> {
     config: ArrayLookupChipConfig,
-    bloom_filter_words: Vec<Vec<F>>,
+    bloom_filter_words: Arc<Vec<Vec<F>>>,
 }
 
 impl<F: PrimeFieldBits> ArrayLookupChip<F> {
     /// Constructs a new instance of the Array Lookup gadget.
-    pub fn construct(config: ArrayLookupChipConfig, bloom_filter_arrays: &Array2<bool>) -> Self {
+    /// The bloom index of the filter `(class, filter)` is `class * num_filters + filter`.
+    pub fn construct(config: ArrayLookupChipConfig, bloom_filters: &PackedBloomFilters) -> Self {
         let bloom_filter_words = Self::compute_bloom_filter_words(
-            bloom_filter_arrays,
+            bloom_filters,
             config.array_lookup_config.bits_per_hash,
             config.array_lookup_config.word_index_bits,
         );
+        Self::construct_with_words(config, Arc::new(bloom_filter_words))
+    }
 
+    /// Like [`ArrayLookupChip::construct`], but with bloom filter words that have already been
+    /// packed (by [`ArrayLookupChip::compute_bloom_filter_words`], for the same config).
+    pub fn construct_with_words(
+        config: ArrayLookupChipConfig,
+        bloom_filter_words: Arc<Vec<Vec<F>>>,
+    ) -> Self {
         ArrayLookupChip {
             config,
             bloom_filter_words,
@@ -136,26 +148,32 @@ impl<F: PrimeFieldBits> ArrayLookupChip<F> {
     }
 
     /// Packs multiple bits into a field element.
-    fn compute_bloom_filter_words(
-        bloom_filter_arrays: &Array2<bool>,
+    /// Only the bits of a single bloom filter are unpacked at a time.
+    pub fn compute_bloom_filter_words(
+        bloom_filters: &PackedBloomFilters,
         bits_per_hash: usize,
         word_index_bits: usize,
     ) -> Vec<Vec<F>> {
-        let bloom_filter_length = 1 << bits_per_hash;
-        assert_eq!(bloom_filter_arrays.shape()[1], bloom_filter_length);
+        let [n_classes, n_filters, bloom_filter_length] = bloom_filters.shape();
+        assert_eq!(bloom_filter_length, 1 << bits_per_hash);
 
         let word_length = 1 << (bits_per_hash - word_index_bits);
-        assert_eq!(bloom_filter_arrays.shape()[1] % word_length, 0);
-
-        let bloom_filter_words = (0..bloom_filter_arrays.shape()[0])
-            .map(|i| {
-                let bits = bloom_filter_arrays.row(i).to_vec();
+        assert_eq!(bloom_filter_length % word_length, 0);
+
+        // Unless the words are cached (see `WnnSynthesisCache`), the circuit packs all bloom
+        // filters each time it is synthesized, e.g. several times during key generation. The
+        // filters are independent, so they are packed in parallel. Only the packing is
+        // parallel: The table is assigned cell by cell in `load`.
+        (0..n_classes * n_filters)
+            .into_par_iter()
+            .map(|bloom_index| {
+                let (class, filter) = (bloom_index / n_filters, bloom_index % n_filters);
+                let bits = bloom_filters.filter(class, filter).collect::<Vec<_>>();
                 bits.chunks_exact(word_length)
                     .map(|word_bits| from_be_bits::<F>(word_bits))
                     .collect::<Vec<_>>()
             })
-            .collect::<Vec<_>>();
-        bloom_filter_words
+            .collect()
     }
 
     /// The number of bytes in each looked up word.
@@ -252,7 +270,8 @@ impl<F: PrimeFieldBits> ArrayLookupChip<F> {
         }
     }
 
-    // Loads the bloom filters into the table.
+    /// Loads the bloom filters into the table. The cells are assigned sequentially (halo2's
+    /// `assign_table` takes a `FnMut` closure), so this doesn't benefit from more threads.
     /// Should be called once before [`ArrayLookupInstructions::array_lookup`]!
     pub fn load(&mut self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
         layouter.assign_table(
@@ -316,14 +335,10 @@ impl<F: PrimeFieldBits> ArrayLookupInstructions<F> for ArrayLookupChip<F> {
                 let word_index_bits = self.config.array_lookup_config.word_index_bits;
 
                 // Compute values to put in cells
-                // For the hash decomposition, we need a little endian representation of the hash value,
-                // so we reverse the hash values here
-                let hash_values_le = hash_value.value().map(|hash_value| {
-                    decompose_word_be(hash_value, n_hashes, bits_per_hash)
-                        .into_iter()
-                        .rev()
-                        .collect::<Vec<_>>()
-                });
+                // For the hash decomposition, we need a little endian representation of the hash value
+                let hash_values_le = hash_value
+                    .value()
+                    .map(|hash_value| decompose_word_le(hash_value, n_hashes, bits_per_hash));
 
                 let index_values: Value<Vec<(F, F, F)>> =
                     hash_values_le.clone().map(|hash_values| {
@@ -466,8 +481,9 @@ mod tests {
         halo2curves::bn256::Fr as Fp,
         plonk::{Advice, Circuit, Column, Instance},
     };
-    use ndarray::Array2;
+    use ndarray::{Array2, Axis};
 
+    use crate::packed_bloom_filters::PackedBloomFilters;
     use crate::utils::to_be_bits;
 
     use super::{
@@ -556,10 +572,10 @@ mod tests {
                 },
             )?;
 
-            let mut bloom_filter_chip = ArrayLookupChip::construct(
-                config.bloom_filter_chip_config,
-                &self.bloom_filter_arrays,
-            );
+            let bloom_filters =
+                PackedBloomFilters::from(self.bloom_filter_arrays.clone().insert_axis(Axis(0)));
+            let mut bloom_filter_chip =
+                ArrayLookupChip::construct(config.bloom_filter_chip_config, &bloom_filters);
             bloom_filter_chip.load(&mut layouter)?;
 
             let results = bloom_filter_chip.array_lookup(
@@ -694,6 +710,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "plotting")]
     fn plot() {
         use plotters::prelude::*;
 
@@ -2,11 +2,11 @@ use std::marker::PhantomData;
 
 use ff::PrimeFieldBits;
 use halo2_proofs::{
-    circuit::{AssignedCell, Layouter, Value},
-    plonk::{Advice, Column, ConstraintSystem, Error, Selector, TableColumn},
-    poly::Rotation,
+    circuit::{AssignedCell, Layouter},
+    plonk::{Advice, Column, ConstraintSystem, Error, Selector},
 };
 
+use crate::gadgets::byte_table::ByteTable;
 use crate::utils::{to_be_bits, to_u32};
 
 /// The interface of the Bit Selector gadget.
@@ -27,15 +27,10 @@ pub struct BitSelectorChipConfig {
     index: Column<Advice>,
     bit: Column<Advice>,
 
-    /// Column of all bytes (not unique). Public so that it can be reused by other gadgets.
-    pub byte_column: TableColumn,
-    index_column: TableColumn,
-    bit_column: TableColumn,
-
     lookup_selector: Selector,
 }
 
-/// Implements a bit selector using a lookup table.
+/// Implements a bit selector using a lookup into the [`ByteTable`].
 /// The layout is a single row with a `(byte, index, bit)` tuple.
 /// Note that this implicitly range-checks `index` to be in `[0, 8)`.
 pub struct BitSelectorChip<F: PrimeFieldBits> {
@@ -52,83 +47,21 @@ impl<F: PrimeFieldBits> BitSelectorChip<F> {
         }
     }
 
-    /// Loads the lookup table.
-    /// Should be called before [`BitSelectorInstructions::select_bit``].
-    pub fn load(&mut self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
-        layouter.assign_table(
-            || "byte,index,bit",
-            |mut table| {
-                let mut table_index = 0;
-                for b in 0..(1 << 8) {
-                    for i in 0..8 {
-                        let bit = if b & (1 << (7 - i)) == 0 {
-                            F::ZERO
-                        } else {
-                            F::ONE
-                        };
-
-                        table.assign_cell(
-                            || "byte",
-                            self.config.byte_column,
-                            table_index,
-                            || Value::known(F::from(b as u64)),
-                        )?;
-                        table.assign_cell(
-                            || "index",
-                            self.config.index_column,
-                            table_index,
-                            || Value::known(F::from(i as u64)),
-                        )?;
-                        table.assign_cell(
-                            || "bit",
-                            self.config.bit_column,
-                            table_index,
-                            || Value::known(bit),
-                        )?;
-
-                        table_index += 1;
-                    }
-                }
-                Ok(())
-            },
-        )
-    }
-
+    /// Configures the chip, registering its lookup with the byte table.
     pub fn configure(
         meta: &mut ConstraintSystem<F>,
         byte: Column<Advice>,
         index: Column<Advice>,
         bit: Column<Advice>,
+        byte_table: &mut ByteTable<F>,
     ) -> BitSelectorChipConfig {
         let lookup_selector = meta.complex_selector();
-        let byte_column = meta.lookup_table_column();
-        let index_column = meta.lookup_table_column();
-        let bit_column = meta.lookup_table_column();
-
-        meta.lookup("bit_lookup", |meta| {
-            let lookup_selector = meta.query_selector(lookup_selector);
-
-            let byte = meta.query_advice(byte, Rotation::cur());
-            let index = meta.query_advice(index, Rotation::cur());
-            let bit = meta.query_advice(bit, Rotation::cur());
-
-            // Note that we don't provide a default value. This is because if the selector
-            // is zero, the tuple (0, 0, 0) is looked up, which is already in the table.
-
-            vec![
-                (lookup_selector.clone() * byte, byte_column),
-                (lookup_selector.clone() * index, index_column),
-                (lookup_selector * bit, bit_column),
-            ]
-        });
+        byte_table.select_bit(lookup_selector, byte, index, bit);
 
         BitSelectorChipConfig {
             byte,
             index,
             bit,
-            byte_column,
-            index_column,
-            bit_column,
             lookup_selector,
         }
     }
@@ -177,6 +110,7 @@ mod tests {
     };
 
     use super::{BitSelectorChip, BitSelectorChipConfig, BitSelectorInstructions};
+    use crate::gadgets::byte_table::{ByteTable, ByteTableConfig};
 
     #[derive(Default)]
     struct MyCircuit<F: PrimeFieldBits> {
@@ -188,6 +122,7 @@ mod tests {
     #[derive(Clone, Debug)]
     struct Config {
         config: BitSelectorChipConfig,
+        byte_table_config: ByteTableConfig,
         instance: Column<Instance>,
     }
 
@@ -212,8 +147,10 @@ mod tests {
             meta.enable_equality(index);
             meta.enable_equality(bit);
 
+            let mut byte_table = ByteTable::new(meta);
             Config {
-                config: BitSelectorChip::configure(meta, byte, index, bit),
+                config: BitSelectorChip::configure(meta, byte, index, bit, &mut byte_table),
+                byte_table_config: byte_table.configure(meta),
                 instance,
             }
         }
@@ -242,8 +179,8 @@ mod tests {
                 },
             )?;
 
-            let mut chip = BitSelectorChip::construct(config.config);
-            chip.load(&mut layouter)?;
+            config.byte_table_config.load(&mut layouter)?;
+            let chip = BitSelectorChip::construct(config.config);
             let result = chip.select_bit(&mut layouter, byte_cell, index_cell)?;
 
             layouter.constrain_instance(result.cell(), config.instance, 0)?;
@@ -291,6 +228,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "plotting")]
     fn plot() {
         use plotters::prelude::*;
 
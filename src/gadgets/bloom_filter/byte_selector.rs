@@ -4,11 +4,12 @@ use ff::PrimeFieldBits;
 use halo2_proofs::{
     circuit::{AssignedCell, Layouter},
     plonk::{
-        Advice, Column, ConstraintSystem, Constraints, Error, Selector, TableColumn, VirtualCells,
+        Advice, Column, ConstraintSystem, Constraints, Error, Expression, Selector, VirtualCells,
     },
     poly::Rotation,
 };
 
+use crate::gadgets::byte_table::ByteTable;
 use crate::utils::{decompose_word_be, enable_range, to_u32};
 
 /// The interface of the Byte Selector gadget.
@@ -85,7 +86,7 @@ impl<F: PrimeFieldBits> ByteSelectorChip<F> {
         byte_selector: Column<Advice>,
         selector_acc: Column<Advice>,
         byte_acc: Column<Advice>,
-        byte_table: TableColumn,
+        byte_table: &mut ByteTable<F>,
     ) -> ByteSelectorChipConfig {
         let byte_decomposition_selector = meta.complex_selector();
         let is_bit_selector = meta.selector();
@@ -93,23 +94,13 @@ impl<F: PrimeFieldBits> ByteSelectorChip<F> {
         let right_byte_selector = meta.selector();
         let byte_acc_selector = meta.selector();
 
-        let reconstruct_byte = |meta: &mut VirtualCells<F>| {
-            // We recover the word from the difference of the running sums:
-            //    z_i = 2^8⋅z_{i + 1} + a_i
-            // => a_i = z_i - 2^8⋅z_{i + 1}
-            let z_cur = meta.query_advice(byte_decomposition, Rotation::cur());
-            let z_next = meta.query_advice(byte_decomposition, Rotation::next());
-            z_cur - z_next * F::from(1 << 8)
-        };
-
-        meta.lookup("byte_decomposition", |meta| {
-            // Validate that the reconstructed values are indeed bytes, via a lookup
-            // into the byte table.
-            let byte_decomposition_selector = meta.query_selector(byte_decomposition_selector);
-            let byte = reconstruct_byte(meta);
-
-            vec![(byte_decomposition_selector * byte, byte_table)]
-        });
+        // Validate that the reconstructed values are indeed bytes, via a lookup
+        // into the byte table.
+        byte_table.range_check(
+            byte_decomposition_selector,
+            byte_decomposition,
+            move |meta| reconstruct_byte(meta, byte_decomposition),
+        );
 
         meta.create_gate("selector_is_bit", |meta| {
             // Validate that the selector values are bits.
@@ -154,7 +145,7 @@ impl<F: PrimeFieldBits> ByteSelectorChip<F> {
             let byte_acc_selector = meta.query_selector(byte_acc_selector);
             let byte_acc_cur = meta.query_advice(byte_acc, Rotation::cur());
             let byte_acc_next = meta.query_advice(byte_acc, Rotation::next());
-            let byte_cur = reconstruct_byte(meta);
+            let byte_cur = reconstruct_byte(meta, byte_decomposition);
 
             let byte_selector_cur = meta.query_advice(byte_selector, Rotation::cur());
 
@@ -180,6 +171,19 @@ impl<F: PrimeFieldBits> ByteSelectorChip<F> {
     }
 }
 
+/// Reconstructs the byte shifted out of the byte decomposition in the current row.
+fn reconstruct_byte<F: PrimeFieldBits>(
+    meta: &mut VirtualCells<'_, F>,
+    byte_decomposition: Column<Advice>,
+) -> Expression<F> {
+    // We recover the word from the difference of the running sums:
+    //    z_i = 2^8⋅z_{i + 1} + a_i
+    // => a_i = z_i - 2^8⋅z_{i + 1}
+    let z_cur = meta.query_advice(byte_decomposition, Rotation::cur());
+    let z_next = meta.query_advice(byte_decomposition, Rotation::next());
+    z_cur - z_next * F::from(1 << 8)
+}
+
 impl<F: PrimeFieldBits> ByteSelectorInstructions<F> for ByteSelectorChip<F> {
     fn select_byte(
         &self,
@@ -360,10 +364,11 @@ mod tests {
         circuit::{SimpleFloorPlanner, Value},
         dev::MockProver,
         halo2curves::bn256::Fr as Fp,
-        plonk::{Circuit, Column, Instance, TableColumn},
+        plonk::{Circuit, Column, Instance},
     };
 
     use super::{ByteSelectorChip, ByteSelectorChipConfig, ByteSelectorInstructions};
+    use crate::gadgets::byte_table::{ByteTable, ByteTableConfig};
 
     #[derive(Default)]
     struct MyCircuit<F: PrimeFieldBits> {
@@ -377,7 +382,7 @@ mod tests {
     struct Config {
         config: ByteSelectorChipConfig,
         instance: Column<Instance>,
-        table_column: TableColumn,
+        byte_table_config: ByteTableConfig,
     }
 
     impl<F: PrimeFieldBits> Circuit<F> for MyCircuit<F> {
@@ -399,7 +404,6 @@ mod tests {
 
             let instance = meta.instance_column();
             let constants = meta.fixed_column();
-            let table_column = meta.lookup_table_column();
 
             meta.enable_equality(instance);
             meta.enable_equality(byte_decomposition);
@@ -410,6 +414,7 @@ mod tests {
             meta.enable_equality(byte_acc);
             meta.enable_constant(constants);
 
+            let mut byte_table = ByteTable::new(meta);
             Config {
                 config: ByteSelectorChip::configure(
                     meta,
@@ -419,10 +424,10 @@ mod tests {
                     byte_selector,
                     selector_acc,
                     byte_acc,
-                    table_column,
+                    &mut byte_table,
                 ),
                 instance,
-                table_column,
+                byte_table_config: byte_table.configure(meta),
             }
         }
 
@@ -450,20 +455,7 @@ mod tests {
                 },
             )?;
 
-            layouter.assign_table(
-                || "bytes",
-                |mut table| {
-                    for i in 0..(1 << 8) {
-                        table.assign_cell(
-                            || "byte",
-                            config.table_column,
-                            i,
-                            || Value::known(F::from(i as u64)),
-                        )?;
-                    }
-                    Ok(())
-                },
-            )?;
+            config.byte_table_config.load(&mut layouter)?;
 
             let chip = ByteSelectorChip::construct(config.config);
             let result = chip.select_byte(&mut layouter, input_cell, index_cell, self.num_bytes)?;
@@ -475,7 +467,7 @@ mod tests {
 
     #[test]
     fn test_1byte() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             input: 0xab,
             index: 0,
@@ -489,7 +481,7 @@ mod tests {
 
     #[test]
     fn test_3byte_0() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             input: 0xabcdef,
             index: 0,
@@ -503,7 +495,7 @@ mod tests {
 
     #[test]
     fn test_3byte_1() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             input: 0xabcdef,
             index: 1,
@@ -516,6 +508,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "plotting")]
     fn plot() {
         use plotters::prelude::*;
 
@@ -0,0 +1,371 @@
+use std::sync::Arc;
+
+use crate::utils::{decompose_word_be, enable_range, to_u32};
+use ff::PrimeFieldBits;
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, FirstPhase, Fixed, Selector},
+    poly::Rotation,
+};
+use rayon::prelude::*;
+
+use super::{ArrayLookupChip, ArrayLookupConfig};
+use crate::packed_bloom_filters::PackedBloomFilters;
+
+/// The result of the class lookup for one hash.
+///
+/// Like [`super::array_lookup::LookupResult`], but with one word per class.
+#[derive(Debug)]
+pub struct ClassLookupResult<F: PrimeFieldBits> {
+    pub words: Vec<AssignedCell<F, F>>,
+    pub byte_index: AssignedCell<F, F>,
+    pub bit_index: AssignedCell<F, F>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassLookupChipConfig {
+    hash_decomposition: Column<Advice>,
+    byte_index: Column<Advice>,
+    bit_index: Column<Advice>,
+    filter_index: Column<Advice>,
+    words: Vec<Column<Advice>>,
+
+    class_lookup_selector: Selector,
+
+    table_filter_index: Column<Fixed>,
+    table_word_index: Column<Fixed>,
+    table_words: Vec<Column<Fixed>>,
+
+    array_lookup_config: ArrayLookupConfig,
+}
+
+/// Looks up the words of all classes at once, using `4 + n_classes` columns and
+/// `n_hashes + 1` advice rows.
+///
+/// The layout is as follows:
+///
+/// | hash_decomposition    | byte_index   | bit_index   | filter_index        | word_0 | ... | word_{n_classes - 1} |
+/// |-----------------------|--------------|-------------|---------------------|--------|-----|----------------------|
+/// | hash (copy)           | byte_index_0 | bit_index_0 | filter (constant)   | ...    | ... | ...                  |
+/// | hash >> bits_per_hash | byte_index_1 | bit_index_1 | filter (constant)   | ...    | ... | ...                  |
+/// | 0 (constant)          |              |             |                     |        |     |                      |
+///
+/// The word index is computed like in [`ArrayLookupChip`], which also describes the range
+/// checks this gadget assumes. Instead of one lookup per class, the words of all classes are
+/// combined into `word_0 * γ^{n_classes - 1} + ... + word_{n_classes - 1}`, where `γ` is a
+/// challenge drawn after the first phase, and the tuple
+/// `(filter + 1, word_index, combined_word)` is looked up in a table with one row per filter
+/// and word index. Because the words are committed before `γ` is known, a combination that
+/// appears in the table implies (except with negligible probability) that every word is the
+/// correct one.
+///
+/// The filter index is shifted by one so that the tuple `(0, 0, 0)`, which is looked up in
+/// rows where the selector is disabled, only matches the unassigned rows of the table.
+///
+/// Compared to [`ArrayLookupChip`], this shrinks the lookup (and the table) from
+/// `n_classes * n_filters` to `n_filters` entries per hash and word, at the cost of one
+/// advice column per class.
+pub struct ClassLookupChip<F: PrimeFieldBits> {
+    config: ClassLookupChipConfig,
+    /// The words, indexed by filter, word index and class.
+    bloom_filter_words: Arc<Vec<Vec<Vec<F>>>>,
+}
+
+impl<F: PrimeFieldBits> ClassLookupChip<F> {
+    pub fn construct(config: ClassLookupChipConfig, bloom_filters: &PackedBloomFilters) -> Self {
+        assert_eq!(bloom_filters.shape()[0], config.words.len());
+        let bloom_filter_words =
+            Self::compute_bloom_filter_words(bloom_filters, &config.array_lookup_config);
+        Self::construct_with_words(config, Arc::new(bloom_filter_words))
+    }
+
+    /// Like [`ClassLookupChip::construct`], but with bloom filter words that have already been
+    /// packed (by [`ClassLookupChip::compute_bloom_filter_words`], for the same config).
+    pub fn construct_with_words(
+        config: ClassLookupChipConfig,
+        bloom_filter_words: Arc<Vec<Vec<Vec<F>>>>,
+    ) -> Self {
+        ClassLookupChip {
+            config,
+            bloom_filter_words,
+        }
+    }
+
+    /// Packs the bloom filters into words (see [`ArrayLookupChip::compute_bloom_filter_words`])
+    /// and groups the words of all classes, indexed by filter, word index and class.
+    pub fn compute_bloom_filter_words(
+        bloom_filters: &PackedBloomFilters,
+        array_lookup_config: &ArrayLookupConfig,
+    ) -> Vec<Vec<Vec<F>>> {
+        let [n_classes, n_filters, _] = bloom_filters.shape();
+        let words = ArrayLookupChip::<F>::compute_bloom_filter_words(
+            bloom_filters,
+            array_lookup_config.bits_per_hash,
+            array_lookup_config.word_index_bits,
+        );
+        (0..n_filters)
+            .into_par_iter()
+            .map(|filter| {
+                (0..words[filter].len())
+                    .map(|word_index| {
+                        (0..n_classes)
+                            .map(|class| words[class * n_filters + filter][word_index])
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The number of bytes in each looked up word.
+    pub fn bytes_per_word(&self) -> usize {
+        1 << (self.config.array_lookup_config.bits_per_hash
+            - self.config.array_lookup_config.word_index_bits
+            - 3)
+    }
+
+    /// Configures the chip, allocating one advice column and one fixed column per class.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        hash_decomposition: Column<Advice>,
+        byte_index: Column<Advice>,
+        bit_index: Column<Advice>,
+        filter_index: Column<Advice>,
+        n_classes: usize,
+        array_lookup_config: ArrayLookupConfig,
+    ) -> ClassLookupChipConfig {
+        assert!(array_lookup_config.bits_per_hash <= 32);
+        assert!(n_classes > 0);
+
+        let words: Vec<_> = (0..n_classes).map(|_| meta.advice_column()).collect();
+        for word in &words {
+            meta.enable_equality(*word);
+        }
+
+        let table_filter_index = meta.fixed_column();
+        let table_word_index = meta.fixed_column();
+        let table_words: Vec<_> = (0..n_classes).map(|_| meta.fixed_column()).collect();
+        let class_lookup_selector = meta.complex_selector();
+        let challenge = meta.challenge_usable_after(FirstPhase);
+
+        meta.lookup_any("class lookup", |meta| {
+            let selector = meta.query_selector(class_lookup_selector);
+
+            // Reconstruct the word index, like in the array lookup
+            let hash_decomposition_cur = meta.query_advice(hash_decomposition, Rotation::cur());
+            let hash_decomposition_next = meta.query_advice(hash_decomposition, Rotation::next());
+            let byte_index = meta.query_advice(byte_index, Rotation::cur());
+            let bit_index = meta.query_advice(bit_index, Rotation::cur());
+
+            let shift_multiplier = F::from(1 << array_lookup_config.bits_per_hash);
+            let current_hash = hash_decomposition_cur - hash_decomposition_next * shift_multiplier;
+
+            let two_pow_3 = F::from(1 << 3);
+            let right_shift_multiplier = F::from(
+                1 << (array_lookup_config.bits_per_hash - array_lookup_config.word_index_bits),
+            )
+            .invert()
+            .unwrap();
+            let word_index =
+                (current_hash - byte_index * two_pow_3 - bit_index) * right_shift_multiplier;
+
+            let filter_index = meta.query_advice(filter_index, Rotation::cur());
+            let words = words
+                .iter()
+                .map(|word| meta.query_advice(*word, Rotation::cur()))
+                .collect::<Vec<_>>();
+            let table_words = table_words
+                .iter()
+                .map(|word| meta.query_fixed(*word, Rotation::cur()))
+                .collect::<Vec<_>>();
+            let gamma = meta.query_challenge(challenge);
+
+            let one = Expression::Constant(F::ONE);
+            vec![
+                (
+                    selector.clone() * (filter_index + one),
+                    meta.query_fixed(table_filter_index, Rotation::cur()),
+                ),
+                (
+                    selector.clone() * word_index,
+                    meta.query_fixed(table_word_index, Rotation::cur()),
+                ),
+                (
+                    selector * combine(words, &gamma),
+                    combine(table_words, &gamma),
+                ),
+            ]
+        });
+
+        ClassLookupChipConfig {
+            // Advice Columns
+            hash_decomposition,
+            byte_index,
+            bit_index,
+            filter_index,
+            words,
+
+            // Selectors
+            class_lookup_selector,
+
+            // Table Columns
+            table_filter_index,
+            table_word_index,
+            table_words,
+
+            array_lookup_config,
+        }
+    }
+
+    /// Loads the bloom filters into the table.
+    /// Should be called once before [`ClassLookupChip::class_lookup`]!
+    pub fn load(&mut self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_region(
+            || "class lookup table",
+            |mut region| {
+                let mut offset = 0;
+                for (filter, filter_words) in self.bloom_filter_words.iter().enumerate() {
+                    for (word_index, class_words) in filter_words.iter().enumerate() {
+                        region.assign_fixed(
+                            || "filter_index",
+                            self.config.table_filter_index,
+                            offset,
+                            || Value::known(F::from(filter as u64 + 1)),
+                        )?;
+                        region.assign_fixed(
+                            || "word_index",
+                            self.config.table_word_index,
+                            offset,
+                            || Value::known(F::from(word_index as u64)),
+                        )?;
+                        for (column, word) in self.config.table_words.iter().zip(class_words) {
+                            region.assign_fixed(
+                                || "word",
+                                *column,
+                                offset,
+                                || Value::known(*word),
+                            )?;
+                        }
+                        offset += 1;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Given a hash value and a filter index, decomposes the hash and looks up the word of
+    /// each class. Returns the words, byte index and bit index for each hash value.
+    pub fn class_lookup(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        hash_value: AssignedCell<F, F>,
+        filter_index: usize,
+    ) -> Result<Vec<ClassLookupResult<F>>, Error> {
+        layouter.assign_region(
+            || "look up hash values of all classes",
+            |mut region| {
+                let n_hashes = self.config.array_lookup_config.n_hashes;
+                let bits_per_hash = self.config.array_lookup_config.bits_per_hash;
+                let word_index_bits = self.config.array_lookup_config.word_index_bits;
+                let n_bits_byte_and_bit_indices = bits_per_hash - word_index_bits;
+
+                // Little endian, so that the hash decomposition shifts out one hash per row
+                let hash_values_le = hash_value.value().map(|hash_value| {
+                    decompose_word_be(hash_value, n_hashes, bits_per_hash)
+                        .into_iter()
+                        .rev()
+                        .map(|hash| to_u32(&hash) as usize)
+                        .collect::<Vec<_>>()
+                });
+
+                let mut hash_decomposition = hash_value.value().copied();
+                for i in 0..n_hashes {
+                    let name = || format!("hash_decomposition_{i}");
+                    let column = self.config.hash_decomposition;
+                    if i == 0 {
+                        hash_value.copy_advice(name, &mut region, column, i)?;
+                    } else {
+                        region.assign_advice(name, column, i, || hash_decomposition)?;
+                    }
+                    let hash = hash_values_le.as_ref().map(|hashes| hashes[i]);
+                    let shift_factor = F::from(1 << bits_per_hash).invert().unwrap();
+                    hash_decomposition = hash_decomposition
+                        .zip(hash)
+                        .map(|(prev, hash)| (prev - F::from(hash as u64)) * shift_factor);
+                }
+                hash_decomposition.assert_if_known(|last_value| *last_value == F::ZERO);
+                region.assign_advice_from_constant(
+                    || format!("hash_decomposition_{n_hashes}"),
+                    self.config.hash_decomposition,
+                    n_hashes,
+                    F::ZERO,
+                )?;
+
+                let mut results = vec![];
+                for i in 0..n_hashes {
+                    let hash = hash_values_le.as_ref().map(|hashes| hashes[i]);
+                    let word_index = hash.map(|hash| hash >> n_bits_byte_and_bit_indices);
+                    let byte_index =
+                        hash.map(|hash| (hash & ((1 << n_bits_byte_and_bit_indices) - 1)) >> 3);
+                    let bit_index = hash.map(|hash| hash & 0b111);
+
+                    region.assign_advice_from_constant(
+                        || "filter_index",
+                        self.config.filter_index,
+                        i,
+                        F::from(filter_index as u64),
+                    )?;
+                    let words = self
+                        .config
+                        .words
+                        .iter()
+                        .enumerate()
+                        .map(|(class, column)| {
+                            region.assign_advice(
+                                || format!("word_{i} of class {class}"),
+                                *column,
+                                i,
+                                || {
+                                    word_index.map(|word_index| {
+                                        self.bloom_filter_words[filter_index][word_index][class]
+                                    })
+                                },
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let byte_index = region.assign_advice(
+                        || format!("byte_index_{i}"),
+                        self.config.byte_index,
+                        i,
+                        || byte_index.map(|byte_index| F::from(byte_index as u64)),
+                    )?;
+                    let bit_index = region.assign_advice(
+                        || format!("bit_index_{i}"),
+                        self.config.bit_index,
+                        i,
+                        || bit_index.map(|bit_index| F::from(bit_index as u64)),
+                    )?;
+                    results.push(ClassLookupResult {
+                        words,
+                        byte_index,
+                        bit_index,
+                    });
+                }
+
+                enable_range(&mut region, self.config.class_lookup_selector, 0..n_hashes)?;
+
+                // Reverse order so that results are returned assuming a big endian decomposition
+                results.reverse();
+                Ok(results)
+            },
+        )
+    }
+}
+
+/// Computes `xs[0] * γ^{n - 1} + ... + xs[n - 1]`.
+fn combine<F: PrimeFieldBits>(xs: Vec<Expression<F>>, gamma: &Expression<F>) -> Expression<F> {
+    xs.into_iter()
+        .reduce(|acc, x| acc * gamma.clone() + x)
+        .expect("At least one class is required")
+}
@@ -547,6 +547,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "plotting")]
     fn plot() {
         use plotters::prelude::*;
 
@@ -0,0 +1,398 @@
+//! A single lookup argument for all byte-sized lookups of the circuit.
+//!
+//! Range checks (see [`crate::gadgets::range_check`]), the byte decomposition of the
+//! [`crate::gadgets::bloom_filter::ByteSelectorChip`] and the bit lookup of the
+//! [`crate::gadgets::bloom_filter::BitSelectorChip`] all look up small values. Instead of one
+//! lookup argument each, they register their inputs with a [`ByteTable`], which combines them
+//! into one lookup into a tagged table:
+//!
+//! | tag          | byte | index | bit                     |
+//! |--------------|------|-------|-------------------------|
+//! | 0 (bit)      | b    | i     | bit `i` of `b` (BE)     |
+//! | 1 (range)    | b    | 0     | 0                       |
+//! | 2 (window)   | w    | 0     | 0                       |
+//!
+//! for all bytes `b`, indices `i` in `[0, 8)` and words `w` of
+//! [`ByteTable::window_num_bits`] bits. The window rows are only added if the window is not a
+//! byte; otherwise, window checks use the range rows. Each input is multiplied by its selector,
+//! and the inputs are summed up, so at most one of them may be active in any row. This is
+//! enforced by requiring all inputs to read their byte from the same advice column: regions
+//! never share cells, so two inputs can't be enabled in the same row.
+
+use ff::PrimeFieldBits;
+use halo2_proofs::{
+    circuit::{Layouter, Value},
+    plonk::{
+        Advice, Column, ConstraintSystem, Error, Expression, Selector, TableColumn, VirtualCells,
+    },
+    poly::Rotation,
+};
+
+/// The tag of the bit selection rows.
+const TAG_BIT: u64 = 0;
+/// The tag of the byte range check rows.
+const TAG_RANGE: u64 = 1;
+/// The tag of the window range check rows.
+const TAG_WINDOW: u64 = 2;
+
+/// The default number of bits per word of [`ByteTable::window_check`].
+pub const DEFAULT_WINDOW_NUM_BITS: usize = 8;
+/// The maximum number of bits per word of [`ByteTable::window_check`], which keeps the table
+/// at `2^16` rows.
+pub const MAX_WINDOW_NUM_BITS: usize = 16;
+
+/// The number of rows of the table, for the given number of bits per word of
+/// [`ByteTable::window_check`]: `2^8 * 8` bit selection rows, `2^8` byte range check rows and,
+/// unless the default is used, `2^window_num_bits` window range check rows.
+pub fn num_table_rows(window_num_bits: usize) -> usize {
+    let window_rows = if window_num_bits == DEFAULT_WINDOW_NUM_BITS {
+        0
+    } else {
+        1 << window_num_bits
+    };
+    (1 << 8) * 8 + (1 << 8) + window_rows
+}
+
+type ByteExpression<F> = Box<dyn Fn(&mut VirtualCells<'_, F>) -> Expression<F>>;
+
+/// An input of the lookup, active whenever its selector is enabled.
+struct ByteLookupInput<F: PrimeFieldBits> {
+    selector: Selector,
+    tag: u64,
+    byte: ByteExpression<F>,
+    /// The index and bit columns, for bit selection inputs.
+    index_and_bit: Option<(Column<Advice>, Column<Advice>)>,
+}
+
+/// Collects the inputs of the byte lookup during configuration, see the
+/// [module documentation](self).
+///
+/// Create it before configuring the chips that use it and call [`ByteTable::configure`]
+/// afterwards.
+#[must_use = "The lookup is only created by ByteTable::configure"]
+pub struct ByteTable<F: PrimeFieldBits> {
+    config: ByteTableConfig,
+    byte_column: Option<Column<Advice>>,
+    inputs: Vec<ByteLookupInput<F>>,
+}
+
+/// The table columns of the [`ByteTable`].
+#[derive(Debug, Clone)]
+pub struct ByteTableConfig {
+    tag: TableColumn,
+    byte: TableColumn,
+    index: TableColumn,
+    bit: TableColumn,
+    window_num_bits: usize,
+}
+
+impl<F: PrimeFieldBits> ByteTable<F> {
+    pub fn new(meta: &mut ConstraintSystem<F>) -> Self {
+        Self {
+            config: ByteTableConfig {
+                tag: meta.lookup_table_column(),
+                byte: meta.lookup_table_column(),
+                index: meta.lookup_table_column(),
+                bit: meta.lookup_table_column(),
+                window_num_bits: DEFAULT_WINDOW_NUM_BITS,
+            },
+            byte_column: None,
+            inputs: vec![],
+        }
+    }
+
+    /// Sets the number of bits per word of [`ByteTable::window_check`], trading the number of
+    /// words needed to decompose a value for the size of the table.
+    pub fn with_window_num_bits(mut self, window_num_bits: usize) -> Self {
+        assert!(
+            (1..=MAX_WINDOW_NUM_BITS).contains(&window_num_bits),
+            "Window must have between 1 and {MAX_WINDOW_NUM_BITS} bits, got {window_num_bits}"
+        );
+        self.config.window_num_bits = window_num_bits;
+        self
+    }
+
+    /// The number of bits per word of [`ByteTable::window_check`].
+    pub fn window_num_bits(&self) -> usize {
+        self.config.window_num_bits
+    }
+
+    /// Checks that `byte` (an expression that reads `column`) is in `[0, 256)` in all rows where
+    /// the (complex) `selector` is enabled.
+    pub fn range_check(
+        &mut self,
+        selector: Selector,
+        column: Column<Advice>,
+        byte: impl Fn(&mut VirtualCells<'_, F>) -> Expression<F> + 'static,
+    ) {
+        self.push_range_input(selector, column, TAG_RANGE, byte);
+    }
+
+    /// Checks that `word` (an expression that reads `column`) is in `[0, 2^window_num_bits)` in
+    /// all rows where the (complex) `selector` is enabled.
+    pub fn window_check(
+        &mut self,
+        selector: Selector,
+        column: Column<Advice>,
+        word: impl Fn(&mut VirtualCells<'_, F>) -> Expression<F> + 'static,
+    ) {
+        let tag = self.config.window_tag();
+        self.push_range_input(selector, column, tag, word);
+    }
+
+    fn push_range_input(
+        &mut self,
+        selector: Selector,
+        column: Column<Advice>,
+        tag: u64,
+        value: impl Fn(&mut VirtualCells<'_, F>) -> Expression<F> + 'static,
+    ) {
+        self.use_column(column);
+        self.inputs.push(ByteLookupInput {
+            selector,
+            tag,
+            byte: Box::new(value),
+            index_and_bit: None,
+        });
+    }
+
+    /// Checks that `bit` is the `index`-th bit (big endian) of `byte` in all rows where the
+    /// (complex) `selector` is enabled. This implicitly range-checks `byte` to be in `[0, 256)`
+    /// and `index` to be in `[0, 8)`.
+    pub fn select_bit(
+        &mut self,
+        selector: Selector,
+        byte: Column<Advice>,
+        index: Column<Advice>,
+        bit: Column<Advice>,
+    ) {
+        self.use_column(byte);
+        self.inputs.push(ByteLookupInput {
+            selector,
+            tag: TAG_BIT,
+            byte: Box::new(move |meta| meta.query_advice(byte, Rotation::cur())),
+            index_and_bit: Some((index, bit)),
+        });
+    }
+
+    fn use_column(&mut self, column: Column<Advice>) {
+        let byte_column = *self.byte_column.get_or_insert(column);
+        assert_eq!(
+            byte_column, column,
+            "All byte lookups must read from the same advice column"
+        );
+    }
+
+    /// Creates the lookup argument for all registered inputs.
+    pub fn configure(self, meta: &mut ConstraintSystem<F>) -> ByteTableConfig {
+        let Self { config, inputs, .. } = self;
+        if inputs.is_empty() {
+            return config;
+        }
+        meta.lookup("byte table", |meta| {
+            let zero = || Expression::Constant(F::ZERO);
+            let (mut tag, mut byte, mut index, mut bit) = (zero(), zero(), zero(), zero());
+            for input in &inputs {
+                let selector = meta.query_selector(input.selector);
+                tag = tag + selector.clone() * Expression::Constant(F::from(input.tag));
+                byte = byte + selector.clone() * (input.byte)(meta);
+                if let Some((index_column, bit_column)) = input.index_and_bit {
+                    index =
+                        index + selector.clone() * meta.query_advice(index_column, Rotation::cur());
+                    bit = bit + selector * meta.query_advice(bit_column, Rotation::cur());
+                }
+            }
+            // If no input is active, the tuple (0, 0, 0, 0) is looked up, which is in the table.
+            vec![
+                (tag, config.tag),
+                (byte, config.byte),
+                (index, config.index),
+                (bit, config.bit),
+            ]
+        });
+        config
+    }
+}
+
+impl ByteTableConfig {
+    /// The tag of the rows used by window checks.
+    fn window_tag(&self) -> u64 {
+        if self.window_num_bits == DEFAULT_WINDOW_NUM_BITS {
+            TAG_RANGE
+        } else {
+            TAG_WINDOW
+        }
+    }
+
+    /// Loads the table. Should be called once, before any of the chips using it.
+    pub fn load<F: PrimeFieldBits>(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "byte table",
+            |mut table| {
+                let mut offset = 0;
+                let mut assign_row = |tag: u64, byte: u64, index: u64, bit: u64| {
+                    for (column, value) in [
+                        (self.tag, tag),
+                        (self.byte, byte),
+                        (self.index, index),
+                        (self.bit, bit),
+                    ] {
+                        table.assign_cell(
+                            || "byte table",
+                            column,
+                            offset,
+                            || Value::known(F::from(value)),
+                        )?;
+                    }
+                    offset += 1;
+                    Ok::<_, Error>(())
+                };
+                for b in 0..(1 << 8) {
+                    for i in 0..8 {
+                        assign_row(TAG_BIT, b, i, (b >> (7 - i)) & 1)?;
+                    }
+                }
+                for b in 0..(1 << 8) {
+                    assign_row(TAG_RANGE, b, 0, 0)?;
+                }
+                if self.window_tag() == TAG_WINDOW {
+                    for w in 0..(1 << self.window_num_bits) {
+                        assign_row(TAG_WINDOW, w, 0, 0)?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use ff::PrimeFieldBits;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::bn256::Fr as Fp,
+        plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Selector},
+        poly::Rotation,
+    };
+
+    use super::{ByteTable, ByteTableConfig};
+
+    /// Range-checks `value` and selects bit `index` of `byte`, in the same column.
+    #[derive(Default)]
+    struct MyCircuit<F: PrimeFieldBits> {
+        value: u64,
+        byte: u64,
+        index: u64,
+        bit: u64,
+        _marker: PhantomData<F>,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Config {
+        byte_table_config: ByteTableConfig,
+        advice_columns: [Column<Advice>; 3],
+        range_selector: Selector,
+        bit_selector: Selector,
+    }
+
+    impl<F: PrimeFieldBits> Circuit<F> for MyCircuit<F> {
+        type Config = Config;
+        type FloorPlanner = SimpleFloorPlanner;
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let advice_columns = [
+                meta.advice_column(),
+                meta.advice_column(),
+                meta.advice_column(),
+            ];
+            let range_selector = meta.complex_selector();
+            let bit_selector = meta.complex_selector();
+
+            let mut byte_table = ByteTable::new(meta);
+            let column = advice_columns[0];
+            byte_table.range_check(range_selector, column, move |meta| {
+                meta.query_advice(column, Rotation::cur())
+            });
+            byte_table.select_bit(bit_selector, column, advice_columns[1], advice_columns[2]);
+
+            Config {
+                byte_table_config: byte_table.configure(meta),
+                advice_columns,
+                range_selector,
+                bit_selector,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.byte_table_config.load(&mut layouter)?;
+            layouter.assign_region(
+                || "inputs",
+                |mut region| {
+                    let [column, index, bit] = config.advice_columns;
+                    let value = |x: u64| Value::known(F::from(x));
+                    region.assign_advice(|| "value", column, 0, || value(self.value))?;
+                    region.assign_advice(|| "byte", column, 1, || value(self.byte))?;
+                    region.assign_advice(|| "index", index, 1, || value(self.index))?;
+                    region.assign_advice(|| "bit", bit, 1, || value(self.bit))?;
+                    config.range_selector.enable(&mut region, 0)?;
+                    config.bit_selector.enable(&mut region, 1)
+                },
+            )
+        }
+    }
+
+    fn verify(value: u64, byte: u64, index: u64, bit: u64) -> bool {
+        let circuit = MyCircuit::<Fp> {
+            value,
+            byte,
+            index,
+            bit,
+            _marker: PhantomData,
+        };
+        MockProver::run(12, &circuit, vec![])
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_byte_table() {
+        assert!(verify(255, 0b0100_0000, 1, 1));
+        assert!(verify(0, 0b0100_0000, 0, 0));
+
+        // Value out of range
+        assert!(!verify(256, 0b0100_0000, 1, 1));
+        // Wrong bit
+        assert!(!verify(255, 0b0100_0000, 1, 0));
+        // The range check rows can't be used to select bits
+        assert!(!verify(255, 0b0100_0000, 8, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "same advice column")]
+    fn test_inputs_in_different_columns() {
+        let mut meta = ConstraintSystem::<Fp>::default();
+        let columns = [meta.advice_column(), meta.advice_column()];
+        let selectors = [meta.complex_selector(), meta.complex_selector()];
+
+        let mut byte_table = ByteTable::new(&mut meta);
+        for (selector, column) in selectors.into_iter().zip(columns) {
+            byte_table.range_check(selector, column, move |meta| {
+                meta.query_advice(column, Rotation::cur())
+            });
+        }
+    }
+}
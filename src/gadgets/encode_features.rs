@@ -0,0 +1,159 @@
+//! Binarizes feature vectors of tabular models (see [`crate::Wnn::new_tabular`]), the
+//! counterpart of [`super::encode_image`] for inputs that are not images.
+
+use std::sync::Arc;
+
+use ff::PrimeFieldBits;
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Error},
+};
+use ndarray::{Array1, Array2};
+
+use super::{
+    range_check::RangeCheckConfig,
+    threshold::{ThresholdChip, ThresholdChipConfig, ThresholdInstructions},
+};
+
+/// The number of bits of a feature.
+pub const FEATURE_NUM_BITS: usize = 16;
+
+pub trait EncodeFeaturesInstructions<F: PrimeFieldBits> {
+    /// Assigns the features in a dedicated region and range-checks them to have at most
+    /// [`FEATURE_NUM_BITS`] bits. Returns one cell per feature.
+    fn assign_features(
+        &self,
+        layouter: impl Layouter<F>,
+        features: Value<Array1<u16>>,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error>;
+
+    /// Maps the assigned features to a bit string.
+    fn encode_features(
+        &self,
+        layouter: impl Layouter<F>,
+        features: &[AssignedCell<F, F>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error>;
+}
+
+#[derive(Clone, Debug)]
+pub struct EncodeFeaturesChipConfig<F: PrimeFieldBits> {
+    feature_column: Column<Advice>,
+    threshold_chip_config: ThresholdChipConfig<F>,
+    range_check_config: RangeCheckConfig<F>,
+}
+
+/// Encodes a feature vector into a bit string, like [`super::encode_image::EncodeImageChip`]
+/// does for images:
+/// - All features are assigned once, in a region named `features`: Feature `i` is in row `i`
+///   of the `x` column of the [`ThresholdChip`]. Each feature is range-checked to be in the
+///   range [0, 2^16).
+/// - Each feature is copied to the [`ThresholdChip`] for each of its thresholds. The bit is set
+///   if the feature is greater than or equal to the threshold.
+///
+/// The bits are ordered by threshold index first, then by feature.
+pub struct EncodeFeaturesChip<F: PrimeFieldBits> {
+    threshold_chip: ThresholdChip<F>,
+    config: EncodeFeaturesChipConfig<F>,
+    /// The thresholds, with shape `(num_features, bits_per_input)`.
+    feature_thresholds: Arc<Array2<u16>>,
+}
+
+impl<F: PrimeFieldBits> EncodeFeaturesChip<F> {
+    pub fn construct(
+        config: EncodeFeaturesChipConfig<F>,
+        feature_thresholds: impl Into<Arc<Array2<u16>>>,
+    ) -> Self {
+        let threshold_chip = ThresholdChip::construct(config.threshold_chip_config.clone());
+        Self {
+            threshold_chip,
+            config,
+            feature_thresholds: feature_thresholds.into(),
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        x: Column<Advice>,
+        threshold: Column<Advice>,
+        diff: Column<Advice>,
+        reached: Column<Advice>,
+        range_check_config: RangeCheckConfig<F>,
+    ) -> EncodeFeaturesChipConfig<F> {
+        let threshold_chip_config = ThresholdChip::configure(
+            meta,
+            x,
+            threshold,
+            diff,
+            reached,
+            range_check_config.clone(),
+            FEATURE_NUM_BITS,
+        );
+        EncodeFeaturesChipConfig {
+            feature_column: x,
+            threshold_chip_config,
+            range_check_config,
+        }
+    }
+}
+
+impl<F: PrimeFieldBits> EncodeFeaturesInstructions<F> for EncodeFeaturesChip<F> {
+    fn assign_features(
+        &self,
+        mut layouter: impl Layouter<F>,
+        features: Value<Array1<u16>>,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let num_features = self.feature_thresholds.shape()[0];
+        let features = features
+            .map(|features| features.to_vec())
+            .transpose_vec(num_features);
+
+        let cells = layouter.assign_region(
+            || "features",
+            |mut region| {
+                features
+                    .iter()
+                    .enumerate()
+                    .map(|(i, feature)| {
+                        region.assign_advice(
+                            || format!("feature {i}"),
+                            self.config.feature_column,
+                            i,
+                            || feature.map(|x| F::from(x as u64)),
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            },
+        )?;
+
+        for (i, cell) in cells.iter().enumerate() {
+            self.config.range_check_config.range_check(
+                layouter.namespace(|| format!("feature {i}")),
+                cell.clone(),
+                FEATURE_NUM_BITS,
+            )?;
+        }
+
+        Ok(cells)
+    }
+
+    fn encode_features(
+        &self,
+        mut layouter: impl Layouter<F>,
+        features: &[AssignedCell<F, F>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let (num_features, bits_per_input) = self.feature_thresholds.dim();
+        assert_eq!(features.len(), num_features);
+
+        let mut bit_cells = vec![];
+        for b in 0..bits_per_input {
+            for (i, feature) in features.iter().enumerate() {
+                bit_cells.push(self.threshold_chip.reaches_threshold(
+                    layouter.namespace(|| format!("feature {i} bit {b}")),
+                    feature,
+                    self.feature_thresholds[(i, b)] as u64,
+                )?);
+            }
+        }
+        Ok(bit_cells)
+    }
+}
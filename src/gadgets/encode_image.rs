@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::sync::Arc;
 
 use ff::PrimeFieldBits;
 use halo2_proofs::{
@@ -8,50 +8,113 @@ use halo2_proofs::{
 use itertools::Itertools;
 use ndarray::{Array2, Array3};
 
-use crate::gadgets::greater_than::GreaterThanWitnessResult;
-
 use super::{
     greater_than::{GreaterThanChip, GreaterThanChipConfig, GreaterThanInstructions},
     range_check::RangeCheckConfig,
 };
 
 pub trait EncodeImageInstructions<F: PrimeFieldBits> {
-    /// Maps an image to a bit string.
-    fn encode_image(
+    /// Assigns the pixel intensities in a dedicated region (see [`EncodeImageChip`]) and
+    /// range-checks them to be bytes. Returns the intensity cells, with the shape of the image.
+    fn assign_image(
         &self,
         layouter: impl Layouter<F>,
         image: Value<Array2<u8>>,
+    ) -> Result<Array2<AssignedCell<F, F>>, Error>;
+
+    /// Maps the assigned pixel intensities to a bit string.
+    fn encode_pixels(
+        &self,
+        layouter: impl Layouter<F>,
+        pixels: &Array2<AssignedCell<F, F>>,
     ) -> Result<Vec<AssignedCell<F, F>>, Error>;
+
+    /// Maps an image to a bit string.
+    fn encode_image(
+        &self,
+        mut layouter: impl Layouter<F>,
+        image: Value<Array2<u8>>,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let pixels = self.assign_image(layouter.namespace(|| "image"), image)?;
+        self.encode_pixels(layouter, &pixels)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct EncodeImageChipConfig<F: PrimeFieldBits> {
     advice_column: Column<Advice>,
+    image_column: Column<Advice>,
     greater_than_chip_config: GreaterThanChipConfig<F>,
+    range_check_config: RangeCheckConfig<F>,
 }
 
 /// Encodes an image into a bit string, as follows:
-/// - Each pixel intensity is passed to [`GreaterThanChip`] for each threshold.
-///   This also range-checks the intensity to make sure it's in the range [0, 255].
+/// - All pixel intensities are assigned once, in a region named `image`: The intensity of
+///   pixel `(i, j)` is in row `i * height + j` of the `x` column of the [`GreaterThanChip`].
+///   Each intensity is range-checked to be in the range [0, 255].
+/// - Each pixel intensity is copied to [`GreaterThanChip`] for each threshold.
 ///   - As a special case, if the threshold is 0, a constant "1" is returned
 ///     (as the intensity is always greater than 1).
-/// - Intensities belonging to the same pixel are constrained to be equal.
+///
+/// Because the layout of the image region only depends on the image shape, other gadgets
+/// (e.g. an image commitment) can consume the intensity cells directly.
 pub struct EncodeImageChip<F: PrimeFieldBits> {
     greater_than_chip: GreaterThanChip<F>,
     config: EncodeImageChipConfig<F>,
-    binarization_thresholds: Array3<u16>,
+    binarization_thresholds: Arc<Array3<u16>>,
+    /// The bit `(row, column, threshold index)` to flip, see [`EncodeImageChip::tamper_bit`].
+    #[cfg(any(test, feature = "test-support"))]
+    tampered_bit: Option<(usize, usize, usize)>,
 }
 
 impl<F: PrimeFieldBits> EncodeImageChip<F> {
     pub fn construct(
         config: EncodeImageChipConfig<F>,
-        binarization_thresholds: Array3<u16>,
+        binarization_thresholds: impl Into<Arc<Array3<u16>>>,
     ) -> Self {
         let greater_than_chip = GreaterThanChip::construct(config.greater_than_chip_config.clone());
         Self {
             greater_than_chip,
             config,
-            binarization_thresholds,
+            binarization_thresholds: binarization_thresholds.into(),
+            #[cfg(any(test, feature = "test-support"))]
+            tampered_bit: None,
+        }
+    }
+
+    /// Test support: Flips the bit of the pixel `(row, column)` for the threshold with index
+    /// `bit`, see [`crate::gadgets::wnn::Tampering::BinarizationBit`].
+    #[cfg(any(test, feature = "test-support"))]
+    pub(crate) fn tamper_bit(&mut self, (row, column): (usize, usize), bit: usize) {
+        self.tampered_bit = Some((row, column, bit));
+    }
+
+    /// Assigns the negated bit of the tampered pixel, which the constraints have to reject.
+    #[cfg(any(test, feature = "test-support"))]
+    fn tampered_bit_cell(
+        &self,
+        mut layouter: impl Layouter<F>,
+        pixel: &AssignedCell<F, F>,
+        threshold: u16,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        if threshold == 0 {
+            layouter.assign_region(
+                || "bit is one",
+                |mut region| {
+                    let cell = region.assign_advice(
+                        || "gt",
+                        self.config.advice_column,
+                        0,
+                        || Value::known(F::ZERO),
+                    )?;
+                    region.constrain_constant(cell.cell(), F::ONE)?;
+                    Ok(cell)
+                },
+            )
+        } else {
+            let t = F::from((threshold - 1) as u64);
+            self.greater_than_chip
+                .greater_than_copy_flipped(layouter.namespace(|| "gt"), pixel, t)
         }
     }
 
@@ -64,20 +127,22 @@ impl<F: PrimeFieldBits> EncodeImageChip<F> {
         range_check_config: RangeCheckConfig<F>,
     ) -> EncodeImageChipConfig<F> {
         let greater_than_chip_config =
-            GreaterThanChip::configure(meta, x, y, diff, is_gt, range_check_config);
+            GreaterThanChip::configure(meta, x, y, diff, is_gt, range_check_config.clone());
         EncodeImageChipConfig {
             advice_column: is_gt,
+            image_column: x,
             greater_than_chip_config,
+            range_check_config,
         }
     }
 }
 
 impl<F: PrimeFieldBits> EncodeImageInstructions<F> for EncodeImageChip<F> {
-    fn encode_image(
+    fn assign_image(
         &self,
         mut layouter: impl Layouter<F>,
         image: Value<Array2<u8>>,
-    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+    ) -> Result<Array2<AssignedCell<F, F>>, Error> {
         let width = self.binarization_thresholds.shape()[0];
         let height = self.binarization_thresholds.shape()[1];
 
@@ -86,7 +151,45 @@ impl<F: PrimeFieldBits> EncodeImageInstructions<F> for EncodeImageChip<F> {
             .map(|image| image.into_iter().collect_vec())
             .transpose_vec(width * height);
 
-        let mut intensity_cells: BTreeMap<(usize, usize), AssignedCell<F, F>> = BTreeMap::new();
+        let pixels = layouter.assign_region(
+            || "image",
+            |mut region| {
+                image_flat
+                    .iter()
+                    .enumerate()
+                    .map(|(index, intensity)| {
+                        region.assign_advice(
+                            || format!("pixel ({}, {})", index / height, index % height),
+                            self.config.image_column,
+                            index,
+                            || intensity.map(|x| F::from(x as u64)),
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            },
+        )?;
+
+        for (index, pixel) in pixels.iter().enumerate() {
+            let (i, j) = (index / height, index % height);
+            self.config.range_check_config.range_check(
+                layouter.namespace(|| format!("pixel ({i}, {j})")),
+                pixel.clone(),
+                8,
+            )?;
+        }
+
+        Ok(Array2::from_shape_vec((width, height), pixels).unwrap())
+    }
+
+    fn encode_pixels(
+        &self,
+        mut layouter: impl Layouter<F>,
+        pixels: &Array2<AssignedCell<F, F>>,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let width = self.binarization_thresholds.shape()[0];
+        let height = self.binarization_thresholds.shape()[1];
+        assert_eq!(pixels.dim(), (width, height));
+
         let mut bit_cells = vec![];
 
         for b in 0..self.binarization_thresholds.shape()[2] {
@@ -94,6 +197,16 @@ impl<F: PrimeFieldBits> EncodeImageInstructions<F> for EncodeImageChip<F> {
                 for j in 0..height {
                     let threshold = self.binarization_thresholds[(i, j, b)];
                     assert!(threshold <= 256);
+                    let mut layouter = layouter.namespace(|| format!("pixel ({i}, {j}) bit {b}"));
+                    #[cfg(any(test, feature = "test-support"))]
+                    if self.tampered_bit == Some((i, j, b)) {
+                        bit_cells.push(self.tampered_bit_cell(
+                            layouter,
+                            &pixels[(i, j)],
+                            threshold,
+                        )?);
+                        continue;
+                    }
 
                     let bit_cell = if threshold == 0 {
                         // If the threshold is zero, the bit is always one, regardless of the of the intensity.
@@ -115,32 +228,12 @@ impl<F: PrimeFieldBits> EncodeImageInstructions<F> for EncodeImageChip<F> {
                         // but the gadget only implements greater than, so we need to subtract 1 from the threshold.
                         // Because we already handled the threshold == 0 case, this means that `t` is now in the
                         // range [0, 255], which is required by the greater than gadget.
-                        let t = F::from((self.binarization_thresholds[(i, j, b)] - 1) as u64);
-
-                        match intensity_cells.get(&(i, j)) {
-                            None => {
-                                let image_value =
-                                    image_flat[i * height + j].map(|x| F::from(x as u64));
-                                // For the first cell, we want to remember the intensity cell, so that we can
-                                // add a copy constraint for the other thresholds.
-                                let GreaterThanWitnessResult { x_cell, gt_cell } =
-                                    self.greater_than_chip.greater_than_witness(
-                                        layouter.namespace(|| format!("gt[{}, {}]", i, j)),
-                                        image_value,
-                                        t,
-                                    )?;
-                                intensity_cells.insert((i, j), x_cell);
-                                gt_cell
-                            }
-                            Some(first_cell) => {
-                                // For the other cells, we want to add a copy constraint to the first cell.
-                                self.greater_than_chip.greater_than_copy(
-                                    layouter.namespace(|| format!("gt[{}, {}]", i, j)),
-                                    first_cell,
-                                    t,
-                                )?
-                            }
-                        }
+                        let t = F::from((threshold - 1) as u64);
+                        self.greater_than_chip.greater_than_copy(
+                            layouter.namespace(|| "gt"),
+                            &pixels[(i, j)],
+                            t,
+                        )?
                     };
                     bit_cells.push(bit_cell);
                 }
@@ -108,12 +108,15 @@ impl<F: PrimeFieldBits> GreaterThanChip<F> {
         region: &mut Region<F>,
         x: &AssignedCell<F, F>,
         y: F,
+        flip: bool,
     ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
         if to_u32(&y) > 255 {
             panic!("y must be less than 256!");
         }
 
-        let greater_than = x.value().map(|x| F::from((to_u32(x) > to_u32(&y)) as u64));
+        let greater_than = x
+            .value()
+            .map(|x| F::from(((to_u32(x) > to_u32(&y)) != flip) as u64));
         // x + diff = 256 * is_gt + y
         // -> diff = 256 * is_gt + y - x
         let diff = x
@@ -142,7 +145,7 @@ impl<F: PrimeFieldBits> GreaterThanInstructions<F> for GreaterThanChip<F> {
             || "greater_than_witness",
             |mut region| {
                 let x_cell = region.assign_advice(|| "x", self.config.x, 0, || x)?;
-                let (diff_cell, result_cell) = self.greater_than(&mut region, &x_cell, y)?;
+                let (diff_cell, result_cell) = self.greater_than(&mut region, &x_cell, y, false)?;
                 Ok((x_cell, diff_cell, result_cell))
             },
         )?;
@@ -165,16 +168,41 @@ impl<F: PrimeFieldBits> GreaterThanInstructions<F> for GreaterThanChip<F> {
     }
 
     fn greater_than_copy(
+        &self,
+        layouter: impl Layouter<F>,
+        x: &AssignedCell<F, F>,
+        y: F,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.assign_greater_than_copy(layouter, x, y, false)
+    }
+}
+
+impl<F: PrimeFieldBits> GreaterThanChip<F> {
+    /// Like [`GreaterThanInstructions::greater_than_copy`], but the result is negated (and
+    /// `diff` computed from the negated result), which the constraints have to reject. See
+    /// [`crate::gadgets::wnn::Tampering`].
+    #[cfg(any(test, feature = "test-support"))]
+    pub(crate) fn greater_than_copy_flipped(
+        &self,
+        layouter: impl Layouter<F>,
+        x: &AssignedCell<F, F>,
+        y: F,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.assign_greater_than_copy(layouter, x, y, true)
+    }
+
+    fn assign_greater_than_copy(
         &self,
         mut layouter: impl Layouter<F>,
         x: &AssignedCell<F, F>,
         y: F,
+        flip: bool,
     ) -> Result<AssignedCell<F, F>, Error> {
         let (diff_cell, result_cell) = layouter.assign_region(
             || "greater_than_copy",
             |mut region| {
                 let x_cell = x.copy_advice(|| "x", &mut region, self.config.x, 0)?;
-                self.greater_than(&mut region, &x_cell, y)
+                self.greater_than(&mut region, &x_cell, y, flip)
             },
         )?;
         self.config.range_check_config.range_check(
@@ -200,10 +228,11 @@ mod tests {
         circuit::{Layouter, SimpleFloorPlanner, Value},
         dev::MockProver,
         halo2curves::bn256::Fr as Fp,
-        plonk::{Circuit, Column, ConstraintSystem, Error, Instance, TableColumn},
+        plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
     };
 
-    use crate::gadgets::range_check::{load_bytes_column, RangeCheckConfig};
+    use crate::gadgets::byte_table::{ByteTable, ByteTableConfig};
+    use crate::gadgets::range_check::RangeCheckConfig;
 
     use super::{GreaterThanChip, GreaterThanChipConfig, GreaterThanInstructions};
 
@@ -218,7 +247,7 @@ mod tests {
     #[derive(Clone, Debug)]
     struct Config<F: PrimeFieldBits> {
         greater_than_config: GreaterThanChipConfig<F>,
-        byte_column: TableColumn,
+        byte_table_config: ByteTableConfig,
         instance: Column<Instance>,
     }
 
@@ -237,7 +266,6 @@ mod tests {
             let diff = meta.advice_column();
             let is_gt = meta.advice_column();
 
-            let byte_column = meta.lookup_table_column();
             let constants = meta.fixed_column();
             let instance = meta.instance_column();
 
@@ -248,13 +276,14 @@ mod tests {
             meta.enable_equality(instance);
             meta.enable_constant(constants);
 
-            let range_check_config = RangeCheckConfig::configure(meta, x, byte_column);
+            let mut byte_table = ByteTable::new(meta);
+            let range_check_config = RangeCheckConfig::configure(meta, x, &mut byte_table);
             let greater_than_config =
                 GreaterThanChip::configure(meta, x, y, diff, is_gt, range_check_config);
 
             Config {
                 greater_than_config,
-                byte_column,
+                byte_table_config: byte_table.configure(meta),
                 instance,
             }
         }
@@ -264,7 +293,7 @@ mod tests {
             config: Self::Config,
             mut layouter: impl Layouter<F>,
         ) -> Result<(), Error> {
-            load_bytes_column(&mut layouter, config.byte_column)?;
+            config.byte_table_config.load(&mut layouter)?;
             let greater_than_chip = GreaterThanChip::construct(config.greater_than_config);
             let result = greater_than_chip.greater_than_witness(
                 layouter.namespace(|| "greater_than"),
@@ -279,7 +308,7 @@ mod tests {
 
     #[test]
     fn test_gt_true() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             x: 129,
             y: 64,
@@ -292,7 +321,7 @@ mod tests {
 
     #[test]
     fn test_gt_false() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             x: 64,
             y: 129,
@@ -305,7 +334,7 @@ mod tests {
 
     #[test]
     fn test_gt_equal() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             x: 64,
             y: 64,
@@ -318,7 +347,7 @@ mod tests {
 
     #[test]
     fn test_x_too_large() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             x: 256,
             y: 64,
@@ -330,6 +359,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "plotting")]
     fn plot() {
         use plotters::prelude::*;
 
@@ -1,16 +1,54 @@
-use ff::PrimeFieldBits;
+//! The "MishMash" hash function `h(x) = (x^3 % p) % 2^l` of BTHOWeN, see [`HashChip`].
+//!
+//! The chip does not depend on the rest of the WNN circuit, so it can be used in other
+//! circuits as well. It needs a [`RangeCheckConfig`] (which is backed by a [`ByteTable`]),
+//! five advice columns with equality enabled and a fixed column for constants.
+//! [`HashCircuit`] is a minimal standalone circuit that shows how to wire it up: It proves
+//! knowledge of a private input whose hash is the public output.
+//!
+//! ```
+//! use halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr as Fp};
+//! use zero_g::gadgets::hash::{HashCircuit, HashFunctionConfig};
+//!
+//! // p = 2^21 - 9 is the largest 21-bit prime, so hashes have l = 20 bits
+//! let config = HashFunctionConfig { p: 2097143, l: 20, n_bits: 12 };
+//! let input = 2237;
+//! assert_eq!(config.hash(input), 825286);
+//!
+//! let circuit = HashCircuit::<Fp>::new(input, config.clone());
+//! let output = Fp::from(config.hash(input));
+//! MockProver::run(12, &circuit, vec![vec![output]])
+//!     .unwrap()
+//!     .assert_satisfied();
+//!
+//! let wrong_output = Fp::from(config.hash(input) + 1);
+//! let prover = MockProver::run(12, &circuit, vec![vec![wrong_output]]).unwrap();
+//! assert!(prover.verify().is_err());
+//! ```
+
+use std::marker::PhantomData;
+
+use ff::{PrimeField, PrimeFieldBits};
 use halo2_proofs::{
-    circuit::{AssignedCell, Layouter, Value},
-    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Selector},
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    plonk::{
+        Advice, Circuit, Column, ConstraintSystem, Constraints, Error, Expression, Instance,
+        Selector,
+    },
     poly::Rotation,
 };
 use num_bigint::BigUint;
 
-use crate::utils::integer_division;
+use crate::utils::integer_div_rem;
 
+use super::byte_table::{ByteTable, ByteTableConfig};
 use super::range_check::RangeCheckConfig;
 
+/// Instructions of the [`HashChip`].
 pub trait HashInstructions<F: PrimeFieldBits> {
+    /// Hashes the input, which has to be range-checked to
+    /// [`HashFunctionConfig::n_bits`] bits by the caller. The output is **not** range-checked
+    /// to `l` bits, see [`HashChip`].
     fn hash(
         &self,
         layouter: impl Layouter<F>,
@@ -29,6 +67,32 @@ pub struct HashFunctionConfig {
     pub n_bits: usize,
 }
 
+impl HashFunctionConfig {
+    /// The largest number of input bits for which `x^3` fits into the field `F`. Larger inputs
+    /// are reduced modulo `p` step by step, see [`HashChip`].
+    pub fn max_direct_bits<F: PrimeField>() -> usize {
+        F::CAPACITY as usize / 3
+    }
+
+    /// Whether `x^3 % p` is computed step by step, see [`HashChip`].
+    pub fn is_reduced<F: PrimeField>(&self) -> bool {
+        self.n_bits > Self::max_direct_bits::<F>()
+    }
+
+    /// Computes the hash outside of the circuit.
+    pub fn hash(&self, x: u128) -> u64 {
+        let x = BigUint::from(x);
+        let hash = (&x * &x * &x % self.p) % (BigUint::from(1u8) << self.l);
+        hash.try_into().expect("The hash has l < 64 bits")
+    }
+}
+
+impl Default for HashFunctionConfig {
+    fn default() -> Self {
+        unimplemented!("Parameters have to be specified manually!")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HashConfig<F: PrimeFieldBits> {
     selector: Selector,
@@ -41,6 +105,16 @@ pub struct HashConfig<F: PrimeFieldBits> {
     pub hash_function_config: HashFunctionConfig,
 }
 
+/// The cells of the reduced hash computation, see [`HashChip`].
+struct ReducedHashCells<F: PrimeFieldBits> {
+    /// The quotients of `x`, `x0^2` and `x1 * x0` by `p`.
+    quotients: [AssignedCell<F, F>; 3],
+    /// The remainders `x0`, `x1` and `x2`.
+    remainders: [AssignedCell<F, F>; 3],
+    msb: AssignedCell<F, F>,
+    hash: AssignedCell<F, F>,
+}
+
 /// Implements the "MishMash" hash function: `h(x) = (x^3 % p) % 2^l`.
 ///
 /// Parameters for the hash function are specified in [`HashFunctionConfig`].
@@ -58,6 +132,23 @@ pub struct HashConfig<F: PrimeFieldBits> {
 /// - `msb` is in 0 or 1
 /// - `remainder` is in [0, p)
 ///
+/// If `x^3` does not fit into the field (see [`HashFunctionConfig::is_reduced`]), it is
+/// computed modulo `p` step by step instead, such that no intermediate value exceeds
+/// `2^(2 * (l + 1))`:
+///
+/// | input    | quotient      | remainder          | msb         | hash        |
+/// |----------|---------------|--------------------|-------------|-------------|
+/// | x (copy) | x // p        | x0 = x % p         |             |             |
+/// |          | x0^2 // p     | x1 = x0^2 % p      |             |             |
+/// |          | x1 * x0 // p  | x2 = x1 * x0 % p   | x2 // 2^l   | x2 % 2^l    |
+///
+/// The following constraints are checked:
+/// - `x = quotient_0 * p + x0`, `x0^2 = quotient_1 * p + x1`, `x1 * x0 = quotient_2 * p + x2`
+/// - `x2 = msb * 2^l + hash`
+/// - `quotient_0` is in [0, 2^(n_bits - l)), `quotient_1` and `quotient_2` are in [0, 2^(l + 1))
+/// - `msb` is in 0 or 1
+/// - `x0`, `x1` and `x2` are in [0, p)
+///
 /// Note that `x` is **not** range-checked. This is assumed to happen
 /// elsewhere in the circuit.
 /// Also note that the `hash` column is not range-checked to be in [0, 2^l).
@@ -81,30 +172,51 @@ impl<F: PrimeFieldBits> HashChip<F> {
     ) -> HashConfig<F> {
         let selector = meta.selector();
 
-        meta.create_gate("hash", |meta| {
-            let selector = meta.query_selector(selector);
-
-            let input = meta.query_advice(input, Rotation::cur());
-            let quotient = meta.query_advice(quotient, Rotation::cur());
-            let remainder = meta.query_advice(remainder, Rotation::cur());
-            let msb = meta.query_advice(msb, Rotation::cur());
-            let hash = meta.query_advice(hash, Rotation::cur());
-
-            let input_cubed = input.clone() * input.clone() * input;
-            let mod_p_decomposition = quotient
-                * Expression::Constant(F::from(hash_function_config.p))
-                + remainder.clone();
-            let mod_2l_decomposition =
-                msb * Expression::Constant(F::from(1 << hash_function_config.l)) + hash;
-
-            Constraints::with_selector(
-                selector,
-                vec![
-                    input_cubed - mod_p_decomposition,
-                    remainder - mod_2l_decomposition,
-                ],
-            )
-        });
+        let p = Expression::Constant(F::from(hash_function_config.p));
+        let two_pow_l = Expression::Constant(F::from(1 << hash_function_config.l));
+        if hash_function_config.is_reduced::<F>() {
+            meta.create_gate("hash (reduced)", |meta| {
+                let selector = meta.query_selector(selector);
+
+                let input = meta.query_advice(input, Rotation::cur());
+                let [q0, q1, q2] = [0, 1, 2].map(|row| meta.query_advice(quotient, Rotation(row)));
+                let [x0, x1, x2] = [0, 1, 2].map(|row| meta.query_advice(remainder, Rotation(row)));
+                let msb = meta.query_advice(msb, Rotation(2));
+                let hash = meta.query_advice(hash, Rotation(2));
+
+                Constraints::with_selector(
+                    selector,
+                    vec![
+                        input - (q0 * p.clone() + x0.clone()),
+                        x0.clone() * x0.clone() - (q1 * p.clone() + x1.clone()),
+                        x1 * x0 - (q2 * p + x2.clone()),
+                        x2 - (msb * two_pow_l + hash),
+                    ],
+                )
+            });
+        } else {
+            meta.create_gate("hash", |meta| {
+                let selector = meta.query_selector(selector);
+
+                let input = meta.query_advice(input, Rotation::cur());
+                let quotient = meta.query_advice(quotient, Rotation::cur());
+                let remainder = meta.query_advice(remainder, Rotation::cur());
+                let msb = meta.query_advice(msb, Rotation::cur());
+                let hash = meta.query_advice(hash, Rotation::cur());
+
+                let input_cubed = input.clone() * input.clone() * input;
+                let mod_p_decomposition = quotient * p + remainder.clone();
+                let mod_2l_decomposition = msb * two_pow_l + hash;
+
+                Constraints::with_selector(
+                    selector,
+                    vec![
+                        input_cubed - mod_p_decomposition,
+                        remainder - mod_2l_decomposition,
+                    ],
+                )
+            });
+        }
 
         HashConfig {
             selector,
@@ -119,8 +231,8 @@ impl<F: PrimeFieldBits> HashChip<F> {
     }
 
     pub fn construct(config: HashConfig<F>) -> Self {
-        if (config.hash_function_config.n_bits * 3) as u32 > F::CAPACITY {
-            panic!("Field too small to store x^3!");
+        if config.hash_function_config.n_bits as u32 >= F::CAPACITY {
+            panic!("Field too small to store x!");
         }
         HashChip { config }
     }
@@ -151,15 +263,8 @@ impl<F: PrimeFieldBits> HashChip<F> {
                     input.copy_advice(|| "input", &mut region, self.config.input, 0)?;
                 let input = input_cell.value_field().evaluate();
                 let input_cubed = input * input * input;
-                let quotient = input_cubed.and_then(|input_cubed| {
-                    Value::known(integer_division(input_cubed, BigUint::from(p)))
-                });
-                let remainder = input_cubed - quotient * Value::known(F::from(p));
-
-                let msb = remainder.and_then(|remainder| {
-                    Value::known(integer_division(remainder, BigUint::from(1u8) << l))
-                });
-                let hash = remainder - msb * Value::known(F::from(1 << l));
+                let (quotient, remainder) = div_rem(input_cubed, BigUint::from(p));
+                let (msb, hash) = split_msb(remainder, l);
 
                 Ok((
                     input_cell,
@@ -171,6 +276,239 @@ impl<F: PrimeFieldBits> HashChip<F> {
             },
         )
     }
+
+    fn compute_hash_reduced(
+        &self,
+        mut layouter: impl Layouter<F>,
+        input: AssignedCell<F, F>,
+    ) -> Result<ReducedHashCells<F>, Error> {
+        let p = self.config.hash_function_config.p;
+        let l = self.config.hash_function_config.l;
+        layouter.assign_region(
+            || "hash (reduced)",
+            |mut region| {
+                self.config.selector.enable(&mut region, 0)?;
+
+                let input_cell =
+                    input.copy_advice(|| "input", &mut region, self.config.input, 0)?;
+                let x = input_cell.value().copied();
+                let (q0, x0) = div_rem(x, BigUint::from(p));
+                let (q1, x1) = div_rem(x0 * x0, BigUint::from(p));
+                let (q2, x2) = div_rem(x1 * x0, BigUint::from(p));
+                let (msb, hash) = split_msb(x2, l);
+
+                let mut assign_row = |row: usize, quotient, remainder| {
+                    Ok::<_, Error>([
+                        region.assign_advice(
+                            || format!("quotient {row}"),
+                            self.config.quotient,
+                            row,
+                            || quotient,
+                        )?,
+                        region.assign_advice(
+                            || format!("remainder {row}"),
+                            self.config.remainder,
+                            row,
+                            || remainder,
+                        )?,
+                    ])
+                };
+                let [q0, x0] = assign_row(0, q0, x0)?;
+                let [q1, x1] = assign_row(1, q1, x1)?;
+                let [q2, x2] = assign_row(2, q2, x2)?;
+
+                Ok(ReducedHashCells {
+                    quotients: [q0, q1, q2],
+                    remainders: [x0, x1, x2],
+                    msb: region.assign_advice(|| "msb", self.config.msb, 2, || msb)?,
+                    hash: region.assign_advice(|| "hash", self.config.hash, 2, || hash)?,
+                })
+            },
+        )
+    }
+
+    fn hash_reduced(
+        &self,
+        mut layouter: impl Layouter<F>,
+        input: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let ReducedHashCells {
+            quotients,
+            remainders,
+            msb,
+            hash,
+        } = self.compute_hash_reduced(layouter.namespace(|| "hash"), input)?;
+
+        let HashFunctionConfig { p, l, n_bits } = self.config.hash_function_config;
+        let range_check = &self.config.range_check_config;
+
+        // x < 2^n_bits and p >= 2^l, so x // p < 2^(n_bits - l).
+        // The other quotients are less than p < 2^(l + 1), because both factors are.
+        for (i, (quotient, n_bits)) in quotients
+            .into_iter()
+            .zip([n_bits - l, l + 1, l + 1])
+            .enumerate()
+        {
+            range_check.range_check(
+                layouter.namespace(|| format!("range check quotient {i}")),
+                quotient,
+                n_bits,
+            )?;
+        }
+        range_check.range_check(layouter.namespace(|| "range check msb"), msb, 1)?;
+
+        // All remainders have to be less than p. As with the direct computation, the last one
+        // is already l + 1 bits, because it is decomposed into msb and hash.
+        let last = remainders.len() - 1;
+        for (i, remainder) in remainders.into_iter().enumerate() {
+            if i != last {
+                range_check.range_check(
+                    layouter.namespace(|| format!("range check remainder {i}")),
+                    remainder.clone(),
+                    l + 1,
+                )?;
+            }
+            range_check.le_constant(
+                layouter.namespace(|| format!("remainder {i} < p")),
+                remainder,
+                F::from(p - 1),
+            )?;
+        }
+
+        Ok(hash)
+    }
+}
+
+/// The configuration of [`HashCircuit`].
+#[derive(Debug, Clone)]
+pub struct HashCircuitConfig<F: PrimeFieldBits> {
+    hash_config: HashConfig<F>,
+    byte_table_config: ByteTableConfig,
+    instance: Column<Instance>,
+}
+
+/// A standalone circuit using [`HashChip`]: Proves knowledge of a private input of
+/// [`HashFunctionConfig::n_bits`] bits whose hash is the public input (in the first row of
+/// the only instance column). Unlike the chip, it range-checks the input and the output.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Debug, Clone)]
+pub struct HashCircuit<F: PrimeFieldBits> {
+    input: Value<u128>,
+    hash_function_config: HashFunctionConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeFieldBits> HashCircuit<F> {
+    pub fn new(input: u128, hash_function_config: HashFunctionConfig) -> Self {
+        Self {
+            input: Value::known(input),
+            hash_function_config,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeFieldBits> Circuit<F> for HashCircuit<F> {
+    type Config = HashCircuitConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+    type Params = HashFunctionConfig;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            input: Value::unknown(),
+            hash_function_config: self.hash_function_config.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn params(&self) -> Self::Params {
+        self.hash_function_config.clone()
+    }
+
+    fn configure_with_params(meta: &mut ConstraintSystem<F>, params: Self::Params) -> Self::Config {
+        let [input, quotient, remainder, msb, hash] = [(); 5].map(|_| meta.advice_column());
+        for column in [input, quotient, remainder, msb, hash] {
+            meta.enable_equality(column);
+        }
+        let constants = meta.fixed_column();
+        meta.enable_constant(constants);
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+
+        let mut byte_table = ByteTable::new(meta);
+        let range_check_config = RangeCheckConfig::configure(meta, input, &mut byte_table);
+        let hash_config = HashChip::configure(
+            meta,
+            input,
+            quotient,
+            remainder,
+            msb,
+            hash,
+            range_check_config,
+            params,
+        );
+        HashCircuitConfig {
+            hash_config,
+            byte_table_config: byte_table.configure(meta),
+            instance,
+        }
+    }
+
+    fn configure(_meta: &mut ConstraintSystem<F>) -> Self::Config {
+        unimplemented!("configure_with_params should be used!")
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.byte_table_config.load(&mut layouter)?;
+
+        let input = layouter.assign_region(
+            || "input",
+            |mut region| {
+                region.assign_advice(
+                    || "input",
+                    config.hash_config.input,
+                    0,
+                    || self.input.map(F::from_u128),
+                )
+            },
+        )?;
+        let HashFunctionConfig { l, n_bits, .. } = self.hash_function_config;
+        let range_check_config = &config.hash_config.range_check_config;
+        range_check_config.range_check(
+            layouter.namespace(|| "range check input"),
+            input.clone(),
+            n_bits,
+        )?;
+
+        let hash_chip = HashChip::construct(config.hash_config.clone());
+        let hash = hash_chip.hash(layouter.namespace(|| "hash"), input)?;
+        range_check_config.range_check(
+            layouter.namespace(|| "range check hash"),
+            hash.clone(),
+            l,
+        )?;
+
+        layouter.constrain_instance(hash.cell(), config.instance, 0)
+    }
+}
+
+/// Computes `(x // divisor, x % divisor)`.
+fn div_rem<F: PrimeFieldBits>(x: Value<F>, divisor: BigUint) -> (Value<F>, Value<F>) {
+    let result = x.map(|x| integer_div_rem(x, &divisor));
+    (
+        result.map(|(quotient, _)| quotient),
+        result.map(|(_, remainder)| remainder),
+    )
+}
+
+/// Computes `(x // 2^l, x % 2^l)`.
+fn split_msb<F: PrimeFieldBits>(x: Value<F>, l: usize) -> (Value<F>, Value<F>) {
+    div_rem(x, BigUint::from(1u8) << l)
 }
 
 impl<F: PrimeFieldBits> HashInstructions<F> for HashChip<F> {
@@ -179,6 +517,10 @@ impl<F: PrimeFieldBits> HashInstructions<F> for HashChip<F> {
         mut layouter: impl Layouter<F>,
         input: AssignedCell<F, F>,
     ) -> Result<AssignedCell<F, F>, Error> {
+        if self.config.hash_function_config.is_reduced::<F>() {
+            return self.hash_reduced(layouter, input);
+        }
+
         let (_input, quotient, remainder, msb, output) =
             self.compute_hash(layouter.namespace(|| "hash"), input)?;
 
@@ -220,27 +562,30 @@ mod tests {
         circuit::SimpleFloorPlanner,
         dev::MockProver,
         halo2curves::bn256::Fr as Fp,
-        plonk::{Circuit, Column, Instance, TableColumn},
+        plonk::{Circuit, Column, Instance},
     };
+    use num_bigint::BigUint;
 
-    use crate::gadgets::range_check::{load_bytes_column, RangeCheckConfig};
+    use crate::gadgets::byte_table::{ByteTable, ByteTableConfig};
+    use crate::gadgets::range_check::RangeCheckConfig;
 
-    use super::{HashChip, HashConfig, HashFunctionConfig, HashInstructions};
+    use super::{HashChip, HashCircuit, HashConfig, HashFunctionConfig, HashInstructions};
 
+    /// Hashes an input of `N_BITS` bits, with `p = 11` and `l = 3`.
     #[derive(Default)]
-    struct MyCircuit<F: PrimeFieldBits> {
-        input: u64,
+    struct MyCircuit<F: PrimeFieldBits, const N_BITS: usize> {
+        input: u128,
         _marker: PhantomData<F>,
     }
 
     #[derive(Clone, Debug)]
     struct Config<F: PrimeFieldBits> {
         hash_config: HashConfig<F>,
-        table_column: TableColumn,
+        byte_table_config: ByteTableConfig,
         instance: Column<Instance>,
     }
 
-    impl<F: PrimeFieldBits> Circuit<F> for MyCircuit<F> {
+    impl<F: PrimeFieldBits, const N_BITS: usize> Circuit<F> for MyCircuit<F, N_BITS> {
         type Config = Config<F>;
         type FloorPlanner = SimpleFloorPlanner;
         type Params = ();
@@ -271,11 +616,11 @@ mod tests {
             let hash_function_config = HashFunctionConfig {
                 p: 11,
                 l: 3,
-                n_bits: 8,
+                n_bits: N_BITS,
             };
 
-            let table_column = meta.lookup_table_column();
-            let lookup_range_check = RangeCheckConfig::configure(meta, input, table_column);
+            let mut byte_table = ByteTable::new(meta);
+            let lookup_range_check = RangeCheckConfig::configure(meta, input, &mut byte_table);
 
             Config {
                 hash_config: HashChip::configure(
@@ -288,7 +633,7 @@ mod tests {
                     lookup_range_check,
                     hash_function_config,
                 ),
-                table_column,
+                byte_table_config: byte_table.configure(meta),
                 instance,
             }
         }
@@ -305,12 +650,12 @@ mod tests {
                         || "input",
                         config.hash_config.input,
                         0,
-                        || Value::known(F::from(self.input)),
+                        || Value::known(F::from_u128(self.input)),
                     )
                 },
             )?;
 
-            load_bytes_column(&mut layouter, config.table_column)?;
+            config.byte_table_config.load(&mut layouter)?;
             let hash_chip = HashChip::construct(config.hash_config);
             let hash_value = hash_chip.hash(layouter.namespace(|| "hash"), assigned_input)?;
 
@@ -321,8 +666,8 @@ mod tests {
 
     #[test]
     fn test_2() {
-        let k = 9;
-        let circuit = MyCircuit::<Fp> {
+        let k = 12;
+        let circuit = MyCircuit::<Fp, 8> {
             input: 2,
             _marker: PhantomData,
         };
@@ -334,8 +679,8 @@ mod tests {
 
     #[test]
     fn test_4() {
-        let k = 9;
-        let circuit = MyCircuit::<Fp> {
+        let k = 12;
+        let circuit = MyCircuit::<Fp, 8> {
             input: 4,
             _marker: PhantomData,
         };
@@ -347,8 +692,8 @@ mod tests {
 
     #[test]
     fn test_42() {
-        let k = 9;
-        let circuit = MyCircuit::<Fp> {
+        let k = 12;
+        let circuit = MyCircuit::<Fp, 8> {
             input: 42,
             _marker: PhantomData,
         };
@@ -360,8 +705,8 @@ mod tests {
 
     #[test]
     fn test_255() {
-        let k = 9;
-        let circuit = MyCircuit::<Fp> {
+        let k = 12;
+        let circuit = MyCircuit::<Fp, 8> {
             input: 255,
             _marker: PhantomData,
         };
@@ -372,6 +717,77 @@ mod tests {
     }
 
     #[test]
+    fn test_reduced() {
+        assert!(HashFunctionConfig {
+            p: 11,
+            l: 3,
+            n_bits: 100
+        }
+        .is_reduced::<Fp>());
+
+        let input = (1 << 99) + 12345;
+        let circuit = MyCircuit::<Fp, 100> {
+            input,
+            _marker: PhantomData,
+        };
+        let x = BigUint::from(input);
+        let output: u64 = ((&x * &x * &x % 11u8) % 8u8).try_into().unwrap();
+        let prover = MockProver::run(12, &circuit, vec![vec![Fp::from(output)]]).unwrap();
+        prover.assert_satisfied();
+
+        let wrong_output = Fp::from((output + 1) % 8);
+        let prover = MockProver::run(12, &circuit, vec![vec![wrong_output]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_hash_circuit() {
+        let config = HashFunctionConfig {
+            p: 2097143,
+            l: 20,
+            n_bits: 12,
+        };
+        // See `crate::test_vectors`
+        assert_eq!(config.hash(2237), 825286);
+        assert_eq!(config.hash(3788), (46 << 10) + 494);
+
+        for input in [0, 2237, 3788, (1 << 12) - 1] {
+            let circuit = HashCircuit::<Fp>::new(input, config.clone());
+            let output = Fp::from(config.hash(input));
+            let prover = MockProver::run(12, &circuit, vec![vec![output]]).unwrap();
+            prover.assert_satisfied();
+
+            let wrong_output = Fp::from(config.hash(input) + 1);
+            let prover = MockProver::run(12, &circuit, vec![vec![wrong_output]]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+
+        // The input has more than n_bits bits
+        let input = (1 << 12) + 5;
+        let circuit = HashCircuit::<Fp>::new(input, config.clone());
+        let output = Fp::from(config.hash(input));
+        let prover = MockProver::run(12, &circuit, vec![vec![output]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_hash_circuit_reduced() {
+        let config = HashFunctionConfig {
+            p: 11,
+            l: 3,
+            n_bits: 100,
+        };
+        assert!(config.is_reduced::<Fp>());
+
+        let input = (1 << 99) + 12345;
+        let circuit = HashCircuit::<Fp>::new(input, config.clone());
+        let output = Fp::from(config.hash(input));
+        let prover = MockProver::run(12, &circuit, vec![vec![output]]).unwrap();
+        prover.assert_satisfied();
+    }
+
+    #[test]
+    #[cfg(feature = "plotting")]
     fn plot() {
         use plotters::prelude::*;
 
@@ -379,7 +795,7 @@ mod tests {
         root.fill(&WHITE).unwrap();
         let root = root.titled("Hash Chip Layout", ("sans-serif", 60)).unwrap();
 
-        let circuit = MyCircuit::<Fp> {
+        let circuit = MyCircuit::<Fp, 8> {
             input: 42,
             _marker: PhantomData,
         };
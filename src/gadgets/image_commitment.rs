@@ -0,0 +1,394 @@
+//! A gadget that commits to the witnessed image, so that a proof shows which image it was
+//! generated for (see [`crate::image_commitment`]) and can link proofs of a sequence of images
+//! (see [`crate::proof_chain`]).
+//!
+//! The commitment is the [Poseidon hash](super::poseidon) of the pixels in row-major order,
+//! packed into field elements of [`BYTES_PER_ELEMENT`] big-endian bytes each, followed by the
+//! two 16-byte halves of the salt (zeros without a salt). The domain separator depends on the
+//! image shape, see [`image_domain`]. Chaining values are the Poseidon hash of the previous
+//! chaining value and the commitment, with another domain separator, see [`chain`].
+//!
+//! [`commit`] and [`chain`] compute the same values outside of the circuit.
+
+use ff::{PrimeField, PrimeFieldBits};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Selector},
+    poly::Rotation,
+};
+use ndarray::Array2;
+
+use super::poseidon::{
+    bytes_to_field, hash_to_field, PoseidonChip, PoseidonChipConfig, PoseidonInstructions,
+    PoseidonSpec,
+};
+use crate::image_commitment::SALT_SIZE;
+
+/// The number of pixels packed into one field element. 31 bytes always fit into the scalar
+/// field of BN254, so the packing is injective.
+pub const BYTES_PER_ELEMENT: usize = 31;
+
+/// The domain separator of the commitments to images of the given shape.
+pub fn image_domain<F: PrimeField>(shape: (usize, usize)) -> F {
+    let mut tag = b"zero_g.image.v2".to_vec();
+    tag.extend((shape.0 as u64).to_le_bytes());
+    tag.extend((shape.1 as u64).to_le_bytes());
+    hash_to_field(&tag)
+}
+
+/// The domain separator of chaining values.
+pub fn chain_domain<F: PrimeField>() -> F {
+    hash_to_field(b"zero_g.chain.v2")
+}
+
+/// The salt as two field elements.
+fn salt_elements<F: PrimeField>(salt: &[u8; SALT_SIZE]) -> [F; 2] {
+    let (high, low) = salt.split_at(SALT_SIZE / 2);
+    [bytes_to_field(high), bytes_to_field(low)]
+}
+
+/// Computes the commitment to an image outside of the circuit, see the module documentation.
+pub fn commit<F: PrimeField>(
+    spec: &PoseidonSpec<F>,
+    image: &Array2<u8>,
+    salt: &[u8; SALT_SIZE],
+) -> F {
+    let pixels: Vec<u8> = image.iter().copied().collect();
+    let inputs: Vec<F> = pixels
+        .chunks(BYTES_PER_ELEMENT)
+        .map(bytes_to_field)
+        .chain(salt_elements(salt))
+        .collect();
+    spec.hash(image_domain(image.dim()), &inputs)
+}
+
+/// Computes a chaining value outside of the circuit, see the module documentation.
+pub fn chain<F: PrimeField>(spec: &PoseidonSpec<F>, previous: F, commitment: F) -> F {
+    spec.hash(chain_domain(), &[previous, commitment])
+}
+
+pub trait ImageCommitmentInstructions<F: PrimeFieldBits> {
+    /// Commits to the pixels with the given salt and returns the cell of the commitment. The
+    /// pixels have to be range-checked to bytes by the caller (see
+    /// [`super::encode_image::EncodeImageInstructions::assign_image`]), otherwise the packing
+    /// is not injective.
+    fn commit(
+        &self,
+        layouter: impl Layouter<F>,
+        pixels: &Array2<AssignedCell<F, F>>,
+        salt: Value<[u8; SALT_SIZE]>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    /// Assigns the previous chaining value and computes the chaining value of the commitment.
+    /// Returns the cells of both (which the caller has to constrain, e.g. to public inputs).
+    fn chain(
+        &self,
+        layouter: impl Layouter<F>,
+        previous: Value<F>,
+        commitment: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error>;
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageCommitmentChipConfig<F: PrimeFieldBits> {
+    byte: Column<Advice>,
+    accumulator: Column<Advice>,
+    selector: Selector,
+    poseidon_config: PoseidonChipConfig<F>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageCommitmentChip<F: PrimeFieldBits> {
+    config: ImageCommitmentChipConfig<F>,
+    poseidon_chip: PoseidonChip<F>,
+}
+
+/// Packs the pixels with a running sum, with one row per pixel, and hashes the packed values
+/// with the [`PoseidonChip`].
+///
+/// The layout of the packing is as follows:
+/// | byte     | accumulator      |
+/// |----------|------------------|
+/// |          | 0 (constant)     |
+/// | b (copy) | acc' * 256 + b   |
+///
+/// The following constraint is enforced (except in the first row of a packed value):
+/// - accumulator = 256 * previous accumulator + byte
+impl<F: PrimeFieldBits> ImageCommitmentChip<F> {
+    pub fn construct(config: ImageCommitmentChipConfig<F>) -> Self {
+        let poseidon_chip = PoseidonChip::construct(config.poseidon_config.clone());
+        Self {
+            config,
+            poseidon_chip,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice_columns: [Column<Advice>; 6],
+    ) -> ImageCommitmentChipConfig<F> {
+        let byte = advice_columns[0];
+        let accumulator = advice_columns[1];
+        let selector = meta.selector();
+
+        meta.create_gate("pack bytes", |meta| {
+            let selector = meta.query_selector(selector);
+            let byte = meta.query_advice(byte, Rotation::cur());
+            let current = meta.query_advice(accumulator, Rotation::cur());
+            let previous = meta.query_advice(accumulator, Rotation::prev());
+            let base = Expression::Constant(F::from(256));
+            Constraints::with_selector(selector, vec![current - (previous * base + byte)])
+        });
+
+        let poseidon_config = PoseidonChip::configure(
+            meta,
+            [advice_columns[0], advice_columns[1], advice_columns[2]],
+            [advice_columns[3], advice_columns[4], advice_columns[5]],
+        );
+
+        ImageCommitmentChipConfig {
+            byte,
+            accumulator,
+            selector,
+            poseidon_config,
+        }
+    }
+
+    /// Packs the bytes into a single field element.
+    fn pack(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bytes: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "pack",
+            |mut region| {
+                let mut accumulator = region.assign_advice_from_constant(
+                    || "accumulator",
+                    self.config.accumulator,
+                    0,
+                    F::ZERO,
+                )?;
+                for (i, byte) in bytes.iter().enumerate() {
+                    let row = i + 1;
+                    self.config.selector.enable(&mut region, row)?;
+                    byte.copy_advice(|| "byte", &mut region, self.config.byte, row)?;
+                    let value = accumulator
+                        .value()
+                        .zip(byte.value())
+                        .map(|(acc, byte)| *acc * F::from(256) + byte);
+                    accumulator = region.assign_advice(
+                        || "accumulator",
+                        self.config.accumulator,
+                        row,
+                        || value,
+                    )?;
+                }
+                Ok(accumulator)
+            },
+        )
+    }
+
+    /// Assigns private values, e.g. the salt.
+    fn assign_private(
+        &self,
+        mut layouter: impl Layouter<F>,
+        values: &[Value<F>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        layouter.assign_region(
+            || "private inputs",
+            |mut region| {
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(row, value)| {
+                        region.assign_advice(|| "value", self.config.accumulator, row, || *value)
+                    })
+                    .collect()
+            },
+        )
+    }
+}
+
+impl<F: PrimeFieldBits> ImageCommitmentInstructions<F> for ImageCommitmentChip<F> {
+    fn commit(
+        &self,
+        mut layouter: impl Layouter<F>,
+        pixels: &Array2<AssignedCell<F, F>>,
+        salt: Value<[u8; SALT_SIZE]>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let pixel_cells: Vec<_> = pixels.iter().cloned().collect();
+        let mut inputs = pixel_cells
+            .chunks(BYTES_PER_ELEMENT)
+            .enumerate()
+            .map(|(i, chunk)| self.pack(layouter.namespace(|| format!("pack {i}")), chunk))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let salt = salt.map(|salt| salt_elements::<F>(&salt));
+        inputs.extend(self.assign_private(
+            layouter.namespace(|| "salt"),
+            &[salt.map(|salt| salt[0]), salt.map(|salt| salt[1])],
+        )?);
+
+        self.poseidon_chip.hash(
+            layouter.namespace(|| "hash"),
+            image_domain(pixels.dim()),
+            &inputs,
+        )
+    }
+
+    fn chain(
+        &self,
+        mut layouter: impl Layouter<F>,
+        previous: Value<F>,
+        commitment: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let previous = self
+            .assign_private(
+                layouter.namespace(|| "previous chaining value"),
+                &[previous],
+            )?
+            .remove(0);
+        let chaining_value = self.poseidon_chip.hash(
+            layouter.namespace(|| "hash"),
+            chain_domain(),
+            &[previous.clone(), commitment.clone()],
+        )?;
+        Ok((previous, chaining_value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use ff::PrimeFieldBits;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::bn256::Fr as Fp,
+        plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+    };
+    use ndarray::Array2;
+
+    use super::{
+        chain, commit, ImageCommitmentChip, ImageCommitmentChipConfig, ImageCommitmentInstructions,
+    };
+    use crate::gadgets::poseidon::PoseidonSpec;
+
+    /// Exposes the commitment to the image, the previous chaining value and the chaining value.
+    #[derive(Default)]
+    struct MyCircuit<F: PrimeFieldBits> {
+        image: Array2<u8>,
+        salt: [u8; 32],
+        previous: u64,
+        _marker: PhantomData<F>,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Config<F: PrimeFieldBits> {
+        commitment_config: ImageCommitmentChipConfig<F>,
+        instance: Column<Instance>,
+    }
+
+    impl<F: PrimeFieldBits> Circuit<F> for MyCircuit<F> {
+        type Config = Config<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                image: Array2::zeros(self.image.dim()),
+                ..Self::default()
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let advice_columns = [(); 6].map(|_| meta.advice_column());
+            let constants = meta.fixed_column();
+            let instance = meta.instance_column();
+
+            for advice in advice_columns {
+                meta.enable_equality(advice);
+            }
+            meta.enable_equality(instance);
+            meta.enable_constant(constants);
+
+            Config {
+                commitment_config: ImageCommitmentChip::configure(meta, advice_columns),
+                instance,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let chip = ImageCommitmentChip::construct(config.commitment_config);
+            let pixels = layouter.assign_region(
+                || "image",
+                |mut region| {
+                    self.image
+                        .iter()
+                        .enumerate()
+                        .map(|(row, x)| {
+                            region.assign_advice(
+                                || "pixel",
+                                chip.config.byte,
+                                row,
+                                || Value::known(F::from(*x as u64)),
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                },
+            )?;
+            let pixels = Array2::from_shape_vec(self.image.dim(), pixels).unwrap();
+
+            let commitment = chip.commit(
+                layouter.namespace(|| "commit"),
+                &pixels,
+                Value::known(self.salt),
+            )?;
+            let (previous, chaining_value) = chip.chain(
+                layouter.namespace(|| "chain"),
+                Value::known(F::from(self.previous)),
+                &commitment,
+            )?;
+            for (i, cell) in [commitment, previous, chaining_value].iter().enumerate() {
+                layouter.constrain_instance(cell.cell(), config.instance, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_commitment() {
+        let spec = PoseidonSpec::<Fp>::new();
+        // 70 pixels are packed into 3 field elements
+        let image = Array2::from_shape_fn((7, 10), |(i, j)| (i * 37 + j * 11) as u8);
+        let salt = [3; 32];
+        let commitment = commit(&spec, &image, &salt);
+        assert_ne!(commitment, commit(&spec, &image, &[0; 32]));
+        let reshaped = image.clone().into_shape((10, 7)).unwrap();
+        assert_ne!(commitment, commit(&spec, &reshaped, &salt));
+
+        let circuit = MyCircuit::<Fp> {
+            image,
+            salt,
+            previous: 5,
+            _marker: PhantomData,
+        };
+        let chaining_value = chain(&spec, Fp::from(5), commitment);
+        let expected = vec![commitment, Fp::from(5), chaining_value];
+        let prover = MockProver::run(10, &circuit, vec![expected.clone()]).unwrap();
+        prover.assert_satisfied();
+
+        for i in 0..expected.len() {
+            let mut wrong = expected.clone();
+            wrong[i] += Fp::from(1);
+            let prover = MockProver::run(10, &circuit, vec![wrong]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+}
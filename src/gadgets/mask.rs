@@ -0,0 +1,123 @@
+//! A gadget that replaces some of the pixels of an image by zeros, e.g. to prove occlusion-based
+//! explanations (see [`crate::occlusion`]).
+
+use ff::PrimeFieldBits;
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+pub trait MaskInstructions<F: PrimeFieldBits> {
+    /// Copies the values and replaces those whose mask bit is set by zero. Returns the masked
+    /// values and the cells of the mask bits (which the caller has to constrain, e.g. to public
+    /// inputs).
+    #[allow(clippy::type_complexity)]
+    fn mask(
+        &self,
+        layouter: impl Layouter<F>,
+        values: &[AssignedCell<F, F>],
+        mask: Value<Vec<bool>>,
+    ) -> Result<(Vec<AssignedCell<F, F>>, Vec<AssignedCell<F, F>>), Error>;
+}
+
+#[derive(Debug, Clone)]
+pub struct MaskChipConfig {
+    x: Column<Advice>,
+    mask: Column<Advice>,
+    masked: Column<Advice>,
+    selector: Selector,
+}
+
+#[derive(Debug, Clone)]
+pub struct MaskChip {
+    config: MaskChipConfig,
+}
+
+/// Implements masking, with one row per value.
+///
+/// The layout is as follows:
+/// | x        | mask | masked           |
+/// |----------|------|------------------|
+/// | x (copy) | m    | x * (1 - m)      |
+///
+/// The following constraints are enforced:
+/// - m is a bit
+/// - masked = x * (1 - m)
+impl MaskChip {
+    pub fn construct(config: MaskChipConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn configure<F: PrimeFieldBits>(
+        meta: &mut ConstraintSystem<F>,
+        x: Column<Advice>,
+        mask: Column<Advice>,
+        masked: Column<Advice>,
+    ) -> MaskChipConfig {
+        let selector = meta.selector();
+
+        meta.create_gate("masked = x * (1 - mask)", |meta| {
+            let selector = meta.query_selector(selector);
+
+            let x = meta.query_advice(x, Rotation::cur());
+            let mask = meta.query_advice(mask, Rotation::cur());
+            let masked = meta.query_advice(masked, Rotation::cur());
+
+            let one = Expression::Constant(F::ONE);
+
+            Constraints::with_selector(
+                selector,
+                vec![
+                    mask.clone() * (one.clone() - mask.clone()),
+                    masked - x * (one - mask),
+                ],
+            )
+        });
+
+        MaskChipConfig {
+            x,
+            mask,
+            masked,
+            selector,
+        }
+    }
+}
+
+impl<F: PrimeFieldBits> MaskInstructions<F> for MaskChip {
+    fn mask(
+        &self,
+        mut layouter: impl Layouter<F>,
+        values: &[AssignedCell<F, F>],
+        mask: Value<Vec<bool>>,
+    ) -> Result<(Vec<AssignedCell<F, F>>, Vec<AssignedCell<F, F>>), Error> {
+        let mask = mask.transpose_vec(values.len());
+        layouter.assign_region(
+            || "mask",
+            |mut region| {
+                let mut masked_cells = vec![];
+                let mut mask_cells = vec![];
+                for (row, (x, m)) in values.iter().zip(&mask).enumerate() {
+                    self.config.selector.enable(&mut region, row)?;
+
+                    x.copy_advice(|| "x", &mut region, self.config.x, row)?;
+                    let m = m.map(|m| F::from(m as u64));
+                    mask_cells.push(region.assign_advice(
+                        || "mask",
+                        self.config.mask,
+                        row,
+                        || m,
+                    )?);
+                    let masked = x.value().zip(m).map(|(x, m)| *x * (F::ONE - m));
+                    masked_cells.push(region.assign_advice(
+                        || "masked",
+                        self.config.masked,
+                        row,
+                        || masked,
+                    )?);
+                }
+                Ok((masked_cells, mask_cells))
+            },
+        )
+    }
+}
@@ -0,0 +1,312 @@
+//! A gadget that checks that two images are within an L∞ distance of each other, e.g. to prove
+//! that a prediction is robust to a bounded perturbation (see [`crate::robustness`]).
+
+use ff::PrimeFieldBits;
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Selector},
+    poly::Rotation,
+};
+
+use super::range_check::RangeCheckConfig;
+
+/// The number of bits of the slack between the bound and a pixel difference, which is in
+/// `[0, 510]` for bytes.
+const SLACK_NUM_BITS: usize = 9;
+
+pub trait PerturbationInstructions<F: PrimeFieldBits> {
+    /// Assigns the bound and constrains `|original[i] - perturbed[i]| <= bound` for all `i`.
+    /// The values are copied from existing cells and assumed to be bytes (this should be
+    /// enforced wherever they are assigned); the bound is range-checked to be a byte. Returns
+    /// the cell of the bound (which the caller has to constrain, e.g. to a public input).
+    fn check_bound(
+        &self,
+        layouter: impl Layouter<F>,
+        original: &[AssignedCell<F, F>],
+        perturbed: &[AssignedCell<F, F>],
+        bound: Value<u8>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+}
+
+#[derive(Debug, Clone)]
+pub struct PerturbationChipConfig<F: PrimeFieldBits> {
+    x: Column<Advice>,
+    y: Column<Advice>,
+    bound: Column<Advice>,
+    lower: Column<Advice>,
+    upper: Column<Advice>,
+    selector: Selector,
+
+    range_check_config: RangeCheckConfig<F>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PerturbationChip<F: PrimeFieldBits> {
+    config: PerturbationChipConfig<F>,
+}
+
+/// Implements the L∞ bound, with one row per value.
+///
+/// The layout is as follows:
+/// | x        | y        | bound        | lower         | upper         |
+/// |----------|----------|--------------|---------------|---------------|
+/// | x (copy) | y (copy) | b (copy)     | b - x + y     | b + x - y     |
+///
+/// The following constraints are enforced:
+/// - lower = b - x + y
+/// - upper = b + x - y
+/// - lower and upper are in `[0, 2^9)` (via RangeCheckConfig)
+///
+/// For bytes `x`, `y` and `b`, `lower` and `upper` are only in range if `|x - y| <= b`.
+impl<F: PrimeFieldBits> PerturbationChip<F> {
+    pub fn construct(config: PerturbationChipConfig<F>) -> Self {
+        Self { config }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        x: Column<Advice>,
+        y: Column<Advice>,
+        bound: Column<Advice>,
+        lower: Column<Advice>,
+        upper: Column<Advice>,
+        range_check_config: RangeCheckConfig<F>,
+    ) -> PerturbationChipConfig<F> {
+        let selector = meta.selector();
+
+        meta.create_gate("|x - y| <= bound", |meta| {
+            let selector = meta.query_selector(selector);
+
+            let x = meta.query_advice(x, Rotation::cur());
+            let y = meta.query_advice(y, Rotation::cur());
+            let bound = meta.query_advice(bound, Rotation::cur());
+            let lower = meta.query_advice(lower, Rotation::cur());
+            let upper = meta.query_advice(upper, Rotation::cur());
+
+            Constraints::with_selector(
+                selector,
+                vec![
+                    lower - (bound.clone() - x.clone() + y.clone()),
+                    upper - (bound + x - y),
+                ],
+            )
+        });
+
+        PerturbationChipConfig {
+            x,
+            y,
+            bound,
+            lower,
+            upper,
+            selector,
+            range_check_config,
+        }
+    }
+}
+
+impl<F: PrimeFieldBits> PerturbationInstructions<F> for PerturbationChip<F> {
+    fn check_bound(
+        &self,
+        mut layouter: impl Layouter<F>,
+        original: &[AssignedCell<F, F>],
+        perturbed: &[AssignedCell<F, F>],
+        bound: Value<u8>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        assert_eq!(original.len(), perturbed.len());
+
+        let (bound_cell, slack_cells) = layouter.assign_region(
+            || "perturbation",
+            |mut region| {
+                let bound = bound.map(|bound| F::from(bound as u64));
+                let mut bound_cell = None;
+                let mut slack_cells = vec![];
+                for (row, (x, y)) in original.iter().zip(perturbed).enumerate() {
+                    self.config.selector.enable(&mut region, row)?;
+
+                    x.copy_advice(|| "x", &mut region, self.config.x, row)?;
+                    y.copy_advice(|| "y", &mut region, self.config.y, row)?;
+                    let b = match &bound_cell {
+                        None => {
+                            region.assign_advice(|| "bound", self.config.bound, row, || bound)?
+                        }
+                        Some(cell) => {
+                            cell.copy_advice(|| "bound", &mut region, self.config.bound, row)?
+                        }
+                    };
+                    bound_cell.get_or_insert(b);
+
+                    let diff = x.value().zip(y.value()).map(|(x, y)| *x - *y);
+                    let lower = bound.zip(diff).map(|(b, diff)| b - diff);
+                    let upper = bound.zip(diff).map(|(b, diff)| b + diff);
+                    slack_cells.push(region.assign_advice(
+                        || "lower",
+                        self.config.lower,
+                        row,
+                        || lower,
+                    )?);
+                    slack_cells.push(region.assign_advice(
+                        || "upper",
+                        self.config.upper,
+                        row,
+                        || upper,
+                    )?);
+                }
+                Ok((
+                    bound_cell.expect("At least one value is required"),
+                    slack_cells,
+                ))
+            },
+        )?;
+
+        self.config.range_check_config.range_check(
+            layouter.namespace(|| "range_check_bound"),
+            bound_cell.clone(),
+            8,
+        )?;
+        for (i, slack) in slack_cells.into_iter().enumerate() {
+            self.config.range_check_config.range_check(
+                layouter.namespace(|| format!("range_check_slack {i}")),
+                slack,
+                SLACK_NUM_BITS,
+            )?;
+        }
+        Ok(bound_cell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use ff::PrimeFieldBits;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::bn256::Fr as Fp,
+        plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+    };
+
+    use crate::gadgets::byte_table::{ByteTable, ByteTableConfig};
+    use crate::gadgets::range_check::RangeCheckConfig;
+
+    use super::{PerturbationChip, PerturbationChipConfig, PerturbationInstructions};
+
+    /// Checks that `|original[i] - perturbed[i]| <= bound` and exposes the bound.
+    #[derive(Default)]
+    struct MyCircuit<F: PrimeFieldBits> {
+        original: Vec<u8>,
+        perturbed: Vec<u8>,
+        bound: u8,
+        _marker: PhantomData<F>,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Config<F: PrimeFieldBits> {
+        perturbation_config: PerturbationChipConfig<F>,
+        byte_table_config: ByteTableConfig,
+        instance: Column<Instance>,
+    }
+
+    impl<F: PrimeFieldBits> Circuit<F> for MyCircuit<F> {
+        type Config = Config<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let advice_columns = [(); 6].map(|_| meta.advice_column());
+            let constants = meta.fixed_column();
+            let instance = meta.instance_column();
+
+            for advice in advice_columns {
+                meta.enable_equality(advice);
+            }
+            meta.enable_equality(instance);
+            meta.enable_constant(constants);
+
+            let mut byte_table = ByteTable::new(meta);
+            let range_check_config =
+                RangeCheckConfig::configure(meta, advice_columns[5], &mut byte_table);
+            let perturbation_config = PerturbationChip::configure(
+                meta,
+                advice_columns[0],
+                advice_columns[1],
+                advice_columns[2],
+                advice_columns[3],
+                advice_columns[4],
+                range_check_config,
+            );
+
+            Config {
+                perturbation_config,
+                byte_table_config: byte_table.configure(meta),
+                instance,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.byte_table_config.load(&mut layouter)?;
+            let chip = PerturbationChip::construct(config.perturbation_config);
+            let (original, perturbed) = layouter.assign_region(
+                || "images",
+                |mut region| {
+                    let mut assign = |name, column, values: &[u8]| {
+                        values
+                            .iter()
+                            .enumerate()
+                            .map(|(row, x)| {
+                                let x = Value::known(F::from(*x as u64));
+                                region.assign_advice(|| name, column, row, || x)
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                    };
+                    Ok((
+                        assign("original", chip.config.x, &self.original)?,
+                        assign("perturbed", chip.config.y, &self.perturbed)?,
+                    ))
+                },
+            )?;
+            let bound = chip.check_bound(
+                layouter.namespace(|| "perturbation"),
+                &original,
+                &perturbed,
+                Value::known(self.bound),
+            )?;
+
+            layouter.constrain_instance(bound.cell(), config.instance, 0)?;
+            Ok(())
+        }
+    }
+
+    fn run(original: &[u8], perturbed: &[u8], bound: u8) -> Result<(), Vec<String>> {
+        let circuit = MyCircuit::<Fp> {
+            original: original.to_vec(),
+            perturbed: perturbed.to_vec(),
+            bound,
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(12, &circuit, vec![vec![Fp::from(bound as u64)]]).unwrap();
+        prover
+            .verify()
+            .map_err(|failures| failures.iter().map(|f| f.to_string()).collect())
+    }
+
+    #[test]
+    fn test_perturbation() {
+        let original = [0, 10, 128, 255, 200];
+        assert_eq!(run(&original, &original, 0), Ok(()));
+        assert_eq!(run(&original, &[3, 7, 128, 252, 203], 3), Ok(()));
+        assert_eq!(run(&original, &[255, 0, 0, 0, 255], 255), Ok(()));
+
+        assert!(run(&original, &[4, 10, 128, 255, 200], 3).is_err());
+        assert!(run(&original, &[0, 10, 128, 251, 200], 3).is_err());
+        assert!(run(&original, &[1, 10, 128, 255, 200], 0).is_err());
+    }
+}
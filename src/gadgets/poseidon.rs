@@ -0,0 +1,504 @@
+//! A [Poseidon](https://eprint.iacr.org/2019/458)-style sponge hash over field elements, e.g.
+//! to commit to the witnessed image inside the circuit (see
+//! [`crate::gadgets::image_commitment`]).
+//!
+//! The permutation has a width of 3 (a rate of 2 and a capacity of 1), uses `x^5` as the S-box
+//! and has 8 full and 57 partial rounds, the parameters recommended for 254-bit fields like the
+//! scalar field of BN254. The round constants are nothing-up-my-sleeve numbers (the keccak256
+//! hashes of `zero_g.poseidon.rc.<round>.<index>`, see [`hash_to_field`]) and the MDS matrix is
+//! the Cauchy matrix `M[i][j] = 1 / (i + j + 3)`. [`PoseidonSpec`] computes the hash outside of
+//! the circuit, [`PoseidonChip`] inside of it.
+//!
+//! Inputs are absorbed two at a time (the last one is padded with 0) into a state whose
+//! capacity element is initialized with a domain separator. The output is the first element of
+//! the final state. The number of inputs is not padded into the state, so callers have to
+//! choose a domain separator per input length (e.g. by including the image shape).
+
+use std::array;
+use std::sync::Arc;
+
+use ff::{PrimeField, PrimeFieldBits};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Fixed, Selector},
+    poly::Rotation,
+};
+
+use crate::utils::keccak256;
+
+/// The number of field elements of the state.
+pub const WIDTH: usize = 3;
+/// The number of inputs absorbed per permutation.
+pub const RATE: usize = 2;
+const FULL_ROUNDS: usize = 8;
+const PARTIAL_ROUNDS: usize = 57;
+const ROUNDS: usize = FULL_ROUNDS + PARTIAL_ROUNDS;
+
+/// Interprets the bytes as a big-endian integer, reduced modulo the field order.
+///
+/// [`crate::gadgets::image_commitment::ImageCommitmentChip`] packs bytes into field elements
+/// the same way.
+pub fn bytes_to_field<F: PrimeField>(bytes: &[u8]) -> F {
+    bytes.iter().fold(F::ZERO, |acc, byte| {
+        acc * F::from(256) + F::from(*byte as u64)
+    })
+}
+
+/// Maps a tag to a field element, via its keccak256 hash, e.g. to derive constants.
+pub fn hash_to_field<F: PrimeField>(tag: &[u8]) -> F {
+    bytes_to_field(&keccak256(tag))
+}
+
+/// The constants of the permutation, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct PoseidonSpec<F: PrimeField> {
+    round_constants: Vec<[F; WIDTH]>,
+    mds: [[F; WIDTH]; WIDTH],
+}
+
+impl<F: PrimeField> PoseidonSpec<F> {
+    pub fn new() -> Self {
+        let round_constants = (0..ROUNDS)
+            .map(|round| {
+                array::from_fn(|i| {
+                    hash_to_field(format!("zero_g.poseidon.rc.{round}.{i}").as_bytes())
+                })
+            })
+            .collect();
+        let mds = array::from_fn(|i| {
+            array::from_fn(|j| {
+                F::from((i + j + WIDTH) as u64)
+                    .invert()
+                    .expect("The entries are small non-zero numbers")
+            })
+        });
+        Self {
+            round_constants,
+            mds,
+        }
+    }
+
+    /// Whether the S-box is applied to the whole state in the given round (or only to its
+    /// first element).
+    fn is_full_round(round: usize) -> bool {
+        round < FULL_ROUNDS / 2 || round >= FULL_ROUNDS / 2 + PARTIAL_ROUNDS
+    }
+
+    /// Applies one round to the state: adds the round constants, applies the S-box and
+    /// multiplies with the MDS matrix.
+    fn round(&self, round: usize, state: &[F; WIDTH]) -> [F; WIDTH] {
+        let x: [F; WIDTH] = array::from_fn(|i| state[i] + self.round_constants[round][i]);
+        let sboxed: [F; WIDTH] = array::from_fn(|i| {
+            if i == 0 || Self::is_full_round(round) {
+                x[i].square().square() * x[i]
+            } else {
+                x[i]
+            }
+        });
+        array::from_fn(|i| {
+            (0..WIDTH)
+                .map(|j| self.mds[i][j] * sboxed[j])
+                .fold(F::ZERO, |acc, x| acc + x)
+        })
+    }
+
+    pub fn permute(&self, state: &mut [F; WIDTH]) {
+        for round in 0..ROUNDS {
+            *state = self.round(round, state);
+        }
+    }
+
+    /// Hashes the inputs with the given domain separator, see the module documentation.
+    pub fn hash(&self, domain: F, inputs: &[F]) -> F {
+        assert!(!inputs.is_empty(), "At least one input is required");
+        let mut state = [F::ZERO, F::ZERO, domain];
+        for chunk in inputs.chunks(RATE) {
+            for (x, input) in state.iter_mut().zip(chunk) {
+                *x += input;
+            }
+            self.permute(&mut state);
+        }
+        state[0]
+    }
+}
+
+impl<F: PrimeField> Default for PoseidonSpec<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub trait PoseidonInstructions<F: PrimeFieldBits> {
+    /// Hashes the input cells with the given domain separator (a constant of the circuit) and
+    /// returns the cell of the output.
+    fn hash(
+        &self,
+        layouter: impl Layouter<F>,
+        domain: F,
+        inputs: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error>;
+}
+
+#[derive(Debug, Clone)]
+pub struct PoseidonChipConfig<F: PrimeFieldBits> {
+    state: [Column<Advice>; WIDTH],
+    aux: [Column<Advice>; WIDTH],
+    round_constants: [Column<Fixed>; WIDTH],
+    full_round: Selector,
+    partial_round: Selector,
+    absorb: Selector,
+    spec: Arc<PoseidonSpec<F>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PoseidonChip<F: PrimeFieldBits> {
+    config: PoseidonChipConfig<F>,
+}
+
+/// Implements the sponge with one row per absorption and one row per round.
+///
+/// The layout of a round is as follows (`x_i = s_i + rc_i`):
+/// | state | aux      | round_constants |
+/// |-------|----------|-----------------|
+/// | s_i   | x_i^2    | rc_i            |
+/// | s'_i  |          |                 |
+///
+/// The following constraints are enforced:
+/// - `aux_i = x_i^2` (in partial rounds only for `i = 0`)
+/// - `s'_i = sum_j M[i][j] * aux_j^2 * x_j` (in partial rounds, `x_j` instead of
+///   `aux_j^2 * x_j` for `j > 0`)
+///
+/// To absorb two inputs, they are copied to the first two `aux` cells of the row, and
+/// `s'_i = s_i + aux_i` (and `s'_2 = s_2`) is enforced. The first row contains the initial state
+/// (constants), and the output is copied from the first state cell of the last row.
+impl<F: PrimeFieldBits> PoseidonChip<F> {
+    pub fn construct(config: PoseidonChipConfig<F>) -> Self {
+        Self { config }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        state: [Column<Advice>; WIDTH],
+        aux: [Column<Advice>; WIDTH],
+    ) -> PoseidonChipConfig<F> {
+        let round_constants = [(); WIDTH].map(|_| meta.fixed_column());
+        let full_round = meta.selector();
+        let partial_round = meta.selector();
+        let absorb = meta.selector();
+        let spec = PoseidonSpec::<F>::new();
+
+        for (name, selector, full) in [
+            ("poseidon full round", full_round, true),
+            ("poseidon partial round", partial_round, false),
+        ] {
+            let mds = spec.mds;
+            meta.create_gate(name, |meta| {
+                let selector = meta.query_selector(selector);
+                let x: [Expression<F>; WIDTH] = array::from_fn(|i| {
+                    meta.query_advice(state[i], Rotation::cur())
+                        + meta.query_fixed(round_constants[i], Rotation::cur())
+                });
+                let aux: [Expression<F>; WIDTH] =
+                    array::from_fn(|i| meta.query_advice(aux[i], Rotation::cur()));
+                let next: [Expression<F>; WIDTH] =
+                    array::from_fn(|i| meta.query_advice(state[i], Rotation::next()));
+
+                let sboxed: [Expression<F>; WIDTH] = array::from_fn(|i| {
+                    if i == 0 || full {
+                        aux[i].clone() * aux[i].clone() * x[i].clone()
+                    } else {
+                        x[i].clone()
+                    }
+                });
+                let squares = (0..WIDTH)
+                    .filter(|i| *i == 0 || full)
+                    .map(|i| aux[i].clone() - x[i].clone() * x[i].clone());
+                let products = (0..WIDTH).map(|i| {
+                    let product = (0..WIDTH)
+                        .map(|j| sboxed[j].clone() * mds[i][j])
+                        .reduce(|acc, x| acc + x)
+                        .unwrap();
+                    next[i].clone() - product
+                });
+                Constraints::with_selector(selector, squares.chain(products).collect::<Vec<_>>())
+            });
+        }
+
+        meta.create_gate("poseidon absorb", |meta| {
+            let selector = meta.query_selector(absorb);
+            let constraints = (0..WIDTH)
+                .map(|i| {
+                    let current = meta.query_advice(state[i], Rotation::cur());
+                    let next = meta.query_advice(state[i], Rotation::next());
+                    if i < RATE {
+                        next - current - meta.query_advice(aux[i], Rotation::cur())
+                    } else {
+                        next - current
+                    }
+                })
+                .collect::<Vec<_>>();
+            Constraints::with_selector(selector, constraints)
+        });
+
+        PoseidonChipConfig {
+            state,
+            aux,
+            round_constants,
+            full_round,
+            partial_round,
+            absorb,
+            spec: Arc::new(spec),
+        }
+    }
+
+    /// The constants of the permutation, e.g. to compute hashes outside of the circuit.
+    pub fn spec(&self) -> &PoseidonSpec<F> {
+        &self.config.spec
+    }
+}
+
+impl<F: PrimeFieldBits> PoseidonInstructions<F> for PoseidonChip<F> {
+    fn hash(
+        &self,
+        mut layouter: impl Layouter<F>,
+        domain: F,
+        inputs: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        assert!(!inputs.is_empty(), "At least one input is required");
+        let config = &self.config;
+        let spec = &config.spec;
+
+        layouter.assign_region(
+            || "poseidon",
+            |mut region| {
+                let mut state = [F::ZERO, F::ZERO, domain]
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, x)| {
+                        region.assign_advice_from_constant(
+                            || "initial state",
+                            config.state[i],
+                            0,
+                            x,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut row = 0;
+
+                for chunk in inputs.chunks(RATE) {
+                    config.absorb.enable(&mut region, row)?;
+                    let mut absorbed = vec![];
+                    for i in 0..RATE {
+                        let cell = match chunk.get(i) {
+                            Some(input) => {
+                                input.copy_advice(|| "input", &mut region, config.aux[i], row)?
+                            }
+                            None => region.assign_advice_from_constant(
+                                || "padding",
+                                config.aux[i],
+                                row,
+                                F::ZERO,
+                            )?,
+                        };
+                        absorbed.push(cell.value().copied());
+                    }
+                    absorbed.push(Value::known(F::ZERO));
+                    state = state
+                        .iter()
+                        .zip(&absorbed)
+                        .enumerate()
+                        .map(|(i, (x, input))| {
+                            region.assign_advice(
+                                || "state",
+                                config.state[i],
+                                row + 1,
+                                || x.value().copied() + *input,
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    row += 1;
+
+                    for round in 0..ROUNDS {
+                        let full = PoseidonSpec::<F>::is_full_round(round);
+                        if full {
+                            config.full_round.enable(&mut region, row)?;
+                        } else {
+                            config.partial_round.enable(&mut region, row)?;
+                        }
+                        for i in 0..WIDTH {
+                            let rc = spec.round_constants[round][i];
+                            region.assign_fixed(
+                                || "round constant",
+                                config.round_constants[i],
+                                row,
+                                || Value::known(rc),
+                            )?;
+                            let square = state[i].value().map(|x| (*x + rc).square());
+                            let square = if i == 0 || full {
+                                square
+                            } else {
+                                // Unconstrained in partial rounds
+                                Value::known(F::ZERO)
+                            };
+                            region.assign_advice(|| "square", config.aux[i], row, || square)?;
+                        }
+
+                        let current: Value<Vec<F>> =
+                            Value::from_iter(state.iter().map(|x| x.value().copied()));
+                        let next = current.map(|current| {
+                            spec.round(
+                                round,
+                                &current.try_into().expect("The state has WIDTH elements"),
+                            )
+                        });
+                        state = (0..WIDTH)
+                            .map(|i| {
+                                region.assign_advice(
+                                    || "state",
+                                    config.state[i],
+                                    row + 1,
+                                    || next.map(|next| next[i]),
+                                )
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        row += 1;
+                    }
+                }
+                Ok(state[0].clone())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use ff::PrimeFieldBits;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::bn256::Fr as Fp,
+        plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+    };
+
+    use super::{
+        bytes_to_field, PoseidonChip, PoseidonChipConfig, PoseidonInstructions, PoseidonSpec,
+    };
+
+    /// Proves knowledge of the private inputs whose hash is the public output.
+    #[derive(Default)]
+    struct MyCircuit<F: PrimeFieldBits> {
+        inputs: Vec<u64>,
+        _marker: PhantomData<F>,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Config<F: PrimeFieldBits> {
+        poseidon_config: PoseidonChipConfig<F>,
+        instance: Column<Instance>,
+    }
+
+    const DOMAIN: u64 = 42;
+
+    impl<F: PrimeFieldBits> Circuit<F> for MyCircuit<F> {
+        type Config = Config<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                inputs: self.inputs.clone(),
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let advice_columns = [(); 6].map(|_| meta.advice_column());
+            let constants = meta.fixed_column();
+            let instance = meta.instance_column();
+
+            for advice in advice_columns {
+                meta.enable_equality(advice);
+            }
+            meta.enable_equality(instance);
+            meta.enable_constant(constants);
+
+            let poseidon_config = PoseidonChip::configure(
+                meta,
+                [advice_columns[0], advice_columns[1], advice_columns[2]],
+                [advice_columns[3], advice_columns[4], advice_columns[5]],
+            );
+            Config {
+                poseidon_config,
+                instance,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let chip = PoseidonChip::construct(config.poseidon_config);
+            let inputs = layouter.assign_region(
+                || "inputs",
+                |mut region| {
+                    self.inputs
+                        .iter()
+                        .enumerate()
+                        .map(|(row, x)| {
+                            region.assign_advice(
+                                || "input",
+                                chip.config.state[0],
+                                row,
+                                || Value::known(F::from(*x)),
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                },
+            )?;
+            let output = chip.hash(layouter.namespace(|| "hash"), F::from(DOMAIN), &inputs)?;
+            layouter.constrain_instance(output.cell(), config.instance, 0)
+        }
+    }
+
+    fn native_hash(inputs: &[u64]) -> Fp {
+        let inputs: Vec<_> = inputs.iter().map(|x| Fp::from(*x)).collect();
+        PoseidonSpec::new().hash(Fp::from(DOMAIN), &inputs)
+    }
+
+    #[test]
+    fn test_native_hash() {
+        let hash = native_hash(&[1, 2]);
+        assert_eq!(hash, native_hash(&[1, 2]));
+        assert_ne!(hash, native_hash(&[2, 1]));
+        assert_ne!(hash, native_hash(&[1, 2, 3]));
+        let spec = PoseidonSpec::new();
+        assert_ne!(
+            hash,
+            spec.hash(Fp::from(DOMAIN + 1), &[Fp::from(1), Fp::from(2)])
+        );
+
+        // Odd inputs are padded with 0
+        assert_eq!(native_hash(&[1, 2, 3]), native_hash(&[1, 2, 3, 0]));
+
+        assert_eq!(bytes_to_field::<Fp>(&[1, 2]), Fp::from(258));
+    }
+
+    #[test]
+    fn test_hash() {
+        for inputs in [vec![7], vec![1, 2], vec![1, 2, 3, 4, 5]] {
+            let circuit = MyCircuit::<Fp> {
+                inputs: inputs.clone(),
+                _marker: PhantomData,
+            };
+            let expected = native_hash(&inputs);
+            let prover = MockProver::run(9, &circuit, vec![vec![expected]]).unwrap();
+            prover.assert_satisfied();
+
+            let prover = MockProver::run(9, &circuit, vec![vec![expected + Fp::from(1)]]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+}
@@ -1,35 +1,66 @@
+use std::marker::PhantomData;
+
 use ff::PrimeFieldBits;
-use halo2_gadgets::utilities::lookup_range_check::LookupRangeCheckConfig;
 use halo2_proofs::{
-    circuit::{AssignedCell, Layouter, Value},
-    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Selector, TableColumn},
+    circuit::{AssignedCell, Layouter},
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Selector},
     poly::Rotation,
 };
 
-/// The number of bits per word.
-const K: usize = 8;
+use super::byte_table::ByteTable;
 
-/// A wrapper around [`LookupRangeCheckConfig`] which uses `K = 8`, i.e., 8 bits per word.
+/// A lookup-based range check with `K` bits per word (the window of the [`ByteTable`], 8 by
+/// default), using the [`ByteTable`].
 /// It can check for an arbitrary number of bits and implements a less-or-equal check.
 ///
+/// A larger window needs fewer rows to decompose a value, but a larger table.
+///
 /// It uses a single advice column.
 #[derive(Clone, Debug)]
 pub struct RangeCheckConfig<F: PrimeFieldBits> {
-    range_check_config: LookupRangeCheckConfig<F, K>,
     advice_column: Column<Advice>,
+    running_sum_selector: Selector,
+    short_selector: Selector,
+    shift_selector: Selector,
     le_selector: Selector,
+    /// The number of bits per word, `K`.
+    window_num_bits: usize,
+    _marker: PhantomData<F>,
 }
 
 impl<F: PrimeFieldBits> RangeCheckConfig<F> {
-    /// Configure the range check with an advice column and a table column filled with all possible
-    /// byte values.
+    /// Configure the range check with an advice column, registering its lookups with the byte
+    /// table.
     pub fn configure(
         meta: &mut ConstraintSystem<F>,
         advice_column: Column<Advice>,
-        byte_column: TableColumn,
+        byte_table: &mut ByteTable<F>,
     ) -> Self {
-        let range_check_config =
-            LookupRangeCheckConfig::configure(meta, advice_column, byte_column);
+        let running_sum_selector = meta.complex_selector();
+        let short_selector = meta.complex_selector();
+        let shift_selector = meta.selector();
+        let window_num_bits = byte_table.window_num_bits();
+
+        // Running sum: z_i = 2^K⋅z_{i + 1} + a_i, where a_i is a word
+        byte_table.window_check(running_sum_selector, advice_column, move |meta| {
+            let z_cur = meta.query_advice(advice_column, Rotation::cur());
+            let z_next = meta.query_advice(advice_column, Rotation::next());
+            z_cur - z_next * F::from(1 << window_num_bits)
+        });
+
+        // Short range check, see `short_range_check()`
+        byte_table.window_check(short_selector, advice_column, move |meta| {
+            meta.query_advice(advice_column, Rotation::cur())
+        });
+        meta.create_gate("short range check shift", |meta| {
+            let shift_selector = meta.query_selector(shift_selector);
+
+            let word = meta.query_advice(advice_column, Rotation::prev());
+            let shifted = meta.query_advice(advice_column, Rotation::cur());
+            let multiplier = meta.query_advice(advice_column, Rotation::next());
+
+            Constraints::with_selector(shift_selector, vec![word * multiplier - shifted])
+        });
 
         let le_selector = meta.selector();
         meta.create_gate("le", |meta| {
@@ -51,9 +82,13 @@ impl<F: PrimeFieldBits> RangeCheckConfig<F> {
         });
 
         Self {
-            range_check_config,
             advice_column,
+            running_sum_selector,
+            short_selector,
+            shift_selector,
             le_selector,
+            window_num_bits,
+            _marker: PhantomData,
         }
     }
 
@@ -91,61 +126,134 @@ impl<F: PrimeFieldBits> RangeCheckConfig<F> {
     }
 
     /// Check that the given input is in the range [0, 2^n_bits).
-    /// It first decomposes the input into bytes and then performs a "short" range check on the last byte (if 8 does not divide `n_bits`).
+    /// It first decomposes the input into words of `K` bits and then performs a "short" range check on the last word (if `K` does not divide `n_bits`).
     pub fn range_check(
         &self,
         mut layouter: impl Layouter<F>,
         input: AssignedCell<F, F>,
         n_bits: usize,
     ) -> Result<(), Error> {
-        let words = n_bits / K;
+        let k = self.window_num_bits;
+        let words = n_bits / k;
         let last_word = {
             if words > 0 {
-                let running_sum = self.range_check_config.copy_check(
+                self.running_sum(
                     layouter.namespace(|| "range check (words)"),
                     input,
                     words,
                     // If n_bits is divisible by K, the last word is enforced to be zero and we can skip the short range check!
-                    n_bits % K == 0,
-                )?;
-                running_sum[running_sum.len() - 1].clone()
+                    n_bits % k == 0,
+                )?
             } else {
                 input
             }
         };
-        if n_bits % K != 0 {
+        if n_bits % k != 0 {
             // If n_bits is not divisible by K, the last word should be of (n_bits % K) bits
-            self.range_check_config.copy_short_check(
+            self.short_range_check(
                 layouter.namespace(|| "range check (short)"),
                 last_word,
-                n_bits % K,
+                n_bits % k,
             )?;
         }
         Ok(())
     }
-}
 
-#[allow(dead_code)]
-/// Fill a table column with bytes.
-/// This is only needed if there isn't already a table with all byte values.
-pub fn load_bytes_column<F: PrimeFieldBits>(
-    layouter: &mut impl Layouter<F>,
-    table_column: TableColumn,
-) -> Result<(), Error> {
-    layouter.assign_table(
-        || "table_idx",
-        |mut table| {
-            for index in 0..(1 << K) {
-                table.assign_cell(
-                    || "table_idx",
-                    table_column,
-                    index,
-                    || Value::known(F::from(index as u64)),
+    /// Decomposes the input into `num_words` words of `K` bits, using a running sum.
+    /// Returns the remaining value after shifting out all words, which is constrained to be
+    /// zero if `strict` is set.
+    ///
+    /// The layout is as follows:
+    ///
+    /// | advice                  | running_sum_selector |
+    /// |-------------------------|----------------------|
+    /// | z_0 = input (copy)      | X                    |
+    /// | z_1 = z_0 >> K          | X                    |
+    /// | ...                     | ...                  |
+    /// | z_{num_words}           |                      |
+    fn running_sum(
+        &self,
+        mut layouter: impl Layouter<F>,
+        input: AssignedCell<F, F>,
+        num_words: usize,
+        strict: bool,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "running sum",
+            |mut region| {
+                let shift_factor = F::from(1 << self.window_num_bits).invert().unwrap();
+                let mut z = input.copy_advice(|| "z_0", &mut region, self.advice_column, 0)?;
+                for i in 0..num_words {
+                    self.running_sum_selector.enable(&mut region, i)?;
+                    let name = || format!("z_{}", i + 1);
+                    z = if strict && i == num_words - 1 {
+                        region.assign_advice_from_constant(
+                            name,
+                            self.advice_column,
+                            i + 1,
+                            F::ZERO,
+                        )?
+                    } else {
+                        let next = z.value().map(|z| {
+                            let word = z
+                                .to_le_bits()
+                                .iter()
+                                .take(self.window_num_bits)
+                                .rev()
+                                .fold(0u64, |acc, bit| (acc << 1) + *bit as u64);
+                            (*z - F::from(word)) * shift_factor
+                        });
+                        region.assign_advice(name, self.advice_column, i + 1, || next)?
+                    };
+                }
+                Ok(z)
+            },
+        )
+    }
+
+    /// Checks that the input is in the range [0, 2^n_bits), for `n_bits < K`, by looking up
+    /// the input and the input shifted by `K - n_bits` bits.
+    ///
+    /// The layout is as follows:
+    ///
+    /// | advice                   | short_selector | shift_selector |
+    /// |--------------------------|----------------|----------------|
+    /// | input (copy)             | X              |                |
+    /// | input⋅2^(K - n_bits)     | X              | X              |
+    /// | 2^(K - n_bits) (constant)|                |                |
+    fn short_range_check(
+        &self,
+        mut layouter: impl Layouter<F>,
+        input: AssignedCell<F, F>,
+        n_bits: usize,
+    ) -> Result<(), Error> {
+        let k = self.window_num_bits;
+        assert!(n_bits < k);
+        layouter.assign_region(
+            || "short range check",
+            |mut region| {
+                let multiplier = F::from(1 << (k - n_bits));
+                input.copy_advice(|| "word", &mut region, self.advice_column, 0)?;
+                region.assign_advice(
+                    || "shifted",
+                    self.advice_column,
+                    1,
+                    || input.value().map(|word| *word * multiplier),
                 )?;
-            }
-            Ok(())
-        },
-    )
+                region.assign_advice_from_constant(
+                    || "multiplier",
+                    self.advice_column,
+                    2,
+                    multiplier,
+                )?;
+
+                self.short_selector.enable(&mut region, 0)?;
+                self.short_selector.enable(&mut region, 1)?;
+                self.shift_selector.enable(&mut region, 1)?;
+                Ok(())
+            },
+        )
+    }
 }
 
 #[cfg(test)]
@@ -157,14 +265,15 @@ mod tests {
         circuit::{Layouter, SimpleFloorPlanner, Value},
         dev::MockProver,
         halo2curves::bn256::Fr as Fp,
-        plonk::{Advice, Circuit, Column, ConstraintSystem, Error, TableColumn},
+        plonk::{Advice, Circuit, Column, ConstraintSystem, Error},
     };
 
-    use super::{load_bytes_column, RangeCheckConfig};
+    use super::RangeCheckConfig;
+    use crate::gadgets::byte_table::{ByteTable, ByteTableConfig};
 
-    /// Checks that `x <= y`, where `y` is a constant.
+    /// Checks that `x <= y`, where `y` is a constant, with a window of `WINDOW` bits.
     #[derive(Default)]
-    struct MyCircuit<F: PrimeFieldBits> {
+    struct MyCircuit<F: PrimeFieldBits, const WINDOW: usize = 8> {
         x: u64,
         y: u64,
         _marker: PhantomData<F>,
@@ -174,10 +283,10 @@ mod tests {
     struct Config<F: PrimeFieldBits> {
         range_check_config: RangeCheckConfig<F>,
         advice_column: Column<Advice>,
-        table_column: TableColumn,
+        byte_table_config: ByteTableConfig,
     }
 
-    impl<F: PrimeFieldBits> Circuit<F> for MyCircuit<F> {
+    impl<F: PrimeFieldBits, const WINDOW: usize> Circuit<F> for MyCircuit<F, WINDOW> {
         type Config = Config<F>;
         type FloorPlanner = SimpleFloorPlanner;
         type Params = ();
@@ -188,18 +297,19 @@ mod tests {
 
         fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
             let advice_column = meta.advice_column();
-            let table_column = meta.lookup_table_column();
             let constants = meta.fixed_column();
 
             meta.enable_equality(advice_column);
             meta.enable_constant(constants);
 
-            let range_check_config = RangeCheckConfig::configure(meta, advice_column, table_column);
+            let mut byte_table = ByteTable::new(meta).with_window_num_bits(WINDOW);
+            let range_check_config =
+                RangeCheckConfig::configure(meta, advice_column, &mut byte_table);
 
             Config {
                 range_check_config,
                 advice_column,
-                table_column,
+                byte_table_config: byte_table.configure(meta),
             }
         }
 
@@ -219,7 +329,7 @@ mod tests {
                     )
                 },
             )?;
-            load_bytes_column(&mut layouter, config.table_column)?;
+            config.byte_table_config.load(&mut layouter)?;
             config
                 .range_check_config
                 .le_constant(layouter, x_cell, F::from(self.y))?;
@@ -229,7 +339,7 @@ mod tests {
 
     #[test]
     fn test_le_equal_10bit() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             x: 1023,
             y: 1023,
@@ -241,7 +351,7 @@ mod tests {
 
     #[test]
     fn test_le_less_10bit() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             x: 1022,
             y: 1023,
@@ -253,7 +363,7 @@ mod tests {
 
     #[test]
     fn test_le_greater_10bit() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             x: 1024,
             y: 1023,
@@ -265,7 +375,7 @@ mod tests {
 
     #[test]
     fn test_le_less_4bit() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             x: 4,
             y: 9,
@@ -277,7 +387,7 @@ mod tests {
 
     #[test]
     fn test_le_less_32bit() {
-        let k = 9;
+        let k = 12;
         let circuit = MyCircuit::<Fp> {
             x: 0,
             y: 0xffabcdef,
@@ -288,6 +398,29 @@ mod tests {
     }
 
     #[test]
+    fn test_window_num_bits() {
+        fn verify<const WINDOW: usize>(x: u64, y: u64) -> bool {
+            let circuit = MyCircuit::<Fp, WINDOW> {
+                x,
+                y,
+                _marker: PhantomData,
+            };
+            MockProver::run(13, &circuit, vec![])
+                .unwrap()
+                .verify()
+                .is_ok()
+        }
+
+        assert!(verify::<3>(1023, 1023));
+        assert!(verify::<3>(4, 9));
+        assert!(!verify::<3>(1024, 1023));
+        assert!(verify::<11>(1023, 1023));
+        assert!(verify::<11>(0, 0xffabcdef));
+        assert!(!verify::<11>(1024, 1023));
+    }
+
+    #[test]
+    #[cfg(feature = "plotting")]
     fn plot() {
         use plotters::prelude::*;
 
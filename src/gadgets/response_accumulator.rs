@@ -78,6 +78,30 @@ impl<F: PrimeField> ResponseAccumulatorInstructions<F> for ResponseAccumulatorCh
         &self,
         layouter: &mut impl Layouter<F>,
         responses: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.assign_accumulators(layouter, responses, None)
+    }
+}
+
+impl<F: PrimeField> ResponseAccumulatorChip<F> {
+    /// Like [`ResponseAccumulatorInstructions::accumulate_responses`], but adds one to the
+    /// accumulator `acc_{tampered_row}` (and thus to all later ones), which the constraints have
+    /// to reject. See [`crate::gadgets::wnn::Tampering::Accumulator`].
+    #[cfg(any(test, feature = "test-support"))]
+    pub(crate) fn accumulate_responses_with_tampering(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        responses: &[AssignedCell<F, F>],
+        tampered_row: usize,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.assign_accumulators(layouter, responses, Some(tampered_row))
+    }
+
+    fn assign_accumulators(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        responses: &[AssignedCell<F, F>],
+        tampered_row: Option<usize>,
     ) -> Result<AssignedCell<F, F>, Error> {
         layouter.assign_region(
             || "accumulate_responses",
@@ -119,6 +143,9 @@ impl<F: PrimeField> ResponseAccumulatorInstructions<F> for ResponseAccumulatorCh
                         }
                     }
                     current_acc_value = current_acc_value + cur_row_sum;
+                    if tampered_row == Some(row_index + 1) {
+                        current_acc_value = current_acc_value + Value::known(F::ONE);
+                    }
                     acc_cell = region.assign_advice(
                         || format!("acc {}", row_index + 1),
                         self.config.advice_columns[4],
@@ -0,0 +1,277 @@
+//! A gadget that compares values with constant thresholds, e.g. the scores of a multi-label
+//! model with the activation thresholds of the classes (see [`crate::Wnn::with_label_thresholds`]).
+
+use ff::PrimeFieldBits;
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter},
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+use super::range_check::RangeCheckConfig;
+use crate::utils::to_u32;
+
+pub trait ThresholdInstructions<F: PrimeFieldBits> {
+    /// Returns a cell that is 1 if `x >= threshold` and 0 otherwise, where `threshold` is a
+    /// constant. `x` is copied from an existing cell and assumed to have at most `num_bits`
+    /// bits (this should be enforced wherever it is computed).
+    fn reaches_threshold(
+        &self,
+        layouter: impl Layouter<F>,
+        x: &AssignedCell<F, F>,
+        threshold: u64,
+    ) -> Result<AssignedCell<F, F>, Error>;
+}
+
+#[derive(Debug, Clone)]
+pub struct ThresholdChipConfig<F: PrimeFieldBits> {
+    x: Column<Advice>,
+    threshold: Column<Advice>,
+    diff: Column<Advice>,
+    reached: Column<Advice>,
+    selector: Selector,
+    num_bits: usize,
+
+    range_check_config: RangeCheckConfig<F>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThresholdChip<F: PrimeFieldBits> {
+    config: ThresholdChipConfig<F>,
+}
+
+/// Implements a comparison with a constant threshold, for values of up to `num_bits` bits.
+///
+/// The layout is as follows:
+/// | x        | threshold    | diff                                | reached |
+/// |----------|--------------|-------------------------------------|---------|
+/// | x (copy) | t (constant) | x - t + 2^num_bits * (1 - reached)  | x >= t  |
+///
+/// The following constraints are enforced:
+/// - reached is a bit
+/// - diff is in `[0, 2^num_bits)` (via RangeCheckConfig)
+/// - x + 2^num_bits * (1 - reached) = diff + t
+///
+/// For `x, t < 2^num_bits`, `diff` is only in range if `reached` is set iff `x >= t`.
+impl<F: PrimeFieldBits> ThresholdChip<F> {
+    pub fn construct(config: ThresholdChipConfig<F>) -> Self {
+        Self { config }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        x: Column<Advice>,
+        threshold: Column<Advice>,
+        diff: Column<Advice>,
+        reached: Column<Advice>,
+        range_check_config: RangeCheckConfig<F>,
+        num_bits: usize,
+    ) -> ThresholdChipConfig<F> {
+        assert!(num_bits <= 32, "Only values of up to 32 bits are supported");
+        let selector = meta.selector();
+
+        meta.create_gate("x + 2^num_bits * (1 - reached) = diff + t", |meta| {
+            let selector = meta.query_selector(selector);
+
+            let x = meta.query_advice(x, Rotation::cur());
+            let threshold = meta.query_advice(threshold, Rotation::cur());
+            let diff = meta.query_advice(diff, Rotation::cur());
+            let reached = meta.query_advice(reached, Rotation::cur());
+
+            let one = Expression::Constant(F::ONE);
+            let shift = Expression::Constant(F::from(1 << num_bits));
+
+            Constraints::with_selector(
+                selector,
+                vec![
+                    reached.clone() * (one.clone() - reached.clone()),
+                    x + shift * (one - reached) - diff - threshold,
+                ],
+            )
+        });
+
+        ThresholdChipConfig {
+            x,
+            threshold,
+            diff,
+            reached,
+            selector,
+            num_bits,
+            range_check_config,
+        }
+    }
+}
+
+impl<F: PrimeFieldBits> ThresholdInstructions<F> for ThresholdChip<F> {
+    fn reaches_threshold(
+        &self,
+        mut layouter: impl Layouter<F>,
+        x: &AssignedCell<F, F>,
+        threshold: u64,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let num_bits = self.config.num_bits;
+        assert!(
+            threshold < 1 << num_bits,
+            "The threshold has to be less than 2^{num_bits}"
+        );
+
+        let (diff_cell, reached_cell) = layouter.assign_region(
+            || "threshold",
+            |mut region| {
+                self.config.selector.enable(&mut region, 0)?;
+
+                x.copy_advice(|| "x", &mut region, self.config.x, 0)?;
+                region.assign_advice_from_constant(
+                    || "threshold",
+                    self.config.threshold,
+                    0,
+                    F::from(threshold),
+                )?;
+
+                let reached = x
+                    .value()
+                    .map(|x| to_u32(x) as u64 >= threshold)
+                    .map(|reached| F::from(reached as u64));
+                let diff = x.value().zip(reached).map(|(x, reached)| {
+                    *x + F::from(1 << num_bits) * (F::ONE - reached) - F::from(threshold)
+                });
+                let diff_cell = region.assign_advice(|| "diff", self.config.diff, 0, || diff)?;
+                let reached_cell =
+                    region.assign_advice(|| "reached", self.config.reached, 0, || reached)?;
+                Ok((diff_cell, reached_cell))
+            },
+        )?;
+        self.config.range_check_config.range_check(
+            layouter.namespace(|| "range_check_diff"),
+            diff_cell,
+            num_bits,
+        )?;
+        Ok(reached_cell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use ff::PrimeFieldBits;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::bn256::Fr as Fp,
+        plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+    };
+
+    use crate::gadgets::byte_table::{ByteTable, ByteTableConfig};
+    use crate::gadgets::range_check::RangeCheckConfig;
+
+    use super::{ThresholdChip, ThresholdChipConfig, ThresholdInstructions};
+
+    /// Checks whether `x >= threshold`, where `threshold` is a constant.
+    #[derive(Default)]
+    struct MyCircuit<F: PrimeFieldBits> {
+        x: u64,
+        threshold: u64,
+        _marker: PhantomData<F>,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Config<F: PrimeFieldBits> {
+        threshold_config: ThresholdChipConfig<F>,
+        byte_table_config: ByteTableConfig,
+        instance: Column<Instance>,
+    }
+
+    impl<F: PrimeFieldBits> Circuit<F> for MyCircuit<F> {
+        type Config = Config<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let advice_columns = [(); 5].map(|_| meta.advice_column());
+            let constants = meta.fixed_column();
+            let instance = meta.instance_column();
+
+            for advice in advice_columns {
+                meta.enable_equality(advice);
+            }
+            meta.enable_equality(instance);
+            meta.enable_constant(constants);
+
+            let mut byte_table = ByteTable::new(meta);
+            let range_check_config =
+                RangeCheckConfig::configure(meta, advice_columns[4], &mut byte_table);
+            let threshold_config = ThresholdChip::configure(
+                meta,
+                advice_columns[0],
+                advice_columns[1],
+                advice_columns[2],
+                advice_columns[3],
+                range_check_config,
+                20,
+            );
+
+            Config {
+                threshold_config,
+                byte_table_config: byte_table.configure(meta),
+                instance,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.byte_table_config.load(&mut layouter)?;
+            let threshold_chip = ThresholdChip::construct(config.threshold_config);
+            let x = layouter.assign_region(
+                || "x",
+                |mut region| {
+                    let x = Value::known(F::from(self.x));
+                    region.assign_advice(|| "x", threshold_chip.config.x, 0, || x)
+                },
+            )?;
+            let reached = threshold_chip.reaches_threshold(
+                layouter.namespace(|| "threshold"),
+                &x,
+                self.threshold,
+            )?;
+
+            layouter.constrain_instance(reached.cell(), config.instance, 0)?;
+            Ok(())
+        }
+    }
+
+    fn run(x: u64, threshold: u64, reached: bool) -> Result<(), Vec<String>> {
+        let circuit = MyCircuit::<Fp> {
+            x,
+            threshold,
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(12, &circuit, vec![vec![Fp::from(reached as u64)]]).unwrap();
+        prover
+            .verify()
+            .map_err(|failures| failures.iter().map(|f| f.to_string()).collect())
+    }
+
+    #[test]
+    fn test_threshold() {
+        for (x, threshold) in [
+            (5, 3),
+            (3, 3),
+            (2, 3),
+            (0, 0),
+            (700_000, 1000),
+            (12, 700_000),
+        ] {
+            let reached = x >= threshold;
+            assert_eq!(run(x, threshold, reached), Ok(()), "{x} >= {threshold}");
+            assert!(run(x, threshold, !reached).is_err(), "{x} >= {threshold}");
+        }
+    }
+}
@@ -1,29 +1,54 @@
 //! Implementation of a gadget & circuit implementing a [BTHOWeN](https://arxiv.org/abs/2203.01479)-style weightless neural network (WNN).
 
+use std::fmt;
 use std::marker::PhantomData;
+#[cfg(feature = "plotting")]
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 use ff::PrimeFieldBits;
 use halo2_proofs::{
     circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
     plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
 };
-use ndarray::{Array1, Array2, Array3};
+use ndarray::{Array1, Array2, Array3, Axis};
 use serde::{Deserialize, Serialize};
+use tracing::{info_span, instrument};
 
 use crate::gadgets::{
     bits2num::{Bits2NumChip, Bits2NumChipConfig, Bits2NumInstruction},
     bloom_filter::{BloomFilterChip, BloomFilterChipConfig},
-    bloom_filter::{BloomFilterConfig, BloomFilterInstructions},
+    bloom_filter::{BloomFilterConfig, BloomFilterInstructions, BloomFilterWords},
+    byte_table::{ByteTable, ByteTableConfig, DEFAULT_WINDOW_NUM_BITS},
     hash::{HashChip, HashConfig, HashInstructions},
+    image_commitment::{
+        ImageCommitmentChip, ImageCommitmentChipConfig, ImageCommitmentInstructions,
+    },
+    mask::{MaskChip, MaskChipConfig, MaskInstructions},
+    perturbation::{PerturbationChip, PerturbationChipConfig, PerturbationInstructions},
     range_check::RangeCheckConfig,
-    response_accumulator::ResponseAccumulatorInstructions,
+    threshold::{ThresholdChip, ThresholdChipConfig, ThresholdInstructions},
 };
 use crate::gadgets::{
     hash::HashFunctionConfig,
-    response_accumulator::{ResponseAccumulatorChip, ResponseAccumulatorChipConfig},
+    response_accumulator::{
+        ResponseAccumulatorChip, ResponseAccumulatorChipConfig, ResponseAccumulatorInstructions,
+    },
 };
 
+use super::encode_features::{
+    EncodeFeaturesChip, EncodeFeaturesChipConfig, EncodeFeaturesInstructions,
+};
 use super::encode_image::{EncodeImageChip, EncodeImageChipConfig, EncodeImageInstructions};
+use crate::blinding::configure_min_blinding_factors;
+use crate::error::ZeroGError;
+use crate::gadgets::annotations::AnnotatedLayouter;
+use crate::image_commitment::SALT_SIZE;
+#[cfg(feature = "plotting")]
+use crate::layout_plot::{plot_layout, PlotOptions};
+use crate::packed_bloom_filters::PackedBloomFilters;
+use crate::utils::PermutationRuns;
+use crate::wnn::Wnn;
 
 /// Instructions for the [`WnnChip`].
 pub trait WnnInstructions<F: PrimeFieldBits> {
@@ -40,71 +65,158 @@ pub trait WnnInstructions<F: PrimeFieldBits> {
 pub struct WnnConfig {
     pub hash_function_config: HashFunctionConfig,
     pub bloom_filter_config: BloomFilterConfig,
+    /// If set, the bloom filters of all (this many) classes are looked up at once, see
+    /// [`BloomFilterChip::configure_with_class_lookup`].
+    pub class_lookup_classes: Option<usize>,
+    /// The number of bits per word of the range checks, see [`ByteTable::window_check`].
+    pub window_num_bits: usize,
+    /// Whether the scores are compared with activation thresholds, see
+    /// [`WnnChip::labels`].
+    pub multi_label: bool,
+    /// Whether the chip also encodes feature vectors, see [`WnnChip::predict_features`].
+    pub tabular: bool,
+    /// Whether pixels can be occluded before binarization, see [`WnnChip::occlude`].
+    pub occlusion: bool,
+    /// Whether the chip can bound the distance of two images, see
+    /// [`WnnChip::check_perturbation`].
+    pub robustness: bool,
+    /// Whether the chip can commit to images and chain the commitments, see
+    /// [`WnnChip::commit_image`] and [`WnnChip::chain_commitment`].
+    pub image_commitment: bool,
 }
 
+/// The maximum number of bits of a score that is compared with an activation threshold, see
+/// [`WnnChip::labels`].
+pub const SCORE_NUM_BITS: usize = 32;
+
 #[derive(Clone, Debug)]
 pub struct WnnChipConfig<F: PrimeFieldBits> {
+    byte_table_config: ByteTableConfig,
     encode_image_chip_config: EncodeImageChipConfig<F>,
     bits2num_chip_config: Bits2NumChipConfig,
     hash_chip_config: HashConfig<F>,
     bloom_filter_chip_config: BloomFilterChipConfig,
     response_accumulator_chip_config: ResponseAccumulatorChipConfig,
+    threshold_chip_config: Option<ThresholdChipConfig<F>>,
+    encode_features_chip_config: Option<EncodeFeaturesChipConfig<F>>,
+    mask_chip_config: Option<MaskChipConfig>,
+    perturbation_chip_config: Option<PerturbationChipConfig<F>>,
+    image_commitment_chip_config: Option<ImageCommitmentChipConfig<F>>,
 }
 
 /// Implements a BTHOWeN- style weightless neural network.
 ///
 /// This happens in the following steps:
-/// 1. The [`EncodeImageChip`] is used to binarize the input image.
-/// 2. The input bits are permuted.
+/// 1. The [`EncodeImageChip`] is used to binarize the input image (or the
+///    [`EncodeFeaturesChip`] to binarize the feature vector of a tabular model).
+///    For occlusion proofs, some pixels are replaced by zeros before, see [`WnnChip::occlude`].
+///    For robustness proofs, a second image is predicted within a bounded distance of the
+///    first one, see [`WnnChip::check_perturbation`].
+/// 2. The input bits are permuted. Runs of consecutive indices are copied at once, so this is
+///    free for models that don't shuffle their inputs.
 /// 3. The [`Bits2NumChip`] is used to convert the bits to numbers.
 /// 4. The [`HashChip`] hash the wach number.
 /// 5. The [`BloomFilterChip`] is used to look up the bloom filter responses
 ///    (for each input and each class).
 /// 6. The [`ResponseAccumulatorChip`] is used to accumulate the responses.
+/// 7. For multi-label models, the [`ThresholdChip`] is used to compare the scores with the
+///    activation thresholds, see [`WnnChip::labels`].
+///
+/// Independently of the prediction, the [`ImageCommitmentChip`] can commit to the pixels, see
+/// [`WnnChip::commit_image`].
 pub struct WnnChip<F: PrimeFieldBits> {
     encode_image_chip: EncodeImageChip<F>,
     bits2num_chip: Bits2NumChip<F>,
     hash_chip: HashChip<F>,
     bloom_filter_chip: BloomFilterChip<F>,
     response_accumulator_chip: ResponseAccumulatorChip<F>,
+    threshold_chip: Option<ThresholdChip<F>>,
+    encode_features_chip: Option<EncodeFeaturesChip<F>>,
+    mask_chip: Option<MaskChip>,
+    perturbation_chip: Option<PerturbationChip<F>>,
+    image_commitment_chip: Option<ImageCommitmentChip<F>>,
 
-    input_permutation: Array1<u64>,
+    input_permutation: Arc<PermutationRuns>,
 
     config: WnnChipConfig<F>,
 
     n_classes: usize,
     n_inputs: usize,
+
+    #[cfg(any(test, feature = "test-support"))]
+    tampering: Option<Tampering>,
 }
 
 impl<F: PrimeFieldBits> WnnChip<F> {
     pub fn construct(
         config: WnnChipConfig<F>,
-        bloom_filter_arrays: Array3<bool>,
+        bloom_filters: &PackedBloomFilters,
         binarization_thresholds: Array3<u16>,
         input_permutation: Array1<u64>,
     ) -> Self {
-        let shape = bloom_filter_arrays.shape();
-        let n_classes = shape[0];
-        let n_inputs = shape[1];
-        let n_filters = shape[2];
-
-        // Flatten array: from shape (C, N, B) to (C * N, B)
-        let bloom_filter_arrays_flat = bloom_filter_arrays
-            .into_shape((n_classes * n_inputs, n_filters))
-            .unwrap();
+        let bloom_filter_chip =
+            BloomFilterChip::construct(config.bloom_filter_chip_config.clone(), bloom_filters);
+        let input_permutation = PermutationRuns::new(input_permutation.iter().copied());
+        Self::with_chips(
+            config,
+            bloom_filter_chip,
+            binarization_thresholds.into(),
+            input_permutation.into(),
+        )
+    }
 
+    /// Like [`WnnChip::construct`], but with the image-independent data precomputed, see
+    /// [`WnnSynthesisCache`].
+    pub fn construct_with_cache(config: WnnChipConfig<F>, cache: &WnnSynthesisCache<F>) -> Self {
+        let bloom_filter_chip = BloomFilterChip::construct_with_words(
+            config.bloom_filter_chip_config.clone(),
+            &cache.bloom_filter_words,
+        );
+        Self::with_chips(
+            config,
+            bloom_filter_chip,
+            cache.binarization_thresholds.clone(),
+            cache.input_permutation.clone(),
+        )
+    }
+
+    fn with_chips(
+        config: WnnChipConfig<F>,
+        bloom_filter_chip: BloomFilterChip<F>,
+        binarization_thresholds: Arc<Array3<u16>>,
+        input_permutation: Arc<PermutationRuns>,
+    ) -> Self {
+        let (n_classes, n_inputs) = bloom_filter_chip.shape();
+
+        // Tabular models store their thresholds as those of an image with a single row
+        let encode_features_chip = config
+            .encode_features_chip_config
+            .clone()
+            .map(|chip_config| {
+                let feature_thresholds = binarization_thresholds.index_axis(Axis(0), 0);
+                EncodeFeaturesChip::construct(chip_config, feature_thresholds.to_owned())
+            });
+        let mask_chip = config.mask_chip_config.clone().map(MaskChip::construct);
+        let perturbation_chip = config
+            .perturbation_chip_config
+            .clone()
+            .map(PerturbationChip::construct);
+        let image_commitment_chip = config
+            .image_commitment_chip_config
+            .clone()
+            .map(ImageCommitmentChip::construct);
         let encode_image_chip = EncodeImageChip::construct(
             config.encode_image_chip_config.clone(),
             binarization_thresholds,
         );
         let bits2num_chip = Bits2NumChip::construct(config.bits2num_chip_config.clone());
         let hash_chip = HashChip::construct(config.hash_chip_config.clone());
-        let bloom_filter_chip = BloomFilterChip::construct(
-            config.bloom_filter_chip_config.clone(),
-            &bloom_filter_arrays_flat,
-        );
         let response_accumulator_chip =
             ResponseAccumulatorChip::construct(config.response_accumulator_chip_config.clone());
+        let threshold_chip = config
+            .threshold_chip_config
+            .clone()
+            .map(ThresholdChip::construct);
 
         WnnChip {
             encode_image_chip,
@@ -112,6 +224,11 @@ impl<F: PrimeFieldBits> WnnChip<F> {
             hash_chip,
             bloom_filter_chip,
             response_accumulator_chip,
+            threshold_chip,
+            encode_features_chip,
+            mask_chip,
+            perturbation_chip,
+            image_commitment_chip,
 
             input_permutation,
 
@@ -119,25 +236,53 @@ impl<F: PrimeFieldBits> WnnChip<F> {
 
             n_classes,
             n_inputs,
+
+            #[cfg(any(test, feature = "test-support"))]
+            tampering: None,
         }
     }
 
+    /// Test support: Corrupts the given witness value during synthesis, see [`Tampering`].
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn with_tampering(mut self, tampering: Tampering) -> Self {
+        match tampering {
+            Tampering::BloomResponse { class, filter } => {
+                self.bloom_filter_chip.tamper_response(class, filter)
+            }
+            Tampering::BinarizationBit { pixel, bit } => {
+                self.encode_image_chip.tamper_bit(pixel, bit)
+            }
+            Tampering::Accumulator { .. } => {}
+        }
+        self.tampering = Some(tampering);
+        self
+    }
+
     pub fn configure(
         meta: &mut ConstraintSystem<F>,
         advice_columns: [Column<Advice>; 6],
         wnn_config: WnnConfig,
     ) -> WnnChipConfig<F> {
-        let bloom_filter_chip_config = BloomFilterChip::configure(
-            meta,
-            advice_columns,
-            wnn_config.bloom_filter_config.clone(),
-        );
-        let lookup_range_check_config = RangeCheckConfig::configure(
-            meta,
-            advice_columns[5],
-            // Re-use byte column of the bloom filter
-            bloom_filter_chip_config.byte_column,
-        );
+        let mut byte_table = ByteTable::new(meta).with_window_num_bits(wnn_config.window_num_bits);
+        let bloom_filter_chip_config = match wnn_config.class_lookup_classes {
+            Some(n_classes) => BloomFilterChip::configure_with_class_lookup(
+                meta,
+                advice_columns,
+                &mut byte_table,
+                wnn_config.bloom_filter_config.clone(),
+                n_classes,
+            ),
+            None => BloomFilterChip::configure(
+                meta,
+                advice_columns,
+                &mut byte_table,
+                wnn_config.bloom_filter_config.clone(),
+            ),
+        };
+        // Like the bloom filter's byte lookups, the range check reads from the last advice
+        // column, see `ByteTable`
+        let lookup_range_check_config =
+            RangeCheckConfig::configure(meta, advice_columns[5], &mut byte_table);
         let encode_image_chip_config = EncodeImageChip::configure(
             meta,
             advice_columns[0],
@@ -146,6 +291,49 @@ impl<F: PrimeFieldBits> WnnChip<F> {
             advice_columns[3],
             lookup_range_check_config.clone(),
         );
+        let threshold_chip_config = wnn_config.multi_label.then(|| {
+            ThresholdChip::configure(
+                meta,
+                advice_columns[0],
+                advice_columns[1],
+                advice_columns[2],
+                advice_columns[3],
+                lookup_range_check_config.clone(),
+                SCORE_NUM_BITS,
+            )
+        });
+        let encode_features_chip_config = wnn_config.tabular.then(|| {
+            EncodeFeaturesChip::configure(
+                meta,
+                advice_columns[0],
+                advice_columns[1],
+                advice_columns[2],
+                advice_columns[3],
+                lookup_range_check_config.clone(),
+            )
+        });
+        let mask_chip_config = wnn_config.occlusion.then(|| {
+            MaskChip::configure(
+                meta,
+                advice_columns[0],
+                advice_columns[1],
+                advice_columns[2],
+            )
+        });
+        let perturbation_chip_config = wnn_config.robustness.then(|| {
+            PerturbationChip::configure(
+                meta,
+                advice_columns[0],
+                advice_columns[1],
+                advice_columns[2],
+                advice_columns[3],
+                advice_columns[4],
+                lookup_range_check_config.clone(),
+            )
+        });
+        let image_commitment_chip_config = wnn_config
+            .image_commitment
+            .then(|| ImageCommitmentChip::configure(meta, advice_columns));
         let hash_chip_config = HashChip::configure(
             meta,
             advice_columns[0],
@@ -162,45 +350,230 @@ impl<F: PrimeFieldBits> WnnChip<F> {
         let bits2num_chip_config =
             Bits2NumChip::configure(meta, advice_columns[3], advice_columns[4]);
 
+        let byte_table_config = byte_table.configure(meta);
+
         WnnChipConfig {
+            byte_table_config,
             encode_image_chip_config,
             hash_chip_config,
             bloom_filter_chip_config,
             response_accumulator_chip_config,
             bits2num_chip_config,
+            threshold_chip_config,
+            encode_features_chip_config,
+            mask_chip_config,
+            perturbation_chip_config,
+            image_commitment_chip_config,
         }
     }
 
     pub fn load(&mut self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        self.config.byte_table_config.load(layouter)?;
         self.bloom_filter_chip.load(layouter)
     }
+
+    /// Compares the scores with the activation thresholds of the classes (see
+    /// [`Wnn::with_label_thresholds`]), returning one cell per class that is 1 iff the score
+    /// reaches the threshold.
+    ///
+    /// Panics if the chip was not configured with [`WnnConfig::multi_label`].
+    pub fn labels(
+        &self,
+        mut layouter: impl Layouter<F>,
+        scores: &[AssignedCell<F, F>],
+        thresholds: &[u64],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let threshold_chip = self
+            .threshold_chip
+            .as_ref()
+            .expect("The chip is not configured for multi-label classification");
+        assert_eq!(scores.len(), thresholds.len());
+        scores
+            .iter()
+            .zip(thresholds)
+            .enumerate()
+            .map(|(c, (score, threshold))| {
+                threshold_chip.reaches_threshold(
+                    layouter.namespace(|| format!("ThresholdChip class {c}")),
+                    score,
+                    *threshold,
+                )
+            })
+            .collect()
+    }
 }
 
 impl<F: PrimeFieldBits> WnnInstructions<F> for WnnChip<F> {
     fn predict(
         &self,
-        mut layouter: impl Layouter<F>,
+        layouter: impl Layouter<F>,
         image: Value<Array2<u8>>,
     ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        Ok(self.predict_with_responses(layouter, image)?.0)
+    }
+}
+
+impl<F: PrimeFieldBits> WnnChip<F> {
+    /// Like [`WnnInstructions::predict`], but also returns the cells of the individual bloom
+    /// filter responses, indexed by class and filter.
+    #[allow(clippy::type_complexity)]
+    pub fn predict_with_responses(
+        &self,
+        mut layouter: impl Layouter<F>,
+        image: Value<Array2<u8>>,
+    ) -> Result<(Vec<AssignedCell<F, F>>, Vec<Vec<AssignedCell<F, F>>>), Error> {
+        let pixels = self.assign_image(layouter.namespace(|| "EncodeImageChip"), image)?;
+        self.predict_pixels(layouter, &pixels)
+    }
+
+    /// Assigns the pixel intensities once, in the `image` region of the [`EncodeImageChip`].
+    pub fn assign_image(
+        &self,
+        layouter: impl Layouter<F>,
+        image: Value<Array2<u8>>,
+    ) -> Result<Array2<AssignedCell<F, F>>, Error> {
+        self.encode_image_chip.assign_image(layouter, image)
+    }
+
+    /// Replaces the pixels whose mask bit is set by zeros (see [`MaskChip`]), e.g. to prove
+    /// that the prediction doesn't depend on them. Returns the occluded pixels (to pass to
+    /// [`WnnChip::predict_pixels`]) and the cells of the mask bits, in row-major order.
+    ///
+    /// Panics if the chip was not configured with [`WnnConfig::occlusion`].
+    #[allow(clippy::type_complexity)]
+    pub fn occlude(
+        &self,
+        layouter: impl Layouter<F>,
+        pixels: &Array2<AssignedCell<F, F>>,
+        mask: Value<Array2<bool>>,
+    ) -> Result<(Array2<AssignedCell<F, F>>, Vec<AssignedCell<F, F>>), Error> {
+        let mask_chip = self
+            .mask_chip
+            .as_ref()
+            .expect("The chip is not configured for occlusion");
+        let pixel_cells: Vec<_> = pixels.iter().cloned().collect();
+        let (occluded, mask_cells) = mask_chip.mask(
+            layouter,
+            &pixel_cells,
+            mask.map(|mask| mask.iter().copied().collect()),
+        )?;
+        Ok((
+            Array2::from_shape_vec(pixels.dim(), occluded).unwrap(),
+            mask_cells,
+        ))
+    }
+
+    /// Constrains the L∞ distance of two assigned images to be at most `bound` (see
+    /// [`PerturbationChip`]), e.g. to prove that both are predicted as the same class. Returns
+    /// the cell of the bound.
+    ///
+    /// Panics if the chip was not configured with [`WnnConfig::robustness`].
+    pub fn check_perturbation(
+        &self,
+        layouter: impl Layouter<F>,
+        original: &Array2<AssignedCell<F, F>>,
+        perturbed: &Array2<AssignedCell<F, F>>,
+        bound: Value<u8>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let perturbation_chip = self
+            .perturbation_chip
+            .as_ref()
+            .expect("The chip is not configured for robustness proofs");
+        assert_eq!(original.dim(), perturbed.dim());
+        let original: Vec<_> = original.iter().cloned().collect();
+        let perturbed: Vec<_> = perturbed.iter().cloned().collect();
+        perturbation_chip.check_bound(layouter, &original, &perturbed, bound)
+    }
+
+    /// Commits to the assigned pixels with the given salt (see [`ImageCommitmentChip`]), e.g.
+    /// to prove which image was predicted. Returns the cell of the commitment.
+    ///
+    /// Panics if the chip was not configured with [`WnnConfig::image_commitment`].
+    pub fn commit_image(
+        &self,
+        layouter: impl Layouter<F>,
+        pixels: &Array2<AssignedCell<F, F>>,
+        salt: Value<[u8; SALT_SIZE]>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.image_commitment_chip
+            .as_ref()
+            .expect("The chip is not configured for image commitments")
+            .commit(layouter, pixels, salt)
+    }
+
+    /// Computes the chaining value of an image commitment (see [`WnnChip::commit_image`]),
+    /// given the previous chaining value, see [`crate::proof_chain`]. Returns the cells of the
+    /// previous chaining value and the chaining value.
+    ///
+    /// Panics if the chip was not configured with [`WnnConfig::image_commitment`].
+    pub fn chain_commitment(
+        &self,
+        layouter: impl Layouter<F>,
+        previous: Value<F>,
+        commitment: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        self.image_commitment_chip
+            .as_ref()
+            .expect("The chip is not configured for image commitments")
+            .chain(layouter, previous, commitment)
+    }
+
+    /// Like [`WnnChip::predict_with_responses`], but for pixels that have already been
+    /// assigned by [`WnnChip::assign_image`].
+    #[allow(clippy::type_complexity)]
+    pub fn predict_pixels(
+        &self,
+        mut layouter: impl Layouter<F>,
+        pixels: &Array2<AssignedCell<F, F>>,
+    ) -> Result<(Vec<AssignedCell<F, F>>, Vec<Vec<AssignedCell<F, F>>>), Error> {
         let bit_cells = self
             .encode_image_chip
-            .encode_image(layouter.namespace(|| "encode image"), image)?;
+            .encode_pixels(layouter.namespace(|| "EncodeImageChip"), pixels)?;
+        self.predict_bits(layouter, &bit_cells)
+    }
 
-        // Permute input bits
-        let permuted_inputs = self
-            .input_permutation
-            .iter()
-            .map(|i| bit_cells[*i as usize].clone())
-            .collect::<Vec<_>>();
+    /// Like [`WnnChip::predict_with_responses`], but for the feature vector of a tabular model
+    /// (see [`Wnn::new_tabular`]).
+    ///
+    /// Panics if the chip was not configured with [`WnnConfig::tabular`].
+    #[allow(clippy::type_complexity)]
+    pub fn predict_features(
+        &self,
+        mut layouter: impl Layouter<F>,
+        features: Value<Array1<u16>>,
+    ) -> Result<(Vec<AssignedCell<F, F>>, Vec<Vec<AssignedCell<F, F>>>), Error> {
+        let encode_features_chip = self
+            .encode_features_chip
+            .as_ref()
+            .expect("The chip is not configured for tabular models");
+        let features = encode_features_chip
+            .assign_features(layouter.namespace(|| "EncodeFeaturesChip"), features)?;
+        let bit_cells = encode_features_chip
+            .encode_features(layouter.namespace(|| "EncodeFeaturesChip"), &features)?;
+        self.predict_bits(layouter, &bit_cells)
+    }
+
+    /// Predicts the scores from the binarized inputs, see [`WnnChip::predict_pixels`].
+    #[allow(clippy::type_complexity)]
+    fn predict_bits(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bit_cells: &[AssignedCell<F, F>],
+    ) -> Result<(Vec<AssignedCell<F, F>>, Vec<Vec<AssignedCell<F, F>>>), Error> {
+        // Permute input bits (a no-op for the identity permutation)
+        let permuted_inputs = self.input_permutation.apply(bit_cells);
 
         let num_bit_size = self.config.hash_chip_config.hash_function_config.n_bits;
 
         // Convert the input bits to a group of field element that can be hashed
         let joint_inputs = permuted_inputs
             .chunks_exact(num_bit_size)
-            .map(|chunk| {
-                self.bits2num_chip
-                    .convert_le(&mut layouter, Vec::from(chunk))
+            .enumerate()
+            .map(|(i, chunk)| {
+                self.bits2num_chip.convert_le(
+                    &mut layouter.namespace(|| format!("Bits2NumChip filter {i}")),
+                    Vec::from(chunk),
+                )
             })
             .collect::<Result<Vec<_>, _>>()?;
 
@@ -208,42 +581,76 @@ impl<F: PrimeFieldBits> WnnInstructions<F> for WnnChip<F> {
 
         let hashes = joint_inputs
             .into_iter()
-            .map(|hash_input| {
-                self.hash_chip
-                    .hash(layouter.namespace(|| "hash"), hash_input)
+            .enumerate()
+            .map(|(i, hash_input)| {
+                self.hash_chip.hash(
+                    layouter.namespace(|| format!("HashChip filter {i}")),
+                    hash_input,
+                )
             })
             .collect::<Result<Vec<_>, _>>()?;
 
-        let mut responses = vec![];
-        for c in 0..self.n_classes {
-            responses.push(Vec::new());
-            for (i, hash) in hashes.clone().into_iter().enumerate() {
-                let array_index = c * hashes.len() + i;
-                responses[c].push(self.bloom_filter_chip.bloom_lookup(
-                    &mut layouter,
+        let mut responses = vec![vec![]; self.n_classes];
+        if self.bloom_filter_chip.looks_up_all_classes() {
+            for (i, hash) in hashes.into_iter().enumerate() {
+                let filter_responses = self.bloom_filter_chip.bloom_lookup_all_classes(
+                    &mut layouter.namespace(|| format!("BloomFilterChip filter {i}")),
                     hash,
-                    F::from(array_index as u64),
-                )?);
+                    i,
+                )?;
+                for (c, response) in filter_responses.into_iter().enumerate() {
+                    responses[c].push(response);
+                }
+            }
+        } else {
+            for (c, class_responses) in responses.iter_mut().enumerate() {
+                for (i, hash) in hashes.iter().enumerate() {
+                    let array_index = c * hashes.len() + i;
+                    class_responses.push(self.bloom_filter_chip.bloom_lookup(
+                        &mut layouter.namespace(|| format!("BloomFilterChip class {c} filter {i}")),
+                        hash.clone(),
+                        F::from(array_index as u64),
+                    )?);
+                }
             }
         }
 
-        responses
+        let scores = responses
             .iter()
-            .map(|class_responses| {
+            .enumerate()
+            .map(|(c, class_responses)| {
+                let mut layouter =
+                    layouter.namespace(|| format!("ResponseAccumulatorChip class {c}"));
+                #[cfg(any(test, feature = "test-support"))]
+                if let Some(Tampering::Accumulator { class, row }) = self.tampering {
+                    if class == c {
+                        return self
+                            .response_accumulator_chip
+                            .accumulate_responses_with_tampering(
+                                &mut layouter,
+                                class_responses,
+                                row,
+                            );
+                    }
+                }
                 self.response_accumulator_chip
                     .accumulate_responses(&mut layouter, class_responses)
             })
-            .collect::<Result<Vec<_>, _>>()
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((scores, responses))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct WnnCircuitConfig<F: PrimeFieldBits> {
     wnn_chip_config: WnnChipConfig<F>,
-    instance_column: Column<Instance>,
+    instance_columns: Vec<Column<Instance>>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+/// The parameters of a [`WnnCircuit`], which are needed to configure it (e.g. when reading
+/// keys). They are derived from the model by [`WnnCircuitParams::from_model`], which all ways
+/// to build the circuit from a [`Wnn`] use; [`WnnCircuitBuilder::params`] overrides them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WnnCircuitParams {
     pub p: u64,
     pub l: usize,
@@ -251,50 +658,688 @@ pub struct WnnCircuitParams {
     pub bits_per_hash: usize,
     pub bits_per_filter: usize,
     pub n_classes: usize,
+    /// Blinding factors to use at least, see [`crate::blinding`]. With 0 (the default), the
+    /// number derived by halo2 is used.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub min_blinding_factors: usize,
+    /// The number of instance columns the public inputs are distributed over, see
+    /// [`InstanceLayout`].
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub num_instance_columns: usize,
+    /// Whether the bloom filters of all classes are looked up at once, see
+    /// [`crate::gadgets::bloom_filter::ClassLookupChip`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub class_lookup: bool,
+    /// The number of bits per word of the range checks, see
+    /// [`crate::gadgets::range_check::RangeCheckConfig`].
+    #[serde(
+        default = "default_window_num_bits",
+        skip_serializing_if = "is_default_window_num_bits"
+    )]
+    pub window_num_bits: usize,
+    /// Whether the circuit exposes the previous chaining value and the chaining value of the
+    /// image, which link proofs of a sequence of images, see [`crate::proof_chain`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub chaining: bool,
+    /// Whether the circuit exposes one bit per class (whether the score reaches the activation
+    /// threshold of the class) instead of the scores, see [`Wnn::with_label_thresholds`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub multi_label: bool,
+    /// Whether the circuit exposes the score of its single class as the output of a regression
+    /// model, see [`Wnn::with_regression`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub regression: bool,
+    /// Whether the input is a feature vector instead of an image, see [`Wnn::new_tabular`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub tabular: bool,
+    /// The number of pixels of the image if the circuit exposes which of them are occluded
+    /// (see [`Wnn::with_occlusion`]), 0 otherwise.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub occlusion_num_pixels: usize,
+    /// Whether the circuit also predicts a perturbed image within a public L∞ distance of the
+    /// image and exposes its scores, see [`Wnn::with_robustness`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub robustness: bool,
+    /// Whether the circuit commits to the image and exposes the commitment, see
+    /// [`Wnn::with_image_commitment`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub image_commitment: bool,
+}
+
+fn is_zero(x: &usize) -> bool {
+    *x == 0
+}
+
+fn is_false(x: &bool) -> bool {
+    !x
+}
+
+fn one() -> usize {
+    1
+}
+
+fn is_one(x: &usize) -> bool {
+    *x == 1
+}
+
+fn default_window_num_bits() -> usize {
+    DEFAULT_WINDOW_NUM_BITS
+}
+
+fn is_default_window_num_bits(x: &usize) -> bool {
+    *x == DEFAULT_WINDOW_NUM_BITS
+}
+
+impl WnnCircuitParams {
+    /// Derives the circuit parameters from the metadata of the given model.
+    pub fn from_model(wnn: &Wnn) -> Self {
+        let bits_per_hash = wnn.num_filter_entries.trailing_zeros() as usize;
+        Self {
+            p: wnn.p,
+            l: wnn.num_filter_hashes * bits_per_hash,
+            n_hashes: wnn.num_filter_hashes,
+            bits_per_hash,
+            bits_per_filter: wnn.num_filter_inputs,
+            n_classes: wnn.bloom_filters.shape()[0],
+            min_blinding_factors: wnn.min_blinding_factors,
+            num_instance_columns: wnn.num_instance_columns,
+            class_lookup: wnn.class_lookup,
+            window_num_bits: wnn.window_num_bits,
+            chaining: wnn.chaining,
+            multi_label: wnn.label_thresholds.is_some(),
+            regression: wnn.regression,
+            tabular: wnn.tabular,
+            occlusion_num_pixels: if wnn.occlusion {
+                wnn.img_shape().0 * wnn.img_shape().1
+            } else {
+                0
+            },
+            robustness: wnn.robustness,
+            image_commitment: wnn.image_commitment,
+        }
+    }
+}
+
+/// A value exposed as a public input by [`WnnCircuit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PublicValue {
+    /// The score (number of positive bloom filter responses) of a class.
+    Score { class: usize },
+    /// Whether the score of a class reaches its activation threshold (1) or not (0), see
+    /// [`Wnn::with_label_thresholds`].
+    Label { class: usize },
+    /// The output of a regression model, i.e. the score of its single class, see
+    /// [`Wnn::with_regression`].
+    Output,
+    /// Whether a pixel (given by its index in row-major order) is occluded (1) or not (0), see
+    /// [`crate::occlusion`].
+    Occluded { pixel: usize },
+    /// The score of a class for the perturbed image of a robustness proof, see
+    /// [`crate::robustness`].
+    PerturbedScore { class: usize },
+    /// The maximum difference between a pixel of the image and the perturbed image, see
+    /// [`crate::robustness`].
+    PerturbationBound,
+    /// The commitment to the image, see [`crate::image_commitment`].
+    ImageCommitment,
+    /// The chaining value of the previous image, see [`crate::proof_chain`].
+    PreviousChainingValue,
+    /// The chaining value of the image, computed from [`PublicValue::PreviousChainingValue`]
+    /// and the commitment to the image, see [`crate::proof_chain`].
+    ChainingValue,
+}
+
+impl fmt::Display for PublicValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Score { class } => write!(f, "Score of class {class}"),
+            Self::Label { class } => write!(f, "Label of class {class}"),
+            Self::Output => write!(f, "Output"),
+            Self::Occluded { pixel } => write!(f, "Occlusion of pixel {pixel}"),
+            Self::PerturbedScore { class } => write!(f, "Perturbed score of class {class}"),
+            Self::PerturbationBound => write!(f, "Perturbation bound"),
+            Self::ImageCommitment => write!(f, "Image commitment"),
+            Self::PreviousChainingValue => write!(f, "Previous chaining value"),
+            Self::ChainingValue => write!(f, "Chaining value"),
+        }
+    }
+}
+
+/// Describes the public inputs of [`WnnCircuit`].
+///
+/// The values are split into consecutive chunks of [`InstanceLayout::rows_per_column`] values,
+/// one per instance column. So the list of values is the concatenation of the instance
+/// columns, which is also the order the EVM verifier expects.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceLayout {
+    pub values: Vec<PublicValue>,
+    #[serde(default = "one")]
+    pub num_columns: usize,
+}
+
+impl InstanceLayout {
+    /// The layout used by [`WnnCircuit`] for the given parameters.
+    pub fn from_params(params: &WnnCircuitParams) -> Self {
+        Self {
+            values: (0..params.n_classes)
+                .map(|class| {
+                    if params.regression {
+                        PublicValue::Output
+                    } else if params.multi_label {
+                        PublicValue::Label { class }
+                    } else {
+                        PublicValue::Score { class }
+                    }
+                })
+                .chain(
+                    (0..params.occlusion_num_pixels).map(|pixel| PublicValue::Occluded { pixel }),
+                )
+                .chain(
+                    (0..params.n_classes)
+                        .map(|class| PublicValue::PerturbedScore { class })
+                        .chain([PublicValue::PerturbationBound])
+                        .filter(|_| params.robustness),
+                )
+                .chain(
+                    params
+                        .image_commitment
+                        .then_some(PublicValue::ImageCommitment),
+                )
+                .chain(
+                    [
+                        PublicValue::PreviousChainingValue,
+                        PublicValue::ChainingValue,
+                    ]
+                    .into_iter()
+                    .filter(|_| params.chaining),
+                )
+                .collect(),
+            num_columns: params.num_instance_columns,
+        }
+    }
+
+    /// The number of rows used in each instance column (the last one might use fewer).
+    pub fn rows_per_column(&self) -> usize {
+        rows_per_column(self.values.len(), self.num_columns)
+    }
+
+    /// The instance column and the row of the value with the given index.
+    pub fn position(&self, index: usize) -> (usize, usize) {
+        let rows = self.rows_per_column();
+        (index / rows, index % rows)
+    }
+
+    /// The number of values in each instance column.
+    pub fn column_lengths(&self) -> Vec<usize> {
+        self.to_columns(&vec![(); self.values.len()])
+            .iter()
+            .map(Vec::len)
+            .collect()
+    }
+
+    /// Distributes the values (in the order of [`InstanceLayout::values`]) over the instance
+    /// columns.
+    pub fn to_columns<T: Clone>(&self, values: &[T]) -> Vec<Vec<T>> {
+        instance_columns(values, self.num_columns)
+    }
+
+    /// The number of public inputs.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+fn rows_per_column(num_values: usize, num_columns: usize) -> usize {
+    ((num_values + num_columns - 1) / num_columns).max(1)
+}
+
+/// Distributes public inputs over `num_columns` instance columns, like [`InstanceLayout`] does.
+/// Trailing columns are empty if there are fewer values than columns.
+pub fn instance_columns<T: Clone>(values: &[T], num_columns: usize) -> Vec<Vec<T>> {
+    let rows = rows_per_column(values.len(), num_columns);
+    (0..num_columns)
+        .map(|column| {
+            let start = (column * rows).min(values.len());
+            let end = (start + rows).min(values.len());
+            values[start..end].to_vec()
+        })
+        .collect()
+}
+
+/// The bloom filter responses (indexed by class and filter) witnessed during synthesis, see
+/// [`WnnCircuit::capture_responses`].
+#[derive(Debug, Clone, Default)]
+pub struct ResponseCapture(Arc<Mutex<Option<Array2<bool>>>>);
+
+impl ResponseCapture {
+    /// The responses of the last synthesis, or `None` if the circuit was not synthesized with
+    /// witnesses.
+    pub fn responses(&self) -> Option<Array2<bool>> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// The image-independent data used to synthesize a [`WnnCircuit`]: the bloom filters packed
+/// into the words of the lookup table (see [`BloomFilterWords`]), the binarization thresholds and
+/// the runs of the input permutation.
+///
+/// By default, every synthesis (several during key generation and one per proof) derives them
+/// from the model again. A prover of many images computes them once (see
+/// [`WnnCircuit::compute_synthesis_cache`]) and shares them between the circuits of all
+/// proofs (see [`WnnCircuit::with_synthesis_cache`]). This doesn't affect the keys or the
+/// proofs.
+#[derive(Debug, Clone)]
+pub struct WnnSynthesisCache<F: PrimeFieldBits> {
+    params: WnnCircuitParams,
+    bloom_filter_words: BloomFilterWords<F>,
+    binarization_thresholds: Arc<Array3<u16>>,
+    input_permutation: Arc<PermutationRuns>,
+}
+
+impl<F: PrimeFieldBits> WnnSynthesisCache<F> {
+    /// The params of the circuit the data was computed for.
+    pub fn params(&self) -> &WnnCircuitParams {
+        &self.params
+    }
+}
+
+/// Test support: A witness value that is deliberately corrupted during synthesis, see
+/// [`WnnCircuit::with_tampering`].
+///
+/// The corrupted value is propagated to the witnesses that depend on it (e.g. a flipped
+/// response changes the score of its class), so that only the constraints of the chip that
+/// witnesses it can reject it. See [`crate::testing::check_tampering_rejected`].
+#[cfg(any(test, feature = "test-support"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tampering {
+    /// Flips the response of the bloom filter of the given class and filter.
+    BloomResponse { class: usize, filter: usize },
+    /// Flips the bit of the pixel `(row, column)` for the threshold with index `bit`, in the
+    /// thermometer encoding.
+    BinarizationBit { pixel: (usize, usize), bit: usize },
+    /// Adds one to the accumulated score of the class after `row` rows of four responses, see
+    /// [`ResponseAccumulatorChip`]. `row` has to be in `1..=ceil(num_filters / 4)`.
+    Accumulator { class: usize, row: usize },
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl fmt::Display for Tampering {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BloomResponse { class, filter } => {
+                write!(f, "Flipped response of class {class}, filter {filter}")
+            }
+            Self::BinarizationBit {
+                pixel: (row, column),
+                bit,
+            } => write!(f, "Flipped bit {bit} of pixel ({row}, {column})"),
+            Self::Accumulator { class, row } => {
+                write!(f, "Incremented accumulator {row} of class {class}")
+            }
+        }
+    }
 }
 
 /// A circuit using [`WnnChip`] to predict the class of an (secret) image.
 #[derive(Clone)]
 pub struct WnnCircuit<F: PrimeFieldBits> {
     image: Value<Array2<u8>>,
-    bloom_filter_arrays: Array3<bool>,
+    /// The input of tabular models (instead of the image), see [`WnnCircuit::from_features`].
+    features: Value<Array1<u16>>,
+    /// The occluded pixels, see [`WnnCircuit::with_occlusion_mask`].
+    occlusion_mask: Option<Array2<bool>>,
+    /// The perturbed image and the bound of its distance to the image, see
+    /// [`WnnCircuit::with_perturbation`].
+    perturbation: Option<(Array2<u8>, u8)>,
+    /// The salt of the image commitment, see [`WnnCircuit::with_salt`].
+    salt: Option<[u8; SALT_SIZE]>,
+    /// See [`WnnCircuit::with_previous_chaining_value`].
+    previous_chaining_value: Option<F>,
+    bloom_filter_arrays: PackedBloomFilters,
     binarization_thresholds: Array3<u16>,
     input_permutation: Array1<u64>,
     params: WnnCircuitParams,
+    /// The activation thresholds of the classes, required iff `params.multi_label` is set.
+    label_thresholds: Option<Array1<u64>>,
+    synthesis_cache: Option<Arc<WnnSynthesisCache<F>>>,
+    response_capture: Option<ResponseCapture>,
+    annotate_regions: bool,
+    #[cfg(any(test, feature = "test-support"))]
+    tampering: Option<Tampering>,
     _marker: PhantomData<F>,
 }
 
 impl<F: PrimeFieldBits> WnnCircuit<F> {
+    /// Creates the circuit from the tensors of a model and manually specified params.
+    ///
+    /// Prefer [`WnnCircuit::from_model`] or [`WnnCircuit::builder`], which derive the params
+    /// from the model (see [`WnnCircuitParams::from_model`]).
     pub fn new(
         image: Array2<u8>,
-        bloom_filter_arrays: Array3<bool>,
+        bloom_filter_arrays: impl Into<PackedBloomFilters>,
         binarization_thresholds: Array3<u16>,
         input_permutation: Array1<u64>,
         params: WnnCircuitParams,
     ) -> Self {
+        let bloom_filter_arrays = bloom_filter_arrays.into();
         assert_eq!(bloom_filter_arrays.shape()[0], params.n_classes);
         Self {
             image: Value::known(image),
+            features: Value::unknown(),
+            occlusion_mask: None,
+            perturbation: None,
+            salt: None,
+            previous_chaining_value: None,
             bloom_filter_arrays,
             binarization_thresholds,
             input_permutation,
             params,
+            label_thresholds: None,
+            synthesis_cache: None,
+            response_capture: None,
+            annotate_regions: false,
+            #[cfg(any(test, feature = "test-support"))]
+            tampering: None,
             _marker: PhantomData,
         }
     }
 
+    /// Creates the circuit for the given model and image.
+    ///
+    /// All tensors and the [`WnnCircuitParams`] are taken from the model, so they are
+    /// consistent by construction. Use [`WnnCircuit::builder`] to also validate the
+    /// model and the image shape.
+    pub fn from_model(wnn: &Wnn, image: Array2<u8>) -> Self {
+        Self {
+            image: Value::known(image),
+            ..Self::without_image(wnn)
+        }
+    }
+
+    /// Creates the circuit for the given tabular model (see [`Wnn::new_tabular`]) and feature
+    /// vector.
+    pub fn from_features(wnn: &Wnn, features: Array1<u16>) -> Self {
+        Self {
+            features: Value::known(features),
+            ..Self::without_image(wnn)
+        }
+    }
+
+    /// Returns a builder for the circuit of the given model.
+    pub fn builder(wnn: &Wnn) -> WnnCircuitBuilder<'_, F> {
+        WnnCircuitBuilder {
+            wnn,
+            image: None,
+            params: None,
+            _marker: PhantomData,
+        }
+    }
+
+    fn without_image(wnn: &Wnn) -> Self {
+        Self {
+            image: Value::unknown(),
+            features: Value::unknown(),
+            occlusion_mask: None,
+            perturbation: None,
+            salt: None,
+            previous_chaining_value: None,
+            bloom_filter_arrays: wnn.bloom_filters.clone(),
+            binarization_thresholds: wnn.binarization_thresholds.clone(),
+            input_permutation: wnn.input_permutation.clone(),
+            params: WnnCircuitParams::from_model(wnn),
+            label_thresholds: wnn.label_thresholds.clone(),
+            synthesis_cache: None,
+            response_capture: None,
+            annotate_regions: false,
+            #[cfg(any(test, feature = "test-support"))]
+            tampering: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Computes the image-independent data used during synthesis, to share it between circuits
+    /// of the same model and params, see [`WnnSynthesisCache`].
+    pub fn compute_synthesis_cache(&self) -> WnnSynthesisCache<F> {
+        let bloom_filter_config = BloomFilterConfig {
+            n_hashes: self.params.n_hashes,
+            bits_per_hash: self.params.bits_per_hash,
+        };
+        WnnSynthesisCache {
+            params: self.params.clone(),
+            bloom_filter_words: BloomFilterWords::new(
+                &self.bloom_filter_arrays,
+                bloom_filter_config,
+                self.params.class_lookup,
+            ),
+            binarization_thresholds: Arc::new(self.binarization_thresholds.clone()),
+            input_permutation: Arc::new(PermutationRuns::new(
+                self.input_permutation.iter().copied(),
+            )),
+        }
+    }
+
+    /// Synthesizes the circuit with the given data instead of computing it from the model.
+    ///
+    /// The cache has to be computed (by [`WnnCircuit::compute_synthesis_cache`]) for the same
+    /// model, otherwise the circuit uses the lookup tables of the other model. Panics if it was
+    /// computed for different params.
+    pub fn with_synthesis_cache(mut self, cache: Arc<WnnSynthesisCache<F>>) -> Self {
+        assert_eq!(
+            cache.params, self.params,
+            "The synthesis cache was computed for different params"
+        );
+        self.synthesis_cache = Some(cache);
+        self
+    }
+
+    /// Sets the activation thresholds of the classes, which circuits with
+    /// [`WnnCircuitParams::multi_label`] compare the scores with. [`WnnCircuit::from_model`]
+    /// takes them from the model (see [`Wnn::with_label_thresholds`]).
+    pub fn with_label_thresholds(mut self, label_thresholds: Array1<u64>) -> Self {
+        assert_eq!(label_thresholds.len(), self.params.n_classes);
+        self.label_thresholds = Some(label_thresholds);
+        self
+    }
+
+    /// Occludes the pixels whose mask bit is set, in circuits with
+    /// [`WnnCircuitParams::occlusion_num_pixels`], see [`crate::occlusion`]. Without a mask, no
+    /// pixel is occluded.
+    pub fn with_occlusion_mask(mut self, mask: Array2<bool>) -> Self {
+        assert_eq!(mask.len(), self.params.occlusion_num_pixels);
+        self.occlusion_mask = Some(mask);
+        self
+    }
+
+    /// Also predicts the perturbed image, whose pixels differ from the image by at most
+    /// `bound`, in circuits with [`WnnCircuitParams::robustness`], see [`crate::robustness`].
+    /// Without a perturbation, the image is compared with itself.
+    pub fn with_perturbation(mut self, perturbed_image: Array2<u8>, bound: u8) -> Self {
+        assert!(self.params.robustness);
+        self.perturbation = Some((perturbed_image, bound));
+        self
+    }
+
+    /// Salts the image commitment in circuits with [`WnnCircuitParams::image_commitment`], see
+    /// [`crate::image_commitment`]. Without a salt, 32 zero bytes are used.
+    pub fn with_salt(mut self, salt: [u8; SALT_SIZE]) -> Self {
+        assert!(self.params.image_commitment);
+        self.salt = Some(salt);
+        self
+    }
+
+    /// Sets the chaining value of the previous image in circuits with
+    /// [`WnnCircuitParams::chaining`], see [`crate::proof_chain`]. Without it, the chain starts
+    /// at [`crate::proof_chain::GENESIS`].
+    pub fn with_previous_chaining_value(mut self, previous: F) -> Self {
+        assert!(self.params.chaining);
+        self.previous_chaining_value = Some(previous);
+        self
+    }
+
+    /// Debug mode: Records the bloom filter responses when the circuit is synthesized (e.g. by
+    /// the [`halo2_proofs::dev::MockProver`]), to compare them with [`Wnn::filter_responses`].
+    pub fn capture_responses(mut self) -> (Self, ResponseCapture) {
+        let capture = ResponseCapture::default();
+        self.response_capture = Some(capture.clone());
+        (self, capture)
+    }
+
+    /// Debug mode: Names every region after the chip and the pixel, filter or class it belongs
+    /// to (see [`crate::gadgets::annotations`]), e.g. for [`halo2_proofs::dev::MockProver`]
+    /// failures and layout plots. Slows down synthesis a bit, but doesn't affect the keys.
+    pub fn with_region_annotations(mut self) -> Self {
+        self.annotate_regions = true;
+        self
+    }
+
+    /// Test support: Corrupts the given witness value during synthesis, which the
+    /// [`halo2_proofs::dev::MockProver`] (and the verifier) have to reject, see [`Tampering`].
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn with_tampering(mut self, tampering: Tampering) -> Self {
+        self.tampering = Some(tampering);
+        self
+    }
+
     /// Plot the circuit circuit layout, outputting to a particular file.
+    #[cfg(feature = "plotting")]
     pub fn plot(&self, filename: &str, k: u32) {
-        use plotters::prelude::*;
-
-        let root = BitMapBackend::new(filename, (1024, 1 << (k + 3))).into_drawing_area();
-        root.fill(&WHITE).unwrap();
-        let root = root.titled("WNN Layout", ("sans-serif", 60)).unwrap();
-        halo2_proofs::dev::CircuitLayout::default()
-            .show_labels(true)
-            .render(k, self, &root)
+        self.plot_with_options(Path::new(filename), k, &PlotOptions::default())
             .unwrap();
     }
+
+    /// Like [`WnnCircuit::plot`], but e.g. as SVG or only for some rows, see [`PlotOptions`].
+    #[cfg(feature = "plotting")]
+    pub fn plot_with_options(
+        &self,
+        path: &Path,
+        k: u32,
+        options: &PlotOptions,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        plot_layout(self, k, path, "WNN Layout", options)
+    }
+}
+
+/// Builds a [`WnnCircuit`] from a [`Wnn`], see [`WnnCircuit::builder`].
+pub struct WnnCircuitBuilder<'a, F: PrimeFieldBits> {
+    wnn: &'a Wnn,
+    image: Option<Array2<u8>>,
+    params: Option<WnnCircuitParams>,
+    _marker: PhantomData<F>,
+}
+
+impl<'a, F: PrimeFieldBits> WnnCircuitBuilder<'a, F> {
+    /// Sets the (secret) input image.
+    /// If no image is set, the circuit is built without witnesses (e.g. for key generation).
+    pub fn image(mut self, image: Array2<u8>) -> Self {
+        self.image = Some(image);
+        self
+    }
+
+    /// Expert override: Uses the given params instead of deriving them from the model, e.g. to
+    /// try other blinding factors, instance columns, class lookup or range check windows
+    /// without changing the model. The params that follow from the model (`p`, `l`,
+    /// `n_hashes`, `bits_per_hash`, `bits_per_filter`, `n_classes`, `multi_label`, `tabular`,
+    /// `occlusion_num_pixels`, `robustness`, `regression`, `image_commitment` and `chaining`)
+    /// must still match.
+    ///
+    /// Note that the keys then don't match [`Wnn::get_circuit_params`] anymore.
+    pub fn params(mut self, params: WnnCircuitParams) -> Self {
+        self.params = Some(params);
+        self
+    }
+
+    /// Validates the model, the image shape and the params (if overridden) and builds the
+    /// circuit.
+    pub fn build(self) -> Result<WnnCircuit<F>, ZeroGError> {
+        self.wnn.validate()?;
+        let mut circuit = WnnCircuit::without_image(self.wnn);
+        if let Some(params) = self.params {
+            check_model_shape(&params, &circuit.params)?;
+            circuit.params = params;
+        }
+        match self.image {
+            Some(image) => {
+                let expected = self.wnn.img_shape();
+                if image.dim() != expected {
+                    return Err(ZeroGError::ImageShape {
+                        expected,
+                        actual: image.dim(),
+                    });
+                }
+                Ok(WnnCircuit {
+                    image: Value::known(image),
+                    ..circuit
+                })
+            }
+            None => Ok(circuit),
+        }
+    }
+}
+
+/// Checks that the params that follow from the model match the derived ones.
+fn check_model_shape(
+    params: &WnnCircuitParams,
+    derived: &WnnCircuitParams,
+) -> Result<(), ZeroGError> {
+    let mismatch = |name: &str, value: u64, expected: u64| {
+        Err(ZeroGError::InvalidModel(format!(
+            "The circuit params set {name} = {value}, but the model implies {expected}"
+        )))
+    };
+    if params.p != derived.p {
+        return mismatch("p", params.p, derived.p);
+    }
+    for (name, value, expected) in [
+        ("l", params.l, derived.l),
+        ("n_hashes", params.n_hashes, derived.n_hashes),
+        ("bits_per_hash", params.bits_per_hash, derived.bits_per_hash),
+        (
+            "bits_per_filter",
+            params.bits_per_filter,
+            derived.bits_per_filter,
+        ),
+        ("n_classes", params.n_classes, derived.n_classes),
+        (
+            "multi_label",
+            params.multi_label as usize,
+            derived.multi_label as usize,
+        ),
+        ("tabular", params.tabular as usize, derived.tabular as usize),
+        (
+            "occlusion_num_pixels",
+            params.occlusion_num_pixels,
+            derived.occlusion_num_pixels,
+        ),
+        (
+            "robustness",
+            params.robustness as usize,
+            derived.robustness as usize,
+        ),
+        (
+            "regression",
+            params.regression as usize,
+            derived.regression as usize,
+        ),
+        (
+            "image_commitment",
+            params.image_commitment as usize,
+            derived.image_commitment as usize,
+        ),
+        (
+            "chaining",
+            params.chaining as usize,
+            derived.chaining as usize,
+        ),
+    ] {
+        if value != expected {
+            return mismatch(name, value as u64, expected as u64);
+        }
+    }
+    Ok(())
 }
 
 impl Default for WnnCircuitParams {
@@ -319,10 +1364,21 @@ impl<F: PrimeFieldBits> Circuit<F> for WnnCircuit<F> {
     fn without_witnesses(&self) -> Self {
         Self {
             image: Value::unknown(),
+            features: Value::unknown(),
+            occlusion_mask: None,
+            perturbation: None,
+            salt: None,
+            previous_chaining_value: None,
             bloom_filter_arrays: self.bloom_filter_arrays.clone(),
             binarization_thresholds: self.binarization_thresholds.clone(),
             input_permutation: self.input_permutation.clone(),
             params: self.params.clone(),
+            label_thresholds: self.label_thresholds.clone(),
+            synthesis_cache: self.synthesis_cache.clone(),
+            response_capture: None,
+            annotate_regions: self.annotate_regions,
+            #[cfg(any(test, feature = "test-support"))]
+            tampering: None,
             _marker: PhantomData,
         }
     }
@@ -332,7 +1388,29 @@ impl<F: PrimeFieldBits> Circuit<F> for WnnCircuit<F> {
     }
 
     fn configure_with_params(meta: &mut ConstraintSystem<F>, params: Self::Params) -> Self::Config {
-        let instance_column = meta.instance_column();
+        assert!(
+            params.num_instance_columns > 0,
+            "At least one instance column is required"
+        );
+        assert!(
+            !params.regression || params.n_classes == 1,
+            "Regression circuits have a single class"
+        );
+        assert!(
+            !params.tabular
+                || !(params.image_commitment
+                    || params.chaining
+                    || params.occlusion_num_pixels > 0
+                    || params.robustness),
+            "Only images can be committed to, occluded or perturbed"
+        );
+        assert!(
+            !(params.occlusion_num_pixels > 0 && params.robustness),
+            "Occlusion and robustness can't be combined"
+        );
+        let instance_columns: Vec<_> = (0..params.num_instance_columns)
+            .map(|_| meta.instance_column())
+            .collect();
 
         let advice_columns = [
             meta.advice_column(),
@@ -346,7 +1424,9 @@ impl<F: PrimeFieldBits> Circuit<F> for WnnCircuit<F> {
         for advice in advice_columns {
             meta.enable_equality(advice);
         }
-        meta.enable_equality(instance_column);
+        for instance in &instance_columns {
+            meta.enable_equality(*instance);
+        }
 
         let constants = meta.fixed_column();
         meta.enable_constant(constants);
@@ -363,30 +1443,141 @@ impl<F: PrimeFieldBits> Circuit<F> for WnnCircuit<F> {
         let wnn_config = WnnConfig {
             bloom_filter_config,
             hash_function_config,
+            class_lookup_classes: params.class_lookup.then_some(params.n_classes),
+            window_num_bits: params.window_num_bits,
+            multi_label: params.multi_label,
+            tabular: params.tabular,
+            occlusion: params.occlusion_num_pixels > 0,
+            robustness: params.robustness,
+            image_commitment: params.image_commitment || params.chaining,
         };
+        let wnn_chip_config = WnnChip::configure(meta, advice_columns, wnn_config);
+        configure_min_blinding_factors(meta, params.min_blinding_factors);
         WnnCircuitConfig {
-            wnn_chip_config: WnnChip::configure(meta, advice_columns, wnn_config),
-            instance_column,
+            wnn_chip_config,
+            instance_columns,
         }
     }
 
-    fn synthesize(
-        &self,
-        config: Self::Config,
-        mut layouter: impl Layouter<F>,
-    ) -> Result<(), Error> {
-        let mut wnn_chip = WnnChip::construct(
-            config.wnn_chip_config,
-            self.bloom_filter_arrays.clone(),
-            self.binarization_thresholds.clone(),
-            self.input_permutation.clone(),
-        );
-        wnn_chip.load(&mut layouter)?;
+    #[instrument(skip_all)]
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<F>) -> Result<(), Error> {
+        let mut layouter = AnnotatedLayouter::new(layouter, self.annotate_regions);
+        let wnn_chip = match &self.synthesis_cache {
+            Some(cache) => WnnChip::construct_with_cache(config.wnn_chip_config, cache),
+            None => WnnChip::construct(
+                config.wnn_chip_config,
+                &self.bloom_filter_arrays,
+                self.binarization_thresholds.clone(),
+                self.input_permutation.clone(),
+            ),
+        };
+        #[cfg(any(test, feature = "test-support"))]
+        let wnn_chip = match self.tampering {
+            Some(tampering) => wnn_chip.with_tampering(tampering),
+            None => wnn_chip,
+        };
+        info_span!("load_tables").in_scope(|| wnn_chip.load(&mut layouter))?;
+
+        // Public values that follow the scores, see `InstanceLayout::from_params`
+        let mut extra_outputs = vec![];
+        let (result, responses) = if self.params.tabular {
+            wnn_chip.predict_features(layouter.namespace(|| "wnn"), self.features.clone())?
+        } else {
+            let pixels = wnn_chip
+                .assign_image(layouter.namespace(|| "EncodeImageChip"), self.image.clone())?;
+            let prediction = if self.params.occlusion_num_pixels > 0 {
+                let mask = self.image.as_ref().map(|image| match &self.occlusion_mask {
+                    Some(mask) => mask.clone(),
+                    None => Array2::from_elem(image.dim(), false),
+                });
+                let (occluded, cells) =
+                    wnn_chip.occlude(layouter.namespace(|| "MaskChip"), &pixels, mask)?;
+                extra_outputs = cells;
+                wnn_chip.predict_pixels(layouter.namespace(|| "wnn"), &occluded)?
+            } else if self.params.robustness {
+                let (perturbed_image, bound) = match &self.perturbation {
+                    Some((image, bound)) => (Value::known(image.clone()), Value::known(*bound)),
+                    None => (self.image.clone(), self.image.as_ref().map(|_| 0)),
+                };
+                let perturbed_pixels = wnn_chip.assign_image(
+                    layouter.namespace(|| "EncodeImageChip perturbed"),
+                    perturbed_image,
+                )?;
+                let bound_cell = wnn_chip.check_perturbation(
+                    layouter.namespace(|| "PerturbationChip"),
+                    &pixels,
+                    &perturbed_pixels,
+                    bound,
+                )?;
+                let (perturbed_scores, _) = wnn_chip
+                    .predict_pixels(layouter.namespace(|| "wnn perturbed"), &perturbed_pixels)?;
+                extra_outputs = perturbed_scores;
+                extra_outputs.push(bound_cell);
+                wnn_chip.predict_pixels(layouter.namespace(|| "wnn"), &pixels)?
+            } else {
+                wnn_chip.predict_pixels(layouter.namespace(|| "wnn"), &pixels)?
+            };
+            if self.params.image_commitment || self.params.chaining {
+                let salt = self
+                    .image
+                    .as_ref()
+                    .map(|_| self.salt.unwrap_or([0; SALT_SIZE]));
+                let commitment = wnn_chip.commit_image(
+                    layouter.namespace(|| "ImageCommitmentChip"),
+                    &pixels,
+                    salt,
+                )?;
+                if self.params.image_commitment {
+                    extra_outputs.push(commitment.clone());
+                }
+                if self.params.chaining {
+                    let previous = self
+                        .image
+                        .as_ref()
+                        .map(|_| self.previous_chaining_value.unwrap_or(F::ZERO));
+                    let (previous, chaining_value) = wnn_chip.chain_commitment(
+                        layouter.namespace(|| "ImageCommitmentChip chain"),
+                        previous,
+                        &commitment,
+                    )?;
+                    extra_outputs.extend([previous, chaining_value]);
+                }
+            }
+            prediction
+        };
+        if let Some(capture) = &self.response_capture {
+            let mut captured = Array2::from_elem((responses.len(), responses[0].len()), false);
+            let mut known = false;
+            for (class, class_responses) in responses.iter().enumerate() {
+                for (filter, response) in class_responses.iter().enumerate() {
+                    response.value().map(|value| {
+                        captured[(class, filter)] = *value == F::ONE;
+                        known = true;
+                    });
+                }
+            }
+            *capture.0.lock().unwrap() = known.then_some(captured);
+        }
 
-        let result = wnn_chip.predict(layouter.namespace(|| "wnn"), self.image.clone())?;
+        let outputs = if self.params.multi_label {
+            let thresholds = self
+                .label_thresholds
+                .as_ref()
+                .expect("Multi-label circuits require label thresholds");
+            wnn_chip.labels(
+                layouter.namespace(|| "labels"),
+                &result,
+                &thresholds.to_vec(),
+            )?
+        } else {
+            result
+        };
+        let outputs: Vec<_> = outputs.into_iter().chain(extra_outputs).collect();
 
-        for (i, score) in result.iter().enumerate() {
-            layouter.constrain_instance(score.cell(), config.instance_column, i)?;
+        let layout = InstanceLayout::from_params(&self.params);
+        for (i, output) in outputs.iter().enumerate() {
+            let (column, row) = layout.position(i);
+            layouter.constrain_instance(output.cell(), config.instance_columns[column], row)?;
         }
 
         Ok(())
@@ -399,12 +1590,20 @@ impl<F: PrimeFieldBits> Circuit<F> for WnnCircuit<F> {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
 
     use halo2_proofs::dev::MockProver;
     use halo2_proofs::halo2curves::bn256::Fr as Fp;
-    use ndarray::{array, Array3};
+    use halo2_proofs::plonk::Circuit;
+    use ndarray::{array, Array2, Array3};
 
-    use super::{WnnCircuit, WnnCircuitParams};
+    use super::{InstanceLayout, PublicValue, WnnCircuit, WnnCircuitParams};
+    use crate::error::ZeroGError;
+    use crate::gadgets::image_commitment::{chain, commit};
+    use crate::gadgets::poseidon::PoseidonSpec;
+    use crate::image_commitment::SALT_SIZE;
+    use crate::testing::describe_failure;
+    use crate::wnn::Wnn;
 
     const PARAMS: WnnCircuitParams = WnnCircuitParams {
         p: 2097143, // (1 << 21) - 9
@@ -413,6 +1612,17 @@ mod tests {
         bits_per_hash: 10,
         bits_per_filter: 12,
         n_classes: 2,
+        min_blinding_factors: 0,
+        num_instance_columns: 1,
+        class_lookup: false,
+        window_num_bits: 8,
+        chaining: false,
+        multi_label: false,
+        regression: false,
+        tabular: false,
+        occlusion_num_pixels: 0,
+        robustness: false,
+        image_commitment: false,
     };
 
     fn make_test_circuit() -> WnnCircuit<Fp> {
@@ -488,6 +1698,480 @@ mod tests {
     }
 
     #[test]
+    fn test_from_model() {
+        let circuit = make_test_circuit();
+        let wnn = Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            PARAMS.p,
+            circuit.bloom_filter_arrays,
+            circuit.input_permutation,
+            circuit.binarization_thresholds,
+        );
+        assert_eq!(WnnCircuitParams::from_model(&wnn), PARAMS);
+
+        let image = array![[70, 100, 150], [20, 110, 200], [27, 50, 211], [200, 100, 3]];
+        let circuit: WnnCircuit<Fp> = WnnCircuit::builder(&wnn).image(image).build().unwrap();
+        let prover = MockProver::run(13, &circuit, vec![vec![Fp::from(1), Fp::from(2)]]).unwrap();
+        prover.assert_satisfied();
+
+        let result = WnnCircuit::<Fp>::builder(&wnn)
+            .image(array![[0, 0], [0, 0]])
+            .build();
+        assert!(matches!(
+            result,
+            Err(ZeroGError::ImageShape {
+                expected: (4, 3),
+                actual: (2, 2)
+            })
+        ));
+    }
+
+    #[test]
+    fn test_params_override() {
+        let circuit = make_test_circuit();
+        let wnn = Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            PARAMS.p,
+            circuit.bloom_filter_arrays,
+            circuit.input_permutation,
+            circuit.binarization_thresholds,
+        );
+        let params = WnnCircuitParams {
+            num_instance_columns: 2,
+            ..PARAMS
+        };
+        let circuit: WnnCircuit<Fp> = WnnCircuit::builder(&wnn)
+            .params(params.clone())
+            .build()
+            .unwrap();
+        assert_eq!(circuit.params, params);
+
+        let result = WnnCircuit::<Fp>::builder(&wnn)
+            .params(WnnCircuitParams {
+                n_hashes: 3,
+                ..PARAMS
+            })
+            .build();
+        assert!(
+            matches!(result, Err(ZeroGError::InvalidModel(message)) if message.contains("n_hashes"))
+        );
+
+        for (params, name) in [
+            (
+                WnnCircuitParams {
+                    image_commitment: true,
+                    ..PARAMS
+                },
+                "image_commitment",
+            ),
+            (
+                WnnCircuitParams {
+                    chaining: true,
+                    ..PARAMS
+                },
+                "chaining",
+            ),
+        ] {
+            let result = WnnCircuit::<Fp>::builder(&wnn).params(params).build();
+            assert!(
+                matches!(result, Err(ZeroGError::InvalidModel(message)) if message.contains(name))
+            );
+        }
+    }
+
+    #[test]
+    fn test_multiple_instance_columns() {
+        for (num_instance_columns, instances) in [
+            (2, vec![vec![Fp::from(1)], vec![Fp::from(2)]]),
+            (3, vec![vec![Fp::from(1)], vec![Fp::from(2)], vec![]]),
+        ] {
+            let params = WnnCircuitParams {
+                num_instance_columns,
+                ..PARAMS
+            };
+            let layout = InstanceLayout::from_params(&params);
+            assert_eq!(layout.position(1), (1, 0));
+            assert_eq!(layout.to_columns(&[Fp::from(1), Fp::from(2)]), instances);
+
+            let circuit = WnnCircuit {
+                params,
+                ..make_test_circuit()
+            };
+            let prover = MockProver::run(13, &circuit, instances).unwrap();
+            prover.assert_satisfied();
+        }
+
+        let layout = InstanceLayout {
+            values: (0..5).map(|class| PublicValue::Score { class }).collect(),
+            num_columns: 2,
+        };
+        assert_eq!(layout.column_lengths(), vec![3, 2]);
+        assert_eq!(layout.position(4), (1, 1));
+    }
+
+    #[test]
+    fn test_class_lookup() {
+        let circuit = WnnCircuit {
+            params: WnnCircuitParams {
+                class_lookup: true,
+                ..PARAMS
+            },
+            ..make_test_circuit()
+        };
+        let prover = MockProver::run(13, &circuit, vec![vec![Fp::from(1), Fp::from(2)]]).unwrap();
+        prover.assert_satisfied();
+
+        let prover = MockProver::run(13, &circuit, vec![vec![Fp::from(2), Fp::from(2)]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_synthesis_cache() {
+        for class_lookup in [false, true] {
+            let circuit = WnnCircuit {
+                params: WnnCircuitParams {
+                    class_lookup,
+                    ..PARAMS
+                },
+                ..make_test_circuit()
+            };
+            let cache = Arc::new(circuit.compute_synthesis_cache());
+            let cached = circuit.clone().with_synthesis_cache(cache.clone());
+            // Key generation synthesizes the circuit without witnesses
+            assert!(cached.without_witnesses().synthesis_cache.is_some());
+
+            let instances = vec![vec![Fp::from(1), Fp::from(2)]];
+            let prover = MockProver::run(13, &cached, instances).unwrap();
+            prover.assert_satisfied();
+            let instances = vec![vec![Fp::from(2), Fp::from(2)]];
+            let prover = MockProver::run(13, &cached, instances).unwrap();
+            assert!(prover.verify().is_err());
+
+            // The cached bloom filters are used instead of the ones of the circuit
+            let empty = WnnCircuit {
+                bloom_filter_arrays: Array3::from_elem((2, 2, 1024), false).into(),
+                ..circuit.clone()
+            };
+            let cached = circuit.with_synthesis_cache(Arc::new(empty.compute_synthesis_cache()));
+            let instances = vec![vec![Fp::from(0), Fp::from(0)]];
+            let prover = MockProver::run(13, &cached, instances).unwrap();
+            prover.assert_satisfied();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "different params")]
+    fn test_synthesis_cache_params() {
+        let circuit = make_test_circuit();
+        let other = WnnCircuit {
+            params: WnnCircuitParams {
+                class_lookup: true,
+                ..PARAMS
+            },
+            ..make_test_circuit()
+        };
+        circuit.with_synthesis_cache(Arc::new(other.compute_synthesis_cache()));
+    }
+
+    #[test]
+    fn test_chaining() {
+        let params = WnnCircuitParams {
+            chaining: true,
+            ..PARAMS
+        };
+        let layout = InstanceLayout::from_params(&params);
+        assert_eq!(
+            layout.values[2..],
+            [
+                PublicValue::PreviousChainingValue,
+                PublicValue::ChainingValue
+            ]
+        );
+
+        // The image of `make_test_circuit`
+        let image = array![[70, 100, 150], [20, 110, 200], [27, 50, 211], [200, 100, 3]];
+        let spec = PoseidonSpec::new();
+        let commitment = commit(&spec, &image, &[0; SALT_SIZE]);
+        for previous in [0, 42] {
+            let circuit = WnnCircuit {
+                params: params.clone(),
+                ..make_test_circuit()
+            }
+            .with_previous_chaining_value(Fp::from(previous));
+            let chaining_value = chain(&spec, Fp::from(previous), commitment);
+            let instances = vec![vec![
+                Fp::from(1),
+                Fp::from(2),
+                Fp::from(previous),
+                chaining_value,
+            ]];
+            let prover = MockProver::run(13, &circuit, instances).unwrap();
+            prover.assert_satisfied();
+
+            // The chaining value is constrained by the circuit
+            let instances = vec![vec![
+                Fp::from(1),
+                Fp::from(2),
+                Fp::from(previous),
+                chaining_value + Fp::from(1),
+            ]];
+            let prover = MockProver::run(13, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_image_commitment() {
+        let params = WnnCircuitParams {
+            image_commitment: true,
+            ..PARAMS
+        };
+        let layout = InstanceLayout::from_params(&params);
+        assert_eq!(layout.values.last(), Some(&PublicValue::ImageCommitment));
+
+        let circuit = WnnCircuit {
+            params,
+            ..make_test_circuit()
+        };
+        // The image of `make_test_circuit`
+        let image = array![[70, 100, 150], [20, 110, 200], [27, 50, 211], [200, 100, 3]];
+        let spec = PoseidonSpec::new();
+        for salt in [None, Some([7; SALT_SIZE])] {
+            let circuit = match salt {
+                Some(salt) => circuit.clone().with_salt(salt),
+                None => circuit.clone(),
+            };
+            let commitment = commit(&spec, &image, &salt.unwrap_or([0; SALT_SIZE]));
+            let instances = vec![vec![Fp::from(1), Fp::from(2), commitment]];
+            let prover = MockProver::run(13, &circuit, instances).unwrap();
+            prover.assert_satisfied();
+
+            let instances = vec![vec![Fp::from(1), Fp::from(2), commitment + Fp::from(1)]];
+            let prover = MockProver::run(13, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_multi_label() {
+        let params = WnnCircuitParams {
+            multi_label: true,
+            ..PARAMS
+        };
+        let layout = InstanceLayout::from_params(&params);
+        assert_eq!(layout.values[0], PublicValue::Label { class: 0 });
+
+        // The scores are [1, 2], so only the second class reaches its threshold
+        let circuit = WnnCircuit {
+            params,
+            ..make_test_circuit()
+        }
+        .with_label_thresholds(array![2, 2]);
+        let prover = MockProver::run(13, &circuit, vec![vec![Fp::from(0), Fp::from(1)]]).unwrap();
+        prover.assert_satisfied();
+        for labels in [[1, 1], [0, 0], [0, 2]] {
+            let instances = vec![labels.map(Fp::from).to_vec()];
+            let prover = MockProver::run(13, &circuit, instances).unwrap();
+            assert!(prover.verify().is_err(), "{labels:?}");
+        }
+    }
+
+    #[test]
+    fn test_window_num_bits() {
+        for window_num_bits in [4, 11] {
+            let circuit = WnnCircuit {
+                params: WnnCircuitParams {
+                    window_num_bits,
+                    ..PARAMS
+                },
+                ..make_test_circuit()
+            };
+            let prover =
+                MockProver::run(13, &circuit, vec![vec![Fp::from(1), Fp::from(2)]]).unwrap();
+            prover.assert_satisfied();
+        }
+    }
+
+    #[test]
+    fn test_tabular() {
+        let features = array![500, 40000, 0, 65535];
+        let tabular_wnn = |bloom_filters: Array3<bool>| {
+            let feature_thresholds = array![
+                [0, 1000, 2000],
+                [10000, 40000, 50000],
+                [1, 2, 3],
+                [65535, 0, 300]
+            ];
+            Wnn::new_tabular(
+                2,
+                1024,
+                2,
+                12,
+                2097143,
+                bloom_filters,
+                (0..12).collect(),
+                feature_thresholds,
+            )
+        };
+
+        // Make the second class respond to the features
+        let mut bloom_filters = Array3::from_elem((2, 1, 1024), false);
+        let wnn = tabular_wnn(bloom_filters.clone());
+        let index = wnn.encode_bits(&wnn.feature_encoding(&features))[0];
+        for entry in wnn.filter_entries(index) {
+            bloom_filters[[1, 0, entry]] = true;
+        }
+        let wnn = tabular_wnn(bloom_filters);
+        assert!(wnn.validate().is_ok());
+        assert_eq!(wnn.predict_features(&features), vec![0, 1]);
+
+        let circuit = WnnCircuit::<Fp>::from_features(&wnn, features);
+        assert!(circuit.params.tabular);
+        let prover = MockProver::run(13, &circuit, vec![vec![Fp::from(0), Fp::from(1)]]).unwrap();
+        prover.assert_satisfied();
+        let prover = MockProver::run(13, &circuit, vec![vec![Fp::from(1), Fp::from(1)]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_occlusion() {
+        let params = WnnCircuitParams {
+            occlusion_num_pixels: 12,
+            ..PARAMS
+        };
+        let layout = InstanceLayout::from_params(&params);
+        assert_eq!(layout.values[2], PublicValue::Occluded { pixel: 0 });
+        assert_eq!(layout.values.len(), 14);
+
+        let circuit = WnnCircuit {
+            params,
+            ..make_test_circuit()
+        };
+        let instances = |scores: [u64; 2], occluded: bool| {
+            let mask = std::iter::repeat(occluded as u64).take(12);
+            vec![scores.into_iter().chain(mask).map(Fp::from).collect()]
+        };
+
+        // Without a mask, no pixel is occluded
+        let prover = MockProver::run(13, &circuit, instances([1, 2], false)).unwrap();
+        prover.assert_satisfied();
+        let prover = MockProver::run(13, &circuit, instances([1, 2], true)).unwrap();
+        assert!(prover.verify().is_err());
+
+        // The all-zero image leads to filter inputs 45 and 128, which none of the filters
+        // contain
+        let circuit = circuit.with_occlusion_mask(Array2::from_elem((4, 3), true));
+        let prover = MockProver::run(13, &circuit, instances([0, 0], true)).unwrap();
+        prover.assert_satisfied();
+        let prover = MockProver::run(13, &circuit, instances([1, 2], true)).unwrap();
+        assert!(prover.verify().is_err());
+        let prover = MockProver::run(13, &circuit, instances([0, 0], false)).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_robustness() {
+        let params = WnnCircuitParams {
+            robustness: true,
+            ..PARAMS
+        };
+        let layout = InstanceLayout::from_params(&params);
+        assert_eq!(layout.values[2], PublicValue::PerturbedScore { class: 0 });
+        assert_eq!(layout.values[4], PublicValue::PerturbationBound);
+
+        let circuit = WnnCircuit {
+            params,
+            ..make_test_circuit()
+        };
+        let instances = |values: [u64; 5]| vec![values.into_iter().map(Fp::from).collect()];
+
+        // Without a perturbation, the image is compared with itself
+        let prover = MockProver::run(13, &circuit, instances([1, 2, 1, 2, 0])).unwrap();
+        prover.assert_satisfied();
+
+        // The all-zero image has a distance of 211 and leads to the scores [0, 0], see
+        // `test_occlusion`
+        let perturbed = circuit
+            .clone()
+            .with_perturbation(Array2::zeros((4, 3)), 211);
+        let prover = MockProver::run(13, &perturbed, instances([1, 2, 0, 0, 211])).unwrap();
+        prover.assert_satisfied();
+        let prover = MockProver::run(13, &perturbed, instances([1, 2, 1, 2, 211])).unwrap();
+        assert!(prover.verify().is_err());
+
+        let too_far = circuit.with_perturbation(Array2::zeros((4, 3)), 210);
+        let prover = MockProver::run(13, &too_far, instances([1, 2, 0, 0, 210])).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only images can be committed to, occluded or perturbed")]
+    fn test_tabular_occlusion() {
+        let circuit = WnnCircuit {
+            params: WnnCircuitParams {
+                tabular: true,
+                occlusion_num_pixels: 12,
+                ..PARAMS
+            },
+            ..make_test_circuit()
+        };
+        let instances = vec![vec![Fp::from(0); 14]];
+        MockProver::run(13, &circuit, instances).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Occlusion and robustness can't be combined")]
+    fn test_occlusion_and_robustness() {
+        let circuit = WnnCircuit {
+            params: WnnCircuitParams {
+                occlusion_num_pixels: 12,
+                robustness: true,
+                ..PARAMS
+            },
+            ..make_test_circuit()
+        };
+        let instances = vec![vec![Fp::from(0); 17]];
+        MockProver::run(13, &circuit, instances).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Only images can be committed to, occluded or perturbed")]
+    fn test_tabular_robustness() {
+        let circuit = WnnCircuit {
+            params: WnnCircuitParams {
+                tabular: true,
+                robustness: true,
+                ..PARAMS
+            },
+            ..make_test_circuit()
+        };
+        let instances = vec![vec![Fp::from(0); 5]];
+        MockProver::run(13, &circuit, instances).unwrap();
+    }
+
+    #[test]
+    fn test_region_annotations() {
+        let circuit = make_test_circuit().with_region_annotations();
+        let prover = MockProver::run(13, &circuit, vec![vec![Fp::from(1), Fp::from(2)]]).unwrap();
+        prover.assert_satisfied();
+
+        // A wrong score is reported in the region of its class
+        let prover = MockProver::run(13, &circuit, vec![vec![Fp::from(1), Fp::from(3)]]).unwrap();
+        let failures = prover.verify().unwrap_err();
+        assert!(failures
+            .iter()
+            .map(describe_failure)
+            .any(|failure| failure.starts_with("[ResponseAccumulatorChip, class 1]")));
+    }
+
+    #[test]
+    #[cfg(feature = "plotting")]
     fn plot() {
         make_test_circuit().plot("wnn-layout.png", 9);
     }
@@ -0,0 +1,353 @@
+//! A gRPC service proving and verifying inference of a fixed model, see [`serve`] and
+//! `proto/zero_g.proto` for the service definition.
+//!
+//! Like the HTTP service, jobs are processed by a [`JobQueue`]. In addition, clients can stream
+//! the status of a job with `WatchJob` instead of polling, including an update for each phase
+//! of a running job (see [`JobPhase`]).
+
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use ndarray::Array1;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::info;
+
+use crate::classifier::{ProvableClassifier, WnnInput};
+use crate::gadgets::wnn::PublicValue;
+use crate::io::load_image_from_bytes;
+use crate::jobs::{JobPhase, JobQueue, JobStatus};
+use crate::proof_file::ProofFile;
+use crate::utils::to_u32;
+use crate::verifier_bundle::VerifierBundle;
+
+/// The types generated from `proto/zero_g.proto`.
+pub mod proto {
+    tonic::include_proto!("zero_g");
+}
+
+use proto::{
+    prove_request,
+    zero_g_server::{ZeroG, ZeroGServer},
+    GetModelInfoRequest, GetModelInfoResponse, GetProofRequest, GetProofResponse, JobState,
+    JobUpdate, ProveRequest, ProveResponse, VerifyRequest, VerifyResponse, WatchJobRequest,
+};
+
+/// The phase of a running job in the protobuf definition.
+fn proto_phase(phase: JobPhase) -> proto::JobPhase {
+    match phase {
+        JobPhase::Proving => proto::JobPhase::Proving,
+        JobPhase::WritingProof => proto::JobPhase::WritingProof,
+    }
+}
+
+/// Number of updates buffered per `WatchJob` stream.
+const STREAM_CAPACITY: usize = 16;
+
+/// Implements the `ZeroG` service.
+pub struct ZeroGService {
+    queue: Arc<JobQueue>,
+    bundle: Arc<VerifierBundle>,
+}
+
+impl ZeroGService {
+    pub fn new(queue: Arc<JobQueue>) -> Self {
+        let bundle = Arc::new(queue.prover().verifier_bundle());
+        Self { queue, bundle }
+    }
+}
+
+/// Runs the gRPC service until the process is terminated.
+pub async fn serve(queue: Arc<JobQueue>, address: SocketAddr) -> eyre::Result<()> {
+    info!("Listening on {address}");
+    Server::builder()
+        .add_service(ZeroGServer::new(ZeroGService::new(queue)))
+        .serve(address)
+        .await?;
+    Ok(())
+}
+
+fn job_update(job_id: &str, status: JobStatus) -> JobUpdate {
+    let mut update = JobUpdate {
+        job_id: job_id.to_string(),
+        ..Default::default()
+    };
+    match status {
+        JobStatus::Queued => update.set_state(JobState::Queued),
+        JobStatus::Running { phase } => {
+            update.set_state(JobState::Running);
+            update.set_phase(proto_phase(phase));
+        }
+        JobStatus::Done { scores } => {
+            update.set_state(JobState::Done);
+            update.scores = scores;
+        }
+        JobStatus::Failed { error } => {
+            update.set_state(JobState::Failed);
+            update.error = error;
+        }
+    }
+    update
+}
+
+#[tonic::async_trait]
+impl ZeroG for ZeroGService {
+    async fn get_model_info(
+        &self,
+        _request: Request<GetModelInfoRequest>,
+    ) -> Result<Response<GetModelInfoResponse>, Status> {
+        let wnn = self.queue.prover().model();
+        let ((rows, columns), num_features) = if wnn.is_tabular() {
+            ((0, 0), wnn.num_features())
+        } else {
+            (wnn.img_shape(), 0)
+        };
+        Ok(Response::new(GetModelInfoResponse {
+            model_commitment: ProvableClassifier::commitment(wnn).to_vec(),
+            vk_fingerprint: self.queue.prover().vk_fingerprint().to_vec(),
+            image_rows: rows as u32,
+            image_columns: columns as u32,
+            num_classes: wnn.num_classes() as u32,
+            num_features: num_features as u32,
+        }))
+    }
+
+    async fn prove(
+        &self,
+        request: Request<ProveRequest>,
+    ) -> Result<Response<ProveResponse>, Status> {
+        let input = match request.into_inner().input {
+            Some(prove_request::Input::Image(image)) => load_image_from_bytes(&image)
+                .map(WnnInput::Image)
+                .map_err(|e| Status::invalid_argument(format!("Invalid image: {e}")))?,
+            Some(prove_request::Input::Features(features)) => features
+                .values
+                .into_iter()
+                .map(u16::try_from)
+                .collect::<Result<Array1<_>, _>>()
+                .map(WnnInput::Features)
+                .map_err(|e| Status::invalid_argument(format!("Invalid features: {e}")))?,
+            None => return Err(Status::invalid_argument("Missing input")),
+        };
+        let job_id = self
+            .queue
+            .submit(input)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        Ok(Response::new(ProveResponse { job_id }))
+    }
+
+    type WatchJobStream = ReceiverStream<Result<JobUpdate, Status>>;
+
+    async fn watch_job(
+        &self,
+        request: Request<WatchJobRequest>,
+    ) -> Result<Response<Self::WatchJobStream>, Status> {
+        let job_id = request.into_inner().job_id;
+        // Subscribe before reading the current status, so that no update is missed
+        let mut updates = self.queue.subscribe();
+        let status = self
+            .queue
+            .status(&job_id)
+            .ok_or_else(|| Status::not_found(format!("Unknown job {job_id}")))?;
+
+        let (sender, receiver) = mpsc::channel(STREAM_CAPACITY);
+        let queue = self.queue.clone();
+        tokio::spawn(async move {
+            let mut status = status;
+            loop {
+                let is_final = status.is_final();
+                if sender.send(Ok(job_update(&job_id, status))).await.is_err() || is_final {
+                    return;
+                }
+                status = loop {
+                    match updates.recv().await {
+                        Ok((id, status)) if id == job_id => break status,
+                        Ok(_) => {}
+                        // Some updates were dropped, so the current status is sent instead
+                        Err(broadcast::error::RecvError::Lagged(_)) => {
+                            break queue.status(&job_id).expect("Jobs are never removed")
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    }
+                };
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn get_proof(
+        &self,
+        request: Request<GetProofRequest>,
+    ) -> Result<Response<GetProofResponse>, Status> {
+        let job_id = request.into_inner().job_id;
+        match self.queue.status(&job_id) {
+            Some(JobStatus::Done { .. }) => {}
+            Some(status) => {
+                return Err(Status::failed_precondition(format!(
+                    "Job {job_id} is not done: {status:?}"
+                )))
+            }
+            None => return Err(Status::not_found(format!("Unknown job {job_id}"))),
+        }
+
+        let proof_file = fs::read(self.queue.proof_path(&job_id))
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(GetProofResponse { proof_file }))
+    }
+
+    async fn verify(
+        &self,
+        request: Request<VerifyRequest>,
+    ) -> Result<Response<VerifyResponse>, Status> {
+        let proof_file = ProofFile::from_bytes(&request.into_inner().proof_file)
+            .map_err(|e| Status::invalid_argument(format!("Invalid proof file: {e}")))?;
+
+        let bundle = self.bundle.clone();
+        let layout = self.bundle.instance_layout.clone();
+        let result =
+            tokio::task::spawn_blocking(move || bundle.verify(&proof_file).map(|()| proof_file))
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(match result {
+            Ok(proof_file) => VerifyResponse {
+                valid: true,
+                // Only the scores (or labels), not the occlusion mask, the perturbation, the
+                // image commitment or the chaining values (if any)
+                public_inputs: layout
+                    .values
+                    .iter()
+                    .zip(&proof_file.public_inputs)
+                    .filter(|(value, _)| {
+                        !matches!(
+                            value,
+                            PublicValue::Occluded { .. }
+                                | PublicValue::PerturbedScore { .. }
+                                | PublicValue::PerturbationBound
+                                | PublicValue::ImageCommitment
+                                | PublicValue::PreviousChainingValue
+                                | PublicValue::ChainingValue
+                        )
+                    })
+                    .map(|(_, input)| to_u32(input))
+                    .collect(),
+                ..Default::default()
+            },
+            Err(e) => VerifyResponse {
+                valid: false,
+                error: e.to_string(),
+                ..Default::default()
+            },
+        }))
+    }
+}
+
+#[cfg(all(test, feature = "hdf5"))]
+mod tests {
+    use std::{env, fs, path::Path, process};
+
+    use tokio::net::TcpListener;
+    use tokio_stream::{wrappers::TcpListenerStream, StreamExt};
+    use tonic::transport::Server;
+
+    use super::proto::{
+        prove_request, zero_g_client::ZeroGClient, zero_g_server::ZeroGServer, GetModelInfoRequest,
+        GetProofRequest, JobPhase, JobState, ProveRequest, VerifyRequest, WatchJobRequest,
+    };
+    use super::ZeroGService;
+    use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+    use crate::jobs::JobQueue;
+    use crate::prover::Prover;
+    use crate::setup::{setup, SrsSource};
+    use crate::{load_grayscale_image, load_wnn};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_round_trip() {
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let dir = env::temp_dir().join(format!("zero_g_grpc_{}", process::id()));
+        setup(&wnn, Some(k), &SrsSource::Generate, &dir).unwrap();
+        let prover = Prover::open(Path::new(model_path), &dir).unwrap();
+        let queue = JobQueue::new(prover, &dir.join("jobs"), 1).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(ZeroGServer::new(ZeroGService::new(queue)))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let mut client = ZeroGClient::connect(format!("http://{address}"))
+            .await
+            .unwrap();
+
+        let info = client
+            .get_model_info(GetModelInfoRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(info.model_commitment, wnn.commitment().to_vec());
+        assert_eq!(info.num_features, 0);
+
+        let image = fs::read(TEST_IMG_PATH).unwrap();
+        let job_id = client
+            .prove(ProveRequest {
+                input: Some(prove_request::Input::Image(image)),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .job_id;
+        let updates: Vec<_> = client
+            .watch_job(WatchJobRequest {
+                job_id: job_id.clone(),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .map(|update| update.unwrap())
+            .collect()
+            .await;
+
+        let expected_scores = wnn.predict(&load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap());
+        let last = updates.last().unwrap();
+        assert_eq!(last.state(), JobState::Done);
+        assert_eq!(last.scores, expected_scores);
+        // Unless the job finished before watching it, the phases of the running job are
+        // streamed in order
+        if updates.len() > 1 {
+            let phases: Vec<_> = updates
+                .iter()
+                .filter(|update| update.state() == JobState::Running)
+                .map(|update| update.phase())
+                .collect();
+            assert_eq!(phases.last(), Some(&JobPhase::WritingProof));
+            assert!(phases.windows(2).all(|pair| pair[0] != pair[1]));
+        }
+
+        let proof_file = client
+            .get_proof(GetProofRequest { job_id })
+            .await
+            .unwrap()
+            .into_inner()
+            .proof_file;
+        let verified = client
+            .verify(VerifyRequest { proof_file })
+            .await
+            .unwrap()
+            .into_inner();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(verified.valid, "{}", verified.error);
+        assert_eq!(
+            verified
+                .public_inputs
+                .into_iter()
+                .map(u64::from)
+                .collect::<Vec<_>>(),
+            expected_scores
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! Commitments to input images, so that external systems can register an image before it is
+//! proven without revealing it.
+//!
+//! The commitment is a Poseidon hash over the shape and the pixels of the image, and an
+//! optional salt (without a salt, 32 zero bytes are used), see
+//! [`crate::gadgets::image_commitment`]. Without a salt, the commitment of a guessable image
+//! (e.g. from a public dataset) can be brute-forced, so salting is recommended for private
+//! images.
+//!
+//! With [`crate::Wnn::with_image_commitment`], the circuit computes the commitment from the
+//! witnessed pixels and exposes it as a public input (see [`PublicValue::ImageCommitment`]), so
+//! a proof shows that it was generated for the committed image, see
+//! [`crate::Wnn::salted_proof`] and [`exposed_image_commitment`]. As a field element, the
+//! commitment is converted to bytes in big-endian order, see [`commitment_to_field`].
+//!
+//! The commitments of many images (e.g. of a batch, see
+//! [`crate::batch_proving::Manifest::image_commitment_root`]) can be registered at once as the
+//! root of a Merkle tree over them, see [`merkle_root`]. A [`MerkleOpening`] (see
+//! [`merkle_opening`]) shows that a single commitment is part of the tree. Leaves and inner nodes
+//! are hashed with different prefixes, and a node without a sibling is moved up to the next
+//! level unchanged, so that no two lists of commitments have the same root. The tree is
+//! computed outside of the circuit (with keccak256, so that it can be recomputed on-chain), and
+//! the root is not a public input of the proofs: A leaf is only bound to a proof if the proof
+//! exposes the same commitment (see [`exposed_image_commitment`]).
+
+use ff::PrimeField;
+use halo2_proofs::halo2curves::bn256::Fr as Fp;
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+use crate::gadgets::image_commitment::commit;
+use crate::gadgets::poseidon::{bytes_to_field, PoseidonSpec};
+use crate::gadgets::wnn::{InstanceLayout, PublicValue};
+use crate::utils::keccak256;
+
+/// The length of a salt, in bytes.
+pub const SALT_SIZE: usize = 32;
+
+/// Computes the commitment to an image, see the module documentation.
+pub fn image_commitment(image: &Array2<u8>, salt: Option<&[u8; SALT_SIZE]>) -> [u8; 32] {
+    let commitment = commit(
+        &PoseidonSpec::<Fp>::new(),
+        image,
+        salt.unwrap_or(&[0; SALT_SIZE]),
+    );
+    commitment_from_field(&commitment)
+}
+
+/// Converts a commitment into a public input, interpreting it as a big-endian integer
+/// (reduced modulo the field order, which doesn't change commitments).
+pub fn commitment_to_field(commitment: &[u8; 32]) -> Fp {
+    bytes_to_field(commitment)
+}
+
+/// The inverse of [`commitment_to_field`].
+pub fn commitment_from_field(x: &Fp) -> [u8; 32] {
+    let mut commitment = x.to_repr();
+    commitment.reverse();
+    commitment
+}
+
+/// The image commitment exposed by a proof, given its public inputs (in the order of
+/// [`InstanceLayout::values`]), or `None` if the layout doesn't contain one.
+pub fn exposed_image_commitment(layout: &InstanceLayout, public_inputs: &[Fp]) -> Option<[u8; 32]> {
+    let index = layout
+        .values
+        .iter()
+        .position(|value| *value == PublicValue::ImageCommitment)?;
+    public_inputs.get(index).map(commitment_from_field)
+}
+
+fn hash_leaf(commitment: &[u8; 32]) -> [u8; 32] {
+    keccak256([&[0u8][..], commitment].concat())
+}
+
+fn hash_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    keccak256([&[1u8][..], left, right].concat())
+}
+
+/// The levels of the Merkle tree over the commitments, from the hashed leaves to the root.
+fn merkle_levels(commitments: &[[u8; 32]]) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![commitments.iter().map(hash_leaf).collect::<Vec<_>>()];
+    while levels.last().unwrap().len() > 1 {
+        let level = levels
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|nodes| match nodes {
+                [left, right] => hash_node(left, right),
+                [node] => *node,
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(level);
+    }
+    levels
+}
+
+/// Computes the root of the Merkle tree over the commitments, see the module documentation.
+/// The root of an empty list is the hash of the empty string.
+pub fn merkle_root(commitments: &[[u8; 32]]) -> [u8; 32] {
+    match merkle_levels(commitments).last().unwrap().first() {
+        Some(root) => *root,
+        None => keccak256([]),
+    }
+}
+
+/// Shows that a commitment is part of a Merkle tree, see [`merkle_opening`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleOpening {
+    /// The index of the commitment.
+    pub index: usize,
+    /// The number of commitments in the tree.
+    pub num_leaves: usize,
+    /// The siblings on the path from the leaf to the root, bottom up.
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl MerkleOpening {
+    /// Computes the root of the tree, assuming that `commitment` is the opened leaf. Returns
+    /// `None` if the opening is malformed.
+    pub fn root(&self, commitment: &[u8; 32]) -> Option<[u8; 32]> {
+        if self.index >= self.num_leaves {
+            return None;
+        }
+        let mut node = hash_leaf(commitment);
+        let mut siblings = self.siblings.iter();
+        let (mut index, mut width) = (self.index, self.num_leaves);
+        while width > 1 {
+            if index % 2 == 1 {
+                node = hash_node(siblings.next()?, &node);
+            } else if index + 1 < width {
+                node = hash_node(&node, siblings.next()?);
+            }
+            index /= 2;
+            width = (width + 1) / 2;
+        }
+        siblings.next().is_none().then_some(node)
+    }
+
+    /// Whether `commitment` is the opened leaf of the tree with the given root.
+    pub fn verify(&self, commitment: &[u8; 32], root: &[u8; 32]) -> bool {
+        self.root(commitment) == Some(*root)
+    }
+}
+
+/// Computes the opening of the commitment at `index` in the Merkle tree over the commitments,
+/// or `None` if the index is out of bounds.
+pub fn merkle_opening(commitments: &[[u8; 32]], index: usize) -> Option<MerkleOpening> {
+    if index >= commitments.len() {
+        return None;
+    }
+    let levels = merkle_levels(commitments);
+    let mut siblings = vec![];
+    let mut node_index = index;
+    for level in &levels[..levels.len() - 1] {
+        if let Some(sibling) = level.get(node_index ^ 1) {
+            siblings.push(*sibling);
+        }
+        node_index /= 2;
+    }
+    Some(MerkleOpening {
+        index,
+        num_leaves: commitments.len(),
+        siblings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array2;
+
+    use super::{
+        commitment_from_field, commitment_to_field, image_commitment, merkle_opening, merkle_root,
+    };
+
+    #[test]
+    fn test_image_commitment() {
+        let image = Array2::from_shape_vec((2, 3), vec![0, 1, 2, 3, 4, 5]).unwrap();
+        let commitment = image_commitment(&image, None);
+        assert_eq!(commitment, image_commitment(&image.clone(), None));
+
+        // The shape is part of the commitment
+        let reshaped = image.clone().into_shape((3, 2)).unwrap();
+        assert_ne!(image_commitment(&reshaped, None), commitment);
+
+        // So is the salt
+        let salted = image_commitment(&image, Some(&[1; 32]));
+        assert_ne!(salted, commitment);
+        assert_ne!(salted, image_commitment(&image, Some(&[2; 32])));
+        assert_eq!(image_commitment(&image, Some(&[0; 32])), commitment);
+
+        let x = commitment_to_field(&commitment);
+        assert_eq!(commitment_from_field(&x), commitment);
+    }
+
+    #[test]
+    fn test_merkle_tree() {
+        let commitments: Vec<[u8; 32]> = (0..9u8).map(|i| [i; 32]).collect();
+        let mut roots = vec![merkle_root(&[])];
+        for n in 1..=commitments.len() {
+            let leaves = &commitments[..n];
+            let root = merkle_root(leaves);
+            assert!(!roots.contains(&root));
+            roots.push(root);
+
+            assert_eq!(merkle_opening(leaves, n), None);
+            for (index, commitment) in leaves.iter().enumerate() {
+                let opening = merkle_opening(leaves, index).unwrap();
+                assert!(opening.verify(commitment, &root));
+                assert!(!opening.verify(&[42; 32], &root));
+
+                let mut wrong_index = opening.clone();
+                wrong_index.index = (index + 1) % n;
+                if n > 1 {
+                    assert!(!wrong_index.verify(commitment, &root));
+                }
+
+                let mut extended = opening.clone();
+                extended.siblings.push([0; 32]);
+                assert_eq!(extended.root(commitment), None);
+
+                if !opening.siblings.is_empty() {
+                    let mut tampered = opening.clone();
+                    tampered.siblings[0][0] ^= 1;
+                    assert!(!tampered.verify(commitment, &root));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Conversion of arbitrary images into the grayscale arrays expected by [`crate::Wnn`].
+//!
+//! Images are decoded at 16 bits per channel, reduced to a single channel and then to 8 bits,
+//! according to the given [`ImageLoadOptions`]. This way, 16-bit and palette images are
+//! handled the same way as 8-bit grayscale or RGB images.
+
+use std::io::{BufRead, Seek};
+use std::path::Path;
+
+use image::{DynamicImage, ImageBuffer, ImageError, Pixel};
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+/// How to reduce an image to a single channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelPolicy {
+    /// Use the first (red) channel. This matches how the MNIST PNGs are read by the
+    /// training code, which stores the same value in all channels.
+    #[default]
+    FirstChannel,
+    /// Compute the luma (perceived brightness) of each pixel.
+    Luma,
+}
+
+/// How to reduce 16-bit samples to 8 bits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BitDepthPolicy {
+    /// Scale to the 8-bit range, rounding to the nearest value.
+    #[default]
+    Round,
+    /// Keep the most significant byte.
+    Truncate,
+}
+
+/// Options for converting images, see the [module docs](self).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageLoadOptions {
+    pub channel_policy: ChannelPolicy,
+    pub bit_depth_policy: BitDepthPolicy,
+}
+
+impl ImageLoadOptions {
+    pub fn with_channel_policy(mut self, channel_policy: ChannelPolicy) -> Self {
+        self.channel_policy = channel_policy;
+        self
+    }
+
+    pub fn with_bit_depth_policy(mut self, bit_depth_policy: BitDepthPolicy) -> Self {
+        self.bit_depth_policy = bit_depth_policy;
+        self
+    }
+}
+
+/// Loads an image from disk, converting it according to the given options.
+pub fn load_image_with(path: &Path, options: ImageLoadOptions) -> Result<Array2<u8>, ImageError> {
+    Ok(convert_image(&image::open(path)?, options))
+}
+
+/// Loads an image from an in-memory buffer, converting it according to the given options.
+pub fn load_image_from_bytes_with(
+    bytes: &[u8],
+    options: ImageLoadOptions,
+) -> Result<Array2<u8>, ImageError> {
+    Ok(convert_image(&image::load_from_memory(bytes)?, options))
+}
+
+/// Loads an image from a reader, converting it according to the given options.
+/// The image format is guessed from the content.
+pub fn load_image_from_reader_with(
+    reader: impl BufRead + Seek,
+    options: ImageLoadOptions,
+) -> Result<Array2<u8>, ImageError> {
+    let image = image::io::Reader::new(reader)
+        .with_guessed_format()?
+        .decode()?;
+    Ok(convert_image(&image, options))
+}
+
+/// Converts a decoded image into an array of shape `(height, width)`.
+pub fn convert_image(image: &DynamicImage, options: ImageLoadOptions) -> Array2<u8> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let samples: Vec<u16> = match options.channel_policy {
+        ChannelPolicy::FirstChannel => first_channel(&image.to_rgb16()),
+        ChannelPolicy::Luma => image.to_luma16().into_raw(),
+    };
+    let samples = samples
+        .into_iter()
+        .map(|sample| reduce_bit_depth(sample, options.bit_depth_policy))
+        .collect();
+
+    Array2::from_shape_vec((height, width), samples).expect("Error converting image to ndarray")
+}
+
+fn first_channel<P: Pixel<Subpixel = u16>>(image: &ImageBuffer<P, Vec<u16>>) -> Vec<u16> {
+    image.pixels().map(|pixel| pixel.channels()[0]).collect()
+}
+
+fn reduce_bit_depth(sample: u16, policy: BitDepthPolicy) -> u8 {
+    match policy {
+        BitDepthPolicy::Round => ((sample as u32 + 128) / 257) as u8,
+        BitDepthPolicy::Truncate => (sample >> 8) as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, ImageBuffer, Luma, Rgb};
+    use ndarray::array;
+
+    use super::{convert_image, BitDepthPolicy, ChannelPolicy, ImageLoadOptions};
+
+    #[test]
+    fn test_8_bit_images_are_unchanged() {
+        let image = DynamicImage::ImageLuma8(
+            ImageBuffer::from_raw(3, 2, vec![0, 1, 127, 128, 254, 255]).unwrap(),
+        );
+        for bit_depth_policy in [BitDepthPolicy::Round, BitDepthPolicy::Truncate] {
+            for channel_policy in [ChannelPolicy::FirstChannel, ChannelPolicy::Luma] {
+                let options = ImageLoadOptions {
+                    channel_policy,
+                    bit_depth_policy,
+                };
+                assert_eq!(
+                    convert_image(&image, options),
+                    array![[0, 1, 127], [128, 254, 255]]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_16_bit_image() {
+        let image = DynamicImage::ImageLuma16(ImageBuffer::from_pixel(1, 1, Luma([0x12ff])));
+
+        let round = ImageLoadOptions::default();
+        let truncate = round.with_bit_depth_policy(BitDepthPolicy::Truncate);
+        assert_eq!(convert_image(&image, round), array![[0x13]]);
+        assert_eq!(convert_image(&image, truncate), array![[0x12]]);
+    }
+
+    #[test]
+    fn test_channel_policy() {
+        let image = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(1, 1, Rgb([200, 0, 0])));
+
+        let first_channel = ImageLoadOptions::default();
+        let luma = first_channel.with_channel_policy(ChannelPolicy::Luma);
+        assert_eq!(convert_image(&image, first_channel), array![[200]]);
+        assert!(convert_image(&image, luma)[[0, 0]] < 100);
+    }
+}
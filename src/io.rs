@@ -1,180 +1,576 @@
-//! Utilities for loading images and WNNs from disk.
+//! Utilities for loading images and WNNs from disk or memory, and for reading and writing
+//! proving artifacts.
 
 use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write};
 use std::path::Path;
+use std::str::FromStr;
 
 use halo2_proofs::halo2curves::bn256::{Bn256, Fr, G1Affine};
 use halo2_proofs::plonk::{ProvingKey, VerifyingKey};
 use halo2_proofs::poly::commitment::Params;
 use halo2_proofs::poly::kzg::commitment::ParamsKZG;
-use halo2_proofs::SerdeFormat::RawBytes;
-use hdf5::{File as Hdf5File, Result as Hdf5Result};
+use halo2_proofs::SerdeFormat;
 use image::ImageError;
-use ndarray::{s, Array, Array2, Array3};
-use ndarray::{Ix1, Ix3};
+use ndarray::Array2;
 use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use zero_g_verifier::VerifierKey;
 
+use crate::artifact_store::ArtifactStore;
+use crate::error::ZeroGError;
 use crate::gadgets::wnn::WnnCircuitParams;
-use crate::gadgets::WnnCircuit;
+use crate::image_loading::{convert_image, load_image_from_reader_with, ImageLoadOptions};
+use crate::key_file::{
+    read_pk_and_params_from, read_pk_from, read_vk_and_params_from, read_vk_from, upgrade_key,
+    write_pk_to, write_vk_to, KeyHeader, KeyKind,
+};
+use crate::labels::LabelSource;
+use crate::model_file::{self, is_model_file, load_model_file, read_model_from};
 use crate::wnn::Wnn;
 
+#[cfg(feature = "hdf5")]
+pub use self::hdf5_model::{
+    load_float_thresholds, load_wnn, load_wnn_from_bytes, load_wnn_from_reader,
+    load_wnn_with_policy, write_wnn,
+};
+
 /// Loads a grayscale image from disk, returning the first channel.
-pub fn load_grayscale_image(img_path: &Path) -> Result<Array2<u8>, ImageError> {
-    let image = image::open(img_path)?.to_rgb8();
-    let array: Array3<u8> = Array::from_shape_vec(
-        (image.height() as usize, image.width() as usize, 3),
-        image.into_raw(),
-    )
-    .expect("Error converting image to ndarray");
-
-    Ok(array.slice_move(s![.., .., 0]))
-}
-
-/// Loads a [`Wnn`] from disk, from a file following [this format](https://github.com/zkp-gravity/BTHOWeN-0g/blob/master/output_format_spec.md).
-pub fn load_wnn(path: &Path) -> Hdf5Result<Wnn> {
-    let file = Hdf5File::open(path)?;
-
-    let num_classes = file.attr("num_classes")?.read_scalar::<i64>()? as usize;
-    let num_inputs = file.attr("num_inputs")?.read_scalar::<i64>()? as usize;
-    let bits_per_input = file.attr("bits_per_input")?.read_scalar::<i64>()? as usize;
-    let num_filter_inputs = file.attr("num_filter_inputs")?.read_scalar::<i64>()? as usize;
-    let num_filter_entries = file.attr("num_filter_entries")?.read_scalar::<i64>()? as usize;
-    let num_filter_hashes = file.attr("num_filter_hashes")?.read_scalar::<i64>()? as usize;
-    let p = file.attr("p")?.read_scalar::<i64>()? as u64;
-
-    let expected_shape = [
-        num_classes,
-        num_inputs * bits_per_input / num_filter_inputs,
-        num_filter_entries,
-    ];
-    let bloom_filters = file.dataset("bloom_filters")?;
-    let bloom_filters = bloom_filters.read::<bool, Ix3>()?;
-    assert_eq!(bloom_filters.shape(), expected_shape);
-
-    let width = (num_inputs as f32).sqrt() as usize;
-    let expected_shape = [width, width, bits_per_input];
-    let binarization_thresholds = file.dataset("binarization_thresholds")?;
-    let binarization_thresholds = binarization_thresholds.read::<f32, Ix3>()?;
-    assert_eq!(binarization_thresholds.shape(), expected_shape);
-
-    // Quantize binarization thresholds.
-    // This should make no difference to the accuracy of the model,
-    // because images are quantized to u8 anyway.
-    // Note that:
-    // - We use ceil(), because <u8> >= <f32> <==> <u8> >= <f32>.ceil() as u8
-    // - We clamp at 0, because intensities cannot be negative
-    // - We clamp at **256**, because intensities cannot be greater than 255
-    //   Note that thresholds set to 256 will never be reached!
-    //   Also note that for this reason, we can't use u8 to store the thresholds.
-    let binarization_thresholds = binarization_thresholds * 255.0;
-    let binarization_thresholds =
-        binarization_thresholds.map(|x| x.ceil().max(0.0).min(256.0) as u16);
-
-    let input_order = file.dataset("input_order")?;
-    let input_order = input_order.read::<u64, Ix1>()?;
-    let num_input_bits = num_inputs * bits_per_input;
-    assert_eq!(input_order.shape(), [num_input_bits]);
-
-    Ok(Wnn::new(
-        num_classes,
-        num_filter_entries,
-        num_filter_hashes,
-        num_filter_inputs,
-        p,
-        bloom_filters,
-        input_order,
-        binarization_thresholds,
+///
+/// See [`crate::image_loading`] for other conversion policies.
+pub fn load_grayscale_image(img_path: &Path) -> Result<Array2<u8>, ZeroGError> {
+    let image = image::open(img_path).map_err(|source| ZeroGError::Image {
+        path: img_path.to_path_buf(),
+        source,
+    })?;
+    Ok(convert_image(&image, ImageLoadOptions::default()))
+}
+
+/// Loads a grayscale image from an in-memory buffer (e.g. the contents of a PNG file),
+/// returning the first channel.
+pub fn load_image_from_bytes(bytes: &[u8]) -> Result<Array2<u8>, ImageError> {
+    Ok(convert_image(
+        &image::load_from_memory(bytes)?,
+        ImageLoadOptions::default(),
     ))
 }
 
+/// Loads a grayscale image from a reader, returning the first channel.
+/// The image format is guessed from the content.
+pub fn load_image_from_reader(reader: impl BufRead + Seek) -> Result<Array2<u8>, ImageError> {
+    load_image_from_reader_with(reader, ImageLoadOptions::default())
+}
+
+/// Loads a [`Wnn`] from disk, either from a `.zgm` file (see [`crate::model_file`])
+/// or, if the `hdf5` feature is enabled, from an HDF5 file (see [`load_wnn`]).
+#[instrument(skip_all, fields(path = %path.display()))]
+pub fn load_model(path: &Path) -> Result<Wnn, ZeroGError> {
+    let is_model_file = is_model_file(path).map_err(|source| ZeroGError::Io {
+        action: "open",
+        path: path.to_path_buf(),
+        source,
+    })?;
+    if is_model_file {
+        load_model_file(path).map_err(|source| ZeroGError::Format {
+            path: path.to_path_buf(),
+            format: "zero_g model file",
+            source: source.into(),
+        })
+    } else {
+        load_hdf5_model(path)
+    }
+}
+
+/// Loads a [`Wnn`] from the contents of a `.zgm` or (if the `hdf5` feature is enabled)
+/// an HDF5 file. HDF5 files are copied to a temporary file first (see `load_wnn_from_bytes`),
+/// so builds without a file system (e.g. wasm) only support `.zgm` files.
+pub fn load_model_from_bytes(bytes: &[u8]) -> Result<Wnn, ZeroGError> {
+    if bytes.starts_with(&model_file::MAGIC) {
+        read_model_from(&mut &bytes[..]).map_err(|e| ZeroGError::Model(e.to_string()))
+    } else {
+        load_hdf5_model_from_bytes(bytes)
+    }
+}
+
+#[cfg(feature = "hdf5")]
+fn load_hdf5_model(path: &Path) -> Result<Wnn, ZeroGError> {
+    load_wnn(path).map_err(|source| ZeroGError::Format {
+        path: path.to_path_buf(),
+        format: "HDF5 model",
+        // Keep only the message, as HDF5 errors hold handles into the library
+        source: source.to_string().into(),
+    })
+}
+
+#[cfg(feature = "hdf5")]
+fn load_hdf5_model_from_bytes(bytes: &[u8]) -> Result<Wnn, ZeroGError> {
+    load_wnn_from_bytes(bytes).map_err(|e| ZeroGError::Model(e.to_string()))
+}
+
+#[cfg(not(feature = "hdf5"))]
+fn load_hdf5_model(path: &Path) -> Result<Wnn, ZeroGError> {
+    Err(ZeroGError::Model(format!(
+        "{} is not a .zgm model file, and HDF5 support is disabled",
+        path.display()
+    )))
+}
+
+#[cfg(not(feature = "hdf5"))]
+fn load_hdf5_model_from_bytes(_bytes: &[u8]) -> Result<Wnn, ZeroGError> {
+    Err(ZeroGError::Model(
+        "Not a .zgm model file, and HDF5 support is disabled".to_string(),
+    ))
+}
+
+/// Streams an artifact from a store, decompressing it if needed, and parses it with `f`.
+fn read_from_store<T>(
+    store: &dyn ArtifactStore,
+    key: &str,
+    format: &'static str,
+    f: impl FnOnce(&mut Box<dyn Read + '_>) -> io::Result<T>,
+) -> Result<T, ZeroGError> {
+    let to_error = |source| ZeroGError::Artifact {
+        key: key.to_string(),
+        format,
+        source,
+    };
+    let reader = store.reader(key).map_err(to_error)?;
+    let mut reader = decompressing_reader(BufReader::new(reader)).map_err(to_error)?;
+    f(&mut reader).map_err(to_error)
+}
+
+/// Loads a [`Wnn`] from an artifact store (see [`load_model_from_bytes`]).
+pub fn load_model_from_store(store: &dyn ArtifactStore, key: &str) -> Result<Wnn, ZeroGError> {
+    let bytes = store.get(key).map_err(|source| ZeroGError::Artifact {
+        key: key.to_string(),
+        format: "model",
+        source,
+    })?;
+    load_model_from_bytes(&bytes)
+}
+
+/// Read SRS from an artifact store.
+pub fn read_srs_from_store(
+    store: &dyn ArtifactStore,
+    key: &str,
+) -> Result<ParamsKZG<Bn256>, ZeroGError> {
+    read_from_store(store, key, "SRS", |reader| ParamsKZG::read(reader))
+}
+
+/// Read proving key from an artifact store.
+pub fn read_pk_from_store(
+    store: &dyn ArtifactStore,
+    key: &str,
+    circuit_params: WnnCircuitParams,
+) -> Result<ProvingKey<G1Affine>, ZeroGError> {
+    read_from_store(store, key, "proving key", |reader| {
+        read_pk_from(
+            &mut BufReader::new(reader),
+            circuit_params,
+            KeyFormat::default(),
+        )
+    })
+}
+
+/// Read verification key from an artifact store.
+pub fn read_vk_from_store(
+    store: &dyn ArtifactStore,
+    key: &str,
+    circuit_params: WnnCircuitParams,
+) -> Result<VerifyingKey<G1Affine>, ZeroGError> {
+    read_from_store(store, key, "verification key", |reader| {
+        read_vk_from(&mut BufReader::new(reader), circuit_params)
+    })
+}
+
 /// Given a path like `data/MNIST/png/0000_7.png`, read the correct class (in this case 7).
+///
+/// See [`LabelSource`] for other ways to label images.
 pub fn parse_png_file(img_path: &Path) -> Option<usize> {
-    match img_path.extension() {
-        Some(extension) => {
-            if extension == "png" {
-                Some(
-                    img_path
-                        .file_stem()
-                        .unwrap()
-                        .to_str()
-                        .unwrap()
-                        .chars()
-                        .last()
-                        .unwrap()
-                        .to_digit(10)
-                        .unwrap()
-                        .try_into()
-                        .unwrap(),
-                )
-            } else {
-                None
-            }
-        }
-        _ => None,
-    }
-}
-
-fn with_writer<E>(path: &Path, f: impl FnOnce(&mut BufWriter<File>) -> Result<(), E>)
+    if img_path.extension()? == "png" {
+        LabelSource::FileName.label(img_path)
+    } else {
+        None
+    }
+}
+
+/// The magic bytes at the start of every zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The zstd compression level used when writing `.zst` files.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Writes to a file, compressing with zstd if requested.
+enum ArtifactWriter {
+    Plain(BufWriter<File>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl ArtifactWriter {
+    /// Creates a writer for the given path, compressing if it has the `.zst` extension.
+    fn create(path: &Path) -> io::Result<Self> {
+        let writer = BufWriter::new(File::create(path)?);
+        if path
+            .extension()
+            .map_or(false, |extension| extension == "zst")
+        {
+            Ok(Self::Zstd(zstd::Encoder::new(writer, ZSTD_LEVEL)?))
+        } else {
+            Ok(Self::Plain(writer))
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Plain(mut writer) => writer.flush(),
+            Self::Zstd(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for ArtifactWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(writer) => writer.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(writer) => writer.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Wraps the reader in a zstd decoder if the data starts with a zstd frame.
+fn decompressing_reader<'a>(mut reader: impl BufRead + 'a) -> io::Result<Box<dyn Read + 'a>> {
+    if reader.fill_buf()?.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(zstd::Decoder::with_buffer(reader)?))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Writes to the given file. If the path has the `.zst` extension, the content is
+/// compressed with zstd.
+fn with_writer<E>(
+    path: &Path,
+    f: impl FnOnce(&mut ArtifactWriter) -> Result<(), E>,
+) -> Result<(), ZeroGError>
 where
-    E: fmt::Debug,
+    E: Into<io::Error>,
 {
-    let file = File::create(path).expect("Unable to create file");
-    let mut writer = BufWriter::new(file);
-    f(&mut writer).expect("Unable to write to file");
-    writer.flush().expect("Unable to flush file");
+    let to_error = |action| {
+        move |source| ZeroGError::Io {
+            action,
+            path: path.to_path_buf(),
+            source,
+        }
+    };
+    let mut writer = ArtifactWriter::create(path).map_err(to_error("create"))?;
+    f(&mut writer)
+        .map_err(Into::into)
+        .map_err(to_error("write"))?;
+    writer.finish().map_err(to_error("write"))
 }
 
-fn with_reader<T, E>(path: &Path, f: impl FnOnce(&mut BufReader<File>) -> Result<T, E>) -> T
+/// Reads from the given file, decompressing it if it is compressed with zstd
+/// (regardless of the file extension).
+fn with_reader<T, E>(
+    path: &Path,
+    format: &'static str,
+    f: impl FnOnce(&mut Box<dyn Read>) -> Result<T, E>,
+) -> Result<T, ZeroGError>
 where
-    E: fmt::Debug,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
 {
-    let file = File::open(path).expect("Unable to open file");
-    let mut reader = BufReader::new(file);
-    f(&mut reader).expect("Unable to read from file")
+    let file = File::open(path).map_err(|source| ZeroGError::Io {
+        action: "open",
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut reader =
+        decompressing_reader(BufReader::new(file)).map_err(|source| ZeroGError::Io {
+            action: "read",
+            path: path.to_path_buf(),
+            source,
+        })?;
+    f(&mut reader).map_err(|source| ZeroGError::Format {
+        path: path.to_path_buf(),
+        format,
+        source: source.into(),
+    })
+}
+
+pub(crate) fn write_length_prefixed(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+pub(crate) fn read_length_prefixed(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let length = u32::from_le_bytes(read_array(reader)?) as usize;
+    let mut bytes = vec![0u8; length];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
 }
 
-/// Write SRS to file.
-pub fn write_srs(srs: &ParamsKZG<Bn256>, path: &Path) {
-    with_writer(path, |writer| srs.write(writer));
+pub(crate) fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
 }
 
-/// Read SRS from file.
-pub fn read_srs(path: &Path) -> ParamsKZG<Bn256> {
-    with_reader(path, |reader| ParamsKZG::read(reader))
+pub(crate) fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Write SRS to file. If the path ends with `.zst`, the SRS is compressed with zstd.
+pub fn write_srs(srs: &ParamsKZG<Bn256>, path: &Path) -> Result<(), ZeroGError> {
+    with_writer(path, |writer| srs.write(writer))
+}
+
+/// Read SRS from file, which may be compressed with zstd.
+#[instrument(skip_all, fields(path = %path.display()))]
+pub fn read_srs(path: &Path) -> Result<ParamsKZG<Bn256>, ZeroGError> {
+    with_reader(path, "SRS", |reader| ParamsKZG::read(reader))
 }
 
 /// Write the circuit parameters to file.
-pub fn write_circuit_params(circuit_params: &WnnCircuitParams, path: &Path) {
-    with_writer(path, |writer| serde_json::to_writer(writer, circuit_params));
+pub fn write_circuit_params(
+    circuit_params: &WnnCircuitParams,
+    path: &Path,
+) -> Result<(), ZeroGError> {
+    with_writer(path, |writer| serde_json::to_writer(writer, circuit_params))
+}
+
+/// Write a verifier key for the `zero_g_verifier` crate (see
+/// [`crate::verifier_bundle::VerifierBundle::verifier_key`]) to file, as JSON.
+pub fn write_verifier_key(verifier_key: &VerifierKey, path: &Path) -> Result<(), ZeroGError> {
+    with_writer(path, |writer| serde_json::to_writer(writer, verifier_key))
 }
 
 /// Read the circuit parameters from file.
-pub fn read_circuit_params(path: &Path) -> WnnCircuitParams {
-    with_reader(path, |reader| serde_json::from_reader(reader))
+pub fn read_circuit_params(path: &Path) -> Result<WnnCircuitParams, ZeroGError> {
+    with_reader(path, "circuit params (JSON)", |reader| {
+        serde_json::from_reader(reader)
+    })
+}
+
+/// The serialization of a proving key, trading file size against loading time and safety.
+///
+/// [`KeyFormat::RawBytes`] and [`KeyFormat::RawBytesUnchecked`] write the same bytes and only
+/// differ in the checks done when reading, so a key written in either format can be read in
+/// either format. Keys written as [`KeyFormat::Processed`] can only be read as such.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyFormat {
+    /// Field elements in their canonical form and compressed curve points. The smallest files,
+    /// but the slowest to load, as all points are decompressed (and checked) and all field
+    /// elements are converted to Montgomery form.
+    Processed,
+    /// Field elements in Montgomery form and uncompressed curve points, checked to be in
+    /// range and on the curve when reading.
+    #[default]
+    RawBytes,
+    /// Like [`KeyFormat::RawBytes`], but nothing is checked when reading. The fastest to load,
+    /// which cuts the cold start of provers, but a corrupted or malicious key is not detected
+    /// and leads to invalid proofs or panics. Only use this for keys from a trusted source,
+    /// e.g. written by the same operator.
+    RawBytesUnchecked,
+}
+
+impl KeyFormat {
+    pub fn serde_format(self) -> SerdeFormat {
+        match self {
+            Self::Processed => SerdeFormat::Processed,
+            Self::RawBytes => SerdeFormat::RawBytes,
+            Self::RawBytesUnchecked => SerdeFormat::RawBytesUnchecked,
+        }
+    }
+}
+
+impl fmt::Display for KeyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Processed => "processed",
+            Self::RawBytes => "raw-bytes",
+            Self::RawBytesUnchecked => "raw-bytes-unchecked",
+        })
+    }
+}
+
+impl FromStr for KeyFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "processed" => Ok(Self::Processed),
+            "raw-bytes" => Ok(Self::RawBytes),
+            "raw-bytes-unchecked" => Ok(Self::RawBytesUnchecked),
+            _ => Err(format!(
+                "Unknown key format {s:?}, expected processed, raw-bytes or raw-bytes-unchecked"
+            )),
+        }
+    }
+}
+
+/// Write proving key and verification key to file, with a header (see [`crate::key_file`]).
+/// Keys written to paths ending with `.zst` are compressed with zstd.
+pub fn write_keys(
+    pk: &ProvingKey<G1Affine>,
+    circuit_params: &WnnCircuitParams,
+    pk_path: &Path,
+    vk_path: &Path,
+) -> Result<(), ZeroGError> {
+    write_keys_with_format(pk, circuit_params, pk_path, vk_path, KeyFormat::default())
+}
+
+/// Like [`write_keys`], but writes the proving key in the given format.
+///
+/// The verifying key is small and read by verifiers that don't know the format, so it is
+/// always written as [`KeyFormat::RawBytes`].
+pub fn write_keys_with_format(
+    pk: &ProvingKey<G1Affine>,
+    circuit_params: &WnnCircuitParams,
+    pk_path: &Path,
+    vk_path: &Path,
+    pk_format: KeyFormat,
+) -> Result<(), ZeroGError> {
+    with_writer(pk_path, |writer| {
+        write_pk_to(writer, pk, circuit_params, pk_format)
+    })?;
+    with_writer(vk_path, |writer| {
+        write_vk_to(writer, pk.get_vk(), circuit_params)
+    })
+}
+
+/// Read proving key from file, which may be compressed with zstd.
+///
+/// Returns an error if the header (see [`crate::key_file`]) shows that the key was generated
+/// for different circuit params or by an incompatible version of this crate.
+pub fn read_pk(
+    path: &Path,
+    circuit_params: WnnCircuitParams,
+) -> Result<ProvingKey<G1Affine>, ZeroGError> {
+    read_pk_with_format(path, circuit_params, KeyFormat::default())
 }
 
-/// Write proving key and verification key to file.
-pub fn write_keys(pk: &ProvingKey<G1Affine>, pk_path: &Path, vk_path: &Path) {
-    with_writer(pk_path, |writer| pk.write(writer, RawBytes));
-    with_writer(vk_path, |writer| pk.get_vk().write(writer, RawBytes));
+/// Like [`read_pk`], for keys written in the given format (see [`KeyFormat`] for the
+/// tradeoffs).
+#[instrument(skip_all, fields(path = %path.display(), %format))]
+pub fn read_pk_with_format(
+    path: &Path,
+    circuit_params: WnnCircuitParams,
+    format: KeyFormat,
+) -> Result<ProvingKey<G1Affine>, ZeroGError> {
+    with_reader(path, "proving key", |reader| {
+        read_pk_from(&mut BufReader::new(reader), circuit_params, format)
+    })
+}
+
+/// Read verification key from file, which may be compressed with zstd. Validates the header
+/// like [`read_pk`].
+pub fn read_vk(
+    path: &Path,
+    circuit_params: WnnCircuitParams,
+) -> Result<VerifyingKey<G1Affine>, ZeroGError> {
+    with_reader(path, "verification key", |reader| {
+        read_vk_from(&mut BufReader::new(reader), circuit_params)
+    })
+}
+
+/// Reads a proving key written in the given format, together with the circuit params embedded
+/// in its header (see [`crate::key_file`]). Legacy files without embedded params need to be
+/// read with [`read_pk_with_format`] (or upgraded with [`upgrade_key_file`]).
+#[instrument(skip_all, fields(path = %path.display(), %format))]
+pub fn read_pk_and_params(
+    path: &Path,
+    format: KeyFormat,
+) -> Result<(ProvingKey<G1Affine>, WnnCircuitParams), ZeroGError> {
+    with_reader(path, "proving key", |reader| {
+        read_pk_and_params_from(&mut BufReader::new(reader), format)
+    })
 }
 
-/// Read proving key from file.
-pub fn read_pk(path: &Path, circuit_params: WnnCircuitParams) -> ProvingKey<G1Affine> {
-    with_reader(path, |reader| {
-        ProvingKey::read::<_, WnnCircuit<_>>(reader, RawBytes, circuit_params)
+/// Like [`read_pk_and_params`], for verifying keys.
+pub fn read_vk_and_params(
+    path: &Path,
+) -> Result<(VerifyingKey<G1Affine>, WnnCircuitParams), ZeroGError> {
+    with_reader(path, "verification key", |reader| {
+        read_vk_and_params_from(&mut BufReader::new(reader))
     })
 }
 
-/// Read verification key from file.
-pub fn read_vk(path: &Path, circuit_params: WnnCircuitParams) -> VerifyingKey<G1Affine> {
-    with_reader(path, |reader| {
-        VerifyingKey::read::<_, WnnCircuit<_>>(reader, RawBytes, circuit_params)
+/// Reads the header of a key file, or `None` for legacy files without a header.
+pub fn read_key_header(path: &Path) -> Result<Option<KeyHeader>, ZeroGError> {
+    with_reader(path, "key", |reader| {
+        KeyHeader::read_from(&mut BufReader::new(reader))
     })
 }
 
+/// Rewrites a key file with a header, e.g. a legacy file without one (see
+/// [`crate::key_file::upgrade_key`]). Returns the new header.
+pub fn upgrade_key_file(
+    path: &Path,
+    kind: KeyKind,
+    circuit_params: WnnCircuitParams,
+) -> Result<KeyHeader, ZeroGError> {
+    let bytes = with_reader(path, "key", |reader| {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).map(|_| bytes)
+    })?;
+    // Upgrade in memory first, so that the file is left untouched if the key is invalid
+    let mut upgraded = vec![];
+    let header = upgrade_key(&mut bytes.as_slice(), &mut upgraded, kind, circuit_params).map_err(
+        |source| ZeroGError::Format {
+            path: path.to_path_buf(),
+            format: "key",
+            source: Box::new(source),
+        },
+    )?;
+    with_writer(path, |writer| writer.write_all(&upgraded))?;
+    Ok(header)
+}
+
+/// Read SRS from memory, which may be compressed with zstd.
+pub fn read_srs_from_bytes(bytes: &[u8]) -> io::Result<ParamsKZG<Bn256>> {
+    ParamsKZG::read(&mut decompressing_reader(bytes)?)
+}
+
+/// Read proving key from memory, which may be compressed with zstd.
+pub fn read_pk_from_bytes(
+    bytes: &[u8],
+    circuit_params: WnnCircuitParams,
+) -> io::Result<ProvingKey<G1Affine>> {
+    read_pk_from(
+        &mut BufReader::new(decompressing_reader(bytes)?),
+        circuit_params,
+        KeyFormat::default(),
+    )
+}
+
+/// Read verification key from memory, which may be compressed with zstd, together with the
+/// circuit params embedded in its header (see [`read_vk_and_params`]).
+pub fn read_vk_and_params_from_bytes(
+    bytes: &[u8],
+) -> io::Result<(VerifyingKey<G1Affine>, WnnCircuitParams)> {
+    read_vk_and_params_from(&mut BufReader::new(decompressing_reader(bytes)?))
+}
+
+/// Read verification key from memory, which may be compressed with zstd.
+pub fn read_vk_from_bytes(
+    bytes: &[u8],
+    circuit_params: WnnCircuitParams,
+) -> io::Result<VerifyingKey<G1Affine>> {
+    read_vk_from(
+        &mut BufReader::new(decompressing_reader(bytes)?),
+        circuit_params,
+    )
+}
+
 /// Wraps the circuit's output and proof, impelements (de)serialization.
 #[derive(Serialize, Deserialize)]
 pub struct ProofWithOutput {
@@ -196,12 +592,377 @@ impl From<ProofWithOutput> for (Vec<u8>, Vec<Fr>) {
 
 impl ProofWithOutput {
     /// Write the proof with output to file.
-    pub fn write(&self, path: &Path) {
-        with_writer(path, |writer| serde_json::to_writer(writer, self));
+    pub fn write(&self, path: &Path) -> Result<(), ZeroGError> {
+        with_writer(path, |writer| serde_json::to_writer(writer, self))
     }
 
     /// Read the proof with output from file.
-    pub fn read(path: &Path) -> Self {
-        with_reader(path, |reader| serde_json::from_reader(reader))
+    pub fn read(path: &Path) -> Result<Self, ZeroGError> {
+        with_reader(path, "proof (JSON)", |reader| {
+            serde_json::from_reader(reader)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use crate::gadgets::wnn::WnnCircuitParams;
+
+    use super::{read_circuit_params, write_circuit_params, KeyFormat, ZSTD_MAGIC};
+
+    #[test]
+    fn test_zstd_roundtrip() {
+        let circuit_params = WnnCircuitParams {
+            p: 2097143,
+            l: 20,
+            n_hashes: 2,
+            bits_per_hash: 10,
+            bits_per_filter: 28,
+            n_classes: 10,
+            min_blinding_factors: 0,
+            num_instance_columns: 1,
+            class_lookup: false,
+            window_num_bits: 8,
+            chaining: false,
+            multi_label: false,
+            regression: false,
+            tabular: false,
+            occlusion_num_pixels: 0,
+            robustness: false,
+            image_commitment: false,
+        };
+        for extension in ["json", "json.zst"] {
+            let path = env::temp_dir().join(format!(
+                "zero_g_circuit_params_{}.{extension}",
+                process::id()
+            ));
+            write_circuit_params(&circuit_params, &path).unwrap();
+            let compressed = fs::read(&path).unwrap().starts_with(&ZSTD_MAGIC);
+            let read = read_circuit_params(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(compressed, extension.ends_with(".zst"));
+            assert_eq!(read, circuit_params);
+        }
+    }
+
+    #[test]
+    fn test_key_format_from_str() {
+        for format in [
+            KeyFormat::Processed,
+            KeyFormat::RawBytes,
+            KeyFormat::RawBytesUnchecked,
+        ] {
+            assert_eq!(format.to_string().parse::<KeyFormat>(), Ok(format));
+        }
+        assert!("raw".parse::<KeyFormat>().is_err());
+    }
+}
+
+#[cfg(feature = "hdf5")]
+mod hdf5_model {
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::{
+        env, fs,
+        io::{self, Read},
+        process,
+    };
+
+    use hdf5::{Dataset, Error as Hdf5Error, File as Hdf5File, H5Type, Result as Hdf5Result};
+    use ndarray::{s, Array3, Ix1, Ix3};
+
+    use crate::packed_bloom_filters::PackedBloomFilters;
+    use crate::quantization::QuantizationPolicy;
+    use crate::wnn::Wnn;
+
+    /// Loads a [`Wnn`] from disk, from a file following [this format](https://github.com/zkp-gravity/BTHOWeN-0g/blob/master/output_format_spec.md).
+    ///
+    /// The binarization thresholds are quantized with the default [`QuantizationPolicy`].
+    pub fn load_wnn(path: &Path) -> Hdf5Result<Wnn> {
+        load_wnn_with_policy(path, &QuantizationPolicy::default())
+    }
+
+    /// Like [`load_wnn`], but quantizes the binarization thresholds with the given policy,
+    /// which is recorded in the model (see [`Wnn::quantization_policy`]).
+    pub fn load_wnn_with_policy(path: &Path, policy: &QuantizationPolicy) -> Hdf5Result<Wnn> {
+        read_wnn_from_hdf5(&Hdf5File::open(path)?, policy)
+    }
+
+    /// Loads the binarization thresholds of an HDF5 model before quantization, as produced by
+    /// training (see [`Wnn::predict_with_float_thresholds`]).
+    pub fn load_float_thresholds(path: &Path) -> Hdf5Result<Array3<f32>> {
+        read_binarization_thresholds(&Hdf5File::open(path)?)
+    }
+
+    /// Loads a [`Wnn`] from an in-memory buffer containing an HDF5 file (see [`load_wnn`]).
+    ///
+    /// The HDF5 bindings can only open files, so the buffer is written to a temporary file
+    /// first. Targets without a file system (e.g. wasm) can't load HDF5 models and should use
+    /// model files instead (see [`crate::model_file`] and `zero_g convert-model`).
+    pub fn load_wnn_from_bytes(bytes: &[u8]) -> Hdf5Result<Wnn> {
+        load_wnn_from_reader(bytes)
+    }
+
+    /// Loads a [`Wnn`] from a reader returning the contents of an HDF5 file (see [`load_wnn`]).
+    ///
+    /// Like [`load_wnn_from_bytes`], the contents are copied to a temporary file, without
+    /// buffering them in memory.
+    pub fn load_wnn_from_reader(mut reader: impl Read) -> Hdf5Result<Wnn> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = env::temp_dir().join(format!(
+            "zero_g_model_{}_{}.hdf5",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let wnn = fs::File::create(&path)
+            .and_then(|mut file| io::copy(&mut reader, &mut file))
+            .map_err(|e| Hdf5Error::from(e.to_string()))
+            .and_then(|_| load_wnn(&path));
+        // Ignore errors when cleaning up, the result is valid anyway.
+        let _ = fs::remove_file(&path);
+        wnn
+    }
+
+    /// Writes a [`Wnn`] to disk, following the same format as [`load_wnn`].
+    ///
+    /// Because [`Wnn`] only stores quantized binarization thresholds, the exported
+    /// thresholds are not the ones originally produced by training. Instead, each
+    /// quantized threshold `t` is written as a value that is quantized back to `t` when
+    /// loading with the model's [`QuantizationPolicy`] (e.g. `(t - 0.5) / 255` for the
+    /// default), and leads to the same predictions.
+    pub fn write_wnn(wnn: &Wnn, path: &Path) -> Hdf5Result<()> {
+        // The HDF5 format describes the inputs as square images
+        if wnn.is_tabular() {
+            return Err("Tabular models can only be written as model files".into());
+        }
+        let file = Hdf5File::create(path)?;
+
+        let (width, height) = wnn.img_shape();
+        let bits_per_input = wnn.binarization_thresholds.shape()[2];
+        for (name, value) in [
+            ("num_classes", wnn.num_classes as i64),
+            ("num_inputs", (width * height) as i64),
+            ("bits_per_input", bits_per_input as i64),
+            ("num_filter_inputs", wnn.num_filter_inputs as i64),
+            ("num_filter_entries", wnn.num_filter_entries as i64),
+            ("num_filter_hashes", wnn.num_filter_hashes as i64),
+            ("p", wnn.p as i64),
+        ] {
+            file.new_attr::<i64>()
+                .shape(())
+                .create(name)?
+                .write_scalar(&value)?;
+        }
+
+        // Write bloom filters one class at a time, so that the unpacked tensor is
+        // never materialized.
+        let shape = wnn.bloom_filters.shape();
+        let bloom_filters = file
+            .new_dataset::<bool>()
+            .shape(shape)
+            .create("bloom_filters")?;
+        for class in 0..shape[0] {
+            let slab = Array3::from_shape_vec(
+                (1, shape[1], shape[2]),
+                (0..shape[1])
+                    .flat_map(|filter| wnn.bloom_filters.filter(class, filter))
+                    .collect(),
+            )
+            .unwrap();
+            bloom_filters.write_slice(&slab, s![class..class + 1, .., ..])?;
+        }
+
+        file.new_dataset_builder()
+            .with_data(&wnn.input_permutation)
+            .create("input_order")?;
+
+        let policy = wnn.quantization_policy().unwrap_or_default();
+        let binarization_thresholds = wnn.binarization_thresholds.map(|t| policy.dequantize(*t));
+        file.new_dataset_builder()
+            .with_data(&binarization_thresholds)
+            .create("binarization_thresholds")?;
+
+        if wnn.is_regression() {
+            file.new_attr::<i64>()
+                .shape(())
+                .create("regression")?
+                .write_scalar(&1)?;
+        }
+        if let Some(label_thresholds) = wnn.label_thresholds() {
+            file.new_dataset_builder()
+                .with_data(label_thresholds)
+                .create("label_thresholds")?;
+        }
+
+        Ok(())
+    }
+
+    fn read_wnn_from_hdf5(file: &Hdf5File, policy: &QuantizationPolicy) -> Hdf5Result<Wnn> {
+        let num_classes = file.attr("num_classes")?.read_scalar::<i64>()? as usize;
+        let num_inputs = file.attr("num_inputs")?.read_scalar::<i64>()? as usize;
+        let bits_per_input = file.attr("bits_per_input")?.read_scalar::<i64>()? as usize;
+        let num_filter_inputs = file.attr("num_filter_inputs")?.read_scalar::<i64>()? as usize;
+        let num_filter_entries = file.attr("num_filter_entries")?.read_scalar::<i64>()? as usize;
+        let num_filter_hashes = file.attr("num_filter_hashes")?.read_scalar::<i64>()? as usize;
+        let p = file.attr("p")?.read_scalar::<i64>()? as u64;
+
+        let bloom_filters = read_bloom_filters(file)?;
+
+        let width = (num_inputs as f32).sqrt() as usize;
+        let expected_shape = [width, width, bits_per_input];
+        let binarization_thresholds = read_binarization_thresholds(file)?;
+        if binarization_thresholds.shape() != expected_shape {
+            return Err(format!(
+                "Expected binarization_thresholds to have shape {expected_shape:?}, got {:?}",
+                binarization_thresholds.shape()
+            )
+            .into());
+        }
+
+        // Quantize binarization thresholds.
+        // With the default policy, this makes no difference to the accuracy of the model,
+        // because images are quantized to u8 anyway (see the `quantization` module).
+        let binarization_thresholds = binarization_thresholds.map(|x| policy.quantize(*x));
+
+        let input_order = file.dataset("input_order")?;
+        let input_order = input_order
+            .read::<u64, Ix1>()
+            .map_err(|e| with_dataset_context(&input_order, "input_order", e))?;
+
+        let mut wnn = Wnn::new(
+            num_classes,
+            num_filter_entries,
+            num_filter_hashes,
+            num_filter_inputs,
+            p,
+            bloom_filters,
+            input_order,
+            binarization_thresholds,
+        )
+        .with_quantization_policy(*policy);
+        // Only present for regression models
+        if file.attr_names()?.iter().any(|name| name == "regression") {
+            wnn = wnn.with_regression(file.attr("regression")?.read_scalar::<i64>()? != 0);
+        }
+        // Only present for multi-label models
+        if file.link_exists("label_thresholds") {
+            let label_thresholds = file.dataset("label_thresholds")?;
+            let label_thresholds = label_thresholds
+                .read::<u64, Ix1>()
+                .map_err(|e| with_dataset_context(&label_thresholds, "label_thresholds", e))?;
+            wnn = wnn.with_label_thresholds(label_thresholds);
+        }
+        wnn.validate().map_err(|e| e.to_string())?;
+        Ok(wnn)
+    }
+
+    /// Reads a 3D dataset in slabs along the first axis, passing each slab to `f`.
+    /// Returns the shape of the dataset.
+    ///
+    /// This works for contiguous as well as chunked (and possibly compressed) datasets.
+    /// Contiguous datasets are read one index of the first axis at a time. For chunked
+    /// datasets, slabs are aligned with the chunks, so that each chunk is only decoded once.
+    /// Only one slab is held in memory at a time, but note that a slab spans the full second
+    /// and third axes, and for chunked datasets as many indices of the first axis as a chunk
+    /// (i.e. the whole tensor if it was written as a single chunk). Whether the total memory
+    /// is bounded depends on `f`: [`read_bloom_filters`] packs each slab, while
+    /// [`read_binarization_thresholds`] collects all values.
+    fn read_3d_in_slabs<T: H5Type>(
+        file: &Hdf5File,
+        name: &str,
+        mut f: impl FnMut(Array3<T>),
+    ) -> Hdf5Result<[usize; 3]> {
+        let dataset = file.dataset(name)?;
+        let shape = dataset.shape();
+        if shape.len() != 3 {
+            return Err(
+                format!("Expected {name} to have 3 dimensions, got shape {shape:?}").into(),
+            );
+        }
+
+        let slab_size = dataset.chunk().map_or(1, |chunk| chunk[0].max(1));
+
+        for start in (0..shape[0]).step_by(slab_size) {
+            let end = (start + slab_size).min(shape[0]);
+            let slab = dataset
+                .read_slice::<T, _, Ix3>(s![start..end, .., ..])
+                .map_err(|e| with_dataset_context(&dataset, name, e))?;
+            f(slab);
+        }
+
+        Ok([shape[0], shape[1], shape[2]])
+    }
+
+    /// Reads the bloom filters slab by slab, packing them on the fly.
+    fn read_bloom_filters(file: &Hdf5File) -> Hdf5Result<PackedBloomFilters> {
+        let shape = file.dataset("bloom_filters")?.shape();
+        let mut bloom_filters =
+            PackedBloomFilters::with_shape(shape.as_slice().try_into().map_err(|_| {
+                format!("Expected bloom_filters to have 3 dimensions, got shape {shape:?}")
+            })?);
+        read_3d_in_slabs::<bool>(file, "bloom_filters", |slab| {
+            bloom_filters.extend(slab.iter().copied())
+        })?;
+        Ok(bloom_filters)
+    }
+
+    fn read_binarization_thresholds(file: &Hdf5File) -> Hdf5Result<Array3<f32>> {
+        let mut values = vec![];
+        let shape = read_3d_in_slabs::<f32>(file, "binarization_thresholds", |slab| {
+            values.extend(slab.iter().copied())
+        })?;
+        Ok(Array3::from_shape_vec(shape, values).unwrap())
+    }
+
+    /// Adds the dataset name and its filter pipeline to a read error, which is
+    /// usually the culprit if the HDF5 library was built without support for a filter.
+    fn with_dataset_context(dataset: &Dataset, name: &str, error: Hdf5Error) -> Hdf5Error {
+        format!(
+            "Error reading dataset {name} (chunks: {:?}, filters: {:?}): {error}",
+            dataset.chunk(),
+            dataset.filters()
+        )
+        .into()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{env, fs, path::Path, process};
+
+        use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+        use crate::load_grayscale_image;
+
+        use super::{load_wnn, load_wnn_from_bytes, load_wnn_from_reader, write_wnn};
+
+        #[test]
+        fn test_write_wnn_roundtrip() {
+            let (_, model_path) = MNIST_TINY;
+            let wnn = load_wnn(Path::new(model_path)).unwrap();
+
+            let path = env::temp_dir().join(format!("zero_g_write_wnn_{}.hdf5", process::id()));
+            write_wnn(&wnn, &path).unwrap();
+            let loaded = load_wnn(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(loaded.commitment(), wnn.commitment());
+            let img = load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap();
+            assert_eq!(loaded.predict(&img), wnn.predict(&img));
+        }
+
+        #[test]
+        fn test_load_wnn_from_reader() {
+            let (_, model_path) = MNIST_TINY;
+            let wnn = load_wnn(Path::new(model_path)).unwrap();
+
+            let from_reader = load_wnn_from_reader(fs::File::open(model_path).unwrap()).unwrap();
+            assert_eq!(from_reader.commitment(), wnn.commitment());
+            let from_bytes = load_wnn_from_bytes(&fs::read(model_path).unwrap()).unwrap();
+            assert_eq!(from_bytes.commitment(), wnn.commitment());
+            assert!(load_wnn_from_bytes(b"not an HDF5 file").is_err());
+        }
     }
 }
@@ -0,0 +1,202 @@
+//! Proving inputs in the background with a bounded number of concurrent proofs, as used by
+//! the proving services.
+//!
+//! Proofs are written to the jobs directory as `<id>.zgp`. Job states are only kept in memory.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use tokio::sync::{broadcast, Semaphore};
+use tracing::warn;
+
+use crate::classifier::{ProvableClassifier, WnnInput};
+use crate::error::ZeroGError;
+use crate::proof_file::write_proof_file;
+use crate::prover::Prover;
+
+/// Number of status updates buffered for slow subscribers, see [`JobQueue::subscribe`].
+const UPDATE_CAPACITY: usize = 1024;
+
+/// The state of a proving job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running { phase: JobPhase },
+    Done { scores: Vec<u64> },
+    Failed { error: String },
+}
+
+/// What a running job is doing, so that clients can show progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobPhase {
+    /// Generating the proof, which takes most of the time.
+    Proving,
+    /// Writing the proof file.
+    WritingProof,
+}
+
+impl JobStatus {
+    /// Whether the status will not change anymore.
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Done { .. } | Self::Failed { .. })
+    }
+}
+
+/// A queue of proving jobs, see the module documentation.
+pub struct JobQueue {
+    prover: Prover,
+    jobs_dir: PathBuf,
+    jobs: Mutex<HashMap<String, JobStatus>>,
+    semaphore: Semaphore,
+    updates: broadcast::Sender<(String, JobStatus)>,
+}
+
+impl JobQueue {
+    /// Creates the jobs directory if needed. At most `concurrency` proofs are generated at
+    /// the same time; as each proof uses all cores, more than 1 mostly helps to overlap
+    /// loading and proving.
+    pub fn new(
+        prover: Prover,
+        jobs_dir: &Path,
+        concurrency: usize,
+    ) -> Result<Arc<Self>, ZeroGError> {
+        fs::create_dir_all(jobs_dir).map_err(|source| ZeroGError::Io {
+            action: "create",
+            path: jobs_dir.to_path_buf(),
+            source,
+        })?;
+        Ok(Arc::new(Self {
+            prover,
+            jobs_dir: jobs_dir.to_path_buf(),
+            jobs: Mutex::new(HashMap::new()),
+            semaphore: Semaphore::new(concurrency.max(1)),
+            updates: broadcast::channel(UPDATE_CAPACITY).0,
+        }))
+    }
+
+    pub fn prover(&self) -> &Prover {
+        &self.prover
+    }
+
+    /// Queues proving the input (an image, or the features of a tabular model) and returns
+    /// the ID of the job.
+    ///
+    /// Returns an error right away if the model doesn't accept the input, see
+    /// [`ProvableClassifier::check_input`]. Must be called from within a Tokio runtime.
+    pub fn submit(self: &Arc<Self>, input: WnnInput) -> Result<String, ZeroGError> {
+        self.prover.check_input(&input)?;
+
+        let mut id = [0; 16];
+        OsRng.fill_bytes(&mut id);
+        let id = hex::encode(id);
+        self.set_status(&id, JobStatus::Queued);
+
+        let queue = self.clone();
+        let job_id = id.clone();
+        tokio::spawn(async move {
+            let _permit = queue
+                .semaphore
+                .acquire()
+                .await
+                .expect("The semaphore is never closed");
+            queue.set_status(
+                &job_id,
+                JobStatus::Running {
+                    phase: JobPhase::Proving,
+                },
+            );
+
+            let prover_queue = queue.clone();
+            let prover_id = job_id.clone();
+            let result =
+                tokio::task::spawn_blocking(move || prover_queue.prove(&prover_id, &input)).await;
+            let status = match result {
+                Ok(Ok(scores)) => JobStatus::Done { scores },
+                Ok(Err(e)) => JobStatus::Failed {
+                    error: e.to_string(),
+                },
+                Err(e) => JobStatus::Failed {
+                    error: format!("Proving panicked: {e}"),
+                },
+            };
+            if let JobStatus::Failed { error } = &status {
+                warn!("Job {job_id} failed: {error}");
+            }
+            queue.set_status(&job_id, status);
+        });
+
+        Ok(id)
+    }
+
+    pub fn status(&self, id: &str) -> Option<JobStatus> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+
+    /// Receives the `(id, status)` of every status change from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<(String, JobStatus)> {
+        self.updates.subscribe()
+    }
+
+    /// The path of the proof file of a job, which exists once the job is done.
+    pub fn proof_path(&self, id: &str) -> PathBuf {
+        self.jobs_dir.join(format!("{id}.zgp"))
+    }
+
+    fn set_status(&self, id: &str, status: JobStatus) {
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), status.clone());
+        // Sending only fails if there are no subscribers
+        let _ = self.updates.send((id.to_string(), status));
+    }
+
+    fn prove(&self, id: &str, input: &WnnInput) -> Result<Vec<u64>, ZeroGError> {
+        let proof_file = self.prover.prove(input)?;
+        self.set_status(
+            id,
+            JobStatus::Running {
+                phase: JobPhase::WritingProof,
+            },
+        );
+        let path = self.proof_path(id);
+        write_proof_file(&proof_file, &path).map_err(|source| ZeroGError::Io {
+            action: "write",
+            path,
+            source,
+        })?;
+        Ok(ProvableClassifier::predict(self.prover.model(), input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{JobPhase, JobStatus};
+
+    #[test]
+    fn test_job_status_json() {
+        assert_eq!(
+            serde_json::to_value(JobStatus::Queued).unwrap(),
+            json!({ "status": "queued" })
+        );
+        assert_eq!(
+            serde_json::to_value(JobStatus::Running {
+                phase: JobPhase::WritingProof
+            })
+            .unwrap(),
+            json!({ "status": "running", "phase": "writing_proof" })
+        );
+        assert_eq!(
+            serde_json::to_value(JobStatus::Done { scores: vec![1, 2] }).unwrap(),
+            json!({ "status": "done", "scores": [1, 2] })
+        );
+    }
+}
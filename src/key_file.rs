@@ -0,0 +1,408 @@
+//! A header for proving and verifying key files, so that a key generated for a different model
+//! or by an incompatible version of this crate is rejected instead of being misread.
+//!
+//! The layout is as follows (all integers are little endian):
+//!
+//! | Field                 | Size                                       |
+//! |-----------------------|--------------------------------------------|
+//! | Magic bytes (`ZGKF`)  | 4 bytes                                    |
+//! | Format version        | 2 bytes                                    |
+//! | Header length         | 4 bytes                                    |
+//! | Header (JSON)         | `header length` bytes, see [`KeyHeader`]   |
+//! | Key                   | In the [`KeyFormat`] given in the header   |
+//!
+//! The header embeds the circuit params, which are needed to deserialize the key, so that keys
+//! can be read without supplying them separately (see [`read_pk_and_params_from`]).
+//!
+//! Files written before the header existed (legacy files) contain only the key. They are still
+//! read, with a warning as nothing can be validated, and can be rewritten with a header by
+//! [`crate::io::upgrade_key_file`].
+
+use std::io::{self, BufRead, Write};
+
+use halo2_proofs::{
+    halo2curves::bn256::G1Affine,
+    plonk::{ProvingKey, VerifyingKey},
+    SerdeFormat::RawBytes,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::gadgets::wnn::WnnCircuitParams;
+use crate::gadgets::WnnCircuit;
+use crate::io::{invalid_data, read_array, read_length_prefixed, write_length_prefixed, KeyFormat};
+use crate::verifier_bundle::circuit_params_hash;
+
+/// Magic bytes at the start of every key file with a header.
+pub const MAGIC: [u8; 4] = *b"ZGKF";
+
+/// The format version written by this version of the crate.
+pub const CURRENT_VERSION: u16 = 1;
+
+/// Whether a key file contains a proving or a verifying key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyKind {
+    Proving,
+    Verifying,
+}
+
+/// Describes the key in a key file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyHeader {
+    pub kind: KeyKind,
+    /// Version of `zero_g` that wrote the key.
+    pub crate_version: String,
+    /// See [`circuit_params_hash`].
+    pub circuit_params_hash: [u8; 32],
+    pub k: u32,
+    /// The format the key is written in. Never [`KeyFormat::RawBytesUnchecked`], as whether
+    /// to check the key is up to the reader.
+    pub format: KeyFormat,
+    /// The circuit params the key was generated for. Missing in headers written before they
+    /// were embedded.
+    #[serde(default)]
+    pub circuit_params: Option<WnnCircuitParams>,
+}
+
+impl KeyHeader {
+    pub fn new(
+        kind: KeyKind,
+        circuit_params: &WnnCircuitParams,
+        k: u32,
+        format: KeyFormat,
+    ) -> Self {
+        Self {
+            kind,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            circuit_params_hash: circuit_params_hash(circuit_params),
+            k,
+            format: match format {
+                KeyFormat::RawBytesUnchecked => KeyFormat::RawBytes,
+                format => format,
+            },
+            circuit_params: Some(circuit_params.clone()),
+        }
+    }
+
+    /// Writes the magic bytes, the format version and the header.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&CURRENT_VERSION.to_le_bytes())?;
+        write_length_prefixed(writer, &serde_json::to_vec(self)?)
+    }
+
+    /// Reads the header, or returns `None` (without consuming anything) for a legacy file.
+    pub fn read_from(reader: &mut impl BufRead) -> io::Result<Option<Self>> {
+        if !reader.fill_buf()?.starts_with(&MAGIC) {
+            return Ok(None);
+        }
+        reader.consume(MAGIC.len());
+        let version = u16::from_le_bytes(read_array(reader)?);
+        if version != CURRENT_VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported key file version {version} (latest supported: {CURRENT_VERSION})"
+            )));
+        }
+        Ok(Some(serde_json::from_slice(&read_length_prefixed(
+            reader,
+        )?)?))
+    }
+
+    /// Checks that the key can be read for a circuit with the given params, in the given
+    /// format, by this version of the crate.
+    pub fn check(
+        &self,
+        kind: KeyKind,
+        circuit_params: &WnnCircuitParams,
+        format: KeyFormat,
+    ) -> io::Result<()> {
+        if self.kind != kind {
+            return Err(invalid_data(format!(
+                "Expected a {kind:?} key, found a {:?} key",
+                self.kind
+            )));
+        }
+        if !is_compatible_version(&self.crate_version) {
+            return Err(invalid_data(format!(
+                "The key was written by zero_g {}, which is incompatible with {}. \
+                 Generate the keys again.",
+                self.crate_version,
+                env!("CARGO_PKG_VERSION")
+            )));
+        }
+        if self.circuit_params_hash != circuit_params_hash(circuit_params) {
+            return Err(invalid_data(
+                "The key was generated for different circuit params (e.g. a different model)",
+            ));
+        }
+        if (self.format == KeyFormat::Processed) != (format == KeyFormat::Processed) {
+            return Err(invalid_data(format!(
+                "The key is written in the {} format, not {format}",
+                self.format
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Reads the header, which must embed the circuit params, and checks it like
+/// [`KeyHeader::check`] (with the embedded params, which guards against a tampered header).
+fn read_embedded_circuit_params(
+    reader: &mut impl BufRead,
+    kind: KeyKind,
+    format: KeyFormat,
+) -> io::Result<WnnCircuitParams> {
+    let header = KeyHeader::read_from(reader)?.ok_or_else(|| {
+        invalid_data("The key file has no header, pass the circuit params or upgrade the file")
+    })?;
+    let circuit_params = header.circuit_params.clone().ok_or_else(|| {
+        invalid_data("The key file has no embedded circuit params, pass them or upgrade the file")
+    })?;
+    header.check(kind, &circuit_params, format)?;
+    Ok(circuit_params)
+}
+
+/// Whether keys written by the given version of this crate can be read, i.e. whether it is
+/// semver compatible with this version.
+fn is_compatible_version(crate_version: &str) -> bool {
+    let compatibility_prefix = |version: &str| {
+        let mut parts = version.split('.');
+        match parts.next() {
+            Some("0") => format!("0.{}", parts.next().unwrap_or_default()),
+            major => major.unwrap_or_default().to_string(),
+        }
+    };
+    compatibility_prefix(crate_version) == compatibility_prefix(env!("CARGO_PKG_VERSION"))
+}
+
+/// Writes a proving key with a header.
+pub fn write_pk_to(
+    writer: &mut impl Write,
+    pk: &ProvingKey<G1Affine>,
+    circuit_params: &WnnCircuitParams,
+    format: KeyFormat,
+) -> io::Result<()> {
+    let k = pk.get_vk().get_domain().k();
+    KeyHeader::new(KeyKind::Proving, circuit_params, k, format).write_to(writer)?;
+    pk.write(writer, format.serde_format())
+}
+
+/// Writes a verifying key with a header, always in the [`KeyFormat::RawBytes`] format.
+pub fn write_vk_to(
+    writer: &mut impl Write,
+    vk: &VerifyingKey<G1Affine>,
+    circuit_params: &WnnCircuitParams,
+) -> io::Result<()> {
+    let k = vk.get_domain().k();
+    KeyHeader::new(KeyKind::Verifying, circuit_params, k, KeyFormat::RawBytes).write_to(writer)?;
+    vk.write(writer, RawBytes)
+}
+
+/// Reads a proving key, validating the header (see [`KeyHeader::check`]) or, for legacy files,
+/// warning that it can't be validated.
+pub fn read_pk_from(
+    reader: &mut impl BufRead,
+    circuit_params: WnnCircuitParams,
+    format: KeyFormat,
+) -> io::Result<ProvingKey<G1Affine>> {
+    check_header(reader, KeyKind::Proving, &circuit_params, format)?;
+    ProvingKey::read::<_, WnnCircuit<_>>(reader, format.serde_format(), circuit_params)
+}
+
+/// Like [`read_pk_from`], for verifying keys, which are always in the [`KeyFormat::RawBytes`]
+/// format.
+pub fn read_vk_from(
+    reader: &mut impl BufRead,
+    circuit_params: WnnCircuitParams,
+) -> io::Result<VerifyingKey<G1Affine>> {
+    check_header(
+        reader,
+        KeyKind::Verifying,
+        &circuit_params,
+        KeyFormat::RawBytes,
+    )?;
+    VerifyingKey::read::<_, WnnCircuit<_>>(reader, RawBytes, circuit_params)
+}
+
+/// Reads a proving key with the circuit params embedded in its header, see
+/// [`read_pk_from`].
+pub fn read_pk_and_params_from(
+    reader: &mut impl BufRead,
+    format: KeyFormat,
+) -> io::Result<(ProvingKey<G1Affine>, WnnCircuitParams)> {
+    let circuit_params = read_embedded_circuit_params(reader, KeyKind::Proving, format)?;
+    let pk = ProvingKey::read::<_, WnnCircuit<_>>(
+        reader,
+        format.serde_format(),
+        circuit_params.clone(),
+    )?;
+    Ok((pk, circuit_params))
+}
+
+/// Reads a verifying key with the circuit params embedded in its header, see
+/// [`read_vk_from`].
+pub fn read_vk_and_params_from(
+    reader: &mut impl BufRead,
+) -> io::Result<(VerifyingKey<G1Affine>, WnnCircuitParams)> {
+    let circuit_params =
+        read_embedded_circuit_params(reader, KeyKind::Verifying, KeyFormat::RawBytes)?;
+    let vk = VerifyingKey::read::<_, WnnCircuit<_>>(reader, RawBytes, circuit_params.clone())?;
+    Ok((vk, circuit_params))
+}
+
+fn check_header(
+    reader: &mut impl BufRead,
+    kind: KeyKind,
+    circuit_params: &WnnCircuitParams,
+    format: KeyFormat,
+) -> io::Result<()> {
+    match KeyHeader::read_from(reader)? {
+        Some(header) => header.check(kind, circuit_params, format),
+        None => {
+            warn!("Reading a {kind:?} key without a header, which can't be validated");
+            Ok(())
+        }
+    }
+}
+
+/// Reads a key of any kind and format written by [`write_pk_to`] or [`write_vk_to`], or a
+/// legacy file in the [`KeyFormat::RawBytes`] format, and writes it again with a current
+/// header (which embeds the circuit params).
+pub fn upgrade_key(
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+    kind: KeyKind,
+    circuit_params: WnnCircuitParams,
+) -> io::Result<KeyHeader> {
+    let header = KeyHeader::read_from(reader)?;
+    let format = header
+        .as_ref()
+        .map_or(KeyFormat::RawBytes, |header| header.format);
+    if let Some(header) = &header {
+        header.check(kind, &circuit_params, format)?;
+    }
+    let k = match kind {
+        KeyKind::Proving => {
+            let pk = ProvingKey::read::<_, WnnCircuit<_>>(
+                reader,
+                format.serde_format(),
+                circuit_params.clone(),
+            )?;
+            write_pk_to(writer, &pk, &circuit_params, format)?;
+            pk.get_vk().get_domain().k()
+        }
+        KeyKind::Verifying => {
+            let vk =
+                VerifyingKey::read::<_, WnnCircuit<_>>(reader, RawBytes, circuit_params.clone())?;
+            write_vk_to(writer, &vk, &circuit_params)?;
+            vk.get_domain().k()
+        }
+    };
+    Ok(KeyHeader::new(kind, &circuit_params, k, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gadgets::wnn::WnnCircuitParams;
+    use crate::io::KeyFormat;
+
+    use super::{is_compatible_version, KeyHeader, KeyKind, CURRENT_VERSION, MAGIC};
+
+    fn circuit_params() -> WnnCircuitParams {
+        WnnCircuitParams {
+            p: 2097143,
+            l: 20,
+            n_hashes: 2,
+            bits_per_hash: 10,
+            bits_per_filter: 28,
+            n_classes: 10,
+            min_blinding_factors: 0,
+            num_instance_columns: 1,
+            class_lookup: false,
+            window_num_bits: 8,
+            chaining: false,
+            multi_label: false,
+            regression: false,
+            tabular: false,
+            occlusion_num_pixels: 0,
+            robustness: false,
+            image_commitment: false,
+        }
+    }
+
+    #[test]
+    fn test_header_roundtrip() {
+        let header = KeyHeader::new(
+            KeyKind::Proving,
+            &circuit_params(),
+            14,
+            KeyFormat::RawBytesUnchecked,
+        );
+        assert_eq!(header.format, KeyFormat::RawBytes);
+        assert_eq!(header.circuit_params, Some(circuit_params()));
+
+        let mut bytes = vec![];
+        header.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes[..4], MAGIC);
+        assert_eq!(bytes[4..6], CURRENT_VERSION.to_le_bytes());
+        bytes.extend([1, 2, 3]);
+
+        let mut reader = bytes.as_slice();
+        let read = KeyHeader::read_from(&mut reader).unwrap().unwrap();
+        assert_eq!(read, header);
+        // The key follows the header
+        assert_eq!(reader, [1, 2, 3]);
+
+        // Headers without embedded circuit params are still read
+        let mut json = serde_json::to_value(&header).unwrap();
+        json.as_object_mut().unwrap().remove("circuit_params");
+        let without_params: KeyHeader = serde_json::from_value(json).unwrap();
+        assert_eq!(without_params.circuit_params, None);
+
+        // Legacy files have no header
+        let mut legacy = [1u8, 2, 3].as_slice();
+        assert_eq!(KeyHeader::read_from(&mut legacy).unwrap(), None);
+        assert_eq!(legacy, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_check() {
+        let params = circuit_params();
+        let header = KeyHeader::new(KeyKind::Proving, &params, 14, KeyFormat::RawBytes);
+        header
+            .check(KeyKind::Proving, &params, KeyFormat::RawBytes)
+            .unwrap();
+        header
+            .check(KeyKind::Proving, &params, KeyFormat::RawBytesUnchecked)
+            .unwrap();
+
+        assert!(header
+            .check(KeyKind::Verifying, &params, KeyFormat::RawBytes)
+            .is_err());
+        assert!(header
+            .check(KeyKind::Proving, &params, KeyFormat::Processed)
+            .is_err());
+        let other_params = WnnCircuitParams {
+            n_classes: 9,
+            ..params.clone()
+        };
+        assert!(header
+            .check(KeyKind::Proving, &other_params, KeyFormat::RawBytes)
+            .is_err());
+        let old_header = KeyHeader {
+            crate_version: "99.0.0".to_string(),
+            ..header
+        };
+        assert!(old_header
+            .check(KeyKind::Proving, &params, KeyFormat::RawBytes)
+            .is_err());
+    }
+
+    #[test]
+    fn test_is_compatible_version() {
+        assert!(is_compatible_version(env!("CARGO_PKG_VERSION")));
+        assert!(!is_compatible_version("99.0.0"));
+        assert!(!is_compatible_version(""));
+    }
+}
@@ -0,0 +1,175 @@
+//! Sources for the ground-truth class of images in a dataset.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use crate::io::invalid_data;
+
+/// Maps image paths to class ids.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelSource {
+    /// The class is the number after the last underscore of the file name,
+    /// e.g. 7 for `data/MNIST/png/0000_7.png` or 12 for `0042_12.png`.
+    FileName,
+    /// Classes are looked up by file name in a table, usually read from a sidecar file
+    /// (see [`LabelSource::read_sidecar`]).
+    Table(HashMap<String, usize>),
+    /// Images are stored in one directory per class (e.g. `test/cat/0001.png`).
+    /// The class is looked up by the name of the parent directory.
+    ClassDirectories(HashMap<String, usize>),
+}
+
+impl LabelSource {
+    /// Returns the class of the given image, or `None` if it is unknown.
+    pub fn label(&self, img_path: &Path) -> Option<usize> {
+        match self {
+            Self::FileName => {
+                let stem = img_path.file_stem()?.to_str()?;
+                let suffix = stem.rsplit_once('_').map_or(stem, |(_, suffix)| suffix);
+                suffix.parse().ok()
+            }
+            Self::Table(labels) => labels.get(img_path.file_name()?.to_str()?).copied(),
+            Self::ClassDirectories(classes) => classes
+                .get(img_path.parent()?.file_name()?.to_str()?)
+                .copied(),
+        }
+    }
+
+    /// Reads labels from a CSV file with lines of the form `<file name>,<class>`.
+    /// A header line is skipped if present.
+    pub fn read_csv(path: &Path) -> io::Result<Self> {
+        let mut labels = HashMap::new();
+        for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (file_name, class) = line
+                .rsplit_once(',')
+                .ok_or_else(|| invalid_data(format!("Line {}: expected two columns", i + 1)))?;
+            match class.trim().parse() {
+                Ok(class) => {
+                    labels.insert(file_name.trim().to_string(), class);
+                }
+                Err(_) if i == 0 => {} // Header
+                Err(_) => {
+                    return Err(invalid_data(format!(
+                        "Line {}: invalid class {class:?}",
+                        i + 1
+                    )))
+                }
+            }
+        }
+        Ok(Self::Table(labels))
+    }
+
+    /// Reads labels from a JSON file containing an object mapping file names to classes.
+    pub fn read_json(path: &Path) -> io::Result<Self> {
+        Ok(Self::Table(serde_json::from_reader(BufReader::new(
+            File::open(path)?,
+        ))?))
+    }
+
+    /// Reads a CSV or JSON sidecar file, depending on the file extension.
+    pub fn read_sidecar(path: &Path) -> io::Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Self::read_csv(path),
+            Some("json") => Self::read_json(path),
+            _ => Err(invalid_data(format!(
+                "Unsupported label file {}, expected .csv or .json",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Assigns a class to each subdirectory of `root`.
+    ///
+    /// If all subdirectories are named by numbers, these are used as class ids. Otherwise,
+    /// classes are numbered in the alphabetical order of the directory names.
+    pub fn class_directories(root: &Path) -> io::Result<Self> {
+        let mut names = vec![];
+        for entry in fs::read_dir(root)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Some(name) = entry.file_name().to_str() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+
+        let classes = if names.iter().all(|name| name.parse::<usize>().is_ok()) {
+            names
+                .into_iter()
+                .map(|name| {
+                    let class = name.parse().unwrap();
+                    (name, class)
+                })
+                .collect()
+        } else {
+            names
+                .into_iter()
+                .enumerate()
+                .map(|(i, name)| (name, i))
+                .collect()
+        };
+        Ok(Self::ClassDirectories(classes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::{env, fs, process};
+
+    use super::LabelSource;
+
+    #[test]
+    fn test_file_name() {
+        let labels = LabelSource::FileName;
+        assert_eq!(
+            labels.label(Path::new("data/MNIST/png/0000_7.png")),
+            Some(7)
+        );
+        assert_eq!(labels.label(Path::new("cifar100/0042_12.png")), Some(12));
+        assert_eq!(labels.label(Path::new("data/readme.md")), None);
+    }
+
+    #[test]
+    fn test_csv() {
+        let dir = env::temp_dir().join(format!("zero_g_labels_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("labels.csv");
+        fs::write(&path, "file,label\nimg_a.png,3\nimg_b.png, 11\n").unwrap();
+
+        let labels = LabelSource::read_sidecar(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(labels.label(Path::new("test/img_a.png")), Some(3));
+        assert_eq!(labels.label(Path::new("test/img_b.png")), Some(11));
+        assert_eq!(labels.label(Path::new("test/img_c.png")), None);
+    }
+
+    #[test]
+    fn test_class_directories() {
+        let dir = env::temp_dir().join(format!("zero_g_class_dirs_{}", process::id()));
+        for class in ["dog", "cat"] {
+            fs::create_dir_all(dir.join(class)).unwrap();
+        }
+
+        let labels = LabelSource::class_directories(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            labels,
+            LabelSource::ClassDirectories(HashMap::from([
+                ("cat".to_string(), 0),
+                ("dog".to_string(), 1)
+            ]))
+        );
+        assert_eq!(labels.label(Path::new("test/dog/0001.png")), Some(1));
+    }
+}
@@ -0,0 +1,165 @@
+//! Plots of the circuit layout (see [`halo2_proofs::dev::CircuitLayout`]), as PNG or SVG.
+//!
+//! For large circuits, plotting all `2^k` rows is rarely useful (and a bitmap with several
+//! pixels per row quickly gets too large), so [`PlotOptions`] can restrict the plot to a row
+//! range or to the rows of the regions with a given name.
+
+use std::ops::Range;
+use std::path::Path;
+
+use ff::Field;
+use halo2_proofs::{dev::CircuitLayout, plonk::Circuit};
+use plotters::prelude::*;
+
+use crate::region_layout::region_rows;
+
+/// The default width of the plot, in pixels.
+pub const DEFAULT_WIDTH: u32 = 1024;
+/// The default height per row, in pixels.
+pub const PIXELS_PER_ROW: u32 = 8;
+/// The maximum default height, in pixels. Larger plots need an explicit height.
+pub const MAX_DEFAULT_HEIGHT: u32 = 1 << 14;
+
+/// The image format of a plot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlotFormat {
+    Png,
+    Svg,
+}
+
+impl PlotFormat {
+    /// SVG for `.svg` files, PNG otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("svg") => Self::Svg,
+            _ => Self::Png,
+        }
+    }
+}
+
+/// Options of [`plot_layout`].
+#[derive(Debug, Clone, Default)]
+pub struct PlotOptions {
+    /// The image format. By default, derived from the file extension.
+    pub format: Option<PlotFormat>,
+    /// The width in pixels, [`DEFAULT_WIDTH`] by default.
+    pub width: Option<u32>,
+    /// The height in pixels. By default, [`PIXELS_PER_ROW`] per plotted row, but at most
+    /// [`MAX_DEFAULT_HEIGHT`].
+    pub height: Option<u32>,
+    /// Only plot these rows.
+    pub rows: Option<Range<usize>>,
+    /// Only plot the rows of regions whose name contains this string. Combined with `rows`,
+    /// the intersection is plotted.
+    pub region_filter: Option<String>,
+}
+
+/// Plots the layout of the circuit to the given file.
+pub fn plot_layout<F: Field, C: Circuit<F>>(
+    circuit: &C,
+    k: u32,
+    path: &Path,
+    title: &str,
+    options: &PlotOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut rows = options.rows.clone().unwrap_or(0..1 << k);
+    if let Some(filter) = &options.region_filter {
+        let matching = region_rows(circuit)?
+            .into_iter()
+            .filter(|region| region.name.contains(filter.as_str()))
+            .filter_map(|region| region.rows)
+            .reduce(|a, b| a.start.min(b.start)..a.end.max(b.end))
+            .ok_or_else(|| format!("No region matches '{filter}'"))?;
+        rows = rows.start.max(matching.start)..rows.end.min(matching.end);
+    }
+    if rows.is_empty() {
+        return Err("No rows to plot".into());
+    }
+
+    let width = options.width.unwrap_or(DEFAULT_WIDTH);
+    let height = options.height.unwrap_or_else(|| {
+        (rows.len() as u32)
+            .saturating_mul(PIXELS_PER_ROW)
+            .clamp(256, MAX_DEFAULT_HEIGHT)
+    });
+    let layout = CircuitLayout::default().show_labels(true).view_height(rows);
+    match options
+        .format
+        .unwrap_or_else(|| PlotFormat::from_path(path))
+    {
+        PlotFormat::Png => {
+            let root = BitMapBackend::new(path, (width, height)).into_drawing_area();
+            render(layout, k, circuit, root, title)
+        }
+        PlotFormat::Svg => {
+            let root = SVGBackend::new(path, (width, height)).into_drawing_area();
+            render(layout, k, circuit, root, title)
+        }
+    }
+}
+
+fn render<F: Field, C: Circuit<F>, DB: DrawingBackend>(
+    layout: CircuitLayout,
+    k: u32,
+    circuit: &C,
+    root: DrawingArea<DB, plotters::coord::Shift>,
+    title: &str,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+    let root = root.titled(title, ("sans-serif", 60))?;
+    layout.render(k, circuit, &root)?;
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use halo2_proofs::halo2curves::bn256::Fr as Fp;
+    use ndarray::{Array2, Array3};
+
+    use super::{plot_layout, PlotFormat, PlotOptions};
+    use crate::gadgets::WnnCircuit;
+    use crate::wnn::Wnn;
+
+    fn circuit() -> WnnCircuit<Fp> {
+        let wnn = Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            2097143,
+            Array3::from_elem((2, 2, 1024), false),
+            (0..24u64).collect(),
+            Array3::zeros((4, 3, 2)),
+        );
+        WnnCircuit::builder(&wnn)
+            .image(Array2::zeros((4, 3)))
+            .build()
+            .unwrap()
+            .with_region_annotations()
+    }
+
+    #[test]
+    fn test_plot_svg() {
+        let path = env::temp_dir().join(format!("zero_g_layout_{}.svg", std::process::id()));
+        assert_eq!(PlotFormat::from_path(&path), PlotFormat::Svg);
+        let options = PlotOptions {
+            region_filter: Some("HashChip".to_string()),
+            ..Default::default()
+        };
+        plot_layout(&circuit(), 12, &path, "Hash regions", &options).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().starts_with("<svg"));
+        std::fs::remove_file(path).unwrap();
+
+        let options = PlotOptions {
+            region_filter: Some("no such region".to_string()),
+            ..Default::default()
+        };
+        assert!(plot_layout(&circuit(), 12, &path, "", &options).is_err());
+    }
+}
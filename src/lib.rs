@@ -6,6 +6,9 @@
 //! [zkp-gravity/BTHOWeN-0g](https://github.com/zkp-gravity/BTHOWeN-0g) (see below).
 //! You might also want to integrate the [gadgets::wnn::WnnChip] into your Halo2 circuit.
 //!
+//! The simplest way to prove and verify inference is [`ZeroG`], which takes care of the SRS,
+//! the keys and the circuit params. The example below shows the lower-level API.
+//!
 //! # Example: Proving inference on an image file
 //! ```
 //! use std::path::Path;
@@ -23,22 +26,91 @@
 //!
 //! // Generate keys
 //! let kzg_params = ParamsKZG::new(k);
-//! let pk = wnn.generate_proving_key(&kzg_params);
+//! let pk = wnn.generate_proving_key(&kzg_params).unwrap();
 //!
 //! // Generate proof
-//! let (proof, outputs) = wnn.proof(&pk, &kzg_params, &img);
+//! let (proof, outputs) = wnn.proof(&pk, &kzg_params, &img).unwrap();
 //!
 //! // Verify proof
 //! Wnn::verify_proof(&proof, &kzg_params, pk.get_vk(), &outputs);
 //! ```
 
+pub mod artifact_store;
+pub mod backend;
+pub mod batch_proving;
+pub mod benchmark;
+pub mod blinding;
+pub mod bloom_analysis;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod circuit_graph;
+pub mod classifier;
+pub mod config;
+pub mod consistency;
+pub mod cost;
+pub mod cs_fingerprint;
+pub mod daemon;
+pub mod datasets;
+#[cfg(feature = "download")]
+pub(crate) mod download;
+pub mod error;
+#[cfg(feature = "evm")]
 pub mod eth;
+pub mod evaluation;
+pub mod explain;
+pub mod facade;
 pub mod gadgets;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod image_commitment;
+pub mod image_loading;
 pub mod io;
+#[cfg(any(feature = "server", feature = "grpc"))]
+pub mod jobs;
+pub mod key_file;
+pub mod labels;
+#[cfg(feature = "plotting")]
+pub mod layout_plot;
+pub mod memory;
+pub mod model_file;
+pub mod model_info;
+#[cfg(feature = "download")]
+pub mod model_zoo;
+pub mod occlusion;
+pub mod packed_bloom_filters;
+pub mod pipeline;
+pub mod pixel_order;
+pub mod preprocessing;
+pub mod proof_chain;
+pub mod proof_file;
+#[cfg(feature = "proptest")]
+pub mod property_testing;
+pub mod prover;
+pub mod pruning;
+pub mod quantization;
+pub mod region_layout;
+pub mod registry;
+pub mod robustness;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod setup;
+pub mod telemetry;
+pub mod test_vectors;
+pub mod testing;
 pub mod utils;
+pub mod verification;
+pub mod verifier_bundle;
+pub mod vk_diff;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 pub mod wnn;
 
-pub use io::{load_grayscale_image, load_wnn};
+pub use classifier::{ProvableClassifier, WnnInput};
+pub use facade::{Verifier, ZeroG};
+pub use io::{load_grayscale_image, load_image_from_bytes, load_model};
+#[cfg(feature = "hdf5")]
+pub use io::{load_wnn, load_wnn_from_bytes, load_wnn_with_policy, write_wnn};
+pub use prover::Prover;
 pub use wnn::Wnn;
 
 pub mod checked_in_test_data {
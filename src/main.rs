@@ -1,22 +1,85 @@
-use std::{fs, path::PathBuf};
+use std::cell::Cell;
+use std::fmt::Display;
+#[cfg(any(feature = "server", feature = "grpc"))]
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use clap::{Parser, Subcommand};
 use ethers::types::Address;
+use eyre::Result;
 use halo2_proofs::{
-    halo2curves::bn256::Bn256,
+    halo2curves::bn256::{Bn256, G1Affine},
+    plonk::VerifyingKey,
     poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
 };
-use hdf5::Result;
-use indicatif::ProgressIterator;
+use indicatif::{ProgressIterator, ProgressStyle};
+use rand_core::{OsRng, RngCore};
+use serde_json::{json, Value};
+use tracing::{info, info_span, Level};
+use tracing_indicatif::{span_ext::IndicatifSpanExt, IndicatifLayer};
+use tracing_subscriber::{
+    filter::{EnvFilter, Targets},
+    fmt::format::FmtSpan,
+    prelude::*,
+};
 use zero_g::{
-    eth::{dry_run_verifier, gen_evm_verifier, EthClient},
+    batch_proving::{image_files, BatchProver, Manifest, ManifestEntry, MANIFEST_FILE_NAME},
+    benchmark::run_benchmark,
+    bloom_analysis::{FalsePositiveAnalysis, ScoreDistribution},
+    config::{CommitmentMode, ProjectConfig, CONFIG_FILE_NAME},
+    consistency::{check_consistency, check_key_matches_model},
+    cost::{estimate, suggest_params, Objective},
+    daemon::{Daemon, DaemonConfig},
+    datasets::Dataset,
+    eth::{
+        dry_run_verifier, export_evm_verifier, gen_evm_verifier, EthClient, RegistrationPayload,
+    },
+    gadgets::wnn::{InstanceLayout, PublicValue, WnnCircuitParams},
+    image_commitment::{commitment_from_field, image_commitment, SALT_SIZE},
     io::{
-        parse_png_file, read_circuit_params, read_pk, read_srs, read_vk, write_circuit_params,
-        write_keys, write_srs, ProofWithOutput,
+        read_circuit_params, read_pk, read_pk_with_format, read_srs, read_vk, read_vk_and_params,
+        upgrade_key_file, write_circuit_params, write_keys_with_format, write_srs,
+        write_verifier_key, KeyFormat,
     },
-    load_grayscale_image, load_wnn,
-    utils::argmax,
-    Wnn,
+    key_file::KeyKind,
+    labels::LabelSource,
+    layout_plot::PlotOptions,
+    load_grayscale_image, load_model,
+    memory::TrackingAllocator,
+    model_file::save_model,
+    pipeline::Pipeline,
+    proof_chain::{chaining_value_from_field, exposed_chaining_value, GENESIS},
+    proof_file::{read_proof_file, upgrade_proof_file, write_proof_file, ProofFile},
+    prover::Prover,
+    pruning::Calibration,
+    setup::{gen_srs, get_srs, setup_with_key_format, SetupFiles, SrsSource},
+    telemetry::TelemetryLog,
+    test_vectors::TestVectors,
+    testing::{describe_failure, mock_prove, Diagnosis},
+    utils::{argmax, to_u32},
+    verifier_bundle::{vk_fingerprint, VerifierBundle},
+    vk_diff::{diff_vks, match_fingerprint, FingerprintMatch, VkSummary},
+    ProvableClassifier, Wnn, WnnInput,
+};
+
+#[cfg(feature = "grpc")]
+use zero_g::grpc;
+#[cfg(any(feature = "server", feature = "grpc"))]
+use zero_g::jobs::JobQueue;
+#[cfg(feature = "server")]
+use zero_g::server;
+#[cfg(feature = "download")]
+use zero_g::{datasets::mnist, model_zoo};
+#[cfg(feature = "hdf5")]
+use zero_g::{
+    io::load_float_thresholds,
+    load_wnn_with_policy,
+    model_file::convert_hdf5_model,
+    pixel_order::PixelOrder,
+    quantization::{QuantizationAudit, QuantizationPolicy, Rounding},
 };
 
 #[derive(Parser)]
@@ -27,40 +90,239 @@ use zero_g::{
 struct Arguments {
     #[clap(subcommand)]
     command: Commands,
+    /// Print the result as a single JSON object (progress messages are written to stderr)
+    #[clap(long, global = true)]
+    json: bool,
+    /// Path to the project file providing defaults for the model, artifact directory, `k` and
+    /// datasets. Defaults to `zero_g.toml` in the working directory, if it exists.
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+    /// Log more details to stderr (once for progress messages, twice for timings of all
+    /// steps). `RUST_LOG` takes precedence, if set.
+    #[clap(long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Predict inference of a particular image (no proving)
     Predict {
-        /// Path to the model (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
         #[clap(short, long)]
-        model_path: PathBuf,
+        model_path: Option<PathBuf>,
         /// Path to the image (e.g. benches/example_image_7.png)
         #[clap(short, long)]
         img_path: PathBuf,
+        /// Also print which pixels fed the positive filter responses of the predicted class
+        #[clap(long)]
+        explain: bool,
     },
-    /// Compute the accuracy on the test set
-    ComputeAccuracy {
-        /// Path to the model (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+    /// Print the model metadata, bloom filter densities, threshold statistics and the
+    /// circuit params implied by the model
+    Inspect {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
         #[clap(short, long)]
-        model_path: PathBuf,
-        /// Path to the test set (e.g. data/MNIST/png)
+        model_path: Option<PathBuf>,
+    },
+    /// Estimate the false-positive rates of the bloom filters and, if a dataset is given, the
+    /// distribution of the scores, to judge whether a model is worth proving
+    AnalyzeFilters {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Optional dataset to simulate the scores on (same formats as for `evaluate`)
+        #[clap(short, long)]
+        test_set_path: Option<PathBuf>,
+        /// Optional CSV or JSON file mapping file names to classes.
+        /// By default, the class is parsed from the file name (e.g. 7 for 0000_7.png).
+        #[clap(short, long)]
+        labels_path: Option<PathBuf>,
+    },
+    /// Compare the predictions of an HDF5 model with its float thresholds (as trained) and with
+    /// quantized thresholds (as in the circuit), listing the images whose predicted class changes
+    #[cfg(feature = "hdf5")]
+    AuditQuantization {
+        /// Path to the model, in HDF5 format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path to the test set (same formats as for `evaluate`)
         #[clap(short, long)]
-        test_set_path: PathBuf,
+        test_set_path: Option<PathBuf>,
+        /// Optional CSV or JSON file mapping file names to classes.
+        /// By default, the class is parsed from the file name (e.g. 7 for 0000_7.png).
+        #[clap(short, long)]
+        labels_path: Option<PathBuf>,
+        /// How scaled thresholds are rounded: ceil, floor or round
+        #[clap(default_value = "ceil", long)]
+        threshold_rounding: Rounding,
+        /// The smallest quantized threshold
+        #[clap(default_value_t = 0, long)]
+        threshold_min: u16,
+        /// The largest quantized threshold (256 means the bit is never set)
+        #[clap(default_value_t = 256, long)]
+        threshold_max: u16,
+    },
+    /// Compute the commitment (Poseidon hash) to an image, e.g. to register it before proving
+    CommitImage {
+        /// Path to the image (e.g. benches/example_image_7.png)
+        #[clap(short, long)]
+        img_path: PathBuf,
+        /// Optional salt (32 bytes, hex encoded), so that guessable images cannot be brute-forced
+        #[clap(short, long, conflicts_with = "random_salt")]
+        salt: Option<String>,
+        /// Generate a random salt and print it
+        #[clap(long)]
+        random_salt: bool,
+    },
+    /// Evaluate the model on a labeled test set (no proving), printing the accuracy, per-class
+    /// metrics and the confusion matrix. Thresholds are applied exactly as in the circuit.
+    #[clap(alias = "compute-accuracy")]
+    Evaluate {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path to the test set: a directory of images (e.g. data/MNIST/png), an IDX images file
+        /// (e.g. data/MNIST/t10k-images-idx3-ubyte) or a CIFAR-10 batch (e.g. test_batch.bin)
+        #[clap(short, long)]
+        test_set_path: Option<PathBuf>,
+        /// Optional CSV or JSON file mapping file names to classes.
+        /// By default, the class is parsed from the file name (e.g. 7 for 0000_7.png).
+        #[clap(short, long)]
+        labels_path: Option<PathBuf>,
+        /// Optional path to write a report with per-class metrics and the confusion matrix to
+        /// (CSV if the extension is .csv, JSON otherwise).
+        #[clap(short, long)]
+        report_path: Option<PathBuf>,
     },
     /// Step 0: Mock proof inference of a particular image. This can be helpful to figure out the
     /// right value of `k` and to test the correctness of the circuit.
     MockProof {
-        /// Path to the model (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
         #[clap(short, long)]
-        model_path: PathBuf,
+        model_path: Option<PathBuf>,
         /// Path to the image (e.g. benches/example_image_7.png)
         #[clap(short, long)]
         img_path: PathBuf,
         /// The value `k` used for the powers of tau. The size of the SRS will be `2^k`.
         #[clap(short, long)]
-        k: u32,
+        k: Option<u32>,
+    },
+    /// Run the mock prover for a particular image and print all constraint failures, grouped by
+    /// the chip (and with --annotate-regions, the pixel, filter or class) they occurred in. Faster than `mock-proof`, as no layout is plotted.
+    MockProve {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path to the image (e.g. benches/example_image_7.png)
+        #[clap(short, long)]
+        img_path: PathBuf,
+        /// The value `k` used for the powers of tau. The size of the SRS will be `2^k`.
+        #[clap(short, long)]
+        k: Option<u32>,
+        /// Name regions after the chip and the pixel, filter or class they belong to, so that
+        /// failures can be traced back to them
+        #[clap(long)]
+        annotate_regions: bool,
+    },
+    /// Plot the circuit layout of a model, as PNG or SVG (depending on the file extension).
+    /// Regions are named after the chip and the pixel, filter or class they belong to.
+    PlotLayout {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// The value `k` used for the powers of tau. The size of the SRS will be `2^k`.
+        #[clap(short, long)]
+        k: Option<u32>,
+        /// Path of the plot
+        #[clap(short, long, default_value = "real_wnn_layout.png")]
+        output_path: PathBuf,
+        /// Width of the plot in pixels
+        #[clap(long)]
+        width: Option<u32>,
+        /// Height of the plot in pixels (by default, 8 per row, up to 16384)
+        #[clap(long)]
+        height: Option<u32>,
+        /// First row to plot
+        #[clap(long)]
+        from_row: Option<usize>,
+        /// Row after the last row to plot
+        #[clap(long)]
+        to_row: Option<usize>,
+        /// Only plot the rows of regions whose name contains this string (e.g. "HashChip filter 3")
+        #[clap(long)]
+        region: Option<String>,
+    },
+    /// Export the structure of the circuit of a model (chips, shared columns, lookup tables and
+    /// region counts) as a Graphviz DOT file, e.g. to render with `dot -Tsvg circuit.dot`
+    ExportGraph {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path of the DOT file
+        #[clap(short, long, default_value = "circuit.dot")]
+        output_path: PathBuf,
+    },
+    /// Write JSON test vectors (image bits, packed filter inputs, hashes, bloom filter indices,
+    /// responses and scores) for images of a dataset, to check other implementations against
+    /// the circuit semantics, or check existing test vectors against the model
+    TestVectors {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path to the dataset to take the images from (same formats as for `evaluate`)
+        #[clap(short, long)]
+        test_set_path: Option<PathBuf>,
+        /// Number of images to write test vectors for
+        #[clap(short, long, default_value_t = 10)]
+        num_images: usize,
+        /// Path of the test vectors
+        #[clap(short, long, default_value = "test_vectors.json")]
+        output_path: PathBuf,
+        /// Instead of writing test vectors, check that the existing ones match the model
+        #[clap(long)]
+        check: bool,
+    },
+    /// Estimate the minimal k, circuit size, key and proof sizes and the proving time for a model
+    Estimate {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+    },
+    /// Suggest the circuit configuration for a model: Tries all combinations of the range check
+    /// window, the class lookup and the number of instance columns, and reports them ordered by
+    /// the minimal k or the estimated proving time
+    SuggestParams {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// What to minimize: k or proving-time
+        #[clap(default_value = "k", long)]
+        objective: Objective,
+        /// Write the model with the best configuration to this path (in .zgm format)
+        #[clap(short, long)]
+        output_path: Option<PathBuf>,
+    },
+    /// Benchmark key generation and proving, with timings per proving phase and the peak memory usage
+    Bench {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path to the image (e.g. benches/example_image_7.png)
+        #[clap(short, long)]
+        img_path: PathBuf,
+        /// Path to read the SRS from
+        #[clap(short, long)]
+        srs_path: Option<PathBuf>,
+        /// Number of proofs to generate
+        #[clap(default_value_t = 10, short, long)]
+        num_proofs: usize,
+        /// Optional path to write the results to as JSON (e.g. to track regressions)
+        #[clap(short, long)]
+        output_path: Option<PathBuf>,
+        /// Append a JSON telemetry record (timings, peak memory, proof size, errors) per proof
+        /// to this file
+        #[clap(long)]
+        telemetry_path: Option<PathBuf>,
     },
     /// Step 1: Generate the SRS
     GenerateSrs {
@@ -71,11 +333,41 @@ enum Commands {
         #[clap(short, long)]
         srs_path: PathBuf,
     },
+    /// Step 1 (alternative): Get an SRS, choosing `k` from the model if not given, and store it
+    /// under `srs/k<k>.srs` in an artifact directory (usable with `setup --srs-store`)
+    GenSrs {
+        /// Path to the model, in HDF5 or .zgm format, used to determine the smallest `k`
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// The value `k` used for the powers of tau. The size of the SRS will be `2^k`.
+        #[clap(short, long)]
+        k: Option<u32>,
+        /// Download the SRS of the perpetual powers of tau ceremony instead of generating
+        /// an (insecure) SRS for development
+        #[cfg(feature = "download")]
+        #[clap(long)]
+        ceremony: bool,
+        /// The artifact directory to store the SRS in
+        #[clap(short, long)]
+        output_dir: PathBuf,
+    },
+    /// Derive an SRS of size `2^k` from a larger SRS (e.g. from a ceremony)
+    DownsizeSrs {
+        /// Path to read the larger SRS from
+        #[clap(short, long)]
+        srs_path: PathBuf,
+        /// The value `k` of the derived SRS, at most the `k` of the larger SRS
+        #[clap(short, long)]
+        k: u32,
+        /// Path to write the derived SRS to
+        #[clap(short, long)]
+        output_path: PathBuf,
+    },
     /// Step 2: Generate the proving and verifying keys
     GenerateKeys {
-        /// Path to the model (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
         #[clap(short, long)]
-        model_path: PathBuf,
+        model_path: Option<PathBuf>,
         /// Path to read the SRS from
         #[clap(short, long)]
         srs_path: PathBuf,
@@ -88,71 +380,237 @@ enum Commands {
         /// Path to write the circuit params to
         #[clap(short, long)]
         circuit_params_path: PathBuf,
+        /// Optional path to write a verifier bundle to, which contains everything needed to
+        /// verify proofs (SRS, verifying key, circuit params and model commitment)
+        #[clap(short, long)]
+        bundle_path: Option<PathBuf>,
+        /// Optional path to write a verifier key (JSON) to, which verifies proofs with the
+        /// zero_g_verifier crate, without depending on the rest of zero_g
+        #[clap(long)]
+        verifier_key_path: Option<PathBuf>,
+        /// Format of the proving key: raw-bytes, raw-bytes-unchecked (same file, but not checked
+        /// when read, only for trusted keys) or processed (smaller, but slower to read)
+        #[clap(default_value = "raw-bytes", long)]
+        key_format: KeyFormat,
+    },
+    /// Steps 1 and 2 in one: Get the SRS, generate the keys and write all artifacts (SRS,
+    /// keys and circuit params) into an artifact directory, described by a manifest
+    Setup {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// The value `k` used for the powers of tau. Defaults to the smallest `k` that fits the circuit.
+        #[clap(short, long)]
+        k: Option<u32>,
+        /// Optional path to read the SRS from. If neither this nor `srs_store` is given,
+        /// a new SRS is generated, which is insecure and only suitable for testing!
+        #[clap(short, long, conflicts_with = "srs_store")]
+        srs_path: Option<PathBuf>,
+        /// Optional artifact store (directory, HTTP(S) or S3 URL) to fetch the SRS from,
+        /// under the key `srs/k<k>.srs`
+        #[clap(long)]
+        srs_store: Option<String>,
+        /// Directory to write the artifacts to (defaults to `artifact_dir` of the project file)
+        #[clap(short, long)]
+        output_dir: Option<PathBuf>,
+        /// Format of the proving key: raw-bytes, raw-bytes-unchecked (same file, but not checked
+        /// when read, only for trusted keys) or processed (smaller, but slower to read)
+        #[clap(default_value = "raw-bytes", long)]
+        key_format: KeyFormat,
+    },
+    /// Prove and verify inference of an image in one step. The SRS, the proving key and the
+    /// proof are cached by content hash, so repeated runs only do the work that is missing
+    Run {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path to the image (e.g. benches/example_image_7.png)
+        #[clap(short, long)]
+        img_path: PathBuf,
+        /// The value `k` used for the powers of tau. Defaults to the smallest `k` that fits the circuit.
+        #[clap(short, long)]
+        k: Option<u32>,
+        /// Optional path to read the SRS from. If neither this nor `srs_store` is given,
+        /// a new SRS is generated, which is insecure and only suitable for testing!
+        #[clap(short, long, conflicts_with = "srs_store")]
+        srs_path: Option<PathBuf>,
+        /// Optional artifact store (directory, HTTP(S) or S3 URL) to fetch the SRS from,
+        /// under the key `srs/k<k>.srs`
+        #[clap(long)]
+        srs_store: Option<String>,
+        /// Directory to cache the artifacts in
+        #[clap(short, long, default_value = ".zero_g_cache")]
+        cache_dir: PathBuf,
+    },
+    /// Check that a model, circuit params and verifying key belong together
+    CheckConsistency {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path to read the verifying key from
+        #[clap(short, long)]
+        vk_path: Option<PathBuf>,
+        /// Path to read the circuit params from
+        #[clap(short, long)]
+        circuit_params_path: Option<PathBuf>,
+        /// Optional path to read the SRS from. If given, the verifying key is regenerated
+        /// to detect keys generated for a different model with the same parameters.
+        #[clap(short, long)]
+        srs_path: Option<PathBuf>,
     },
     /// Step 2.1: Generate the EVM verifier and run a test proof
     DryRunEvmVerifier {
-        /// Path to the model (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
         #[clap(short, long)]
-        model_path: PathBuf,
+        model_path: Option<PathBuf>,
         /// Path to the image (e.g. benches/example_image_7.png)
         #[clap(short, long)]
         img_path: PathBuf,
         /// Path to read the SRS from
         #[clap(short, long)]
-        srs_path: PathBuf,
+        srs_path: Option<PathBuf>,
         /// Path to read the proving key from (used to simulate test a proof and extract the verifying key)
         #[clap(short, long)]
-        pk_path: PathBuf,
+        pk_path: Option<PathBuf>,
     },
     /// Step 2.2: Generate and deploy the EVM verifier
     DeployEvmVerifier {
         /// Path to read the SRS from
         #[clap(short, long)]
-        srs_path: PathBuf,
+        srs_path: Option<PathBuf>,
         /// Path to read the verifying key from
         #[clap(short, long)]
-        vk_path: PathBuf,
-        /// Path to read the circuit params from
+        vk_path: Option<PathBuf>,
+        /// Path to read the circuit params from. Defaults to the params embedded in the
+        /// verifying key, if not set in the project file either.
         #[clap(short, long)]
-        circuit_params_path: PathBuf,
+        circuit_params_path: Option<PathBuf>,
         /// The HTTP endpoint to the chain, or "anvil" to use the Anvil testnet.
         /// If not "anvil", the "ETH_PRIVATE_KEY" must be set to your private key.
         #[clap(default_value_t = String::from("anvil"), short, long)]
         endpoint: String,
     },
+    /// Step 2.3: Export the EVM verifier: The Yul contract and its bytecode, a Solidity contract
+    /// with a typed `verify` function and its ABI, and optionally example calldata for a proof
+    ExportEvm {
+        /// Path to read the SRS from
+        #[clap(short, long)]
+        srs_path: Option<PathBuf>,
+        /// Path to read the verifying key from
+        #[clap(short, long)]
+        vk_path: Option<PathBuf>,
+        /// Path to read the circuit params from. Defaults to the params embedded in the
+        /// verifying key, if not set in the project file either.
+        #[clap(short, long)]
+        circuit_params_path: Option<PathBuf>,
+        /// Optional path to a proof to generate example calldata for
+        #[clap(short, long)]
+        proof_path: Option<PathBuf>,
+        /// Directory to write the contracts, ABI and calldata to
+        #[clap(short, long)]
+        output_dir: PathBuf,
+    },
+    /// Print the values to register a model with an on-chain model registry
+    RegistrationPayload {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path to read the verifying key from
+        #[clap(short, long)]
+        vk_path: Option<PathBuf>,
+        /// Address of the deployed verifier contract
+        #[clap(long)]
+        verifier: Address,
+    },
     /// Step 3: Proof inference of a particular image
     Proof {
-        /// Path to the model (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
         #[clap(short, long)]
-        model_path: PathBuf,
+        model_path: Option<PathBuf>,
         /// Path to the image (e.g. benches/example_image_7.png)
-        #[clap(short, long)]
-        img_path: PathBuf,
+        #[clap(short, long, required_unless_present = "features")]
+        img_path: Option<PathBuf>,
+        /// For tabular models: The feature vector, comma-separated (e.g. 3,1000,65535)
+        #[clap(long, value_delimiter = ',', conflicts_with = "img_path")]
+        features: Option<Vec<u16>>,
         /// Path to read the SRS from
         #[clap(short, long)]
-        srs_path: PathBuf,
+        srs_path: Option<PathBuf>,
         /// Path to read the proving key from
         #[clap(short, long)]
-        pk_path: PathBuf,
-        /// Path to store the proof to
+        pk_path: Option<PathBuf>,
+        /// Path to store the proof to (e.g. proof.zgp)
         #[clap(short, long)]
         proof_path: PathBuf,
+        /// Format of the proving key: raw-bytes, raw-bytes-unchecked (skips the checks of the
+        /// key, faster but only for trusted keys) or processed
+        #[clap(default_value = "raw-bytes", long)]
+        key_format: KeyFormat,
+        /// For models that link proofs: The chaining value of the previous image (hex encoded).
+        /// Defaults to starting a new chain
+        #[clap(long)]
+        previous_chaining_value: Option<String>,
     },
-    /// Step 4: Verify the proof
+    /// Step 3 (batch): Prove every image in a directory. Proof files and a manifest are
+    /// written to the output directory; images proven in a previous run are skipped.
+    ProveDir {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path to the directory of images (e.g. data/MNIST/png)
+        #[clap(short, long)]
+        img_dir: PathBuf,
+        /// Path to read the SRS from
+        #[clap(short, long)]
+        srs_path: Option<PathBuf>,
+        /// Path to read the proving key from
+        #[clap(short, long)]
+        pk_path: Option<PathBuf>,
+        /// Directory to write the proofs and the manifest to
+        #[clap(short, long)]
+        output_dir: PathBuf,
+        /// Number of images proven at the same time
+        #[clap(default_value_t = 1, short, long)]
+        workers: usize,
+        /// Don't start new proofs while the process uses more memory than this (in MiB)
+        #[clap(long)]
+        memory_budget: Option<u64>,
+        /// Format of the proving key: raw-bytes, raw-bytes-unchecked (skips the checks of the
+        /// key, faster but only for trusted keys) or processed
+        #[clap(default_value = "raw-bytes", long)]
+        key_format: KeyFormat,
+    },
+    /// Step 4: Verify the proof, either against a verifier bundle or against the SRS,
+    /// verifying key and circuit params
     Verify {
+        /// Path to read the verifier bundle from
+        #[clap(short, long, conflicts_with_all = ["srs_path", "vk_path", "circuit_params_path"])]
+        bundle_path: Option<PathBuf>,
         /// Path to read the SRS from
         #[clap(short, long)]
-        srs_path: PathBuf,
+        srs_path: Option<PathBuf>,
         /// Path to read the verifying key from
         #[clap(short, long)]
-        vk_path: PathBuf,
-        /// Path to read the circuit params from
+        vk_path: Option<PathBuf>,
+        /// Path to read the circuit params from. Defaults to the params embedded in the
+        /// verifying key, if not set in the project file either.
         #[clap(short, long)]
-        circuit_params_path: PathBuf,
+        circuit_params_path: Option<PathBuf>,
         /// Path to read the proof from
         #[clap(short, long)]
         proof_path: PathBuf,
     },
+    /// Step 4 (batch): Verify all proofs listed in a manifest written by `prove-dir` against a
+    /// verifier bundle. The proofs are verified in a single batch, which is faster than
+    /// verifying them one by one.
+    VerifyBatch {
+        /// Path to read the verifier bundle from
+        #[clap(short, long)]
+        bundle_path: PathBuf,
+        /// Path to the manifest (e.g. proofs/manifest.json)
+        #[clap(short, long)]
+        manifest_path: PathBuf,
+    },
     /// Step 4.1: Submit the proof to the (deployed) EVM verifier
     SubmitProof {
         /// Path to read the proof from
@@ -166,49 +624,442 @@ enum Commands {
         #[clap(default_value_t = String::from("anvil"), short, long)]
         endpoint: String,
     },
+    /// Shrink the bloom filters of a model to the entries needed on a calibration dataset
+    /// (e.g. the training set), and write the result in the .zgm format
+    PruneModel {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path to the calibration set (same formats as for `evaluate`)
+        #[clap(short, long)]
+        calibration_set_path: PathBuf,
+        /// Optional CSV or JSON file mapping file names to classes.
+        /// By default, the class is parsed from the file name (e.g. 7 for 0000_7.png).
+        #[clap(short, long)]
+        labels_path: Option<PathBuf>,
+        /// Number of entries of each bloom filter in the pruned model (a power of two)
+        #[clap(short, long)]
+        num_filter_entries: usize,
+        /// Path to write the pruned model to (e.g. pruned.zgm)
+        #[clap(short, long)]
+        output_path: PathBuf,
+    },
+    /// Convert an HDF5 model into the .zgm format, which can be loaded without the HDF5 library
+    #[cfg(feature = "hdf5")]
+    ConvertModel {
+        /// Path to the HDF5 model (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: PathBuf,
+        /// Path to write the converted model to (e.g. model.zgm)
+        #[clap(short, long)]
+        output_path: PathBuf,
+        /// How scaled thresholds are rounded: ceil, floor or round
+        #[clap(default_value = "ceil", long)]
+        threshold_rounding: Rounding,
+        /// The smallest quantized threshold
+        #[clap(default_value_t = 0, long)]
+        threshold_min: u16,
+        /// The largest quantized threshold (256 means the bit is never set)
+        #[clap(default_value_t = 256, long)]
+        threshold_max: u16,
+        /// The order of the pixels of the images to predict, relative to the training images:
+        /// row-major (unchanged) or column-major (transposed)
+        #[clap(default_value = "row-major", long)]
+        pixel_order: PixelOrder,
+    },
+    /// Download the MNIST dataset and export the test set as PNG files
+    #[cfg(feature = "download")]
+    FetchMnist {
+        /// Directory to download the dataset to
+        #[clap(default_value = "data/MNIST", short, long)]
+        dir: PathBuf,
+    },
+    /// Download a published model (e.g. mnist-small)
+    #[cfg(feature = "download")]
+    FetchModel {
+        /// Name of the model, one of mnist-tiny, mnist-small, mnist-medium and mnist-large
+        name: String,
+        /// Directory to download the model to
+        #[clap(default_value = "models", short, long)]
+        dir: PathBuf,
+    },
+    /// Watch a directory and prove every image placed into it with the artifacts of `setup`,
+    /// until interrupted. Proofs and the manifest are written like `prove-dir` does, so a
+    /// restarted daemon continues where it stopped.
+    Daemon {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// The artifact directory written by `setup`
+        #[clap(short, long)]
+        artifact_dir: Option<PathBuf>,
+        /// Directory to watch for images
+        #[clap(short, long)]
+        input_dir: PathBuf,
+        /// Directory to write the proofs and the manifest to
+        #[clap(short, long)]
+        output_dir: PathBuf,
+        /// Number of images proven at the same time
+        #[clap(default_value_t = 1, short, long)]
+        workers: usize,
+        /// Don't start new proofs while the process uses more memory than this (in MiB)
+        #[clap(long)]
+        memory_budget: Option<u64>,
+        /// Seconds between scans of the input directory
+        #[clap(default_value_t = 5, long)]
+        poll_interval: u64,
+        /// Append a JSON telemetry record (timings, peak memory, proof size, errors) per proof
+        /// to this file
+        #[clap(long)]
+        telemetry_path: Option<PathBuf>,
+    },
+    /// Run an HTTP service proving images with the artifacts of `setup` (see the `server` module
+    /// for the API)
+    #[cfg(feature = "server")]
+    Serve {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// The artifact directory written by `setup`
+        #[clap(short, long)]
+        artifact_dir: Option<PathBuf>,
+        /// Directory to write the proofs to
+        #[clap(default_value = "jobs", short, long)]
+        jobs_dir: PathBuf,
+        /// Maximum number of proofs generated at the same time
+        #[clap(default_value_t = 1, short, long)]
+        concurrency: usize,
+        /// Address to listen on
+        #[clap(default_value = "127.0.0.1:8080", long)]
+        address: SocketAddr,
+        /// Append a JSON telemetry record (timings, peak memory, proof size, errors) per proof
+        /// to this file
+        #[clap(long)]
+        telemetry_path: Option<PathBuf>,
+    },
+    /// Run a gRPC service proving and verifying images with the artifacts of `setup` (see
+    /// proto/zero_g.proto for the service definition)
+    #[cfg(feature = "grpc")]
+    ServeGrpc {
+        /// Path to the model, in HDF5 or .zgm format (e.g. models/model_28input_2048entry_2hash_3bpi.hdf5)
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// The artifact directory written by `setup`
+        #[clap(short, long)]
+        artifact_dir: Option<PathBuf>,
+        /// Directory to write the proofs to
+        #[clap(default_value = "jobs", short, long)]
+        jobs_dir: PathBuf,
+        /// Maximum number of proofs generated at the same time
+        #[clap(default_value_t = 1, short, long)]
+        concurrency: usize,
+        /// Address to listen on
+        #[clap(default_value = "127.0.0.1:50051", long)]
+        address: SocketAddr,
+        /// Append a JSON telemetry record (timings, peak memory, proof size, errors) per proof
+        /// to this file
+        #[clap(long)]
+        telemetry_path: Option<PathBuf>,
+    },
+    /// Rewrite a proof file written by an older version in the current format
+    UpgradeProof {
+        /// Path to the proof file, which is overwritten in place
+        #[clap(short, long)]
+        proof_path: PathBuf,
+    },
+    /// Add a header (crate version, circuit params and k) to key files written by older
+    /// versions, so that they are validated when read
+    UpgradeKeys {
+        /// Path to the model the keys were generated for, in HDF5 or .zgm format
+        #[clap(short, long)]
+        model_path: Option<PathBuf>,
+        /// Path to the proving key, which is overwritten in place
+        #[clap(short, long)]
+        pk_path: Option<PathBuf>,
+        /// Path to the verifying key, which is overwritten in place
+        #[clap(short, long)]
+        vk_path: Option<PathBuf>,
+    },
+    /// Compare two verifying keys (or a verifying key and a recorded fingerprint) and report
+    /// whether proofs are interchangeable and, if not, which parameter differs. Fails if they
+    /// are not
+    DiffKeys {
+        /// Path to the first verifying key
+        #[clap(long)]
+        left: PathBuf,
+        /// Path to the second verifying key
+        #[clap(long, required_unless_present = "fingerprint")]
+        right: Option<PathBuf>,
+        /// A VK fingerprint (e.g. from a proof file or the registry) or a constraint system
+        /// fingerprint to compare the first key with, instead of a second key
+        #[clap(long, conflicts_with = "right")]
+        fingerprint: Option<String>,
+        /// Path to read the circuit params from, for keys written by versions that didn't
+        /// embed them
+        #[clap(short, long)]
+        circuit_params_path: Option<PathBuf>,
+    },
+}
+
+/// Writes the output of a command: Human-readable text by default, or a single JSON object
+/// with `--json`. In JSON mode, human-readable messages are written to stderr instead, so
+/// that stdout only contains the JSON object.
+struct Output {
+    json: bool,
+    emitted: Cell<bool>,
+}
+
+impl Output {
+    fn say(&self, message: impl Display) {
+        if self.json {
+            eprintln!("{message}");
+        } else {
+            println!("{message}");
+        }
+    }
+
+    /// Emits the JSON result of the command (only in JSON mode).
+    fn emit(&self, value: Value) {
+        if self.json {
+            println!("{value}");
+            self.emitted.set(true);
+        }
+    }
 }
 
+/// Like `println!`, but respecting the output mode (see [`Output`]).
+macro_rules! say {
+    ($out:expr) => {
+        $out.say("")
+    };
+    ($out:expr, $($arg:tt)*) => {
+        $out.say(format_args!($($arg)*))
+    };
+}
+
+/// Counts the heap allocations, so that the peak memory usage of key generation and proving
+/// can be reported and memory budgets refer to the heap (see `zero_g::memory`).
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args: Arguments = Arguments::parse();
+    init_tracing(args.verbose);
+    let out = Output {
+        json: args.json,
+        emitted: Cell::new(false),
+    };
+
+    let result = match load_config(args.config.as_deref()) {
+        Ok(config) => run(args.command, &out, &config).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = &result {
+        if out.json && !out.emitted.get() {
+            println!("{}", json!({ "error": format!("{e:#}") }));
+        }
+    }
+    result
+}
 
-    match args.command {
+async fn run(command: Commands, out: &Output, config: &ProjectConfig) -> Result<()> {
+    match command {
         Commands::Predict {
             model_path,
             img_path,
+            explain,
         } => {
-            let wnn = load_wnn(&model_path)?;
-            let img = load_grayscale_image(&img_path).unwrap();
-            println!("{:?}", wnn.predict(&img));
+            let wnn = load_project_model(config, model_path)?;
+            let img = load_grayscale_image(&img_path)?;
+            let responses = wnn.predict_with_responses(&img);
+            let scores = responses.scores();
+            let class = argmax(&scores);
+            if wnn.is_regression() {
+                say!(out, "Output: {}", scores[0]);
+            } else {
+                say!(out, "Scores: {scores:?}");
+                say!(out, "Predicted class: {class}");
+            }
+            if !explain {
+                out.emit(if wnn.is_regression() {
+                    json!({ "output": scores[0] })
+                } else {
+                    json!({ "scores": scores, "class": class })
+                });
+                return Ok(());
+            }
 
+            // Number of positive filters of the predicted class that each pixel is an input to
+            let heatmap = responses.heatmap(class);
+            let max = heatmap.iter().copied().max().unwrap_or(0).max(1);
+            say!(
+                out,
+                "\nPixels driving the prediction (darker = more positive filters):"
+            );
+            for row in heatmap.rows() {
+                let line: String = row
+                    .iter()
+                    .map(|count| {
+                        [' ', '.', ':', '*', '#'][(*count * 4 + max - 1) as usize / max as usize]
+                    })
+                    .collect();
+                say!(out, "  |{line}|");
+            }
+            let positive_filters: Vec<_> = (0..scores.len())
+                .map(|class| responses.positive_filters(class))
+                .collect();
+            out.emit(json!({
+                "scores": scores,
+                "class": class,
+                "positive_filters": positive_filters,
+                "filter_pixels": responses.filter_pixels,
+                "heatmap": heatmap.rows().into_iter().map(|row| row.to_vec()).collect::<Vec<_>>(),
+            }));
+
+            Ok(())
+        }
+        Commands::Inspect { model_path } => {
+            let wnn = load_project_model(config, model_path)?;
+            let info = wnn.stats();
+            say!(out, "{info}");
+            out.emit(serde_json::to_value(&info)?);
             Ok(())
         }
-        Commands::ComputeAccuracy {
+        Commands::AnalyzeFilters {
             model_path,
             test_set_path,
+            labels_path,
         } => {
-            let wnn = load_wnn(&model_path)?;
-
-            let mut correct = 0;
-            let mut total = 0;
+            let wnn = load_project_model(config, model_path)?;
+            let analysis = FalsePositiveAnalysis::new(&wnn);
+            say!(out, "{analysis}");
+            let distribution = match test_set_path.or_else(|| config.datasets.test_set.clone()) {
+                Some(test_set_path) => {
+                    let dataset = match labels_path.or_else(|| config.datasets.labels.clone()) {
+                        Some(labels_path) => Dataset::directory(
+                            &test_set_path,
+                            &LabelSource::read_sidecar(&labels_path)?,
+                        )?,
+                        None => Dataset::open(&test_set_path)?,
+                    };
+                    let distribution = ScoreDistribution::simulate(
+                        &wnn,
+                        dataset.par_iter().progress_count(dataset.len() as u64),
+                    )?;
+                    say!(out, "\n{distribution}");
+                    Some(distribution)
+                }
+                None => None,
+            };
+            out.emit(json!({
+                "false_positives": analysis,
+                "score_distribution": distribution,
+            }));
+            Ok(())
+        }
+        Commands::CommitImage {
+            img_path,
+            salt,
+            random_salt,
+        } => {
+            let img = load_grayscale_image(&img_path)?;
+            let salt: Option<[u8; SALT_SIZE]> = match salt {
+                Some(salt) => Some(
+                    hex::decode(salt.trim_start_matches("0x"))?
+                        .try_into()
+                        .map_err(|_| eyre::eyre!("The salt must be {SALT_SIZE} bytes long"))?,
+                ),
+                None if random_salt || config.commitment == CommitmentMode::RandomSalt => {
+                    let mut salt = [0; SALT_SIZE];
+                    OsRng.fill_bytes(&mut salt);
+                    say!(out, "Salt: {}", to_hex(salt));
+                    Some(salt)
+                }
+                None => None,
+            };
+            let commitment = image_commitment(&img, salt.as_ref());
+            say!(out, "Image commitment: {}", to_hex(commitment));
+            out.emit(json!({
+                "commitment": to_hex(commitment),
+                "salt": salt.map(to_hex),
+            }));
+            Ok(())
+        }
+        #[cfg(feature = "hdf5")]
+        Commands::AuditQuantization {
+            model_path,
+            test_set_path,
+            labels_path,
+            threshold_rounding,
+            threshold_min,
+            threshold_max,
+        } => {
+            let model_path = required(model_path.or_else(|| config.model.clone()), "model-path")?;
+            let policy = QuantizationPolicy {
+                rounding: threshold_rounding,
+                min: threshold_min,
+                max: threshold_max,
+            };
+            let hdf5_error =
+                |e| eyre::eyre!("Unable to read HDF5 model {}: {e}", model_path.display());
+            let wnn = load_wnn_with_policy(&model_path, &policy).map_err(hdf5_error)?;
+            let float_thresholds = load_float_thresholds(&model_path).map_err(hdf5_error)?;
+            let test_set_path = required(
+                test_set_path.or_else(|| config.datasets.test_set.clone()),
+                "test-set-path",
+            )?;
+            let dataset = match labels_path.or_else(|| config.datasets.labels.clone()) {
+                Some(labels_path) => {
+                    Dataset::directory(&test_set_path, &LabelSource::read_sidecar(&labels_path)?)?
+                }
+                None => Dataset::open(&test_set_path)?,
+            };
 
-            let dir_entries: Vec<_> = fs::read_dir(test_set_path).unwrap().collect();
-            for dir_entry in dir_entries.into_iter().progress() {
-                let img_path = dir_entry.unwrap().path();
+            let audit = QuantizationAudit::run(
+                &wnn,
+                &float_thresholds,
+                dataset.par_iter().progress_count(dataset.len() as u64),
+            )?;
+            say!(out, "{audit}");
+            out.emit(json!({
+                "quantization_policy": policy,
+                "equivalent": audit.is_equivalent(),
+                "audit": audit,
+            }));
+            Ok(())
+        }
+        Commands::Evaluate {
+            model_path,
+            test_set_path,
+            labels_path,
+            report_path,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let test_set_path = required(
+                test_set_path.or_else(|| config.datasets.test_set.clone()),
+                "test-set-path",
+            )?;
+            let dataset = match labels_path.or_else(|| config.datasets.labels.clone()) {
+                Some(labels_path) => {
+                    Dataset::directory(&test_set_path, &LabelSource::read_sidecar(&labels_path)?)?
+                }
+                None => Dataset::open(&test_set_path)?,
+            };
 
-                if let Some(correct_class) = parse_png_file(&img_path) {
-                    let img = load_grayscale_image(&img_path).unwrap();
-                    let scores = wnn.predict(&img);
-                    let prediction = argmax(&scores);
+            let report =
+                wnn.evaluate_examples(dataset.par_iter().progress_count(dataset.len() as u64))?;
 
-                    if prediction == correct_class {
-                        correct += 1;
-                    }
-                    total += 1;
-                }
+            say!(out, "{report}");
+            if let Some(report_path) = &report_path {
+                report.write(report_path)?;
             }
-
-            println!("Accuracy: {} / {}", correct, total);
+            out.emit(json!({
+                "accuracy": report.accuracy(),
+                "class_metrics": report.class_metrics(),
+                "confusion_matrix": report.confusion_matrix,
+                "report_path": report_path,
+            }));
 
             Ok(())
         }
@@ -217,20 +1068,280 @@ async fn main() -> Result<()> {
             img_path,
             k,
         } => {
-            let wnn = load_wnn(&model_path)?;
-            let img = load_grayscale_image(&img_path).unwrap();
-            println!("Prediction: {:?}", wnn.predict(&img));
+            let wnn = load_project_model(config, model_path)?;
+            let img = load_grayscale_image(&img_path)?;
+            let scores = wnn.predict(&img);
+            say!(out, "Prediction: {scores:?}");
 
-            println!("Verifying constraints...");
+            say!(out, "Verifying constraints...");
+            let k = required(k.or(config.k), "k")?;
             wnn.mock_proof(&img, k);
-            println!("Valid!");
+            say!(out, "Valid!");
 
             wnn.plot_circuit("real_wnn_layout.png", k);
+            out.emit(json!({
+                "scores": scores,
+                "valid": true,
+                "layout_path": "real_wnn_layout.png",
+            }));
+            Ok(())
+        }
+        Commands::MockProve {
+            model_path,
+            img_path,
+            k,
+            annotate_regions,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let img = load_grayscale_image(&img_path)?;
+            let scores = wnn.predict(&img);
+            say!(out, "Prediction: {scores:?}");
+
+            let k = required(k.or(config.k), "k")?;
+            let failures = mock_prove(&wnn, &img, k, annotate_regions)?;
+            let descriptions: Vec<_> = failures.iter().map(describe_failure).collect();
+            out.emit(json!({ "scores": scores, "failures": descriptions }));
+            if failures.is_empty() {
+                say!(out, "All constraints are satisfied!");
+                return Ok(());
+            }
+            say!(out, "{}", Diagnosis::new(&failures, Some(&wnn)));
+            eyre::bail!("{} constraints are not satisfied", failures.len())
+        }
+        Commands::PlotLayout {
+            model_path,
+            k,
+            output_path,
+            width,
+            height,
+            from_row,
+            to_row,
+            region,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let k = required(k.or(config.k), "k")?;
+            let rows = (from_row.is_some() || to_row.is_some())
+                .then(|| from_row.unwrap_or(0)..to_row.unwrap_or(1 << k));
+            let options = PlotOptions {
+                format: None,
+                width,
+                height,
+                rows,
+                region_filter: region,
+            };
+            wnn.plot_circuit_with_options(&output_path, k, &options)
+                .map_err(|e| eyre::eyre!("Could not plot the layout: {e}"))?;
+            say!(out, "Wrote layout to {}", output_path.display());
+            out.emit(json!({ "layout_path": output_path }));
+            Ok(())
+        }
+        Commands::ExportGraph {
+            model_path,
+            output_path,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let graph = wnn.circuit_graph()?;
+            graph.write_dot(&output_path)?;
+            say!(
+                out,
+                "Wrote graph of {} chips and {} lookup tables to {}",
+                graph.chips.len(),
+                graph.tables.len(),
+                output_path.display()
+            );
+            out.emit(json!({
+                "graph_path": output_path,
+                "chips": graph.chips.iter().map(|chip| json!({
+                    "name": chip.name,
+                    "gates": chip.gates,
+                    "num_regions": chip.num_regions,
+                })).collect::<Vec<_>>(),
+                "tables": graph.tables.iter().map(|table| &table.name).collect::<Vec<_>>(),
+            }));
+            Ok(())
+        }
+        Commands::TestVectors {
+            model_path,
+            test_set_path,
+            num_images,
+            output_path,
+            check,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            if check {
+                let vectors = TestVectors::read(&output_path)?;
+                let mismatches = vectors.mismatches(&wnn);
+                say!(
+                    out,
+                    "{} of {} test vectors match",
+                    vectors.vectors.len() - mismatches.len(),
+                    vectors.vectors.len()
+                );
+                for id in &mismatches {
+                    say!(out, "  Mismatch: {id}");
+                }
+                out.emit(json!({ "num_vectors": vectors.vectors.len(), "mismatches": mismatches }));
+                if !mismatches.is_empty() {
+                    eyre::bail!("{} test vectors don't match", mismatches.len());
+                }
+                return Ok(());
+            }
+
+            let test_set_path = required(
+                test_set_path.or_else(|| config.datasets.test_set.clone()),
+                "test-set-path",
+            )?;
+            let examples = Dataset::open(&test_set_path)?
+                .iter()
+                .take(num_images)
+                .collect::<Result<Vec<_>, _>>()?;
+            let vectors = TestVectors::new(
+                &wnn,
+                examples
+                    .iter()
+                    .map(|example| (example.id.as_str(), &example.image)),
+            );
+            vectors.write(&output_path)?;
+            say!(
+                out,
+                "Wrote {} test vectors to {}",
+                vectors.vectors.len(),
+                output_path.display()
+            );
+            out.emit(json!({
+                "num_vectors": vectors.vectors.len(),
+                "output_path": output_path,
+            }));
+            Ok(())
+        }
+        Commands::Estimate { model_path } => {
+            let wnn = load_project_model(config, model_path)?;
+            let estimate = estimate(&wnn)?;
+            say!(out, "{estimate}");
+            out.emit(serde_json::to_value(&estimate)?);
+            Ok(())
+        }
+        Commands::SuggestParams {
+            model_path,
+            objective,
+            output_path,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let suggestions = suggest_params(&wnn, objective)?;
+            say!(
+                out,
+                "{:<8} {:<14} {:<18} {:>4} {:>14}",
+                "Window",
+                "Class lookup",
+                "Instance columns",
+                "k",
+                "Proving time"
+            );
+            for suggestion in &suggestions {
+                let knobs = suggestion.knobs;
+                say!(
+                    out,
+                    "{:<8} {:<14} {:<18} {:>4} {:>14}",
+                    knobs.window_num_bits,
+                    knobs.class_lookup,
+                    knobs.num_instance_columns,
+                    suggestion.k,
+                    suggestion
+                        .proving_time
+                        .map_or("-".to_string(), |time| format!(
+                            ">{:.1}s",
+                            time.as_secs_f64()
+                        ))
+                );
+            }
+            let best = suggestions
+                .first()
+                .ok_or_else(|| eyre::eyre!("No valid configuration for this model"))?;
+            say!(out, "\nBest configuration: {:?}", best.knobs);
+            if let Some(output_path) = &output_path {
+                save_model(&best.knobs.apply(&wnn), output_path)?;
+                say!(out, "Wrote the model to {}", output_path.display());
+            }
+            out.emit(json!({
+                "objective": objective,
+                "suggestions": suggestions,
+                "output_path": output_path,
+            }));
+            Ok(())
+        }
+        Commands::Bench {
+            model_path,
+            img_path,
+            srs_path,
+            num_proofs,
+            output_path,
+            telemetry_path,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let img = load_grayscale_image(&img_path)?;
+            let srs_path = artifact_path(srs_path, config, |files| &files.srs, "srs-path")?;
+            let kzg_params = read_srs(&srs_path)?;
+
+            eyre::ensure!(num_proofs > 0, "At least one proof is required");
+            let telemetry = telemetry_path
+                .as_deref()
+                .map(TelemetryLog::open)
+                .transpose()?;
+            let report = run_benchmark(&wnn, &kzg_params, &img, num_proofs, telemetry.as_ref())?;
+            say!(out, "{report}");
+            if let Some(output_path) = output_path {
+                report.write_json(&output_path)?;
+            }
+            out.emit(serde_json::to_value(&report)?);
             Ok(())
         }
         Commands::GenerateSrs { k, srs_path } => {
             let srs = ParamsKZG::<Bn256>::new(k);
-            write_srs(&srs, &srs_path);
+            write_srs(&srs, &srs_path)?;
+            out.emit(json!({ "k": k, "srs_path": srs_path }));
+            Ok(())
+        }
+        Commands::GenSrs {
+            model_path,
+            k,
+            #[cfg(feature = "download")]
+            ceremony,
+            output_dir,
+        } => {
+            let k = k.or(config.k);
+            let wnn = match model_path.or_else(|| config.model.clone()) {
+                Some(model_path) => Some(load_project_model(config, Some(model_path))?),
+                None if k.is_none() => eyre::bail!("Either --model-path or -k is required"),
+                None => None,
+            };
+            #[cfg(feature = "download")]
+            let srs_source = if ceremony {
+                SrsSource::Ceremony
+            } else {
+                SrsSource::Generate
+            };
+            #[cfg(not(feature = "download"))]
+            let srs_source = SrsSource::Generate;
+            if srs_source == SrsSource::Generate {
+                say!(
+                    out,
+                    "Generating a new SRS, which is insecure and only suitable for testing!"
+                );
+            }
+
+            let path = gen_srs(wnn.as_ref(), k, &srs_source, &output_dir)?;
+            say!(out, "Wrote SRS to {}", path.display());
+            out.emit(json!({ "srs_path": path }));
+            Ok(())
+        }
+        Commands::DownsizeSrs {
+            srs_path,
+            k,
+            output_path,
+        } => {
+            let srs = get_srs(&SrsSource::File(srs_path), k)?;
+            write_srs(&srs, &output_path)?;
+            out.emit(json!({ "k": k, "srs_path": output_path }));
             Ok(())
         }
         Commands::GenerateKeys {
@@ -239,35 +1350,192 @@ async fn main() -> Result<()> {
             vk_path,
             pk_path,
             circuit_params_path,
+            bundle_path,
+            verifier_key_path,
+            key_format,
         } => {
-            let wnn = load_wnn(&model_path)?;
-            let kzg_params = read_srs(&srs_path);
-            let pk = wnn.generate_proving_key(&kzg_params);
-            write_keys(&pk, &pk_path, &vk_path);
-            write_circuit_params(&wnn.get_circuit_params(), &circuit_params_path);
+            let wnn = load_project_model(config, model_path)?;
+            let kzg_params = read_srs(&srs_path)?;
+            let pk = wnn.generate_proving_key(&kzg_params)?;
+            write_keys_with_format(
+                &pk,
+                &wnn.get_circuit_params(),
+                &pk_path,
+                &vk_path,
+                key_format,
+            )?;
+            write_circuit_params(&wnn.get_circuit_params(), &circuit_params_path)?;
+            let fingerprint = vk_fingerprint(pk.get_vk(), &wnn.get_circuit_params());
+            say!(out, "Verifying key fingerprint: {}", to_hex(fingerprint));
+            if bundle_path.is_some() || verifier_key_path.is_some() {
+                let bundle = VerifierBundle::new(&wnn, pk.get_vk().clone(), kzg_params);
+                if let Some(bundle_path) = &bundle_path {
+                    bundle
+                        .write(bundle_path)
+                        .expect("Unable to write verifier bundle");
+                }
+                if let Some(verifier_key_path) = &verifier_key_path {
+                    write_verifier_key(&bundle.verifier_key(), verifier_key_path)?;
+                }
+            }
+            out.emit(json!({
+                "vk_fingerprint": to_hex(fingerprint),
+                "pk_path": pk_path,
+                "vk_path": vk_path,
+                "circuit_params_path": circuit_params_path,
+                "bundle_path": bundle_path,
+                "verifier_key_path": verifier_key_path,
+            }));
             Ok(())
         }
+        Commands::Setup {
+            model_path,
+            k,
+            srs_path,
+            srs_store,
+            output_dir,
+            key_format,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let srs_source = match (srs_path, srs_store) {
+                (Some(srs_path), _) => SrsSource::File(srs_path),
+                (None, Some(srs_store)) => SrsSource::Store(srs_store),
+                (None, None) => {
+                    say!(
+                        out,
+                        "Generating a new SRS, which is insecure and only suitable for testing!"
+                    );
+                    SrsSource::Generate
+                }
+            };
+            let output_dir = required(
+                output_dir.or_else(|| config.artifact_dir.clone()),
+                "output-dir",
+            )?;
+            let manifest =
+                setup_with_key_format(&wnn, k.or(config.k), &srs_source, &output_dir, key_format)?;
+            say!(
+                out,
+                "Wrote artifacts for k = {} to {}",
+                manifest.k,
+                output_dir.display()
+            );
+            say!(
+                out,
+                "Verifying key fingerprint: {}",
+                to_hex(manifest.vk_fingerprint)
+            );
+            out.emit(json!({
+                "output_dir": output_dir,
+                "k": manifest.k,
+                "vk_fingerprint": to_hex(manifest.vk_fingerprint),
+                "files": manifest.files,
+                "key_format": manifest.key_format,
+            }));
+            Ok(())
+        }
+        Commands::Run {
+            model_path,
+            img_path,
+            k,
+            srs_path,
+            srs_store,
+            cache_dir,
+        } => {
+            let model_path = required(model_path.or_else(|| config.model.clone()), "model-path")?;
+            let srs_source = match (srs_path, srs_store) {
+                (Some(srs_path), _) => SrsSource::File(srs_path),
+                (None, Some(srs_store)) => SrsSource::Store(srs_store),
+                (None, None) => {
+                    say!(
+                        out,
+                        "Generating a new SRS, which is insecure and only suitable for testing!"
+                    );
+                    SrsSource::Generate
+                }
+            };
+            let mut pipeline = Pipeline::new(cache_dir, srs_source);
+            if let Some(k) = k.or(config.k) {
+                pipeline = pipeline.with_k(k);
+            }
+            let output = pipeline.run(&model_path, &img_path)?;
+            say!(out, "Verified scores: {:?}", output.scores);
+            say!(out, "Proof: {}", output.proof_path.display());
+            out.emit(json!({
+                "scores": output.scores,
+                "proof_path": output.proof_path,
+                "cache_hits": {
+                    "srs": output.cache_hits.srs,
+                    "pk": output.cache_hits.pk,
+                    "proof": output.cache_hits.proof,
+                },
+            }));
+            Ok(())
+        }
+        Commands::CheckConsistency {
+            model_path,
+            vk_path,
+            circuit_params_path,
+            srs_path,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let circuit_params_path = artifact_path(
+                circuit_params_path,
+                config,
+                |files| &files.circuit_params,
+                "circuit-params-path",
+            )?;
+            let circuit_params = read_circuit_params(&circuit_params_path)?;
+            let vk_path = artifact_path(vk_path, config, |files| &files.vk, "vk-path")?;
+            let vk = read_vk(&vk_path, circuit_params.clone())?;
+
+            let mut inconsistencies = check_consistency(&wnn, &circuit_params, &vk)?;
+            if let Some(srs_path) = srs_path {
+                let kzg_params = read_srs(&srs_path)?;
+                inconsistencies.extend(check_key_matches_model(&wnn, &vk, &kzg_params)?);
+            }
+
+            let inconsistencies: Vec<_> = inconsistencies.iter().map(ToString::to_string).collect();
+            out.emit(json!({ "inconsistencies": inconsistencies }));
+            if inconsistencies.is_empty() {
+                say!(
+                    out,
+                    "Model, circuit params and verifying key are consistent"
+                );
+                return Ok(());
+            }
+            for inconsistency in &inconsistencies {
+                say!(out, "{inconsistency}");
+            }
+            eyre::bail!("Found {} inconsistencies", inconsistencies.len())
+        }
         Commands::DryRunEvmVerifier {
             model_path,
             img_path,
             srs_path,
             pk_path,
         } => {
-            let img = load_grayscale_image(&img_path).unwrap();
-            let wnn = load_wnn(&model_path).unwrap();
+            let img = load_grayscale_image(&img_path)?;
+            let wnn = load_project_model(config, model_path)?;
 
-            let kzg_params = read_srs(&srs_path);
-            let pk = read_pk(&pk_path, wnn.get_circuit_params());
+            let srs_path = artifact_path(srs_path, config, |files| &files.srs, "srs-path")?;
+            let kzg_params = read_srs(&srs_path)?;
+            let pk_path = artifact_path(pk_path, config, |files| &files.pk, "pk-path")?;
+            let pk = read_pk(&pk_path, wnn.get_circuit_params())?;
 
-            println!("Generating proof...");
-            let (proof, outputs) = wnn.proof(&pk, &kzg_params, &img);
+            say!(out, "Generating proof...");
+            let (proof, outputs) = wnn.proof(&pk, &kzg_params, &img)?;
+            let layout = InstanceLayout::from_params(&wnn.get_circuit_params());
 
-            println!("Generating EVM verifier...");
-            let deployment_code = gen_evm_verifier(&kzg_params, pk.get_vk(), vec![outputs.len()]);
+            say!(out, "Generating EVM verifier...");
+            let deployment_code =
+                gen_evm_verifier(&kzg_params, pk.get_vk(), layout.column_lengths());
 
-            println!("Dry-running EVM verifier...");
-            let gas_used = dry_run_verifier(deployment_code, vec![outputs], proof).unwrap();
-            println!("=> Gas used: {}", gas_used);
+            say!(out, "Dry-running EVM verifier...");
+            let gas_used =
+                dry_run_verifier(deployment_code, layout.to_columns(&outputs), proof).unwrap();
+            say!(out, "=> Gas used: {}", gas_used);
+            out.emit(json!({ "gas_used": gas_used }));
             Ok(())
         }
         Commands::DeployEvmVerifier {
@@ -276,53 +1544,333 @@ async fn main() -> Result<()> {
             circuit_params_path,
             endpoint,
         } => {
-            let kzg_params = read_srs(&srs_path);
-            let circuit_params = read_circuit_params(&circuit_params_path);
-            let n_classes = circuit_params.n_classes;
-            let vk = read_vk(&vk_path, circuit_params);
+            let srs_path = artifact_path(srs_path, config, |files| &files.srs, "srs-path")?;
+            let kzg_params = read_srs(&srs_path)?;
+            let (vk, circuit_params) =
+                read_vk_and_circuit_params(vk_path, circuit_params_path, config)?;
+            let layout = InstanceLayout::from_params(&circuit_params);
 
-            println!("Generating EVM verifier...");
-            let deployment_code = gen_evm_verifier(&kzg_params, &vk, vec![n_classes]);
+            say!(out, "Generating EVM verifier...");
+            let deployment_code = gen_evm_verifier(&kzg_params, &vk, layout.column_lengths());
 
             let client = EthClient::new(endpoint)
                 .await
                 .expect("Error creating client");
 
-            println!("Address: {:?}", client.address);
+            say!(out, "Address: {:?}", client.address);
 
-            println!("Deploying...");
+            say!(out, "Deploying...");
             let contract_address = client.deploy_contract(deployment_code).await.unwrap();
-            println!("Contract address: {:?}", contract_address);
+            say!(out, "Contract address: {:?}", contract_address);
+            out.emit(json!({ "contract_address": contract_address }));
+            Ok(())
+        }
+        Commands::ExportEvm {
+            srs_path,
+            vk_path,
+            circuit_params_path,
+            proof_path,
+            output_dir,
+        } => {
+            let srs_path = artifact_path(srs_path, config, |files| &files.srs, "srs-path")?;
+            let kzg_params = read_srs(&srs_path)?;
+            let (vk, circuit_params) =
+                read_vk_and_circuit_params(vk_path, circuit_params_path, config)?;
+            let layout = InstanceLayout::from_params(&circuit_params);
+            let proof_file = proof_path.map(|path| read_proof_file(&path)).transpose()?;
+
+            say!(out, "Generating EVM verifier...");
+            export_evm_verifier(
+                &kzg_params,
+                &vk,
+                &layout,
+                proof_file.as_ref().map(|proof_file| {
+                    (
+                        proof_file.proof.as_slice(),
+                        proof_file.public_inputs.as_slice(),
+                    )
+                }),
+                &output_dir,
+            )?;
+            say!(out, "Wrote EVM verifier to {}", output_dir.display());
+            out.emit(json!({ "output_dir": output_dir }));
+            Ok(())
+        }
+        Commands::RegistrationPayload {
+            model_path,
+            vk_path,
+            verifier,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let vk_path = artifact_path(vk_path, config, |files| &files.vk, "vk-path")?;
+            let vk = read_vk(&vk_path, wnn.get_circuit_params())?;
+            let payload = RegistrationPayload::new(&wnn, &vk, verifier);
+            say!(out, "VK fingerprint: {}", to_hex(payload.vk_fingerprint));
+            say!(
+                out,
+                "Model commitment: {}",
+                to_hex(payload.model_commitment)
+            );
+            say!(
+                out,
+                "Instance layout hash: {}",
+                to_hex(payload.instance_layout_hash)
+            );
+            say!(out, "Verifier: {:?}", payload.verifier);
+            out.emit(json!({
+                "vk_fingerprint": to_hex(payload.vk_fingerprint),
+                "model_commitment": to_hex(payload.model_commitment),
+                "instance_layout_hash": to_hex(payload.instance_layout_hash),
+                "verifier": payload.verifier,
+                "slot_values": payload.slot_values().iter().map(to_hex).collect::<Vec<_>>(),
+                "register_calldata": to_hex(payload.encode_register_call()),
+            }));
             Ok(())
         }
         Commands::Proof {
             model_path,
             img_path,
+            features,
             srs_path,
             pk_path,
             proof_path,
+            key_format,
+            previous_chaining_value,
         } => {
-            let wnn = load_wnn(&model_path)?;
-            let img = load_grayscale_image(&img_path).unwrap();
+            let wnn = load_project_model(config, model_path)?;
+            let input = match (img_path, features) {
+                (Some(img_path), _) => WnnInput::Image(load_grayscale_image(&img_path)?),
+                (None, Some(features)) => WnnInput::Features(features.into()),
+                (None, None) => unreachable!("Either an image or features are required"),
+            };
+            ProvableClassifier::check_input(&wnn, &input)?;
 
-            let kzg_params = read_srs(&srs_path);
-            let pk = read_pk(&pk_path, wnn.get_circuit_params());
+            let srs_path = artifact_path(srs_path, config, |files| &files.srs, "srs-path")?;
+            let kzg_params = read_srs(&srs_path)?;
+            let pk_path = artifact_path(pk_path, config, |files| &files.pk, "pk-path")?;
+            let pk = read_pk_with_format(&pk_path, wnn.get_circuit_params(), key_format)?;
+            let fingerprint = vk_fingerprint(pk.get_vk(), &wnn.get_circuit_params());
 
-            ProofWithOutput::from(wnn.proof(&pk, &kzg_params, &img)).write(&proof_path);
+            let start = Instant::now();
+            let proof_file = match &input {
+                WnnInput::Image(img) if wnn.get_circuit_params().chaining => {
+                    let previous = match previous_chaining_value {
+                        Some(previous) => hex::decode(previous.trim_start_matches("0x"))?
+                            .try_into()
+                            .map_err(|_| eyre::eyre!("The chaining value must be 32 bytes long"))?,
+                        None => GENESIS,
+                    };
+                    let (proof, outputs) = wnn.chained_proof(&pk, &kzg_params, img, &previous)?;
+                    let layout = InstanceLayout::from_params(&wnn.get_circuit_params());
+                    if let Some(chaining_value) = exposed_chaining_value(&layout, &outputs) {
+                        say!(out, "Chaining value: {}", to_hex(chaining_value));
+                    }
+                    ProofFile::new(proof, outputs)
+                        .with_circuit_params(wnn.get_circuit_params())
+                        .with_vk_fingerprint(fingerprint)
+                        .with_model_commitment(wnn.commitment())
+                }
+                _ => {
+                    if previous_chaining_value.is_some() {
+                        eyre::bail!("The model doesn't link proofs");
+                    }
+                    ProvableClassifier::prove(&wnn, &pk, &kzg_params, &input)?
+                }
+            };
+            let proving_time = start.elapsed();
+            say!(out, "Verifying key fingerprint: {}", to_hex(fingerprint));
+            write_proof_file(&proof_file, &proof_path).expect("Unable to write proof file");
+            out.emit(json!({
+                "proof_path": proof_path,
+                "vk_fingerprint": to_hex(fingerprint),
+                "public_inputs": public_inputs_json(
+                    &InstanceLayout::from_params(&wnn.get_circuit_params()),
+                    &proof_file,
+                ),
+                "proving_time": proving_time.as_secs_f64(),
+            }));
+            Ok(())
+        }
+        Commands::ProveDir {
+            model_path,
+            img_dir,
+            srs_path,
+            pk_path,
+            output_dir,
+            workers,
+            memory_budget,
+            key_format,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let srs_path = artifact_path(srs_path, config, |files| &files.srs, "srs-path")?;
+            let kzg_params = read_srs(&srs_path)?;
+            let pk_path = artifact_path(pk_path, config, |files| &files.pk, "pk-path")?;
+            let pk = read_pk_with_format(&pk_path, wnn.get_circuit_params(), key_format)?;
+
+            let images = image_files(&img_dir)?;
+            let mut prover = BatchProver::open(&wnn, &pk, &kzg_params, &output_dir)?;
+            let skipped = images
+                .iter()
+                .filter(|(image_id, _)| prover.is_proven(image_id))
+                .count();
+            let mut proven = 0;
+            let span = info_span!("prove_dir");
+            span.pb_set_style(&ProgressStyle::default_bar());
+            span.pb_set_length(images.len() as u64);
+            span.pb_inc(skipped as u64);
+            span.in_scope(|| {
+                prover.prove_all(
+                    &images,
+                    workers,
+                    memory_budget.map(|mib| mib << 20),
+                    |_, entry| {
+                        if let ManifestEntry::Proven { .. } = entry {
+                            proven += 1;
+                        }
+                        span.pb_inc(1);
+                    },
+                )
+            })?;
+
+            let failures: Vec<_> = prover.manifest().failures().collect();
+            say!(
+                out,
+                "Proved {proven} images ({skipped} skipped, already proven), {} failures",
+                failures.len()
+            );
+            for (image_id, error) in &failures {
+                say!(out, "  {image_id}: {error}");
+            }
+            let image_commitment_root = prover.manifest().image_commitment_root();
+            say!(
+                out,
+                "Image commitment root: {}",
+                to_hex(image_commitment_root)
+            );
+            out.emit(json!({
+                "proven": proven,
+                "skipped": skipped,
+                "failures": failures
+                    .iter()
+                    .map(|(image_id, error)| json!({ "image": image_id, "error": error }))
+                    .collect::<Vec<_>>(),
+                "manifest_path": output_dir.join(MANIFEST_FILE_NAME),
+                "image_commitment_root": to_hex(image_commitment_root),
+            }));
             Ok(())
         }
         Commands::Verify {
+            bundle_path,
             srs_path,
             vk_path,
             circuit_params_path,
             proof_path,
         } => {
-            let kzg_params = read_srs(&srs_path);
-            let circuit_params = read_circuit_params(&circuit_params_path);
-            let vk = read_vk(&vk_path, circuit_params);
-            let (proof, outputs) = ProofWithOutput::read(&proof_path).into();
+            let proof_file = read_proof_file(&proof_path)?;
+            let (fingerprint, instance_layout) = match bundle_path {
+                Some(bundle_path) => {
+                    let bundle = VerifierBundle::read(&bundle_path)?;
+                    say!(
+                        out,
+                        "Verifying key fingerprint: {}",
+                        to_hex(bundle.vk_fingerprint())
+                    );
+                    bundle.verify(&proof_file)?;
+                    (bundle.vk_fingerprint(), bundle.instance_layout)
+                }
+                None => {
+                    let srs_path = artifact_path(srs_path, config, |files| &files.srs, "srs-path")?;
+                    let kzg_params = read_srs(&srs_path)?;
+                    let (vk, circuit_params) =
+                        read_vk_and_circuit_params(vk_path, circuit_params_path, config)?;
 
-            Wnn::verify_proof(&proof, &kzg_params, &vk, &outputs);
+                    let fingerprint = vk_fingerprint(&vk, &circuit_params);
+                    say!(out, "Verifying key fingerprint: {}", to_hex(fingerprint));
+                    if let Some(proof_fingerprint) = proof_file.metadata.vk_fingerprint {
+                        if proof_fingerprint != fingerprint {
+                            eyre::bail!(
+                                "Proof was generated for verifying key {}",
+                                to_hex(proof_fingerprint)
+                            );
+                        }
+                    }
+
+                    Wnn::try_verify_proof(
+                        &proof_file.proof,
+                        &kzg_params,
+                        &vk,
+                        &proof_file.public_inputs,
+                    )
+                    .map_err(|e| eyre::eyre!("Invalid proof: {e:?}"))?;
+                    (fingerprint, InstanceLayout::from_params(&circuit_params))
+                }
+            };
+            say!(out, "Valid! Checked public inputs:");
+            print_public_inputs(out, &instance_layout, &proof_file);
+            out.emit(json!({
+                "valid": true,
+                "vk_fingerprint": to_hex(fingerprint),
+                "public_inputs": public_inputs_json(&instance_layout, &proof_file),
+            }));
+            Ok(())
+        }
+        Commands::VerifyBatch {
+            bundle_path,
+            manifest_path,
+        } => {
+            let bundle = VerifierBundle::read(&bundle_path)?;
+            let manifest = Manifest::read(&manifest_path)?;
+            if manifest.model_commitment != bundle.model_commitment {
+                eyre::bail!("The proofs were generated for a different model than the bundle");
+            }
+            let proofs_dir = manifest_path.parent().unwrap_or(Path::new("."));
+
+            let mut image_ids = vec![];
+            let mut proof_files = vec![];
+            for (image_id, entry) in &manifest.entries {
+                if let ManifestEntry::Proven { proof_file, .. } = entry {
+                    image_ids.push(image_id);
+                    proof_files.push(read_proof_file(&proofs_dir.join(proof_file))?);
+                }
+            }
+            say!(
+                out,
+                "Verifying {} proofs against verifying key {}...",
+                proof_files.len(),
+                to_hex(bundle.vk_fingerprint())
+            );
+            let results = bundle.verify_batch(&proof_files);
+
+            let mut num_invalid = 0;
+            let mut json_results = vec![];
+            for ((image_id, proof_file), result) in image_ids.iter().zip(&proof_files).zip(results)
+            {
+                match result {
+                    Ok(()) => {
+                        say!(out, "{image_id}: Valid");
+                        print_public_inputs(out, &bundle.instance_layout, proof_file);
+                        json_results.push(json!({
+                            "image": image_id,
+                            "valid": true,
+                            "public_inputs": public_inputs_json(&bundle.instance_layout, proof_file),
+                        }));
+                    }
+                    Err(e) => {
+                        say!(out, "{image_id}: {e}");
+                        json_results.push(json!({
+                            "image": image_id,
+                            "valid": false,
+                            "error": e.to_string(),
+                        }));
+                        num_invalid += 1;
+                    }
+                }
+            }
+            out.emit(json!({ "results": json_results }));
+            if num_invalid > 0 {
+                eyre::bail!("{num_invalid} of {} proofs are invalid", proof_files.len());
+            }
+            say!(out, "All {} proofs are valid", proof_files.len());
             Ok(())
         }
         Commands::SubmitProof {
@@ -330,7 +1878,9 @@ async fn main() -> Result<()> {
             mut contract_address,
             endpoint,
         } => {
-            let (proof, outputs) = ProofWithOutput::read(&proof_path).into();
+            let (proof, outputs) = read_proof_file(&proof_path)
+                .expect("Unable to read proof file")
+                .into();
 
             let client = EthClient::new(endpoint)
                 .await
@@ -348,7 +1898,407 @@ async fn main() -> Result<()> {
                 .await
                 .unwrap();
 
+            out.emit(json!({ "submitted": true, "contract_address": contract_address }));
+            Ok(())
+        }
+        Commands::PruneModel {
+            model_path,
+            calibration_set_path,
+            labels_path,
+            num_filter_entries,
+            output_path,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let dataset = match labels_path {
+                Some(labels_path) => Dataset::directory(
+                    &calibration_set_path,
+                    &LabelSource::read_sidecar(&labels_path)?,
+                )?,
+                None => Dataset::open(&calibration_set_path)?,
+            };
+            let progress = || dataset.par_iter().progress_count(dataset.len() as u64);
+
+            let calibration = Calibration::collect(&wnn, progress())?;
+            let (pruned, report) = calibration.repack(&wnn, num_filter_entries)?;
+            say!(out, "{report}");
+            say!(out, "Evaluating the pruned model on the calibration set");
+            let accuracy = pruned.evaluate_examples(progress())?.accuracy();
+            say!(
+                out,
+                "Calibration accuracy of the pruned model: {:.2}%",
+                accuracy * 100.0
+            );
+            save_model(&pruned, &output_path)
+                .map_err(|e| eyre::eyre!("Unable to write {}: {e}", output_path.display()))?;
+            out.emit(json!({
+                "model_path": output_path,
+                "report": report,
+                "calibration_accuracy": accuracy,
+                "commitment": to_hex(pruned.commitment()),
+            }));
+            Ok(())
+        }
+        #[cfg(feature = "hdf5")]
+        Commands::ConvertModel {
+            model_path,
+            output_path,
+            threshold_rounding,
+            threshold_min,
+            threshold_max,
+            pixel_order,
+        } => {
+            let policy = QuantizationPolicy {
+                rounding: threshold_rounding,
+                min: threshold_min,
+                max: threshold_max,
+            };
+            convert_hdf5_model(&model_path, &output_path, &policy, &pixel_order)?;
+            say!(out, "Quantized thresholds with {policy}");
+            out.emit(json!({ "model_path": output_path, "quantization_policy": policy }));
+            Ok(())
+        }
+        #[cfg(feature = "download")]
+        Commands::FetchMnist { dir } => {
+            let files = mnist::fetch(&dir)?;
+            say!(out, "Test set written to {}", files.test_png_dir.display());
+            out.emit(json!({ "test_png_dir": files.test_png_dir }));
+            Ok(())
+        }
+        #[cfg(feature = "download")]
+        Commands::FetchModel { name, dir } => {
+            let path = model_zoo::fetch_model(&name, &dir)?;
+            let k = model_zoo::find_model(&name).unwrap().k;
+            say!(
+                out,
+                "Model written to {} (recommended k: {k})",
+                path.display()
+            );
+            out.emit(json!({ "model_path": path, "k": k }));
+            Ok(())
+        }
+        Commands::Daemon {
+            model_path,
+            artifact_dir,
+            input_dir,
+            output_dir,
+            workers,
+            memory_budget,
+            poll_interval,
+            telemetry_path,
+        } => {
+            let prover = open_prover(config, model_path, artifact_dir, telemetry_path)?;
+            let daemon = Arc::new(Daemon::open(
+                prover,
+                DaemonConfig {
+                    input_dir,
+                    output_dir: output_dir.clone(),
+                    workers,
+                    memory_budget: memory_budget.map(|mib| mib << 20),
+                    poll_interval: Duration::from_secs(poll_interval),
+                },
+            )?);
+
+            let stop = Arc::new(AtomicBool::new(false));
+            let stop_on_signal = stop.clone();
+            tokio::spawn(async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    info!("Stopping after the running proofs");
+                    stop_on_signal.store(true, Ordering::Relaxed);
+                }
+            });
+            let running = daemon.clone();
+            tokio::task::spawn_blocking(move || running.run(&stop)).await??;
+
+            let manifest = daemon.manifest();
+            say!(
+                out,
+                "Stopped, {} images in the manifest, {} failures",
+                manifest.entries.len(),
+                manifest.failures().count()
+            );
+            out.emit(json!({
+                "images": manifest.entries.len(),
+                "failures": manifest.failures().count(),
+                "manifest_path": output_dir.join(MANIFEST_FILE_NAME),
+            }));
+            Ok(())
+        }
+        #[cfg(feature = "server")]
+        Commands::Serve {
+            model_path,
+            artifact_dir,
+            jobs_dir,
+            concurrency,
+            address,
+            telemetry_path,
+        } => {
+            let queue = open_job_queue(
+                config,
+                model_path,
+                artifact_dir,
+                telemetry_path,
+                &jobs_dir,
+                concurrency,
+            )?;
+            say!(out, "Listening on http://{address}");
+            server::serve(queue, address).await
+        }
+        #[cfg(feature = "grpc")]
+        Commands::ServeGrpc {
+            model_path,
+            artifact_dir,
+            jobs_dir,
+            concurrency,
+            address,
+            telemetry_path,
+        } => {
+            let queue = open_job_queue(
+                config,
+                model_path,
+                artifact_dir,
+                telemetry_path,
+                &jobs_dir,
+                concurrency,
+            )?;
+            say!(out, "Listening on {address}");
+            grpc::serve(queue, address).await
+        }
+        Commands::UpgradeProof { proof_path } => {
+            upgrade_proof_file(&proof_path).expect("Unable to upgrade proof file");
+            out.emit(json!({ "proof_path": proof_path }));
+            Ok(())
+        }
+        Commands::UpgradeKeys {
+            model_path,
+            pk_path,
+            vk_path,
+        } => {
+            let wnn = load_project_model(config, model_path)?;
+            let mut headers = vec![];
+            for (path, kind) in [(pk_path, KeyKind::Proving), (vk_path, KeyKind::Verifying)] {
+                if let Some(path) = path {
+                    let header = upgrade_key_file(&path, kind, wnn.get_circuit_params())?;
+                    say!(out, "Upgraded {} (k = {})", path.display(), header.k);
+                    headers.push(json!({ "path": path, "header": header }));
+                }
+            }
+            if headers.is_empty() {
+                say!(out, "Nothing to upgrade, pass --pk-path and/or --vk-path");
+            }
+            out.emit(json!({ "keys": headers }));
+            Ok(())
+        }
+        Commands::DiffKeys {
+            left,
+            right,
+            fingerprint,
+            circuit_params_path,
+        } => {
+            let read_summary = |path: &Path| -> Result<VkSummary> {
+                let (vk, circuit_params) = match &circuit_params_path {
+                    Some(params_path) => {
+                        let circuit_params = read_circuit_params(params_path)?;
+                        (read_vk(path, circuit_params.clone())?, circuit_params)
+                    }
+                    None => read_vk_and_params(path)?,
+                };
+                Ok(VkSummary::new(&vk, circuit_params))
+            };
+            let left = read_summary(&left)?;
+
+            if let Some(fingerprint) = fingerprint {
+                let fingerprint_match = match_fingerprint(&left, &fingerprint);
+                say!(out, "{fingerprint_match}");
+                out.emit(json!({ "left": left, "fingerprint_match": fingerprint_match }));
+                if fingerprint_match != FingerprintMatch::VerifyingKey {
+                    eyre::bail!("The key does not match the VK fingerprint {fingerprint}");
+                }
+                return Ok(());
+            }
+
+            let right = read_summary(&required(right, "right")?)?;
+            let diff = diff_vks(&left, &right);
+            say!(out, "{diff}");
+            out.emit(json!({ "left": left, "right": right, "diff": diff }));
+            if !diff.interchangeable {
+                eyre::bail!("The keys are not interchangeable");
+            }
             Ok(())
         }
     }
 }
+
+/// Logs to stderr and shows a spinner for each running step (e.g. synthesis or loading the
+/// lookup tables), so that long-running commands give feedback.
+fn init_tracing(verbose: u8) {
+    let level = match verbose {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        _ => Level::DEBUG,
+    };
+    let log_filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("zero_g={level}")));
+    let span_events = if verbose >= 2 {
+        FmtSpan::CLOSE
+    } else {
+        FmtSpan::NONE
+    };
+
+    let indicatif_layer = IndicatifLayer::new();
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(indicatif_layer.get_stderr_writer())
+                .with_span_events(span_events)
+                .with_filter(log_filter),
+        )
+        .with(indicatif_layer.with_filter(Targets::new().with_target("zero_g", Level::INFO)))
+        .init();
+}
+
+/// Loads the prover for the model and artifact directory given on the command line or in the
+/// project file.
+fn open_prover(
+    config: &ProjectConfig,
+    model_path: Option<PathBuf>,
+    artifact_dir: Option<PathBuf>,
+    telemetry_path: Option<PathBuf>,
+) -> Result<Prover> {
+    let model_path = required(model_path.or_else(|| config.model.clone()), "model-path")?;
+    let artifact_dir = required(
+        artifact_dir.or_else(|| config.artifact_dir.clone()),
+        "artifact-dir",
+    )?;
+    let mut prover = Prover::open(&model_path, &artifact_dir)?;
+    config.check_circuit_params(prover.wnn())?;
+    if let Some(telemetry_path) = telemetry_path {
+        prover = prover.with_telemetry(TelemetryLog::open(&telemetry_path)?);
+    }
+    Ok(prover)
+}
+
+#[cfg(any(feature = "server", feature = "grpc"))]
+fn open_job_queue(
+    config: &ProjectConfig,
+    model_path: Option<PathBuf>,
+    artifact_dir: Option<PathBuf>,
+    telemetry_path: Option<PathBuf>,
+    jobs_dir: &Path,
+    concurrency: usize,
+) -> Result<Arc<JobQueue>> {
+    let prover = open_prover(config, model_path, artifact_dir, telemetry_path)?;
+    Ok(JobQueue::new(prover, jobs_dir, concurrency)?)
+}
+
+/// Reads the project file given by `--config`, or [`CONFIG_FILE_NAME`] in the working directory
+/// if it exists.
+fn load_config(path: Option<&Path>) -> Result<ProjectConfig> {
+    Ok(match path {
+        Some(path) => ProjectConfig::read(path)?,
+        None => ProjectConfig::find(Path::new("."))?.unwrap_or_default(),
+    })
+}
+
+/// Loads the model given on the command line or in the project file, and checks the circuit
+/// params pinned in the project file.
+fn load_project_model(config: &ProjectConfig, model_path: Option<PathBuf>) -> Result<Wnn> {
+    let model_path = required(model_path.or_else(|| config.model.clone()), "model-path")?;
+    let wnn = load_model(&model_path)?;
+    config.check_circuit_params(&wnn)?;
+    Ok(wnn)
+}
+
+/// The path given on the command line, or the artifact from the artifact directory of the
+/// project file.
+fn artifact_path(
+    path: Option<PathBuf>,
+    config: &ProjectConfig,
+    file: impl FnOnce(&SetupFiles) -> &String,
+    name: &str,
+) -> Result<PathBuf> {
+    let path = match path {
+        Some(path) => Some(path),
+        None => config.artifact_path(file)?,
+    };
+    required(path, name)
+}
+
+/// Reads the verifying key with the circuit params from `circuit_params_path` (or the project
+/// file) if given, otherwise with the params embedded in the key file.
+fn read_vk_and_circuit_params(
+    vk_path: Option<PathBuf>,
+    circuit_params_path: Option<PathBuf>,
+    config: &ProjectConfig,
+) -> Result<(VerifyingKey<G1Affine>, WnnCircuitParams)> {
+    let vk_path = artifact_path(vk_path, config, |files| &files.vk, "vk-path")?;
+    let circuit_params_path = match circuit_params_path {
+        Some(path) => Some(path),
+        None => config.artifact_path(|files| &files.circuit_params)?,
+    };
+    Ok(match circuit_params_path {
+        Some(path) => {
+            let circuit_params = read_circuit_params(&path)?;
+            (read_vk(&vk_path, circuit_params.clone())?, circuit_params)
+        }
+        None => read_vk_and_params(&vk_path)?,
+    })
+}
+
+fn required<T>(value: Option<T>, name: &str) -> Result<T> {
+    value.ok_or_else(|| {
+        eyre::eyre!(
+            "--{name} is required, unless it is set in the project file ({CONFIG_FILE_NAME})"
+        )
+    })
+}
+
+fn to_hex(bytes: impl AsRef<[u8]>) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// Prints each public input of the proof, together with what it represents.
+fn print_public_inputs(out: &Output, instance_layout: &InstanceLayout, proof_file: &ProofFile) {
+    for (value, input) in instance_layout.values.iter().zip(&proof_file.public_inputs) {
+        match value {
+            PublicValue::Score { .. }
+            | PublicValue::Label { .. }
+            | PublicValue::Output
+            | PublicValue::Occluded { .. }
+            | PublicValue::PerturbedScore { .. }
+            | PublicValue::PerturbationBound => say!(out, "  {value}: {}", to_u32(input)),
+            PublicValue::ImageCommitment => {
+                say!(out, "  {value}: {}", to_hex(commitment_from_field(input)))
+            }
+            PublicValue::PreviousChainingValue | PublicValue::ChainingValue => {
+                say!(
+                    out,
+                    "  {value}: {}",
+                    to_hex(chaining_value_from_field(input))
+                )
+            }
+        }
+    }
+}
+
+fn public_inputs_json(instance_layout: &InstanceLayout, proof_file: &ProofFile) -> Value {
+    instance_layout
+        .values
+        .iter()
+        .zip(&proof_file.public_inputs)
+        .map(|(value, input)| {
+            let input = match value {
+                PublicValue::Score { .. }
+                | PublicValue::Label { .. }
+                | PublicValue::Output
+                | PublicValue::Occluded { .. }
+                | PublicValue::PerturbedScore { .. }
+                | PublicValue::PerturbationBound => json!(to_u32(input)),
+                PublicValue::ImageCommitment => json!(to_hex(commitment_from_field(input))),
+                PublicValue::PreviousChainingValue | PublicValue::ChainingValue => {
+                    json!(to_hex(chaining_value_from_field(input)))
+                }
+            };
+            json!({ "value": value.to_string(), "input": input })
+        })
+        .collect()
+}
@@ -0,0 +1,146 @@
+//! Tracking the memory usage of key generation and proving, and waiting for memory to become
+//! available before starting more proofs.
+//!
+//! Binaries can install [`TrackingAllocator`] as the global allocator (the `zero_g` binary
+//! does), which counts the bytes allocated on the heap:
+//! ```
+//! #[global_allocator]
+//! static ALLOCATOR: zero_g::memory::TrackingAllocator = zero_g::memory::TrackingAllocator;
+//! # fn main() {}
+//! ```
+//! Without it, [`measure_peak`] returns `None` and [`memory_usage`] falls back to the resident
+//! set size of the process (Linux only).
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::benchmark::current_rss;
+
+/// How often [`wait_for_memory`] checks whether the memory usage dropped below the budget.
+const MEMORY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// Wraps the system allocator, counting the currently allocated bytes and their peak.
+pub struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                record_alloc(new_size - layout.size());
+            } else {
+                ALLOCATED.fetch_sub(layout.size() - new_size, Ordering::Relaxed);
+            }
+        }
+        new_ptr
+    }
+}
+
+fn record_alloc(size: usize) {
+    if !INSTALLED.load(Ordering::Relaxed) {
+        INSTALLED.store(true, Ordering::Relaxed);
+    }
+    let allocated = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(allocated, Ordering::Relaxed);
+}
+
+/// The number of bytes currently allocated on the heap, if [`TrackingAllocator`] is installed.
+pub fn allocated() -> Option<u64> {
+    INSTALLED
+        .load(Ordering::Relaxed)
+        .then(|| ALLOCATED.load(Ordering::Relaxed) as u64)
+}
+
+/// Runs `f` and returns the peak number of bytes allocated on the heap while it ran, if
+/// [`TrackingAllocator`] is installed.
+///
+/// The peak is tracked for the whole process, so it includes the allocations of other threads
+/// (and is only an upper bound if several measurements run at the same time).
+pub fn measure_peak<T>(f: impl FnOnce() -> T) -> (T, Option<u64>) {
+    PEAK.store(ALLOCATED.load(Ordering::Relaxed), Ordering::Relaxed);
+    let result = f();
+    let peak = INSTALLED
+        .load(Ordering::Relaxed)
+        .then(|| PEAK.load(Ordering::Relaxed) as u64);
+    (result, peak)
+}
+
+/// The memory usage of the process in bytes: The heap allocations if [`TrackingAllocator`] is
+/// installed, the resident set size otherwise (if available on this platform).
+pub fn memory_usage() -> Option<u64> {
+    allocated().or_else(current_rss)
+}
+
+/// Waits until the memory usage (see [`memory_usage`]) is below `budget` (in bytes), no proof
+/// is `running` anymore, or `stop` is set.
+///
+/// Calling this before starting a proof reduces the number of concurrent proofs while the
+/// memory is scarce, but always allows at least one proof to run.
+pub fn wait_for_memory(budget: u64, running: &AtomicUsize, stop: &AtomicBool) {
+    while running.load(Ordering::SeqCst) > 0 && !stop.load(Ordering::Relaxed) {
+        match memory_usage() {
+            Some(usage) if usage > budget => thread::sleep(MEMORY_POLL_INTERVAL),
+            _ => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hint::black_box;
+    use std::sync::atomic::{AtomicBool, AtomicUsize};
+
+    use super::{allocated, measure_peak, wait_for_memory, TrackingAllocator};
+
+    #[global_allocator]
+    static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+    #[test]
+    fn test_measure_peak() {
+        const SIZE: usize = 64 << 20;
+        let (sum, peak) = measure_peak(|| {
+            let buffer = black_box(vec![1u8; SIZE]);
+            buffer.iter().map(|x| *x as usize).sum::<usize>()
+        });
+        assert_eq!(sum, SIZE);
+        assert!(peak.unwrap() >= SIZE as u64);
+        assert!(allocated().is_some());
+    }
+
+    #[test]
+    fn test_wait_for_memory() {
+        // Returns immediately if no proof is running, even if the budget is exceeded
+        wait_for_memory(0, &AtomicUsize::new(0), &AtomicBool::new(false));
+        // ... or if stopped
+        wait_for_memory(0, &AtomicUsize::new(1), &AtomicBool::new(true));
+        // ... or if the usage is below the budget
+        wait_for_memory(u64::MAX, &AtomicUsize::new(1), &AtomicBool::new(false));
+    }
+}
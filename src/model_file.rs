@@ -0,0 +1,502 @@
+//! A pure-Rust container for [`Wnn`] models (`.zgm` files).
+//!
+//! Unlike the HDF5 format written by the Python training code, this format can be read
+//! without the HDF5 C library (e.g. when compiling to WASM or musl). Use
+//! [`convert_hdf5_model`] (or the `convert-model` command) to convert a model.
+//!
+//! The layout is as follows (all integers are little endian):
+//!
+//! | Field                   | Size                                  |
+//! |-------------------------|---------------------------------------|
+//! | Magic bytes (`ZGMF`)    | 4 bytes                               |
+//! | Format version          | 2 bytes                               |
+//! | Header length           | 4 bytes                               |
+//! | Header (JSON)           | `header length` bytes                 |
+//! | Bloom filters length    | 4 bytes                               |
+//! | Bloom filters           | 1 bit per entry, see [`pack_bits_le`] |
+//! | Input order length      | 4 bytes                               |
+//! | Input order             | 8 bytes per entry                     |
+//! | Thresholds length       | 4 bytes                               |
+//! | Binarization thresholds | 2 bytes per entry                     |
+//!
+//! All tensors are stored in row-major order. Note that binarization thresholds are
+//! stored after quantization. The feature thresholds of tabular models (see
+//! [`Wnn::new_tabular`]) are stored as the binarization thresholds of an image with one row.
+//!
+//! The same encoding is used to implement `Serialize` and `Deserialize` for [`Wnn`], so that
+//! models can be embedded in other formats.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use ndarray::{Array1, Array3};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[cfg(feature = "hdf5")]
+use crate::error::ZeroGError;
+use crate::gadgets::byte_table::DEFAULT_WINDOW_NUM_BITS;
+use crate::io::{invalid_data, read_array, read_length_prefixed, write_length_prefixed};
+use crate::packed_bloom_filters::PackedBloomFilters;
+#[cfg(feature = "hdf5")]
+use crate::pixel_order::PixelOrder;
+use crate::quantization::QuantizationPolicy;
+use crate::utils::pack_bits_le;
+use crate::wnn::Wnn;
+
+/// Magic bytes at the start of every model file.
+pub const MAGIC: [u8; 4] = *b"ZGMF";
+
+/// The format version written by this version of the crate.
+pub const CURRENT_VERSION: u16 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    num_classes: usize,
+    num_filter_inputs: usize,
+    num_filter_entries: usize,
+    num_filter_hashes: usize,
+    p: u64,
+    bloom_filters_shape: [usize; 3],
+    binarization_thresholds_shape: [usize; 3],
+    /// Absent in files written before the policy was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quantization_policy: Option<QuantizationPolicy>,
+    /// See [`Wnn::with_min_blinding_factors`], absent if 0.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    min_blinding_factors: usize,
+    /// See [`Wnn::with_num_instance_columns`], absent if 1.
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    num_instance_columns: usize,
+    /// See [`Wnn::with_class_lookup`], absent if false.
+    #[serde(default, skip_serializing_if = "is_false")]
+    class_lookup: bool,
+    /// See [`Wnn::with_window_num_bits`], absent if 8.
+    #[serde(
+        default = "default_window_num_bits",
+        skip_serializing_if = "is_default_window_num_bits"
+    )]
+    window_num_bits: usize,
+    /// See [`Wnn::with_chaining`], absent if false.
+    #[serde(default, skip_serializing_if = "is_false")]
+    chaining: bool,
+    /// See [`Wnn::with_label_thresholds`], absent for single-label models.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label_thresholds: Option<Vec<u64>>,
+    /// See [`Wnn::with_regression`], absent if false.
+    #[serde(default, skip_serializing_if = "is_false")]
+    regression: bool,
+    /// See [`Wnn::new_tabular`], absent if false.
+    #[serde(default, skip_serializing_if = "is_false")]
+    tabular: bool,
+    /// See [`Wnn::with_occlusion`], absent if false.
+    #[serde(default, skip_serializing_if = "is_false")]
+    occlusion: bool,
+    /// See [`Wnn::with_robustness`], absent if false.
+    #[serde(default, skip_serializing_if = "is_false")]
+    robustness: bool,
+    /// See [`Wnn::with_image_commitment`], absent if false.
+    #[serde(default, skip_serializing_if = "is_false")]
+    image_commitment: bool,
+}
+
+fn is_zero(x: &usize) -> bool {
+    *x == 0
+}
+
+fn is_false(x: &bool) -> bool {
+    !x
+}
+
+fn one() -> usize {
+    1
+}
+
+fn is_one(x: &usize) -> bool {
+    *x == 1
+}
+
+fn default_window_num_bits() -> usize {
+    DEFAULT_WINDOW_NUM_BITS
+}
+
+fn is_default_window_num_bits(x: &usize) -> bool {
+    *x == DEFAULT_WINDOW_NUM_BITS
+}
+
+/// The tensors of a model, encoded as in the model file.
+struct EncodedTensors {
+    bloom_filters: Vec<u8>,
+    input_order: Vec<u8>,
+    binarization_thresholds: Vec<u8>,
+}
+
+fn encode(wnn: &Wnn) -> (Header, EncodedTensors) {
+    let shape = |s: &[usize]| -> [usize; 3] { s.try_into().unwrap() };
+    let header = Header {
+        num_classes: wnn.num_classes,
+        num_filter_inputs: wnn.num_filter_inputs,
+        num_filter_entries: wnn.num_filter_entries,
+        num_filter_hashes: wnn.num_filter_hashes,
+        p: wnn.p,
+        bloom_filters_shape: wnn.bloom_filters.shape(),
+        binarization_thresholds_shape: shape(wnn.binarization_thresholds.shape()),
+        quantization_policy: wnn.quantization_policy(),
+        min_blinding_factors: wnn.min_blinding_factors,
+        num_instance_columns: wnn.num_instance_columns,
+        class_lookup: wnn.class_lookup,
+        window_num_bits: wnn.window_num_bits,
+        chaining: wnn.chaining,
+        label_thresholds: wnn.label_thresholds.as_ref().map(|t| t.to_vec()),
+        regression: wnn.regression,
+        tabular: wnn.tabular,
+        occlusion: wnn.occlusion,
+        robustness: wnn.robustness,
+        image_commitment: wnn.image_commitment,
+    };
+    let tensors = EncodedTensors {
+        bloom_filters: pack_bits_le(wnn.bloom_filters.iter()),
+        input_order: wnn
+            .input_permutation
+            .iter()
+            .flat_map(|i| i.to_le_bytes())
+            .collect(),
+        binarization_thresholds: wnn
+            .binarization_thresholds
+            .iter()
+            .flat_map(|t| t.to_le_bytes())
+            .collect(),
+    };
+    (header, tensors)
+}
+
+fn decode(header: Header, tensors: EncodedTensors) -> io::Result<Wnn> {
+    let bloom_filters =
+        PackedBloomFilters::from_le_bytes(header.bloom_filters_shape, &tensors.bloom_filters)
+            .ok_or_else(|| invalid_data("Bloom filters have the wrong size"))?;
+
+    if tensors.input_order.len() % 8 != 0 {
+        return Err(invalid_data("Input order has the wrong size"));
+    }
+    let input_order = tensors
+        .input_order
+        .chunks_exact(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect::<Array1<_>>();
+
+    let binarization_thresholds = tensors
+        .binarization_thresholds
+        .chunks_exact(2)
+        .map(|chunk| u16::from_le_bytes(chunk.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    let binarization_thresholds = Array3::from_shape_vec(
+        header.binarization_thresholds_shape,
+        binarization_thresholds,
+    )
+    .map_err(|_| invalid_data("Binarization thresholds have the wrong size"))?;
+
+    let mut wnn = Wnn::new(
+        header.num_classes,
+        header.num_filter_entries,
+        header.num_filter_hashes,
+        header.num_filter_inputs,
+        header.p,
+        bloom_filters,
+        input_order,
+        binarization_thresholds,
+    );
+    wnn.quantization_policy = header.quantization_policy;
+    wnn.min_blinding_factors = header.min_blinding_factors;
+    wnn.num_instance_columns = header.num_instance_columns;
+    wnn.class_lookup = header.class_lookup;
+    wnn.chaining = header.chaining;
+    wnn.window_num_bits = header.window_num_bits;
+    wnn.label_thresholds = header.label_thresholds.map(Array1::from);
+    wnn.regression = header.regression;
+    wnn.tabular = header.tabular;
+    wnn.occlusion = header.occlusion;
+    wnn.robustness = header.robustness;
+    wnn.image_commitment = header.image_commitment;
+    wnn.validate().map_err(|e| invalid_data(e.to_string()))?;
+    Ok(wnn)
+}
+
+/// Serializes a model.
+pub fn write_model_to(wnn: &Wnn, writer: &mut impl Write) -> io::Result<()> {
+    let (header, tensors) = encode(wnn);
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&CURRENT_VERSION.to_le_bytes())?;
+    write_length_prefixed(writer, &serde_json::to_vec(&header)?)?;
+    write_length_prefixed(writer, &tensors.bloom_filters)?;
+    write_length_prefixed(writer, &tensors.input_order)?;
+    write_length_prefixed(writer, &tensors.binarization_thresholds)
+}
+
+/// Deserializes a model.
+pub fn read_model_from(reader: &mut impl Read) -> io::Result<Wnn> {
+    if read_array::<4>(reader)? != MAGIC {
+        return Err(invalid_data("Not a zero_g model file"));
+    }
+    let version = u16::from_le_bytes(read_array(reader)?);
+    if version != CURRENT_VERSION {
+        return Err(invalid_data(format!(
+            "Unsupported model file version {version} (latest supported: {CURRENT_VERSION})"
+        )));
+    }
+
+    let header: Header = serde_json::from_slice(&read_length_prefixed(reader)?)?;
+    let tensors = EncodedTensors {
+        bloom_filters: read_length_prefixed(reader)?,
+        input_order: read_length_prefixed(reader)?,
+        binarization_thresholds: read_length_prefixed(reader)?,
+    };
+    decode(header, tensors)
+}
+
+/// The serde representation of a [`Wnn`]: The header of the model file, and each tensor as
+/// encoded in the model file. In human-readable formats (e.g. JSON or TOML), tensors are
+/// hex strings; other formats store them as raw bytes.
+#[derive(Serialize, Deserialize)]
+struct SerializedWnn {
+    version: u16,
+    header: Header,
+    bloom_filters: TensorBytes,
+    input_order: TensorBytes,
+    binarization_thresholds: TensorBytes,
+}
+
+struct TensorBytes(Vec<u8>);
+
+impl Serialize for TensorBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hex::encode(&self.0))
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TensorBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TensorBytesVisitor;
+
+        impl<'de> Visitor<'de> for TensorBytesVisitor {
+            type Value = TensorBytes;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("bytes or a hex string")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                hex::decode(v).map(TensorBytes).map_err(E::custom)
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(TensorBytes(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(TensorBytes(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(TensorBytes(bytes))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(TensorBytesVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(TensorBytesVisitor)
+        }
+    }
+}
+
+impl Serialize for Wnn {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (header, tensors) = encode(self);
+        SerializedWnn {
+            version: CURRENT_VERSION,
+            header,
+            bloom_filters: TensorBytes(tensors.bloom_filters),
+            input_order: TensorBytes(tensors.input_order),
+            binarization_thresholds: TensorBytes(tensors.binarization_thresholds),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Wnn {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized = SerializedWnn::deserialize(deserializer)?;
+        if serialized.version != CURRENT_VERSION {
+            return Err(de::Error::custom(format!(
+                "Unsupported model version {} (latest supported: {CURRENT_VERSION})",
+                serialized.version
+            )));
+        }
+        let tensors = EncodedTensors {
+            bloom_filters: serialized.bloom_filters.0,
+            input_order: serialized.input_order.0,
+            binarization_thresholds: serialized.binarization_thresholds.0,
+        };
+        decode(serialized.header, tensors).map_err(de::Error::custom)
+    }
+}
+
+/// Writes a model to file.
+pub fn save_model(wnn: &Wnn, path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_model_to(wnn, &mut writer)?;
+    writer.flush()
+}
+
+/// Reads a model from file.
+pub fn load_model_file(path: &Path) -> io::Result<Wnn> {
+    read_model_from(&mut BufReader::new(File::open(path)?))
+}
+
+/// Returns true if the file at the given path starts with the model file magic bytes.
+pub fn is_model_file(path: &Path) -> io::Result<bool> {
+    let mut magic = [0u8; 4];
+    let n = File::open(path)?.read(&mut magic)?;
+    Ok(n == magic.len() && magic == MAGIC)
+}
+
+/// Converts an HDF5 model (see [`crate::load_wnn`]) into a `.zgm` file, quantizing the
+/// binarization thresholds with the given policy and adapting the model to images stored in
+/// the given pixel order (see [`Wnn::with_pixel_order`]).
+#[cfg(feature = "hdf5")]
+pub fn convert_hdf5_model(
+    hdf5_path: &Path,
+    output_path: &Path,
+    policy: &QuantizationPolicy,
+    pixel_order: &PixelOrder,
+) -> Result<(), ZeroGError> {
+    let wnn =
+        crate::load_wnn_with_policy(hdf5_path, policy).map_err(|source| ZeroGError::Format {
+            path: hdf5_path.to_path_buf(),
+            format: "HDF5 model",
+            // Keep only the message, as HDF5 errors hold handles into the library
+            source: source.to_string().into(),
+        })?;
+    let wnn = wnn.with_pixel_order(pixel_order)?;
+    save_model(&wnn, output_path).map_err(|source| ZeroGError::Io {
+        action: "write",
+        path: output_path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use ndarray::{array, Array2, Array3};
+
+    use crate::quantization::{QuantizationPolicy, Rounding};
+    use crate::wnn::Wnn;
+
+    use super::{decode, encode, read_model_from, write_model_to, Header};
+
+    fn test_model() -> Wnn {
+        let mut bloom_filters = Array3::from_elem((2, 2, 1024), false);
+        bloom_filters[[0, 0, 966]] = true;
+        bloom_filters[[1, 1, 46]] = true;
+        let thresholds = Array3::from_shape_fn((4, 3, 2), |(i, j, b)| (i * 50 + j * 7 + b) as u16);
+        let input_order = (0..24u64).rev().collect();
+        Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            2097143,
+            bloom_filters,
+            input_order,
+            thresholds,
+        )
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let wnn = test_model();
+
+        let mut bytes = vec![];
+        write_model_to(&wnn, &mut bytes).unwrap();
+        let loaded = read_model_from(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(loaded.commitment(), wnn.commitment());
+        assert_eq!(loaded.quantization_policy(), None);
+        let image: Array2<u8> =
+            array![[70, 100, 150], [20, 110, 200], [27, 50, 211], [200, 100, 3]];
+        assert_eq!(loaded.predict(&image), wnn.predict(&image));
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let wnn = test_model();
+
+        let json = serde_json::to_value(&wnn).unwrap();
+        let from_json: Wnn = serde_json::from_value(json.clone()).unwrap();
+
+        assert_eq!(json["header"]["num_classes"], 2);
+        assert!(json["input_order"].is_string());
+        assert_eq!(from_json.commitment(), wnn.commitment());
+    }
+
+    #[test]
+    fn test_quantization_policy_roundtrip() {
+        let policy = QuantizationPolicy {
+            rounding: Rounding::Round,
+            min: 1,
+            max: 255,
+        };
+        let wnn = test_model().with_quantization_policy(policy);
+
+        let mut bytes = vec![];
+        write_model_to(&wnn, &mut bytes).unwrap();
+        let loaded = read_model_from(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(loaded.quantization_policy(), Some(policy));
+        assert_eq!(loaded.commitment(), test_model().commitment());
+    }
+
+    #[test]
+    fn test_label_thresholds_roundtrip() {
+        let wnn = test_model().with_label_thresholds(array![1, 2]);
+
+        let mut bytes = vec![];
+        write_model_to(&wnn, &mut bytes).unwrap();
+        let loaded = read_model_from(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(loaded.label_thresholds(), Some(&array![1, 2]));
+        assert_eq!(loaded.commitment(), wnn.commitment());
+        assert_ne!(loaded.commitment(), test_model().commitment());
+    }
+
+    #[test]
+    fn test_overflowing_shapes() {
+        let corruptions: [fn(&mut Header); 2] = [
+            |header| header.bloom_filters_shape = [usize::MAX, 2, 1024],
+            |header| header.binarization_thresholds_shape = [usize::MAX, 3, 2],
+        ];
+        for corrupt in corruptions {
+            let (mut header, tensors) = encode(&test_model());
+            corrupt(&mut header);
+            let error = decode(header, tensors).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_wrong_magic() {
+        assert!(read_model_from(&mut b"HDF5 file".as_slice()).is_err());
+    }
+}
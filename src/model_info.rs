@@ -0,0 +1,322 @@
+//! A summary of the parameters of a model, to debug mismatches between models and circuit params
+//! and to size the circuit, see [`Wnn::stats`].
+
+use std::fmt;
+
+use ndarray::Axis;
+use serde::Serialize;
+
+use crate::cost::MIN_K;
+use crate::gadgets::bloom_filter::{ArrayLookupConfig, BloomFilterConfig};
+use crate::gadgets::byte_table::{num_table_rows, DEFAULT_WINDOW_NUM_BITS};
+use crate::gadgets::wnn::WnnCircuitParams;
+use crate::quantization::QuantizationPolicy;
+use crate::wnn::Wnn;
+
+/// Number of buckets of [`ModelInfo::threshold_histogram`], each covering 16 intensities.
+pub const THRESHOLD_HISTOGRAM_BUCKETS: usize = 16;
+
+/// Statistics over the binarization thresholds of one bit of the thermometer encoding.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThresholdStats {
+    pub min: u16,
+    pub max: u16,
+    pub mean: f64,
+}
+
+/// Metadata and statistics of a model, see [`ModelInfo::new`] (or [`Wnn::stats`]).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelInfo {
+    pub num_classes: usize,
+    /// `(rows, columns)` of the input image.
+    pub image_shape: (usize, usize),
+    /// Number of bits per pixel in the thermometer encoding.
+    pub bits_per_input: usize,
+    pub num_filter_inputs: usize,
+    /// Number of filters per class.
+    pub num_filters: usize,
+    pub num_filter_entries: usize,
+    pub num_filter_hashes: usize,
+    pub p: u64,
+    /// Fraction of bloom filter entries that are set, for each class.
+    pub bloom_filter_density: Vec<f64>,
+    /// Statistics over the thresholds of each bit of the thermometer encoding.
+    pub thresholds: Vec<ThresholdStats>,
+    /// The number of thresholds (of all bits) in each range of 16 intensities, i.e. `[0, 16)`,
+    /// `[16, 32)`, ... The last bucket also counts thresholds above 255.
+    pub threshold_histogram: Vec<usize>,
+    /// How the thresholds were quantized, if recorded in the model.
+    pub quantization_policy: Option<QuantizationPolicy>,
+    /// Number of rows of the bloom filter lookup table, which usually determines `k`.
+    pub total_lookup_rows: usize,
+    /// Number of rows of the byte table (see [`crate::gadgets::byte_table::num_table_rows`]).
+    pub byte_table_rows: usize,
+    /// A lower bound for `k`, from the size of the lookup tables alone (which are stored in
+    /// separate columns, so the larger one counts). The exact value is
+    /// computed by [`crate::cost::minimal_k`], which synthesizes the circuit.
+    pub estimated_k: u32,
+    /// The circuit parameters implied by the model.
+    pub circuit_params: WnnCircuitParams,
+    /// See [`Wnn::commitment`].
+    pub commitment: [u8; 32],
+}
+
+impl ModelInfo {
+    pub fn new(wnn: &Wnn) -> Self {
+        let [num_classes, num_filters, num_filter_entries] = wnn.bloom_filters.shape();
+        let bloom_filter_density = (0..num_classes)
+            .map(|class| {
+                let set = (0..num_filters)
+                    .map(|filter| wnn.bloom_filters.count_set(class, filter))
+                    .sum::<usize>();
+                set as f64 / (num_filters * num_filter_entries) as f64
+            })
+            .collect();
+        let thresholds = wnn
+            .binarization_thresholds
+            .axis_iter(Axis(2))
+            .map(|thresholds| ThresholdStats {
+                min: thresholds.iter().copied().min().unwrap_or_default(),
+                max: thresholds.iter().copied().max().unwrap_or_default(),
+                mean: thresholds.iter().map(|t| *t as f64).sum::<f64>() / thresholds.len() as f64,
+            })
+            .collect();
+        let mut threshold_histogram = vec![0; THRESHOLD_HISTOGRAM_BUCKETS];
+        for threshold in &wnn.binarization_thresholds {
+            threshold_histogram[(*threshold as usize / 16).min(THRESHOLD_HISTOGRAM_BUCKETS - 1)] +=
+                1;
+        }
+        let circuit_params = wnn.get_circuit_params();
+        let total_lookup_rows = lookup_table_rows(&circuit_params, num_filters);
+        let byte_table_rows = num_table_rows(circuit_params.window_num_bits);
+        // Each table needs its rows to be usable, plus at least one blinding row
+        let estimated_k = ((total_lookup_rows.max(byte_table_rows) + 1)
+            .next_power_of_two()
+            .trailing_zeros())
+        .max(MIN_K);
+
+        Self {
+            num_classes: wnn.num_classes,
+            image_shape: wnn.img_shape(),
+            bits_per_input: wnn.binarization_thresholds.shape()[2],
+            num_filter_inputs: wnn.num_filter_inputs,
+            num_filters,
+            num_filter_entries: wnn.num_filter_entries,
+            num_filter_hashes: wnn.num_filter_hashes,
+            p: wnn.p,
+            bloom_filter_density,
+            thresholds,
+            threshold_histogram,
+            quantization_policy: wnn.quantization_policy(),
+            total_lookup_rows,
+            byte_table_rows,
+            estimated_k,
+            circuit_params,
+            commitment: wnn.commitment(),
+        }
+    }
+}
+
+/// The number of rows of the lookup table of [`crate::gadgets::bloom_filter::BloomFilterChip`],
+/// which stores each bloom filter in words of `2^(bits_per_hash - word_index_bits)` bits. With
+/// `class_lookup`, a row holds the words of all classes.
+fn lookup_table_rows(params: &WnnCircuitParams, num_filters: usize) -> usize {
+    // Smaller bloom filters are not supported by the chip; they would be stored bit by bit
+    let words_per_filter = if params.bits_per_hash < 7 {
+        1 << params.bits_per_hash
+    } else {
+        let config = ArrayLookupConfig::from(BloomFilterConfig {
+            n_hashes: params.n_hashes,
+            bits_per_hash: params.bits_per_hash,
+        });
+        1 << config.word_index_bits
+    };
+    let tables = if params.class_lookup {
+        1
+    } else {
+        params.n_classes
+    };
+    tables * num_filters * words_per_filter
+}
+
+impl fmt::Display for ModelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Commitment:         0x{}", hex::encode(self.commitment))?;
+        writeln!(f, "Classes:            {}", self.num_classes)?;
+        writeln!(
+            f,
+            "Image shape:        {}x{} ({} bits per pixel)",
+            self.image_shape.0, self.image_shape.1, self.bits_per_input
+        )?;
+        writeln!(
+            f,
+            "Filters per class:  {} ({} inputs each)",
+            self.num_filters, self.num_filter_inputs
+        )?;
+        writeln!(
+            f,
+            "Bloom filters:      {} entries, {} hashes",
+            self.num_filter_entries, self.num_filter_hashes
+        )?;
+        writeln!(f, "p:                  {}", self.p)?;
+
+        writeln!(f, "\nBloom filter density:")?;
+        for (class, density) in self.bloom_filter_density.iter().enumerate() {
+            writeln!(f, "  Class {class:<4} {:>6.1}%", density * 100.0)?;
+        }
+
+        writeln!(f, "\nLookup table rows:  {}", self.total_lookup_rows)?;
+        writeln!(f, "Byte table rows:    {}", self.byte_table_rows)?;
+        writeln!(f, "Estimated k:        >= {}", self.estimated_k)?;
+
+        writeln!(f, "\nThresholds:")?;
+        writeln!(f, "  {:<8} {:>5} {:>5} {:>7}", "Bit", "min", "max", "mean")?;
+        for (bit, stats) in self.thresholds.iter().enumerate() {
+            writeln!(
+                f,
+                "  {bit:<8} {:>5} {:>5} {:>7.1}",
+                stats.min, stats.max, stats.mean
+            )?;
+        }
+        match &self.quantization_policy {
+            Some(policy) => writeln!(f, "  Quantization: {policy}")?,
+            None => writeln!(f, "  Quantization: unknown")?,
+        }
+        writeln!(f, "\nThreshold histogram:")?;
+        let max_count = self
+            .threshold_histogram
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        for (bucket, count) in self.threshold_histogram.iter().enumerate() {
+            writeln!(
+                f,
+                "  {:>3}-{:<3} {count:>8} {}",
+                bucket * 16,
+                bucket * 16 + 15,
+                "#".repeat(count * 40 / max_count)
+            )?;
+        }
+
+        let WnnCircuitParams {
+            p,
+            l,
+            n_hashes,
+            bits_per_hash,
+            bits_per_filter,
+            n_classes,
+            min_blinding_factors,
+            num_instance_columns,
+            class_lookup,
+            window_num_bits,
+            chaining,
+            multi_label,
+            regression,
+            tabular,
+            occlusion_num_pixels,
+            robustness,
+            image_commitment,
+        } = &self.circuit_params;
+        writeln!(f, "\nCircuit params:")?;
+        writeln!(
+            f,
+            "  p = {p}, l = {l}, n_hashes = {n_hashes}, bits_per_hash = {bits_per_hash},"
+        )?;
+        write!(
+            f,
+            "  bits_per_filter = {bits_per_filter}, n_classes = {n_classes}"
+        )?;
+        if *min_blinding_factors != 0 {
+            write!(f, ", min_blinding_factors = {min_blinding_factors}")?;
+        }
+        if *num_instance_columns != 1 {
+            write!(f, ", num_instance_columns = {num_instance_columns}")?;
+        }
+        if *class_lookup {
+            write!(f, ", class_lookup = true")?;
+        }
+        if *window_num_bits != DEFAULT_WINDOW_NUM_BITS {
+            write!(f, ", window_num_bits = {window_num_bits}")?;
+        }
+        if *chaining {
+            write!(f, ", chaining = true")?;
+        }
+        if *multi_label {
+            write!(f, ", multi_label = true")?;
+        }
+        if *regression {
+            write!(f, ", regression = true")?;
+        }
+        if *tabular {
+            write!(f, ", tabular = true")?;
+        }
+        if *occlusion_num_pixels != 0 {
+            write!(f, ", occlusion_num_pixels = {occlusion_num_pixels}")?;
+        }
+        if *robustness {
+            write!(f, ", robustness = true")?;
+        }
+        if *image_commitment {
+            write!(f, ", image_commitment = true")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{s, Array1, Array3};
+
+    use crate::wnn::Wnn;
+
+    #[test]
+    fn test_model_info() {
+        // Class 0 has one entry set, class 1 all entries
+        let mut bloom_filters = Array3::from_elem((2, 2, 4), false);
+        bloom_filters[[0, 1, 3]] = true;
+        bloom_filters.slice_mut(s![1, .., ..]).fill(true);
+        let mut thresholds = Array3::zeros((2, 2, 2));
+        thresholds.slice_mut(s![.., .., 1]).fill(100);
+        thresholds[[0, 0, 1]] = 200;
+
+        let wnn = || {
+            Wnn::new(
+                2,
+                4,
+                1,
+                4,
+                2097143,
+                bloom_filters.clone(),
+                (0..8u64).collect::<Array1<_>>(),
+                thresholds.clone(),
+            )
+        };
+        let info = wnn().stats();
+
+        assert_eq!(info.image_shape, (2, 2));
+        assert_eq!(info.num_filters, 2);
+        assert_eq!(info.bloom_filter_density, vec![0.125, 1.0]);
+        assert_eq!(info.thresholds[0].max, 0);
+        assert_eq!(info.thresholds[1].min, 100);
+        assert_eq!(info.thresholds[1].max, 200);
+        assert_eq!(info.thresholds[1].mean, 125.0);
+        assert_eq!(info.circuit_params.bits_per_hash, 2);
+        assert_eq!(info.threshold_histogram[0], 4);
+        assert_eq!(info.threshold_histogram[6], 3);
+        assert_eq!(info.threshold_histogram[12], 1);
+        // 2 classes with 2 filters of 4 bits each
+        assert_eq!(info.total_lookup_rows, 16);
+        // The byte table is larger and determines the estimate
+        assert_eq!(info.byte_table_rows, 2304);
+        assert_eq!(info.estimated_k, 12);
+
+        // With the class lookup, both classes share the rows of a filter
+        let info = wnn().with_class_lookup(true).stats();
+        assert_eq!(info.total_lookup_rows, 8);
+        let info = wnn().with_window_num_bits(12).stats();
+        assert_eq!(info.byte_table_rows, 2304 + 4096);
+        assert_eq!(info.estimated_k, 13);
+    }
+}
@@ -0,0 +1,117 @@
+//! A registry of published models, trained with
+//! [zkp-gravity/BTHOWeN-0g](https://github.com/zkp-gravity/BTHOWeN-0g).
+//!
+//! See the [models readme](https://github.com/zkp-gravity/0g-halo2/blob/main/models/readme.md)
+//! for details on each model.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::download::{download, verify_sha256};
+
+/// The location of the published model files.
+const BASE_URL: &str = "https://raw.githubusercontent.com/zkp-gravity/0g-halo2/main/models/";
+
+/// A published model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZooModel {
+    /// The name used to look up the model, e.g. `mnist-small`.
+    pub name: &'static str,
+    /// The name of the HDF5 file.
+    pub file_name: &'static str,
+    /// The SHA-256 hash of the HDF5 file (in hex).
+    pub sha256: &'static str,
+    /// The smallest `k` (see [`crate::Wnn::mock_proof`]) for which the circuit fits.
+    pub k: u32,
+    /// Accuracy on the MNIST test set.
+    pub accuracy: f32,
+}
+
+impl ZooModel {
+    /// The URL of the HDF5 file.
+    pub fn url(&self) -> String {
+        format!("{BASE_URL}{}", self.file_name)
+    }
+}
+
+/// All published models.
+pub const MODELS: [ZooModel; 4] = [
+    ZooModel {
+        name: "mnist-tiny",
+        file_name: "model_28input_256entry_1hash_1bpi.hdf5",
+        sha256: "3e5850317481dc67751067174d38464263e8af75988101370545843f1c25c781",
+        k: 14,
+        accuracy: 0.8306,
+    },
+    ZooModel {
+        name: "mnist-small",
+        file_name: "model_28input_1024entry_2hash_2bpi.hdf5",
+        sha256: "09c458df2b963d0c230980c5b10b451137d1888900908349ba8e5cc1bbc337ea",
+        k: 15,
+        accuracy: 0.9281,
+    },
+    ZooModel {
+        name: "mnist-medium",
+        file_name: "model_28input_2048entry_2hash_3bpi.hdf5",
+        sha256: "3e5ca072b29dc30d104586445c3d61021d77b0684efda0e0a1ac9b00ba909613",
+        k: 15,
+        accuracy: 0.9395,
+    },
+    ZooModel {
+        name: "mnist-large",
+        file_name: "model_49input_8192entry_4hash_6bpi.hdf5",
+        sha256: "502946a664e5c88eda4568371fa05df5cbe702cfcb2f83d9a673c7c6c4f232f3",
+        k: 17,
+        accuracy: 0.9510,
+    },
+];
+
+/// Looks up a model by name.
+pub fn find_model(name: &str) -> Option<&'static ZooModel> {
+    MODELS.iter().find(|model| model.name == name)
+}
+
+/// Downloads the model with the given name into `dir`, verifying its checksum,
+/// and returns the path to the HDF5 file.
+///
+/// If a file with the right checksum already exists, it is not downloaded again.
+pub fn fetch_model(name: &str, dir: &Path) -> io::Result<PathBuf> {
+    let model = find_model(name).ok_or_else(|| {
+        let names = MODELS.map(|model| model.name).join(", ");
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Unknown model {name}, available models: {names}"),
+        )
+    })?;
+
+    let path = dir.join(model.file_name);
+    if let Ok(bytes) = fs::read(&path) {
+        if verify_sha256(model.file_name, &bytes, model.sha256).is_ok() {
+            return Ok(path);
+        }
+    }
+
+    let bytes = download(&model.url())?;
+    verify_sha256(model.file_name, &bytes, model.sha256)?;
+    fs::create_dir_all(dir)?;
+    fs::write(&path, bytes)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use crate::download::verify_sha256;
+
+    use super::MODELS;
+
+    #[test]
+    fn test_checksums_match_checked_in_models() {
+        for model in MODELS {
+            let bytes = fs::read(Path::new("models").join(model.file_name)).unwrap();
+            verify_sha256(model.file_name, &bytes, model.sha256).unwrap();
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Occlusion-based explanations: Proofs that the model still predicts a class if a region of
+//! the image is replaced by zeros, e.g. to show that a prediction doesn't depend on a
+//! watermark, or that it does depend on the region (if the predicted class changes).
+//!
+//! With [`crate::Wnn::with_occlusion`], the circuit replaces the pixels whose mask bit is set by
+//! zeros before binarizing the image and exposes the mask bits as public inputs, one per pixel
+//! in row-major order (see [`crate::gadgets::mask`]). The mask is a witness, so the same keys
+//! work for any region: [`crate::Wnn::occlusion_proof`] proves the prediction for a given
+//! [`Region`], and a verifier checks that the proof occludes exactly that region and predicts
+//! the expected class, see [`check_occlusion`].
+//!
+//! Proofs without an occluded region (e.g. by [`crate::Wnn::proof`]) expose a mask of zeros.
+
+use halo2_proofs::halo2curves::bn256::Fr as Fp;
+use ndarray::Array2;
+use thiserror::Error;
+
+use crate::gadgets::wnn::{InstanceLayout, PublicValue};
+use crate::utils::{argmax, try_to_u64};
+
+/// A rectangular region of an image, given by its top left pixel and its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub top: usize,
+    pub left: usize,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl Region {
+    pub fn new(top: usize, left: usize, height: usize, width: usize) -> Self {
+        Self {
+            top,
+            left,
+            height,
+            width,
+        }
+    }
+
+    /// Whether the pixel in the given row and column is part of the region.
+    pub fn contains(&self, row: usize, column: usize) -> bool {
+        (self.top..self.top + self.height).contains(&row)
+            && (self.left..self.left + self.width).contains(&column)
+    }
+
+    /// The mask of an image of the given shape, which is set for the pixels of the region.
+    /// Parts of the region outside of the image are ignored.
+    pub fn mask(&self, shape: (usize, usize)) -> Array2<bool> {
+        Array2::from_shape_fn(shape, |(row, column)| self.contains(row, column))
+    }
+
+    /// Returns a copy of the image with the pixels of the region replaced by zeros.
+    pub fn occlude(&self, image: &Array2<u8>) -> Array2<u8> {
+        let mut occluded = image.clone();
+        for ((row, column), pixel) in occluded.indexed_iter_mut() {
+            if self.contains(row, column) {
+                *pixel = 0;
+            }
+        }
+        occluded
+    }
+}
+
+/// The occlusion mask exposed by a proof, given its public inputs (in the order of
+/// [`InstanceLayout::values`]), in row-major order. `None` if the layout doesn't contain one or
+/// a mask bit is not a bit.
+pub fn exposed_mask(layout: &InstanceLayout, public_inputs: &[Fp]) -> Option<Vec<bool>> {
+    let mask: Vec<_> = layout
+        .values
+        .iter()
+        .zip(public_inputs)
+        .filter(|(value, _)| matches!(value, PublicValue::Occluded { .. }))
+        .map(|(_, x)| match try_to_u64(x) {
+            Some(0) => Some(false),
+            Some(1) => Some(true),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    (!mask.is_empty()).then_some(mask)
+}
+
+/// Reasons why [`check_occlusion`] fails.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum OcclusionError {
+    #[error("The proof doesn't expose an occlusion mask")]
+    NoMask,
+    #[error("The proof doesn't expose the scores of the classes")]
+    NoScores,
+    #[error("The occlusion mask has {actual} pixels, expected {expected}")]
+    MaskSize { expected: usize, actual: usize },
+    #[error("Pixel {pixel} is occluded by the proof but not by the region, or vice versa")]
+    WrongRegion { pixel: usize },
+    #[error("The proof predicts class {actual}, expected {expected}")]
+    WrongClass { expected: usize, actual: usize },
+}
+
+/// Checks that a proof occludes exactly the given region of an image of the given shape, and
+/// that the model predicts the given class for the occluded image (i.e. the class has the
+/// highest score).
+///
+/// The proof itself is not verified, see e.g. [`crate::facade::Verifier`].
+pub fn check_occlusion(
+    layout: &InstanceLayout,
+    public_inputs: &[Fp],
+    region: &Region,
+    image_shape: (usize, usize),
+    class: usize,
+) -> Result<(), OcclusionError> {
+    let mask = exposed_mask(layout, public_inputs).ok_or(OcclusionError::NoMask)?;
+    let expected_mask = region.mask(image_shape);
+    if mask.len() != expected_mask.len() {
+        return Err(OcclusionError::MaskSize {
+            expected: expected_mask.len(),
+            actual: mask.len(),
+        });
+    }
+    if let Some(pixel) = mask
+        .iter()
+        .zip(expected_mask.iter())
+        .position(|(actual, expected)| actual != expected)
+    {
+        return Err(OcclusionError::WrongRegion { pixel });
+    }
+
+    let scores = layout
+        .values
+        .iter()
+        .zip(public_inputs)
+        .filter(|(value, _)| matches!(value, PublicValue::Score { .. }))
+        .map(|(_, x)| try_to_u64(x))
+        .collect::<Option<Vec<_>>>()
+        .filter(|scores| !scores.is_empty())
+        .ok_or(OcclusionError::NoScores)?;
+    let actual = argmax(&scores);
+    if actual != class {
+        return Err(OcclusionError::WrongClass {
+            expected: class,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::halo2curves::bn256::Fr as Fp;
+    use ndarray::array;
+
+    use super::{check_occlusion, exposed_mask, OcclusionError, Region};
+    use crate::gadgets::wnn::{InstanceLayout, PublicValue};
+
+    fn layout() -> InstanceLayout {
+        InstanceLayout {
+            values: vec![
+                PublicValue::Score { class: 0 },
+                PublicValue::Score { class: 1 },
+            ]
+            .into_iter()
+            .chain((0..6).map(|pixel| PublicValue::Occluded { pixel }))
+            .collect(),
+            num_columns: 1,
+        }
+    }
+
+    fn public_inputs(scores: [u64; 2], mask: [u64; 6]) -> Vec<Fp> {
+        scores.into_iter().chain(mask).map(Fp::from).collect()
+    }
+
+    #[test]
+    fn test_region() {
+        let region = Region::new(1, 1, 1, 5);
+        assert_eq!(
+            region.mask((2, 3)),
+            array![[false, false, false], [false, true, true]]
+        );
+        assert_eq!(
+            region.occlude(&array![[1, 2, 3], [4, 5, 6]]),
+            array![[1, 2, 3], [4, 0, 0]]
+        );
+    }
+
+    #[test]
+    fn test_check_occlusion() {
+        let region = Region::new(1, 1, 1, 2);
+        let inputs = public_inputs([3, 5], [0, 0, 0, 0, 1, 1]);
+        assert_eq!(
+            exposed_mask(&layout(), &inputs),
+            Some(vec![false, false, false, false, true, true])
+        );
+        assert_eq!(
+            check_occlusion(&layout(), &inputs, &region, (2, 3), 1),
+            Ok(())
+        );
+        assert_eq!(
+            check_occlusion(&layout(), &inputs, &region, (2, 3), 0),
+            Err(OcclusionError::WrongClass {
+                expected: 0,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            check_occlusion(&layout(), &inputs, &Region::new(0, 1, 1, 2), (2, 3), 1),
+            Err(OcclusionError::WrongRegion { pixel: 1 })
+        );
+        assert_eq!(
+            check_occlusion(&layout(), &inputs, &region, (3, 3), 1),
+            Err(OcclusionError::MaskSize {
+                expected: 9,
+                actual: 6
+            })
+        );
+
+        let not_a_bit = public_inputs([3, 5], [0, 0, 0, 0, 1, 2]);
+        assert_eq!(
+            check_occlusion(&layout(), &not_a_bit, &region, (2, 3), 1),
+            Err(OcclusionError::NoMask)
+        );
+        let unmasked = InstanceLayout {
+            values: layout().values[..2].to_vec(),
+            num_columns: 1,
+        };
+        assert_eq!(
+            check_occlusion(&unmasked, &inputs, &region, (2, 3), 1),
+            Err(OcclusionError::NoMask)
+        );
+    }
+}
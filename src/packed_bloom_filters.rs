@@ -0,0 +1,211 @@
+//! Bit-packed storage for bloom filter arrays.
+
+use ndarray::Array3;
+
+/// Bloom filter arrays of shape `(num_classes, num_filters, num_filter_entries)`.
+///
+/// Bits are stored in row-major order, packed into `u64` words (least significant bit first),
+/// which uses 8x less memory than an `Array3<bool>`.
+/// The arrays can be built incrementally using [`Extend`], so that loaders can stream large
+/// models without ever materializing the unpacked tensor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedBloomFilters {
+    shape: [usize; 3],
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl PackedBloomFilters {
+    /// Creates an empty instance, which should be filled by extending it with
+    /// `shape[0] * shape[1] * shape[2]` bits.
+    pub fn with_shape(shape: [usize; 3]) -> Self {
+        let n_bits = shape.iter().product::<usize>();
+        Self {
+            shape,
+            words: Vec::with_capacity((n_bits + 63) / 64),
+            len: 0,
+        }
+    }
+
+    /// Constructs the arrays from bytes as written by [`crate::utils::pack_bits_le`].
+    /// Returns `None` if the number of bytes doesn't match the shape (including shapes whose
+    /// number of bits overflows).
+    pub fn from_le_bytes(shape: [usize; 3], bytes: &[u8]) -> Option<Self> {
+        let n_bits = shape
+            .iter()
+            .try_fold(1usize, |n, dim| n.checked_mul(*dim))?;
+        if bytes.len() != n_bits.div_ceil(8) {
+            return None;
+        }
+        let words = bytes
+            .chunks(8)
+            .map(|chunk| {
+                let mut word = [0u8; 8];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u64::from_le_bytes(word)
+            })
+            .collect();
+        Some(Self {
+            shape,
+            words,
+            len: n_bits,
+        })
+    }
+
+    /// Appends a single bit.
+    pub fn push(&mut self, bit: bool) {
+        if self.len % 64 == 0 {
+            self.words.push(0);
+        }
+        *self.words.last_mut().unwrap() |= (bit as u64) << (self.len % 64);
+        self.len += 1;
+    }
+
+    /// Whether all bits have been pushed.
+    pub fn is_complete(&self) -> bool {
+        self.len == self.shape.iter().product::<usize>()
+    }
+
+    pub fn shape(&self) -> [usize; 3] {
+        self.shape
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        (self.words[index / 64] >> (index % 64)) & 1 == 1
+    }
+
+    /// Returns the bit at the given position.
+    pub fn get(&self, class: usize, filter: usize, entry: usize) -> bool {
+        let [_, n_filters, n_entries] = self.shape;
+        assert!(filter < n_filters && entry < n_entries);
+        self.bit((class * n_filters + filter) * n_entries + entry)
+    }
+
+    /// Iterates over the bits of a single bloom filter.
+    pub fn filter(&self, class: usize, filter: usize) -> impl Iterator<Item = bool> + '_ {
+        let [_, n_filters, n_entries] = self.shape;
+        let start = (class * n_filters + filter) * n_entries;
+        (start..start + n_entries).map(|i| self.bit(i))
+    }
+
+    /// Iterates over the indices of the set entries of a single bloom filter, in increasing
+    /// order. Unlike [`PackedBloomFilters::filter`], this skips unset entries a word at a time.
+    pub fn set_entries(&self, class: usize, filter: usize) -> impl Iterator<Item = usize> + '_ {
+        let [_, n_filters, n_entries] = self.shape;
+        let start = (class * n_filters + filter) * n_entries;
+        let end = start + n_entries;
+        (start / 64..(end + 63) / 64).flat_map(move |w| {
+            // Mask out the bits of neighboring filters
+            let mut word = self.words[w] & (!0u64 << (start.max(w * 64) - w * 64));
+            if end - w * 64 < 64 {
+                word &= (1u64 << (end - w * 64)) - 1;
+            }
+            std::iter::from_fn(move || {
+                (word != 0).then(|| {
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    w * 64 + bit - start
+                })
+            })
+        })
+    }
+
+    /// The number of set entries of a single bloom filter.
+    pub fn count_set(&self, class: usize, filter: usize) -> usize {
+        self.set_entries(class, filter).count()
+    }
+
+    /// Iterates over all bits, in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(|i| self.bit(i))
+    }
+
+    /// Unpacks the bits into an array.
+    pub fn to_array(&self) -> Array3<bool> {
+        assert!(self.is_complete());
+        Array3::from_shape_vec(self.shape, self.iter().collect()).unwrap()
+    }
+}
+
+impl Extend<bool> for PackedBloomFilters {
+    fn extend<T: IntoIterator<Item = bool>>(&mut self, iter: T) {
+        for bit in iter {
+            self.push(bit);
+        }
+    }
+}
+
+impl From<&Array3<bool>> for PackedBloomFilters {
+    fn from(array: &Array3<bool>) -> Self {
+        let shape = array.shape();
+        let mut packed = Self::with_shape([shape[0], shape[1], shape[2]]);
+        packed.extend(array.iter().copied());
+        packed
+    }
+}
+
+impl From<Array3<bool>> for PackedBloomFilters {
+    fn from(array: Array3<bool>) -> Self {
+        Self::from(&array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array3;
+
+    use crate::utils::pack_bits_le;
+
+    use super::PackedBloomFilters;
+
+    fn example() -> Array3<bool> {
+        Array3::from_shape_fn((3, 5, 16), |(c, f, e)| (c * 7 + f * 3 + e) % 5 == 0)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let array = example();
+        let packed = PackedBloomFilters::from(&array);
+
+        assert!(packed.is_complete());
+        assert_eq!(packed.shape(), [3, 5, 16]);
+        assert_eq!(packed.to_array(), array);
+        assert_eq!(packed.get(2, 4, 9), array[[2, 4, 9]]);
+        assert_eq!(
+            packed.filter(1, 2).collect::<Vec<_>>(),
+            array.slice(ndarray::s![1, 2, ..]).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_set_entries() {
+        let array = example();
+        let packed = PackedBloomFilters::from(&array);
+        for class in 0..3 {
+            for filter in 0..5 {
+                let expected: Vec<_> = packed
+                    .filter(class, filter)
+                    .enumerate()
+                    .filter(|(_, bit)| *bit)
+                    .map(|(entry, _)| entry)
+                    .collect();
+                assert_eq!(
+                    packed.set_entries(class, filter).collect::<Vec<_>>(),
+                    expected
+                );
+                assert_eq!(packed.count_set(class, filter), expected.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_le_bytes() {
+        let array = example();
+        let bytes = pack_bits_le(array.iter().copied());
+
+        let packed = PackedBloomFilters::from_le_bytes([3, 5, 16], &bytes).unwrap();
+        assert_eq!(packed, PackedBloomFilters::from(&array));
+        assert!(PackedBloomFilters::from_le_bytes([3, 5, 17], &bytes).is_none());
+        assert!(PackedBloomFilters::from_le_bytes([usize::MAX, 2, 8], &bytes).is_none());
+    }
+}
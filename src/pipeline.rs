@@ -0,0 +1,351 @@
+//! Proves an image end to end with a single call, see [`Pipeline`].
+//!
+//! Every intermediate artifact is cached in a directory, under a name derived from the hashes
+//! of its inputs:
+//!
+//! - `srs/<hash(SRS)>.bin`: The SRS, named by the hash of its content
+//! - `srs/<source>-k<k>.ref`: The hash of the SRS last obtained from a source (not cached for
+//!   [`SrsSource::File`], which is read directly)
+//! - `keys/<hash(model commitment, SRS)>.pk`: The proving key
+//! - `proofs/<hash(verifying key fingerprint, image commitment)>.zgp`: The proof file
+//!
+//! So a second run with the same model and image returns the cached proof, and a run with a new
+//! image of the same model reuses the SRS and the proving key. Files are written to a unique
+//! temporary file and renamed, so several processes (or threads) can share a cache directory;
+//! at worst, an artifact is computed twice. Since an SRS is only read if its content matches
+//! its name, a proving key is never paired with another SRS than the one it was generated
+//! for. The proof is verified in every run, also if it was cached.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use halo2_proofs::halo2curves::bn256::{Bn256, G1Affine};
+use halo2_proofs::plonk::ProvingKey;
+use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+use tempfile::NamedTempFile;
+use thiserror::Error;
+use tracing::{info, instrument, warn};
+
+use crate::classifier::{ProvableClassifier, WnnInput};
+use crate::cost::minimal_k;
+use crate::error::ZeroGError;
+use crate::facade::Verifier;
+use crate::image_commitment::image_commitment;
+use crate::io::{
+    load_grayscale_image, load_model, read_pk_from_bytes, read_srs_from_bytes, KeyFormat,
+};
+use crate::key_file::write_pk_to;
+use crate::proof_file::ProofFile;
+use crate::prover::Prover;
+use crate::setup::{get_srs, SrsSource};
+use crate::utils::keccak256;
+use crate::verifier_bundle::VerificationError;
+use crate::wnn::Wnn;
+
+/// Errors returned by [`Pipeline::run`].
+#[derive(Debug, Error)]
+pub enum PipelineError {
+    #[error(transparent)]
+    ZeroG(#[from] ZeroGError),
+    /// The freshly generated proof did not verify.
+    #[error(transparent)]
+    Verification(#[from] VerificationError),
+}
+
+/// Which artifacts of a [`Pipeline::run`] were read from the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheHits {
+    pub srs: bool,
+    pub pk: bool,
+    pub proof: bool,
+}
+
+/// The result of a [`Pipeline::run`].
+#[derive(Debug)]
+pub struct PipelineOutput {
+    pub proof_file: ProofFile,
+    /// Where the proof file is cached.
+    pub proof_path: PathBuf,
+    /// The verified score of each class.
+    pub scores: Vec<u64>,
+    pub cache_hits: CacheHits,
+}
+
+/// Proves images with a cache of all intermediate artifacts, see the module documentation.
+pub struct Pipeline {
+    cache_dir: PathBuf,
+    srs_source: SrsSource,
+    k: Option<u32>,
+}
+
+impl Pipeline {
+    pub fn new(cache_dir: impl Into<PathBuf>, srs_source: SrsSource) -> Self {
+        Self {
+            cache_dir: cache_dir.into(),
+            srs_source,
+            k: None,
+        }
+    }
+
+    /// Uses an SRS of size `2^k`, instead of the smallest one that fits the model.
+    pub fn with_k(mut self, k: u32) -> Self {
+        self.k = Some(k);
+        self
+    }
+
+    /// Proves inference of the image and verifies the proof, generating or reading the SRS,
+    /// the proving key and the proof as needed.
+    #[instrument(skip(self))]
+    pub fn run(
+        &self,
+        model_path: &Path,
+        image_path: &Path,
+    ) -> Result<PipelineOutput, PipelineError> {
+        let wnn = load_model(model_path)?;
+        let image = load_grayscale_image(image_path)?;
+        let commitment = image_commitment(&image, None);
+        let input = WnnInput::Image(image);
+        wnn.check_input(&input)?;
+        let k = match self.k {
+            Some(k) => k,
+            None => minimal_k(&wnn)?,
+        };
+
+        let (kzg_params, srs_hash, srs_hit) = self.srs(k)?;
+        let (pk, pk_hit) = self.pk(&wnn, &kzg_params, srs_hash)?;
+        let prover = Prover::new(wnn, kzg_params, pk);
+        let verifier = Verifier::new(prover.verifier_bundle());
+
+        let proof_key = [prover.vk_fingerprint(), commitment].concat();
+        let proof_path = self.artifact_path("proofs", &proof_key, "zgp");
+        if let Some(bytes) = read_cached(&proof_path)? {
+            // A cached proof that doesn't verify (e.g. a corrupted file) is generated again
+            match ProofFile::from_bytes(&bytes)
+                .map_err(|e| e.to_string())
+                .and_then(|proof_file| {
+                    let scores = verifier.verify(&proof_file).map_err(|e| e.to_string())?;
+                    Ok((proof_file, scores))
+                }) {
+                Ok((proof_file, scores)) => {
+                    return Ok(PipelineOutput {
+                        proof_file,
+                        proof_path,
+                        scores,
+                        cache_hits: CacheHits {
+                            srs: srs_hit,
+                            pk: pk_hit,
+                            proof: true,
+                        },
+                    })
+                }
+                Err(e) => warn!("Ignoring cached proof {}: {e}", proof_path.display()),
+            }
+        }
+
+        info!("Proving");
+        let proof_file = prover.prove(&input)?;
+        let scores = verifier.verify(&proof_file)?;
+        write_cached(&proof_path, &proof_file.to_bytes())?;
+        Ok(PipelineOutput {
+            proof_file,
+            proof_path,
+            scores,
+            cache_hits: CacheHits {
+                srs: srs_hit,
+                pk: pk_hit,
+                proof: false,
+            },
+        })
+    }
+
+    /// Returns the SRS, its hash and whether it was cached.
+    fn srs(&self, k: u32) -> Result<(ParamsKZG<Bn256>, [u8; 32], bool), ZeroGError> {
+        let ref_path = self.srs_ref_path(k);
+        if let Some(ref_path) = &ref_path {
+            if let Some(hash) = read_cached(ref_path)? {
+                let path = self.srs_path(&hash);
+                if let Some(bytes) = read_cached(&path)? {
+                    let actual_hash = keccak256(&bytes);
+                    if actual_hash.as_slice() != hash {
+                        warn!("Ignoring cached SRS {}: Wrong hash", path.display());
+                    } else {
+                        let kzg_params =
+                            read_srs_from_bytes(&bytes).map_err(|source| ZeroGError::Format {
+                                path: path.clone(),
+                                format: "SRS",
+                                source: Box::new(source),
+                            })?;
+                        return Ok((kzg_params, actual_hash, true));
+                    }
+                }
+            }
+        }
+
+        info!("Getting the SRS for k = {k}");
+        let kzg_params = get_srs(&self.srs_source, k)?;
+        let mut bytes = vec![];
+        kzg_params
+            .write(&mut bytes)
+            .expect("Writing to a vector does not fail");
+        let hash = keccak256(&bytes);
+        if let Some(ref_path) = &ref_path {
+            // Write the SRS before referencing it
+            write_cached(&self.srs_path(&hash), &bytes)?;
+            write_cached(ref_path, &hash)?;
+        }
+        Ok((kzg_params, hash, false))
+    }
+
+    /// The path of the SRS with the given hash.
+    fn srs_path(&self, hash: &[u8]) -> PathBuf {
+        self.cache_dir
+            .join("srs")
+            .join(format!("{}.bin", hex::encode(hash)))
+    }
+
+    /// The path of the file referencing the SRS last obtained from the source.
+    fn srs_ref_path(&self, k: u32) -> Option<PathBuf> {
+        let source = match &self.srs_source {
+            SrsSource::Generate => "generated".to_string(),
+            SrsSource::File(_) => return None,
+            SrsSource::Store(location) => {
+                format!(
+                    "store-{}",
+                    hex::encode(&keccak256(location.as_bytes())[..8])
+                )
+            }
+            #[cfg(feature = "download")]
+            SrsSource::Ceremony => "ceremony".to_string(),
+        };
+        Some(
+            self.cache_dir
+                .join("srs")
+                .join(format!("{source}-k{k}.ref")),
+        )
+    }
+
+    /// Returns the proving key and whether it was cached.
+    fn pk(
+        &self,
+        wnn: &Wnn,
+        kzg_params: &ParamsKZG<Bn256>,
+        srs_hash: [u8; 32],
+    ) -> Result<(ProvingKey<G1Affine>, bool), ZeroGError> {
+        let key = [wnn.commitment(), srs_hash].concat();
+        let path = self.artifact_path("keys", &key, "pk");
+        if let Some(bytes) = read_cached(&path)? {
+            let pk = read_pk_from_bytes(&bytes, wnn.get_circuit_params()).map_err(|source| {
+                ZeroGError::Format {
+                    path: path.clone(),
+                    format: "proving key",
+                    source: Box::new(source),
+                }
+            })?;
+            return Ok((pk, true));
+        }
+
+        info!("Generating the proving key");
+        let pk = wnn.generate_proving_key(kzg_params)?;
+        let mut bytes = vec![];
+        write_pk_to(
+            &mut bytes,
+            &pk,
+            &wnn.get_circuit_params(),
+            KeyFormat::default(),
+        )
+        .expect("Writing to a vector does not fail");
+        write_cached(&path, &bytes)?;
+        Ok((pk, false))
+    }
+
+    /// The path of an artifact, named by the hash of `key`.
+    fn artifact_path(&self, dir: &str, key: &[u8], extension: &str) -> PathBuf {
+        self.cache_dir
+            .join(dir)
+            .join(format!("{}.{extension}", hex::encode(keccak256(key))))
+    }
+}
+
+/// Reads a cached artifact, or returns `None` if it isn't cached yet.
+fn read_cached(path: &Path) -> Result<Option<Vec<u8>>, ZeroGError> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(ZeroGError::Io {
+            action: "read",
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// Writes an artifact via a uniquely named temporary file in the same directory, so that other
+/// processes and threads never read a half-written artifact.
+fn write_cached(path: &Path, bytes: &[u8]) -> Result<(), ZeroGError> {
+    let write = || -> io::Result<()> {
+        let parent = path.parent().expect("Artifact paths have a parent");
+        fs::create_dir_all(parent)?;
+        let mut tmp_file = NamedTempFile::new_in(parent)?;
+        tmp_file.write_all(bytes)?;
+        tmp_file.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    };
+    write().map_err(|source| ZeroGError::Io {
+        action: "write",
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(all(test, feature = "hdf5"))]
+mod tests {
+    use std::{env, fs, path::Path, process, thread};
+
+    use super::{write_cached, CacheHits, Pipeline};
+    use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+    use crate::setup::SrsSource;
+
+    #[test]
+    fn test_pipeline_caches_artifacts() {
+        let (k, model_path) = MNIST_TINY;
+        let model_path = Path::new(model_path);
+        let dir = env::temp_dir().join(format!("zero_g_pipeline_{}", process::id()));
+        let pipeline = Pipeline::new(dir.join("cache"), SrsSource::Generate).with_k(k);
+
+        let first = pipeline.run(model_path, Path::new(TEST_IMG_PATH)).unwrap();
+        let second = pipeline.run(model_path, Path::new(TEST_IMG_PATH)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first.cache_hits, CacheHits::default());
+        assert_eq!(
+            second.cache_hits,
+            CacheHits {
+                srs: true,
+                pk: true,
+                proof: true,
+            }
+        );
+        assert_eq!(first.scores, second.scores);
+    }
+
+    #[test]
+    fn test_concurrent_writes() {
+        let dir = env::temp_dir().join(format!("zero_g_pipeline_writes_{}", process::id()));
+        let path = dir.join("artifact");
+        thread::scope(|scope| {
+            for i in 0..8u8 {
+                let path = &path;
+                scope.spawn(move || write_cached(path, &[i; 1 << 16]).unwrap());
+            }
+        });
+        let bytes = fs::read(&path).unwrap();
+        let num_files = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // One of the writes wins, without leftover temporary files
+        assert!(bytes.iter().all(|b| *b == bytes[0]));
+        assert_eq!(bytes.len(), 1 << 16);
+        assert_eq!(num_files, 1);
+    }
+}
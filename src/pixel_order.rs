@@ -0,0 +1,108 @@
+//! Adapting a model to images whose pixels are stored in a different order than the images it
+//! was trained on, see [`PixelOrder`] and [`crate::Wnn::with_pixel_order`].
+//!
+//! A mismatch in the pixel order does not cause any error, the model just predicts (almost)
+//! randomly. Instead of reordering every image, the reordering can be composed with the input
+//! permutation (and the binarization thresholds) of the model once.
+
+use std::str::FromStr;
+
+/// The order of the pixels of the images to predict, relative to the training images.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PixelOrder {
+    /// Images are stored like the training images.
+    RowMajor,
+    /// Images are the transpose of the training images, e.g. because column-major data was
+    /// read as row-major.
+    ColumnMajor,
+    /// For each pixel of an image (in row-major order), the index of the same pixel in the
+    /// training images (in row-major order). The shape of the images is unchanged.
+    Custom(Vec<usize>),
+}
+
+impl FromStr for PixelOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "row-major" => Ok(Self::RowMajor),
+            "column-major" => Ok(Self::ColumnMajor),
+            _ => Err(format!(
+                "Unknown pixel order {s:?}, expected row-major or column-major"
+            )),
+        }
+    }
+}
+
+impl PixelOrder {
+    /// Given the shape `(rows, columns)` of the training images, returns the shape of the images
+    /// to predict, and for each of their pixels, the index of the pixel in the training images.
+    pub(crate) fn training_indices(
+        &self,
+        (rows, columns): (usize, usize),
+    ) -> Result<((usize, usize), Vec<usize>), String> {
+        match self {
+            Self::RowMajor => Ok(((rows, columns), (0..rows * columns).collect())),
+            Self::ColumnMajor => {
+                // Pixel (i, j) of the transposed image is pixel (j, i) of the training image
+                let indices = (0..columns)
+                    .flat_map(|i| (0..rows).map(move |j| j * columns + i))
+                    .collect();
+                Ok(((columns, rows), indices))
+            }
+            Self::Custom(indices) => {
+                check_permutation(indices.iter().map(|i| *i as u64), rows * columns)
+                    .map_err(|e| format!("Invalid pixel order: {e}"))?;
+                Ok(((rows, columns), indices.clone()))
+            }
+        }
+    }
+}
+
+/// Checks that `values` is a permutation of `0..len`.
+pub(crate) fn check_permutation(
+    values: impl ExactSizeIterator<Item = u64>,
+    len: usize,
+) -> Result<(), String> {
+    if values.len() != len {
+        return Err(format!("Expected {len} entries, got {}", values.len()));
+    }
+    let mut seen = vec![false; len];
+    for (index, value) in values.enumerate() {
+        match seen.get_mut(value as usize) {
+            None => {
+                return Err(format!(
+                    "Entry {index} is {value}, which is out of range [0, {len})"
+                ))
+            }
+            Some(seen) if *seen => {
+                return Err(format!("Entry {index} is {value}, which appears twice"))
+            }
+            Some(seen) => *seen = true,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_permutation, PixelOrder};
+
+    #[test]
+    fn test_training_indices() {
+        assert_eq!(
+            PixelOrder::ColumnMajor.training_indices((2, 3)).unwrap(),
+            ((3, 2), vec![0, 3, 1, 4, 2, 5])
+        );
+        assert!(PixelOrder::Custom(vec![1, 0, 2])
+            .training_indices((1, 3))
+            .is_ok());
+        assert!(PixelOrder::Custom(vec![1, 1, 2])
+            .training_indices((1, 3))
+            .is_err());
+
+        assert!(check_permutation([2, 0, 1].into_iter(), 3).is_ok());
+        assert!(check_permutation([2, 0, 3].into_iter(), 3).is_err());
+        assert!(check_permutation([2, 0].into_iter(), 3).is_err());
+    }
+}
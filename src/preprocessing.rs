@@ -0,0 +1,313 @@
+//! Normalization of arbitrary input images into the format a model was trained on.
+//!
+//! A [`PreprocessingConfig`] describes how an image is cropped, resized, padded and
+//! rescaled. It can be stored as JSON next to the model (see [`PreprocessingConfig::path_for_model`]),
+//! so that provers use the exact same preprocessing as the training code.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use image::error::{ParameterError, ParameterErrorKind};
+use image::{imageops, DynamicImage, GrayImage, ImageError, Luma};
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+use crate::image_loading::{convert_image, ImageLoadOptions};
+use crate::wnn::Wnn;
+
+/// The filter used when resizing images, see [`imageops::FilterType`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResizeFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for imageops::FilterType {
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Nearest => Self::Nearest,
+            ResizeFilter::Triangle => Self::Triangle,
+            ResizeFilter::CatmullRom => Self::CatmullRom,
+            ResizeFilter::Gaussian => Self::Gaussian,
+            ResizeFilter::Lanczos3 => Self::Lanczos3,
+        }
+    }
+}
+
+/// How pixel intensities are rescaled after resizing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IntensityRescaling {
+    /// Keep intensities as they are.
+    None,
+    /// Linearly stretch intensities, so that the darkest pixel becomes 0 and the
+    /// brightest pixel becomes 255.
+    MinMax,
+}
+
+/// Describes the preprocessing applied to input images.
+///
+/// The steps are applied in this order:
+/// 1. Convert to grayscale (see [`ImageLoadOptions`])
+/// 2. Optionally invert intensities (e.g. for dark digits on white paper)
+/// 3. Optionally crop the largest centered region with the aspect ratio of the output
+/// 4. Resize to `width - 2 * padding` by `height - 2 * padding` pixels
+/// 5. Pad with black pixels to `width` by `height` pixels
+/// 6. Rescale intensities
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreprocessingConfig {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub padding: u32,
+    #[serde(default)]
+    pub center_crop: bool,
+    pub resize_filter: ResizeFilter,
+    #[serde(default)]
+    pub invert: bool,
+    pub intensity_rescaling: IntensityRescaling,
+    #[serde(default)]
+    pub image_load_options: ImageLoadOptions,
+}
+
+impl PreprocessingConfig {
+    /// Creates a config that only resizes images to the given shape.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            padding: 0,
+            center_crop: false,
+            resize_filter: ResizeFilter::Triangle,
+            invert: false,
+            intensity_rescaling: IntensityRescaling::None,
+            image_load_options: ImageLoadOptions::default(),
+        }
+    }
+
+    /// Creates a config that only resizes images to the input shape of the given model.
+    pub fn for_wnn(wnn: &Wnn) -> Self {
+        let (height, width) = wnn.img_shape();
+        Self::new(width as u32, height as u32)
+    }
+
+    /// Checks that the output shape is not empty and that the padding leaves at least one pixel
+    /// for the resized image.
+    pub fn validate(&self) -> Result<(), ParameterError> {
+        let error = |message: &str| {
+            Err(ParameterError::from_kind(ParameterErrorKind::Generic(
+                message.to_string(),
+            )))
+        };
+        if self.width == 0 || self.height == 0 {
+            return error("The output shape must not be empty");
+        }
+        if 2 * self.padding as u64 >= self.width.min(self.height) as u64 {
+            return error("Padding is too large for the output shape");
+        }
+        Ok(())
+    }
+
+    /// Applies the preprocessing to a decoded image, returning an array of shape `(height, width)`.
+    /// Fails if the config is invalid (see [`Self::validate`]).
+    pub fn apply(&self, image: &DynamicImage) -> Result<Array2<u8>, ImageError> {
+        self.validate().map_err(ImageError::Parameter)?;
+
+        let mut pixels = convert_image(image, self.image_load_options);
+        if self.invert {
+            pixels.mapv_inplace(|x| 255 - x);
+        }
+        let (rows, cols) = pixels.dim();
+        let mut image = GrayImage::from_raw(cols as u32, rows as u32, pixels.into_raw_vec())
+            .expect("Shape matches the number of pixels");
+
+        if self.center_crop {
+            image = center_crop(&image, self.width, self.height);
+        }
+
+        let (inner_width, inner_height) = (
+            self.width - 2 * self.padding,
+            self.height - 2 * self.padding,
+        );
+        if image.dimensions() != (inner_width, inner_height) {
+            image = imageops::resize(&image, inner_width, inner_height, self.resize_filter.into());
+        }
+
+        let mut padded = GrayImage::from_pixel(self.width, self.height, Luma([0]));
+        imageops::overlay(
+            &mut padded,
+            &image,
+            self.padding as i64,
+            self.padding as i64,
+        );
+
+        let mut pixels = Array2::from_shape_vec(
+            (self.height as usize, self.width as usize),
+            padded.into_raw(),
+        )
+        .expect("Shape matches the number of pixels");
+        if self.intensity_rescaling == IntensityRescaling::MinMax {
+            rescale_min_max(&mut pixels);
+        }
+        Ok(pixels)
+    }
+
+    /// Loads an image from disk and applies the preprocessing.
+    pub fn load_image(&self, path: &Path) -> Result<Array2<u8>, ImageError> {
+        self.apply(&image::open(path)?)
+    }
+
+    /// The path at which the config for the model at `model_path` is stored,
+    /// e.g. `models/model.preprocessing.json` for `models/model.hdf5`.
+    pub fn path_for_model(model_path: &Path) -> PathBuf {
+        model_path.with_extension("preprocessing.json")
+    }
+
+    /// Reads the config stored next to the given model, if it exists.
+    pub fn read_for_model(model_path: &Path) -> io::Result<Option<Self>> {
+        let path = Self::path_for_model(model_path);
+        if path.exists() {
+            Self::read(&path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Write the config to file (as JSON).
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+
+    /// Read the config from file. Fails with [`io::ErrorKind::InvalidData`] if the config is
+    /// invalid (see [`Self::validate`]).
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let config: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        config
+            .validate()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(config)
+    }
+}
+
+/// Crops the largest centered region with the aspect ratio `width:height`.
+fn center_crop(image: &GrayImage, width: u32, height: u32) -> GrayImage {
+    let (image_width, image_height) = image.dimensions();
+    // Compare aspect ratios without dividing: image_width / image_height > width / height
+    let (crop_width, crop_height) =
+        if image_width as u64 * height as u64 > image_height as u64 * width as u64 {
+            (
+                (image_height as u64 * width as u64 / height as u64) as u32,
+                image_height,
+            )
+        } else {
+            (
+                image_width,
+                (image_width as u64 * height as u64 / width as u64) as u32,
+            )
+        };
+    imageops::crop_imm(
+        image,
+        (image_width - crop_width) / 2,
+        (image_height - crop_height) / 2,
+        crop_width,
+        crop_height,
+    )
+    .to_image()
+}
+
+fn rescale_min_max(pixels: &mut Array2<u8>) {
+    let min = pixels.iter().copied().min().unwrap_or(0) as u32;
+    let max = pixels.iter().copied().max().unwrap_or(0) as u32;
+    if max > min {
+        pixels.mapv_inplace(|x| ((x as u32 - min) * 255 / (max - min)) as u8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, io, process};
+
+    use image::{DynamicImage, GrayImage, Luma};
+    use ndarray::array;
+
+    use super::{IntensityRescaling, PreprocessingConfig};
+
+    fn gray(width: u32, height: u32, f: impl Fn(u32, u32) -> u8) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| Luma([f(x, y)])))
+    }
+
+    #[test]
+    fn test_identity() {
+        let image = gray(4, 3, |x, y| (x * 10 + y) as u8);
+        let config = PreprocessingConfig::new(4, 3);
+        assert_eq!(
+            config.apply(&image).unwrap(),
+            array![[0, 10, 20, 30], [1, 11, 21, 31], [2, 12, 22, 32]]
+        );
+    }
+
+    #[test]
+    fn test_center_crop_and_padding() {
+        // A wide image with a bright square in the center
+        let image = gray(12, 4, |x, _| if (4..8).contains(&x) { 200 } else { 0 });
+        let config = PreprocessingConfig {
+            padding: 1,
+            center_crop: true,
+            ..PreprocessingConfig::new(6, 6)
+        };
+        let expected = ndarray::Array2::from_shape_fn((6, 6), |(i, j)| {
+            if (1..5).contains(&i) && (1..5).contains(&j) {
+                200
+            } else {
+                0
+            }
+        });
+        assert_eq!(config.apply(&image).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_invert_and_rescale() {
+        let image = gray(2, 1, |x, _| 100 + 50 * x as u8);
+        let config = PreprocessingConfig {
+            invert: true,
+            intensity_rescaling: IntensityRescaling::MinMax,
+            ..PreprocessingConfig::new(2, 1)
+        };
+        assert_eq!(config.apply(&image).unwrap(), array![[255, 0]]);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let image = gray(4, 4, |_, _| 0);
+        let too_much_padding = PreprocessingConfig {
+            padding: 2,
+            ..PreprocessingConfig::new(4, 5)
+        };
+        assert!(too_much_padding.apply(&image).is_err());
+        assert!(PreprocessingConfig::new(0, 4).apply(&image).is_err());
+
+        let path = env::temp_dir().join(format!("zero_g_preprocessing_{}.json", process::id()));
+        PreprocessingConfig::new(4, 0).write(&path).unwrap();
+        let read = PreprocessingConfig::read(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_serialization() {
+        let config = PreprocessingConfig {
+            padding: 4,
+            ..PreprocessingConfig::new(28, 28)
+        };
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PreprocessingConfig>(&json).unwrap(),
+            config
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Links the proofs of a sequence of images (e.g. the frames of a video), so that a verifier
+//! can check that the images were classified in order.
+//!
+//! With [`crate::Wnn::with_chaining`], the circuit exposes the previous chaining value and the
+//! chaining value of the image as additional public inputs (see [`crate::Wnn::chained_proof`]).
+//! The chaining value of the `i`-th image is `c_i = chain(c_(i-1), commitment_i)`, the Poseidon
+//! hash computed by [`crate::gadgets::image_commitment::chain`], where `c_(-1)` is [`GENESIS`]
+//! and `commitment_i` is the unsalted commitment to the image (see
+//! [`crate::image_commitment`]). Given the committed stream, a verifier recomputes the chaining
+//! values and checks that the `i`-th proof exposes `c_i`, see [`check_chain`].
+//!
+//! The circuit computes the commitment to the witnessed image and the chaining value from the
+//! public previous chaining value, so a proof exposing `c_i` shows that it was generated for
+//! the `i`-th image of the chain. Proofs can't be reordered, dropped or reused in another
+//! position of the chain without the check failing.
+
+use halo2_proofs::halo2curves::bn256::Fr as Fp;
+use thiserror::Error;
+
+use crate::gadgets::image_commitment::chain;
+use crate::gadgets::poseidon::{bytes_to_field, PoseidonSpec};
+use crate::gadgets::wnn::{InstanceLayout, PublicValue};
+use crate::image_commitment::{commitment_from_field, commitment_to_field};
+use crate::proof_file::ProofFile;
+
+/// The value preceding the first chaining value of a chain.
+pub const GENESIS: [u8; 32] = [0; 32];
+
+/// Computes the chaining value of an image, given the chaining value of the previous image.
+pub fn chaining_value(previous: &[u8; 32], image_commitment: &[u8; 32]) -> [u8; 32] {
+    commitment_from_field(&chain(
+        &PoseidonSpec::new(),
+        chaining_value_to_field(previous),
+        commitment_to_field(image_commitment),
+    ))
+}
+
+/// Converts a chaining value into a public input, interpreting it as a big-endian integer
+/// (modulo the field order, chaining values are always smaller).
+pub fn chaining_value_to_field(chaining_value: &[u8; 32]) -> Fp {
+    bytes_to_field(chaining_value)
+}
+
+/// The inverse of [`chaining_value_to_field`].
+pub fn chaining_value_from_field(x: &Fp) -> [u8; 32] {
+    commitment_from_field(x)
+}
+
+/// Computes the chaining values of a sequence of images, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofChain {
+    previous: [u8; 32],
+}
+
+impl ProofChain {
+    /// Starts a new chain.
+    pub fn new() -> Self {
+        Self::resume(GENESIS)
+    }
+
+    /// Continues a chain whose last chaining value is `previous`.
+    pub fn resume(previous: [u8; 32]) -> Self {
+        Self { previous }
+    }
+
+    /// The last chaining value, or [`GENESIS`] for an empty chain.
+    pub fn previous(&self) -> [u8; 32] {
+        self.previous
+    }
+
+    /// Appends the image with the given commitment and returns its chaining value.
+    pub fn push(&mut self, image_commitment: &[u8; 32]) -> [u8; 32] {
+        self.previous = chaining_value(&self.previous, image_commitment);
+        self.previous
+    }
+}
+
+impl Default for ProofChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The chaining value exposed by a proof, given its public inputs (in the order of
+/// [`InstanceLayout::values`]), or `None` if the layout doesn't contain one.
+pub fn exposed_chaining_value(layout: &InstanceLayout, public_inputs: &[Fp]) -> Option<[u8; 32]> {
+    let index = layout
+        .values
+        .iter()
+        .position(|value| *value == PublicValue::ChainingValue)?;
+    public_inputs.get(index).map(chaining_value_from_field)
+}
+
+/// Reasons why [`check_chain`] fails.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ChainError {
+    #[error("The proofs don't expose a chaining value")]
+    NoChainingValue,
+    #[error("Expected one proof per image ({images}), got {proofs}")]
+    Length { proofs: usize, images: usize },
+    #[error(
+        "Proof {index} exposes the chaining value {}, expected {}",
+        hex::encode(actual),
+        hex::encode(expected)
+    )]
+    Mismatch {
+        index: usize,
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+}
+
+/// Checks that the proofs expose the chaining values of the images with the given commitments,
+/// in order, continuing the chain after `previous` ([`GENESIS`] for a new chain). Returns the
+/// last chaining value, e.g. to check the next part of the stream.
+///
+/// The proofs themselves are not verified, see e.g.
+/// [`crate::verifier_bundle::VerifierBundle::verify_batch`].
+pub fn check_chain(
+    layout: &InstanceLayout,
+    proofs: &[ProofFile],
+    image_commitments: &[[u8; 32]],
+    previous: [u8; 32],
+) -> Result<[u8; 32], ChainError> {
+    if !layout.values.contains(&PublicValue::ChainingValue) {
+        return Err(ChainError::NoChainingValue);
+    }
+    if proofs.len() != image_commitments.len() {
+        return Err(ChainError::Length {
+            proofs: proofs.len(),
+            images: image_commitments.len(),
+        });
+    }
+    let mut chain = ProofChain::resume(previous);
+    for (index, (proof, image_commitment)) in proofs.iter().zip(image_commitments).enumerate() {
+        let expected = chain.push(image_commitment);
+        let actual = exposed_chaining_value(layout, &proof.public_inputs)
+            .ok_or(ChainError::NoChainingValue)?;
+        if actual != expected {
+            return Err(ChainError::Mismatch {
+                index,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(chain.previous())
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::halo2curves::bn256::Fr as Fp;
+
+    use super::{
+        chaining_value, chaining_value_from_field, chaining_value_to_field, check_chain,
+        ChainError, ProofChain, GENESIS,
+    };
+    use crate::gadgets::wnn::{InstanceLayout, PublicValue};
+    use crate::proof_file::ProofFile;
+
+    fn layout() -> InstanceLayout {
+        InstanceLayout {
+            values: vec![
+                PublicValue::Score { class: 0 },
+                PublicValue::Score { class: 1 },
+                PublicValue::PreviousChainingValue,
+                PublicValue::ChainingValue,
+            ],
+            num_columns: 1,
+        }
+    }
+
+    fn proof(previous: &[u8; 32], chaining_value: &[u8; 32]) -> ProofFile {
+        let public_inputs = vec![
+            Fp::from(1),
+            Fp::from(2),
+            chaining_value_to_field(previous),
+            chaining_value_to_field(chaining_value),
+        ];
+        ProofFile::new(vec![], public_inputs)
+    }
+
+    #[test]
+    fn test_chaining_value() {
+        let value = chaining_value(&GENESIS, &[1; 32]);
+        assert_eq!(value, chaining_value(&GENESIS, &[1; 32]));
+        assert_ne!(value, chaining_value(&GENESIS, &[2; 32]));
+        assert_ne!(value, chaining_value(&value, &[1; 32]));
+
+        let x = chaining_value_to_field(&value);
+        assert_eq!(chaining_value_from_field(&x), value);
+        assert_eq!(chaining_value_to_field(&GENESIS), Fp::from(0));
+        let mut one = [0; 32];
+        one[31] = 1;
+        assert_eq!(chaining_value_to_field(&one), Fp::from(1));
+    }
+
+    #[test]
+    fn test_check_chain() {
+        let commitments = [[1; 32], [2; 32], [3; 32]];
+        let mut chain = ProofChain::new();
+        let proofs: Vec<_> = commitments
+            .iter()
+            .map(|commitment| {
+                let previous = chain.previous();
+                proof(&previous, &chain.push(commitment))
+            })
+            .collect();
+        let last = chain.previous();
+        assert_eq!(
+            check_chain(&layout(), &proofs, &commitments, GENESIS),
+            Ok(last)
+        );
+
+        // The chain can be checked in parts
+        let middle = check_chain(&layout(), &proofs[..1], &commitments[..1], GENESIS).unwrap();
+        assert_eq!(
+            check_chain(&layout(), &proofs[1..], &commitments[1..], middle),
+            Ok(last)
+        );
+
+        let reordered = [proofs[1].clone(), proofs[0].clone(), proofs[2].clone()];
+        assert!(matches!(
+            check_chain(&layout(), &reordered, &commitments, GENESIS),
+            Err(ChainError::Mismatch { index: 0, .. })
+        ));
+        let dropped = [proofs[0].clone(), proofs[2].clone()];
+        assert!(matches!(
+            check_chain(&layout(), &dropped, &commitments[..2], GENESIS),
+            Err(ChainError::Mismatch { index: 1, .. })
+        ));
+        assert_eq!(
+            check_chain(&layout(), &proofs, &commitments[..2], GENESIS),
+            Err(ChainError::Length {
+                proofs: 3,
+                images: 2
+            })
+        );
+
+        let unchained = InstanceLayout {
+            values: layout().values[..2].to_vec(),
+            num_columns: 1,
+        };
+        assert_eq!(
+            check_chain(&unchained, &proofs, &commitments, GENESIS),
+            Err(ChainError::NoChainingValue)
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! A versioned binary container for proofs (`.zgp` files).
+//!
+//! The layout is as follows (all integers are little endian):
+//!
+//! | Field                 | Size                       |
+//! |-----------------------|----------------------------|
+//! | Magic bytes (`ZGPF`)  | 4 bytes                    |
+//! | Format version        | 2 bytes                    |
+//! | Metadata length       | 4 bytes                    |
+//! | Metadata (JSON)       | `metadata length` bytes    |
+//! | Proof length          | 4 bytes                    |
+//! | Proof                 | `proof length` bytes       |
+//! | Number of inputs      | 4 bytes                    |
+//! | Public inputs         | 32 bytes per field element |
+//!
+//! Files written before this format existed (JSON-serialized [`ProofWithOutput`])
+//! are treated as version 0 and are upgraded transparently when read.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use ff::PrimeField;
+use halo2_proofs::halo2curves::bn256::Fr;
+use serde::{Deserialize, Serialize};
+
+use crate::gadgets::wnn::WnnCircuitParams;
+use crate::io::{
+    invalid_data, read_array, read_length_prefixed, write_length_prefixed, ProofWithOutput,
+};
+
+/// Magic bytes at the start of every `.zgp` file.
+pub const MAGIC: [u8; 4] = *b"ZGPF";
+
+/// The format version written by this version of the crate.
+pub const CURRENT_VERSION: u16 = 1;
+
+/// Metadata stored alongside the proof.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofMetadata {
+    /// Version of `zero_g` that created the proof.
+    pub crate_version: String,
+    /// Parameters of the circuit the proof was generated for, if known.
+    #[serde(default)]
+    pub circuit_params: Option<WnnCircuitParams>,
+    /// Fingerprint of the verification key the proof was generated for, if known
+    /// (see [`crate::verifier_bundle::vk_fingerprint`]).
+    #[serde(default)]
+    pub vk_fingerprint: Option<[u8; 32]>,
+    /// Commitment to the model the proof was generated for, if known (see
+    /// [`crate::Wnn::commitment`]).
+    #[serde(default)]
+    pub model_commitment: Option<[u8; 32]>,
+}
+
+impl Default for ProofMetadata {
+    fn default() -> Self {
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            circuit_params: None,
+            vk_fingerprint: None,
+            model_commitment: None,
+        }
+    }
+}
+
+/// A proof, its public inputs and some metadata.
+#[derive(Debug, Clone)]
+pub struct ProofFile {
+    pub metadata: ProofMetadata,
+    pub proof: Vec<u8>,
+    pub public_inputs: Vec<Fr>,
+}
+
+impl ProofFile {
+    pub fn new(proof: Vec<u8>, public_inputs: Vec<Fr>) -> Self {
+        Self {
+            metadata: ProofMetadata::default(),
+            proof,
+            public_inputs,
+        }
+    }
+
+    /// Records the circuit parameters in the metadata.
+    pub fn with_circuit_params(mut self, circuit_params: WnnCircuitParams) -> Self {
+        self.metadata.circuit_params = Some(circuit_params);
+        self
+    }
+
+    /// Records the fingerprint of the verification key in the metadata.
+    pub fn with_vk_fingerprint(mut self, vk_fingerprint: [u8; 32]) -> Self {
+        self.metadata.vk_fingerprint = Some(vk_fingerprint);
+        self
+    }
+
+    /// Records the commitment to the model in the metadata.
+    pub fn with_model_commitment(mut self, model_commitment: [u8; 32]) -> Self {
+        self.metadata.model_commitment = Some(model_commitment);
+        self
+    }
+
+    /// Serializes the proof file using the current format version.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write_to(&mut bytes)
+            .expect("Writing to a vector should not fail");
+        bytes
+    }
+
+    /// Serializes the proof file using the current format version.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let metadata = serde_json::to_vec(&self.metadata)?;
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&CURRENT_VERSION.to_le_bytes())?;
+        write_length_prefixed(writer, &metadata)?;
+        write_length_prefixed(writer, &self.proof)?;
+        writer.write_all(&(self.public_inputs.len() as u32).to_le_bytes())?;
+        for input in &self.public_inputs {
+            writer.write_all(input.to_repr().as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Deserializes a proof file of any known format version.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if !bytes.starts_with(&MAGIC) {
+            return Self::from_legacy_json(bytes);
+        }
+
+        let mut reader = &bytes[MAGIC.len()..];
+        let version = u16::from_le_bytes(read_array(&mut reader)?);
+        match version {
+            1 => Self::read_v1(&mut reader),
+            _ => Err(invalid_data(format!(
+                "Unsupported proof file version {version} (latest supported: {CURRENT_VERSION})"
+            ))),
+        }
+    }
+
+    fn read_v1(reader: &mut impl Read) -> io::Result<Self> {
+        let metadata = serde_json::from_slice(&read_length_prefixed(reader)?)?;
+        let proof = read_length_prefixed(reader)?;
+
+        let n_inputs = u32::from_le_bytes(read_array(reader)?);
+        let public_inputs = (0..n_inputs)
+            .map(|_| {
+                let mut repr = <Fr as PrimeField>::Repr::default();
+                reader.read_exact(repr.as_mut())?;
+                Option::from(Fr::from_repr(repr))
+                    .ok_or_else(|| invalid_data("Public input is not a canonical field element"))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            metadata,
+            proof,
+            public_inputs,
+        })
+    }
+
+    /// Version 0: A JSON-serialized [`ProofWithOutput`] without metadata.
+    fn from_legacy_json(bytes: &[u8]) -> io::Result<Self> {
+        let ProofWithOutput { proof, output } = serde_json::from_slice(bytes)
+            .map_err(|_| invalid_data("Not a zero_g proof file (missing magic bytes)"))?;
+        Ok(Self {
+            metadata: ProofMetadata {
+                crate_version: "unknown".to_string(),
+                circuit_params: None,
+                vk_fingerprint: None,
+                model_commitment: None,
+            },
+            proof,
+            public_inputs: output,
+        })
+    }
+}
+
+impl From<ProofWithOutput> for ProofFile {
+    fn from(proof_with_output: ProofWithOutput) -> Self {
+        Self::new(proof_with_output.proof, proof_with_output.output)
+    }
+}
+
+impl From<ProofFile> for (Vec<u8>, Vec<Fr>) {
+    fn from(proof_file: ProofFile) -> Self {
+        (proof_file.proof, proof_file.public_inputs)
+    }
+}
+
+/// Write a proof file to disk, using the current format version.
+pub fn write_proof_file(proof_file: &ProofFile, path: &Path) -> io::Result<()> {
+    fs::write(path, proof_file.to_bytes())
+}
+
+/// Read a proof file from disk. Older format versions are upgraded in memory.
+pub fn read_proof_file(path: &Path) -> io::Result<ProofFile> {
+    ProofFile::from_bytes(&fs::read(path)?)
+}
+
+/// Rewrite a proof file of any known version in the current format version.
+pub fn upgrade_proof_file(path: &Path) -> io::Result<()> {
+    let proof_file = read_proof_file(path)?;
+    write_proof_file(&proof_file, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::halo2curves::bn256::Fr;
+
+    use crate::io::ProofWithOutput;
+
+    use super::{ProofFile, CURRENT_VERSION, MAGIC};
+
+    fn example() -> ProofFile {
+        ProofFile::new(vec![1, 2, 3, 4], vec![Fr::from(9), -Fr::one()]).with_vk_fingerprint([7; 32])
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let bytes = example().to_bytes();
+        assert_eq!(bytes[..4], MAGIC);
+        assert_eq!(bytes[4..6], CURRENT_VERSION.to_le_bytes());
+
+        let proof_file = ProofFile::from_bytes(&bytes).unwrap();
+        assert_eq!(proof_file.proof, vec![1, 2, 3, 4]);
+        assert_eq!(proof_file.public_inputs, vec![Fr::from(9), -Fr::one()]);
+        assert_eq!(proof_file.metadata.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(proof_file.metadata.vk_fingerprint, Some([7; 32]));
+    }
+
+    #[test]
+    fn test_upgrade_legacy_json() {
+        let legacy = ProofWithOutput {
+            proof: vec![5, 6],
+            output: vec![Fr::from(3)],
+        };
+        let bytes = serde_json::to_vec(&legacy).unwrap();
+
+        let proof_file = ProofFile::from_bytes(&bytes).unwrap();
+        assert_eq!(proof_file.proof, vec![5, 6]);
+        assert_eq!(proof_file.public_inputs, vec![Fr::from(3)]);
+    }
+
+    #[test]
+    fn test_unknown_version() {
+        let mut bytes = example().to_bytes();
+        bytes[4..6].copy_from_slice(&(CURRENT_VERSION + 1).to_le_bytes());
+        assert!(ProofFile::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = example().to_bytes();
+        assert!(ProofFile::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
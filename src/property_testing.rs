@@ -0,0 +1,234 @@
+//! Property tests of the circuit over random small models and images, based on
+//! [proptest](https://docs.rs/proptest).
+//!
+//! [`check_properties`] checks for each generated [`TestCase`] that
+//! - the circuit is satisfied by the scores computed by [`Wnn::predict`] (see
+//!   [`check_circuit_matches_predict`]),
+//! - the circuit is not satisfied if any score is changed, and
+//! - the circuit rejects corrupted witness values (see [`check_tampering_rejected`]).
+//!
+//! The suite is exposed (with the `proptest` feature) rather than only being run by the tests
+//! of this crate, so that forks changing the circuit can run it from their own tests:
+//! ```ignore
+//! #[test]
+//! fn circuit_properties() {
+//!     zero_g::property_testing::check_properties(&Default::default()).unwrap();
+//! }
+//! ```
+
+use halo2_proofs::dev::MockProver;
+use halo2_proofs::halo2curves::bn256::Fr as Fp;
+use ndarray::{Array2, Array3};
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestError, TestRunner};
+
+use crate::cost::minimal_k;
+use crate::testing::{check_circuit_matches_predict, check_tampering_rejected};
+use crate::utils::is_prime;
+use crate::wnn::{Wnn, MAX_FILTER_INPUTS};
+
+/// Bounds of the generated models and images.
+#[derive(Debug, Clone)]
+pub struct PropertyTestConfig {
+    /// The number of test cases.
+    pub cases: u32,
+    pub max_classes: usize,
+    /// The maximum height and width of the images.
+    pub max_image_side: usize,
+    /// The maximum number of thresholds per pixel.
+    pub max_bits_per_input: usize,
+    /// The maximum number of bits of the bloom filter indices, i.e. the filters have at most
+    /// `2^max_entry_bits` entries.
+    pub max_entry_bits: usize,
+    pub max_filter_hashes: usize,
+}
+
+impl Default for PropertyTestConfig {
+    fn default() -> Self {
+        Self {
+            cases: 16,
+            max_classes: 3,
+            max_image_side: 4,
+            max_bits_per_input: 2,
+            max_entry_bits: 4,
+            max_filter_hashes: 2,
+        }
+    }
+}
+
+/// A random model (see [`TestCase::wnn`]) and image.
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    pub num_classes: usize,
+    pub num_filter_entries: usize,
+    pub num_filter_hashes: usize,
+    pub num_filter_inputs: usize,
+    pub p: u64,
+    pub bloom_filters: Array3<bool>,
+    pub input_permutation: Vec<u64>,
+    pub binarization_thresholds: Array3<u16>,
+    pub image: Array2<u8>,
+}
+
+impl TestCase {
+    pub fn wnn(&self) -> Wnn {
+        Wnn::new(
+            self.num_classes,
+            self.num_filter_entries,
+            self.num_filter_hashes,
+            self.num_filter_inputs,
+            self.p,
+            self.bloom_filters.clone(),
+            self.input_permutation.iter().copied().collect(),
+            self.binarization_thresholds.clone(),
+        )
+    }
+}
+
+/// The largest prime `p` in `[2^l, 2^(l + 1))`, as required by the hash function (see
+/// [`Wnn::validate`]).
+fn hash_prime(l: usize) -> u64 {
+    ((1u64 << l)..(1u64 << (l + 1)))
+        .rev()
+        .find(|p| is_prime(*p))
+        .expect("There is a prime between n and 2n")
+}
+
+/// Generates valid models (see [`Wnn::validate`]) within the bounds of the config, and images
+/// of their input shape.
+pub fn test_cases(config: &PropertyTestConfig) -> impl Strategy<Value = TestCase> {
+    (
+        2..=config.max_classes.max(2),
+        1..=config.max_image_side,
+        1..=config.max_image_side,
+        1..=config.max_bits_per_input,
+        1..=config.max_entry_bits,
+        1..=config.max_filter_hashes,
+        any::<prop::sample::Index>(),
+    )
+        .prop_flat_map(
+            |(num_classes, height, width, bits_per_input, entry_bits, num_filter_hashes, index)| {
+                let num_input_bits = height * width * bits_per_input;
+                let divisors: Vec<_> = (1..=num_input_bits.min(MAX_FILTER_INPUTS))
+                    .filter(|d| num_input_bits % d == 0)
+                    .collect();
+                let num_filter_inputs = *index.get(&divisors);
+                let num_filter_entries = 1 << entry_bits;
+                let num_filters = num_input_bits / num_filter_inputs;
+                (
+                    Just((
+                        num_classes,
+                        num_filter_entries,
+                        num_filter_hashes,
+                        num_filter_inputs,
+                    )),
+                    prop::collection::vec(
+                        any::<bool>(),
+                        num_classes * num_filters * num_filter_entries,
+                    ),
+                    Just((0..num_input_bits as u64).collect::<Vec<_>>()).prop_shuffle(),
+                    prop::collection::vec(0..=256u16, num_input_bits),
+                    prop::collection::vec(any::<u8>(), height * width),
+                    Just((height, width, bits_per_input, num_filters)),
+                )
+            },
+        )
+        .prop_map(
+            |(
+                (num_classes, num_filter_entries, num_filter_hashes, num_filter_inputs),
+                bloom_filters,
+                input_permutation,
+                thresholds,
+                pixels,
+                (height, width, bits_per_input, num_filters),
+            )| {
+                let l = num_filter_hashes * num_filter_entries.trailing_zeros() as usize;
+                TestCase {
+                    num_classes,
+                    num_filter_entries,
+                    num_filter_hashes,
+                    num_filter_inputs,
+                    p: hash_prime(l),
+                    bloom_filters: Array3::from_shape_vec(
+                        (num_classes, num_filters, num_filter_entries),
+                        bloom_filters,
+                    )
+                    .unwrap(),
+                    input_permutation,
+                    binarization_thresholds: Array3::from_shape_vec(
+                        (height, width, bits_per_input),
+                        thresholds,
+                    )
+                    .unwrap(),
+                    image: Array2::from_shape_vec((height, width), pixels).unwrap(),
+                }
+            },
+        )
+}
+
+/// Checks the properties of the module documentation for a single test case.
+pub fn check_test_case(test_case: &TestCase) -> Result<(), TestCaseError> {
+    let wnn = test_case.wnn();
+    prop_assert!(wnn.validate().is_ok(), "{:?}", wnn.validate());
+    let k = minimal_k(&wnn).map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+    let divergence = check_circuit_matches_predict(&wnn, &[test_case.image.clone()], k)
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+    prop_assert!(divergence.is_none(), "{}", divergence.unwrap());
+
+    let circuit = wnn.get_circuit(&test_case.image);
+    let public_inputs = wnn.public_inputs(&test_case.image);
+    for (column, values) in public_inputs.iter().enumerate() {
+        for row in 0..values.len() {
+            let mut tampered = public_inputs.clone();
+            tampered[column][row] += Fp::from(1);
+            let prover = MockProver::run(k, &circuit, tampered)
+                .map_err(|e| TestCaseError::fail(e.to_string()))?;
+            prop_assert!(
+                prover.verify().is_err(),
+                "Changing public input {row} of column {column} is not detected"
+            );
+        }
+    }
+
+    let accepted = check_tampering_rejected(&wnn, &test_case.image, k)
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+    prop_assert!(
+        accepted.is_empty(),
+        "The circuit accepts corrupted witnesses: {accepted:?}"
+    );
+    Ok(())
+}
+
+/// Runs [`check_test_case`] on `config.cases` random test cases, returning the (shrunk) first
+/// failing test case, if any.
+pub fn check_properties(config: &PropertyTestConfig) -> Result<(), TestError<TestCase>> {
+    let mut runner = TestRunner::new(Config {
+        cases: config.cases,
+        ..Config::default()
+    });
+    runner.run(&test_cases(config), |test_case| check_test_case(&test_case))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_properties, hash_prime, PropertyTestConfig};
+
+    #[test]
+    fn test_hash_prime() {
+        assert_eq!(hash_prime(1), 3);
+        assert_eq!(hash_prime(10), 2039);
+        assert_eq!(hash_prime(20), 2097143);
+    }
+
+    #[test]
+    fn test_properties() {
+        let config = PropertyTestConfig {
+            cases: 4,
+            ..Default::default()
+        };
+        if let Err(e) = check_properties(&config) {
+            panic!("{e}");
+        }
+    }
+}
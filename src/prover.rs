@@ -0,0 +1,187 @@
+//! A prover that keeps the model, the SRS and the proving key in memory, so that loading them
+//! is only paid once when proving many images (e.g. in a long-running service). Likewise, the
+//! image-independent parts of the circuit are computed once, see
+//! [`ProvableClassifier::synthesis_cache`].
+//!
+//! The prover works with any [`ProvableClassifier`]; loading from an artifact directory is
+//! currently only implemented for [`Wnn`].
+
+use std::path::Path;
+use std::time::{Instant, SystemTime};
+
+use crate::classifier::ProvableClassifier;
+use crate::error::ZeroGError;
+use crate::io::{invalid_data, load_model, read_pk_with_format, read_srs};
+use crate::memory::measure_peak;
+use crate::proof_file::ProofFile;
+use crate::setup::{SetupManifest, SETUP_MANIFEST_FILE_NAME};
+use crate::telemetry::{ProofTelemetry, TelemetryLog};
+use crate::verifier_bundle::VerifierBundle;
+use crate::wnn::Wnn;
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, G1Affine},
+    plonk::ProvingKey,
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+};
+
+/// Proves inference of a fixed model, see the module documentation.
+pub struct Prover<C: ProvableClassifier = Wnn> {
+    model: C,
+    kzg_params: ParamsKZG<Bn256>,
+    pk: ProvingKey<G1Affine>,
+    synthesis_cache: C::SynthesisCache,
+    vk_fingerprint: [u8; 32],
+    telemetry: Option<TelemetryLog>,
+}
+
+impl<C: ProvableClassifier> Prover<C> {
+    pub fn new(model: C, kzg_params: ParamsKZG<Bn256>, pk: ProvingKey<G1Affine>) -> Self {
+        let vk_fingerprint = model.vk_fingerprint(pk.get_vk());
+        let synthesis_cache = model.synthesis_cache();
+        Self {
+            model,
+            kzg_params,
+            pk,
+            synthesis_cache,
+            vk_fingerprint,
+            telemetry: None,
+        }
+    }
+
+    /// Writes a telemetry record for every proof (including failed ones) to the log.
+    pub fn with_telemetry(mut self, telemetry: TelemetryLog) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    pub fn telemetry(&self) -> Option<&TelemetryLog> {
+        self.telemetry.as_ref()
+    }
+
+    pub fn model(&self) -> &C {
+        &self.model
+    }
+
+    /// See [`ProvableClassifier::vk_fingerprint`].
+    pub fn vk_fingerprint(&self) -> [u8; 32] {
+        self.vk_fingerprint
+    }
+
+    /// Checks that the model accepts the input, without proving.
+    pub fn check_input(&self, input: &C::Input) -> Result<(), ZeroGError> {
+        self.model.check_input(input)
+    }
+
+    /// Proves inference of the input. The proof file also records the fingerprint of the
+    /// verification key and the commitment to the model.
+    pub fn prove(&self, input: &C::Input) -> Result<ProofFile, ZeroGError> {
+        self.check_input(input)?;
+        let started_at = SystemTime::now();
+        let start = Instant::now();
+        let (result, peak_heap) = measure_peak(|| {
+            self.model
+                .prove_with_cache(&self.pk, &self.kzg_params, &self.synthesis_cache, input)
+        });
+        if let Some(telemetry) = &self.telemetry {
+            let record = ProofTelemetry::new(
+                self.model.commitment(),
+                self.kzg_params.k(),
+                started_at,
+                start.elapsed(),
+                result.as_ref().map(|proof_file| proof_file.proof.len()),
+            );
+            telemetry.record(&record.with_peak_heap(peak_heap));
+        }
+        Ok(result?
+            .with_vk_fingerprint(self.vk_fingerprint)
+            .with_model_commitment(self.model.commitment()))
+    }
+}
+
+impl Prover<Wnn> {
+    /// Loads the model and the SRS and proving key from an artifact directory written by
+    /// [`crate::setup::setup`].
+    ///
+    /// Returns an error if the artifacts were generated for a different model.
+    pub fn open(model_path: &Path, artifact_dir: &Path) -> Result<Self, ZeroGError> {
+        let wnn = load_model(model_path)?;
+        let manifest = SetupManifest::read(artifact_dir)?;
+        if manifest.model_commitment != wnn.commitment() {
+            return Err(ZeroGError::Format {
+                path: artifact_dir.join(SETUP_MANIFEST_FILE_NAME),
+                format: "setup manifest",
+                source: Box::new(invalid_data(
+                    "The artifacts were generated for a different model",
+                )),
+            });
+        }
+
+        let kzg_params = read_srs(&artifact_dir.join(&manifest.files.srs))?;
+        let pk = read_pk_with_format(
+            &artifact_dir.join(&manifest.files.pk),
+            wnn.get_circuit_params(),
+            manifest.key_format,
+        )?;
+        Ok(Self::new(wnn, kzg_params, pk))
+    }
+
+    pub fn wnn(&self) -> &Wnn {
+        &self.model
+    }
+
+    /// A bundle to verify the proofs of this prover.
+    pub fn verifier_bundle(&self) -> VerifierBundle {
+        VerifierBundle::new(
+            &self.model,
+            self.pk.get_vk().clone(),
+            self.kzg_params.clone(),
+        )
+    }
+}
+
+#[cfg(all(test, feature = "hdf5"))]
+mod tests {
+    use std::{env, fs, path::Path, process};
+
+    use super::Prover;
+    use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+    use crate::io::load_grayscale_image;
+    use crate::setup::{setup, SrsSource};
+    use crate::telemetry::{read_telemetry, TelemetryLog};
+    use crate::{load_wnn, Wnn};
+
+    #[test]
+    fn test_prover() {
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let dir = env::temp_dir().join(format!("zero_g_prover_{}", process::id()));
+        setup(&wnn, Some(k), &SrsSource::Generate, &dir).unwrap();
+
+        let telemetry_path = dir.join("telemetry.jsonl");
+        let prover = Prover::open(Path::new(model_path), &dir)
+            .unwrap()
+            .with_telemetry(TelemetryLog::open(&telemetry_path).unwrap());
+        let img = load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap();
+        let proof_file = prover.prove(&img.into()).unwrap();
+        let telemetry = read_telemetry(&telemetry_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(telemetry.len(), 1);
+        assert!(telemetry[0].success);
+        assert_eq!(telemetry[0].k, k);
+        assert_eq!(telemetry[0].model_commitment, wnn.commitment());
+        assert_eq!(telemetry[0].proof_size, Some(proof_file.proof.len()));
+
+        assert_eq!(
+            proof_file.metadata.vk_fingerprint,
+            Some(prover.vk_fingerprint())
+        );
+        assert_eq!(proof_file.metadata.model_commitment, Some(wnn.commitment()));
+        Wnn::verify_proof(
+            &proof_file.proof,
+            &prover.kzg_params,
+            prover.pk.get_vk(),
+            &proof_file.public_inputs,
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Shrinking the bloom filters of a trained model for deployment, see [`Calibration`].
+//!
+//! Bloom filters are usually sized generously for training, but most of their entries are not
+//! needed afterwards. [`Calibration::collect`] records the inputs that each filter responds to
+//! on a calibration dataset (e.g. the training set), and [`Calibration::repack`] builds a model
+//! with smaller bloom filters that only store these inputs. The range of the hash function
+//! shrinks with the filters (a new prime `p` is chosen), and with it the lookup tables of the
+//! circuit, which usually determine `k`.
+//!
+//! The repacked model responds to every recorded input, so it predicts the calibration set
+//! like the original model, up to false positives of the smaller filters. Inputs that were not
+//! seen during calibration are lost, so the calibration set should be representative.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use image::ImageError;
+use ndarray::Array3;
+use serde::Serialize;
+
+use crate::datasets::Example;
+use crate::error::ZeroGError;
+use crate::utils::is_prime;
+use crate::wnn::Wnn;
+
+/// The inputs each filter of a model responds to on a dataset, see [`Calibration::collect`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Calibration {
+    num_examples: usize,
+    /// The inputs of each filter with a positive response, indexed by class and filter.
+    hits: Vec<Vec<BTreeSet<u128>>>,
+}
+
+/// Summary of a [`Calibration::repack`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PruningReport {
+    pub num_examples: usize,
+    /// The number of distinct (class, filter, input) triples with a positive response.
+    pub hit_inputs: usize,
+    pub original_filter_entries: usize,
+    pub num_filter_entries: usize,
+    /// The prime of the hash function of the repacked model.
+    pub p: u64,
+    /// The number of set entries over all bloom filters of the original model.
+    pub original_set_entries: usize,
+    /// The number of set entries over all bloom filters of the repacked model.
+    pub set_entries: usize,
+}
+
+impl Calibration {
+    /// Records the positive filter responses of the model on each example.
+    pub fn collect(
+        wnn: &Wnn,
+        examples: impl IntoIterator<Item = Result<Example, ImageError>>,
+    ) -> Result<Self, ImageError> {
+        let num_filters = wnn.bloom_filters.shape()[1];
+        let mut hits = vec![vec![BTreeSet::new(); num_filters]; wnn.num_classes];
+        let mut num_examples = 0;
+        for example in examples {
+            let example = example?;
+            for (filter, input) in wnn.filter_indices(&example.image).into_iter().enumerate() {
+                for (class, class_hits) in hits.iter_mut().enumerate() {
+                    if wnn.bloom_filter_lookup(class, filter, input) {
+                        class_hits[filter].insert(input);
+                    }
+                }
+            }
+            num_examples += 1;
+        }
+        Ok(Self { num_examples, hits })
+    }
+
+    pub fn num_examples(&self) -> usize {
+        self.num_examples
+    }
+
+    /// The number of distinct (class, filter, input) triples with a positive response.
+    pub fn num_hit_inputs(&self) -> usize {
+        self.hits.iter().flatten().map(BTreeSet::len).sum()
+    }
+
+    /// Builds a model with `num_filter_entries` entries per bloom filter (a power of two), in
+    /// which each filter responds to the inputs recorded for it.
+    ///
+    /// `p` is chosen as the smallest prime that fits the new hash range. All other parameters
+    /// are taken from `wnn`, which must be the model the calibration was collected with.
+    pub fn repack(
+        &self,
+        wnn: &Wnn,
+        num_filter_entries: usize,
+    ) -> Result<(Wnn, PruningReport), ZeroGError> {
+        let [num_classes, num_filters, original_filter_entries] = wnn.bloom_filters.shape();
+        if self.hits.len() != num_classes || self.hits[0].len() != num_filters {
+            return Err(ZeroGError::InvalidModel(
+                "The calibration was collected with a different model".to_string(),
+            ));
+        }
+        if !num_filter_entries.is_power_of_two() {
+            return Err(ZeroGError::InvalidModel(format!(
+                "Number of filter entries must be a power of two, got {num_filter_entries}"
+            )));
+        }
+        let l = wnn.num_filter_hashes * num_filter_entries.trailing_zeros() as usize;
+        let p = (l > 0 && l < 63)
+            .then(|| ((1u64 << l)..(1u64 << (l + 1))).find(|p| is_prime(*p)))
+            .flatten()
+            .ok_or_else(|| {
+                ZeroGError::InvalidModel(format!(
+                    "The hash range {num_filter_entries}^{} is not supported",
+                    wnn.num_filter_hashes
+                ))
+            })?;
+
+        let mut repacked = Wnn::new(
+            num_classes,
+            num_filter_entries,
+            wnn.num_filter_hashes,
+            wnn.num_filter_inputs,
+            p,
+            Array3::from_elem((num_classes, num_filters, num_filter_entries), false),
+            wnn.input_permutation.clone(),
+            wnn.binarization_thresholds.clone(),
+        );
+        let mut bloom_filters =
+            Array3::from_elem((num_classes, num_filters, num_filter_entries), false);
+        for (class, class_hits) in self.hits.iter().enumerate() {
+            for (filter, inputs) in class_hits.iter().enumerate() {
+                for input in inputs {
+                    for entry in repacked.filter_entries(*input) {
+                        bloom_filters[(class, filter, entry)] = true;
+                    }
+                }
+            }
+        }
+        let set_entries = bloom_filters.iter().filter(|b| **b).count();
+        repacked.bloom_filters = bloom_filters.into();
+        repacked.quantization_policy = wnn.quantization_policy;
+        repacked.min_blinding_factors = wnn.min_blinding_factors;
+        repacked.num_instance_columns = wnn.num_instance_columns;
+        repacked.class_lookup = wnn.class_lookup;
+        repacked.window_num_bits = wnn.window_num_bits;
+        repacked.validate()?;
+
+        let report = PruningReport {
+            num_examples: self.num_examples,
+            hit_inputs: self.num_hit_inputs(),
+            original_filter_entries,
+            num_filter_entries,
+            p,
+            original_set_entries: wnn.bloom_filters.iter().filter(|b| *b).count(),
+            set_entries,
+        };
+        Ok((repacked, report))
+    }
+}
+
+impl fmt::Display for PruningReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Calibrated on {} examples: {} inputs with a positive response",
+            self.num_examples, self.hit_inputs
+        )?;
+        writeln!(
+            f,
+            "  Filter entries: {} -> {} (p = {})",
+            self.original_filter_entries, self.num_filter_entries, self.p
+        )?;
+        write!(
+            f,
+            "  Set entries:    {} -> {}",
+            self.original_set_entries, self.set_entries
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Array1, Array2, Array3};
+
+    use super::Calibration;
+    use crate::datasets::Example;
+    use crate::wnn::Wnn;
+
+    #[test]
+    fn test_repack() {
+        let thresholds = Array3::from_shape_fn((4, 3, 2), |(i, j, b)| (i * 50 + j * 7 + b) as u16);
+        let wnn = Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            2097143,
+            Array3::from_elem((2, 2, 1024), true),
+            (0..24u64).rev().collect::<Array1<_>>(),
+            thresholds,
+        );
+        let images: Vec<_> = (0..5u8)
+            .map(|i| Array2::from_shape_fn((4, 3), |(r, c)| i * 40 + (r * 3 + c) as u8 * 5))
+            .collect();
+        let examples = images.iter().enumerate().map(|(label, image)| {
+            Ok(Example {
+                id: label.to_string(),
+                image: image.clone(),
+                label: label % 2,
+            })
+        });
+
+        let calibration = Calibration::collect(&wnn, examples).unwrap();
+        assert_eq!(calibration.num_examples(), 5);
+        let (repacked, report) = calibration.repack(&wnn, 128).unwrap();
+        assert!(repacked.validate().is_ok());
+        assert_eq!(report.num_filter_entries, 128);
+        assert!((1 << 14..1 << 15).contains(&report.p));
+        assert!(report.set_entries <= 2 * report.hit_inputs);
+
+        // Every calibration image still gets the full score
+        for image in &images {
+            assert_eq!(repacked.predict(image), wnn.predict(image));
+        }
+        assert!(calibration.repack(&wnn, 100).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Quantization of the binarization thresholds, see [`QuantizationPolicy`].
+//!
+//! Training produces thresholds as floats in `[0, 1]`, while the circuit compares `u8` pixel
+//! intensities with integer thresholds. The policy determines how a float threshold `x` is
+//! mapped to an integer: `clamp(rounding(x * 255), min, max)`.
+//!
+//! The default policy (ceil, clamped to `[0, 256]`) preserves the predictions exactly, because
+//! for any intensity `i`, `i >= x * 255` holds iff `i >= ceil(x * 255)`. A threshold of 256 is
+//! never reached. Other policies are only needed to reproduce a training pipeline that
+//! quantized differently.
+//!
+//! [`QuantizationAudit`] checks on a dataset that the quantized model (which is what the
+//! circuit proves) predicts the same classes as the trained model.
+
+use std::fmt;
+use std::str::FromStr;
+
+use image::ImageError;
+use ndarray::Array3;
+use serde::{Deserialize, Serialize};
+
+use crate::datasets::Example;
+use crate::utils::argmax;
+use crate::wnn::Wnn;
+
+/// How scaled thresholds are rounded to integers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    #[default]
+    Ceil,
+    Floor,
+    /// Round half away from zero.
+    Round,
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ceil => "ceil",
+            Self::Floor => "floor",
+            Self::Round => "round",
+        })
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ceil" => Ok(Self::Ceil),
+            "floor" => Ok(Self::Floor),
+            "round" => Ok(Self::Round),
+            _ => Err(format!(
+                "Unknown rounding {s:?}, expected ceil, floor or round"
+            )),
+        }
+    }
+}
+
+/// Maps float thresholds to the integer thresholds of the circuit, see the module
+/// documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuantizationPolicy {
+    pub rounding: Rounding,
+    /// The smallest quantized threshold.
+    pub min: u16,
+    /// The largest quantized threshold.
+    pub max: u16,
+}
+
+impl Default for QuantizationPolicy {
+    fn default() -> Self {
+        Self {
+            rounding: Rounding::Ceil,
+            min: 0,
+            max: 256,
+        }
+    }
+}
+
+impl QuantizationPolicy {
+    /// Quantizes a threshold in `[0, 1]`.
+    pub fn quantize(&self, threshold: f32) -> u16 {
+        let scaled = threshold * 255.0;
+        let rounded = match self.rounding {
+            Rounding::Ceil => scaled.ceil(),
+            Rounding::Floor => scaled.floor(),
+            Rounding::Round => scaled.round(),
+        };
+        rounded.max(self.min as f32).min(self.max as f32) as u16
+    }
+
+    /// A float threshold that is quantized to `threshold`, e.g. to export a quantized model
+    /// in the HDF5 format.
+    pub fn dequantize(&self, threshold: u16) -> f32 {
+        let offset = match self.rounding {
+            Rounding::Ceil => -0.5,
+            Rounding::Floor => 0.5,
+            Rounding::Round => 0.0,
+        };
+        (threshold as f32 + offset) / 255.0
+    }
+}
+
+impl fmt::Display for QuantizationPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, clamped to [{}, {}]",
+            self.rounding, self.min, self.max
+        )
+    }
+}
+
+/// An example whose predicted class changes through quantization.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangedPrediction {
+    /// See [`Example::id`].
+    pub id: String,
+    pub label: usize,
+    /// The scores with the float thresholds.
+    pub float_scores: Vec<u64>,
+    /// The scores with the quantized thresholds.
+    pub quantized_scores: Vec<u64>,
+    pub float_class: usize,
+    pub quantized_class: usize,
+}
+
+/// Compares the predictions with float and quantized thresholds, see
+/// [`QuantizationAudit::run`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuantizationAudit {
+    pub num_examples: usize,
+    /// The number of examples where any score changes, which includes all changed predictions.
+    pub changed_scores: usize,
+    /// The number of correct predictions with the float thresholds.
+    pub float_correct: usize,
+    /// The number of correct predictions with the quantized thresholds.
+    pub quantized_correct: usize,
+    pub changed_predictions: Vec<ChangedPrediction>,
+}
+
+impl QuantizationAudit {
+    /// Predicts every example with the float thresholds (e.g. from
+    /// [`crate::io::load_float_thresholds`]) and with the quantized thresholds of the model.
+    pub fn run(
+        wnn: &Wnn,
+        float_thresholds: &Array3<f32>,
+        examples: impl IntoIterator<Item = Result<Example, ImageError>>,
+    ) -> Result<Self, ImageError> {
+        let mut audit = Self {
+            num_examples: 0,
+            changed_scores: 0,
+            float_correct: 0,
+            quantized_correct: 0,
+            changed_predictions: vec![],
+        };
+        for example in examples {
+            let example = example?;
+            let float_scores = wnn.predict_with_float_thresholds(&example.image, float_thresholds);
+            let quantized_scores = wnn.predict(&example.image);
+            let float_class = argmax(&float_scores);
+            let quantized_class = argmax(&quantized_scores);
+
+            audit.num_examples += 1;
+            audit.float_correct += (float_class == example.label) as usize;
+            audit.quantized_correct += (quantized_class == example.label) as usize;
+            if float_scores != quantized_scores {
+                audit.changed_scores += 1;
+            }
+            if float_class != quantized_class {
+                audit.changed_predictions.push(ChangedPrediction {
+                    id: example.id,
+                    label: example.label,
+                    float_scores,
+                    quantized_scores,
+                    float_class,
+                    quantized_class,
+                });
+            }
+        }
+        Ok(audit)
+    }
+
+    /// Whether the quantized model predicts the same class for every example.
+    pub fn is_equivalent(&self) -> bool {
+        self.changed_predictions.is_empty()
+    }
+}
+
+impl fmt::Display for QuantizationAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let accuracy = |correct: usize| correct as f64 / self.num_examples.max(1) as f64 * 100.0;
+        writeln!(f, "Audited {} examples", self.num_examples)?;
+        writeln!(
+            f,
+            "  Accuracy (float):     {:.2}%",
+            accuracy(self.float_correct)
+        )?;
+        writeln!(
+            f,
+            "  Accuracy (quantized): {:.2}%",
+            accuracy(self.quantized_correct)
+        )?;
+        writeln!(f, "  Changed scores:       {}", self.changed_scores)?;
+        write!(
+            f,
+            "  Changed predictions:  {}",
+            self.changed_predictions.len()
+        )?;
+        for changed in &self.changed_predictions {
+            write!(
+                f,
+                "\n    {} (label {}): {} -> {}",
+                changed.id, changed.label, changed.float_class, changed.quantized_class
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array1, Array3};
+
+    use super::{QuantizationAudit, QuantizationPolicy, Rounding};
+    use crate::datasets::Example;
+    use crate::wnn::Wnn;
+
+    #[test]
+    fn test_quantize() {
+        let threshold = 100.4 / 255.0;
+        let policy = |rounding| QuantizationPolicy {
+            rounding,
+            ..Default::default()
+        };
+        assert_eq!(policy(Rounding::Ceil).quantize(threshold), 101);
+        assert_eq!(policy(Rounding::Floor).quantize(threshold), 100);
+        assert_eq!(policy(Rounding::Round).quantize(threshold), 100);
+
+        let clamped = QuantizationPolicy {
+            rounding: Rounding::Ceil,
+            min: 1,
+            max: 255,
+        };
+        assert_eq!(clamped.quantize(-0.1), 1);
+        assert_eq!(clamped.quantize(1.5), 255);
+
+        for rounding in [Rounding::Ceil, Rounding::Floor, Rounding::Round] {
+            for t in 0..=256 {
+                assert_eq!(policy(rounding).quantize(policy(rounding).dequantize(t)), t);
+            }
+        }
+    }
+
+    #[test]
+    fn test_audit() {
+        // One filter per class over the two bits of a 1x2 image. Class 0 responds to the
+        // input 0b00, class 1 to 0b01 and 0b11. The hash `x^3 % 7 % 4` maps them to 0, 1 and 2.
+        let mut bloom_filters = Array3::from_elem((2, 1, 4), false);
+        bloom_filters[[0, 0, 0]] = true;
+        bloom_filters[[1, 0, 1]] = true;
+        bloom_filters[[1, 0, 2]] = true;
+        let float_thresholds = array![[[100.4f32 / 255.0], [0.5]]];
+        let policy = QuantizationPolicy {
+            rounding: Rounding::Floor,
+            ..Default::default()
+        };
+        let wnn = Wnn::new(
+            2,
+            4,
+            1,
+            2,
+            7,
+            bloom_filters,
+            (0..2u64).collect::<Array1<_>>(),
+            float_thresholds.map(|t| policy.quantize(*t)),
+        );
+
+        // A pixel of 100 is below the float threshold, but not below the floored one
+        let examples = [(0, array![[100u8, 0]]), (1, array![[200, 200]])]
+            .into_iter()
+            .map(|(label, image)| {
+                Ok(Example {
+                    id: label.to_string(),
+                    image,
+                    label,
+                })
+            });
+        let audit = QuantizationAudit::run(&wnn, &float_thresholds, examples).unwrap();
+        assert_eq!(audit.num_examples, 2);
+        assert_eq!(audit.float_correct, 2);
+        assert_eq!(audit.quantized_correct, 1);
+        assert_eq!(audit.changed_scores, 1);
+        assert!(!audit.is_equivalent());
+        assert_eq!(audit.changed_predictions[0].id, "0");
+        assert_eq!(audit.changed_predictions[0].float_scores, vec![1, 0]);
+    }
+}
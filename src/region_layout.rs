@@ -0,0 +1,212 @@
+//! The rows and columns each region of a circuit is assigned to, e.g. to estimate its cost
+//! (see [`crate::cost`]) or to restrict a plot of its layout to some regions (see
+//! `layout_plot`, with the `plotting` feature).
+//!
+//! The layout is recorded by synthesizing the circuit without witnesses, so this doesn't need
+//! the plotting dependencies.
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use ff::Field;
+use halo2_proofs::{
+    circuit::Value,
+    plonk::{
+        Advice, Any, Assigned, Assignment, Challenge, Circuit, Column, ConstraintSystem, Error,
+        Fixed, FloorPlanner, Instance, Selector,
+    },
+};
+
+use crate::circuit_graph::ColumnId;
+
+/// The rows a region was assigned to, see [`region_rows`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionRows {
+    pub name: String,
+    /// `None` if nothing was assigned in the region.
+    pub rows: Option<Range<usize>>,
+    /// The advice and fixed columns assigned in the region.
+    pub columns: BTreeSet<ColumnId>,
+}
+
+/// Returns the rows (and columns) of each region of the circuit, in the order they are
+/// assigned. Tables count as regions, too.
+pub fn region_rows<F: Field, C: Circuit<F>>(circuit: &C) -> Result<Vec<RegionRows>, Error> {
+    Ok(record_layout(circuit)?.regions)
+}
+
+/// Returns the number of rows the circuit assigns (including tables and constants), i.e. the
+/// number of usable rows it needs. The layout doesn't depend on `k`, so this is computed
+/// without trying different sizes.
+pub fn used_rows<F: Field, C: Circuit<F>>(circuit: &C) -> Result<usize, Error> {
+    Ok(record_layout(circuit)?.num_rows)
+}
+
+fn record_layout<F: Field, C: Circuit<F>>(circuit: &C) -> Result<RegionRecorder, Error> {
+    let mut cs = ConstraintSystem::default();
+    let config = C::configure_with_params(&mut cs, circuit.params());
+    let mut recorder = RegionRecorder::default();
+    C::FloorPlanner::synthesize(&mut recorder, circuit, config, cs.constants().clone())?;
+    Ok(recorder)
+}
+/// Records the rows used by each region, see [`region_rows`].
+#[derive(Default)]
+struct RegionRecorder {
+    regions: Vec<RegionRows>,
+    current: Option<RegionRows>,
+    /// One more than the largest row used, also outside of regions (e.g. by constants).
+    num_rows: usize,
+}
+
+impl RegionRecorder {
+    fn use_cell(&mut self, column: ColumnId, row: usize) {
+        if let Some(region) = &mut self.current {
+            region.columns.insert(column);
+        }
+        self.use_row(row);
+    }
+
+    fn use_row(&mut self, row: usize) {
+        self.num_rows = self.num_rows.max(row + 1);
+        if let Some(region) = &mut self.current {
+            region.rows = Some(match &region.rows {
+                Some(rows) => rows.start.min(row)..rows.end.max(row + 1),
+                None => row..row + 1,
+            });
+        }
+    }
+}
+
+impl<F: Field> Assignment<F> for RegionRecorder {
+    fn enter_region<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.current = Some(RegionRows {
+            name: name_fn().into(),
+            rows: None,
+            columns: BTreeSet::new(),
+        });
+    }
+
+    fn annotate_column<A, AR>(&mut self, _annotation: A, _column: Column<Any>)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+    }
+
+    fn exit_region(&mut self) {
+        self.regions.extend(self.current.take());
+    }
+
+    fn enable_selector<A, AR>(&mut self, _: A, _: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.use_row(row);
+        Ok(())
+    }
+
+    fn query_instance(&self, _: Column<Instance>, _: usize) -> Result<Value<F>, Error> {
+        Ok(Value::unknown())
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        column: Column<Advice>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.use_cell(ColumnId::Advice(column.index()), row);
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        column: Column<Fixed>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.use_cell(ColumnId::Fixed(column.index()), row);
+        Ok(())
+    }
+
+    fn copy(&mut self, _: Column<Any>, _: usize, _: Column<Any>, _: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn fill_from_row(
+        &mut self,
+        _: Column<Fixed>,
+        _: usize,
+        _: Value<Assigned<F>>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn get_challenge(&self, _: Challenge) -> Value<F> {
+        Value::unknown()
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::halo2curves::bn256::Fr as Fp;
+    use ndarray::{Array2, Array3};
+
+    use super::region_rows;
+    use crate::gadgets::WnnCircuit;
+    use crate::wnn::Wnn;
+
+    #[test]
+    fn test_region_rows() {
+        let wnn = Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            2097143,
+            Array3::from_elem((2, 2, 1024), false),
+            (0..24u64).collect(),
+            Array3::zeros((4, 3, 2)),
+        );
+        let circuit = WnnCircuit::<Fp>::builder(&wnn)
+            .image(Array2::zeros((4, 3)))
+            .build()
+            .unwrap()
+            .with_region_annotations();
+        let regions = region_rows(&circuit).unwrap();
+        let image = regions
+            .iter()
+            .find(|region| region.name.ends_with("EncodeImageChip/image"))
+            .unwrap();
+        // One row per pixel
+        assert_eq!(image.rows.as_ref().unwrap().len(), 12);
+    }
+}
@@ -0,0 +1,235 @@
+//! A registry of approved models, as a Merkle tree over their (model commitment, verifying key
+//! fingerprint) pairs, see [`ModelRegistry`].
+//!
+//! Publishing only the root on-chain is enough to accept proofs from any registered model: The
+//! prover shows that its model is a leaf of the tree with an [`InclusionWitness`].
+//!
+//! Hashing uses keccak256, so the tree can be recomputed in Solidity:
+//!
+//! - Leaf: `keccak256(abi.encodePacked(bytes1(0x00), modelCommitment, vkFingerprint))`
+//! - Inner node: `keccak256(abi.encodePacked(bytes1(0x01), left, right))`
+//!
+//! The leaves are padded with [`EMPTY_LEAF`] to the next power of two. The domain separation
+//! bytes make sure that an inner node can't be passed off as a leaf.
+//!
+//! Inclusion is checked outside of the circuit, by whoever verifies the proof (see
+//! [`InclusionWitness::verify`], or the Solidity equivalent): The verifier checks that the
+//! metadata of the proof matches a registered entry and that the entry is a leaf of the
+//! published root. The proof itself doesn't hide which model was used.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ZeroGError;
+use crate::utils::keccak256;
+
+/// The value of unused leaves.
+pub const EMPTY_LEAF: [u8; 32] = [0; 32];
+
+/// A registered model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    /// See [`crate::Wnn::commitment`].
+    pub model_commitment: [u8; 32],
+    /// See [`crate::verifier_bundle::vk_fingerprint`].
+    pub vk_fingerprint: [u8; 32],
+}
+
+impl RegistryEntry {
+    /// The leaf of the entry in the Merkle tree.
+    pub fn leaf(&self) -> [u8; 32] {
+        let mut bytes = vec![0x00];
+        bytes.extend(self.model_commitment);
+        bytes.extend(self.vk_fingerprint);
+        keccak256(bytes)
+    }
+}
+
+fn hash_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut bytes = vec![0x01];
+    bytes.extend(left);
+    bytes.extend(right);
+    keccak256(bytes)
+}
+
+/// Proves that an entry is part of the registry with a given root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InclusionWitness {
+    /// The position of the leaf.
+    pub index: u64,
+    /// The sibling at each level, from the leaf to the root.
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl InclusionWitness {
+    /// Whether the current node is the right child at each level, from the leaf to the root.
+    pub fn path_bits(&self) -> Vec<bool> {
+        (0..self.siblings.len())
+            .map(|level| (self.index >> level) & 1 == 1)
+            .collect()
+    }
+
+    /// Computes the root implied by the witness for the given entry.
+    pub fn compute_root(&self, entry: &RegistryEntry) -> [u8; 32] {
+        self.siblings.iter().zip(self.path_bits()).fold(
+            entry.leaf(),
+            |node, (sibling, is_right)| {
+                if is_right {
+                    hash_node(sibling, &node)
+                } else {
+                    hash_node(&node, sibling)
+                }
+            },
+        )
+    }
+
+    /// Checks that the entry is included in the registry with the given root.
+    pub fn verify(&self, root: &[u8; 32], entry: &RegistryEntry) -> bool {
+        self.compute_root(entry) == *root
+    }
+}
+
+/// What has to be published on-chain for a registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistryRoot {
+    pub root: [u8; 32],
+    /// The number of levels, i.e. the length of every inclusion witness.
+    pub depth: u32,
+    pub num_entries: u64,
+}
+
+/// A Merkle tree of approved models, see the module documentation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelRegistry {
+    entries: Vec<RegistryEntry>,
+}
+
+impl ModelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> &[RegistryEntry] {
+        &self.entries
+    }
+
+    /// Adds an entry and returns its index. Entries that are already registered are not
+    /// added again.
+    pub fn add(&mut self, entry: RegistryEntry) -> usize {
+        match self.index_of(&entry) {
+            Some(index) => index,
+            None => {
+                self.entries.push(entry);
+                self.entries.len() - 1
+            }
+        }
+    }
+
+    pub fn index_of(&self, entry: &RegistryEntry) -> Option<usize> {
+        self.entries.iter().position(|e| e == entry)
+    }
+
+    /// The levels of the tree, from the (padded) leaves to the root.
+    fn levels(&self) -> Vec<Vec<[u8; 32]>> {
+        let width = self.entries.len().max(1).next_power_of_two();
+        let mut level: Vec<_> = self.entries.iter().map(RegistryEntry::leaf).collect();
+        level.resize(width, EMPTY_LEAF);
+        let mut levels = vec![level];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| hash_node(&pair[0], &pair[1]))
+                .collect();
+            levels.push(next);
+        }
+        levels
+    }
+
+    pub fn root(&self) -> RegistryRoot {
+        let levels = self.levels();
+        RegistryRoot {
+            root: levels.last().unwrap()[0],
+            depth: levels.len() as u32 - 1,
+            num_entries: self.entries.len() as u64,
+        }
+    }
+
+    /// The inclusion witness of a registered entry, or `None` if it isn't registered.
+    pub fn witness(&self, entry: &RegistryEntry) -> Option<InclusionWitness> {
+        let index = self.index_of(entry)?;
+        let levels = self.levels();
+        let siblings = levels[..levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(level, nodes)| nodes[(index >> level) ^ 1])
+            .collect();
+        Some(InclusionWitness {
+            index: index as u64,
+            siblings,
+        })
+    }
+
+    /// Reads a registry written by [`ModelRegistry::write`].
+    pub fn read(path: &Path) -> Result<Self, ZeroGError> {
+        let file = File::open(path).map_err(|source| ZeroGError::Io {
+            action: "open",
+            path: path.to_path_buf(),
+            source,
+        })?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|source| ZeroGError::Format {
+            path: path.to_path_buf(),
+            format: "model registry",
+            source: Box::new(source),
+        })
+    }
+
+    /// Writes the registry as JSON.
+    pub fn write(&self, path: &Path) -> Result<(), ZeroGError> {
+        let write = || -> io::Result<()> {
+            let mut writer = BufWriter::new(File::create(path)?);
+            serde_json::to_writer_pretty(&mut writer, self)?;
+            writer.flush()
+        };
+        write().map_err(|source| ZeroGError::Io {
+            action: "write",
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ModelRegistry, RegistryEntry};
+
+    fn entry(i: u8) -> RegistryEntry {
+        RegistryEntry {
+            model_commitment: [i; 32],
+            vk_fingerprint: [i + 100; 32],
+        }
+    }
+
+    #[test]
+    fn test_inclusion_witnesses() {
+        let mut registry = ModelRegistry::new();
+        for i in 0..5 {
+            assert_eq!(registry.add(entry(i)), i as usize);
+        }
+        assert_eq!(registry.add(entry(2)), 2);
+
+        let root = registry.root();
+        assert_eq!((root.depth, root.num_entries), (3, 5));
+        for i in 0..5 {
+            let witness = registry.witness(&entry(i)).unwrap();
+            assert_eq!(witness.siblings.len(), 3);
+            assert!(witness.verify(&root.root, &entry(i)));
+            assert!(!witness.verify(&root.root, &entry(i + 1)));
+        }
+        assert!(registry.witness(&entry(9)).is_none());
+    }
+}
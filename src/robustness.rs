@@ -0,0 +1,169 @@
+//! Local robustness claims: Proofs that the model predicts the same class for an image and a
+//! perturbed version of it, whose pixels differ by at most a public bound.
+//!
+//! With [`crate::Wnn::with_robustness`], the circuit takes two images, checks that their L∞
+//! distance is at most the bound (see [`crate::gadgets::perturbation`]) and predicts both. It
+//! exposes the scores of the image, then the scores of the perturbed image (see
+//! [`PublicValue::PerturbedScore`]) and the bound (see [`PublicValue::PerturbationBound`]).
+//! Both images are witnesses, so the same keys work for any pair of images and any bound:
+//! [`crate::Wnn::robustness_proof`] generates a proof, and a verifier checks that the bound is
+//! small enough and both images are predicted as the same class, see [`check_robustness`].
+//!
+//! Note that a proof covers a single perturbation, not all images within the bound.
+//!
+//! Proofs without a perturbation (e.g. by [`crate::Wnn::proof`]) compare the image with itself
+//! and expose a bound of 0.
+
+use halo2_proofs::halo2curves::bn256::Fr as Fp;
+use ndarray::Array2;
+use thiserror::Error;
+
+use crate::gadgets::wnn::{InstanceLayout, PublicValue};
+use crate::utils::{argmax, try_to_u64};
+
+/// The maximum difference between corresponding pixels of two images of the same shape.
+pub fn linf_distance(image: &Array2<u8>, other: &Array2<u8>) -> u8 {
+    assert_eq!(image.dim(), other.dim());
+    image
+        .iter()
+        .zip(other)
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap_or(0)
+}
+
+/// The perturbation bound exposed by a proof, given its public inputs (in the order of
+/// [`InstanceLayout::values`]), or `None` if the layout doesn't contain one.
+pub fn exposed_bound(layout: &InstanceLayout, public_inputs: &[Fp]) -> Option<u8> {
+    let index = layout
+        .values
+        .iter()
+        .position(|value| *value == PublicValue::PerturbationBound)?;
+    public_inputs
+        .get(index)
+        .and_then(try_to_u64)
+        .and_then(|bound| u8::try_from(bound).ok())
+}
+
+/// Reasons why [`check_robustness`] fails.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RobustnessError {
+    #[error("The proof doesn't expose a perturbation bound")]
+    NoBound,
+    #[error("The proof doesn't expose the scores of both images")]
+    NoScores,
+    #[error("The proof allows perturbations of up to {bound}, expected at most {max_bound}")]
+    Bound { bound: u8, max_bound: u8 },
+    #[error("The image is predicted as class {class}, but the perturbed image as {perturbed}")]
+    ClassChanged { class: usize, perturbed: usize },
+}
+
+/// Checks that a proof bounds the perturbation by at most `max_bound` and that the model
+/// predicts the same class for both images (i.e. the same class has the highest score).
+/// Returns the predicted class.
+///
+/// The proof itself is not verified, see e.g. [`crate::facade::Verifier`].
+pub fn check_robustness(
+    layout: &InstanceLayout,
+    public_inputs: &[Fp],
+    max_bound: u8,
+) -> Result<usize, RobustnessError> {
+    let bound = exposed_bound(layout, public_inputs).ok_or(RobustnessError::NoBound)?;
+    if bound > max_bound {
+        return Err(RobustnessError::Bound { bound, max_bound });
+    }
+
+    let scores = |perturbed: bool| {
+        layout
+            .values
+            .iter()
+            .zip(public_inputs)
+            .filter(|(value, _)| match value {
+                PublicValue::Score { .. } => !perturbed,
+                PublicValue::PerturbedScore { .. } => perturbed,
+                _ => false,
+            })
+            .map(|(_, x)| try_to_u64(x))
+            .collect::<Option<Vec<_>>>()
+            .filter(|scores| !scores.is_empty())
+            .ok_or(RobustnessError::NoScores)
+    };
+    let class = argmax(&scores(false)?);
+    let perturbed = argmax(&scores(true)?);
+    if class != perturbed {
+        return Err(RobustnessError::ClassChanged { class, perturbed });
+    }
+    Ok(class)
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::halo2curves::bn256::Fr as Fp;
+    use ndarray::array;
+
+    use super::{check_robustness, exposed_bound, linf_distance, RobustnessError};
+    use crate::gadgets::wnn::{InstanceLayout, PublicValue};
+
+    fn layout() -> InstanceLayout {
+        InstanceLayout {
+            values: vec![
+                PublicValue::Score { class: 0 },
+                PublicValue::Score { class: 1 },
+                PublicValue::PerturbedScore { class: 0 },
+                PublicValue::PerturbedScore { class: 1 },
+                PublicValue::PerturbationBound,
+            ],
+            num_columns: 1,
+        }
+    }
+
+    fn public_inputs(scores: [u64; 2], perturbed_scores: [u64; 2], bound: u64) -> Vec<Fp> {
+        scores
+            .into_iter()
+            .chain(perturbed_scores)
+            .chain([bound])
+            .map(Fp::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_linf_distance() {
+        let image = array![[0, 10], [255, 128]];
+        assert_eq!(linf_distance(&image, &image), 0);
+        assert_eq!(linf_distance(&image, &array![[3, 8], [254, 128]]), 3);
+        assert_eq!(linf_distance(&image, &array![[255, 10], [255, 128]]), 255);
+    }
+
+    #[test]
+    fn test_check_robustness() {
+        let inputs = public_inputs([3, 5], [4, 6], 2);
+        assert_eq!(exposed_bound(&layout(), &inputs), Some(2));
+        assert_eq!(check_robustness(&layout(), &inputs, 2), Ok(1));
+        assert_eq!(check_robustness(&layout(), &inputs, 8), Ok(1));
+        assert_eq!(
+            check_robustness(&layout(), &inputs, 1),
+            Err(RobustnessError::Bound {
+                bound: 2,
+                max_bound: 1
+            })
+        );
+
+        let changed = public_inputs([3, 5], [6, 4], 2);
+        assert_eq!(
+            check_robustness(&layout(), &changed, 2),
+            Err(RobustnessError::ClassChanged {
+                class: 1,
+                perturbed: 0
+            })
+        );
+
+        let unperturbed = InstanceLayout {
+            values: layout().values[..2].to_vec(),
+            num_columns: 1,
+        };
+        assert_eq!(
+            check_robustness(&unperturbed, &inputs, 2),
+            Err(RobustnessError::NoBound)
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! An HTTP service proving inference of a fixed model, see [`serve`].
+//!
+//! Endpoints (all responses are JSON, except for proofs):
+//!
+//! | Endpoint                | Description                                                     |
+//! |-------------------------|-----------------------------------------------------------------|
+//! | `GET /health`           | Returns `{"status": "ok"}`                                      |
+//! | `GET /model`            | The model commitment, the verification key fingerprint, the circuit params and the input shape |
+//! | `POST /jobs`            | Submits an image (PNG, JPEG, ... as the request body) or, for tabular models, `{"features": [...]}` as JSON, returns `{"id": ...}` |
+//! | `GET /jobs/{id}`        | The status of a job, see [`crate::jobs::JobStatus`]             |
+//! | `GET /jobs/{id}/proof`  | The proof file of a finished job                                |
+//!
+//! Jobs are processed by a [`JobQueue`].
+
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use ndarray::Array1;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::classifier::{ProvableClassifier, WnnInput};
+use crate::io::load_image_from_bytes;
+use crate::jobs::{JobQueue, JobStatus};
+
+/// An error response: The status code and `{"error": <message>}`.
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// Runs the proving service until the process is terminated.
+pub async fn serve(queue: Arc<JobQueue>, address: SocketAddr) -> eyre::Result<()> {
+    let app = Router::new()
+        .route("/health", get(health))
+        .route("/model", get(model))
+        .route("/jobs", post(submit_job))
+        .route("/jobs/:id", get(job_status))
+        .route("/jobs/:id/proof", get(job_proof))
+        .with_state(queue);
+
+    info!("Listening on {address}");
+    axum::Server::bind(&address)
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+async fn model(State(queue): State<Arc<JobQueue>>) -> Json<Value> {
+    let wnn = queue.prover().model();
+    let mut info = json!({
+        "commitment": format!("0x{}", hex::encode(ProvableClassifier::commitment(wnn))),
+        "vk_fingerprint": format!("0x{}", hex::encode(queue.prover().vk_fingerprint())),
+        "circuit_params": wnn.get_circuit_params(),
+        "num_classes": wnn.num_classes(),
+    });
+    if wnn.is_tabular() {
+        info["num_features"] = json!(wnn.num_features());
+    } else {
+        info["image_shape"] = json!(wnn.img_shape());
+    }
+    Json(info)
+}
+
+/// The JSON body of `POST /jobs` for tabular models.
+#[derive(Deserialize)]
+struct FeaturesRequest {
+    features: Vec<u16>,
+}
+
+async fn submit_job(
+    State(queue): State<Arc<JobQueue>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .map_or(false, |content_type| {
+            content_type.as_bytes().starts_with(b"application/json")
+        });
+    let input = if is_json {
+        let request: FeaturesRequest = serde_json::from_slice(&body)
+            .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("Invalid features: {e}")))?;
+        WnnInput::Features(Array1::from(request.features))
+    } else {
+        let image = load_image_from_bytes(&body)
+            .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("Invalid image: {e}")))?;
+        WnnInput::Image(image)
+    };
+    let id = queue
+        .submit(input)
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "id": id }))))
+}
+
+async fn job_status(
+    State(queue): State<Arc<JobQueue>>,
+    Path(id): Path<String>,
+) -> Result<Json<JobStatus>, ApiError> {
+    match queue.status(&id) {
+        Some(status) => Ok(Json(status)),
+        None => Err(ApiError(StatusCode::NOT_FOUND, format!("Unknown job {id}"))),
+    }
+}
+
+async fn job_proof(
+    State(queue): State<Arc<JobQueue>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    match queue.status(&id) {
+        Some(JobStatus::Done { .. }) => {}
+        Some(status) => {
+            return Err(ApiError(
+                StatusCode::CONFLICT,
+                format!("Job {id} is not done: {status:?}"),
+            ))
+        }
+        None => return Err(ApiError(StatusCode::NOT_FOUND, format!("Unknown job {id}"))),
+    }
+
+    let proof = fs::read(queue.proof_path(&id))
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(([(header::CONTENT_TYPE, "application/octet-stream")], proof))
+}
@@ -0,0 +1,385 @@
+//! Generating all artifacts needed to prove and verify inference of a model into a single
+//! directory, together with a manifest describing them.
+//!
+//! The layout of the directory is as follows:
+//!
+//! | File                   | Content                                       |
+//! |------------------------|-----------------------------------------------|
+//! | `manifest.json`        | See [`SetupManifest`]                         |
+//! | `srs.bin`              | The SRS, downsized to `k`                     |
+//! | `circuit_params.json`  | See [`crate::io::write_circuit_params`]       |
+//! | `keys/pk.bin`          | The proving key                               |
+//! | `keys/vk.bin`          | The verifying key                             |
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use halo2_proofs::{
+    halo2curves::bn256::Bn256,
+    plonk::Error,
+    poly::{
+        commitment::{Params, ParamsProver},
+        kzg::commitment::ParamsKZG,
+    },
+};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::artifact_store::open_store;
+use crate::cost::minimal_k;
+use crate::error::ZeroGError;
+use crate::gadgets::wnn::WnnCircuitParams;
+use crate::io::{
+    read_srs, read_srs_from_store, write_circuit_params, write_keys_with_format, write_srs,
+    KeyFormat,
+};
+use crate::verifier_bundle::vk_fingerprint;
+use crate::wnn::Wnn;
+
+/// The name of the manifest in the artifact directory.
+pub const SETUP_MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// The URL of the SRS from the perpetual powers of tau ceremony, without the trailing `k`.
+#[cfg(feature = "download")]
+pub const CEREMONY_SRS_URL: &str =
+    "https://trusted-setup-halo2kzg.s3.eu-central-1.amazonaws.com/perpetual-powers-of-tau-raw-";
+
+/// Where to get the SRS from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SrsSource {
+    /// Generate a new SRS. The toxic waste is known to this process, so this is only
+    /// suitable for testing!
+    Generate,
+    /// Read an SRS from a file (see [`read_srs`]).
+    File(PathBuf),
+    /// Fetch an SRS from an artifact store (see [`open_store`]), under the key [`srs_key`].
+    Store(String),
+    /// Download the SRS of the perpetual powers of tau ceremony (see [`CEREMONY_SRS_URL`]).
+    #[cfg(feature = "download")]
+    Ceremony,
+}
+
+/// The key of the SRS for the given `k` in an artifact store, e.g. `srs/k14.srs`.
+pub fn srs_key(k: u32) -> String {
+    format!("srs/k{k}.srs")
+}
+
+/// The paths of the artifacts, relative to the artifact directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetupFiles {
+    pub srs: String,
+    pub circuit_params: String,
+    pub pk: String,
+    pub vk: String,
+}
+
+impl Default for SetupFiles {
+    fn default() -> Self {
+        Self {
+            srs: "srs.bin".to_string(),
+            circuit_params: "circuit_params.json".to_string(),
+            pk: "keys/pk.bin".to_string(),
+            vk: "keys/vk.bin".to_string(),
+        }
+    }
+}
+
+/// Describes the artifacts in an artifact directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetupManifest {
+    /// Version of `zero_g` that generated the artifacts.
+    pub crate_version: String,
+    /// See [`Wnn::commitment`].
+    pub model_commitment: [u8; 32],
+    pub k: u32,
+    pub circuit_params: WnnCircuitParams,
+    /// See [`vk_fingerprint`].
+    pub vk_fingerprint: [u8; 32],
+    pub files: SetupFiles,
+    /// The format of the proving key (see [`KeyFormat`]), which is also used to read it.
+    #[serde(default)]
+    pub key_format: KeyFormat,
+}
+
+impl SetupManifest {
+    /// Reads the manifest of an artifact directory.
+    pub fn read(dir: &Path) -> Result<Self, ZeroGError> {
+        let path = dir.join(SETUP_MANIFEST_FILE_NAME);
+        let file = File::open(&path).map_err(|source| ZeroGError::Io {
+            action: "open",
+            path: path.clone(),
+            source,
+        })?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|source| ZeroGError::Format {
+            path,
+            format: "setup manifest",
+            source: Box::new(source),
+        })
+    }
+
+    fn write(&self, dir: &Path) -> Result<(), ZeroGError> {
+        let path = dir.join(SETUP_MANIFEST_FILE_NAME);
+        let write = || -> io::Result<()> {
+            let mut writer = BufWriter::new(File::create(&path)?);
+            serde_json::to_writer_pretty(&mut writer, self)?;
+            writer.flush()
+        };
+        write().map_err(|source| ZeroGError::Io {
+            action: "write",
+            path,
+            source,
+        })
+    }
+}
+
+/// Gets the SRS, generates the keys for the model and writes all artifacts to `dir`.
+///
+/// If `k` is not given, the smallest `k` that fits the circuit is used (see [`minimal_k`]).
+/// The manifest is written last, so a directory with a manifest is always complete.
+pub fn setup(
+    wnn: &Wnn,
+    k: Option<u32>,
+    srs_source: &SrsSource,
+    dir: &Path,
+) -> Result<SetupManifest, ZeroGError> {
+    setup_with_key_format(wnn, k, srs_source, dir, KeyFormat::default())
+}
+
+/// Like [`setup`], but writes the proving key in the given format, which is recorded in the
+/// manifest. With [`KeyFormat::RawBytesUnchecked`], provers opening the directory skip all
+/// checks of the proving key, so only use it if the directory can't be tampered with.
+#[instrument(skip_all, fields(dir = %dir.display(), %key_format))]
+pub fn setup_with_key_format(
+    wnn: &Wnn,
+    k: Option<u32>,
+    srs_source: &SrsSource,
+    dir: &Path,
+    key_format: KeyFormat,
+) -> Result<SetupManifest, ZeroGError> {
+    let k = match k {
+        Some(k) => k,
+        None => minimal_k(wnn)?,
+    };
+    let kzg_params = get_srs(srs_source, k)?;
+    let pk = wnn.generate_proving_key(&kzg_params)?;
+
+    let files = SetupFiles::default();
+    fs::create_dir_all(dir.join("keys")).map_err(|source| ZeroGError::Io {
+        action: "create",
+        path: dir.join("keys"),
+        source,
+    })?;
+    write_srs(&kzg_params, &dir.join(&files.srs))?;
+    let circuit_params = wnn.get_circuit_params();
+    write_circuit_params(&circuit_params, &dir.join(&files.circuit_params))?;
+    write_keys_with_format(
+        &pk,
+        &circuit_params,
+        &dir.join(&files.pk),
+        &dir.join(&files.vk),
+        key_format,
+    )?;
+
+    let manifest = SetupManifest {
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        model_commitment: wnn.commitment(),
+        k,
+        vk_fingerprint: vk_fingerprint(pk.get_vk(), &circuit_params),
+        circuit_params,
+        files,
+        key_format,
+    };
+    manifest.write(dir)?;
+    Ok(manifest)
+}
+
+/// Gets an SRS and writes it to `dir` under [`srs_key`], so that `dir` can be used as an
+/// artifact store for [`SrsSource::Store`]. Returns the path of the written SRS.
+///
+/// If `k` is not given, the smallest `k` that fits the circuit of `wnn` is used.
+/// Returns [`ZeroGError::MissingArgument`] if neither `wnn` nor `k` is given.
+pub fn gen_srs(
+    wnn: Option<&Wnn>,
+    k: Option<u32>,
+    srs_source: &SrsSource,
+    dir: &Path,
+) -> Result<PathBuf, ZeroGError> {
+    let k = match (k, wnn) {
+        (Some(k), _) => k,
+        (None, Some(wnn)) => minimal_k(wnn)?,
+        (None, None) => {
+            return Err(ZeroGError::MissingArgument(
+                "Either a model or k is required",
+            ))
+        }
+    };
+    let kzg_params = get_srs(srs_source, k)?;
+
+    let path = dir.join(srs_key(k));
+    let parent = path.parent().expect("The key has a parent directory");
+    fs::create_dir_all(parent).map_err(|source| ZeroGError::Io {
+        action: "create",
+        path: parent.to_path_buf(),
+        source,
+    })?;
+    write_srs(&kzg_params, &path)?;
+    Ok(path)
+}
+
+/// Gets an SRS for exactly `2^k` rows, downsizing a larger one if necessary.
+pub fn get_srs(source: &SrsSource, k: u32) -> Result<ParamsKZG<Bn256>, ZeroGError> {
+    let mut kzg_params = match source {
+        SrsSource::Generate => return Ok(ParamsKZG::<Bn256>::new(k)),
+        SrsSource::File(path) => read_srs(path)?,
+        SrsSource::Store(location) => {
+            let key = srs_key(k);
+            let store = open_store(location).map_err(|source| ZeroGError::Artifact {
+                key: key.clone(),
+                format: "SRS",
+                source,
+            })?;
+            read_srs_from_store(store.as_ref(), &key)?
+        }
+        #[cfg(feature = "download")]
+        SrsSource::Ceremony => {
+            let url = format!("{CEREMONY_SRS_URL}{k}");
+            let to_error = |source| ZeroGError::Artifact {
+                key: url.clone(),
+                format: "SRS",
+                source,
+            };
+            let bytes = crate::download::download(&url).map_err(to_error)?;
+            ParamsKZG::read(&mut bytes.as_slice()).map_err(to_error)?
+        }
+    };
+    if kzg_params.k() < k {
+        return Err(ZeroGError::Plonk {
+            action: "Reading the SRS",
+            source: Error::NotEnoughRowsAvailable {
+                current_k: kzg_params.k(),
+            },
+        });
+    }
+    kzg_params.downsize(k);
+    Ok(kzg_params)
+}
+
+#[cfg(all(test, feature = "hdf5"))]
+mod tests {
+    use std::{env, fs, path::Path, process};
+
+    use super::{gen_srs, setup, setup_with_key_format, SetupManifest, SrsSource};
+    use crate::checked_in_test_data::MNIST_TINY;
+    use crate::error::ZeroGError;
+    use halo2_proofs::SerdeFormat::RawBytes;
+
+    use crate::gadgets::wnn::WnnCircuitParams;
+    use crate::io::{
+        read_circuit_params, read_key_header, read_pk, read_pk_with_format, read_vk,
+        read_vk_and_params, upgrade_key_file, KeyFormat,
+    };
+    use crate::key_file::KeyKind;
+    use crate::load_wnn;
+    use crate::verifier_bundle::vk_fingerprint;
+
+    #[test]
+    fn test_setup() {
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let dir = env::temp_dir().join(format!("zero_g_setup_{}", process::id()));
+
+        let manifest = setup(&wnn, Some(k), &SrsSource::Generate, &dir).unwrap();
+        let read_manifest = SetupManifest::read(&dir).unwrap();
+        let circuit_params =
+            read_circuit_params(&dir.join(&manifest.files.circuit_params)).unwrap();
+        let vk_path = dir.join(&manifest.files.vk);
+        let vk = read_vk(&vk_path, circuit_params.clone()).unwrap();
+        let header = read_key_header(&vk_path).unwrap().unwrap();
+        let (_, embedded_params) = read_vk_and_params(&vk_path).unwrap();
+        // Keys for other circuit params are rejected
+        let other_params = WnnCircuitParams {
+            n_classes: circuit_params.n_classes + 1,
+            ..circuit_params.clone()
+        };
+        let wrong_params_error = read_vk(&vk_path, other_params).unwrap_err();
+
+        // Legacy keys without a header are read and can be upgraded
+        let legacy_path = dir.join("legacy_vk.bin");
+        let mut legacy_bytes = vec![];
+        vk.write(&mut legacy_bytes, RawBytes).unwrap();
+        fs::write(&legacy_path, legacy_bytes).unwrap();
+        let legacy_header = read_key_header(&legacy_path).unwrap();
+        let legacy_vk = read_vk(&legacy_path, circuit_params.clone()).unwrap();
+        // ... but the circuit params need to be supplied
+        let legacy_params_result = read_vk_and_params(&legacy_path);
+        let upgraded_header =
+            upgrade_key_file(&legacy_path, KeyKind::Verifying, circuit_params.clone()).unwrap();
+        let upgraded_vk = read_vk(&legacy_path, circuit_params.clone()).unwrap();
+        let read_upgraded_header = read_key_header(&legacy_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(read_manifest, manifest);
+        assert_eq!(manifest.k, k);
+        assert_eq!(manifest.model_commitment, wnn.commitment());
+        assert_eq!(
+            vk_fingerprint(&vk, &circuit_params),
+            manifest.vk_fingerprint
+        );
+
+        assert_eq!(header.kind, KeyKind::Verifying);
+        assert_eq!(header.k, k);
+        assert_eq!(embedded_params, circuit_params);
+        assert!(wrong_params_error
+            .to_string()
+            .contains("different circuit params"));
+
+        assert_eq!(legacy_header, None);
+        assert!(legacy_params_result.is_err());
+        assert_eq!(upgraded_header, header);
+        assert_eq!(read_upgraded_header, Some(header));
+        for vk in [legacy_vk, upgraded_vk] {
+            assert_eq!(
+                vk_fingerprint(&vk, &circuit_params),
+                manifest.vk_fingerprint
+            );
+        }
+    }
+
+    #[test]
+    fn test_setup_with_key_format() {
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let dir = env::temp_dir().join(format!("zero_g_setup_processed_{}", process::id()));
+
+        let manifest = setup_with_key_format(
+            &wnn,
+            Some(k),
+            &SrsSource::Generate,
+            &dir,
+            KeyFormat::Processed,
+        )
+        .unwrap();
+        let pk_path = dir.join(&manifest.files.pk);
+        let pk = read_pk_with_format(&pk_path, wnn.get_circuit_params(), KeyFormat::Processed);
+        // The header records the format
+        let raw_pk = read_pk(&pk_path, wnn.get_circuit_params());
+        let read_manifest = SetupManifest::read(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(read_manifest.key_format, KeyFormat::Processed);
+        assert_eq!(
+            vk_fingerprint(pk.unwrap().get_vk(), &wnn.get_circuit_params()),
+            manifest.vk_fingerprint
+        );
+        assert!(raw_pk.is_err());
+    }
+
+    #[test]
+    fn test_gen_srs_requires_model_or_k() {
+        let dir = env::temp_dir().join(format!("zero_g_gen_srs_{}", process::id()));
+        let result = gen_srs(None, None, &SrsSource::Generate, &dir);
+        assert!(matches!(result, Err(ZeroGError::MissingArgument(_))));
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,204 @@
+//! Telemetry of single proofs, for monitoring proving services and tracking benchmarks.
+//!
+//! A [`TelemetryLog`] appends one JSON object per proof (see [`ProofTelemetry`]) to a file,
+//! i.e. the file is in the [JSON Lines](https://jsonlines.org) format and can be tailed by a
+//! log shipper or read back with [`read_telemetry`].
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::benchmark::{peak_rss, PhaseTimings};
+
+/// Durations (in seconds) of the proving phases, see [`crate::benchmark`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhaseSeconds {
+    pub synthesis: f64,
+    pub commitment: f64,
+    pub opening: f64,
+}
+
+impl From<PhaseTimings> for PhaseSeconds {
+    fn from(timings: PhaseTimings) -> Self {
+        Self {
+            synthesis: timings.synthesis.as_secs_f64(),
+            commitment: timings.commitment.as_secs_f64(),
+            opening: timings.opening.as_secs_f64(),
+        }
+    }
+}
+
+/// The telemetry record of a single proof.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProofTelemetry {
+    /// Version of `zero_g` that generated the proof.
+    pub crate_version: String,
+    /// When the proof was started, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// See [`crate::classifier::ProvableClassifier::commitment`].
+    pub model_commitment: [u8; 32],
+    pub k: u32,
+    /// Time (in seconds) of the whole proof, including the failed part if the proof failed.
+    pub total: f64,
+    /// Only available if the prover observes the transcript (e.g. in benchmarks).
+    pub phases: Option<PhaseSeconds>,
+    /// Peak resident set size of the process (in bytes) after the proof, if available on this
+    /// platform. As it is the peak of the whole process, it is an upper bound for the proof.
+    pub peak_rss: Option<u64>,
+    /// Peak heap allocation (in bytes) during the proof, if the
+    /// [`crate::memory::TrackingAllocator`] is installed, see [`crate::memory::measure_peak`].
+    #[serde(default)]
+    pub peak_heap: Option<u64>,
+    /// Size of the proof (in bytes), if successful.
+    pub proof_size: Option<usize>,
+    pub success: bool,
+    /// The error, if the proof failed.
+    pub error: Option<String>,
+}
+
+impl ProofTelemetry {
+    /// Creates the record of a proof that was started at `start` and took `total`.
+    ///
+    /// `result` is the size of the proof, or the error.
+    pub fn new<E: ToString>(
+        model_commitment: [u8; 32],
+        k: u32,
+        start: SystemTime,
+        total: Duration,
+        result: Result<usize, &E>,
+    ) -> Self {
+        let (proof_size, error) = match result {
+            Ok(proof_size) => (Some(proof_size), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp: start
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_secs()),
+            model_commitment,
+            k,
+            total: total.as_secs_f64(),
+            phases: None,
+            peak_rss: peak_rss(),
+            peak_heap: None,
+            proof_size,
+            success: error.is_none(),
+            error,
+        }
+    }
+
+    pub fn with_phases(mut self, phases: PhaseTimings) -> Self {
+        self.phases = Some(phases.into());
+        self
+    }
+
+    pub fn with_peak_heap(mut self, peak_heap: Option<u64>) -> Self {
+        self.peak_heap = peak_heap;
+        self
+    }
+}
+
+/// Appends [`ProofTelemetry`] records to a file, see the module documentation.
+///
+/// Records are written by one thread at a time, so the log can be shared by the workers of a
+/// proving service.
+#[derive(Debug)]
+pub struct TelemetryLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl TelemetryLog {
+    /// Opens the file for appending, creating it if it doesn't exist.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the record as a single line.
+    pub fn write(&self, record: &ProofTelemetry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.file.lock().unwrap().write_all(&line)
+    }
+
+    /// Like [`TelemetryLog::write`], but only logs a warning on failure, so that telemetry
+    /// never fails a proof.
+    pub fn record(&self, record: &ProofTelemetry) {
+        if let Err(e) = self.write(record) {
+            warn!("Could not write telemetry to {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Reads all records of a telemetry file.
+pub fn read_telemetry(path: &Path) -> io::Result<Vec<ProofTelemetry>> {
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+    use std::{env, fs, process};
+
+    use super::{read_telemetry, ProofTelemetry, TelemetryLog};
+    use crate::benchmark::PhaseTimings;
+
+    #[test]
+    fn test_telemetry_log() {
+        let path = env::temp_dir().join(format!("zero_g_telemetry_{}.jsonl", process::id()));
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let success =
+            ProofTelemetry::new::<String>([1; 32], 14, start, Duration::from_secs(2), Ok(1024))
+                .with_phases(PhaseTimings {
+                    synthesis: Duration::from_millis(500),
+                    commitment: Duration::from_millis(1000),
+                    opening: Duration::from_millis(500),
+                })
+                .with_peak_heap(Some(1 << 30));
+        let failure = ProofTelemetry::new(
+            [1; 32],
+            14,
+            start,
+            Duration::from_secs(1),
+            Err(&"Out of rows"),
+        );
+
+        let log = TelemetryLog::open(&path).unwrap();
+        log.write(&success).unwrap();
+        // Appends to existing files
+        drop(log);
+        TelemetryLog::open(&path).unwrap().write(&failure).unwrap();
+
+        let records = read_telemetry(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(records, vec![success.clone(), failure.clone()]);
+
+        assert!(success.success);
+        assert_eq!(success.timestamp, 1_700_000_000);
+        assert_eq!(success.proof_size, Some(1024));
+        assert_eq!(success.phases.unwrap().commitment, 1.0);
+        assert_eq!(success.peak_heap, Some(1 << 30));
+        assert!(!failure.success);
+        assert_eq!(failure.error.as_deref(), Some("Out of rows"));
+        assert_eq!(failure.proof_size, None);
+    }
+}
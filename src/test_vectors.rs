@@ -0,0 +1,195 @@
+//! Test vectors of the inference semantics of the circuit, so that other implementations (e.g.
+//! the Python training code of [BTHOWeN-0g](https://github.com/zkp-gravity/BTHOWeN-0g) or other
+//! verifiers) can check that they agree with it bit for bit.
+//!
+//! For each image, a [`TestVector`] lists every intermediate value of the inference:
+//! 1. The thermometer encoding of the image (threshold-major, then row-major).
+//! 2. For each filter, the (permuted) input bits packed into an integer (little endian), its
+//!    MishMash hash (`x^3 % p % num_filter_entries^num_filter_hashes`) and the bloom filter
+//!    indices the hash is split into (least significant first).
+//! 3. The bloom filter responses of each class and the scores.
+//!
+//! Integers that don't fit into a double (the packed inputs and the hashes) are written as
+//! decimal strings.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+use crate::wnn::Wnn;
+
+/// The intermediate values of one filter, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterVector {
+    /// The packed input bits, as a decimal string.
+    pub input: String,
+    /// The MishMash hash of the input, as a decimal string.
+    pub hash: String,
+    pub bloom_indices: Vec<usize>,
+}
+
+/// The intermediate values of the inference of one image, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVector {
+    /// Identifies the image, e.g. its path.
+    pub id: String,
+    /// The pixels, row by row.
+    pub image: Vec<Vec<u8>>,
+    /// The thermometer encoding, as a string of `0`s and `1`s.
+    pub image_bits: String,
+    pub filters: Vec<FilterVector>,
+    /// The bloom filter responses, indexed by class and filter.
+    pub responses: Vec<Vec<bool>>,
+    pub scores: Vec<u64>,
+}
+
+impl TestVector {
+    pub fn new(wnn: &Wnn, id: &str, image: &Array2<u8>) -> Self {
+        let image_bits = wnn.thermometer_encoding(image);
+        let filters = wnn
+            .encode_bits(&image_bits)
+            .into_iter()
+            .map(|input| FilterVector {
+                input: input.to_string(),
+                hash: wnn.mish_mash_hash(input).to_string(),
+                bloom_indices: wnn.filter_entries(input),
+            })
+            .collect();
+        let responses = wnn.filter_responses(image);
+        Self {
+            id: id.to_string(),
+            image: image.rows().into_iter().map(|row| row.to_vec()).collect(),
+            image_bits: image_bits
+                .iter()
+                .map(|bit| if *bit { '1' } else { '0' })
+                .collect(),
+            filters,
+            responses: responses
+                .rows()
+                .into_iter()
+                .map(|row| row.to_vec())
+                .collect(),
+            scores: wnn.predict(image),
+        }
+    }
+}
+
+/// Test vectors of a model, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVectors {
+    /// Version of `zero_g` that generated the test vectors.
+    pub crate_version: String,
+    /// See [`Wnn::commitment`].
+    pub model_commitment: [u8; 32],
+    pub p: u64,
+    pub num_filter_inputs: usize,
+    pub num_filter_entries: usize,
+    pub num_filter_hashes: usize,
+    pub vectors: Vec<TestVector>,
+}
+
+impl TestVectors {
+    /// Computes the test vectors of the given `(id, image)` pairs.
+    pub fn new<'a>(wnn: &Wnn, images: impl IntoIterator<Item = (&'a str, &'a Array2<u8>)>) -> Self {
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            model_commitment: wnn.commitment(),
+            p: wnn.p,
+            num_filter_inputs: wnn.num_filter_inputs,
+            num_filter_entries: wnn.num_filter_entries,
+            num_filter_hashes: wnn.num_filter_hashes,
+            vectors: images
+                .into_iter()
+                .map(|(id, image)| TestVector::new(wnn, id, image))
+                .collect(),
+        }
+    }
+
+    /// Recomputes the test vectors with the given model and returns the IDs of the images whose
+    /// vectors differ, e.g. to check that a change of this crate doesn't change the semantics.
+    pub fn mismatches(&self, wnn: &Wnn) -> Vec<String> {
+        self.vectors
+            .iter()
+            .filter(|vector| {
+                let image = Array2::from_shape_fn(
+                    (vector.image.len(), vector.image.first().map_or(0, Vec::len)),
+                    |(row, column)| vector.image[row][column],
+                );
+                TestVector::new(wnn, &vector.id, &image) != **vector
+            })
+            .map(|vector| vector.id.clone())
+            .collect()
+    }
+
+    /// Writes the test vectors as (pretty-printed) JSON.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use ndarray::{array, Array3};
+
+    use super::TestVectors;
+    use crate::wnn::Wnn;
+
+    #[test]
+    fn test_test_vectors() {
+        let mut bloom_filters = Array3::from_elem((2, 2, 1024), false);
+        bloom_filters[[0, 0, 966]] = true;
+        bloom_filters[[0, 0, 805]] = true;
+        bloom_filters[[1, 1, 494]] = true;
+        bloom_filters[[1, 1, 46]] = true;
+        let wnn = Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            2097143,
+            bloom_filters,
+            (0..24u64).map(|i| (i + 6) % 24).collect(),
+            array![
+                [[50, 150], [0, 50], [200, 256]],
+                [[10, 80], [100, 200], [50, 150]],
+                [[0, 100], [100, 200], [0, 100]],
+                [[0, 100], [100, 200], [0, 100]]
+            ],
+        );
+        let image = array![[70, 100, 150], [20, 110, 200], [27, 50, 211], [200, 100, 3]];
+        let vectors = TestVectors::new(&wnn, [("example", &image)]);
+
+        // See the test circuit in `crate::gadgets::wnn`
+        let vector = &vectors.vectors[0];
+        assert_eq!(vector.image_bits, "110111101111010001001100");
+        assert_eq!(vector.filters[0].input, "2237");
+        assert_eq!(vector.filters[0].hash, "825286");
+        assert_eq!(vector.filters[0].bloom_indices, vec![966, 805]);
+        assert_eq!(vector.filters[1].input, "3788");
+        assert_eq!(vector.filters[1].bloom_indices, vec![494, 46]);
+        assert_eq!(vector.responses, vec![vec![true, false], vec![false, true]]);
+        assert_eq!(vector.scores, vec![1, 1]);
+
+        let path = env::temp_dir().join(format!("zero_g_test_vectors_{}.json", process::id()));
+        vectors.write(&path).unwrap();
+        let read = TestVectors::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read, vectors);
+        assert!(read.mismatches(&wnn).is_empty());
+
+        let mut tampered = read;
+        tampered.vectors[0].scores = vec![2, 1];
+        assert_eq!(tampered.mismatches(&wnn), vec!["example".to_string()]);
+    }
+}
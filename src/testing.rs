@@ -0,0 +1,622 @@
+//! Test support: Differential testing of the circuit against the in-the-clear prediction.
+//!
+//! [`check_circuit_matches_predict`] synthesizes the circuit of a model using the
+//! [`MockProver`] and checks that it is satisfied by the scores computed by [`Wnn::predict`].
+//! This catches bugs when changing the encoding or hash function on one side only.
+//!
+//! [`Diagnosis`] maps the failures of the [`MockProver`] back to the chips, regions and the
+//! pixels, filters or classes they belong to.
+//!
+//! [`check_tampering_rejected`] checks soundness the other way around: It corrupts single
+//! witness values (see [`Tampering`]) and checks that the circuit rejects them, so that
+//! gadget refactors that drop a constraint are caught. It requires the `test-support` feature
+//! (which is always enabled in the crate's own tests).
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use halo2_proofs::dev::{MockProver, VerifyFailure};
+use ndarray::{Array2, Array3};
+
+use crate::error::ZeroGError;
+use crate::gadgets::annotations::RegionPath;
+#[cfg(any(test, feature = "test-support"))]
+use crate::gadgets::wnn::Tampering;
+use crate::wnn::Wnn;
+
+/// Describes an image for which the circuit and [`Wnn::predict`] disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the image in the checked batch.
+    pub image_index: usize,
+    /// The scores computed by [`Wnn::predict`].
+    pub expected_scores: Vec<u64>,
+    /// The first filter whose response differs, if it could be isolated.
+    pub filter: Option<usize>,
+    /// The pixels `(row, column)` that are inputs to `filter`.
+    pub pixels: Vec<(usize, usize)>,
+    /// The failures reported by the [`MockProver`].
+    pub failures: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Circuit does not match prediction {:?} for image {}",
+            self.expected_scores, self.image_index
+        )?;
+        match self.filter {
+            Some(filter) => writeln!(
+                f,
+                "First diverging filter: {filter} (pixels {:?})",
+                self.pixels
+            )?,
+            None => writeln!(f, "Unable to isolate a diverging filter")?,
+        }
+        for failure in &self.failures {
+            writeln!(f, "  {failure}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the [`MockProver`] on the circuit of each image, with the scores computed by
+/// [`Wnn::predict`] as public inputs. Returns the first image for which the circuit is not
+/// satisfied, or `None` if the circuit matches the prediction for all images.
+pub fn check_circuit_matches_predict(
+    wnn: &Wnn,
+    images: &[Array2<u8>],
+    k: u32,
+) -> Result<Option<Divergence>, ZeroGError> {
+    for (image_index, image) in images.iter().enumerate() {
+        let failures = mock_prover_failures(wnn, image, k)?;
+        if failures.is_empty() {
+            continue;
+        }
+
+        let filter = find_diverging_filter(wnn, image, k)?;
+        return Ok(Some(Divergence {
+            image_index,
+            expected_scores: wnn.predict(image),
+            filter,
+            pixels: filter.map_or(vec![], |filter| wnn.filter_pixels(filter)),
+            failures,
+        }));
+    }
+    Ok(None)
+}
+
+/// Like [`check_circuit_matches_predict`], but panics with a description of the divergence.
+pub fn assert_circuit_matches_predict(wnn: &Wnn, images: &[Array2<u8>], k: u32) {
+    match check_circuit_matches_predict(wnn, images, k) {
+        Ok(None) => {}
+        Ok(Some(divergence)) => panic!("{divergence}"),
+        Err(e) => panic!("{e}"),
+    }
+}
+
+fn mock_prover_failures(wnn: &Wnn, image: &Array2<u8>, k: u32) -> Result<Vec<String>, ZeroGError> {
+    Ok(mock_prove(wnn, image, k, true)?
+        .iter()
+        .map(|failure| failure.to_string())
+        .collect())
+}
+
+/// Runs the [`MockProver`] on the circuit for the given image, with the scores computed by
+/// [`Wnn::predict`] as public inputs, and returns all constraint failures.
+///
+/// With `annotate_regions`, the region names in the failures include the chip and the pixel,
+/// filter or class (see [`crate::gadgets::WnnCircuit::with_region_annotations`]).
+pub fn mock_prove(
+    wnn: &Wnn,
+    image: &Array2<u8>,
+    k: u32,
+    annotate_regions: bool,
+) -> Result<Vec<VerifyFailure>, ZeroGError> {
+    let mut circuit = wnn.get_circuit(image);
+    if annotate_regions {
+        circuit = circuit.with_region_annotations();
+    }
+    let prover = MockProver::run(k, &circuit, wnn.public_inputs(image)).map_err(|source| {
+        ZeroGError::Plonk {
+            action: "Synthesizing the circuit",
+            source,
+        }
+    })?;
+    Ok(prover.verify().err().unwrap_or_default())
+}
+
+/// The scores computed from the witness of the circuit with the given [`Tampering`], as the
+/// corrupted value is propagated to the witnesses that depend on it.
+#[cfg(any(test, feature = "test-support"))]
+pub fn tampered_scores(wnn: &Wnn, image: &Array2<u8>, tampering: Tampering) -> Vec<u64> {
+    let mut responses = match tampering {
+        Tampering::BinarizationBit {
+            pixel: (row, column),
+            bit,
+        } => {
+            let (rows, columns) = wnn.img_shape();
+            let mut image_bits = wnn.thermometer_encoding(image);
+            let index = (bit * rows + row) * columns + column;
+            image_bits[index] = !image_bits[index];
+            wnn.filter_responses_from_bits(&image_bits)
+        }
+        _ => wnn.filter_responses(image),
+    };
+    if let Tampering::BloomResponse { class, filter } = tampering {
+        responses[(class, filter)] = !responses[(class, filter)];
+    }
+
+    let mut scores: Vec<_> = responses
+        .rows()
+        .into_iter()
+        .map(|responses| responses.iter().filter(|r| **r).count() as u64)
+        .collect();
+    if let Tampering::Accumulator { class, .. } = tampering {
+        scores[class] += 1;
+    }
+    scores
+}
+
+/// Runs the [`MockProver`] on the circuit for the given image with the given [`Tampering`],
+/// with the tampered scores (see [`tampered_scores`]) as public inputs, and returns all
+/// constraint failures. So if there are none, the circuit accepts the corrupted witness.
+#[cfg(any(test, feature = "test-support"))]
+pub fn tampering_failures(
+    wnn: &Wnn,
+    image: &Array2<u8>,
+    k: u32,
+    tampering: Tampering,
+) -> Result<Vec<VerifyFailure>, ZeroGError> {
+    let circuit = wnn.get_circuit(image).with_tampering(tampering);
+    let public_inputs = wnn.public_inputs_for_scores(&tampered_scores(wnn, image, tampering));
+    let prover =
+        MockProver::run(k, &circuit, public_inputs).map_err(|source| ZeroGError::Plonk {
+            action: "Synthesizing the circuit",
+            source,
+        })?;
+    Ok(prover.verify().err().unwrap_or_default())
+}
+
+/// The tamperings tried by [`check_tampering_rejected`]: For each class, the responses of the
+/// first and last filter and the first and last accumulator, and each bit of the first and the
+/// last pixel.
+#[cfg(any(test, feature = "test-support"))]
+pub fn tamperings(wnn: &Wnn) -> Vec<Tampering> {
+    let [num_classes, num_filters, _] = wnn.bloom_filters.shape();
+    let num_accumulators = (num_filters + 3) / 4;
+    let (rows, columns) = wnn.img_shape();
+    let num_bits = wnn.binarization_thresholds.shape()[2];
+
+    let mut tamperings = vec![];
+    for class in 0..num_classes {
+        for filter in [0, num_filters - 1] {
+            tamperings.push(Tampering::BloomResponse { class, filter });
+        }
+        for row in [1, num_accumulators] {
+            tamperings.push(Tampering::Accumulator { class, row });
+        }
+    }
+    for bit in 0..num_bits {
+        for pixel in [(0, 0), (rows - 1, columns - 1)] {
+            tamperings.push(Tampering::BinarizationBit { pixel, bit });
+        }
+    }
+    // Models with a single filter, accumulator or pixel
+    tamperings.dedup();
+    tamperings
+}
+
+/// Checks that the circuit for the given image rejects each of the [`tamperings`], returning
+/// those that are accepted (i.e. an empty vector if the check passes).
+#[cfg(any(test, feature = "test-support"))]
+pub fn check_tampering_rejected(
+    wnn: &Wnn,
+    image: &Array2<u8>,
+    k: u32,
+) -> Result<Vec<Tampering>, ZeroGError> {
+    let mut accepted = vec![];
+    for tampering in tamperings(wnn) {
+        if tampering_failures(wnn, image, k, tampering)?.is_empty() {
+            accepted.push(tampering);
+        }
+    }
+    Ok(accepted)
+}
+
+/// Like [`check_tampering_rejected`], but panics with the accepted tamperings.
+#[cfg(any(test, feature = "test-support"))]
+pub fn assert_tampering_rejected(wnn: &Wnn, image: &Array2<u8>, k: u32) {
+    match check_tampering_rejected(wnn, image, k) {
+        Ok(accepted) if accepted.is_empty() => {}
+        Ok(accepted) => panic!(
+            "The circuit accepts corrupted witnesses:\n{}",
+            accepted
+                .iter()
+                .map(|tampering| format!("  {tampering}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// Returns the name of the chip that assigns regions with the given name, if known.
+/// Annotated region names (see [`RegionPath`]) name the chip themselves.
+pub fn chip_for_region(region_name: &str) -> Option<&'static str> {
+    let path = RegionPath::parse(region_name);
+    if path.chip.is_some() {
+        return path.chip;
+    }
+    Some(match path.region.as_str() {
+        "image" | "bit is one" => "EncodeImageChip",
+        "bits2num" => "Bits2NumChip",
+        name if name.starts_with("input bit") => "Bits2NumChip",
+        "hash" | "hash (reduced)" => "HashChip",
+        "bloom_filters"
+        | "look up hash values"
+        | "and bits"
+        | "select_byte"
+        | "select_bit"
+        | "class lookup table"
+        | "look up hash values of all classes" => "BloomFilterChip",
+        "accumulate_responses" => "ResponseAccumulatorChip",
+        "greater_than_witness" | "greater_than_copy" => "GreaterThanChip",
+        "le" | "running sum" | "short range check" => "RangeCheckConfig",
+        _ => return None,
+    })
+}
+
+/// Describes a failure reported by the [`MockProver`], prefixed by the chip it occurred in
+/// (see [`chip_for_region`]), e.g. `[HashChip] Constraint 0 in gate 2 ('hash') is not satisfied
+/// in Region 5 ('hash') at offset 3`. For annotated regions, the prefix also contains the
+/// pixel, filter or class, e.g. `[HashChip, filter 3]`.
+pub fn describe_failure(failure: &VerifyFailure) -> String {
+    MappedFailure::new(failure).to_string()
+}
+
+/// A [`MockProver`] failure, mapped back to the chip, region and semantic indices (pixel,
+/// filter, class) it occurred in. The indices are only known for annotated regions (see
+/// [`mock_prove`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedFailure {
+    /// The failure as reported by the [`MockProver`].
+    pub description: String,
+    pub chip: Option<&'static str>,
+    /// The index of the region, in the order regions were assigned.
+    pub region_index: Option<usize>,
+    pub region: Option<RegionPath>,
+    /// The offset of the offending row within the region.
+    pub offset: Option<usize>,
+    /// The offending row, for failures outside of any region.
+    pub row: Option<usize>,
+}
+
+impl MappedFailure {
+    pub fn new(failure: &VerifyFailure) -> Self {
+        Self::from_description(failure.to_string())
+    }
+
+    fn from_description(description: String) -> Self {
+        let region = region_name(&description).map(RegionPath::parse);
+        Self {
+            chip: region_name(&description).and_then(chip_for_region),
+            region_index: number_after(&description, "Region "),
+            region,
+            offset: number_after(&description, "at offset "),
+            row: number_after(&description, "on row "),
+            description,
+        }
+    }
+
+    /// The chip and the semantic indices, e.g. `HashChip, filter 3`, or an empty string if
+    /// neither is known.
+    pub fn location(&self) -> String {
+        let mut parts: Vec<_> = self.chip.iter().map(|chip| chip.to_string()).collect();
+        if let Some(path) = &self.region {
+            if let Some((row, column)) = path.pixel {
+                parts.push(format!("pixel ({row}, {column})"));
+            }
+            for (keyword, index) in [
+                ("bit", path.bit),
+                ("class", path.class),
+                ("filter", path.filter),
+            ] {
+                if let Some(index) = index {
+                    parts.push(format!("{keyword} {index}"));
+                }
+            }
+        }
+        parts.join(", ")
+    }
+}
+
+impl fmt::Display for MappedFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = self.location();
+        if location.is_empty() {
+            write!(f, "{}", self.description)
+        } else {
+            write!(f, "[{location}] {}", self.description)
+        }
+    }
+}
+
+/// [`MockProver`] failures grouped by their location (see [`MappedFailure::location`]), e.g.
+/// to print a summary of a failed [`mock_prove`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    /// The failures per location, in the order of their first failure.
+    pub groups: Vec<(String, Vec<MappedFailure>)>,
+    /// The pixels that are inputs to each filter that appears in a location, if a model was
+    /// given.
+    pub filter_pixels: BTreeMap<usize, Vec<(usize, usize)>>,
+}
+
+impl Diagnosis {
+    /// Maps and groups the failures. If the model is given, filters are also mapped to the
+    /// pixels they see.
+    pub fn new(failures: &[VerifyFailure], wnn: Option<&Wnn>) -> Self {
+        Self::from_mapped(failures.iter().map(MappedFailure::new), wnn)
+    }
+
+    fn from_mapped(failures: impl IntoIterator<Item = MappedFailure>, wnn: Option<&Wnn>) -> Self {
+        let mut groups: Vec<(String, Vec<MappedFailure>)> = vec![];
+        let mut filter_pixels = BTreeMap::new();
+        for failure in failures {
+            let filter = failure.region.as_ref().and_then(|path| path.filter);
+            if let (Some(wnn), Some(filter)) = (wnn, filter) {
+                filter_pixels
+                    .entry(filter)
+                    .or_insert_with(|| wnn.filter_pixels(filter));
+            }
+            let location = failure.location();
+            match groups.iter_mut().find(|(other, _)| *other == location) {
+                Some((_, group)) => group.push(failure),
+                None => groups.push((location, vec![failure])),
+            }
+        }
+        Self {
+            groups,
+            filter_pixels,
+        }
+    }
+
+    pub fn num_failures(&self) -> usize {
+        self.groups.iter().map(|(_, group)| group.len()).sum()
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failures in {} locations",
+            self.num_failures(),
+            self.groups.len()
+        )?;
+        for (location, group) in &self.groups {
+            let location = match location.is_empty() {
+                true => "Unknown location",
+                false => location,
+            };
+            write!(f, "\n  {location}: {} failures", group.len())?;
+            let filter = group[0].region.as_ref().and_then(|path| path.filter);
+            if let Some(pixels) = filter.and_then(|filter| self.filter_pixels.get(&filter)) {
+                write!(f, " (filter sees pixels {pixels:?})")?;
+            }
+            for failure in group {
+                write!(f, "\n    {}", failure.description)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the number following `prefix` in `description`.
+fn number_after(description: &str, prefix: &str) -> Option<usize> {
+    let start = description.find(prefix)? + prefix.len();
+    let digits: String = description[start..]
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+/// Extracts the region name from a failure, formatted as `... Region <index> ('<name>') ...`.
+fn region_name(description: &str) -> Option<&str> {
+    let region = &description[description.find("Region ")?..];
+    let start = region.find("('")? + 2;
+    let end = start + region[start..].find("')")?;
+    Some(&region[start..end])
+}
+
+/// Bisects the filters, replacing all filters outside of the current range by filters that
+/// always respond positively, until a single filter is left on which the circuit diverges.
+/// The circuit is known to diverge if no filter is replaced.
+fn find_diverging_filter(
+    wnn: &Wnn,
+    image: &Array2<u8>,
+    k: u32,
+) -> Result<Option<usize>, ZeroGError> {
+    let bloom_filters = wnn.bloom_filters.to_array();
+    let diverges = |start: usize, end: usize| -> Result<bool, ZeroGError> {
+        let mut masked = bloom_filters.clone();
+        for ((_, filter, _), entry) in masked.indexed_iter_mut() {
+            if !(start..end).contains(&filter) {
+                *entry = true;
+            }
+        }
+        let masked_wnn = with_bloom_filters(wnn, masked);
+        Ok(!mock_prover_failures(&masked_wnn, image, k)?.is_empty())
+    };
+
+    let (mut start, mut end) = (0, bloom_filters.shape()[1]);
+    while end - start > 1 {
+        let middle = (start + end) / 2;
+        if diverges(start, middle)? {
+            end = middle;
+        } else if diverges(middle, end)? {
+            start = middle;
+        } else {
+            // The divergence only shows up for a combination of filters
+            return Ok(None);
+        }
+    }
+    Ok((end > start).then_some(start))
+}
+
+fn with_bloom_filters(wnn: &Wnn, bloom_filters: Array3<bool>) -> Wnn {
+    Wnn::new(
+        wnn.num_classes,
+        wnn.num_filter_entries,
+        wnn.num_filter_hashes,
+        wnn.num_filter_inputs,
+        wnn.p,
+        bloom_filters,
+        wnn.input_permutation.clone(),
+        wnn.binarization_thresholds.clone(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array1, Array3};
+
+    use super::{
+        assert_tampering_rejected, chip_for_region, region_name, tampered_scores, tamperings,
+        Diagnosis, MappedFailure,
+    };
+    use crate::cost::minimal_k;
+    use crate::gadgets::wnn::Tampering;
+    use crate::wnn::Wnn;
+
+    #[test]
+    fn test_filter_pixels() {
+        // A 4x3 image with two thresholds -> 24 bits, 2 filters of 12 inputs
+        let input_order: Array1<u64> = (0..24u64).rev().collect();
+        let wnn = Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            2097143,
+            Array3::from_elem((2, 2, 1024), false),
+            input_order,
+            Array3::zeros((4, 3, 2)),
+        );
+        // Filter 0 sees bits 23..=12, i.e. all pixels for the second threshold
+        assert_eq!(wnn.filter_pixels(0).len(), 12);
+        assert_eq!(wnn.filter_pixels(1)[0], (0, 0));
+    }
+
+    #[test]
+    fn test_region_name() {
+        let description =
+            "Constraint 0 in gate 2 ('hash') is not satisfied in Region 5 ('hash') at offset 3";
+        assert_eq!(region_name(description), Some("hash"));
+        assert_eq!(
+            region_name(description).and_then(chip_for_region),
+            Some("HashChip")
+        );
+        assert_eq!(region_name("Equality constraint not satisfied"), None);
+        assert_eq!(
+            chip_for_region("HashChip filter 3/range check msb/le"),
+            Some("HashChip")
+        );
+    }
+
+    #[test]
+    fn test_diagnosis() {
+        let failures = [
+            "Constraint 0 in gate 2 ('hash') is not satisfied in Region 57 ('HashChip filter 1/hash/hash') at offset 3",
+            "Lookup 1 is not satisfied in Region 61 ('HashChip filter 1/range check msb/le') at offset 0",
+            "Equality constraint not satisfied by cell (Column('Instance', 0), outside any region, on row 1)",
+        ]
+        .map(|description| MappedFailure::from_description(description.to_string()));
+        assert_eq!(failures[0].chip, Some("HashChip"));
+        assert_eq!(failures[0].region_index, Some(57));
+        assert_eq!(failures[0].offset, Some(3));
+        assert_eq!(failures[0].location(), "HashChip, filter 1");
+        assert_eq!(failures[2].row, Some(1));
+        assert_eq!(failures[2].location(), "");
+
+        let wnn = Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            2097143,
+            Array3::from_elem((2, 2, 1024), false),
+            (0..24u64).collect(),
+            Array3::zeros((4, 3, 2)),
+        );
+        let diagnosis = Diagnosis::from_mapped(failures, Some(&wnn));
+        assert_eq!(diagnosis.num_failures(), 3);
+        assert_eq!(diagnosis.groups.len(), 2);
+        assert_eq!(diagnosis.groups[0].1.len(), 2);
+        assert_eq!(diagnosis.filter_pixels[&1], wnn.filter_pixels(1));
+        assert!(diagnosis
+            .to_string()
+            .contains("HashChip, filter 1: 2 failures (filter sees pixels"));
+    }
+
+    #[test]
+    fn test_tampering_rejected() {
+        // The model of the test circuit in `crate::gadgets::wnn`
+        let mut bloom_filters = Array3::from_elem((2, 2, 1024), false);
+        bloom_filters[[0, 0, 966]] = true;
+        bloom_filters[[0, 0, 805]] = true;
+        bloom_filters[[1, 1, 494]] = true;
+        bloom_filters[[1, 1, 46]] = true;
+        let wnn = Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            2097143,
+            bloom_filters,
+            (0..24u64).map(|i| (i + 6) % 24).collect(),
+            array![
+                [[50, 150], [0, 50], [200, 256]],
+                [[10, 80], [100, 200], [50, 150]],
+                [[0, 100], [100, 200], [0, 100]],
+                [[0, 100], [100, 200], [0, 100]]
+            ],
+        );
+        let image = array![[70, 100, 150], [20, 110, 200], [27, 50, 211], [200, 100, 3]];
+
+        assert_eq!(wnn.predict(&image), vec![1, 1]);
+        let flip_response = Tampering::BloomResponse {
+            class: 1,
+            filter: 1,
+        };
+        assert_eq!(tampered_scores(&wnn, &image, flip_response), vec![1, 0]);
+        let increment = Tampering::Accumulator { class: 0, row: 1 };
+        assert_eq!(tampered_scores(&wnn, &image, increment), vec![2, 1]);
+        // Pixel (3, 2) has intensity 3, so its bit for threshold 0 is one
+        let flip_bit = Tampering::BinarizationBit {
+            pixel: (3, 2),
+            bit: 0,
+        };
+        assert_ne!(tampered_scores(&wnn, &image, flip_bit), wnn.predict(&image));
+        assert!(tamperings(&wnn).contains(&flip_bit));
+
+        assert_tampering_rejected(&wnn, &image, minimal_k(&wnn).unwrap());
+    }
+
+    #[cfg(feature = "hdf5")]
+    #[test]
+    fn test_circuit_matches_predict() {
+        use std::path::Path;
+
+        use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+        use crate::{load_grayscale_image, load_wnn};
+
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let image = load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap();
+        super::assert_circuit_matches_predict(&wnn, &[image], k);
+    }
+}
@@ -1,5 +1,9 @@
-//! Utility functions.
+//! Utility functions, in particular conversions between field elements, integers and bits.
+//!
+//! Conversions to fixed-width integers ([`to_u32`], [`to_u64`]) panic if the value does not
+//! fit, use [`try_to_u64`] or [`to_biguint`] for values that might be wider.
 
+use std::borrow::Cow;
 use std::ops::Range;
 
 use ff::{PrimeField, PrimeFieldBits};
@@ -8,16 +12,22 @@ use halo2_proofs::{
     plonk::{Error, Selector},
 };
 use num_bigint::BigUint;
+use sha3::{Digest, Keccak256};
+
+/// The keccak256 hash of the bytes, as computed by the EVM.
+pub fn keccak256(bytes: impl AsRef<[u8]>) -> [u8; 32] {
+    Keccak256::digest(bytes.as_ref()).into()
+}
 
 #[allow(dead_code)]
 pub fn print_value<F: PrimeFieldBits>(name: &str, value: Value<&F>) {
-    value.map(|x| println!("{name}: {:#01x}", to_u32(x)));
+    value.map(|x| println!("{name}: {:#01x}", to_biguint(x)));
 }
 
 #[allow(dead_code)]
 pub fn print_values<F: PrimeFieldBits>(name: &str, values: &[Value<F>]) {
     values.iter().for_each(|value| {
-        value.map(|x| println!("{name}: {:#01x}", to_u32(&x)));
+        value.map(|x| println!("{name}: {:#01x}", to_biguint(&x)));
     });
 }
 
@@ -44,26 +54,76 @@ pub fn argmax(vec: &[u64]) -> usize {
     index
 }
 
+/// Divides `x` (as an integer in `[0, modulus)`) by `divisor`, rounding down.
+///
+/// # Panics
+/// If `divisor` is zero.
 pub fn integer_division<F: PrimeField>(x: F, divisor: BigUint) -> F {
-    let x_bigint = BigUint::from_bytes_le(x.to_repr().as_ref());
-    let quotient = x_bigint / divisor;
+    integer_div_rem(x, &divisor).0
+}
+
+/// Returns the quotient (rounded down) and remainder of dividing `x` (as an integer in
+/// `[0, modulus)`) by `divisor`, such that `x = quotient * divisor + remainder` holds over the
+/// integers.
+///
+/// # Panics
+/// If `divisor` is zero.
+pub fn integer_div_rem<F: PrimeField>(x: F, divisor: &BigUint) -> (F, F) {
+    assert!(*divisor != BigUint::from(0u8), "Division by zero");
+    let x = to_biguint(&x);
+    (from_biguint(&(&x / divisor)), from_biguint(&(&x % divisor)))
+}
+
+/// Returns the canonical integer representation of `x`, in `[0, modulus)`.
+pub fn to_biguint<F: PrimeField>(x: &F) -> BigUint {
+    // The representation of the fields used in this crate is little endian
+    BigUint::from_bytes_le(x.to_repr().as_ref())
+}
 
-    // Convert from BigInt to F
-    let bytes_be = quotient.to_bytes_be();
+/// Converts an integer to a field element, reducing it modulo the field's modulus.
+pub fn from_biguint<F: PrimeField>(x: &BigUint) -> F {
     let shift_factor = F::from(256);
-    bytes_be
+    x.to_bytes_be()
         .iter()
         .fold(F::ZERO, |acc, b| acc * shift_factor + F::from(*b as u64))
 }
 
+/// Returns `x` as a `u64`, or `None` if it is `2^64` or larger.
+pub fn try_to_u64<F: PrimeFieldBits>(x: &F) -> Option<u64> {
+    let bits = x.to_le_bits();
+    if bits.iter().by_vals().skip(64).any(|bit| bit) {
+        return None;
+    }
+    Some(
+        bits.iter()
+            .by_vals()
+            .take(64)
+            .rev()
+            .fold(0u64, |acc, bit| (acc << 1) | bit as u64),
+    )
+}
+
+/// Returns `x` as a `u64`.
+///
+/// # Panics
+/// If `x` does not fit into 64 bits.
+pub fn to_u64<F: PrimeFieldBits>(x: &F) -> u64 {
+    try_to_u64(x).expect("Field element does not fit into 64 bits")
+}
+
+/// Returns `x` as a `u32`.
+///
+/// # Panics
+/// If `x` does not fit into 32 bits.
+pub fn to_u32<F: PrimeFieldBits>(x: &F) -> u32 {
+    try_to_u64(x)
+        .and_then(|x| u32::try_from(x).ok())
+        .expect("Field element does not fit into 32 bits")
+}
+
 /// Return the big-endian bits of `x` as a vector of `n_bits` (least significant) bits.
 pub fn to_be_bits<F: PrimeFieldBits>(x: &F, n_bits: usize) -> Vec<bool> {
-    let mut result = x
-        .to_le_bits()
-        .iter()
-        .by_vals()
-        .take(n_bits)
-        .collect::<Vec<_>>();
+    let mut result = to_le_bits(x, n_bits);
 
     // Convert to big endian order
     result.reverse();
@@ -71,6 +131,12 @@ pub fn to_be_bits<F: PrimeFieldBits>(x: &F, n_bits: usize) -> Vec<bool> {
     result
 }
 
+/// Return the little-endian bits of `x` as a vector of `n_bits` (least significant) bits.
+pub fn to_le_bits<F: PrimeFieldBits>(x: &F, n_bits: usize) -> Vec<bool> {
+    x.to_le_bits().iter().by_vals().take(n_bits).collect()
+}
+
+/// Inverse of [`to_be_bits`].
 pub fn from_be_bits<F: PrimeField>(bits: &[bool]) -> F {
     let mut result = F::ZERO;
     let two = F::from(2_u64);
@@ -83,6 +149,14 @@ pub fn from_be_bits<F: PrimeField>(bits: &[bool]) -> F {
     result
 }
 
+/// Inverse of [`to_le_bits`].
+pub fn from_le_bits<F: PrimeField>(bits: &[bool]) -> F {
+    let reversed: Vec<_> = bits.iter().rev().copied().collect();
+    from_be_bits(&reversed)
+}
+
+/// Decomposes the `num_windows * window_num_bits` least significant bits of `word` into
+/// `num_windows` windows, most significant window first.
 pub fn decompose_word_be<F: PrimeFieldBits>(
     word: &F,
     num_windows: usize,
@@ -101,20 +175,143 @@ pub fn decompose_word_be<F: PrimeFieldBits>(
         .collect()
 }
 
-pub fn to_u32<F: PrimeFieldBits>(field_element: &F) -> u32 {
-    to_be_bits(field_element, 32)
-        .iter()
-        .fold(0u32, |acc, b| (acc << 1) + (*b as u32))
+/// Like [`decompose_word_be`], but least significant window first.
+pub fn decompose_word_le<F: PrimeFieldBits>(
+    word: &F,
+    num_windows: usize,
+    window_num_bits: usize,
+) -> Vec<F> {
+    let mut windows = decompose_word_be(word, num_windows, window_num_bits);
+    windows.reverse();
+    windows
+}
+
+/// Packs bits into bytes, 8 bits per byte. The first bit of each group of 8 is stored in
+/// the least significant bit. The last byte is zero-padded.
+pub fn pack_bits_le(bits: impl IntoIterator<Item = bool>) -> Vec<u8> {
+    let mut bytes = vec![];
+    for (i, bit) in bits.into_iter().enumerate() {
+        if i % 8 == 0 {
+            bytes.push(0u8);
+        }
+        *bytes.last_mut().unwrap() |= (bit as u8) << (i % 8);
+    }
+    bytes
+}
+
+/// Inverse of [`pack_bits_le`], returning the first `n_bits` bits.
+pub fn unpack_bits_le(bytes: &[u8], n_bits: usize) -> Vec<bool> {
+    (0..n_bits)
+        .map(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1)
+        .collect()
+}
+
+/// Deterministic Miller-Rabin primality test.
+pub fn is_prime(n: u64) -> bool {
+    const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+    if n < 2 {
+        return false;
+    }
+    for w in WITNESSES {
+        if n % w == 0 {
+            return n == w;
+        }
+    }
+
+    let mul_mod = |a: u64, b: u64| ((a as u128 * b as u128) % n as u128) as u64;
+    let pow_mod = |mut base: u64, mut exponent: u64| {
+        let mut result = 1;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = mul_mod(result, base);
+            }
+            base = mul_mod(base, base);
+            exponent >>= 1;
+        }
+        result
+    };
+
+    // Write n - 1 = d * 2^s with d odd
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    // These witnesses are sufficient for all 64-bit integers
+    WITNESSES.iter().all(|&w| {
+        let mut x = pow_mod(w, d);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// A permutation, stored as runs of consecutive indices. Applying it copies each run as a
+/// slice, so that identity permutations (a single run) and block permutations are cheap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermutationRuns {
+    runs: Vec<Range<usize>>,
+    len: usize,
+}
+
+impl PermutationRuns {
+    /// `permutation[i]` is the index of the value at position `i` of the output.
+    pub fn new(permutation: impl IntoIterator<Item = u64>) -> Self {
+        let mut runs: Vec<Range<usize>> = vec![];
+        let mut len = 0;
+        for index in permutation {
+            let index = index as usize;
+            match runs.last_mut() {
+                Some(run) if run.end == index => run.end += 1,
+                _ => runs.push(index..index + 1),
+            }
+            len += 1;
+        }
+        Self { runs, len }
+    }
+
+    /// The number of runs, i.e. 1 for the identity permutation.
+    pub fn num_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.runs.len() <= 1 && self.runs.iter().all(|run| run.start == 0)
+    }
+
+    /// Permutes `values`, without copying them if the permutation is the identity.
+    pub fn apply<'a, T: Clone>(&self, values: &'a [T]) -> Cow<'a, [T]> {
+        if self.is_identity() && values.len() == self.len {
+            return Cow::Borrowed(values);
+        }
+        let mut permuted = Vec::with_capacity(self.len);
+        for run in &self.runs {
+            permuted.extend_from_slice(&values[run.clone()]);
+        }
+        Cow::Owned(permuted)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
+    use ff::PrimeField;
     use halo2_proofs::halo2curves::bn256::Fr as Fp;
     use num_bigint::BigUint;
 
-    use crate::utils::{decompose_word_be, from_be_bits, to_be_bits, to_u32};
+    use crate::utils::{
+        decompose_word_be, decompose_word_le, from_be_bits, from_biguint, from_le_bits, is_prime,
+        pack_bits_le, to_be_bits, to_biguint, to_le_bits, to_u32, to_u64, try_to_u64,
+        unpack_bits_le, PermutationRuns,
+    };
 
-    use super::integer_division;
+    use super::{integer_div_rem, integer_division};
 
     #[test]
     fn test_to_be_bits() {
@@ -153,12 +350,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_to_le_bits() {
+        assert_eq!(to_le_bits(&Fp::from(6), 4), vec![false, true, true, false]);
+        assert_eq!(from_le_bits::<Fp>(&[true, true, false, true]), Fp::from(11));
+    }
+
+    #[test]
+    fn test_decompose_word_le() {
+        assert_eq!(
+            decompose_word_le(&Fp::from(0xabcdef), 2, 12),
+            vec![Fp::from(0xdef), Fp::from(0xabc)]
+        );
+    }
+
     #[test]
     fn test_to_u32() {
         assert_eq!(to_u32(&Fp::from(6)), 6u32);
         assert_eq!(to_u32(&Fp::from(0x11223344u64)), 0x11223344u32);
     }
 
+    #[test]
+    #[should_panic(expected = "does not fit into 32 bits")]
+    fn test_to_u32_too_large() {
+        to_u32(&Fp::from(1 << 32));
+    }
+
+    #[test]
+    fn test_to_u64() {
+        assert_eq!(to_u64(&Fp::from(u64::MAX)), u64::MAX);
+        assert_eq!(to_u64(&Fp::from(0x1122334455667788)), 0x1122334455667788);
+        assert_eq!(try_to_u64(&Fp::from_u128(1 << 64)), None);
+        assert_eq!(try_to_u64(&-Fp::one()), None);
+    }
+
+    #[test]
+    fn test_biguint() {
+        let x = (BigUint::from(1u8) << 200) + 42u8;
+        assert_eq!(to_biguint(&from_biguint::<Fp>(&x)), x);
+        assert_eq!(to_biguint(&Fp::from(6)), BigUint::from(6u8));
+    }
+
+    #[test]
+    fn test_pack_bits_le() {
+        let bits = [
+            true, false, true, true, false, false, false, false, false, true,
+        ];
+        let bytes = pack_bits_le(bits);
+        assert_eq!(bytes, vec![0b1101, 0b10]);
+        assert_eq!(unpack_bits_le(&bytes, bits.len()), bits.to_vec());
+    }
+
+    #[test]
+    fn test_permutation_runs() {
+        let values = [10, 11, 12, 13, 14];
+
+        let identity = PermutationRuns::new(0..5);
+        assert!(identity.is_identity());
+        assert!(matches!(identity.apply(&values), Cow::Borrowed(_)));
+
+        let blocks = PermutationRuns::new([3, 4, 0, 1, 2]);
+        assert_eq!(blocks.num_runs(), 2);
+        assert!(!blocks.is_identity());
+        assert_eq!(blocks.apply(&values).as_ref(), &[13, 14, 10, 11, 12]);
+
+        let reversed = PermutationRuns::new((0..5).rev());
+        assert_eq!(reversed.num_runs(), 5);
+        assert_eq!(reversed.apply(&values).as_ref(), &[14, 13, 12, 11, 10]);
+    }
+
     #[test]
     fn test_integer_division() {
         assert_eq!(
@@ -173,5 +433,53 @@ mod tests {
             integer_division(-Fp::one(), BigUint::from(2u8)).double(),
             -Fp::one()
         );
+
+        // Values wider than 64 bits
+        let x = (BigUint::from(1u8) << 200) + 5u8;
+        assert_eq!(
+            integer_division(from_biguint::<Fp>(&x), BigUint::from(3u8)),
+            from_biguint(&(&x / 3u8))
+        );
+        // Divisors wider than the field
+        let divisor = BigUint::from(1u8) << 300;
+        assert_eq!(integer_division(-Fp::one(), divisor), Fp::from(0));
+    }
+
+    #[test]
+    fn test_integer_div_rem() {
+        let divisor = BigUint::from(1u8) << 130;
+        let x = -Fp::from(7);
+        let (quotient, remainder) = integer_div_rem(x, &divisor);
+        assert_eq!(quotient * from_biguint::<Fp>(&divisor) + remainder, x);
+        assert!(to_biguint(&remainder) < divisor);
+        assert_eq!(to_biguint(&quotient), to_biguint(&x) >> 130);
+    }
+
+    #[test]
+    #[should_panic(expected = "Division by zero")]
+    fn test_integer_division_by_zero() {
+        integer_division(Fp::from(6), BigUint::from(0u8));
+    }
+
+    #[test]
+    fn test_is_prime() {
+        let small_primes = (0..100).filter(|n| is_prime(*n)).collect::<Vec<_>>();
+        assert_eq!(
+            small_primes,
+            vec![
+                2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79,
+                83, 89, 97
+            ]
+        );
+
+        // Primes used by the checked-in models
+        assert!(is_prime(509));
+        assert!(is_prime(2097143));
+        assert!(is_prime(8388593));
+        assert!(is_prime(9007199254740881));
+
+        // Strong pseudoprime to bases 2, 3, 5 and 7
+        assert!(!is_prime(3215031751));
+        assert!(!is_prime((1 << 21) - 1));
     }
 }
@@ -0,0 +1,148 @@
+//! The proof verification path: Deserializing the verification key and the SRS, replaying the
+//! transcript and the final pairing check.
+//!
+//! Unlike the rest of the crate, this module only works on in-memory buffers and does not use
+//! the file system, networking, threads or C libraries (e.g. zstd), so that it can be used in
+//! constrained environments such as embedded attestation devices or zkVM guests. Its only
+//! dependencies are `halo2_proofs` and the EVM transcript of `snark_verifier` (through the
+//! [`crate::backend`]).
+//!
+//! Reading the verification key requires the circuit, i.e. all of zero_g's gadgets. To verify
+//! without the rest of zero_g, use the `zero_g_verifier` crate (in verifier/) with a key from
+//! [`crate::verifier_bundle::VerifierBundle::verifier_key`].
+//!
+//! Note that `halo2_proofs` itself still requires `std` (e.g. for `std::io::Read`), so a
+//! `no_std` build of this module is blocked on upstream support.
+
+use std::fmt;
+use std::io;
+
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{self, VerifyingKey},
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    SerdeFormat::RawBytes,
+};
+
+use crate::backend::{DefaultBackend, ProvingBackend};
+use crate::gadgets::wnn::{instance_columns, InstanceLayout, WnnCircuitParams};
+use crate::gadgets::WnnCircuit;
+use crate::key_file;
+
+/// Reasons why a proof is rejected, see [`verify_with_key`] and
+/// [`crate::verifier_bundle::VerifierBundle::verify`].
+#[derive(Debug)]
+pub enum VerificationError {
+    /// The proof was generated for a circuit with different parameters.
+    CircuitParamsMismatch {
+        expected: WnnCircuitParams,
+        actual: WnnCircuitParams,
+    },
+    /// The proof was generated for a different verification key (see
+    /// [`crate::verifier_bundle::vk_fingerprint`]).
+    VkFingerprintMismatch {
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    /// The proof was generated for a different model (see [`crate::Wnn::commitment`]).
+    ModelCommitmentMismatch {
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    /// The proof file doesn't record the given metadata, so it can't be checked (e.g. proof
+    /// files of version 0, see [`crate::proof_file`]).
+    MissingMetadata(&'static str),
+    /// The number of public inputs does not match the instance layout.
+    WrongNumberOfPublicInputs { expected: usize, actual: usize },
+    /// The proof itself is invalid.
+    InvalidProof(plonk::Error),
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CircuitParamsMismatch { expected, actual } => write!(
+                f,
+                "Proof was generated for circuit params {actual:?}, expected {expected:?}"
+            ),
+            Self::VkFingerprintMismatch { expected, actual } => write!(
+                f,
+                "Proof was generated for verification key 0x{}, expected 0x{}",
+                hex::encode(actual),
+                hex::encode(expected)
+            ),
+            Self::ModelCommitmentMismatch { expected, actual } => write!(
+                f,
+                "Proof was generated for model 0x{}, expected 0x{}",
+                hex::encode(actual),
+                hex::encode(expected)
+            ),
+            Self::MissingMetadata(field) => {
+                write!(f, "The proof file doesn't record the {field}")
+            }
+            Self::WrongNumberOfPublicInputs { expected, actual } => {
+                write!(f, "Expected {expected} public inputs, got {actual}")
+            }
+            Self::InvalidProof(e) => write!(f, "Invalid proof: {e:?}"),
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+/// Reads an (uncompressed) verification key in the `RawBytes` format. The header of key files
+/// (see [`crate::key_file`]) is skipped without validating it.
+pub fn read_verifying_key(
+    mut bytes: &[u8],
+    circuit_params: WnnCircuitParams,
+) -> io::Result<VerifyingKey<G1Affine>> {
+    if bytes.starts_with(&key_file::MAGIC) {
+        // Magic bytes, format version and the length-prefixed header
+        let header_length = bytes
+            .get(6..10)
+            .map(|length| u32::from_le_bytes(length.try_into().unwrap()) as usize)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        bytes = bytes
+            .get(10 + header_length..)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    }
+    VerifyingKey::read::<_, WnnCircuit<_>>(&mut bytes, RawBytes, circuit_params)
+}
+
+/// Reads an (uncompressed) SRS.
+pub fn read_params(mut bytes: &[u8]) -> io::Result<ParamsKZG<Bn256>> {
+    ParamsKZG::read(&mut bytes)
+}
+
+/// Verifies a proof with the EVM transcript (i.e. as generated by [`crate::Wnn::proof`]),
+/// without checking the number of public inputs. The public inputs are distributed over the
+/// instance columns of the verification key like [`InstanceLayout`] does.
+pub fn verify_raw_proof(
+    proof: &[u8],
+    kzg_params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    public_inputs: &[Fr],
+) -> Result<(), plonk::Error> {
+    let instances = instance_columns(public_inputs, vk.cs().num_instance_columns());
+    DefaultBackend::verify(kzg_params, vk, &instances, proof)
+}
+
+/// Verifies a proof without a bundle or proof file, e.g. when the verification key and the
+/// SRS are distributed separately. Apart from the proof itself, only the number of public
+/// inputs is checked.
+pub fn verify_with_key(
+    proof: &[u8],
+    public_inputs: &[Fr],
+    vk: &VerifyingKey<G1Affine>,
+    kzg_params: &ParamsKZG<Bn256>,
+    circuit_params: &WnnCircuitParams,
+) -> Result<(), VerificationError> {
+    let expected = InstanceLayout::from_params(circuit_params).len();
+    if public_inputs.len() != expected {
+        return Err(VerificationError::WrongNumberOfPublicInputs {
+            expected,
+            actual: public_inputs.len(),
+        });
+    }
+    verify_raw_proof(proof, kzg_params, vk, public_inputs).map_err(VerificationError::InvalidProof)
+}
@@ -0,0 +1,551 @@
+//! A single file containing everything needed to verify proofs for a particular model.
+//!
+//! Without the bundle, verifiers need the SRS, the verification key and the circuit params
+//! (which are needed to deserialize the verification key), and have to know which public
+//! inputs to expect.
+//!
+//! The layout is as follows (all integers are little endian):
+//!
+//! | Field                 | Size                     |
+//! |-----------------------|--------------------------|
+//! | Magic bytes (`ZGVB`)  | 4 bytes                  |
+//! | Format version        | 2 bytes                  |
+//! | Header length         | 4 bytes                  |
+//! | Header (JSON)         | `header length` bytes    |
+//! | Verification key size | 4 bytes                  |
+//! | Verification key      | `RawBytes` format        |
+//! | SRS size              | 4 bytes                  |
+//! | SRS                   | See [`ParamsKZG::write`] |
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, G1Affine},
+    plonk::VerifyingKey,
+    poly::{
+        commitment::{Params, ParamsProver},
+        kzg::commitment::ParamsKZG,
+    },
+    SerdeFormat::RawBytes,
+};
+use serde::{Deserialize, Serialize};
+use snark_verifier::system::halo2::{compile, Config};
+use zero_g_verifier::VerifierKey;
+
+use crate::{
+    backend::{DefaultBackend, ProvingBackend},
+    gadgets::{
+        byte_table::DEFAULT_WINDOW_NUM_BITS,
+        wnn::{instance_columns, InstanceLayout, WnnCircuitParams},
+        WnnCircuit,
+    },
+    io::{invalid_data, read_array, read_length_prefixed, write_length_prefixed},
+    proof_file::ProofFile,
+    utils::keccak256,
+    Wnn,
+};
+
+pub use crate::verification::{verify_with_key, VerificationError};
+
+/// Magic bytes at the start of every verifier bundle.
+pub const MAGIC: [u8; 4] = *b"ZGVB";
+
+/// The format version written by this version of the crate.
+pub const CURRENT_VERSION: u16 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    circuit_params: WnnCircuitParams,
+    instance_layout: InstanceLayout,
+    model_commitment: [u8; 32],
+}
+
+/// Checks that a proof file records the given circuit params, verification key fingerprint
+/// and model commitment. All of them are required, so proofs without metadata (e.g. of proof
+/// file version 0) are rejected.
+pub(crate) fn check_proof_metadata(
+    proof: &ProofFile,
+    circuit_params: &WnnCircuitParams,
+    vk_fingerprint: [u8; 32],
+    model_commitment: [u8; 32],
+) -> Result<(), VerificationError> {
+    let metadata = &proof.metadata;
+    let actual_params = metadata
+        .circuit_params
+        .as_ref()
+        .ok_or(VerificationError::MissingMetadata("circuit params"))?;
+    if actual_params != circuit_params {
+        return Err(VerificationError::CircuitParamsMismatch {
+            expected: circuit_params.clone(),
+            actual: actual_params.clone(),
+        });
+    }
+    let actual_fingerprint = metadata
+        .vk_fingerprint
+        .ok_or(VerificationError::MissingMetadata(
+            "verification key fingerprint",
+        ))?;
+    if actual_fingerprint != vk_fingerprint {
+        return Err(VerificationError::VkFingerprintMismatch {
+            expected: vk_fingerprint,
+            actual: actual_fingerprint,
+        });
+    }
+    let actual_commitment = metadata
+        .model_commitment
+        .ok_or(VerificationError::MissingMetadata("model commitment"))?;
+    if actual_commitment != model_commitment {
+        return Err(VerificationError::ModelCommitmentMismatch {
+            expected: model_commitment,
+            actual: actual_commitment,
+        });
+    }
+    Ok(())
+}
+
+/// Computes a fingerprint (keccak256 hash) of the verification key and the circuit params.
+///
+/// The fingerprint is stable across versions of this crate as long as the serialization
+/// of the verification key does not change, so it can be used to pin the exact circuit
+/// a proof must verify against (e.g. in an on-chain registry).
+pub fn vk_fingerprint(vk: &VerifyingKey<G1Affine>, circuit_params: &WnnCircuitParams) -> [u8; 32] {
+    let mut bytes = b"zero_g.vk.v1".to_vec();
+    encode_circuit_params(circuit_params, &mut bytes);
+    vk.write(&mut bytes, RawBytes)
+        .expect("Writing to a vector should not fail");
+    keccak256(bytes)
+}
+
+/// Computes a hash (keccak256) of the circuit params, which is stable across versions of this
+/// crate like [`vk_fingerprint`].
+pub fn circuit_params_hash(circuit_params: &WnnCircuitParams) -> [u8; 32] {
+    let mut bytes = b"zero_g.circuit_params.v1".to_vec();
+    encode_circuit_params(circuit_params, &mut bytes);
+    keccak256(bytes)
+}
+
+fn encode_circuit_params(circuit_params: &WnnCircuitParams, bytes: &mut Vec<u8>) {
+    let WnnCircuitParams {
+        p,
+        l,
+        n_hashes,
+        bits_per_hash,
+        bits_per_filter,
+        n_classes,
+        min_blinding_factors,
+        num_instance_columns,
+        class_lookup,
+        window_num_bits,
+        chaining,
+        multi_label,
+        regression,
+        tabular,
+        occlusion_num_pixels,
+        robustness,
+        image_commitment,
+    } = *circuit_params;
+    bytes.extend(p.to_le_bytes());
+    for x in [l, n_hashes, bits_per_hash, bits_per_filter, n_classes] {
+        bytes.extend((x as u64).to_le_bytes());
+    }
+    // The optional fields are only included if set (so that existing fingerprints don't
+    // change) and tagged (so that different params can't produce the same bytes)
+    if min_blinding_factors != 0 {
+        bytes.extend(b"min_blinding_factors");
+        bytes.extend((min_blinding_factors as u64).to_le_bytes());
+    }
+    if num_instance_columns != 1 {
+        bytes.extend(b"instance_columns");
+        bytes.extend((num_instance_columns as u64).to_le_bytes());
+    }
+    if class_lookup {
+        bytes.extend(b"class_lookup");
+    }
+    if window_num_bits != DEFAULT_WINDOW_NUM_BITS {
+        bytes.extend(b"window_num_bits");
+        bytes.extend((window_num_bits as u64).to_le_bytes());
+    }
+    if chaining {
+        bytes.extend(b"chaining");
+    }
+    if multi_label {
+        bytes.extend(b"multi_label");
+    }
+    if regression {
+        bytes.extend(b"regression");
+    }
+    if tabular {
+        bytes.extend(b"tabular");
+    }
+    if occlusion_num_pixels != 0 {
+        bytes.extend(b"occlusion");
+        bytes.extend((occlusion_num_pixels as u64).to_le_bytes());
+    }
+    if robustness {
+        bytes.extend(b"robustness");
+    }
+    if image_commitment {
+        bytes.extend(b"image_commitment");
+    }
+}
+
+/// Packages the verification key, circuit params, instance layout and model commitment
+/// (together with the SRS) into one serializable artifact.
+pub struct VerifierBundle {
+    pub circuit_params: WnnCircuitParams,
+    pub instance_layout: InstanceLayout,
+    /// See [`Wnn::commitment`].
+    pub model_commitment: [u8; 32],
+    pub vk: VerifyingKey<G1Affine>,
+    pub kzg_params: ParamsKZG<Bn256>,
+}
+
+impl VerifierBundle {
+    /// Creates a bundle for the given model.
+    pub fn new(wnn: &Wnn, vk: VerifyingKey<G1Affine>, kzg_params: ParamsKZG<Bn256>) -> Self {
+        let circuit_params = wnn.get_circuit_params();
+        Self {
+            instance_layout: InstanceLayout::from_params(&circuit_params),
+            circuit_params,
+            model_commitment: wnn.commitment(),
+            vk,
+            kzg_params,
+        }
+    }
+
+    /// Compiles the bundle into a [`VerifierKey`], which verifies proofs with the
+    /// `zero_g_verifier` crate (in verifier/), without depending on the rest of zero_g.
+    /// The proof metadata is not checked by it.
+    pub fn verifier_key(&self) -> VerifierKey {
+        let num_instance = self.instance_layout.column_lengths();
+        VerifierKey {
+            protocol: compile(
+                &self.kzg_params,
+                &self.vk,
+                Config::kzg().with_num_instance(num_instance.clone()),
+            ),
+            num_instance,
+            g1: self.kzg_params.get_g()[0],
+            g2: self.kzg_params.g2(),
+            s_g2: self.kzg_params.s_g2(),
+        }
+    }
+
+    /// Verifies a proof against the bundled verification key.
+    pub fn verify(&self, proof: &ProofFile) -> Result<(), VerificationError> {
+        self.check_metadata(proof)?;
+        Wnn::try_verify_proof(
+            &proof.proof,
+            &self.kzg_params,
+            &self.vk,
+            &proof.public_inputs,
+        )
+        .map_err(VerificationError::InvalidProof)
+    }
+
+    /// Verifies several proofs against the bundled verification key, returning the result
+    /// for each proof.
+    ///
+    /// The final multi-scalar multiplications and pairings of all proofs are accumulated,
+    /// so that they only have to be computed once. Only if the accumulated check fails are
+    /// the proofs verified individually, to find out which of them are invalid.
+    pub fn verify_batch(&self, proofs: &[ProofFile]) -> Vec<Result<(), VerificationError>> {
+        let mut results: Vec<_> = proofs
+            .iter()
+            .map(|proof| self.check_metadata(proof))
+            .collect();
+
+        let num_instance_columns = self.vk.cs().num_instance_columns();
+        let checked: Vec<_> = proofs
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| result.is_ok())
+            .map(|(proof, _)| {
+                (
+                    proof.proof.as_slice(),
+                    instance_columns(&proof.public_inputs, num_instance_columns),
+                )
+            })
+            .collect();
+        let (checked_results, accumulated_valid) =
+            DefaultBackend::verify_batch(&self.kzg_params, &self.vk, &checked);
+        let mut checked_results = checked_results.into_iter();
+        for result in results.iter_mut().filter(|result| result.is_ok()) {
+            if let Some(Err(e)) = checked_results.next() {
+                *result = Err(VerificationError::InvalidProof(e));
+            }
+        }
+
+        if !accumulated_valid {
+            for (proof, result) in proofs.iter().zip(results.iter_mut()) {
+                if result.is_ok() {
+                    *result = self.verify(proof);
+                }
+            }
+        }
+        results
+    }
+
+    /// Checks the metadata (see [`check_proof_metadata`]) and the number of public inputs of
+    /// a proof.
+    fn check_metadata(&self, proof: &ProofFile) -> Result<(), VerificationError> {
+        check_proof_metadata(
+            proof,
+            &self.circuit_params,
+            self.vk_fingerprint(),
+            self.model_commitment,
+        )?;
+        if proof.public_inputs.len() != self.instance_layout.len() {
+            return Err(VerificationError::WrongNumberOfPublicInputs {
+                expected: self.instance_layout.len(),
+                actual: proof.public_inputs.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// See [`vk_fingerprint`].
+    pub fn vk_fingerprint(&self) -> [u8; 32] {
+        vk_fingerprint(&self.vk, &self.circuit_params)
+    }
+
+    /// Serializes the bundle.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let header = serde_json::to_vec(&Header {
+            circuit_params: self.circuit_params.clone(),
+            instance_layout: self.instance_layout.clone(),
+            model_commitment: self.model_commitment,
+        })?;
+        let mut vk = vec![];
+        self.vk.write(&mut vk, RawBytes)?;
+        let mut kzg_params = vec![];
+        self.kzg_params.write(&mut kzg_params)?;
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&CURRENT_VERSION.to_le_bytes())?;
+        write_length_prefixed(writer, &header)?;
+        write_length_prefixed(writer, &vk)?;
+        write_length_prefixed(writer, &kzg_params)
+    }
+
+    /// Deserializes a bundle.
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        if read_array::<4>(reader)? != MAGIC {
+            return Err(invalid_data("Not a zero_g verifier bundle"));
+        }
+        let version = u16::from_le_bytes(read_array(reader)?);
+        if version != CURRENT_VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported verifier bundle version {version} (latest supported: {CURRENT_VERSION})"
+            )));
+        }
+
+        let header: Header = serde_json::from_slice(&read_length_prefixed(reader)?)?;
+        let vk = VerifyingKey::read::<_, WnnCircuit<_>>(
+            &mut read_length_prefixed(reader)?.as_slice(),
+            RawBytes,
+            header.circuit_params.clone(),
+        )?;
+        let kzg_params = ParamsKZG::read(&mut read_length_prefixed(reader)?.as_slice())?;
+
+        Ok(Self {
+            circuit_params: header.circuit_params,
+            instance_layout: header.instance_layout,
+            model_commitment: header.model_commitment,
+            vk,
+            kzg_params,
+        })
+    }
+
+    /// Write the bundle to file.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Read the bundle from file.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::circuit_params_hash;
+    use crate::gadgets::wnn::WnnCircuitParams;
+
+    #[cfg(feature = "hdf5")]
+    #[test]
+    fn test_check_metadata() {
+        use std::path::Path;
+
+        use halo2_proofs::{
+            halo2curves::bn256::Bn256,
+            poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
+        };
+
+        use super::VerificationError;
+        use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+        use crate::proof_file::ProofMetadata;
+        use crate::prover::Prover;
+        use crate::{load_grayscale_image, load_wnn};
+
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let kzg_params = ParamsKZG::<Bn256>::new(k);
+        let pk = wnn.generate_proving_key(&kzg_params).unwrap();
+        let prover = Prover::new(wnn, kzg_params, pk);
+        let bundle = prover.verifier_bundle();
+        let image = load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap();
+        let proof = prover.prove(&image.into()).unwrap();
+        assert!(bundle.verify(&proof).is_ok());
+
+        let mut other_model = proof.clone();
+        other_model.metadata.model_commitment = Some([0; 32]);
+        assert!(matches!(
+            bundle.verify(&other_model),
+            Err(VerificationError::ModelCommitmentMismatch { .. })
+        ));
+
+        let mut other_params = proof.clone();
+        other_params
+            .metadata
+            .circuit_params
+            .as_mut()
+            .unwrap()
+            .class_lookup ^= true;
+        assert!(matches!(
+            bundle.verify(&other_params),
+            Err(VerificationError::CircuitParamsMismatch { .. })
+        ));
+
+        let mut other_vk = proof.clone();
+        other_vk.metadata.vk_fingerprint = Some([0; 32]);
+        assert!(matches!(
+            bundle.verify(&other_vk),
+            Err(VerificationError::VkFingerprintMismatch { .. })
+        ));
+
+        let mut missing_input = proof.clone();
+        missing_input.public_inputs.pop();
+        assert!(matches!(
+            bundle.verify(&missing_input),
+            Err(VerificationError::WrongNumberOfPublicInputs { .. })
+        ));
+
+        let removals: [(fn(&mut ProofMetadata), &str); 3] = [
+            (|metadata| metadata.circuit_params = None, "circuit params"),
+            (
+                |metadata| metadata.vk_fingerprint = None,
+                "verification key fingerprint",
+            ),
+            (
+                |metadata| metadata.model_commitment = None,
+                "model commitment",
+            ),
+        ];
+        for (remove, field) in removals {
+            let mut missing = proof.clone();
+            remove(&mut missing.metadata);
+            assert!(matches!(
+                bundle.verify(&missing),
+                Err(VerificationError::MissingMetadata(missing)) if missing == field
+            ));
+        }
+        assert!(bundle.verify_batch(&[proof, other_model])[1].is_err());
+    }
+
+    #[cfg(feature = "hdf5")]
+    #[test]
+    fn test_verifier_key() {
+        use std::path::Path;
+
+        use halo2_proofs::{
+            halo2curves::bn256::{Bn256, Fr},
+            poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
+        };
+        use zero_g_verifier::VerifierKey;
+
+        use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+        use crate::prover::Prover;
+        use crate::{load_grayscale_image, load_wnn};
+
+        let (k, model_path) = MNIST_TINY;
+        let wnn = load_wnn(Path::new(model_path)).unwrap();
+        let kzg_params = ParamsKZG::<Bn256>::new(k);
+        let pk = wnn.generate_proving_key(&kzg_params).unwrap();
+        let prover = Prover::new(wnn, kzg_params, pk);
+        let image = load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap();
+        let proof = prover.prove(&image.into()).unwrap();
+
+        let json = serde_json::to_string(&prover.verifier_bundle().verifier_key()).unwrap();
+        let verifier_key: VerifierKey = serde_json::from_str(&json).unwrap();
+        assert!(verifier_key
+            .verify(&proof.proof, &proof.public_inputs)
+            .is_ok());
+
+        let mut wrong_inputs = proof.public_inputs.clone();
+        wrong_inputs[0] += Fr::one();
+        assert!(verifier_key.verify(&proof.proof, &wrong_inputs).is_err());
+        assert!(verifier_key
+            .verify(&proof.proof, &proof.public_inputs[1..])
+            .is_err());
+    }
+
+    #[test]
+    fn test_circuit_params_hash() {
+        let params = WnnCircuitParams {
+            p: 2097143,
+            l: 20,
+            n_hashes: 2,
+            bits_per_hash: 10,
+            bits_per_filter: 12,
+            n_classes: 2,
+            min_blinding_factors: 0,
+            num_instance_columns: 1,
+            class_lookup: false,
+            window_num_bits: 8,
+            chaining: false,
+            multi_label: false,
+            regression: false,
+            tabular: false,
+            occlusion_num_pixels: 0,
+            robustness: false,
+            image_commitment: false,
+        };
+        let variants: Vec<fn(&mut WnnCircuitParams)> = vec![
+            |params| params.p = 1021,
+            |params| params.l = 21,
+            |params| params.n_hashes = 3,
+            |params| params.bits_per_hash = 11,
+            |params| params.bits_per_filter = 13,
+            |params| params.n_classes = 3,
+            |params| params.min_blinding_factors = 5,
+            |params| params.num_instance_columns = 2,
+            |params| params.class_lookup = true,
+            |params| params.window_num_bits = 4,
+            |params| params.chaining = true,
+            |params| params.multi_label = true,
+            |params| params.regression = true,
+            |params| params.tabular = true,
+            |params| params.occlusion_num_pixels = 5,
+            |params| params.robustness = true,
+            |params| params.image_commitment = true,
+        ];
+
+        let mut hashes = HashSet::from([circuit_params_hash(&params)]);
+        for (i, variant) in variants.iter().enumerate() {
+            let mut changed = params.clone();
+            variant(&mut changed);
+            assert!(
+                hashes.insert(circuit_params_hash(&changed)),
+                "Variant {i} collides"
+            );
+        }
+    }
+}
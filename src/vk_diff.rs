@@ -0,0 +1,307 @@
+//! Compares verifying keys, to manage circuit upgrades across deployed verifiers.
+//!
+//! A proof can be verified with a verifying key iff it was generated with a proving key whose
+//! verifying key has the same transcript representation, which halo2 derives from the domain,
+//! the constraint system and the fixed and permutation commitments. [`diff_vks`] reports
+//! whether this is the case, and if not, which of the underlying parameters differ, from the
+//! most structural (e.g. `k` or a circuit param) to the least (e.g. the contents of the lookup
+//! tables, i.e. the model).
+//!
+//! Keys can also be compared with a recorded fingerprint, see [`match_fingerprint`]: Either a
+//! [`vk_fingerprint`] (e.g. from a proof file or the on-chain registry) or a constraint system
+//! fingerprint (see [`crate::cs_fingerprint`]), e.g. to check which circuit version a deployed
+//! key belongs to.
+
+use std::fmt;
+
+use group::GroupEncoding;
+use halo2_proofs::{halo2curves::bn256::G1Affine, plonk::VerifyingKey};
+use serde::Serialize;
+
+use crate::cs_fingerprint::fingerprint;
+use crate::gadgets::wnn::WnnCircuitParams;
+use crate::utils::keccak256;
+use crate::verifier_bundle::vk_fingerprint;
+
+/// The parameters of a verifying key that determine whether proofs are interchangeable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VkSummary {
+    pub k: u32,
+    pub circuit_params: WnnCircuitParams,
+    /// See [`crate::cs_fingerprint::fingerprint`], hex-encoded.
+    pub cs_fingerprint: String,
+    pub num_advice_columns: usize,
+    pub num_fixed_columns: usize,
+    pub num_instance_columns: usize,
+    pub num_lookups: usize,
+    pub degree: usize,
+    /// keccak256 hash of the fixed commitments (which include the lookup tables), hex-encoded.
+    pub fixed_commitments: String,
+    /// keccak256 hash of the permutation commitments, hex-encoded.
+    pub permutation_commitments: String,
+    /// The transcript representation of the key, which every proof is bound to.
+    pub transcript_repr: String,
+    /// See [`vk_fingerprint`], hex-encoded.
+    pub vk_fingerprint: String,
+}
+
+impl VkSummary {
+    pub fn new(vk: &VerifyingKey<G1Affine>, circuit_params: WnnCircuitParams) -> Self {
+        let cs = vk.cs();
+        Self {
+            k: vk.get_domain().k(),
+            cs_fingerprint: hex::encode(fingerprint(&circuit_params)),
+            num_advice_columns: cs.num_advice_columns(),
+            num_fixed_columns: cs.num_fixed_columns(),
+            num_instance_columns: cs.num_instance_columns(),
+            num_lookups: cs.lookups().len(),
+            degree: cs.degree(),
+            fixed_commitments: hash_points(vk.fixed_commitments()),
+            permutation_commitments: hash_points(vk.permutation().commitments()),
+            transcript_repr: format!("{:?}", vk.transcript_repr()),
+            vk_fingerprint: hex::encode(vk_fingerprint(vk, &circuit_params)),
+            circuit_params,
+        }
+    }
+
+    /// The parameters as `(name, value)` pairs, most structural first.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let params = &self.circuit_params;
+        vec![
+            ("k", self.k.to_string()),
+            ("p", params.p.to_string()),
+            ("l", params.l.to_string()),
+            ("n_hashes", params.n_hashes.to_string()),
+            ("bits_per_hash", params.bits_per_hash.to_string()),
+            ("bits_per_filter", params.bits_per_filter.to_string()),
+            ("n_classes", params.n_classes.to_string()),
+            (
+                "min_blinding_factors",
+                params.min_blinding_factors.to_string(),
+            ),
+            (
+                "num_instance_columns",
+                params.num_instance_columns.to_string(),
+            ),
+            ("class_lookup", params.class_lookup.to_string()),
+            ("window_num_bits", params.window_num_bits.to_string()),
+            ("chaining", params.chaining.to_string()),
+            ("multi_label", params.multi_label.to_string()),
+            ("regression", params.regression.to_string()),
+            ("tabular", params.tabular.to_string()),
+            (
+                "occlusion_num_pixels",
+                params.occlusion_num_pixels.to_string(),
+            ),
+            ("robustness", params.robustness.to_string()),
+            ("image_commitment", params.image_commitment.to_string()),
+            ("cs_fingerprint", self.cs_fingerprint.clone()),
+            ("num_advice_columns", self.num_advice_columns.to_string()),
+            ("num_fixed_columns", self.num_fixed_columns.to_string()),
+            ("num_lookups", self.num_lookups.to_string()),
+            ("degree", self.degree.to_string()),
+            ("fixed_commitments", self.fixed_commitments.clone()),
+            (
+                "permutation_commitments",
+                self.permutation_commitments.clone(),
+            ),
+        ]
+    }
+}
+
+fn hash_points(points: &[G1Affine]) -> String {
+    let bytes: Vec<u8> = points
+        .iter()
+        .flat_map(|point| point.to_bytes().as_ref().to_vec())
+        .collect();
+    hex::encode(keccak256(bytes))
+}
+
+/// A parameter that differs between two keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VkDifference {
+    pub name: &'static str,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for VkDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} != {}", self.name, self.left, self.right)
+    }
+}
+
+/// The result of [`diff_vks`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VkDiff {
+    /// Whether proofs for one key are accepted by the other, i.e. the transcript
+    /// representations are equal.
+    pub interchangeable: bool,
+    /// The differing parameters, most structural first. Empty if the keys only differ in a way
+    /// that is not covered by the parameters (e.g. the `Debug` representation of halo2's
+    /// constraint system changed).
+    pub differences: Vec<VkDifference>,
+}
+
+impl fmt::Display for VkDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.interchangeable {
+            return write!(f, "The keys are interchangeable");
+        }
+        write!(f, "The keys are not interchangeable")?;
+        match self.differences.first() {
+            Some(difference) => {
+                write!(f, ", because {} differs", difference.name)?;
+                for difference in &self.differences {
+                    write!(f, "\n  {difference}")?;
+                }
+                Ok(())
+            }
+            None => write!(f, ", but no parameter differs"),
+        }
+    }
+}
+
+/// Compares two keys, see the module documentation.
+pub fn diff_vks(left: &VkSummary, right: &VkSummary) -> VkDiff {
+    let differences = left
+        .fields()
+        .into_iter()
+        .zip(right.fields())
+        .filter(|((_, left), (_, right))| left != right)
+        .map(|((name, left), (_, right))| VkDifference { name, left, right })
+        .collect();
+    VkDiff {
+        interchangeable: left.transcript_repr == right.transcript_repr,
+        differences,
+    }
+}
+
+/// What a recorded fingerprint identifies, see [`match_fingerprint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FingerprintMatch {
+    /// The [`vk_fingerprint`] of the key, i.e. proofs are interchangeable.
+    VerifyingKey,
+    /// The constraint system fingerprint of the key, i.e. the key was generated for the same
+    /// circuit, but possibly for a different model or `k`.
+    ConstraintSystem,
+    /// Neither.
+    None,
+}
+
+impl fmt::Display for FingerprintMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VerifyingKey => write!(
+                f,
+                "The fingerprint is the VK fingerprint of the key, so proofs are interchangeable"
+            ),
+            Self::ConstraintSystem => write!(
+                f,
+                "The fingerprint is the constraint system fingerprint of the key, so proofs are \
+                 only interchangeable for the same model and k"
+            ),
+            Self::None => write!(f, "The fingerprint does not belong to the key"),
+        }
+    }
+}
+
+/// Compares a key with a recorded (hex-encoded) fingerprint, which is either a
+/// [`vk_fingerprint`] or a constraint system fingerprint.
+pub fn match_fingerprint(summary: &VkSummary, fingerprint: &str) -> FingerprintMatch {
+    let fingerprint = fingerprint.trim_start_matches("0x").to_lowercase();
+    if fingerprint == summary.vk_fingerprint {
+        FingerprintMatch::VerifyingKey
+    } else if fingerprint == summary.cs_fingerprint {
+        FingerprintMatch::ConstraintSystem
+    } else {
+        FingerprintMatch::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_vks, match_fingerprint, FingerprintMatch, VkSummary};
+    use crate::gadgets::wnn::WnnCircuitParams;
+    use crate::verifier_bundle::vk_fingerprint;
+
+    fn summary() -> VkSummary {
+        VkSummary {
+            k: 14,
+            circuit_params: WnnCircuitParams {
+                p: 2097143,
+                l: 20,
+                n_hashes: 2,
+                bits_per_hash: 10,
+                bits_per_filter: 12,
+                n_classes: 2,
+                min_blinding_factors: 0,
+                num_instance_columns: 1,
+                class_lookup: false,
+                window_num_bits: 8,
+                chaining: false,
+                multi_label: false,
+                regression: false,
+                tabular: false,
+                occlusion_num_pixels: 0,
+                robustness: false,
+                image_commitment: false,
+            },
+            cs_fingerprint: "ab".repeat(32),
+            num_advice_columns: 6,
+            num_fixed_columns: 4,
+            num_instance_columns: 1,
+            num_lookups: 5,
+            degree: 5,
+            fixed_commitments: "01".repeat(32),
+            permutation_commitments: "02".repeat(32),
+            transcript_repr: "0x03".to_string(),
+            vk_fingerprint: "cd".repeat(32),
+        }
+    }
+
+    #[test]
+    fn test_diff_vks() {
+        let diff = diff_vks(&summary(), &summary());
+        assert!(diff.interchangeable);
+        assert!(diff.differences.is_empty());
+        assert_eq!(diff.to_string(), "The keys are interchangeable");
+
+        // A different model with the same shape only changes the lookup tables
+        let mut other_model = summary();
+        other_model.fixed_commitments = "04".repeat(32);
+        other_model.transcript_repr = "0x05".to_string();
+        let diff = diff_vks(&summary(), &other_model);
+        assert!(!diff.interchangeable);
+        assert_eq!(diff.differences.len(), 1);
+        assert_eq!(diff.differences[0].name, "fixed_commitments");
+
+        let mut upgraded = other_model;
+        upgraded.k = 15;
+        upgraded.circuit_params.class_lookup = true;
+        let diff = diff_vks(&summary(), &upgraded);
+        let names: Vec<_> = diff.differences.iter().map(|d| d.name).collect();
+        assert_eq!(names, vec!["k", "class_lookup", "fixed_commitments"]);
+        assert!(diff
+            .to_string()
+            .starts_with("The keys are not interchangeable, because k differs\n  k: 14 != 15"));
+    }
+
+    #[test]
+    fn test_match_fingerprint() {
+        let summary = summary();
+        assert_eq!(
+            match_fingerprint(&summary, &format!("0x{}", "CD".repeat(32))),
+            FingerprintMatch::VerifyingKey
+        );
+        assert_eq!(
+            match_fingerprint(&summary, &"ab".repeat(32)),
+            FingerprintMatch::ConstraintSystem
+        );
+        assert_eq!(
+            match_fingerprint(&summary, &"ef".repeat(32)),
+            FingerprintMatch::None
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! JavaScript bindings (via `wasm-bindgen`) to verify proofs in the browser or in JS backends,
+//! and to prove inference of small models without the image leaving the client.
+//!
+//! Everything is passed as bytes, so no file system is needed. Build the package with
+//! `wasm-pack build bindings/wasm`.
+//!
+//! Both verification functions return `false` if the proof is invalid, and throw if the inputs
+//! can't be parsed or don't match the verification key (e.g. the wrong number of public inputs).
+//!
+//! Proving runs single-threaded, unless the `wasm-threads` feature is enabled: rayon's global
+//! pool (which halo2 and zero_g use for parallelism) is then built with only the calling thread
+//! as worker, so no thread is ever spawned. With `wasm-threads`, the module has to be built with
+//! atomics enabled and `initThreadPool` has to be awaited before proving, see
+//! [`wasm-bindgen-rayon`](https://github.com/RReverser/wasm-bindgen-rayon).
+
+use halo2_proofs::halo2curves::bn256::Fr;
+use ndarray::Array1;
+use wasm_bindgen::prelude::*;
+
+#[cfg(feature = "wasm-threads")]
+pub use wasm_bindgen_rayon::init_thread_pool;
+
+use crate::classifier::{ProvableClassifier, WnnInput};
+use crate::gadgets::wnn::WnnCircuitParams;
+use crate::io::{
+    load_image_from_bytes, load_model_from_bytes, read_pk_from_bytes, read_srs_from_bytes,
+    read_vk_from_bytes,
+};
+use crate::proof_file::ProofFile;
+use crate::prover::Prover;
+use crate::verification::{verify_with_key, VerificationError};
+use crate::verifier_bundle::VerifierBundle;
+
+/// Verifies a raw proof.
+///
+/// - `proof`: The proof bytes (the `proof` field of a proof file)
+/// - `public_inputs`: The public inputs, i.e. the score of each class
+/// - `vk`: The verification key, as written by `zero_g generate-keys` (optionally
+///   zstd-compressed)
+/// - `params`: The SRS, as written by `zero_g gen-srs` (optionally zstd-compressed)
+/// - `circuit_params`: The circuit params as JSON, which are needed to read the verification key
+#[wasm_bindgen]
+pub fn verify(
+    proof: &[u8],
+    public_inputs: &[u32],
+    vk: &[u8],
+    params: &[u8],
+    circuit_params: &str,
+) -> Result<bool, JsError> {
+    init_single_threaded();
+    let circuit_params: WnnCircuitParams = serde_json::from_str(circuit_params)?;
+    let kzg_params = read_srs_from_bytes(params)?;
+    let vk = read_vk_from_bytes(vk, circuit_params.clone())?;
+    let public_inputs: Vec<_> = public_inputs.iter().map(|x| Fr::from(*x as u64)).collect();
+    to_validity(verify_with_key(
+        proof,
+        &public_inputs,
+        &vk,
+        &kzg_params,
+        &circuit_params,
+    ))
+}
+
+/// Verifies a proof file (`.zgp`) against a verifier bundle (written by
+/// `zero_g generate-keys --bundle-path`).
+#[wasm_bindgen(js_name = verifyWithBundle)]
+pub fn verify_with_bundle(bundle: &[u8], proof_file: &[u8]) -> Result<bool, JsError> {
+    init_single_threaded();
+    let bundle = VerifierBundle::read_from(&mut &bundle[..])?;
+    let proof_file = ProofFile::from_bytes(proof_file)?;
+    to_validity(bundle.verify(&proof_file))
+}
+
+/// Builds rayon's global pool with the calling thread as its only worker, unless the
+/// `wasm-threads` feature is enabled. Otherwise, the first parallel iterator would try to spawn
+/// worker threads, which panics without atomics.
+fn init_single_threaded() {
+    #[cfg(not(feature = "wasm-threads"))]
+    {
+        static INIT: std::sync::Once = std::sync::Once::new();
+        INIT.call_once(|| {
+            // Fails if the embedder already built the global pool, which is fine.
+            let _ = rayon::ThreadPoolBuilder::new()
+                .num_threads(1)
+                .use_current_thread()
+                .build_global();
+        });
+    }
+}
+
+/// Maps a verification result to whether the proof is valid.
+fn to_validity(result: Result<(), VerificationError>) -> Result<bool, JsError> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(VerificationError::InvalidProof(_)) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Proves inference of a model, see [`Prover`].
+#[wasm_bindgen(js_name = Prover)]
+pub struct WasmProver(Prover);
+
+#[wasm_bindgen(js_class = Prover)]
+impl WasmProver {
+    /// Loads the prover from a `.zgm` model and the SRS.
+    ///
+    /// If no proving key (as written by `zero_g generate-keys`) is given, it is generated,
+    /// which takes about as long as proving.
+    #[wasm_bindgen(constructor)]
+    pub fn new(model: &[u8], params: &[u8], pk: Option<Vec<u8>>) -> Result<WasmProver, JsError> {
+        init_single_threaded();
+        let wnn = load_model_from_bytes(model)?;
+        let kzg_params = read_srs_from_bytes(params)?;
+        let pk = match pk {
+            Some(pk) => read_pk_from_bytes(&pk, wnn.get_circuit_params())?,
+            None => wnn.generate_proving_key(&kzg_params)?,
+        };
+        Ok(Self(Prover::new(wnn, kzg_params, pk)))
+    }
+
+    /// Proves inference of the image (PNG, JPEG, ...) and returns the proof file (`.zgp`),
+    /// which also contains the scores.
+    pub fn prove(&self, image: &[u8]) -> Result<Vec<u8>, JsError> {
+        let input = WnnInput::Image(load_image_from_bytes(image)?);
+        Ok(self.0.prove(&input)?.to_bytes())
+    }
+
+    /// Like [`WasmProver::prove`], for the feature vector of a tabular model.
+    #[wasm_bindgen(js_name = proveFeatures)]
+    pub fn prove_features(&self, features: &[u16]) -> Result<Vec<u8>, JsError> {
+        let input = WnnInput::Features(Array1::from(features.to_vec()));
+        Ok(self.0.prove(&input)?.to_bytes())
+    }
+
+    /// The scores of each class for the image, without proving.
+    pub fn predict(&self, image: &[u8]) -> Result<Vec<u32>, JsError> {
+        self.predict_input(WnnInput::Image(load_image_from_bytes(image)?))
+    }
+
+    /// Like [`WasmProver::predict`], for the feature vector of a tabular model.
+    #[wasm_bindgen(js_name = predictFeatures)]
+    pub fn predict_features(&self, features: &[u16]) -> Result<Vec<u32>, JsError> {
+        self.predict_input(WnnInput::Features(Array1::from(features.to_vec())))
+    }
+
+    /// The verifier bundle for the proofs of this prover, see [`verify_with_bundle`].
+    #[wasm_bindgen(js_name = verifierBundle)]
+    pub fn verifier_bundle(&self) -> Result<Vec<u8>, JsError> {
+        let mut bundle = vec![];
+        self.0.verifier_bundle().write_to(&mut bundle)?;
+        Ok(bundle)
+    }
+}
+
+impl WasmProver {
+    fn predict_input(&self, input: WnnInput) -> Result<Vec<u32>, JsError> {
+        self.0.check_input(&input)?;
+        Ok(ProvableClassifier::predict(self.0.model(), &input)
+            .into_iter()
+            .map(|score| score as u32)
+            .collect())
+    }
+}
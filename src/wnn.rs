@@ -1,56 +1,125 @@
 //! Module implementing the a weightless neural network (WNN), with the ability to proof inference.
 
+#[cfg(feature = "plotting")]
+use std::path::Path;
+use std::sync::Arc;
+
 use halo2_proofs::{
     dev::MockProver,
-    plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, ProvingKey, VerifyingKey},
+    plonk::{Error, ProvingKey, VerifyingKey},
     poly::{
-        commitment::ParamsProver,
-        kzg::{
-            commitment::{KZGCommitmentScheme, ParamsKZG},
-            multiopen::{ProverGWC, VerifierGWC},
-            strategy::SingleStrategy,
-        },
+        commitment::{Params, ParamsProver},
+        kzg::commitment::ParamsKZG,
     },
-    transcript::{TranscriptReadBuffer, TranscriptWriterBuffer},
+    transcript::{TranscriptWrite, TranscriptWriterBuffer},
 };
-use ndarray::{s, Array1, Array2, Array3};
+use ndarray::{Array1, Array2, Array3, Axis};
 
 use halo2_proofs::halo2curves::bn256::{Bn256, Fr as Fp, G1Affine};
+use image::ImageError;
 use num_bigint::BigUint;
-use rand_core::OsRng;
-use snark_verifier::system::halo2::transcript::evm::EvmTranscript;
+use rayon::prelude::*;
+use snark_verifier::{
+    loader::native::NativeLoader,
+    system::halo2::transcript::evm::{ChallengeEvm, EvmTranscript},
+};
+use tracing::{info_span, instrument};
+
+use crate::backend::{DefaultBackend, ProvingBackend};
+use crate::circuit_graph::CircuitGraph;
+use crate::datasets::{Dataset, Example};
+use crate::error::ZeroGError;
+use crate::evaluation::EvalReport;
+use crate::explain::FilterResponses;
+use crate::gadgets::byte_table::{DEFAULT_WINDOW_NUM_BITS, MAX_WINDOW_NUM_BITS};
+use crate::gadgets::encode_features::FEATURE_NUM_BITS;
+use crate::gadgets::image_commitment::chain;
+use crate::gadgets::poseidon::PoseidonSpec;
+use crate::gadgets::wnn::{
+    InstanceLayout, PublicValue, WnnCircuit, WnnCircuitParams, WnnSynthesisCache, SCORE_NUM_BITS,
+};
+use crate::image_commitment::{commitment_to_field, image_commitment, SALT_SIZE};
+#[cfg(feature = "plotting")]
+use crate::layout_plot::PlotOptions;
+use crate::model_info::ModelInfo;
+use crate::occlusion::Region;
+use crate::packed_bloom_filters::PackedBloomFilters;
+use crate::pixel_order::{check_permutation, PixelOrder};
+use crate::proof_chain::{chaining_value_to_field, GENESIS};
+use crate::quantization::QuantizationPolicy;
+use crate::robustness::linf_distance;
+use crate::utils::keccak256;
+use crate::utils::{argmax, is_prime, pack_bits_le, PermutationRuns};
+use crate::verification::verify_raw_proof;
 
-use crate::gadgets::wnn::{WnnCircuit, WnnCircuitParams};
+/// The maximum number of input bits per filter. Inputs of more than
+/// [`crate::gadgets::hash::HashFunctionConfig::max_direct_bits`] bits are reduced step by step
+/// in the circuit, see [`crate::gadgets::hash::HashChip`].
+pub const MAX_FILTER_INPUTS: usize = 128;
 
 /// Implementation of a [BTHOWeN](https://arxiv.org/abs/2203.01479)-style weightless neural network (WNN).
+#[derive(Clone)]
 pub struct Wnn {
     /// Number of classes (e.g. 10 for MNIST)
     pub num_classes: usize,
 
     /// Number fo input bits per filter
-    num_filter_inputs: usize,
+    pub(crate) num_filter_inputs: usize,
 
     /// The length of the bloom filter array
-    num_filter_entries: usize,
+    pub(crate) num_filter_entries: usize,
 
     /// The number of hashes used by the bloom filter
-    num_filter_hashes: usize,
+    pub(crate) num_filter_hashes: usize,
 
     /// Prime `p` used in the MishMash hash function
-    p: u64,
+    pub(crate) p: u64,
 
-    /// Bloom filter array, shape (num_classes, num_inputs * bits_per_input / num_filter_inputs, num_filter_entries)
-    bloom_filters: Array3<bool>,
+    /// Bloom filter array (bit-packed), shape (num_classes, num_inputs * bits_per_input / num_filter_inputs, num_filter_entries)
+    pub(crate) bloom_filters: PackedBloomFilters,
     /// Permutation of input bits, shape (num_inputs * bits_per_input)
-    input_permutation: Array1<u64>,
+    pub(crate) input_permutation: Array1<u64>,
+    /// The input permutation as runs, to skip the reordering for (near-)identity permutations.
+    input_runs: PermutationRuns,
     /// Thresholds for pixels, shape (width, height, bits_per_input)
     /// The numbers are in the range [0, 256].
-    binarization_thresholds: Array3<u16>,
+    pub(crate) binarization_thresholds: Array3<u16>,
+    /// How the thresholds were quantized, if known. This is metadata only, it does not affect
+    /// predictions or the commitment.
+    pub(crate) quantization_policy: Option<QuantizationPolicy>,
+    /// The minimum number of blinding factors of the circuit, see [`crate::blinding`].
+    pub(crate) min_blinding_factors: usize,
+    /// The number of instance columns of the circuit, see [`InstanceLayout`].
+    pub(crate) num_instance_columns: usize,
+    /// Whether the circuit looks up the bloom filters of all classes at once, see
+    /// [`Wnn::with_class_lookup`].
+    pub(crate) class_lookup: bool,
+    /// The number of bits per word of the range checks in the circuit, see
+    /// [`Wnn::with_window_num_bits`].
+    pub(crate) window_num_bits: usize,
+    /// Whether the circuit exposes a chaining value, see [`Wnn::with_chaining`].
+    pub(crate) chaining: bool,
+    /// The activation threshold of each class for multi-label models, see
+    /// [`Wnn::with_label_thresholds`].
+    pub(crate) label_thresholds: Option<Array1<u64>>,
+    /// Whether the model regresses a scalar, see [`Wnn::with_regression`].
+    pub(crate) regression: bool,
+    /// Whether the inputs are feature vectors instead of images, see [`Wnn::new_tabular`].
+    pub(crate) tabular: bool,
+    /// Whether the circuit can occlude pixels, see [`Wnn::with_occlusion`].
+    pub(crate) occlusion: bool,
+    /// Whether the circuit also predicts a perturbed image, see [`Wnn::with_robustness`].
+    pub(crate) robustness: bool,
+    /// Whether the circuit exposes a commitment to the image, see
+    /// [`Wnn::with_image_commitment`].
+    pub(crate) image_commitment: bool,
 }
 
 impl Wnn {
     /// Constructs a new WNN.
     /// Instead of calling this function directly, consider using [`crate::load_wnn`].
+    ///
+    /// Note that the parameters are not checked for consistency, see [`Wnn::validate`].
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         num_classes: usize,
@@ -59,7 +128,7 @@ impl Wnn {
         num_filter_inputs: usize,
         p: u64,
 
-        bloom_filters: Array3<bool>,
+        bloom_filters: impl Into<PackedBloomFilters>,
         input_order: Array1<u64>,
         binarization_thresholds: Array3<u16>,
     ) -> Self {
@@ -69,16 +138,400 @@ impl Wnn {
             num_filter_hashes,
             num_filter_inputs,
             p,
-            bloom_filters,
+            bloom_filters: bloom_filters.into(),
+            input_runs: PermutationRuns::new(input_order.iter().copied()),
             input_permutation: input_order,
             binarization_thresholds,
+            quantization_policy: None,
+            min_blinding_factors: 0,
+            num_instance_columns: 1,
+            class_lookup: false,
+            window_num_bits: DEFAULT_WINDOW_NUM_BITS,
+            chaining: false,
+            label_thresholds: None,
+            regression: false,
+            tabular: false,
+            occlusion: false,
+            robustness: false,
+            image_commitment: false,
+        }
+    }
+
+    /// Constructs a WNN for tabular data (e.g. the UCI datasets of the BTHOWeN paper), whose
+    /// inputs are feature vectors of `u16` values instead of images.
+    ///
+    /// Each feature is thermometer-encoded with its own thresholds, given with shape
+    /// `(num_features, bits_per_input)`: A bit is set if the feature is greater than or equal
+    /// to the threshold. Like for images, the bits are ordered by threshold index first, then
+    /// by feature, before the input order is applied. Internally, the thresholds are stored as
+    /// the binarization thresholds of an image with a single row.
+    ///
+    /// Use [`Wnn::predict_features`] and [`Wnn::features_proof`] instead of the image-based
+    /// methods.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_tabular(
+        num_classes: usize,
+        num_filter_entries: usize,
+        num_filter_hashes: usize,
+        num_filter_inputs: usize,
+        p: u64,
+
+        bloom_filters: impl Into<PackedBloomFilters>,
+        input_order: Array1<u64>,
+        feature_thresholds: Array2<u16>,
+    ) -> Self {
+        let mut wnn = Self::new(
+            num_classes,
+            num_filter_entries,
+            num_filter_hashes,
+            num_filter_inputs,
+            p,
+            bloom_filters,
+            input_order,
+            feature_thresholds.insert_axis(Axis(0)),
+        );
+        wnn.tabular = true;
+        wnn
+    }
+
+    /// Whether the inputs are feature vectors, see [`Wnn::new_tabular`].
+    pub fn is_tabular(&self) -> bool {
+        self.tabular
+    }
+
+    /// The number of features of a tabular model, see [`Wnn::new_tabular`].
+    pub fn num_features(&self) -> usize {
+        self.binarization_thresholds.shape()[1]
+    }
+
+    /// Records the policy that was used to quantize the binarization thresholds.
+    pub fn with_quantization_policy(mut self, policy: QuantizationPolicy) -> Self {
+        self.quantization_policy = Some(policy);
+        self
+    }
+
+    /// The policy that was used to quantize the binarization thresholds, if known.
+    pub fn quantization_policy(&self) -> Option<QuantizationPolicy> {
+        self.quantization_policy
+    }
+
+    /// Makes the circuit use at least `min_blinding_factors` blinding factors (see
+    /// [`crate::blinding`]). 0 uses the number derived by halo2.
+    ///
+    /// Note that this changes the verification key of the model.
+    pub fn with_min_blinding_factors(mut self, min_blinding_factors: usize) -> Self {
+        self.min_blinding_factors = min_blinding_factors;
+        self
+    }
+
+    /// Distributes the public inputs over the given number of instance columns (see
+    /// [`InstanceLayout`]), so that more public values fit into a circuit with few rows.
+    ///
+    /// Note that this changes the verification key of the model.
+    pub fn with_num_instance_columns(mut self, num_instance_columns: usize) -> Self {
+        self.num_instance_columns = num_instance_columns;
+        self
+    }
+
+    /// Makes the circuit look up the bloom filters of all classes at once (see
+    /// [`crate::gadgets::bloom_filter::ClassLookupChip`]), which shrinks the lookup table by a
+    /// factor of the number of classes, at the cost of two columns per class.
+    ///
+    /// Note that this changes the verification key of the model.
+    pub fn with_class_lookup(mut self, class_lookup: bool) -> Self {
+        self.class_lookup = class_lookup;
+        self
+    }
+
+    /// Sets the number of bits per word of the range checks in the circuit (8 by default). A
+    /// larger window decomposes values into fewer rows, but needs a lookup table of
+    /// `2^window_num_bits` additional rows.
+    ///
+    /// Note that this changes the verification key of the model.
+    pub fn with_window_num_bits(mut self, window_num_bits: usize) -> Self {
+        self.window_num_bits = window_num_bits;
+        self
+    }
+
+    /// Makes the circuit commit to the image and expose the previous chaining value and the
+    /// chaining value of the image as additional public inputs, which links the proofs of a
+    /// sequence of images, see [`crate::proof_chain`]. Proofs then have to be generated with
+    /// [`Wnn::chained_proof`].
+    ///
+    /// Note that this changes the verification key of the model.
+    pub fn with_chaining(mut self, chaining: bool) -> Self {
+        self.chaining = chaining;
+        self
+    }
+
+    /// Makes the model a multi-label classifier: Every class whose score reaches its threshold
+    /// (one per class) applies, see [`Wnn::predict_labels`]. The circuit then exposes one bit
+    /// per class instead of the scores.
+    ///
+    /// Note that this changes the commitment and the verification key of the model.
+    pub fn with_label_thresholds(mut self, label_thresholds: Array1<u64>) -> Self {
+        self.label_thresholds = Some(label_thresholds);
+        self
+    }
+
+    /// The activation thresholds of a multi-label model, see [`Wnn::with_label_thresholds`].
+    pub fn label_thresholds(&self) -> Option<&Array1<u64>> {
+        self.label_thresholds.as_ref()
+    }
+
+    /// Makes the model a regressor: Instead of classifying the image, a model with a single
+    /// discriminator bank (i.e. one class) outputs the number of positive responses of the
+    /// bank, see [`Wnn::regress`]. The circuit then exposes this output as its only public
+    /// value (besides the chaining value, if any), e.g. to prove a score or a rank.
+    ///
+    /// The constraint system doesn't change, but the commitment and the fingerprint of the
+    /// verification key (see [`crate::verifier_bundle::vk_fingerprint`]) do.
+    pub fn with_regression(mut self, regression: bool) -> Self {
+        self.regression = regression;
+        self
+    }
+
+    /// Whether the model regresses a scalar, see [`Wnn::with_regression`].
+    pub fn is_regression(&self) -> bool {
+        self.regression
+    }
+
+    /// Makes the circuit replace a public region of the image by zeros before binarization,
+    /// which proves occlusion-based explanations, see [`crate::occlusion`]. The circuit then
+    /// exposes one bit per pixel (whether it is occluded) after the scores, and proofs with an
+    /// occluded region are generated with [`Wnn::occlusion_proof`].
+    ///
+    /// Note that this changes the verification key of the model, but not its predictions.
+    pub fn with_occlusion(mut self, occlusion: bool) -> Self {
+        self.occlusion = occlusion;
+        self
+    }
+
+    /// Makes the circuit predict a second, perturbed image, whose pixels differ from the image
+    /// by at most a public bound (i.e. the images are within that L∞ distance), which proves
+    /// local robustness claims, see [`crate::robustness`]. The circuit then exposes the scores
+    /// of the perturbed image and the bound after the scores, and proofs with a perturbation
+    /// are generated with [`Wnn::robustness_proof`].
+    ///
+    /// Like [`Wnn::with_occlusion`], this only changes the circuit (and thus the verification
+    /// key), not the predictions.
+    pub fn with_robustness(mut self, robustness: bool) -> Self {
+        self.robustness = robustness;
+        self
+    }
+
+    /// Makes the circuit commit to the image and expose the commitment after the other public
+    /// values (but before the chaining value, if any), which shows that a proof was generated
+    /// for a registered image, see [`crate::image_commitment`]. Proofs with a salted commitment
+    /// are generated with [`Wnn::salted_proof`].
+    ///
+    /// Note that this changes the verification key of the model, but not its predictions.
+    pub fn with_image_commitment(mut self, image_commitment: bool) -> Self {
+        self.image_commitment = image_commitment;
+        self
+    }
+
+    /// Adapts the model to images whose pixels are stored in the given order (see
+    /// [`PixelOrder`]), by composing the reordering with the input permutation.
+    ///
+    /// Note that this changes the commitment of the model.
+    pub fn with_pixel_order(mut self, order: &PixelOrder) -> Result<Self, ZeroGError> {
+        if self.tabular {
+            return Err(ZeroGError::InvalidModel(
+                "Tabular models have no pixel order".to_string(),
+            ));
+        }
+        let (shape, training_indices) = order
+            .training_indices(self.img_shape())
+            .map_err(ZeroGError::InvalidModel)?;
+        let num_pixels = training_indices.len();
+        let bits_per_input = self.binarization_thresholds.shape()[2];
+
+        let mut image_indices = vec![0; num_pixels];
+        for (image_index, training_index) in training_indices.iter().enumerate() {
+            image_indices[*training_index] = image_index as u64;
+        }
+        self.input_permutation.mapv_inplace(|bit| {
+            let (b, pixel) = (bit / num_pixels as u64, bit as usize % num_pixels);
+            b * num_pixels as u64 + image_indices[pixel]
+        });
+        self.input_runs = PermutationRuns::new(self.input_permutation.iter().copied());
+
+        let thresholds = &self.binarization_thresholds;
+        let (_, training_columns) = self.img_shape();
+        self.binarization_thresholds =
+            Array3::from_shape_fn((shape.0, shape.1, bits_per_input), |(i, j, b)| {
+                let training_index = training_indices[i * shape.1 + j];
+                thresholds[(
+                    training_index / training_columns,
+                    training_index % training_columns,
+                    b,
+                )]
+            });
+        Ok(self)
+    }
+
+    /// Checks that the model parameters are consistent with each other and with the
+    /// constraints of the circuit, so that errors are reported before synthesis.
+    ///
+    /// Models are validated when loaded from disk.
+    pub fn validate(&self) -> Result<(), ZeroGError> {
+        let invalid = |message: String| Err(ZeroGError::InvalidModel(message));
+
+        if !(1..=MAX_WINDOW_NUM_BITS).contains(&self.window_num_bits) {
+            return invalid(format!(
+                "Range check window must be in [1, {MAX_WINDOW_NUM_BITS}] bits, got {}",
+                self.window_num_bits
+            ));
+        }
+        if self.num_instance_columns == 0 {
+            return invalid("At least one instance column is required".to_string());
+        }
+        if !self.num_filter_entries.is_power_of_two() {
+            return invalid(format!(
+                "Number of filter entries must be a power of two, got {}",
+                self.num_filter_entries
+            ));
+        }
+        if self.num_filter_inputs == 0 || self.num_filter_inputs > MAX_FILTER_INPUTS {
+            return invalid(format!(
+                "Number of filter inputs must be in [1, {MAX_FILTER_INPUTS}], got {}",
+                self.num_filter_inputs
+            ));
+        }
+
+        // The hash function maps into [0, num_filter_entries^num_filter_hashes) = [0, 2^l).
+        // The hash gadget requires that p has exactly l + 1 bits.
+        let l = self.num_filter_hashes * self.num_filter_entries.trailing_zeros() as usize;
+        if l == 0 || l >= 64 || self.p < (1 << l) || self.p >= (1 << (l + 1)) {
+            return invalid(format!(
+                "p = {} must be in [2^l, 2^(l + 1)), where 2^l = {}^{} is the range of the hash function",
+                self.p, self.num_filter_entries, self.num_filter_hashes
+            ));
+        }
+        if !is_prime(self.p) {
+            return invalid(format!("p = {} is not prime", self.p));
+        }
+
+        let [width, height, bits_per_input] = match self.binarization_thresholds.shape() {
+            &[width, height, bits_per_input] => [width, height, bits_per_input],
+            _ => unreachable!("Array3 has 3 dimensions"),
+        };
+        let num_input_bits = width * height * bits_per_input;
+        if self.input_permutation.len() != num_input_bits {
+            return invalid(format!(
+                "Input permutation has length {}, but thresholds of shape {:?} yield {num_input_bits} input bits",
+                self.input_permutation.len(),
+                self.binarization_thresholds.shape()
+            ));
+        }
+        if let Err(e) = check_permutation(self.input_permutation.iter().copied(), num_input_bits) {
+            return invalid(format!("Input order is not a permutation: {e}"));
+        }
+        if self.tabular && self.binarization_thresholds.shape()[0] != 1 {
+            return invalid(format!(
+                "Tabular models store their thresholds with shape (1, num_features, \
+                 bits_per_input), got {:?}",
+                self.binarization_thresholds.shape()
+            ));
+        }
+        let shape = self.binarization_thresholds.shape();
+        if self.tabular {
+            // The feature gadget compares features of `FEATURE_NUM_BITS` bits, which only works
+            // for thresholds of the same size
+            let max_threshold = (1u64 << FEATURE_NUM_BITS) - 1;
+            if let Some(((_, feature, index), t)) = self
+                .binarization_thresholds
+                .indexed_iter()
+                .find(|(_, t)| u64::from(**t) > max_threshold)
+            {
+                return invalid(format!(
+                    "Feature thresholds must be in [0, {max_threshold}], got {t} for feature \
+                     {feature}, threshold {index} (feature thresholds of shape {:?})",
+                    &shape[1..]
+                ));
+            }
+        } else if let Some(((row, column, index), t)) = self
+            .binarization_thresholds
+            .indexed_iter()
+            .find(|(_, t)| **t > 256)
+        {
+            return invalid(format!(
+                "Binarization thresholds must be in [0, 256], got {t} for pixel ({row}, \
+                 {column}), threshold {index} (thresholds of shape {shape:?})"
+            ));
+        }
+
+        if self.occlusion && self.tabular {
+            return invalid("Tabular models have no pixels to occlude".to_string());
+        }
+        if (self.image_commitment || self.chaining) && self.tabular {
+            return invalid("Tabular models have no image to commit to".to_string());
+        }
+        if self.robustness
+            && (self.tabular
+                || self.occlusion
+                || self.regression
+                || self.label_thresholds.is_some())
+        {
+            return invalid(
+                "Robustness proofs require a single-label image classifier without occlusion"
+                    .to_string(),
+            );
+        }
+
+        if self.regression {
+            if self.num_classes != 1 {
+                return invalid(format!(
+                    "Regression models have a single discriminator bank, got {} classes",
+                    self.num_classes
+                ));
+            }
+            if self.label_thresholds.is_some() {
+                return invalid("Regression models can't have label thresholds".to_string());
+            }
         }
+
+        if let Some(label_thresholds) = &self.label_thresholds {
+            if label_thresholds.len() != self.num_classes {
+                return invalid(format!(
+                    "Expected one label threshold per class ({}), got {}",
+                    self.num_classes,
+                    label_thresholds.len()
+                ));
+            }
+            if let Some(t) = label_thresholds.iter().find(|t| **t >= 1 << SCORE_NUM_BITS) {
+                return invalid(format!(
+                    "Label thresholds must be less than 2^{SCORE_NUM_BITS}, got {t}"
+                ));
+            }
+        }
+
+        if num_input_bits % self.num_filter_inputs != 0 {
+            return invalid(format!(
+                "Number of input bits ({num_input_bits}) is not a multiple of the number of filter inputs ({})",
+                self.num_filter_inputs
+            ));
+        }
+        let expected_shape = [
+            self.num_classes,
+            num_input_bits / self.num_filter_inputs,
+            self.num_filter_entries,
+        ];
+        if self.bloom_filters.shape() != expected_shape || !self.bloom_filters.is_complete() {
+            return invalid(format!(
+                "Bloom filters have shape {:?}, expected {expected_shape:?}",
+                self.bloom_filters.shape()
+            ));
+        }
+
+        Ok(())
     }
 
     /// Implements the thermometer encoding: Each pixels is mapped to a vector
     /// of bits, one per threshold. The bit is set if the pixel value is greater
     /// than or equal to the threshold.
-    fn thermometer_encoding(&self, image: &Array2<u8>) -> Vec<bool> {
+    pub(crate) fn thermometer_encoding(&self, image: &Array2<u8>) -> Vec<bool> {
         let (width, height) = (image.shape()[0], image.shape()[1]);
 
         let mut image_bits = vec![];
@@ -95,24 +548,31 @@ impl Wnn {
         image_bits
     }
 
+    /// Like [`Wnn::thermometer_encoding`], for the feature vector of a tabular model.
+    pub(crate) fn feature_encoding(&self, features: &Array1<u16>) -> Vec<bool> {
+        assert_eq!(features.len(), self.num_features());
+        let mut feature_bits = vec![];
+        for b in 0..self.binarization_thresholds.shape()[2] {
+            for (i, feature) in features.iter().enumerate() {
+                feature_bits.push(*feature >= self.binarization_thresholds[(0, i, b)]);
+            }
+        }
+        feature_bits
+    }
+
     /// Computes the MishMash hash: `x^3 % p % 2^l`
-    fn mish_mash_hash(&self, x: u64) -> BigUint {
+    pub(crate) fn mish_mash_hash(&self, x: u128) -> BigUint {
         let x = BigUint::from(x);
         let modulus = BigUint::from(self.num_filter_entries).pow(self.num_filter_hashes as u32);
         ((&x * &x * &x) % self.p) % modulus
     }
 
-    /// Encodes an image into a vector of filter indices
-    fn encode_image(&self, image: &Array2<u8>) -> Vec<u64> {
-        let image_bits = self.thermometer_encoding(image);
+    /// Encodes thermometer-encoded image bits into a vector of filter indices
+    pub(crate) fn encode_bits(&self, image_bits: &[bool]) -> Vec<u128> {
         assert_eq!(image_bits.len(), self.input_permutation.shape()[0]);
 
         // Permute inputs
-        let permuted_bits = self
-            .input_permutation
-            .iter()
-            .map(|i| image_bits[*i as usize])
-            .collect::<Vec<_>>();
+        let permuted_bits = self.input_runs.apply(image_bits);
 
         // Pack inputs into integers of `num_filter_inputs` bits
         // (LITTLE endian order)
@@ -122,94 +582,380 @@ impl Wnn {
                 chunk
                     .iter()
                     .rev()
-                    .fold(0, |acc, b| (acc << 1) + (*b as u64))
+                    .fold(0, |acc, b| (acc << 1) + (*b as u128))
             })
             .collect()
     }
 
-    /// Computes the bloom filter response for the given index.
-    ///
-    /// The index is hashed, split into multiple array indices.
-    /// The bloom filter response is true if all of the corresponding
-    /// array entries are true.
-    fn bloom_filter_lookup(&self, bloom_array: &[bool], filter_index: u64) -> bool {
+    /// The bloom filter entries of an index: The index is hashed and the hash is split into
+    /// `num_filter_hashes` array indices.
+    pub(crate) fn filter_entries(&self, filter_index: u128) -> Vec<usize> {
         let hash = self.mish_mash_hash(filter_index);
-
-        // Split hash into multiple indices
-        let array_indices: Vec<usize> = (0..self.num_filter_hashes)
+        (0..self.num_filter_hashes)
             .map(|i| {
                 ((&hash / BigUint::from(self.num_filter_entries).pow(i as u32))
                     % self.num_filter_entries)
                     .try_into()
                     .unwrap()
             })
-            .collect();
+            .collect()
+    }
 
-        array_indices.into_iter().all(|i| bloom_array[i])
+    /// Computes the bloom filter response for the given index.
+    ///
+    /// The bloom filter response is true if all of the entries of the index
+    /// (see [`Wnn::filter_entries`]) are true.
+    pub(crate) fn bloom_filter_lookup(
+        &self,
+        class: usize,
+        filter: usize,
+        filter_index: u128,
+    ) -> bool {
+        self.filter_entries(filter_index)
+            .into_iter()
+            .all(|i| self.bloom_filters.get(class, filter, i))
+    }
+
+    /// Encodes an image into the index of each filter.
+    pub(crate) fn filter_indices(&self, image: &Array2<u8>) -> Vec<u128> {
+        self.encode_bits(&self.thermometer_encoding(image))
     }
 
     /// Predicts a given image
     pub fn predict(&self, image: &Array2<u8>) -> Vec<u64> {
-        let filter_indices = self.encode_image(image);
+        self.filter_responses(image)
+            .rows()
+            .into_iter()
+            .map(|responses| responses.iter().filter(|r| **r).count() as u64)
+            .collect()
+    }
+
+    /// Predicts a given feature vector of a tabular model (see [`Wnn::new_tabular`]).
+    pub fn predict_features(&self, features: &Array1<u16>) -> Vec<u64> {
+        self.filter_responses_from_bits(&self.feature_encoding(features))
+            .rows()
+            .into_iter()
+            .map(|responses| responses.iter().filter(|r| **r).count() as u64)
+            .collect()
+    }
+
+    /// For multi-label models (see [`Wnn::with_label_thresholds`]), whether each class applies
+    /// to the image, i.e. its score reaches its threshold. `None` for other models.
+    pub fn predict_labels(&self, image: &Array2<u8>) -> Option<Vec<bool>> {
+        self.labels(&self.predict(image))
+    }
+
+    /// Like [`Wnn::predict_labels`], for the given scores.
+    pub fn labels(&self, scores: &[u64]) -> Option<Vec<bool>> {
+        let label_thresholds = self.label_thresholds.as_ref()?;
+        Some(
+            scores
+                .iter()
+                .zip(label_thresholds)
+                .map(|(score, threshold)| score >= threshold)
+                .collect(),
+        )
+    }
+
+    /// Predicts the image with the given region replaced by zeros, see [`crate::occlusion`].
+    pub fn predict_occluded(&self, image: &Array2<u8>, region: &Region) -> Vec<u64> {
+        self.predict(&region.occlude(image))
+    }
+
+    /// For regression models (see [`Wnn::with_regression`]), the output for the image, i.e. the
+    /// number of positive responses of the discriminator bank. `None` for other models.
+    pub fn regress(&self, image: &Array2<u8>) -> Option<u64> {
+        self.regression.then(|| self.predict(image)[0])
+    }
+
+    /// The response of each bloom filter to the image, indexed by class and filter. The score
+    /// of a class is the number of positive responses.
+    pub fn filter_responses(&self, image: &Array2<u8>) -> Array2<bool> {
+        self.filter_responses_from_bits(&self.thermometer_encoding(image))
+    }
+
+    /// Predicts a given image with unquantized binarization thresholds (as produced by
+    /// training, in `[0, 1]`), i.e. as the trained model would, see
+    /// [`crate::quantization::QuantizationAudit`].
+    ///
+    /// Note that the circuit only supports the quantized thresholds of the model.
+    pub fn predict_with_float_thresholds(
+        &self,
+        image: &Array2<u8>,
+        thresholds: &Array3<f32>,
+    ) -> Vec<u64> {
+        assert_eq!(thresholds.shape(), self.binarization_thresholds.shape());
+        let (width, height) = (image.shape()[0], image.shape()[1]);
+        let mut image_bits = vec![];
+        for b in 0..thresholds.shape()[2] {
+            for i in 0..width {
+                for j in 0..height {
+                    image_bits.push(image[(i, j)] as f32 >= thresholds[(i, j, b)] * 255.0);
+                }
+            }
+        }
+        self.filter_responses_from_bits(&image_bits)
+            .rows()
+            .into_iter()
+            .map(|responses| responses.iter().filter(|r| **r).count() as u64)
+            .collect()
+    }
+
+    pub(crate) fn filter_responses_from_bits(&self, image_bits: &[bool]) -> Array2<bool> {
+        let filter_indices = self.encode_bits(image_bits);
         assert_eq!(filter_indices.len(), self.bloom_filters.shape()[1]);
 
         // Look up bloom filters
-        (0..self.num_classes)
-            .map(|c| {
-                filter_indices
-                    .iter()
-                    .enumerate()
-                    .map(|(index_of_filter, index_into_filter)| {
-                        let bloom_filter_array = self
-                            .bloom_filters
-                            .slice(s![c, index_of_filter, ..])
-                            .to_slice()
-                            .unwrap();
-                        self.bloom_filter_lookup(bloom_filter_array, *index_into_filter) as u64
-                    })
-                    .sum()
+        Array2::from_shape_fn(
+            (self.num_classes, filter_indices.len()),
+            |(class, filter)| self.bloom_filter_lookup(class, filter, filter_indices[filter]),
+        )
+    }
+
+    /// Predicts a given image and explains the prediction by the individual filter responses,
+    /// see [`FilterResponses`].
+    pub fn predict_with_responses(&self, image: &Array2<u8>) -> FilterResponses {
+        FilterResponses::new(self, self.filter_responses(image))
+    }
+
+    /// Returns the pixels `(row, column)` whose bits (in the thermometer encoding) are inputs
+    /// to the given filter.
+    pub fn filter_pixels(&self, filter: usize) -> Vec<(usize, usize)> {
+        let (rows, columns) = self.img_shape();
+        let mut pixels: Vec<_> = self
+            .input_permutation
+            .iter()
+            .skip(filter * self.num_filter_inputs)
+            .take(self.num_filter_inputs)
+            .map(|bit| {
+                let pixel = *bit as usize % (rows * columns);
+                (pixel / columns, pixel % columns)
+            })
+            .collect();
+        pixels.sort_unstable();
+        pixels.dedup();
+        pixels
+    }
+
+    /// Predicts a batch of images in parallel.
+    pub fn predict_batch(&self, images: &[Array2<u8>]) -> Vec<Vec<u64>> {
+        images.par_iter().map(|image| self.predict(image)).collect()
+    }
+
+    /// Predicts a given image, returning the class scores normalized to sum to one.
+    ///
+    /// If no bloom filter responds for any class, all classes are equally likely.
+    pub fn predict_proba(&self, image: &Array2<u8>) -> Vec<f64> {
+        let scores = self.predict(image);
+        let total: u64 = scores.iter().sum();
+        if total == 0 {
+            return vec![1.0 / scores.len() as f64; scores.len()];
+        }
+        scores
+            .into_iter()
+            .map(|score| score as f64 / total as f64)
+            .collect()
+    }
+
+    /// Computes the public inputs of the circuit for the given image, i.e. one vector per
+    /// instance column, distributed as given by the [`InstanceLayout`].
+    ///
+    /// If the circuit exposes a chaining value (see [`Wnn::with_chaining`]), the chain starts
+    /// at [`GENESIS`], e.g. for the [`halo2_proofs::dev::MockProver`]. The image commitment (if
+    /// any) is not salted.
+    pub fn public_inputs(&self, image: &Array2<u8>) -> Vec<Vec<Fp>> {
+        let layout = InstanceLayout::from_params(&self.get_circuit_params());
+        layout.to_columns(&self.public_values(
+            &layout,
+            &self.predict(image),
+            ProofVariant::Plain,
+            self.committed_value(image, None),
+            &GENESIS,
+        ))
+    }
+
+    /// Like [`Wnn::public_inputs`], but for the given scores instead of the predicted ones. The
+    /// image commitment (if any) is 0, so this is only correct for tabular models.
+    pub(crate) fn public_inputs_for_scores(&self, scores: &[u64]) -> Vec<Vec<Fp>> {
+        let layout = InstanceLayout::from_params(&self.get_circuit_params());
+        layout.to_columns(&self.public_values(
+            &layout,
+            scores,
+            ProofVariant::Plain,
+            Fp::from(0),
+            &GENESIS,
+        ))
+    }
+
+    /// The commitment to the image as a public input if the circuit computes one (see
+    /// [`Wnn::with_image_commitment`] and [`Wnn::with_chaining`]), 0 otherwise.
+    fn committed_value(&self, image: &Array2<u8>, salt: Option<&[u8; SALT_SIZE]>) -> Fp {
+        if self.image_commitment || self.chaining {
+            commitment_to_field(&image_commitment(image, salt))
+        } else {
+            Fp::from(0)
+        }
+    }
+
+    /// The public values for the given scores, proof variant, image commitment and previous
+    /// chaining value, in the order of [`InstanceLayout::values`]. Values that the circuit
+    /// doesn't expose are ignored.
+    fn public_values(
+        &self,
+        layout: &InstanceLayout,
+        scores: &[u64],
+        variant: ProofVariant,
+        image_commitment: Fp,
+        previous_chaining_value: &[u8; 32],
+    ) -> Vec<Fp> {
+        let previous_chaining_value = chaining_value_to_field(previous_chaining_value);
+        let labels = self.labels(scores);
+        let occluded: Vec<bool> = match variant {
+            ProofVariant::Occlusion(mask) => mask.iter().copied().collect(),
+            _ => vec![],
+        };
+        // Without a perturbation, the circuit compares the image with itself
+        let (perturbed_scores, bound) = match variant {
+            ProofVariant::Robustness {
+                perturbed_scores,
+                bound,
+            } => (perturbed_scores, bound),
+            _ => (scores, 0),
+        };
+        layout
+            .values
+            .iter()
+            .map(|value| match value {
+                PublicValue::Score { class } => Fp::from(scores[*class]),
+                PublicValue::Label { class } => {
+                    let labels = labels.as_ref().expect("Labels require label thresholds");
+                    Fp::from(labels[*class] as u64)
+                }
+                PublicValue::Output => Fp::from(scores[0]),
+                PublicValue::Occluded { pixel } => {
+                    Fp::from(occluded.get(*pixel).copied().unwrap_or(false) as u64)
+                }
+                PublicValue::PerturbedScore { class } => Fp::from(perturbed_scores[*class]),
+                PublicValue::PerturbationBound => Fp::from(bound as u64),
+                PublicValue::ImageCommitment => image_commitment,
+                PublicValue::PreviousChainingValue => previous_chaining_value,
+                PublicValue::ChainingValue => chain(
+                    &PoseidonSpec::new(),
+                    previous_chaining_value,
+                    image_commitment,
+                ),
             })
             .collect()
     }
 
-    pub fn get_circuit_params(&self) -> WnnCircuitParams {
-        WnnCircuitParams {
-            p: self.p,
-            l: self.num_filter_hashes * (self.num_filter_entries as f32).log2() as usize,
-            n_hashes: self.num_filter_hashes,
-            bits_per_hash: (self.num_filter_entries as f32).log2() as usize,
-            bits_per_filter: self.num_filter_inputs,
-            n_classes: self.bloom_filters.shape()[0],
+    /// Evaluates the model on a labeled dataset, decoding images in parallel.
+    ///
+    /// Only image classifiers that predict a single class can be evaluated, so multi-label,
+    /// regression and tabular models are rejected.
+    pub fn evaluate(&self, dataset: &Dataset) -> Result<EvalReport, ZeroGError> {
+        self.evaluate_examples(dataset.par_iter())
+    }
+
+    /// Evaluates the model on the given examples, e.g. a [`Dataset`] wrapped in a progress bar.
+    /// See [`Wnn::evaluate`].
+    pub fn evaluate_examples(
+        &self,
+        examples: impl IntoIterator<Item = Result<Example, ImageError>>,
+    ) -> Result<EvalReport, ZeroGError> {
+        let mode = if self.label_thresholds.is_some() {
+            Some("multi-label")
+        } else if self.regression {
+            Some("regression")
+        } else if self.tabular {
+            Some("tabular")
+        } else {
+            None
+        };
+        if let Some(mode) = mode {
+            return Err(ZeroGError::InvalidInput(format!(
+                "Only single-label image classifiers can be evaluated on a labeled dataset, \
+                 got a {mode} model"
+            )));
         }
+
+        let mut report = EvalReport::new(self.num_classes);
+        for example in examples {
+            let example = example.map_err(ZeroGError::Dataset)?;
+            report.add(example.label, argmax(&self.predict(&example.image)));
+        }
+        Ok(report)
+    }
+
+    /// Metadata and statistics of the model, e.g. the bloom filter densities and the size of
+    /// the lookup tables.
+    pub fn stats(&self) -> ModelInfo {
+        ModelInfo::new(self)
+    }
+
+    pub fn get_circuit_params(&self) -> WnnCircuitParams {
+        WnnCircuitParams::from_model(self)
     }
 
     /// Returns the Halo2 circuit corresponding to this WNN.
     pub fn get_circuit(&self, image: &Array2<u8>) -> WnnCircuit<Fp> {
-        WnnCircuit::new(
-            image.clone(),
-            self.bloom_filters.clone(),
-            self.binarization_thresholds.clone(),
-            self.input_permutation.clone(),
-            self.get_circuit_params(),
-        )
+        WnnCircuit::from_model(self, image.clone())
+    }
+
+    /// Computes the image-independent data used to synthesize the circuit once, to reuse it for
+    /// many proofs (see [`Wnn::proof_with_cache`] and [`crate::prover::Prover`]).
+    pub fn synthesis_cache(&self) -> Arc<WnnSynthesisCache<Fp>> {
+        let circuit = self.get_circuit(&Array2::zeros(self.img_shape()));
+        Arc::new(circuit.compute_synthesis_cache())
     }
 
     /// Plots the circuit corresponding to this WNN.
+    #[cfg(feature = "plotting")]
     pub fn plot_circuit(&self, filename: &str, k: u32) {
         let image = Array2::zeros(self.img_shape());
         self.get_circuit(&image).plot(filename, k);
     }
 
+    /// Like [`Wnn::plot_circuit`], but e.g. as SVG or only for some rows, see [`PlotOptions`].
+    /// Regions are annotated (see [`WnnCircuit::with_region_annotations`]), so that they can be
+    /// filtered by chip, pixel, filter or class.
+    #[cfg(feature = "plotting")]
+    pub fn plot_circuit_with_options(
+        &self,
+        path: &Path,
+        k: u32,
+        options: &PlotOptions,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let image = Array2::zeros(self.img_shape());
+        self.get_circuit(&image)
+            .with_region_annotations()
+            .plot_with_options(path, k, options)
+    }
+
+    /// Returns the structure of the circuit corresponding to this WNN (chips, shared columns,
+    /// lookup tables and region counts), see [`CircuitGraph`].
+    pub fn circuit_graph(&self) -> Result<CircuitGraph, Error> {
+        let image = Array2::zeros(self.img_shape());
+        CircuitGraph::new(&self.get_circuit(&image))
+    }
+
     /// Check that the circuit is satisfied for the given image.
     pub fn mock_proof(&self, image: &Array2<u8>, k: u32) {
-        let outputs: Vec<Fp> = self.predict(image).into_iter().map(Fp::from).collect();
         let circuit = self.get_circuit(image);
 
-        let prover = MockProver::run(k, &circuit, vec![outputs]).unwrap();
+        let prover = MockProver::run(k, &circuit, self.public_inputs(image)).unwrap();
+        prover.assert_satisfied();
+    }
+
+    /// Like [`Wnn::mock_proof`], for the feature vector of a tabular model.
+    pub fn mock_proof_features(&self, features: &Array1<u16>, k: u32) {
+        let circuit = WnnCircuit::from_features(self, features.clone());
+        let public_inputs = self.public_inputs_for_scores(&self.predict_features(features));
+
+        let prover = MockProver::run(k, &circuit, public_inputs).unwrap();
         prover.assert_satisfied();
     }
 
-    fn img_shape(&self) -> (usize, usize) {
+    pub(crate) fn img_shape(&self) -> (usize, usize) {
         (
             self.binarization_thresholds.shape()[0],
             self.binarization_thresholds.shape()[1],
@@ -219,63 +965,720 @@ impl Wnn {
     /// Generate a proving key and verification key.
     ///
     /// The verification key can be accessed via `pk.get_vk()`.
-    pub fn generate_proving_key(&self, kzg_params: &ParamsKZG<Bn256>) -> ProvingKey<G1Affine> {
+    ///
+    /// The parallel parts of key generation (like proving) run on the current rayon thread
+    /// pool, i.e. they use all cores unless `RAYON_NUM_THREADS` is set or it is called from
+    /// within [`rayon::ThreadPool::install`]. These are halo2's FFTs and multi-scalar
+    /// multiplications and the packing of the bloom filters into table words; assigning the
+    /// tables (see [`crate::gadgets::bloom_filter::ArrayLookupChip::load`]) is sequential.
+    #[instrument(skip_all, fields(k = kzg_params.k()))]
+    pub fn generate_proving_key(
+        &self,
+        kzg_params: &ParamsKZG<Bn256>,
+    ) -> Result<ProvingKey<G1Affine>, ZeroGError> {
         // They keys should not depend on the input, so we're generating a dummy input here
         let circuit = self.get_circuit(&Array2::zeros(self.img_shape()));
+        // Both steps synthesize the circuit, so the lookup tables are only packed once
+        let synthesis_cache = Arc::new(circuit.compute_synthesis_cache());
+        let circuit = circuit.with_synthesis_cache(synthesis_cache);
 
-        let vk = keygen_vk(kzg_params, &circuit).expect("keygen_vk should not fail");
+        let vk = info_span!("keygen_vk").in_scope(|| {
+            DefaultBackend::keygen_vk(kzg_params, &circuit).map_err(|source| ZeroGError::Plonk {
+                action: "Generating the verification key",
+                source,
+            })
+        })?;
 
-        keygen_pk(kzg_params, vk, &circuit).expect("keygen_pk should not fail")
+        info_span!("keygen_pk").in_scope(|| {
+            DefaultBackend::keygen_pk(kzg_params, vk, &circuit).map_err(|source| {
+                ZeroGError::Plonk {
+                    action: "Generating the proving key",
+                    source,
+                }
+            })
+        })
     }
 
-    /// Generate a proof for the given image.
+    /// Generate a proof for the given image. If the circuit commits to the image (see
+    /// [`Wnn::with_image_commitment`]), the commitment is not salted.
+    ///
+    /// Returns an error if the circuit exposes a chaining value, see [`Wnn::chained_proof`].
     pub fn proof(
         &self,
         pk: &ProvingKey<G1Affine>,
         kzg_params: &ParamsKZG<Bn256>,
         image: &Array2<u8>,
-    ) -> (Vec<u8>, Vec<Fp>) {
-        let outputs: Vec<Fp> = self.predict(image).into_iter().map(Fp::from).collect();
+    ) -> Result<(Vec<u8>, Vec<Fp>), ZeroGError> {
+        self.proof_with_chaining_value(pk, kzg_params, image, None, None, None)
+    }
 
-        let circuit = self.get_circuit(image);
-        let mut transcript = TranscriptWriterBuffer::<_, G1Affine, _>::init(Vec::new());
-        create_proof::<
-            KZGCommitmentScheme<Bn256>,
-            ProverGWC<_>,
-            _,
-            _,
-            // Use `EvmTranscript` (based on keccak256) so that proofs are verifiable
-            // with the EVM verifier
-            EvmTranscript<_, _, _, _>,
-            _,
-        >(
+    /// Generate a proof for the given image that exposes the commitment to the image with the
+    /// given salt (see [`crate::image_commitment`]), which requires
+    /// [`Wnn::with_image_commitment`].
+    ///
+    /// Returns an error if the circuit exposes a chaining value.
+    pub fn salted_proof(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        image: &Array2<u8>,
+        salt: &[u8; SALT_SIZE],
+    ) -> Result<(Vec<u8>, Vec<Fp>), ZeroGError> {
+        if !self.image_commitment {
+            return Err(ZeroGError::InvalidModel(
+                "The circuit doesn't commit to the image, see Wnn::with_image_commitment"
+                    .to_string(),
+            ));
+        }
+        self.proof_with_chaining_value(pk, kzg_params, image, Some(salt), None, None)
+    }
+
+    /// Like [`Wnn::proof`], but synthesizes the circuit with the data computed by
+    /// [`Wnn::synthesis_cache`] instead of deriving it from the model again, which saves time
+    /// when proving many images.
+    pub fn proof_with_cache(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        image: &Array2<u8>,
+        synthesis_cache: &Arc<WnnSynthesisCache<Fp>>,
+    ) -> Result<(Vec<u8>, Vec<Fp>), ZeroGError> {
+        self.proof_with_chaining_value(pk, kzg_params, image, None, None, Some(synthesis_cache))
+    }
+
+    /// Generate a proof for the given image that continues the chain after the given chaining
+    /// value ([`GENESIS`] for the first image), which requires [`Wnn::with_chaining`]. The proof
+    /// exposes the previous chaining value and the chaining value of the image, see
+    /// [`crate::proof_chain`].
+    pub fn chained_proof(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        image: &Array2<u8>,
+        previous_chaining_value: &[u8; 32],
+    ) -> Result<(Vec<u8>, Vec<Fp>), ZeroGError> {
+        self.proof_with_chaining_value(
+            pk,
             kzg_params,
+            image,
+            None,
+            Some(previous_chaining_value),
+            None,
+        )
+    }
+
+    fn proof_with_chaining_value(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        image: &Array2<u8>,
+        salt: Option<&[u8; SALT_SIZE]>,
+        previous_chaining_value: Option<&[u8; 32]>,
+        synthesis_cache: Option<&Arc<WnnSynthesisCache<Fp>>>,
+    ) -> Result<(Vec<u8>, Vec<Fp>), ZeroGError> {
+        // Use `EvmTranscript` (based on keccak256) so that proofs are verifiable
+        // with the EVM verifier
+        let mut transcript: EvmTranscript<G1Affine, NativeLoader, _, _> =
+            TranscriptWriterBuffer::init(Vec::new());
+        let outputs = self.proof_with_transcript(
             pk,
-            &[circuit],
-            &[&[outputs.as_ref()]],
-            OsRng,
+            kzg_params,
+            image,
+            salt,
+            previous_chaining_value,
+            synthesis_cache,
             &mut transcript,
+        )?;
+        Ok((transcript.finalize(), outputs))
+    }
+
+    /// Generate a proof for the given image (with the given salt of the image commitment and
+    /// previous chaining value, if any), writing it to the given transcript.
+    ///
+    /// The transcript has to produce the challenges of an [`EvmTranscript`], e.g. a wrapper
+    /// around it that observes the proof as it is written.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn proof_with_transcript<T>(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        image: &Array2<u8>,
+        salt: Option<&[u8; SALT_SIZE]>,
+        previous_chaining_value: Option<&[u8; 32]>,
+        synthesis_cache: Option<&Arc<WnnSynthesisCache<Fp>>>,
+        transcript: &mut T,
+    ) -> Result<Vec<Fp>, ZeroGError>
+    where
+        T: TranscriptWrite<G1Affine, ChallengeEvm<G1Affine>>,
+    {
+        if self.tabular {
+            return Err(ZeroGError::InvalidModel(
+                "Tabular models take feature vectors, use Wnn::features_proof".to_string(),
+            ));
+        }
+        let mut circuit = self.get_circuit(image);
+        if let Some(synthesis_cache) = synthesis_cache {
+            circuit = circuit.with_synthesis_cache(synthesis_cache.clone());
+        }
+        if let Some(salt) = salt {
+            circuit = circuit.with_salt(*salt);
+        }
+        if let (true, Some(previous)) = (self.chaining, previous_chaining_value) {
+            circuit = circuit.with_previous_chaining_value(chaining_value_to_field(previous));
+        }
+        self.prove_circuit(
+            pk,
+            kzg_params,
+            circuit,
+            &self.predict(image),
+            ProofVariant::Plain,
+            self.committed_value(image, salt),
+            previous_chaining_value,
+            transcript,
         )
-        .unwrap();
-        let proof = transcript.finalize();
-        (proof, outputs)
     }
 
-    /// Verify the given proof.
+    /// Generate a proof for the given feature vector of a tabular model (see
+    /// [`Wnn::new_tabular`]).
+    ///
+    /// Returns an error if the circuit exposes a chaining value.
+    pub fn features_proof(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        features: &Array1<u16>,
+    ) -> Result<(Vec<u8>, Vec<Fp>), ZeroGError> {
+        self.features_proof_with_optional_cache(pk, kzg_params, features, None)
+    }
+
+    /// Like [`Wnn::features_proof`], reusing the data computed by [`Wnn::synthesis_cache`].
+    pub fn features_proof_with_cache(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        features: &Array1<u16>,
+        synthesis_cache: &Arc<WnnSynthesisCache<Fp>>,
+    ) -> Result<(Vec<u8>, Vec<Fp>), ZeroGError> {
+        self.features_proof_with_optional_cache(pk, kzg_params, features, Some(synthesis_cache))
+    }
+
+    fn features_proof_with_optional_cache(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        features: &Array1<u16>,
+        synthesis_cache: Option<&Arc<WnnSynthesisCache<Fp>>>,
+    ) -> Result<(Vec<u8>, Vec<Fp>), ZeroGError> {
+        if !self.tabular {
+            return Err(ZeroGError::InvalidModel(
+                "The model takes images, use Wnn::proof".to_string(),
+            ));
+        }
+        let mut transcript: EvmTranscript<G1Affine, NativeLoader, _, _> =
+            TranscriptWriterBuffer::init(Vec::new());
+        let mut circuit = WnnCircuit::from_features(self, features.clone());
+        if let Some(synthesis_cache) = synthesis_cache {
+            circuit = circuit.with_synthesis_cache(synthesis_cache.clone());
+        }
+        let outputs = self.prove_circuit(
+            pk,
+            kzg_params,
+            circuit,
+            &self.predict_features(features),
+            ProofVariant::Plain,
+            Fp::from(0),
+            None,
+            &mut transcript,
+        )?;
+        Ok((transcript.finalize(), outputs))
+    }
+
+    /// Generate a proof that the model predicts the given image, with the given region replaced
+    /// by zeros, as the returned scores (see [`crate::occlusion`]), which requires
+    /// [`Wnn::with_occlusion`]. The occluded pixels are part of the public inputs.
+    ///
+    /// Returns an error if the circuit exposes a chaining value.
+    pub fn occlusion_proof(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        image: &Array2<u8>,
+        region: &Region,
+    ) -> Result<(Vec<u8>, Vec<Fp>), ZeroGError> {
+        if !self.occlusion {
+            return Err(ZeroGError::InvalidModel(
+                "The circuit can't occlude pixels, see Wnn::with_occlusion".to_string(),
+            ));
+        }
+        let mask = region.mask(image.dim());
+        let mut transcript: EvmTranscript<G1Affine, NativeLoader, _, _> =
+            TranscriptWriterBuffer::init(Vec::new());
+        let circuit = self.get_circuit(image).with_occlusion_mask(mask.clone());
+        let outputs = self.prove_circuit(
+            pk,
+            kzg_params,
+            circuit,
+            &self.predict_occluded(image, region),
+            ProofVariant::Occlusion(&mask),
+            self.committed_value(image, None),
+            None,
+            &mut transcript,
+        )?;
+        Ok((transcript.finalize(), outputs))
+    }
+
+    /// Generate a proof that the model predicts the given image and the perturbed image, whose
+    /// pixels differ by at most `bound`, as the returned scores (see [`crate::robustness`]),
+    /// which requires [`Wnn::with_robustness`]. The bound is part of the public inputs.
+    ///
+    /// Returns an error if the images are further apart or the circuit exposes a chaining
+    /// value.
+    pub fn robustness_proof(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        image: &Array2<u8>,
+        perturbed_image: &Array2<u8>,
+        bound: u8,
+    ) -> Result<(Vec<u8>, Vec<Fp>), ZeroGError> {
+        if !self.robustness {
+            return Err(ZeroGError::InvalidModel(
+                "The circuit can't predict perturbed images, see Wnn::with_robustness".to_string(),
+            ));
+        }
+        if perturbed_image.dim() != image.dim() {
+            return Err(ZeroGError::ImageShape {
+                expected: image.dim(),
+                actual: perturbed_image.dim(),
+            });
+        }
+        let distance = linf_distance(image, perturbed_image);
+        if distance > bound {
+            return Err(ZeroGError::Perturbation { distance, bound });
+        }
+        let mut transcript: EvmTranscript<G1Affine, NativeLoader, _, _> =
+            TranscriptWriterBuffer::init(Vec::new());
+        let circuit = self
+            .get_circuit(image)
+            .with_perturbation(perturbed_image.clone(), bound);
+        let outputs = self.prove_circuit(
+            pk,
+            kzg_params,
+            circuit,
+            &self.predict(image),
+            ProofVariant::Robustness {
+                perturbed_scores: &self.predict(perturbed_image),
+                bound,
+            },
+            self.committed_value(image, None),
+            None,
+            &mut transcript,
+        )?;
+        Ok((transcript.finalize(), outputs))
+    }
+
+    /// Proves that the circuit leads to the given scores (continuing the chain after the given
+    /// chaining value, if any), writing the proof to the transcript.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(name = "prove", skip_all, fields(k = kzg_params.k()))]
+    fn prove_circuit<T>(
+        &self,
+        pk: &ProvingKey<G1Affine>,
+        kzg_params: &ParamsKZG<Bn256>,
+        circuit: WnnCircuit<Fp>,
+        scores: &[u64],
+        variant: ProofVariant,
+        image_commitment: Fp,
+        previous_chaining_value: Option<&[u8; 32]>,
+        transcript: &mut T,
+    ) -> Result<Vec<Fp>, ZeroGError>
+    where
+        T: TranscriptWrite<G1Affine, ChallengeEvm<G1Affine>>,
+    {
+        let previous_chaining_value = match (self.chaining, previous_chaining_value) {
+            (true, Some(previous)) => previous,
+            (false, None) => &GENESIS,
+            (true, None) => {
+                return Err(ZeroGError::InvalidModel(
+                    "The circuit exposes a chaining value, use Wnn::chained_proof".to_string(),
+                ))
+            }
+            (false, Some(_)) => {
+                return Err(ZeroGError::InvalidModel(
+                    "The circuit doesn't expose a chaining value, see Wnn::with_chaining"
+                        .to_string(),
+                ))
+            }
+        };
+        let layout = InstanceLayout::from_params(&self.get_circuit_params());
+        let outputs = self.public_values(
+            &layout,
+            scores,
+            variant,
+            image_commitment,
+            previous_chaining_value,
+        );
+        let instances = layout.to_columns(&outputs);
+
+        DefaultBackend::prove(kzg_params, pk, circuit, &instances, transcript).map_err(
+            |source| ZeroGError::Plonk {
+                action: "Generating the proof",
+                source,
+            },
+        )?;
+        Ok(outputs)
+    }
+
+    /// Verify the given proof, panicking if it is invalid.
     pub fn verify_proof(
         proof: &[u8],
         kzg_params: &ParamsKZG<Bn256>,
         vk: &VerifyingKey<G1Affine>,
         outputs: &Vec<Fp>,
     ) {
-        let mut transcript = TranscriptReadBuffer::<_, G1Affine, _>::init(proof);
-        verify_proof::<_, VerifierGWC<_>, _, EvmTranscript<_, _, _, _>, _>(
-            kzg_params.verifier_params(),
-            vk,
-            SingleStrategy::new(kzg_params),
-            &[&[outputs.as_ref()]],
-            &mut transcript,
+        Self::try_verify_proof(proof, kzg_params, vk, outputs).unwrap()
+    }
+
+    /// Verify the given proof, returning an error if it is invalid.
+    pub fn try_verify_proof(
+        proof: &[u8],
+        kzg_params: &ParamsKZG<Bn256>,
+        vk: &VerifyingKey<G1Affine>,
+        outputs: &[Fp],
+    ) -> Result<(), Error> {
+        verify_raw_proof(proof, kzg_params, vk, outputs)
+    }
+
+    /// Computes a commitment (keccak256 hash) to all model parameters.
+    ///
+    /// Two models have the same commitment if and only if they lead to the same circuit
+    /// and predictions.
+    pub fn commitment(&self) -> [u8; 32] {
+        let mut bytes = b"zero_g.model.v1".to_vec();
+
+        for x in [
+            self.num_classes,
+            self.num_filter_inputs,
+            self.num_filter_entries,
+            self.num_filter_hashes,
+        ] {
+            bytes.extend((x as u64).to_le_bytes());
+        }
+        bytes.extend(self.p.to_le_bytes());
+
+        for dim in self
+            .bloom_filters
+            .shape()
+            .iter()
+            .chain(self.binarization_thresholds.shape())
+        {
+            bytes.extend((*dim as u64).to_le_bytes());
+        }
+        bytes.extend(pack_bits_le(self.bloom_filters.iter()));
+        for i in self.input_permutation.iter() {
+            bytes.extend(i.to_le_bytes());
+        }
+        for t in self.binarization_thresholds.iter() {
+            bytes.extend(t.to_le_bytes());
+        }
+        // Everything below is appended only if it differs from the default, so that the
+        // commitments of models without these options don't change
+        if let Some(label_thresholds) = &self.label_thresholds {
+            bytes.extend(b"label_thresholds");
+            for t in label_thresholds.iter() {
+                bytes.extend(t.to_le_bytes());
+            }
+        }
+        if self.regression {
+            bytes.extend(b"regression");
+        }
+        if self.tabular {
+            bytes.extend(b"tabular");
+        }
+        // The circuit parameters that don't affect predictions
+        for (tag, value, default) in [
+            (&b"min_blinding_factors"[..], self.min_blinding_factors, 0),
+            (b"num_instance_columns", self.num_instance_columns, 1),
+            (
+                b"window_num_bits",
+                self.window_num_bits,
+                DEFAULT_WINDOW_NUM_BITS,
+            ),
+        ] {
+            if value != default {
+                bytes.extend(tag);
+                bytes.extend((value as u64).to_le_bytes());
+            }
+        }
+        for (tag, enabled) in [
+            (&b"class_lookup"[..], self.class_lookup),
+            (b"chaining", self.chaining),
+            (b"occlusion", self.occlusion),
+            (b"robustness", self.robustness),
+            (b"image_commitment", self.image_commitment),
+        ] {
+            if enabled {
+                bytes.extend(tag);
+            }
+        }
+
+        keccak256(bytes)
+    }
+}
+
+/// What a proof shows besides the prediction of the image, which determines the public values
+/// that follow the scores.
+#[derive(Clone, Copy)]
+enum ProofVariant<'a> {
+    Plain,
+    /// The image is predicted with the pixels of the mask replaced by zeros, see
+    /// [`Wnn::occlusion_proof`].
+    Occlusion(&'a Array2<bool>),
+    /// A perturbed image is predicted as well, see [`Wnn::robustness_proof`].
+    Robustness {
+        perturbed_scores: &'a [u64],
+        bound: u8,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use halo2_proofs::halo2curves::bn256::Fr as Fp;
+    use ndarray::{array, Array1, Array2, Array3};
+
+    use super::Wnn;
+    use crate::error::ZeroGError;
+    use crate::pixel_order::PixelOrder;
+
+    fn wnn(p: u64, input_order: Array1<u64>) -> Wnn {
+        let thresholds = Array3::from_shape_fn((4, 3, 2), |(i, j, b)| (i * 50 + j * 7 + b) as u16);
+        Wnn::new(
+            2,
+            1024,
+            2,
+            12,
+            p,
+            Array3::from_elem((2, 2, 1024), false),
+            input_order,
+            thresholds,
         )
-        .unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let input_order: Array1<u64> = (0..24u64).rev().collect();
+        assert!(wnn(2097143, input_order.clone()).validate().is_ok());
+
+        // Not prime
+        assert!(wnn(2097145, input_order.clone()).validate().is_err());
+        // Prime, but too large for the hash gadget
+        assert!(wnn(4194319, input_order.clone()).validate().is_err());
+
+        let mut duplicate = input_order;
+        duplicate[0] = duplicate[1];
+        assert!(wnn(2097143, duplicate).validate().is_err());
+    }
+
+    #[test]
+    fn test_evaluate_mode() {
+        let model = || wnn(2097143, (0..24u64).rev().collect());
+        assert!(model().evaluate_examples(iter::empty()).is_ok());
+        for wnn in [
+            model().with_label_thresholds(array![1, 1]),
+            model().with_regression(true),
+        ] {
+            assert!(matches!(
+                wnn.evaluate_examples(iter::empty()),
+                Err(ZeroGError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_validate_thresholds() {
+        let mut image_wnn = wnn(2097143, (0..24u64).rev().collect());
+        image_wnn.binarization_thresholds[(1, 2, 0)] = 257;
+        let error = image_wnn.validate().unwrap_err().to_string();
+        assert!(error.contains("[0, 256], got 257 for pixel (1, 2), threshold 0"));
+
+        // Features have 16 bits, so the pixel bound doesn't apply
+        let tabular_wnn = |feature_thresholds| {
+            Wnn::new_tabular(
+                2,
+                1024,
+                2,
+                6,
+                2097143,
+                Array3::from_elem((2, 1, 1024), false),
+                (0..6u64).rev().collect(),
+                feature_thresholds,
+            )
+        };
+        let tabular = tabular_wnn(array![[0, 1000, 2000], [10000, 40000, 65535]]);
+        assert!(tabular.validate().is_ok());
+
+        let mut misshaped = tabular;
+        misshaped.binarization_thresholds = Array3::zeros((2, 1, 3));
+        let error = misshaped.validate().unwrap_err().to_string();
+        assert!(error.contains("shape (1, num_features, bits_per_input), got [2, 1, 3]"));
+    }
+
+    #[test]
+    fn test_commitment_covers_circuit_params() {
+        let model = || wnn(2097143, (0..24u64).rev().collect());
+        let commitments = [
+            model(),
+            model().with_min_blinding_factors(5),
+            model().with_num_instance_columns(2),
+            model().with_class_lookup(true),
+            model().with_window_num_bits(10),
+            model().with_chaining(true),
+            model().with_occlusion(true),
+            model().with_robustness(true),
+            model().with_image_commitment(true),
+        ]
+        .map(|wnn| wnn.commitment());
+        for (i, commitment) in commitments.iter().enumerate() {
+            assert!(!commitments[..i].contains(commitment));
+        }
+        // Default values don't change the commitment
+        assert_eq!(model().with_window_num_bits(8).commitment(), commitments[0]);
+    }
+
+    #[test]
+    fn test_regression() {
+        let input_order: Array1<u64> = (0..24u64).rev().collect();
+        let classifier = wnn(2097143, input_order.clone()).with_regression(true);
+        assert!(classifier.validate().is_err());
+
+        let bank = || {
+            let mut bloom_filters = Array3::from_elem((1, 2, 1024), false);
+            bloom_filters[[0, 0, 966]] = true;
+            let thresholds =
+                Array3::from_shape_fn((4, 3, 2), |(i, j, b)| (i * 50 + j * 7 + b) as u16);
+            let input_order = input_order.clone();
+            Wnn::new(
+                1,
+                1024,
+                2,
+                12,
+                2097143,
+                bloom_filters,
+                input_order,
+                thresholds,
+            )
+        };
+        let regressor = bank().with_regression(true);
+        assert!(regressor.validate().is_ok());
+        let bank = bank();
+        assert_ne!(regressor.commitment(), bank.commitment());
+
+        let image = Array2::from_shape_fn((4, 3), |(i, j)| (i * 60 + j * 20) as u8);
+        let output = regressor.regress(&image).unwrap();
+        assert_eq!(output, bank.predict(&image)[0]);
+        assert_eq!(bank.regress(&image), None);
+        assert_eq!(
+            regressor.public_inputs(&image),
+            vec![vec![Fp::from(output)]]
+        );
+    }
+
+    #[test]
+    fn test_robustness() {
+        let input_order: Array1<u64> = (0..24u64).rev().collect();
+        let occluding = wnn(2097143, input_order.clone())
+            .with_robustness(true)
+            .with_occlusion(true);
+        assert!(occluding.validate().is_err());
+        let wnn = wnn(2097143, input_order).with_robustness(true);
+        assert!(wnn.validate().is_ok());
+
+        // Without a perturbation, the circuit compares the image with itself
+        let image = Array2::from_shape_fn((4, 3), |(i, j)| (i * 60 + j * 20) as u8);
+        let scores = wnn.predict(&image);
+        let expected: Vec<_> = scores
+            .iter()
+            .chain(&scores)
+            .chain(&[0])
+            .map(|x| Fp::from(*x))
+            .collect();
+        assert_eq!(wnn.public_inputs(&image), vec![expected]);
+    }
+
+    #[test]
+    fn test_tabular() {
+        let feature_thresholds = array![[0, 1000, 2000], [10000, 40000, 50000]];
+        let wnn = Wnn::new_tabular(
+            2,
+            1024,
+            2,
+            6,
+            2097143,
+            Array3::from_elem((2, 1, 1024), false),
+            (0..6u64).rev().collect(),
+            feature_thresholds,
+        );
+        assert!(wnn.validate().is_ok());
+        assert!(wnn.is_tabular());
+        assert_eq!(wnn.num_features(), 2);
+        assert_eq!(
+            wnn.feature_encoding(&array![1000, 65535]),
+            vec![true, true, true, true, false, true]
+        );
+        assert_eq!(wnn.predict_features(&array![1000, 65535]), vec![0, 0]);
+        assert!(wnn.with_pixel_order(&PixelOrder::ColumnMajor).is_err());
+    }
+
+    #[test]
+    fn test_with_pixel_order() {
+        let wnn = wnn(2097143, (0..24u64).rev().collect());
+        let image = Array2::from_shape_fn((4, 3), |(i, j)| (i * 60 + j * 20) as u8);
+        let filter_indices = wnn.encode_bits(&wnn.thermometer_encoding(&image));
+
+        let transposed = wnn.with_pixel_order(&PixelOrder::ColumnMajor).unwrap();
+        assert!(transposed.validate().is_ok());
+        assert_eq!(transposed.img_shape(), (3, 4));
+        let transposed_image = image.t().to_owned();
+        assert_eq!(
+            transposed.encode_bits(&transposed.thermometer_encoding(&transposed_image)),
+            filter_indices
+        );
+
+        let reversed = transposed
+            .with_pixel_order(&PixelOrder::Custom((0..12).rev().collect()))
+            .unwrap();
+        let reversed_image =
+            Array2::from_shape_vec((3, 4), transposed_image.iter().rev().copied().collect())
+                .unwrap();
+        assert_eq!(
+            reversed.encode_bits(&reversed.thermometer_encoding(&reversed_image)),
+            filter_indices
+        );
+    }
+
+    #[cfg(feature = "hdf5")]
+    #[test]
+    fn test_predict_batch_and_proba() {
+        use std::path::Path;
+
+        use crate::checked_in_test_data::{MNIST_TINY, TEST_IMG_PATH};
+        use crate::{load_grayscale_image, load_wnn};
+
+        let wnn = load_wnn(Path::new(MNIST_TINY.1)).unwrap();
+        let image = load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap();
+        let scores = wnn.predict(&image);
+
+        assert_eq!(
+            wnn.predict_batch(&[image.clone(), image.clone()]),
+            vec![scores.clone(), scores.clone()]
+        );
+
+        let proba = wnn.predict_proba(&image);
+        assert!((proba.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        for (p, score) in proba.iter().zip(&scores) {
+            assert_eq!(*p == 0.0, *score == 0);
+        }
     }
 }
@@ -13,6 +13,12 @@ $ZERO_G predict \
     -m models/model_28input_256entry_1hash_1bpi.hdf5 \
     -i benches/example_image_7.png
 
+echo ""
+echo "==== Running predict --json"
+$ZERO_G --json predict \
+    -m models/model_28input_256entry_1hash_1bpi.hdf5 \
+    -i benches/example_image_7.png
+
 if [ -d "data/MNIST/png" ]; then
     echo ""
     echo "==== Running compute-accuracy"
@@ -34,6 +40,13 @@ $ZERO_G generate-srs \
     -k 14 \
     --srs-path test_data/srs_14
 
+echo ""
+echo "==== Running downsize-srs"
+$ZERO_G downsize-srs \
+    --srs-path test_data/srs_14 \
+    -k 12 \
+    --output-path test_data/srs_12
+
 echo ""
 echo "==== Running generate-keys"
 $ZERO_G generate-keys \
@@ -41,7 +54,15 @@ $ZERO_G generate-keys \
     --srs-path test_data/srs_14 \
     --vk-path test_data/vk \
     --pk-path test_data/pk \
-    --circuit-params-path test_data/circuit_params.json
+    --circuit-params-path test_data/circuit_params.json \
+    --bundle-path test_data/verifier_bundle.zgvb
+
+echo ""
+echo "==== Running setup"
+$ZERO_G setup \
+    -m models/model_28input_256entry_1hash_1bpi.hdf5 \
+    --srs-path test_data/srs_14 \
+    --output-dir test_data/setup
 
 echo ""
 echo "==== Running dry-run-evm-verifier"
@@ -65,7 +86,7 @@ $ZERO_G proof \
     -i benches/example_image_7.png \
     --srs-path test_data/srs_14 \
     --pk-path test_data/pk \
-    --proof-path test_data/proof.json
+    --proof-path test_data/proof.zgp
 
 echo ""
 echo "==== Running verify"
@@ -73,11 +94,26 @@ $ZERO_G verify \
     --srs-path test_data/srs_14 \
     --vk-path test_data/vk \
     --circuit-params-path test_data/circuit_params.json \
-    --proof-path test_data/proof.json
+    --proof-path test_data/proof.zgp
+
+echo ""
+echo "==== Running verify (bundle)"
+$ZERO_G verify \
+    --bundle-path test_data/verifier_bundle.zgvb \
+    --proof-path test_data/proof.zgp
+
+echo ""
+echo "==== Running export-evm"
+$ZERO_G export-evm \
+    --srs-path test_data/srs_14 \
+    --vk-path test_data/vk \
+    --circuit-params-path test_data/circuit_params.json \
+    --proof-path test_data/proof.zgp \
+    --output-dir test_data/evm
 
 echo ""
 echo "==== Running submit-proof"
 # Using Anvil, the contract address is always the same
 $ZERO_G submit-proof \
-    --proof-path test_data/proof.json \
+    --proof-path test_data/proof.zgp \
     --contract-address 0x5fbdb2315678afecb367f032d93f642f64180aa3
\ No newline at end of file
@@ -1,6 +1,13 @@
-use std::path::Path;
+use std::{fs, path::Path};
 
-use zero_g::{checked_in_test_data::*, load_grayscale_image, load_wnn};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr},
+    poly::{commitment::ParamsProver, kzg::commitment::ParamsKZG},
+};
+use zero_g::{
+    checked_in_test_data::*, load_grayscale_image, load_image_from_bytes, load_wnn,
+    load_wnn_from_bytes, proof_file::ProofFile, verifier_bundle::VerifierBundle,
+};
 
 #[test]
 fn mock_proof_mnist_tiny() {
@@ -69,3 +76,39 @@ fn snapshot_mnist_large_predictions() {
     let predictions = wnn.predict(&img);
     assert_eq!(predictions, vec![16, 10, 22, 22, 29, 25, 9, 91, 21, 51]);
 }
+
+#[test]
+fn load_from_bytes_matches_load_from_path() {
+    let (_, model_path) = MNIST_TINY;
+    let img = load_image_from_bytes(&fs::read(TEST_IMG_PATH).unwrap()).unwrap();
+    assert_eq!(img, load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap());
+
+    let wnn = load_wnn_from_bytes(&fs::read(model_path).unwrap()).unwrap();
+    assert_eq!(
+        wnn.predict(&img),
+        load_wnn(Path::new(model_path)).unwrap().predict(&img)
+    );
+}
+
+#[test]
+fn verify_batch_finds_invalid_proof() {
+    let img = load_grayscale_image(Path::new(TEST_IMG_PATH)).unwrap();
+    let (k, model_path) = MNIST_TINY;
+    let wnn = load_wnn(Path::new(model_path)).unwrap();
+    let kzg_params = ParamsKZG::<Bn256>::new(k);
+    let pk = wnn.generate_proving_key(&kzg_params).unwrap();
+
+    let (proof, outputs) = wnn.proof(&pk, &kzg_params, &img).unwrap();
+    let bundle = VerifierBundle::new(&wnn, pk.get_vk().clone(), kzg_params);
+    let valid = ProofFile::new(proof, outputs)
+        .with_circuit_params(wnn.get_circuit_params())
+        .with_vk_fingerprint(bundle.vk_fingerprint())
+        .with_model_commitment(wnn.commitment());
+    let mut tampered = valid.clone();
+    tampered.public_inputs[0] += Fr::one();
+
+    let results = bundle.verify_batch(&[valid.clone(), tampered, valid]);
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+    assert!(results[2].is_ok());
+}
@@ -0,0 +1,56 @@
+//! Proves and verifies inference of a tiny model in WASM, without threads.
+//! Run with `wasm-pack test --node --release -- --no-default-features --features wasm --test wasm`.
+
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use std::io::Cursor;
+
+use halo2_proofs::{
+    halo2curves::bn256::Bn256,
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+};
+use image::{DynamicImage, GrayImage, ImageOutputFormat};
+use ndarray::{Array1, Array3};
+use rand_core::OsRng;
+use wasm_bindgen_test::wasm_bindgen_test;
+use zero_g::{
+    cost::minimal_k,
+    model_file::write_model_to,
+    wasm::{verify_with_bundle, WasmProver},
+    wnn::Wnn,
+};
+
+#[wasm_bindgen_test]
+fn test_prove_and_verify() {
+    // A 4x3 image with 2 bits per pixel
+    let thresholds = Array3::from_shape_fn((4, 3, 2), |(i, j, b)| (i * 50 + j * 7 + b) as u16);
+    let wnn = Wnn::new(
+        2,
+        1024,
+        2,
+        12,
+        2097143,
+        Array3::from_elem((2, 2, 1024), true),
+        (0..24u64).rev().collect::<Array1<u64>>(),
+        thresholds,
+    );
+    let mut model = vec![];
+    write_model_to(&wnn, &mut model).unwrap();
+
+    let mut params = vec![];
+    ParamsKZG::<Bn256>::setup(minimal_k(&wnn).unwrap(), OsRng)
+        .write(&mut params)
+        .unwrap();
+
+    let mut image = vec![];
+    DynamicImage::ImageLuma8(GrayImage::from_fn(3, 4, |x, y| {
+        [(x * 60 + y * 20) as u8].into()
+    }))
+    .write_to(&mut Cursor::new(&mut image), ImageOutputFormat::Png)
+    .unwrap();
+
+    let prover = WasmProver::new(&model, &params, None).unwrap();
+    let proof_file = prover.prove(&image).unwrap();
+    let bundle = prover.verifier_bundle().unwrap();
+    assert!(verify_with_bundle(&bundle, &proof_file).unwrap());
+}
@@ -0,0 +1,99 @@
+//! Verifies zero_g proofs with a minimal set of dependencies (halo2, snark-verifier and serde),
+//! e.g. in embedded attestation devices or zkVM guests.
+//!
+//! Reading a halo2 verification key requires the circuit (and with it, all of zero_g). Instead,
+//! a [`VerifierKey`] contains the circuit compiled to a [`PlonkProtocol`] by snark-verifier,
+//! which is also how the EVM verifier is generated. It is created by zero_g (see
+//! `VerifierBundle::verifier_key`) and can be (de)serialized with any serde format.
+//!
+//! Only the proof itself is checked: Unlike zero_g's `VerifierBundle`, the circuit params,
+//! verification key fingerprint and model commitment recorded in a proof file are not.
+
+use std::fmt;
+
+use halo2_proofs::halo2curves::bn256::{Bn256, Fr, G1Affine, G2Affine};
+use serde::{Deserialize, Serialize};
+use snark_verifier::{
+    loader::native::NativeLoader,
+    pcs::kzg::{Gwc19, KzgAs, KzgDecidingKey},
+    system::halo2::transcript::evm::EvmTranscript,
+    verifier::{
+        plonk::{PlonkProtocol, PlonkVerifier},
+        SnarkVerifier,
+    },
+};
+
+type Verifier = PlonkVerifier<KzgAs<Bn256, Gwc19>>;
+
+/// Everything needed to verify the proofs of one model.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VerifierKey {
+    /// The circuit, compiled from the verification key.
+    pub protocol: PlonkProtocol<G1Affine>,
+    /// The number of public inputs in each instance column.
+    pub num_instance: Vec<usize>,
+    /// The generator of G1 of the SRS.
+    pub g1: G1Affine,
+    /// The generator of G2 of the SRS.
+    pub g2: G2Affine,
+    /// The generator of G2 of the SRS, multiplied by the secret.
+    pub s_g2: G2Affine,
+}
+
+/// Reasons why a proof is rejected, see [`VerifierKey::verify`].
+#[derive(Debug)]
+pub enum VerificationError {
+    /// The number of public inputs does not match the verifier key.
+    WrongNumberOfPublicInputs { expected: usize, actual: usize },
+    /// The proof itself is invalid.
+    InvalidProof(snark_verifier::Error),
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongNumberOfPublicInputs { expected, actual } => {
+                write!(f, "Expected {expected} public inputs, got {actual}")
+            }
+            Self::InvalidProof(e) => write!(f, "Invalid proof: {e:?}"),
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+impl VerifierKey {
+    /// The total number of public inputs.
+    pub fn num_public_inputs(&self) -> usize {
+        self.num_instance.iter().sum()
+    }
+
+    /// Verifies a proof (the `proof` field of a zero_g proof file) with its public inputs, in
+    /// the order of zero_g's `InstanceLayout`.
+    pub fn verify(&self, proof: &[u8], public_inputs: &[Fr]) -> Result<(), VerificationError> {
+        let expected = self.num_public_inputs();
+        if public_inputs.len() != expected {
+            return Err(VerificationError::WrongNumberOfPublicInputs {
+                expected,
+                actual: public_inputs.len(),
+            });
+        }
+        let mut rest = public_inputs;
+        let instances: Vec<Vec<Fr>> = self
+            .num_instance
+            .iter()
+            .map(|&length| {
+                let (column, tail) = rest.split_at(length);
+                rest = tail;
+                column.to_vec()
+            })
+            .collect();
+
+        let dk: KzgDecidingKey<Bn256> = (self.g1, self.g2, self.s_g2).into();
+        let mut transcript = EvmTranscript::<G1Affine, NativeLoader, _, _>::new(proof);
+        let proof = Verifier::read_proof(&dk, &self.protocol, &instances, &mut transcript)
+            .map_err(VerificationError::InvalidProof)?;
+        Verifier::verify(&dk, &self.protocol, &instances, &proof)
+            .map_err(VerificationError::InvalidProof)
+    }
+}